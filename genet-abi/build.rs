@@ -0,0 +1,40 @@
+#[cfg(feature = "header")]
+extern crate cbindgen;
+
+fn main() {
+    #[cfg(feature = "header")]
+    header::generate();
+}
+
+/// Generates the C header of the plugin ABI.
+///
+/// The header is written to `$OUT_DIR/genet_abi.h`, and copied to
+/// `$GENET_ABI_HEADER_DIR` if the variable is set.
+#[cfg(feature = "header")]
+mod header {
+    use cbindgen::{Builder, Config};
+    use std::{env, fs, path::Path};
+
+    const HEADER: &str = "genet_abi.h";
+
+    pub fn generate() {
+        let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+        let out_dir = env::var("OUT_DIR").unwrap();
+        let config = Config::from_file(&format!("{}/cbindgen.toml", crate_dir))
+            .expect("failed to read cbindgen.toml");
+        let out = Path::new(&out_dir).join(HEADER);
+        Builder::new()
+            .with_crate(&crate_dir)
+            .with_config(config)
+            .generate()
+            .expect("failed to generate the C header")
+            .write_to_file(&out);
+        if let Ok(dir) = env::var("GENET_ABI_HEADER_DIR") {
+            fs::create_dir_all(&dir).unwrap();
+            fs::copy(&out, Path::new(&dir).join(HEADER)).unwrap();
+        }
+        println!("cargo:rerun-if-changed=src");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+        println!("cargo:rerun-if-env-changed=GENET_ABI_HEADER_DIR");
+    }
+}
@@ -1,14 +1,21 @@
 use cast::{Cast, Typed};
 use env;
-use error::Error;
+use error::{panic_description, Error};
 use fixed::Fixed;
+use fnv::FnvHashMap;
 use layer::Layer;
 use metadata::Metadata;
 use result::Result;
 use slice::ByteSlice;
-use std::{fmt, io, mem, ops::Range, slice};
+use std::{
+    fmt, io, mem,
+    net::Ipv6Addr,
+    ops::Range,
+    panic::{self, AssertUnwindSafe},
+    ptr, slice, str,
+};
 use token::Token;
-use variant::Variant;
+use variant::{Value, Variant};
 use vec::SafeVec;
 
 /// A builder object for Attr.
@@ -87,6 +94,17 @@ impl Attr {
         self.class.is_value()
     }
 
+    /// Returns true if the attribute may occur multiple times in a layer.
+    pub fn is_repeated(&self) -> bool {
+        self.class.is_repeated()
+    }
+
+    /// Returns the symbolic name of the attribute value, if any.
+    pub fn label(&self, layer: &Layer) -> Option<&str> {
+        let value: u64 = self.try_get(layer).ok()?.try_into().ok()?;
+        self.class.label(value)
+    }
+
     /// Returns the byte range of self.
     pub fn range(&self) -> Range<usize> {
         self.class.range(self)
@@ -126,6 +144,23 @@ enum ValueType {
     String = 5,
     Buffer = 6,
     ByteSlice = 7,
+    Ipv6Addr = 8,
+    MacAddr = 9,
+    Timestamp = 10,
+}
+
+#[derive(Clone)]
+struct Lazy<F>(F);
+
+impl<F> Typed for Lazy<F>
+where
+    F: Fn(&Attr, &ByteSlice) -> io::Result<Variant>,
+{
+    type Output = Variant;
+
+    fn cast(&self, attr: &Attr, data: &ByteSlice) -> io::Result<Variant> {
+        (self.0)(attr, data)
+    }
 }
 
 #[derive(Clone)]
@@ -145,6 +180,9 @@ pub struct AttrClassBuilder {
     typ: Token,
     meta: Metadata,
     cast: Option<Box<Cast>>,
+    labels: FnvHashMap<u64, String>,
+    repeated: bool,
+    lazy: bool,
 }
 
 impl AttrClassBuilder {
@@ -166,12 +204,53 @@ impl AttrClassBuilder {
         self
     }
 
+    /// Marks AttrClass as repeated.
+    ///
+    /// A repeated attribute may occur multiple times in a layer,
+    /// e.g. DNS answers or TCP options.
+    pub fn repeated(mut self) -> AttrClassBuilder {
+        self.repeated = true;
+        self
+    }
+
+    /// Sets symbolic names for the values of AttrClass.
+    ///
+    /// ```
+    /// # use genet_abi::attr::AttrClass;
+    /// let class = AttrClass::builder("eth.type")
+    ///     .labels(vec![(0x0800, "IPv4"), (0x0806, "ARP"), (0x86dd, "IPv6")])
+    ///     .build();
+    /// ```
+    pub fn labels<I, S>(mut self, labels: I) -> AttrClassBuilder
+    where
+        I: IntoIterator<Item = (u64, S)>,
+        S: Into<String>,
+    {
+        self.labels
+            .extend(labels.into_iter().map(|(k, v)| (k, v.into())));
+        self
+    }
+
     /// Sets a cast of AttrClass.
     pub fn cast<T: Cast>(mut self, cast: T) -> AttrClassBuilder {
         self.cast = Some(cast.into_box());
         self
     }
 
+    /// Sets a closure to compute the value of AttrClass on first access.
+    ///
+    /// The result is cached in the layer, so the closure runs at most once
+    /// for each pair of a layer and an attribute, even if the attribute is
+    /// a header shared by the layers of the same class.
+    pub fn lazy<F>(mut self, func: F) -> AttrClassBuilder
+    where
+        F: 'static + Fn(&Attr, &ByteSlice) -> io::Result<Variant> + Send + Sync + Clone,
+    {
+        self.cast = Some(Box::new(Lazy(func)));
+        self.lazy = true;
+        self
+    }
+
     /// Sets a constant value of AttrClass.
     pub fn value<T: 'static + Into<Variant> + Send + Sync + Clone>(
         mut self,
@@ -187,12 +266,18 @@ impl AttrClassBuilder {
             get_id: abi_id,
             get_typ: abi_typ,
             is_value: abi_is_value,
+            is_repeated: abi_is_repeated,
+            is_lazy: abi_is_lazy,
             range: abi_range,
             get: abi_get,
+            get_label: abi_get_label,
             id: self.id,
             typ: self.typ,
             meta: self.meta,
             cast: self.cast,
+            labels: self.labels,
+            repeated: self.repeated,
+            lazy: self.lazy,
         }
     }
 }
@@ -203,12 +288,18 @@ pub struct AttrClass {
     get_id: extern "C" fn(class: *const AttrClass) -> Token,
     get_typ: extern "C" fn(class: *const AttrClass) -> Token,
     is_value: extern "C" fn(class: *const AttrClass) -> u8,
+    is_repeated: extern "C" fn(class: *const AttrClass) -> u8,
+    is_lazy: extern "C" fn(class: *const AttrClass) -> u8,
     range: extern "C" fn(*const Attr, *mut u64, *mut u64),
     get: extern "C" fn(*const Attr, *mut *const u8, u64, *mut i64, *mut Error) -> ValueType,
+    get_label: extern "C" fn(*const AttrClass, u64, *mut u64) -> *const u8,
     id: Token,
     typ: Token,
     meta: Metadata,
     cast: Option<Box<Cast>>,
+    labels: FnvHashMap<u64, String>,
+    repeated: bool,
+    lazy: bool,
 }
 
 impl AttrClass {
@@ -219,6 +310,9 @@ impl AttrClass {
             typ: Token::null(),
             meta: Metadata::new(),
             cast: None,
+            labels: FnvHashMap::default(),
+            repeated: false,
+            lazy: false,
         }
     }
 
@@ -234,6 +328,29 @@ impl AttrClass {
         (self.is_value)(self) != 0
     }
 
+    fn is_repeated(&self) -> bool {
+        (self.is_repeated)(self) != 0
+    }
+
+    fn is_lazy(&self) -> bool {
+        (self.is_lazy)(self) != 0
+    }
+
+    fn label(&self, value: u64) -> Option<&str> {
+        let mut len = 0;
+        let ptr = (self.get_label)(self, value, &mut len);
+        if ptr.is_null() {
+            None
+        } else {
+            unsafe {
+                Some(str::from_utf8_unchecked(slice::from_raw_parts(
+                    ptr,
+                    len as usize,
+                )))
+            }
+        }
+    }
+
     fn bit_range(&self, attr: &Attr) -> Range<usize> {
         let mut start;
         let mut end;
@@ -251,6 +368,18 @@ impl AttrClass {
     }
 
     fn try_get(&self, attr: &Attr, layer: &Layer) -> Result<Variant> {
+        if !self.is_lazy() {
+            return self.get(attr, layer);
+        }
+        if let Some(value) = layer.cached_value(attr) {
+            return Ok(value);
+        }
+        let value = self.get(attr, layer)?;
+        layer.cache_value(attr, value.clone());
+        Ok(value)
+    }
+
+    fn get(&self, attr: &Attr, layer: &Layer) -> Result<Variant> {
         let data = layer.data();
         let mut buf: *const u8 = data.as_ptr();
         let mut num = 0;
@@ -281,6 +410,20 @@ impl AttrClass {
                     ByteSlice::from_raw_parts(buf, len as usize)
                 }))
             }
+            ValueType::Ipv6Addr => unsafe {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(slice::from_raw_parts(buf, 16));
+                env::dealloc(buf as *mut u8);
+                Ok(Variant::Ipv6Addr(Ipv6Addr::from(octets)))
+            },
+            ValueType::MacAddr => {
+                let mut octets = [0u8; 6];
+                for (i, b) in octets.iter_mut().enumerate() {
+                    *b = (num >> ((5 - i) * 8)) as u8;
+                }
+                Ok(Variant::MacAddr(octets))
+            }
+            ValueType::Timestamp => Ok(Variant::Timestamp(num as u64)),
             _ => Ok(Variant::Nil),
         }
     }
@@ -318,6 +461,31 @@ extern "C" fn abi_is_value(class: *const AttrClass) -> u8 {
     }
 }
 
+extern "C" fn abi_get_label(class: *const AttrClass, value: u64, len: *mut u64) -> *const u8 {
+    match unsafe { (*class).labels.get(&value) } {
+        Some(label) => {
+            unsafe { *len = label.len() as u64 };
+            label.as_ptr()
+        }
+        None => ptr::null(),
+    }
+}
+
+extern "C" fn abi_is_repeated(class: *const AttrClass) -> u8 {
+    unsafe { (*class).repeated as u8 }
+}
+
+extern "C" fn abi_is_lazy(class: *const AttrClass) -> u8 {
+    unsafe { (*class).lazy as u8 }
+}
+
+fn checked_cast(attr: &Attr, cast: &Cast, slice: &ByteSlice) -> io::Result<Variant> {
+    panic::catch_unwind(AssertUnwindSafe(|| cast.cast(attr, slice))).unwrap_or_else(|payload| {
+        let desc = panic_description(payload.as_ref());
+        Err(io::Error::new(io::ErrorKind::Other, desc))
+    })
+}
+
 extern "C" fn abi_get(
     attr: *const Attr,
     data: *mut *const u8,
@@ -333,7 +501,7 @@ extern "C" fn abi_get(
     let verr;
 
     let result = if let Some(cast) = cast.as_ref() {
-        res = cast.cast(attr, &slice);
+        res = checked_cast(attr, cast.as_ref(), &slice);
         res.as_ref()
     } else if let Some(val) = value {
         Ok(val.as_ref())
@@ -383,10 +551,26 @@ extern "C" fn abi_get(
                 };
                 ValueType::ByteSlice
             }
+            Variant::Ipv6Addr(val) => {
+                unsafe {
+                    let (buf, _) = SafeVec::into_raw(SafeVec::from(&val.octets()[..]));
+                    *data = buf;
+                };
+                ValueType::Ipv6Addr
+            }
+            Variant::MacAddr(val) => {
+                let n = val.iter().fold(0i64, |n, b| (n << 8) | i64::from(*b));
+                unsafe { *num = n };
+                ValueType::MacAddr
+            }
+            Variant::Timestamp(val) => {
+                unsafe { *(num as *mut u64) = *val };
+                ValueType::Timestamp
+            }
             _ => ValueType::Nil,
         },
         Err(e) => {
-            unsafe { *err = Error::new(::std::error::Error::description(&*e)) }
+            unsafe { *err = Error::new(&e.to_string()) }
             ValueType::Error
         }
     }
@@ -401,11 +585,74 @@ mod tests {
     use slice::{ByteSlice, TryGet};
     use std::{
         io::{Error, ErrorKind, Result},
+        net::Ipv6Addr,
         str::from_utf8,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
     };
     use token::Token;
     use variant::Variant;
 
+    #[test]
+    fn lazy() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = count.clone();
+        let class = Fixed::new(
+            AttrClass::builder("lazy")
+                .lazy(move |_attr, data| {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    Ok(Variant::UInt64(u64::from(data[0])))
+                })
+                .build(),
+        );
+        let attr = Attr::builder(class).range(0..1).build();
+        let layer_class = Fixed::new(LayerClass::builder(Token::null()).build());
+        let data = b"\x05";
+        let layer = Layer::new(layer_class.clone(), ByteSlice::from(&data[..]));
+        assert_eq!(attr.try_get(&layer).unwrap(), Variant::UInt64(5));
+        assert_eq!(attr.try_get(&layer).unwrap(), Variant::UInt64(5));
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        let data = b"\x07";
+        let layer = Layer::new(layer_class, ByteSlice::from(&data[..]));
+        assert_eq!(attr.try_get(&layer).unwrap(), Variant::UInt64(7));
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn lazy_header() {
+        let class = Fixed::new(
+            AttrClass::builder("lazy")
+                .lazy(|_attr, data| Ok(Variant::UInt64(u64::from(data[0]))))
+                .build(),
+        );
+        let layer_class = Fixed::new(
+            LayerClass::builder(Token::null())
+                .header(Attr::builder(class).range(0..1).build())
+                .build(),
+        );
+        let value = |layer: &Layer| layer.headers()[0].try_get(layer).unwrap();
+
+        let mut buf = [5u8];
+        let layer = Layer::new(layer_class.clone(), unsafe {
+            ByteSlice::from_raw_parts(buf.as_ptr(), buf.len())
+        });
+        let other = Layer::new(layer_class.clone(), ByteSlice::from(&b"\x06"[..]));
+        assert_eq!(value(&layer), Variant::UInt64(5));
+        assert_eq!(value(&other), Variant::UInt64(6));
+        assert_eq!(value(&layer), Variant::UInt64(5));
+        drop(layer);
+
+        // A new layer over the same address does not see the value of the previous one.
+        buf[0] = 7;
+        let layer = Layer::new(layer_class, unsafe {
+            ByteSlice::from_raw_parts(buf.as_ptr(), buf.len())
+        });
+        assert_eq!(value(&layer), Variant::UInt64(7));
+    }
+
     #[test]
     fn bool() {
         #[derive(Clone)]
@@ -582,6 +829,44 @@ mod tests {
         };
     }
 
+    #[test]
+    fn typed_values() {
+        let class = Fixed::new(AttrClass::builder("typed").build());
+        let layer_class = Fixed::new(LayerClass::builder(Token::null()).build());
+        let layer = Layer::new(layer_class, ByteSlice::new());
+
+        let addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+        let attr = Attr::builder(class.clone()).value(addr).build();
+        assert_eq!(attr.try_get(&layer).unwrap(), Variant::Ipv6Addr(addr));
+
+        let mac = [0x00, 0x1b, 0x21, 0xff, 0xfe, 0x80];
+        let attr = Attr::builder(class.clone()).value(mac).build();
+        assert_eq!(attr.try_get(&layer).unwrap(), Variant::MacAddr(mac));
+
+        let attr = Attr::builder(class)
+            .value(Variant::Timestamp(1_514_764_800_000_000_001))
+            .build();
+        assert_eq!(
+            attr.try_get(&layer).unwrap(),
+            Variant::Timestamp(1_514_764_800_000_000_001)
+        );
+    }
+
+    #[test]
+    fn labels() {
+        let class = Fixed::new(
+            AttrClass::builder("eth.type")
+                .labels(vec![(0x0800, "IPv4"), (0x86dd, "IPv6")])
+                .build(),
+        );
+        let layer_class = Fixed::new(LayerClass::builder(Token::null()).build());
+        let layer = Layer::new(layer_class, ByteSlice::new());
+        let attr = Attr::builder(class.clone()).value(0x86ddu64).build();
+        assert_eq!(attr.label(&layer), Some("IPv6"));
+        let attr = Attr::builder(class).value(0x0806u64).build();
+        assert_eq!(attr.label(&layer), None);
+    }
+
     #[test]
     fn error() {
         #[derive(Clone)]
@@ -606,7 +891,7 @@ mod tests {
         let class = Fixed::new(LayerClass::builder(Token::null()).build());
         let layer = Layer::new(class, ByteSlice::from(&b"123456789"[..]));
         match attr.try_get(&layer) {
-            Err(err) => assert_eq!(err.description(), "oh no!"),
+            Err(err) => assert_eq!(err.to_string(), "oh no!"),
             _ => panic!(),
         };
     }
@@ -1,12 +1,69 @@
+use error::Error;
 use fixed::Fixed;
-use fnv::FnvHashMap;
-use std::{slice, str};
+use fnv::{FnvHashMap, FnvHasher};
+use result::Result;
+use serde::de::DeserializeOwned;
+use serde_json;
+use std::{
+    any::Any,
+    hash::{Hash, Hasher},
+    ptr, slice, str,
+};
+
+/// Returns a key of the state for the value.
+pub fn state_key<T: Hash>(value: &T) -> u64 {
+    let mut hasher = FnvHasher::default();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Returns a key of the state shared by both directions of a flow.
+///
+/// `a` and `b` identify the endpoints, e.g. tuples of the address and the port.
+pub fn flow_key<T: Hash + Ord>(a: T, b: T) -> u64 {
+    if a <= b {
+        state_key(&(a, b))
+    } else {
+        state_key(&(b, a))
+    }
+}
+
+type AnyBox = Box<Any + Send>;
+
+struct State {
+    data: *mut u8,
+    drop: extern "C" fn(*mut u8),
+    frame_index: u32,
+}
+
+unsafe impl Send for State {}
+
+impl Drop for State {
+    fn drop(&mut self) {
+        (self.drop)(self.data);
+    }
+}
+
+extern "C" fn abi_drop_state(data: *mut u8) {
+    unsafe { Box::from_raw(data as *mut AnyBox) };
+}
 
 /// A context object.
+///
+/// Each decoder has its own context, which holds the keyed states
+/// persisting across frames, e.g. the handshake state of a connection.
+/// The states which have not been accessed for a while are discarded
+/// by the kernel, so they can be kept without explicit cleanup.
+///
+/// The frames are processed in order only by `SerialSync` decoders;
+/// `ParallelSync` decoders see the states of the frames processed
+/// by the same thread.
 #[repr(C)]
 pub struct Context {
     class: Fixed<ContextClass>,
     config: FnvHashMap<String, String>,
+    frame_index: u32,
+    states: FnvHashMap<u64, State>,
 }
 
 impl Context {
@@ -15,26 +72,110 @@ impl Context {
         Self {
             class: CONTEXT_CLASS.clone(),
             config,
+            frame_index: 0,
+            states: FnvHashMap::default(),
         }
     }
 
+    /// Returns the index of the frame being decoded.
+    pub fn frame_index(&self) -> u32 {
+        self.frame_index
+    }
+
+    /// Sets the index of the frame being decoded.
+    pub fn set_frame_index(&mut self, index: u32) {
+        self.frame_index = index;
+    }
+
     /// Returns a config value in the current profile.
+    ///
+    /// The value is a JSON string, or an empty string if the key is not found.
     pub fn get_config(&self, key: &str) -> &str {
         let mut len = key.len() as u64;
         let data = (self.class.get_config)(self, key.as_ptr(), &mut len);
         unsafe { str::from_utf8_unchecked(slice::from_raw_parts(data, len as usize)) }
     }
+
+    /// Returns a config value in the current profile as the type `T`.
+    ///
+    /// The values declared in the config schema of the package are validated
+    /// when they are set, and fall back to the default values in the schema.
+    ///
+    /// Returns an error if the key is not found or the value has an unexpected type.
+    pub fn config<T: DeserializeOwned>(&self, key: &str) -> Result<T> {
+        let value = self.get_config(key);
+        if value.is_empty() {
+            return Err(Box::new(Error::new(&format!("config not found: {}", key))));
+        }
+        serde_json::from_str(value).map_err(|err| {
+            Box::new(Error::new(&format!("invalid config {}: {}", key, err)))
+                as Box<::std::error::Error>
+        })
+    }
+
+    /// Returns the state associated with the key.
+    ///
+    /// Returns None if the state does not exist or has a different type.
+    pub fn state<T: Any + Send>(&mut self, key: u64) -> Option<&mut T> {
+        let data = (self.class.get_state)(self, key) as *mut AnyBox;
+        if data.is_null() {
+            None
+        } else {
+            unsafe { (*data).downcast_mut::<T>() }
+        }
+    }
+
+    /// Returns the state associated with the key, inserting the result of `f`
+    /// if the state does not exist or has a different type.
+    pub fn state_or_insert_with<T: Any + Send, F: FnOnce() -> T>(
+        &mut self,
+        key: u64,
+        f: F,
+    ) -> &mut T {
+        if self.state::<T>(key).is_none() {
+            self.set_state(key, f());
+        }
+        self.state(key).unwrap()
+    }
+
+    /// Associates the state with the key, replacing the existing one.
+    pub fn set_state<T: Any + Send>(&mut self, key: u64, state: T) {
+        let data: AnyBox = Box::new(state);
+        let data = Box::into_raw(Box::new(data)) as *mut u8;
+        (self.class.set_state)(self, key, data, abi_drop_state);
+    }
+
+    /// Removes the state associated with the key.
+    pub fn remove_state(&mut self, key: u64) {
+        (self.class.remove_state)(self, key);
+    }
+
+    /// Returns the number of the states.
+    pub fn states_len(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Removes the states which have not been accessed since the frame `index`.
+    pub fn expire_states(&mut self, index: u32) {
+        self.states.retain(|_, state| state.frame_index >= index);
+    }
 }
 
 #[repr(C)]
 pub struct ContextClass {
     get_config: extern "C" fn(*const Context, *const u8, *mut u64) -> *const u8,
+    get_state: extern "C" fn(*mut Context, u64) -> *mut u8,
+    set_state: extern "C" fn(*mut Context, u64, *mut u8, extern "C" fn(*mut u8)),
+    remove_state: extern "C" fn(*mut Context, u64),
 }
 
 impl ContextClass {
     fn new() -> ContextClass {
         Self {
             get_config: abi_get_config,
+            get_state: abi_get_state,
+            set_state: abi_set_state,
+            remove_state: abi_remove_state,
         }
     }
 }
@@ -48,6 +189,79 @@ extern "C" fn abi_get_config(ctx: *const Context, data: *const u8, len: *mut u64
     }
 }
 
+extern "C" fn abi_get_state(ctx: *mut Context, key: u64) -> *mut u8 {
+    let ctx = unsafe { &mut *ctx };
+    let frame_index = ctx.frame_index;
+    match ctx.states.get_mut(&key) {
+        Some(state) => {
+            state.frame_index = frame_index;
+            state.data
+        }
+        None => ptr::null_mut(),
+    }
+}
+
+extern "C" fn abi_set_state(
+    ctx: *mut Context,
+    key: u64,
+    data: *mut u8,
+    drop: extern "C" fn(*mut u8),
+) {
+    let ctx = unsafe { &mut *ctx };
+    let state = State {
+        data,
+        drop,
+        frame_index: ctx.frame_index,
+    };
+    ctx.states.insert(key, state);
+}
+
+extern "C" fn abi_remove_state(ctx: *mut Context, key: u64) {
+    let ctx = unsafe { &mut *ctx };
+    ctx.states.remove(&key);
+}
+
 lazy_static! {
     static ref CONTEXT_CLASS: Fixed<ContextClass> = Fixed::new(ContextClass::new());
 }
+
+#[cfg(test)]
+mod tests {
+    use context::{flow_key, Context};
+    use fnv::FnvHashMap;
+
+    #[test]
+    fn config() {
+        let mut config = FnvHashMap::default();
+        config.insert("port".to_string(), "8080".to_string());
+        config.insert("name".to_string(), "\"http\"".to_string());
+        let ctx = Context::new(config);
+        assert_eq!(ctx.get_config("port"), "8080");
+        assert_eq!(ctx.config::<u16>("port").unwrap(), 8080);
+        assert_eq!(ctx.config::<String>("name").unwrap(), "http");
+        assert!(ctx.config::<u16>("name").is_err());
+        assert!(ctx.config::<u16>("prot").is_err());
+    }
+
+    #[test]
+    fn state() {
+        let mut ctx = Context::new(FnvHashMap::default());
+        let key = flow_key(("10.0.0.1", 80), ("10.0.0.2", 5000));
+        assert_eq!(key, flow_key(("10.0.0.2", 5000), ("10.0.0.1", 80)));
+        assert!(ctx.state::<u32>(key).is_none());
+
+        *ctx.state_or_insert_with(key, || 0u32) += 1;
+        *ctx.state_or_insert_with(key, || 0u32) += 1;
+        assert_eq!(ctx.state::<u32>(key), Some(&mut 2));
+        assert!(ctx.state::<String>(key).is_none());
+
+        ctx.set_frame_index(10);
+        ctx.set_state(1, String::from("idle"));
+        ctx.expire_states(5);
+        assert_eq!(ctx.states_len(), 1);
+        assert!(ctx.state::<u32>(key).is_none());
+
+        ctx.remove_state(1);
+        assert_eq!(ctx.states_len(), 0);
+    }
+}
@@ -1,11 +1,17 @@
 use bincode;
 use context::Context;
-use error::Error;
+use error::{panic_description, DecodeError, ErrorKind};
 use fixed::MutFixed;
 use layer::{Layer, LayerStack, Parent};
 use result::Result;
 use serde::ser::{Serialize, Serializer};
-use std::ptr;
+use slice::{ByteChain, ByteSlice, TryGet};
+use std::{
+    collections::BTreeMap,
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+use token::Token;
 use vec::SafeVec;
 
 /// Execution type.
@@ -16,19 +22,64 @@ pub enum ExecType {
 }
 
 /// Decoding status.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Status {
+    /// The worker produced its result for the current layer.
     Done,
+    /// The worker is not interested in the current layer.
     Skip,
+    /// The worker consumed the current layer but needs subsequent frames
+    /// before producing a result.
+    ///
+    /// The worker is invoked again for the next layer of the same flow,
+    /// even if the layer does not match its triggers or another worker scores higher.
+    Pending,
+}
+
+/// Dispatch binding.
+///
+/// A decoder with triggers is executed only for layers
+/// in which one of the attributes has the bound value,
+/// or which have a payload with the bound tag.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Trigger {
+    pub attr: String,
+    pub value: u64,
+    #[serde(default)]
+    pub payload: Option<String>,
+}
+
+impl Trigger {
+    /// Creates a new Trigger.
+    pub fn new<T: Into<String>>(attr: T, value: u64) -> Trigger {
+        Trigger {
+            attr: attr.into(),
+            value,
+            payload: None,
+        }
+    }
+
+    /// Creates a new Trigger bound to the payload tag.
+    pub fn payload<T: Into<String>>(tag: T) -> Trigger {
+        Trigger {
+            attr: String::new(),
+            value: 0,
+            payload: Some(tag.into()),
+        }
+    }
 }
 
 /// Decoder metadata.
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub struct Metadata {
     pub id: String,
     pub name: String,
     pub description: String,
     pub exec_type: ExecType,
+    pub triggers: Vec<Trigger>,
+    pub author: String,
+    pub references: Vec<String>,
 }
 
 impl Default for Metadata {
@@ -38,13 +89,163 @@ impl Default for Metadata {
             name: String::new(),
             description: String::new(),
             exec_type: ExecType::ParallelSync,
+            triggers: Vec::new(),
+            author: String::new(),
+            references: Vec::new(),
         }
     }
 }
 
+/// Policy for bytes received more than once with different contents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overlap {
+    /// Keeps the bytes received first.
+    First,
+
+    /// Replaces buffered bytes with the ones received last.
+    ///
+    /// Bytes which have already been returned are never replaced.
+    Last,
+}
+
+impl Default for Overlap {
+    fn default() -> Self {
+        Overlap::First
+    }
+}
+
+/// A reassembly buffer for byte streams spanning multiple frames.
+///
+/// Segments can be put in any order; the bytes before the current offset
+/// are discarded.
+#[derive(Debug, Default)]
+pub struct StreamBuffer {
+    overlap: Overlap,
+    offset: u64,
+    pending: BTreeMap<u64, ByteSlice>,
+}
+
+impl StreamBuffer {
+    /// Creates a new StreamBuffer with the default overlap policy.
+    pub fn new() -> StreamBuffer {
+        Self::default()
+    }
+
+    /// Creates a new StreamBuffer with the given overlap policy.
+    pub fn with_overlap(overlap: Overlap) -> StreamBuffer {
+        StreamBuffer {
+            overlap,
+            ..Self::default()
+        }
+    }
+
+    /// Returns the overlap policy.
+    pub fn overlap(&self) -> Overlap {
+        self.overlap
+    }
+
+    /// Returns the stream offset of the next byte to be fetched.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the number of bytes buffered out of order.
+    pub fn pending_len(&self) -> usize {
+        self.pending.values().map(|data| data.len()).sum()
+    }
+
+    /// Discards the buffered segments.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Buffers a segment located at the given stream offset.
+    pub fn put(&mut self, pos: u64, data: ByteSlice) {
+        let end = pos + data.len() as u64;
+        if data.is_empty() || end <= self.offset {
+            return;
+        }
+        let (pos, data) = if pos < self.offset {
+            let skip = (self.offset - pos) as usize;
+            (self.offset, data.try_get(skip..).unwrap())
+        } else {
+            (pos, data)
+        };
+
+        let overlapped = self
+            .pending
+            .range(..end)
+            .rev()
+            .take_while(|(start, slice)| **start + slice.len() as u64 > pos)
+            .map(|(start, _)| *start)
+            .collect::<Vec<_>>();
+
+        match self.overlap {
+            Overlap::First => {
+                let mut cursor = pos;
+                for start in overlapped.into_iter().rev() {
+                    let len = self.pending[&start].len() as u64;
+                    if start > cursor {
+                        let gap = (cursor - pos) as usize..(start - pos) as usize;
+                        self.pending.insert(cursor, data.try_get(gap).unwrap());
+                    }
+                    cursor = cursor.max(start + len);
+                }
+                if cursor < end {
+                    let rest = (cursor - pos) as usize..;
+                    self.pending.insert(cursor, data.try_get(rest).unwrap());
+                }
+            }
+            Overlap::Last => {
+                for start in overlapped {
+                    let slice = self.pending.remove(&start).unwrap();
+                    let slice_end = start + slice.len() as u64;
+                    if start < pos {
+                        let head = ..(pos - start) as usize;
+                        self.pending.insert(start, slice.try_get(head).unwrap());
+                    }
+                    if slice_end > end {
+                        let tail = (end - start) as usize..;
+                        self.pending.insert(end, slice.try_get(tail).unwrap());
+                    }
+                }
+                self.pending.insert(pos, data);
+            }
+        }
+    }
+
+    /// Removes and returns the contiguous segments from the current offset.
+    pub fn fetch(&mut self) -> ByteChain {
+        let mut chain = ByteChain::new();
+        while let Some(start) = self.pending.keys().next().cloned() {
+            if start != self.offset {
+                break;
+            }
+            let slice = self.pending.remove(&start).unwrap();
+            self.offset += slice.len() as u64;
+            chain.push(slice);
+        }
+        chain
+    }
+}
+
 /// Decoder worker trait.
+///
+/// A worker only sees the layers of one frame. Protocols carried over a byte
+/// stream keep a StreamBuffer per flow in the context state, and add the
+/// reassembled bytes to the parent as a chained payload typed `@stream:<proto>`,
+/// which the application-layer workers decode as a continuous stream.
 pub trait Worker {
     fn decode(&mut self, &mut Context, &LayerStack, &mut Parent) -> Result<Status>;
+
+    /// Returns the confidence (0-100) that this worker can decode the layer.
+    ///
+    /// Workers returning a non-zero score compete for the layer and
+    /// only the highest-scoring one is executed; on a tie, the one whose decoder
+    /// is registered first wins. The default returns 0, which opts out of the competition.
+    fn probe(&mut self, _ctx: &Context, _stack: &LayerStack, _parent: &Parent) -> u8 {
+        0
+    }
 }
 
 #[repr(C)]
@@ -54,8 +255,19 @@ pub struct WorkerBox {
         *mut Context,
         *const *const Layer,
         u64,
+        *const u8,
+        u64,
         *mut Parent,
-        *mut Error,
+        *mut DecodeError,
+    ) -> u8,
+    probe: extern "C" fn(
+        *mut WorkerBox,
+        *const Context,
+        *const *const Layer,
+        u64,
+        *const u8,
+        u64,
+        *const Parent,
     ) -> u8,
     worker: *mut Box<Worker>,
 }
@@ -64,23 +276,54 @@ impl WorkerBox {
     fn new(worker: Box<Worker>) -> WorkerBox {
         Self {
             decode: abi_decode,
+            probe: abi_probe,
             worker: Box::into_raw(Box::new(worker)),
         }
     }
 
+    pub fn probe(
+        &mut self,
+        ctx: &Context,
+        layers: &[MutFixed<Layer>],
+        indices: &[u8],
+        layer: &Parent,
+    ) -> u8 {
+        let stack = layers.as_ptr() as *const *const Layer;
+        (self.probe)(
+            self,
+            ctx,
+            stack,
+            layers.len() as u64,
+            indices.as_ptr(),
+            indices.len() as u64,
+            layer,
+        )
+    }
+
     pub fn decode(
         &mut self,
         ctx: &mut Context,
         layers: &[MutFixed<Layer>],
+        indices: &[u8],
         layer: &mut Parent,
-    ) -> Result<bool> {
+    ) -> ::std::result::Result<Status, DecodeError> {
         let stack = layers.as_ptr() as *const *const Layer;
-        let mut error = Error::new("");
-        let result = (self.decode)(self, ctx, stack, layers.len() as u64, layer, &mut error);
+        let mut error = DecodeError::new(ErrorKind::Other, "");
+        let result = (self.decode)(
+            self,
+            ctx,
+            stack,
+            layers.len() as u64,
+            indices.as_ptr(),
+            indices.len() as u64,
+            layer,
+            &mut error,
+        );
         match result {
-            2 => Ok(true),
-            1 => Ok(false),
-            _ => Err(Box::new(error)),
+            3 => Ok(Status::Pending),
+            2 => Ok(Status::Done),
+            1 => Ok(Status::Skip),
+            _ => Err(error),
         }
     }
 }
@@ -90,27 +333,61 @@ extern "C" fn abi_decode(
     ctx: *mut Context,
     layers: *const *const Layer,
     len: u64,
+    indices: *const u8,
+    indices_len: u64,
     layer: *mut Parent,
-    error: *mut Error,
+    error: *mut DecodeError,
 ) -> u8 {
     let worker = unsafe { &mut *((*worker).worker) };
     let ctx = unsafe { &mut (*ctx) };
     let mut layer = unsafe { &mut *layer };
-    let stack = unsafe { LayerStack::new(layers, len as usize) };
-    match worker.decode(ctx, &stack, &mut layer) {
+    let stack =
+        unsafe { LayerStack::with_indices(layers, len as usize, indices, indices_len as usize) };
+    let result = panic::catch_unwind(AssertUnwindSafe(|| worker.decode(ctx, &stack, &mut layer)))
+        .unwrap_or_else(|payload| {
+            let desc = panic_description(payload.as_ref());
+            let err: Box<::std::error::Error> =
+                Box::new(DecodeError::new(ErrorKind::Internal, &desc));
+            Err(err)
+        });
+    match result {
         Ok(stat) => match stat {
+            Status::Pending => 3,
             Status::Done => 2,
             Status::Skip => 1,
         },
         Err(err) => {
+            let mut err = DecodeError::from(err.as_ref());
+            if err.source() == Token::null() {
+                err = err.with_source(layer.id());
+            }
             unsafe {
-                ptr::write(error, Error::new(err.description()));
+                ptr::write(error, err);
             }
             0
         }
     }
 }
 
+extern "C" fn abi_probe(
+    worker: *mut WorkerBox,
+    ctx: *const Context,
+    layers: *const *const Layer,
+    len: u64,
+    indices: *const u8,
+    indices_len: u64,
+    layer: *const Parent,
+) -> u8 {
+    let worker = unsafe { &mut *((*worker).worker) };
+    let ctx = unsafe { &(*ctx) };
+    let layer = unsafe { &*layer };
+    let stack =
+        unsafe { LayerStack::with_indices(layers, len as usize, indices, indices_len as usize) };
+    panic::catch_unwind(AssertUnwindSafe(|| worker.probe(ctx, &stack, layer)))
+        .unwrap_or(0)
+        .min(100)
+}
+
 /// Decoder trait.
 pub trait Decoder: DecoderClone + Send {
     fn new_worker(&self, &Context) -> Box<Worker>;
@@ -161,12 +438,14 @@ impl DecoderBox {
         }
     }
 
+    /// Creates a new worker, or a worker skipping every layer if the decoder panicked.
     pub fn new_worker(&mut self, ctx: &Context) -> WorkerBox {
         (self.new_worker)(self, ctx)
     }
 
+    /// Returns the metadata, or the default one if the decoder panicked.
     pub fn metadata(&self) -> Metadata {
-        bincode::deserialize(&(self.metadata)(self)).unwrap()
+        bincode::deserialize(&(self.metadata)(self)).unwrap_or_default()
     }
 }
 
@@ -179,21 +458,42 @@ impl Serialize for DecoderBox {
     }
 }
 
+/// A worker substituted for the one whose construction panicked.
+struct NullWorker {}
+
+impl Worker for NullWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        _parent: &mut Parent,
+    ) -> Result<Status> {
+        Ok(Status::Skip)
+    }
+}
+
 extern "C" fn abi_new_worker(diss: *mut DecoderBox, ctx: *const Context) -> WorkerBox {
     let diss = unsafe { &mut *((*diss).decoder) };
     let ctx = unsafe { &(*ctx) };
-    WorkerBox::new(diss.new_worker(ctx))
+    let worker = panic::catch_unwind(AssertUnwindSafe(|| diss.new_worker(ctx)))
+        .unwrap_or_else(|_| Box::new(NullWorker {}));
+    WorkerBox::new(worker)
 }
 
 extern "C" fn abi_metadata(diss: *const DecoderBox) -> SafeVec<u8> {
     let diss = unsafe { &*((*diss).decoder) };
-    bincode::serialize(&diss.metadata()).unwrap().into()
+    panic::catch_unwind(AssertUnwindSafe(|| bincode::serialize(&diss.metadata())))
+        .ok()
+        .and_then(|data| data.ok())
+        .unwrap_or_default()
+        .into()
 }
 
 #[cfg(test)]
 mod tests {
     use context::Context;
-    use decoder::{Decoder, DecoderBox, ExecType, Metadata, Status, Worker};
+    use decoder::{Decoder, DecoderBox, ExecType, Metadata, Overlap, Status, StreamBuffer, Worker};
+    use error::ErrorKind;
     use fixed::Fixed;
     use fnv::FnvHashMap;
     use layer::{Layer, LayerClass, LayerStack, Parent};
@@ -243,6 +543,101 @@ mod tests {
         let mut layer = Layer::new(class, ByteSlice::new());
         let mut layer = Parent::from_mut_ref(&mut layer);
 
-        assert_eq!(worker.decode(&mut ctx, &[], &mut layer).unwrap(), true);
+        assert_eq!(
+            worker.decode(&mut ctx, &[], &[], &mut layer).unwrap(),
+            Status::Done
+        );
+    }
+
+    #[test]
+    fn decode_panic() {
+        struct TestWorker {}
+
+        impl Worker for TestWorker {
+            fn decode(
+                &mut self,
+                _ctx: &mut Context,
+                _stack: &LayerStack,
+                _parent: &mut Parent,
+            ) -> Result<Status> {
+                panic!("oh no!");
+            }
+        }
+
+        #[derive(Clone)]
+        struct TestDecoder {}
+
+        impl Decoder for TestDecoder {
+            fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+                Box::new(TestWorker {})
+            }
+
+            fn metadata(&self) -> Metadata {
+                Metadata::default()
+            }
+        }
+
+        let mut ctx = Context::new(FnvHashMap::default());
+        let mut diss = DecoderBox::new(TestDecoder {});
+        let mut worker = diss.new_worker(&ctx);
+
+        let class = Fixed::new(LayerClass::builder(Token::from("eth")).build());
+        let mut layer = Layer::new(class, ByteSlice::new());
+        let mut layer = Parent::from_mut_ref(&mut layer);
+
+        let err = worker.decode(&mut ctx, &[], &[], &mut layer).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Internal);
+        assert_eq!(err.source(), Token::from("eth"));
+    }
+
+    #[test]
+    fn new_worker_panic() {
+        #[derive(Clone)]
+        struct TestDecoder {}
+
+        impl Decoder for TestDecoder {
+            fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+                panic!("oh no!");
+            }
+
+            fn metadata(&self) -> Metadata {
+                panic!("oh no!");
+            }
+        }
+
+        let mut ctx = Context::new(FnvHashMap::default());
+        let mut diss = DecoderBox::new(TestDecoder {});
+        assert_eq!(diss.metadata().id, "");
+        let mut worker = diss.new_worker(&ctx);
+
+        let class = Fixed::new(LayerClass::builder(Token::null()).build());
+        let mut layer = Layer::new(class, ByteSlice::new());
+        let mut layer = Parent::from_mut_ref(&mut layer);
+
+        assert_eq!(
+            worker.decode(&mut ctx, &[], &[], &mut layer).unwrap(),
+            Status::Skip
+        );
+    }
+
+    #[test]
+    fn stream_buffer() {
+        let mut stream = StreamBuffer::new();
+        stream.put(5, ByteSlice::from(&b"world"[..]));
+        assert!(stream.fetch().is_empty());
+        assert_eq!(stream.pending_len(), 5);
+        stream.put(0, ByteSlice::from(&b"hello"[..]));
+        stream.put(3, ByteSlice::from(&b"LOWO"[..]));
+        assert_eq!(stream.fetch().bytes().collect::<Vec<u8>>(), b"helloworld");
+        assert_eq!(stream.offset(), 10);
+        stream.put(8, ByteSlice::from(&b"ld!!"[..]));
+        assert_eq!(stream.fetch().bytes().collect::<Vec<u8>>(), b"!!");
+        assert_eq!(stream.pending_len(), 0);
+
+        let mut stream = StreamBuffer::with_overlap(Overlap::Last);
+        stream.put(3, ByteSlice::from(&b"lo"[..]));
+        stream.put(2, ByteSlice::from(&b"LLO"[..]));
+        stream.put(0, ByteSlice::from(&b"he"[..]));
+        assert_eq!(stream.fetch().bytes().collect::<Vec<u8>>(), b"heLLO");
     }
 }
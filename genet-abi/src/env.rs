@@ -1,16 +1,26 @@
 use fixed::Fixed;
-use fnv::FnvHashMap;
+use fnv::{FnvHashMap, FnvHasher};
 use libc;
-use parking_lot::Mutex;
-use std::{cell::RefCell, collections::hash_map::Entry, slice, str};
+use parking_lot::RwLock;
+use spill;
+use std::{
+    collections::BTreeMap,
+    hash::{Hash, Hasher},
+    mem, ptr, slice, str,
+};
 use token::Token;
+use version::{self, AbiInfo};
 
 #[cfg(not(feature = "genet-static"))]
 #[no_mangle]
 pub extern "C" fn genet_abi_version() -> u64 {
-    let major: u64 = env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap_or(0);
-    let minor: u64 = env!("CARGO_PKG_VERSION_MINOR").parse().unwrap_or(0);
-    major << 32 | minor
+    version::current()
+}
+
+#[cfg(not(feature = "genet-static"))]
+#[no_mangle]
+pub extern "C" fn genet_abi_v1_info() -> AbiInfo {
+    AbiInfo::current()
 }
 
 #[cfg(not(feature = "genet-static"))]
@@ -27,6 +37,40 @@ pub extern "C" fn genet_abi_v1_register_get_string(
     unsafe { GENET_GET_STRING = ptr };
 }
 
+#[cfg(not(feature = "genet-static"))]
+#[no_mangle]
+pub extern "C" fn genet_abi_v1_register_get_tokens(
+    ptr: extern "C" fn(*const *const u8, *const u64, u64, *mut Token),
+) {
+    unsafe { GENET_GET_TOKENS = ptr };
+}
+
+#[cfg(not(feature = "genet-static"))]
+#[no_mangle]
+pub extern "C" fn genet_abi_v1_register_lookup_token(ptr: extern "C" fn(*const u8, u64) -> Token) {
+    unsafe { GENET_LOOKUP_TOKEN = ptr };
+}
+
+#[cfg(not(feature = "genet-static"))]
+#[no_mangle]
+pub extern "C" fn genet_abi_v1_register_token_count(ptr: extern "C" fn() -> u64) {
+    unsafe { GENET_TOKEN_COUNT = ptr };
+}
+
+#[cfg(not(feature = "genet-static"))]
+#[no_mangle]
+pub extern "C" fn genet_abi_v1_register_get_descendants(
+    ptr: extern "C" fn(*const u8, u64, *mut Token, u64) -> u64,
+) {
+    unsafe { GENET_GET_DESCENDANTS = ptr };
+}
+
+#[cfg(not(feature = "genet-static"))]
+#[no_mangle]
+pub extern "C" fn genet_abi_v1_register_spill(ptr: extern "C" fn(*const u8, u64) -> *const u8) {
+    unsafe { GENET_SPILL = ptr };
+}
+
 #[cfg(not(feature = "genet-static"))]
 #[no_mangle]
 pub extern "C" fn genet_abi_v1_register_get_allocator(ptr: extern "C" fn() -> Fixed<Allocator>) {
@@ -36,7 +80,15 @@ pub extern "C" fn genet_abi_v1_register_get_allocator(ptr: extern "C" fn() -> Fi
 static mut GENET_GET_TOKEN: unsafe extern "C" fn(*const u8, u64) -> Token = abi_genet_get_token;
 static mut GENET_GET_STRING: unsafe extern "C" fn(Token, *mut u64) -> *const u8 =
     abi_genet_get_string;
+static mut GENET_GET_TOKENS: unsafe extern "C" fn(*const *const u8, *const u64, u64, *mut Token) =
+    abi_genet_get_tokens;
+static mut GENET_LOOKUP_TOKEN: unsafe extern "C" fn(*const u8, u64) -> Token =
+    abi_genet_lookup_token;
+static mut GENET_TOKEN_COUNT: unsafe extern "C" fn() -> u64 = abi_genet_token_count;
+static mut GENET_GET_DESCENDANTS: unsafe extern "C" fn(*const u8, u64, *mut Token, u64) -> u64 =
+    abi_genet_get_descendants;
 static mut GENET_GET_ALLOCATOR: extern "C" fn() -> Fixed<Allocator> = abi_genet_get_allocator;
+static mut GENET_SPILL: extern "C" fn(*const u8, u64) -> *const u8 = spill::abi_genet_spill;
 
 pub extern "C" fn abi_genet_get_allocator() -> Fixed<Allocator> {
     extern "C" fn alloc(size: u64) -> *mut u8 {
@@ -56,25 +108,66 @@ pub extern "C" fn abi_genet_get_allocator() -> Fixed<Allocator> {
 }
 
 pub unsafe extern "C" fn abi_genet_get_token(data: *const u8, len: u64) -> Token {
-    let tokens = GLOBAL_TOKENS.lock();
-    let mut tokens = tokens.borrow_mut();
-    let strings = GLOBAL_STRINGS.lock();
-    let mut strings = strings.borrow_mut();
     let id = str::from_utf8_unchecked(slice::from_raw_parts(data, len as usize));
-    if id.is_empty() {
-        return Token::null();
+    register_tokens(&[id])[0]
+}
+
+/// Registers `len` strings at once and writes the tokens into `tokens`.
+pub unsafe extern "C" fn abi_genet_get_tokens(
+    data: *const *const u8,
+    lens: *const u64,
+    len: u64,
+    tokens: *mut Token,
+) {
+    let data = slice::from_raw_parts(data, len as usize);
+    let lens = slice::from_raw_parts(lens, len as usize);
+    let ids = data
+        .iter()
+        .zip(lens.iter())
+        .map(|(data, len)| str::from_utf8_unchecked(slice::from_raw_parts(*data, *len as usize)))
+        .collect::<Vec<_>>();
+    let result = register_tokens(&ids);
+    ptr::copy_nonoverlapping(result.as_ptr(), tokens, result.len());
+}
+
+fn register_tokens(ids: &[&str]) -> Vec<Token> {
+    ids.iter()
+        .map(|id| GLOBAL_TOKENS.get_or_insert(id))
+        .collect()
+}
+
+/// Writes the tokens under the namespace into the buffer and returns the total count.
+pub unsafe extern "C" fn abi_genet_get_descendants(
+    data: *const u8,
+    len: u64,
+    buf: *mut Token,
+    cap: u64,
+) -> u64 {
+    let namespaces = GLOBAL_NAMESPACES.read();
+    let id = str::from_utf8_unchecked(slice::from_raw_parts(data, len as usize));
+    let mut tokens = Vec::new();
+    if let Some(ns) = namespaces.find(id) {
+        for child in ns.children.values() {
+            child.collect(&mut tokens);
+        }
     }
-    let next = tokens.len() + 1;
-    let entry = tokens.entry(String::from(id));
-    if let Entry::Vacant(_) = entry {
-        strings.push(String::from(id));
+    if tokens.len() as u64 <= cap {
+        ptr::copy_nonoverlapping(tokens.as_ptr(), buf, tokens.len());
     }
-    *entry.or_insert_with(|| Token::from(next as u32))
+    tokens.len() as u64
+}
+
+pub unsafe extern "C" fn abi_genet_lookup_token(data: *const u8, len: u64) -> Token {
+    let id = str::from_utf8_unchecked(slice::from_raw_parts(data, len as usize));
+    GLOBAL_TOKENS.get(id).unwrap_or_else(Token::null)
+}
+
+pub unsafe extern "C" fn abi_genet_token_count() -> u64 {
+    GLOBAL_TOKENS.strings.read().len() as u64 - 1
 }
 
 pub unsafe extern "C" fn abi_genet_get_string(token: Token, len: *mut u64) -> *const u8 {
-    let strings = GLOBAL_STRINGS.lock();
-    let strings = strings.borrow();
+    let strings = GLOBAL_TOKENS.strings.read();
     let index: u32 = token.into();
     let index = index as usize;
     let s = if index < strings.len() {
@@ -88,10 +181,104 @@ pub unsafe extern "C" fn abi_genet_get_string(token: Token, len: *mut u64) -> *c
 
 lazy_static! {
     static ref GLOBAL_ALLOCATOR: Fixed<Allocator> = unsafe { GENET_GET_ALLOCATOR() };
-    static ref GLOBAL_TOKENS: Mutex<RefCell<FnvHashMap<String, Token>>> =
-        Mutex::new(RefCell::new(FnvHashMap::default()));
-    static ref GLOBAL_STRINGS: Mutex<RefCell<Vec<String>>> =
-        Mutex::new(RefCell::new(vec![String::new()]));
+    static ref GLOBAL_TOKENS: TokenTable = TokenTable::new();
+    static ref GLOBAL_NAMESPACES: RwLock<Namespace> = RwLock::new(Namespace::default());
+}
+
+const TOKEN_SHARDS: usize = 16;
+
+/// A token table sharded by the string hash.
+///
+/// Lookups of the registered tokens only take a shared lock of a single shard,
+/// so concurrent workers rarely contend with each other.
+struct TokenTable {
+    shards: Vec<RwLock<FnvHashMap<String, Token>>>,
+    strings: RwLock<Vec<String>>,
+}
+
+impl TokenTable {
+    fn new() -> TokenTable {
+        TokenTable {
+            shards: (0..TOKEN_SHARDS)
+                .map(|_| RwLock::new(FnvHashMap::default()))
+                .collect(),
+            strings: RwLock::new(vec![String::new()]),
+        }
+    }
+
+    fn shard(&self, id: &str) -> &RwLock<FnvHashMap<String, Token>> {
+        let mut hasher = FnvHasher::default();
+        id.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % TOKEN_SHARDS]
+    }
+
+    fn get(&self, id: &str) -> Option<Token> {
+        self.shard(id).read().get(id).cloned()
+    }
+
+    fn get_or_insert(&self, id: &str) -> Token {
+        if id.is_empty() {
+            return Token::null();
+        }
+        if let Some(token) = self.get(id) {
+            return token;
+        }
+        let mut shard = self.shard(id).write();
+        if let Some(token) = shard.get(id) {
+            return *token;
+        }
+        let token = {
+            let mut strings = self.strings.write();
+            strings.push(String::from(id));
+            Token::from((strings.len() - 1) as u32)
+        };
+        GLOBAL_NAMESPACES.write().insert(id, token);
+        shard.insert(String::from(id), token);
+        token
+    }
+}
+
+/// A tree of the tokens split by dots.
+struct Namespace {
+    token: Token,
+    children: BTreeMap<String, Namespace>,
+}
+
+impl Default for Namespace {
+    fn default() -> Namespace {
+        Namespace {
+            token: Token::null(),
+            children: BTreeMap::new(),
+        }
+    }
+}
+
+impl Namespace {
+    fn insert(&mut self, id: &str, token: Token) {
+        let node = id.split('.').fold(self, |node, name| {
+            node.children
+                .entry(name.to_string())
+                .or_insert_with(Namespace::default)
+        });
+        node.token = token;
+    }
+
+    fn find(&self, id: &str) -> Option<&Namespace> {
+        if id.is_empty() {
+            return Some(self);
+        }
+        id.split('.')
+            .try_fold(self, |node, name| node.children.get(name))
+    }
+
+    fn collect(&self, tokens: &mut Vec<Token>) {
+        if self.token != Token::null() {
+            tokens.push(self.token);
+        }
+        for child in self.children.values() {
+            child.collect(tokens);
+        }
+    }
 }
 
 #[repr(C)]
@@ -109,6 +296,57 @@ pub(crate) fn token(id: &str) -> Token {
     }
 }
 
+/// Returns the tokens corresponded with the given strings.
+///
+/// This is equivalent to calling `Token::from` for each string,
+/// but registers all of them in a single call across the plugin boundary.
+pub fn tokens(ids: &[&str]) -> Vec<Token> {
+    let data = ids.iter().map(|id| id.as_ptr()).collect::<Vec<_>>();
+    let lens = ids.iter().map(|id| id.len() as u64).collect::<Vec<_>>();
+    let mut tokens = vec![Token::null(); ids.len()];
+    unsafe {
+        GENET_GET_TOKENS(
+            data.as_ptr(),
+            lens.as_ptr(),
+            ids.len() as u64,
+            tokens.as_mut_ptr(),
+        )
+    };
+    tokens
+}
+
+pub(crate) fn lookup(id: &str) -> Token {
+    if id.is_empty() {
+        Token::null()
+    } else {
+        unsafe { GENET_LOOKUP_TOKEN(id.as_ptr(), id.len() as u64) }
+    }
+}
+
+pub(crate) fn token_count() -> u32 {
+    unsafe { GENET_TOKEN_COUNT() as u32 }
+}
+
+pub(crate) fn descendants(id: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    loop {
+        let cap = tokens.capacity();
+        let len = unsafe {
+            GENET_GET_DESCENDANTS(
+                id.as_ptr(),
+                id.len() as u64,
+                tokens.as_mut_ptr(),
+                cap as u64,
+            )
+        } as usize;
+        if len <= cap {
+            unsafe { tokens.set_len(len) };
+            return tokens;
+        }
+        tokens.reserve_exact(len);
+    }
+}
+
 pub(crate) fn string(id: Token) -> String {
     if id == Token::null() {
         String::new()
@@ -119,6 +357,26 @@ pub(crate) fn string(id: Token) -> String {
     }
 }
 
+/// Copies the buffer to disk if the host exceeds the memory limit.
+pub(crate) fn spill(data: &[u8]) -> Option<*const u8> {
+    let ptr = unsafe { GENET_SPILL(data.as_ptr(), data.len() as u64) };
+    if ptr.is_null() {
+        None
+    } else {
+        Some(ptr)
+    }
+}
+
+/// Copies the value to disk if the host exceeds the memory limit.
+pub(crate) fn spill_value<T>(value: &T) -> Option<*mut T> {
+    let size = mem::size_of::<T>();
+    if size == 0 || mem::align_of::<T>() > spill::ALIGN {
+        return None;
+    }
+    let data = unsafe { slice::from_raw_parts(value as *const T as *const u8, size) };
+    spill(data).map(|ptr| ptr as *mut T)
+}
+
 pub fn alloc(len: usize) -> *mut u8 {
     (GLOBAL_ALLOCATOR.alloc)(len as u64)
 }
@@ -133,6 +391,8 @@ pub fn dealloc(ptr: *mut u8) {
 
 #[cfg(test)]
 mod tests {
+    use env;
+    use std::thread;
     use token::Token;
 
     #[test]
@@ -150,4 +410,69 @@ mod tests {
         assert_eq!(token.to_string(), "dd31817d-1501-4b2b-bcf6-d02e148d3ab9");
         assert_eq!(Token::from(1000).to_string(), "");
     }
+
+    #[test]
+    fn concurrent() {
+        let handles = (0..8)
+            .map(|_| {
+                thread::spawn(|| {
+                    (0..256)
+                        .map(|i| Token::from(format!("0b6f1d3e-concurrent-{}", i)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        let results = handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .collect::<Vec<_>>();
+        for tokens in &results {
+            assert_eq!(tokens, &results[0]);
+        }
+        for (i, token) in results[0].iter().enumerate() {
+            assert_eq!(token.to_string(), format!("0b6f1d3e-concurrent-{}", i));
+        }
+    }
+
+    #[test]
+    fn tokens() {
+        let tokens = env::tokens(&["eth", "", "5c0e8a4b-bulk", "eth"]);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::from("eth"),
+                Token::null(),
+                Token::from("5c0e8a4b-bulk"),
+                Token::from("eth"),
+            ]
+        );
+        assert!(env::tokens(&[]).is_empty());
+    }
+
+    #[test]
+    fn descendants() {
+        let flags = Token::from("d1a7e0c2.flags");
+        let syn = Token::from("d1a7e0c2.flags.syn");
+        let ack = Token::from("d1a7e0c2.flags.ack");
+        let src = Token::from("d1a7e0c2.src");
+        assert_eq!(Token::descendants("d1a7e0c2.flags"), vec![ack, syn]);
+        assert_eq!(Token::descendants("d1a7e0c2"), vec![flags, ack, syn, src]);
+        assert_eq!(Token::descendants("d1a7e0c2.src"), vec![]);
+        assert_eq!(Token::descendants("d1a7e0c2.unknown"), vec![]);
+        assert_eq!(
+            Token::from("d1a7e0c2.flags.syn").namespace(),
+            "d1a7e0c2.flags"
+        );
+        assert_eq!(Token::from("d1a7e0c2").namespace(), "");
+    }
+
+    #[test]
+    fn lookup() {
+        assert_eq!(Token::lookup(""), None);
+        assert_eq!(Token::lookup("4fb2e6a1-lookup-unregistered"), None);
+        let token = Token::from("4fb2e6a1-lookup-registered");
+        assert_eq!(Token::lookup("4fb2e6a1-lookup-registered"), Some(token));
+        assert!(Token::all().any(|t| t == token));
+        assert!(Token::all().all(|t| t != Token::null()));
+    }
 }
@@ -1,5 +1,13 @@
-use std::{error, fmt, str};
+use result;
+use std::{
+    any::Any,
+    error, fmt, io,
+    ops::Range,
+    panic::{self, AssertUnwindSafe},
+    str,
+};
 use string::SafeString;
+use token::Token;
 
 /// An error object.
 #[repr(C)]
@@ -19,13 +27,13 @@ impl Error {
 
 impl fmt::Debug for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Error: {}", error::Error::description(self))
+        write!(f, "Error: {}", &*self.desc)
     }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", error::Error::description(self))
+        write!(f, "{}", &*self.desc)
     }
 }
 
@@ -35,15 +43,198 @@ impl error::Error for Error {
     }
 }
 
+/// A list specifying categories of decoding errors.
+///
+/// The kind can be attached to an `io::Error` as the inner error,
+/// from which `DecodeError` recovers it.
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ErrorKind {
+    /// The input packet is malformed.
+    Malformed = 1,
+    /// The input packet is shorter than expected.
+    OutOfBounds = 2,
+    /// The decoder itself is broken.
+    Internal = 3,
+    /// Any other error.
+    Other = 0,
+}
+
+impl ErrorKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            ErrorKind::Malformed => "malformed",
+            ErrorKind::OutOfBounds => "out of bounds",
+            ErrorKind::Internal => "internal error",
+            ErrorKind::Other => "other error",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl error::Error for ErrorKind {
+    fn description(&self) -> &str {
+        self.as_str()
+    }
+}
+
+/// A structured decoding error.
+#[repr(C)]
+#[derive(Clone, PartialEq)]
+pub struct DecodeError {
+    kind: ErrorKind,
+    start: u64,
+    end: u64,
+    source: Token,
+    desc: SafeString,
+}
+
+impl DecodeError {
+    /// Creates a new DecodeError.
+    pub fn new(kind: ErrorKind, desc: &str) -> DecodeError {
+        Self {
+            kind,
+            start: 0,
+            end: 0,
+            source: Token::null(),
+            desc: SafeString::from(desc),
+        }
+    }
+
+    /// Sets the offending byte range.
+    pub fn with_range(mut self, range: Range<usize>) -> DecodeError {
+        self.start = range.start as u64;
+        self.end = range.end as u64;
+        self
+    }
+
+    /// Sets the token of the layer or attribute which caused the error.
+    pub fn with_source<T: Into<Token>>(mut self, source: T) -> DecodeError {
+        self.source = source.into();
+        self
+    }
+
+    /// Returns the kind of self.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Returns the offending byte range of self.
+    pub fn range(&self) -> Range<usize> {
+        self.start as usize..self.end as usize
+    }
+
+    /// Returns the source token of self.
+    pub fn source(&self) -> Token {
+        self.source
+    }
+}
+
+impl<'a> From<&'a (error::Error + 'static)> for DecodeError {
+    fn from(err: &'a (error::Error + 'static)) -> DecodeError {
+        if let Some(err) = err.downcast_ref::<DecodeError>() {
+            return err.clone();
+        }
+        let io = err.downcast_ref::<io::Error>();
+        let payload = io
+            .and_then(|e| e.get_ref())
+            .and_then(|e| e.downcast_ref::<ErrorKind>());
+        let kind = match (payload, io.map(|e| e.kind())) {
+            (Some(kind), _) => *kind,
+            (None, Some(io::ErrorKind::InvalidData)) => ErrorKind::Malformed,
+            (None, Some(io::ErrorKind::UnexpectedEof)) => ErrorKind::OutOfBounds,
+            _ => ErrorKind::Other,
+        };
+        DecodeError::new(kind, &err.to_string())
+    }
+}
+
+impl fmt::Debug for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DecodeError({:?}): {}", self.kind, &*self.desc)
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", &*self.desc)
+    }
+}
+
+impl error::Error for DecodeError {
+    fn description(&self) -> &str {
+        &self.desc
+    }
+}
+
+/// Returns the error of accessing out of the bounds of a slice.
+pub(crate) fn out_of_bounds() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, ErrorKind::OutOfBounds)
+}
+
+/// Returns a description of the panic payload.
+pub(crate) fn panic_description(payload: &(Any + Send)) -> String {
+    let message = if let Some(string) = payload.downcast_ref::<String>() {
+        string.as_str()
+    } else if let Some(string) = payload.downcast_ref::<&str>() {
+        string
+    } else {
+        "unknown error"
+    };
+    format!("plugin panicked: {}", message)
+}
+
+/// Calls the function and converts a panic into an error.
+pub(crate) fn catch_unwind<T, F: FnOnce() -> result::Result<T>>(func: F) -> result::Result<T> {
+    panic::catch_unwind(AssertUnwindSafe(func)).unwrap_or_else(|payload| {
+        let err: Box<error::Error> = Box::new(Error::new(&panic_description(payload.as_ref())));
+        Err(err)
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::error;
 
+    use slice::{ByteSlice, TryGet};
+
     #[test]
     fn description() {
         let msg = "out of bounds";
         let err = Error::new(msg);
-        assert_eq!(error::Error::description(&err), msg);
+        assert_eq!(err.to_string(), msg);
+    }
+
+    #[test]
+    fn decode_error() {
+        let err: Box<error::Error> = Box::new(
+            DecodeError::new(ErrorKind::Malformed, "bad header")
+                .with_range(2..4)
+                .with_source("tcp"),
+        );
+        let err = DecodeError::from(err.as_ref());
+        assert_eq!(err.kind(), ErrorKind::Malformed);
+        assert_eq!(err.range(), 2..4);
+        assert_eq!(err.source(), Token::from("tcp"));
+        assert_eq!(err.to_string(), "bad header");
+
+        let err: Box<error::Error> = Box::new(ByteSlice::new().try_get(0..1).unwrap_err());
+        let err = DecodeError::from(err.as_ref());
+        assert_eq!(err.kind(), ErrorKind::OutOfBounds);
+        assert_eq!(err.to_string(), "out of bounds");
+
+        let err: Box<error::Error> =
+            Box::new(io::Error::new(io::ErrorKind::Other, "out of bounds"));
+        assert_eq!(DecodeError::from(err.as_ref()).kind(), ErrorKind::Other);
+
+        let err: Box<error::Error> =
+            Box::new(io::Error::new(io::ErrorKind::Other, ErrorKind::Malformed));
+        assert_eq!(DecodeError::from(err.as_ref()).kind(), ErrorKind::Malformed);
     }
 }
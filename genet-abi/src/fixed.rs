@@ -1,11 +1,24 @@
 //! Fixed-lifetime shareable containers.
 
+use env;
 use std::{
-    fmt,
+    fmt, mem,
     ops::{Deref, DerefMut},
     ptr::NonNull,
 };
 
+/// Moves the value to the spill pool of the host if it exceeds the memory limit,
+/// or to the heap otherwise.
+fn into_raw<T>(data: T) -> *mut T {
+    match env::spill_value(&data) {
+        Some(ptr) => {
+            mem::forget(data);
+            ptr
+        }
+        None => Box::into_raw(Box::new(data)),
+    }
+}
+
 /// A fixed memory location.
 #[repr(C)]
 #[derive(Copy)]
@@ -34,7 +47,7 @@ impl<T> Fixed<T> {
     /// Creates a new Fixed containing the given value.
     pub fn new(data: T) -> Fixed<T> {
         Self {
-            ptr: unsafe { NonNull::new_unchecked(Box::into_raw(Box::new(data))) },
+            ptr: unsafe { NonNull::new_unchecked(into_raw(data)) },
         }
     }
 
@@ -91,7 +104,7 @@ impl<T> MutFixed<T> {
     /// Creates a new MutFixed containing the given value.
     pub fn new(data: T) -> MutFixed<T> {
         Self {
-            ptr: unsafe { NonNull::new_unchecked(Box::into_raw(Box::new(data))) },
+            ptr: unsafe { NonNull::new_unchecked(into_raw(data)) },
         }
     }
 
@@ -1,24 +1,60 @@
 use attr::Attr;
 use fixed::{Fixed, MutFixed};
+use fnv::FnvHashMap;
 use metadata::Metadata;
-use slice::ByteSlice;
+use slice::{ByteChain, ByteSlice};
 use std::{
     fmt,
     marker::PhantomData,
-    ops::{Deref, DerefMut},
-    slice,
+    ops::{Deref, DerefMut, Range},
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+    sync::Mutex,
 };
+use string::SafeString;
 use token::Token;
+use variant::Variant;
 
 /// A layer stack object.
+///
+/// The layers are stored in breadth-first order. If the stack has
+/// the tree indices, which are the numbers of the children of the layers,
+/// the parent and sibling layers can be traversed.
 pub struct LayerStack<'a> {
     buffer: &'a [*const Layer],
+    indices: &'a [u8],
 }
 
 impl<'a> LayerStack<'a> {
     pub(crate) unsafe fn new(ptr: *const *const Layer, len: usize) -> LayerStack<'a> {
+        Self::with_indices(ptr, len, ptr::null(), 0)
+    }
+
+    pub(crate) unsafe fn with_indices(
+        ptr: *const *const Layer,
+        len: usize,
+        indices: *const u8,
+        indices_len: usize,
+    ) -> LayerStack<'a> {
         Self {
             buffer: slice::from_raw_parts(ptr, len),
+            indices: if indices.is_null() {
+                &[]
+            } else {
+                slice::from_raw_parts(indices, indices_len)
+            },
+        }
+    }
+
+    /// Creates a LayerStack from the layers in breadth-first order and the tree indices.
+    pub fn from_layers(layers: &'a [MutFixed<Layer>], indices: &'a [u8]) -> LayerStack<'a> {
+        unsafe {
+            Self::with_indices(
+                layers.as_ptr() as *const *const Layer,
+                layers.len(),
+                indices.as_ptr(),
+                indices.len(),
+            )
         }
     }
 
@@ -47,9 +83,85 @@ impl<'a> LayerStack<'a> {
         self.layers().find(|layer| layer.id() == id)
     }
 
-    fn layers(&self) -> impl DoubleEndedIterator<Item = &'a Layer> {
+    /// Returns the parent of the layer.
+    pub fn parent(&self, layer: &Layer) -> Option<&'a Layer> {
+        self.index_of(layer)
+            .and_then(|index| self.parent_index(index))
+            .map(|index| self.get(index))
+    }
+
+    /// Returns the ancestors of the layer, from the parent to the root.
+    pub fn ancestors(&self, layer: &Layer) -> impl Iterator<Item = &'a Layer> {
+        let mut ancestors = Vec::new();
+        let mut index = self.index_of(layer);
+        while let Some(parent) = index.and_then(|index| self.parent_index(index)) {
+            ancestors.push(self.get(parent));
+            index = Some(parent);
+        }
+        ancestors.into_iter()
+    }
+
+    /// Returns the children of the layer.
+    pub fn children(&self, layer: &Layer) -> impl Iterator<Item = &'a Layer> {
+        let range = self
+            .index_of(layer)
+            .and_then(|index| self.children_range(index))
+            .unwrap_or(0..0);
+        let buffer = self.buffer;
+        range.map(move |index| unsafe { &*buffer[index] })
+    }
+
+    /// Returns the siblings of the layer, excluding the layer itself.
+    pub fn siblings(&self, layer: &Layer) -> impl Iterator<Item = &'a Layer> {
+        let ptr = layer as *const Layer;
+        let range = self
+            .index_of(layer)
+            .and_then(|index| self.parent_index(index))
+            .and_then(|parent| self.children_range(parent))
+            .unwrap_or(0..0);
+        let buffer = self.buffer;
+        range
+            .filter(move |index| buffer[*index] != ptr)
+            .map(move |index| unsafe { &*buffer[index] })
+    }
+
+    /// Returns an iterator over the layers.
+    pub fn layers(&self) -> impl DoubleEndedIterator<Item = &'a Layer> {
         self.buffer.iter().map(|layer| unsafe { &**layer })
     }
+
+    fn get(&self, index: usize) -> &'a Layer {
+        unsafe { &*self.buffer[index] }
+    }
+
+    fn index_of(&self, layer: &Layer) -> Option<usize> {
+        let ptr = layer as *const Layer;
+        self.buffer.iter().position(|layer| *layer == ptr)
+    }
+
+    fn parent_index(&self, index: usize) -> Option<usize> {
+        if index == 0 {
+            return None;
+        }
+        let mut start = 1;
+        for (parent, len) in self.indices.iter().enumerate() {
+            let end = start + *len as usize;
+            if index < end {
+                return Some(parent);
+            }
+            start = end;
+        }
+        None
+    }
+
+    fn children_range(&self, index: usize) -> Option<Range<usize>> {
+        let len = *self.indices.get(index)? as usize;
+        let start = 1 + self.indices[..index]
+            .iter()
+            .map(|len| *len as usize)
+            .sum::<usize>();
+        Some(start..(start + len).min(self.buffer.len()))
+    }
 }
 
 /// A mutable proxy for a layer object.
@@ -100,6 +212,11 @@ impl<'a> Parent<'a> {
         self.deref().attr(id)
     }
 
+    /// Find the `index`-th occurrence of the attribute in the Layer.
+    pub fn attr_at<T: Into<Token>>(&self, id: T, index: usize) -> Option<&Attr> {
+        self.deref().attr_at(id, index)
+    }
+
     /// Adds an attribute to the Layer.
     pub fn add_attr<T: Into<Fixed<Attr>>>(&mut self, attr: T) {
         self.deref_mut().add_attr(attr);
@@ -110,11 +227,36 @@ impl<'a> Parent<'a> {
         self.deref().payloads()
     }
 
+    /// Returns the payloads with the given tag.
+    pub fn payloads_by_tag<T: Into<Token>>(&self, tag: T) -> impl Iterator<Item = &Payload> {
+        self.deref().payloads_by_tag(tag)
+    }
+
     /// Adds a payload to the Layer.
     pub fn add_payload(&mut self, payload: Payload) {
         self.deref_mut().add_payload(payload);
     }
 
+    /// Returns the slice of annotations.
+    pub fn annotations(&self) -> &[Annotation] {
+        self.deref().annotations()
+    }
+
+    /// Adds an annotation to the Layer.
+    pub fn annotate(&mut self, annotation: Annotation) {
+        self.deref_mut().annotate(annotation);
+    }
+
+    /// Returns the slice of links.
+    pub fn links(&self) -> &[Link] {
+        self.deref().links()
+    }
+
+    /// Adds a link to the Layer.
+    pub fn add_link(&mut self, link: Link) {
+        self.deref_mut().add_link(link);
+    }
+
     pub fn add_child<T: Into<MutFixed<Layer>>>(&mut self, layer: T) {
         (self.add_child)(self, layer.into().as_mut_ptr());
     }
@@ -159,6 +301,9 @@ pub struct Layer {
     data: ByteSlice,
     attrs: Vec<Fixed<Attr>>,
     payloads: Vec<Payload>,
+    annotations: Vec<Annotation>,
+    links: Vec<Link>,
+    values: Mutex<FnvHashMap<*const Attr, Variant>>,
 }
 
 unsafe impl Send for Layer {}
@@ -171,6 +316,9 @@ impl Layer {
             data: data.into(),
             attrs: Vec::new(),
             payloads: Vec::new(),
+            annotations: Vec::new(),
+            links: Vec::new(),
+            values: Mutex::new(FnvHashMap::default()),
         }
     }
 
@@ -194,8 +342,23 @@ impl Layer {
         self.class.attrs(self)
     }
 
+    /// Returns the attribute aliases as pairs of the alias and the target.
+    pub fn aliases<'a>(&'a self) -> impl Iterator<Item = (Token, Token)> + 'a {
+        self.class.aliases().map(|alias| (alias.id, alias.target))
+    }
+
     /// Find the attribute in the Layer.
     pub fn attr<T: Into<Token>>(&self, id: T) -> Option<&Attr> {
+        self.attrs_by_id(id).next()
+    }
+
+    /// Find the `index`-th occurrence of the attribute in the Layer.
+    pub fn attr_at<T: Into<Token>>(&self, id: T, index: usize) -> Option<&Attr> {
+        self.attrs_by_id(id).nth(index)
+    }
+
+    /// Returns an iterator over all occurrences of the attribute in the Layer.
+    pub fn attrs_by_id<T: Into<Token>>(&self, id: T) -> impl Iterator<Item = &Attr> {
         let id = id.into();
         let id = self
             .class
@@ -206,7 +369,7 @@ impl Layer {
         self.attrs()
             .iter()
             .chain(self.class.headers().iter())
-            .find(|attr| attr.id() == id)
+            .filter(move |attr| attr.id() == id)
             .map(|attr| attr.as_ref())
     }
 
@@ -221,11 +384,102 @@ impl Layer {
         self.class.payloads(self)
     }
 
+    /// Returns the payloads with the given tag.
+    pub fn payloads_by_tag<T: Into<Token>>(&self, tag: T) -> impl Iterator<Item = &Payload> {
+        let tag = tag.into();
+        self.payloads().iter().filter(move |p| p.tag() == tag)
+    }
+
     /// Adds a payload to the Layer.
     pub fn add_payload(&mut self, payload: Payload) {
         let func = self.class.add_payload;
         (func)(self, payload);
     }
+
+    /// Returns the slice of annotations.
+    pub fn annotations(&self) -> &[Annotation] {
+        self.class.annotations(self)
+    }
+
+    /// Adds an annotation to the Layer.
+    pub fn annotate(&mut self, annotation: Annotation) {
+        let func = self.class.add_annotation;
+        (func)(self, annotation);
+    }
+
+    /// Returns the slice of links.
+    pub fn links(&self) -> &[Link] {
+        self.class.links(self)
+    }
+
+    /// Adds a link to the Layer.
+    pub fn add_link(&mut self, link: Link) {
+        let func = self.class.add_link;
+        (func)(self, link);
+    }
+
+    /// Returns the one-line summary of the Layer in the stack,
+    /// e.g. `GET /index.html HTTP/1.1`.
+    pub fn summary(&self, stack: &LayerStack) -> Option<String> {
+        let mut out = SafeString::new();
+        let func = self.class.summary;
+        let result = (func)(
+            self,
+            stack.buffer.as_ptr(),
+            stack.buffer.len() as u64,
+            stack.indices.as_ptr(),
+            stack.indices.len() as u64,
+            &mut out,
+        );
+        if result == 1 {
+            Some(out.as_str().to_string())
+        } else {
+            None
+        }
+    }
+
+    /// Returns the value of the lazy attribute computed for self.
+    pub(crate) fn cached_value(&self, attr: &Attr) -> Option<Variant> {
+        let values = self.values.lock().ok()?;
+        values.get(&(attr as *const Attr)).cloned()
+    }
+
+    /// Caches the value of the lazy attribute computed for self.
+    pub(crate) fn cache_value(&self, attr: &Attr, value: Variant) {
+        if let Ok(mut values) = self.values.lock() {
+            values.insert(attr, value);
+        }
+    }
+
+    /// Returns a copy of the Layer with the same class, data and attributes.
+    ///
+    /// The payloads, annotations and links are not copied.
+    pub fn shallow_copy(&self) -> Layer {
+        Layer {
+            class: self.class.clone(),
+            data: self.data,
+            attrs: self.attrs.clone(),
+            payloads: Vec::new(),
+            annotations: Vec::new(),
+            links: Vec::new(),
+            values: Mutex::new(FnvHashMap::default()),
+        }
+    }
+
+    /// Adds a note annotation to the Layer.
+    pub fn note(&mut self, message: &str) {
+        self.annotate(Annotation::new(Severity::Note, message));
+    }
+
+    /// Adds a warning annotation to the Layer.
+    pub fn warn(&mut self, message: &str) {
+        self.annotate(Annotation::new(Severity::Warn, message));
+    }
+
+    /// Adds an error annotation to the Layer.
+    pub fn error(&mut self, message: &str) {
+        self.annotate(Annotation::new(Severity::Error, message));
+    }
 }
 
 impl fmt::Debug for Layer {
@@ -245,8 +499,11 @@ impl Into<MutFixed<Layer>> for Layer {
 pub struct Payload {
     data: *const u8,
     len: u64,
+    offset: u64,
+    chain: *const ByteChain,
     id: Token,
     typ: Token,
+    tag: Token,
 }
 
 impl Payload {
@@ -265,11 +522,33 @@ impl Payload {
         Self {
             data: data.as_ptr(),
             len: data.len() as u64,
+            offset: data.offset() as u64,
+            chain: ptr::null(),
             id: id.into(),
             typ: typ.into(),
+            tag: Token::null(),
         }
     }
 
+    /// Creates a new payload from the non-contiguous data.
+    ///
+    /// The segments are not copied until `data()` is called.
+    pub fn chained<T: Into<Token>>(chain: ByteChain, id: T) -> Payload {
+        let mut payload = Self::new(ByteSlice::new(), id);
+        payload.len = chain.len() as u64;
+        payload.chain = Fixed::new(chain).as_ptr();
+        payload
+    }
+
+    /// Sets a tag of self.
+    ///
+    /// A tag distinguishes payload regions of the same layer,
+    /// e.g. `encrypted`, `fragment` or `padding`.
+    pub fn tagged<T: Into<Token>>(mut self, tag: T) -> Payload {
+        self.tag = tag.into();
+        self
+    }
+
     /// Returns the ID of self.
     pub fn id(&self) -> Token {
         self.id
@@ -280,9 +559,147 @@ impl Payload {
         self.typ
     }
 
+    /// Returns the tag of self.
+    pub fn tag(&self) -> Token {
+        self.tag
+    }
+
     /// Returns the data of self.
+    ///
+    /// The chained payload is copied into a contiguous buffer
+    /// unless it consists of a single segment.
     pub fn data(&self) -> ByteSlice {
-        unsafe { ByteSlice::from_raw_parts(self.data, self.len as usize) }
+        if !self.chain.is_null() {
+            return unsafe { (*self.chain).to_slice() };
+        }
+        unsafe {
+            ByteSlice::from_raw_parts_with_offset(
+                self.data,
+                self.len as usize,
+                self.offset as usize,
+            )
+        }
+    }
+
+    /// Returns the data of self as a ByteChain without copying.
+    pub fn chain(&self) -> ByteChain {
+        if self.chain.is_null() {
+            ByteChain::from(self.data())
+        } else {
+            unsafe { (*self.chain).clone() }
+        }
+    }
+
+    /// Returns the length of the data.
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Returns true if the data has a length of zero.
+    ///
+    /// Returns false otherwise.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Severity of an annotation.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Note = 1,
+    Warn = 2,
+    Error = 3,
+}
+
+/// A diagnostic annotation of a layer.
+///
+/// Annotations flag conditions such as malformed lengths or bad checksums
+/// without failing the whole decode.
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct Annotation {
+    severity: Severity,
+    attr: Token,
+    message: SafeString,
+}
+
+impl Annotation {
+    /// Creates a new annotation.
+    pub fn new(severity: Severity, message: &str) -> Annotation {
+        Annotation {
+            severity,
+            attr: Token::null(),
+            message: SafeString::from(message),
+        }
+    }
+
+    /// Sets the attribute which the annotation refers to.
+    pub fn with_attr<T: Into<Token>>(mut self, attr: T) -> Annotation {
+        self.attr = attr.into();
+        self
+    }
+
+    /// Returns the severity of self.
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// Returns the ID of the attribute which self refers to.
+    pub fn attr(&self) -> Token {
+        self.attr
+    }
+
+    /// Returns the message of self.
+    pub fn message(&self) -> &str {
+        self.message.as_str()
+    }
+}
+
+/// A link to another frame.
+///
+/// A link associates a layer with a layer in another frame,
+/// e.g. a DNS response with its query. The link is visible as an attribute
+/// whose value is the index of the linked frame.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Link {
+    id: Token,
+    frame: u32,
+    reverse: Token,
+}
+
+impl Link {
+    /// Creates a new link to the frame.
+    pub fn new<T: Into<Token>>(id: T, frame: u32) -> Link {
+        Link {
+            id: id.into(),
+            frame,
+            reverse: Token::null(),
+        }
+    }
+
+    /// Sets the ID of the reverse link.
+    ///
+    /// The linked frame will have the reverse link to the frame of the layer.
+    pub fn with_reverse<T: Into<Token>>(mut self, id: T) -> Link {
+        self.reverse = id.into();
+        self
+    }
+
+    /// Returns the ID of self.
+    pub fn id(&self) -> Token {
+        self.id
+    }
+
+    /// Returns the index of the linked frame.
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// Returns the ID of the reverse link.
+    pub fn reverse(&self) -> Token {
+        self.reverse
     }
 }
 
@@ -292,6 +709,7 @@ pub struct LayerClassBuilder {
     aliases: Vec<Alias>,
     headers: Vec<Fixed<Attr>>,
     meta: Metadata,
+    summarizer: Option<Summarizer>,
 }
 
 impl LayerClassBuilder {
@@ -310,6 +728,17 @@ impl LayerClassBuilder {
         self
     }
 
+    /// Adds header attributes for LayerClass.
+    pub fn headers<I, T>(mut self, attrs: I) -> LayerClassBuilder
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<Fixed<Attr>>,
+    {
+        self.headers
+            .extend(attrs.into_iter().map(|attr| attr.into()));
+        self
+    }
+
     /// Sets a name of LayerClass.
     pub fn name(mut self, name: &'static str) -> LayerClassBuilder {
         self.meta.set_name(name);
@@ -322,6 +751,18 @@ impl LayerClassBuilder {
         self
     }
 
+    /// Sets a function to generate the one-line summary of the layers.
+    ///
+    /// The stack contains the whole frame, so the summary may include
+    /// the attributes of the parent layers such as the ports.
+    pub fn summary<F>(mut self, func: F) -> LayerClassBuilder
+    where
+        F: 'static + Fn(&Layer, &LayerStack) -> Option<String> + Send + Sync,
+    {
+        self.summarizer = Some(Box::new(func));
+        self
+    }
+
     /// Builds a new LayerClass.
     pub fn build(self) -> LayerClass {
         LayerClass {
@@ -337,14 +778,24 @@ impl LayerClassBuilder {
             payloads_len: abi_payloads_len,
             payloads_data: abi_payloads_data,
             add_payload: abi_add_payload,
+            annotations_len: abi_annotations_len,
+            annotations_data: abi_annotations_data,
+            add_annotation: abi_add_annotation,
+            links_len: abi_links_len,
+            links_data: abi_links_data,
+            add_link: abi_add_link,
+            summary: abi_summary,
             id: self.id,
             meta: self.meta,
             aliases: self.aliases,
             headers: self.headers,
+            summarizer: self.summarizer,
         }
     }
 }
 
+type Summarizer = Box<Fn(&Layer, &LayerStack) -> Option<String> + Send + Sync>;
+
 #[repr(C)]
 struct Alias {
     id: Token,
@@ -359,17 +810,32 @@ pub struct LayerClass {
     aliases_data: extern "C" fn(*const LayerClass) -> *const Alias,
     headers_len: extern "C" fn(*const LayerClass) -> u64,
     headers_data: extern "C" fn(*const LayerClass) -> *const Fixed<Attr>,
-    data: extern "C" fn(*const Layer, *mut u64) -> *const u8,
+    data: extern "C" fn(*const Layer, *mut u64, *mut u64) -> *const u8,
     attrs_len: extern "C" fn(*const Layer) -> u64,
     attrs_data: extern "C" fn(*const Layer) -> *const Fixed<Attr>,
     add_attr: extern "C" fn(*mut Layer, Fixed<Attr>),
     payloads_len: extern "C" fn(*const Layer) -> u64,
     payloads_data: extern "C" fn(*const Layer) -> *const Payload,
     add_payload: extern "C" fn(*mut Layer, Payload),
+    annotations_len: extern "C" fn(*const Layer) -> u64,
+    annotations_data: extern "C" fn(*const Layer) -> *const Annotation,
+    add_annotation: extern "C" fn(*mut Layer, Annotation),
+    links_len: extern "C" fn(*const Layer) -> u64,
+    links_data: extern "C" fn(*const Layer) -> *const Link,
+    add_link: extern "C" fn(*mut Layer, Link),
+    summary: extern "C" fn(
+        *const Layer,
+        *const *const Layer,
+        u64,
+        *const u8,
+        u64,
+        *mut SafeString,
+    ) -> u8,
     id: Token,
     meta: Metadata,
     aliases: Vec<Alias>,
     headers: Vec<Fixed<Attr>>,
+    summarizer: Option<Summarizer>,
 }
 
 impl LayerClass {
@@ -380,6 +846,7 @@ impl LayerClass {
             meta: Metadata::new(),
             aliases: Vec::new(),
             headers: Vec::new(),
+            summarizer: None,
         }
     }
 
@@ -402,8 +869,9 @@ impl LayerClass {
 
     fn data(&self, layer: &Layer) -> ByteSlice {
         let mut len = 0;
-        let data = (self.data)(layer, &mut len);
-        unsafe { ByteSlice::from_raw_parts(data, len as usize) }
+        let mut offset = 0;
+        let data = (self.data)(layer, &mut len, &mut offset);
+        unsafe { ByteSlice::from_raw_parts_with_offset(data, len as usize, offset as usize) }
     }
 
     fn attrs(&self, layer: &Layer) -> &[Fixed<Attr>] {
@@ -417,6 +885,18 @@ impl LayerClass {
         let len = (self.payloads_len)(layer) as usize;
         unsafe { slice::from_raw_parts(data, len) }
     }
+
+    fn annotations(&self, layer: &Layer) -> &[Annotation] {
+        let data = (self.annotations_data)(layer);
+        let len = (self.annotations_len)(layer) as usize;
+        unsafe { slice::from_raw_parts(data, len) }
+    }
+
+    fn links(&self, layer: &Layer) -> &[Link] {
+        let data = (self.links_data)(layer);
+        let len = (self.links_len)(layer) as usize;
+        unsafe { slice::from_raw_parts(data, len) }
+    }
 }
 
 impl Into<Fixed<LayerClass>> for &'static LayerClass {
@@ -445,10 +925,11 @@ extern "C" fn abi_headers_data(class: *const LayerClass) -> *const Fixed<Attr> {
     unsafe { (*class).headers.as_ptr() }
 }
 
-extern "C" fn abi_data(layer: *const Layer, len: *mut u64) -> *const u8 {
+extern "C" fn abi_data(layer: *const Layer, len: *mut u64, offset: *mut u64) -> *const u8 {
     unsafe {
         let data = &(*layer).data;
         *len = data.len() as u64;
+        *offset = data.offset() as u64;
         data.as_ptr()
     }
 }
@@ -479,13 +960,63 @@ extern "C" fn abi_add_payload(layer: *mut Layer, payload: Payload) {
     payloads.push(payload);
 }
 
+extern "C" fn abi_annotations_len(layer: *const Layer) -> u64 {
+    unsafe { (*layer).annotations.len() as u64 }
+}
+
+extern "C" fn abi_annotations_data(layer: *const Layer) -> *const Annotation {
+    unsafe { (*layer).annotations.as_ptr() }
+}
+
+extern "C" fn abi_add_annotation(layer: *mut Layer, annotation: Annotation) {
+    let annotations = unsafe { &mut (*layer).annotations };
+    annotations.push(annotation);
+}
+
+extern "C" fn abi_links_len(layer: *const Layer) -> u64 {
+    unsafe { (*layer).links.len() as u64 }
+}
+
+extern "C" fn abi_links_data(layer: *const Layer) -> *const Link {
+    unsafe { (*layer).links.as_ptr() }
+}
+
+extern "C" fn abi_add_link(layer: *mut Layer, link: Link) {
+    let links = unsafe { &mut (*layer).links };
+    links.push(link);
+}
+
+extern "C" fn abi_summary(
+    layer: *const Layer,
+    layers: *const *const Layer,
+    len: u64,
+    indices: *const u8,
+    indices_len: u64,
+    out: *mut SafeString,
+) -> u8 {
+    let layer = unsafe { &*layer };
+    let summarizer = match &layer.class.summarizer {
+        Some(func) => func,
+        None => return 0,
+    };
+    let stack =
+        unsafe { LayerStack::with_indices(layers, len as usize, indices, indices_len as usize) };
+    match panic::catch_unwind(AssertUnwindSafe(|| summarizer(layer, &stack))) {
+        Ok(Some(s)) => {
+            unsafe { ptr::write(out, SafeString::from(&s)) };
+            1
+        }
+        _ => 0,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use attr::{Attr, AttrClass};
     use cast::Cast;
-    use fixed::Fixed;
-    use layer::{Layer, LayerClass, Payload};
-    use slice::ByteSlice;
+    use fixed::{Fixed, MutFixed};
+    use layer::{Annotation, Layer, LayerClass, LayerStack, Link, Payload, Severity};
+    use slice::{ByteChain, ByteSlice, TryGet};
     use std::io::Result;
     use token::Token;
     use variant::Variant;
@@ -506,6 +1037,31 @@ mod tests {
         assert_eq!(layer.data(), ByteSlice::from(&data[..]));
     }
 
+    #[test]
+    fn repeated_attrs() {
+        let class = Fixed::new(LayerClass::builder(Token::null()).build());
+        let mut layer = Layer::new(class, ByteSlice::new());
+        let opt = Fixed::new(AttrClass::builder("tcp.options.kind").repeated().build());
+        let other = Fixed::new(AttrClass::builder("tcp.dst").build());
+        for i in 0..3u8 {
+            layer.add_attr(Attr::builder(opt.clone()).value(i).build());
+        }
+        layer.add_attr(Attr::builder(other).value(80u16).build());
+
+        assert!(layer.attr("tcp.options.kind").unwrap().is_repeated());
+        assert!(!layer.attr("tcp.dst").unwrap().is_repeated());
+        assert_eq!(layer.attrs_by_id("tcp.options.kind").count(), 3);
+        assert_eq!(
+            layer
+                .attr_at("tcp.options.kind", 2)
+                .unwrap()
+                .try_get(&layer)
+                .unwrap(),
+            Variant::UInt64(2)
+        );
+        assert!(layer.attr_at("tcp.options.kind", 3).is_none());
+    }
+
     #[test]
     fn payloads() {
         let class = Fixed::new(LayerClass::builder(Token::null()).build());
@@ -528,6 +1084,161 @@ mod tests {
         assert!(iter.next().is_none());
     }
 
+    #[test]
+    fn tagged_payloads() {
+        let class = Fixed::new(LayerClass::builder(Token::null()).build());
+        let mut layer = Layer::new(class, ByteSlice::new());
+        let data = b"hello";
+        layer.add_payload(Payload::new(ByteSlice::from(&data[..2]), "@data:tls"));
+        layer.add_payload(
+            Payload::new(ByteSlice::from(&data[2..4]), "@data:tls").tagged("encrypted"),
+        );
+        layer.add_payload(Payload::new(ByteSlice::from(&data[4..]), "@data:tls").tagged("padding"));
+
+        let mut iter = layer.payloads_by_tag("encrypted");
+        assert_eq!(iter.next().unwrap().data(), ByteSlice::from(&data[2..4]));
+        assert!(iter.next().is_none());
+        assert_eq!(layer.payloads_by_tag(Token::null()).count(), 1);
+        assert_eq!(layer.payloads()[2].tag(), Token::from("padding"));
+    }
+
+    #[test]
+    fn offset() {
+        let class = Fixed::new(LayerClass::builder(Token::null()).build());
+        let data = ByteSlice::from(&b"\x00\x01\x02\x03\x04\x05"[..]);
+        let mut layer = Layer::new(class.clone(), data.try_get(2..).unwrap());
+        assert_eq!(layer.data().offset(), 2);
+
+        let data = layer.data().try_get(1..3).unwrap();
+        layer.add_payload(Payload::new(data, "@data"));
+        let payload = layer.payloads()[0].data();
+        assert_eq!(payload.offset(), 3);
+        assert_eq!(Layer::new(class, payload).data().offset(), 3);
+    }
+
+    #[test]
+    fn chained_payload() {
+        let chain = vec![
+            ByteSlice::from(&b"\x00\x01"[..]),
+            ByteSlice::from(&b"\x02"[..]),
+        ]
+        .into_iter()
+        .collect::<ByteChain>();
+        let payload = Payload::chained(chain.clone(), "@stream:tcp");
+        assert_eq!(payload.len(), 3);
+        assert_eq!(payload.chain(), chain);
+        assert_eq!(payload.data(), ByteSlice::from(&b"\x00\x01\x02"[..]));
+
+        let payload = Payload::new(ByteSlice::from(&b"\x03"[..]), "@data");
+        assert_eq!(payload.chain().segments().len(), 1);
+    }
+
+    #[test]
+    fn annotations() {
+        let class = Fixed::new(LayerClass::builder(Token::null()).build());
+        let mut layer = Layer::new(class, ByteSlice::new());
+        assert!(layer.annotations().is_empty());
+
+        layer.note("retransmission");
+        layer.annotate(Annotation::new(Severity::Error, "bad checksum").with_attr("ipv4.checksum"));
+        let annotations = layer.annotations();
+        assert_eq!(annotations.len(), 2);
+        assert_eq!(annotations[0].severity(), Severity::Note);
+        assert_eq!(annotations[0].message(), "retransmission");
+        assert_eq!(annotations[1].severity(), Severity::Error);
+        assert_eq!(annotations[1].attr(), Token::from("ipv4.checksum"));
+    }
+
+    #[test]
+    fn shallow_copy() {
+        let class = Fixed::new(LayerClass::builder("eth").build());
+        let attr = Fixed::new(AttrClass::builder("eth.type").build());
+        let mut layer = Layer::new(class, ByteSlice::from(&b"\x08\x00"[..]));
+        layer.add_attr(Attr::builder(attr).range(0..2).build());
+        layer.warn("truncated");
+
+        let copy = layer.shallow_copy();
+        assert_eq!(copy.id(), Token::from("eth"));
+        assert_eq!(copy.data(), layer.data());
+        assert_eq!(copy.attrs().len(), 1);
+        assert!(copy.annotations().is_empty());
+    }
+
+    #[test]
+    fn links() {
+        let class = Fixed::new(LayerClass::builder(Token::null()).build());
+        let mut layer = Layer::new(class, ByteSlice::new());
+        assert!(layer.links().is_empty());
+
+        layer.add_link(Link::new("dns.response_to", 3).with_reverse("dns.response_in"));
+        let links = layer.links();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].id(), Token::from("dns.response_to"));
+        assert_eq!(links[0].frame(), 3);
+        assert_eq!(links[0].reverse(), Token::from("dns.response_in"));
+    }
+
+    #[test]
+    fn stack() {
+        let class = Fixed::new(LayerClass::builder(Token::null()).build());
+        let layers = (0..5)
+            .map(|_| Layer::new(class.clone(), ByteSlice::new()))
+            .collect::<Vec<_>>();
+        let ptrs = layers.iter().map(|l| l as *const Layer).collect::<Vec<_>>();
+
+        // 0 -+- 1 --- 3
+        //    +- 2 --- 4
+        let indices = [2, 1, 1, 0];
+        let stack = unsafe {
+            LayerStack::with_indices(ptrs.as_ptr(), ptrs.len(), indices.as_ptr(), indices.len())
+        };
+        let ptr = |layer: &Layer| layer as *const Layer;
+
+        assert!(stack.parent(&layers[0]).is_none());
+        assert_eq!(stack.parent(&layers[3]).map(ptr), Some(ptrs[1]));
+        assert_eq!(stack.parent(&layers[4]).map(ptr), Some(ptrs[2]));
+        assert_eq!(
+            stack.ancestors(&layers[4]).map(ptr).collect::<Vec<_>>(),
+            vec![ptrs[2], ptrs[0]]
+        );
+        assert_eq!(
+            stack.children(&layers[0]).map(ptr).collect::<Vec<_>>(),
+            vec![ptrs[1], ptrs[2]]
+        );
+        assert_eq!(
+            stack.siblings(&layers[1]).map(ptr).collect::<Vec<_>>(),
+            vec![ptrs[2]]
+        );
+        assert!(stack.siblings(&layers[3]).next().is_none());
+
+        let stack = unsafe { LayerStack::new(ptrs.as_ptr(), ptrs.len()) };
+        assert!(stack.parent(&layers[3]).is_none());
+    }
+
+    #[test]
+    fn summary() {
+        let parent = Fixed::new(LayerClass::builder("parent").build());
+        let child = Fixed::new(
+            LayerClass::builder("child")
+                .summary(|layer, stack| {
+                    let parent = stack.parent(layer)?;
+                    Some(format!("{} in {}", layer.id(), parent.id()))
+                })
+                .build(),
+        );
+        let layers = vec![
+            MutFixed::new(Layer::new(parent, ByteSlice::new())),
+            MutFixed::new(Layer::new(child, ByteSlice::new())),
+        ];
+        let indices = [1];
+        let stack = LayerStack::from_layers(&layers, &indices);
+        assert_eq!(layers[0].summary(&stack), None);
+        assert_eq!(
+            layers[1].summary(&stack),
+            Some("child in parent".to_string())
+        );
+    }
+
     #[test]
     fn attrs() {
         let class = Fixed::new(LayerClass::builder(Token::null()).build());
@@ -1,8 +1,22 @@
+//! ABI definitions shared by the genet kernel and the plugin libraries.
+//!
+//! The `#[repr(C)]` types can also be used from C, C++ or Zig through
+//! the header generated by the `header` feature:
+//!
+//! ```text
+//! GENET_ABI_HEADER_DIR=include cargo build -p genet-abi --features header
+//! ```
+//!
+//! The header declares the types with the `Genet` prefix, e.g. `GenetDecoderBox`
+//! and `GenetToken`. A library written in another language exports the same
+//! symbols as the SDK, such as `genet_abi_v1_get_decoders` and `genet_abi_v1_info`.
+
 extern crate bincode;
 extern crate fnv;
 extern crate libc;
 extern crate parking_lot;
 extern crate serde;
+extern crate serde_json;
 
 #[macro_use]
 extern crate serde_derive;
@@ -21,10 +35,13 @@ pub mod fixed;
 pub mod layer;
 pub mod metadata;
 pub mod reader;
+pub mod renderer;
 pub mod result;
 pub mod slice;
+pub mod spill;
 pub mod token;
 pub mod variant;
+pub mod version;
 pub mod writer;
 
 mod string;
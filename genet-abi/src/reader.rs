@@ -1,12 +1,16 @@
 use bincode;
 use context::Context;
-use error::Error;
+use error::{catch_unwind, Error};
 use file::FileType;
 use fixed::MutFixed;
 use layer::Layer;
 use result::Result;
 use serde::ser::{Serialize, Serializer};
-use std::{fmt, mem, ptr, slice, str};
+use std::{
+    fmt, mem,
+    panic::{self, AssertUnwindSafe},
+    ptr, slice, str,
+};
 use vec::SafeVec;
 
 /// Reader metadata.
@@ -78,8 +82,9 @@ impl ReaderBox {
         }
     }
 
+    /// Returns the metadata, or the default one if the reader panicked.
     pub fn metadata(&self) -> Metadata {
-        bincode::deserialize(&(self.metadata)(self)).unwrap()
+        bincode::deserialize(&(self.metadata)(self)).unwrap_or_default()
     }
 }
 
@@ -103,13 +108,13 @@ extern "C" fn abi_reader_new_worker(
     let reader = unsafe { &*reader };
     let ctx = unsafe { &*ctx };
     let arg = unsafe { str::from_utf8_unchecked(slice::from_raw_parts(arg, arg_len as usize)) };
-    match reader.new_worker(ctx, arg) {
+    match catch_unwind(|| reader.new_worker(ctx, arg)) {
         Ok(worker) => {
             unsafe { ptr::write(out, WorkerBox::new(worker)) };
             1
         }
         Err(e) => {
-            unsafe { *err = Error::new(e.description()) };
+            unsafe { *err = Error::new(&e.to_string()) };
             0
         }
     }
@@ -117,15 +122,29 @@ extern "C" fn abi_reader_new_worker(
 
 extern "C" fn abi_metadata(reader: *const ReaderBox) -> SafeVec<u8> {
     let reader = unsafe { &*((*reader).reader) };
-    bincode::serialize(&reader.metadata()).unwrap().into()
+    panic::catch_unwind(AssertUnwindSafe(|| bincode::serialize(&reader.metadata())))
+        .ok()
+        .and_then(|data| data.ok())
+        .unwrap_or_default()
+        .into()
 }
 
 /// Reader worker trait.
+///
+/// The kernel pulls the input in chunks. It stops calling `read`
+/// while the frames already read are waiting to be decoded,
+/// so a reader should not buffer the input ahead by itself.
 pub trait Worker: Send {
-    fn read(&mut self) -> Result<Vec<Layer>>;
+    /// Reads at most `max` layers.
+    ///
+    /// An empty vector means that no input is available for now,
+    /// and the worker will be polled again later.
+    /// Returns an error at the end of the input.
+    fn read(&mut self, max: usize) -> Result<Vec<Layer>>;
 }
 
-type ReaderFunc = extern "C" fn(*mut Box<Worker>, *mut SafeVec<MutFixed<Layer>>, *mut Error) -> u8;
+type ReaderFunc =
+    extern "C" fn(*mut Box<Worker>, u64, *mut SafeVec<MutFixed<Layer>>, *mut Error) -> u8;
 
 pub struct WorkerBox {
     worker: *mut Box<Worker>,
@@ -144,10 +163,10 @@ impl WorkerBox {
         }
     }
 
-    pub fn read(&mut self) -> Result<Vec<MutFixed<Layer>>> {
+    pub fn read(&mut self, max: usize) -> Result<Vec<MutFixed<Layer>>> {
         let mut v = SafeVec::new();
         let mut e = Error::new("");
-        if (self.read)(self.worker, &mut v, &mut e) == 0 {
+        if (self.read)(self.worker, max as u64, &mut v, &mut e) == 0 {
             Err(Box::new(e))
         } else {
             Ok(v.into_iter().collect())
@@ -173,11 +192,12 @@ extern "C" fn abi_reader_worker_drop(worker: *mut Box<Worker>) {
 
 extern "C" fn abi_reader_worker_read(
     worker: *mut Box<Worker>,
+    max: u64,
     out: *mut SafeVec<MutFixed<Layer>>,
     err: *mut Error,
 ) -> u8 {
     let worker = unsafe { &mut *worker };
-    match worker.read() {
+    match catch_unwind(|| worker.read(max as usize)) {
         Ok(layers) => {
             let mut safe = SafeVec::with_capacity(layers.len() as u64);
             for layer in layers {
@@ -187,7 +207,7 @@ extern "C" fn abi_reader_worker_read(
             1
         }
         Err(e) => {
-            unsafe { *err = Error::new(e.description()) };
+            unsafe { *err = Error::new(&e.to_string()) };
             0
         }
     }
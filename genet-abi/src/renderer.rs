@@ -0,0 +1,188 @@
+use attr::Attr;
+use bincode;
+use layer::Layer;
+use serde::ser::{Serialize, Serializer};
+use std::{
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+use string::SafeString;
+use vec::SafeVec;
+
+/// Renderer metadata.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct Metadata {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+
+    /// Attribute IDs or types handled by the renderer.
+    pub targets: Vec<String>,
+}
+
+impl Default for Metadata {
+    fn default() -> Self {
+        Metadata {
+            id: String::new(),
+            name: String::new(),
+            description: String::new(),
+            targets: Vec::new(),
+        }
+    }
+}
+
+/// Renderer trait.
+///
+/// A renderer formats attribute values for display,
+/// e.g. as a hex dump, a vendor name or a flag string.
+pub trait Renderer: RendererClone + Send {
+    /// Returns the display string of the attribute,
+    /// or `None` to fall back to the default representation.
+    fn render(&self, attr: &Attr, layer: &Layer) -> Option<String>;
+    fn metadata(&self) -> Metadata;
+}
+
+pub trait RendererClone {
+    fn clone_box(&self) -> Box<Renderer>;
+    fn into_box(self) -> Box<Renderer>;
+}
+
+impl<T> RendererClone for T
+where
+    T: 'static + Renderer + Clone,
+{
+    fn clone_box(&self) -> Box<Renderer> {
+        Box::new(self.clone())
+    }
+
+    fn into_box(self) -> Box<Renderer> {
+        Box::new(self)
+    }
+}
+
+impl Clone for Box<Renderer> {
+    fn clone(&self) -> Box<Renderer> {
+        self.clone_box()
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct RendererBox {
+    render: extern "C" fn(*const RendererBox, *const Attr, *const Layer, *mut SafeString) -> u8,
+    metadata: extern "C" fn(*const RendererBox) -> SafeVec<u8>,
+    renderer: *mut Box<Renderer>,
+}
+
+unsafe impl Send for RendererBox {}
+
+impl RendererBox {
+    pub fn new<T: 'static + Renderer>(renderer: T) -> RendererBox {
+        let renderer: Box<Renderer> = Box::new(renderer);
+        Self {
+            render: abi_render,
+            metadata: abi_metadata,
+            renderer: Box::into_raw(Box::new(renderer)),
+        }
+    }
+
+    pub fn render(&self, attr: &Attr, layer: &Layer) -> Option<String> {
+        let mut out = SafeString::new();
+        if (self.render)(self, attr, layer, &mut out) == 1 {
+            Some(out.as_str().to_string())
+        } else {
+            None
+        }
+    }
+
+    /// Returns the metadata, or the default one if the renderer panicked.
+    pub fn metadata(&self) -> Metadata {
+        bincode::deserialize(&(self.metadata)(self)).unwrap_or_default()
+    }
+}
+
+impl Serialize for RendererBox {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.metadata().serialize(serializer)
+    }
+}
+
+extern "C" fn abi_render(
+    renderer: *const RendererBox,
+    attr: *const Attr,
+    layer: *const Layer,
+    out: *mut SafeString,
+) -> u8 {
+    let renderer = unsafe { &*((*renderer).renderer) };
+    let attr = unsafe { &*attr };
+    let layer = unsafe { &*layer };
+    match panic::catch_unwind(AssertUnwindSafe(|| renderer.render(attr, layer))) {
+        Ok(Some(s)) => {
+            unsafe { ptr::write(out, SafeString::from(&s)) };
+            1
+        }
+        _ => 0,
+    }
+}
+
+extern "C" fn abi_metadata(renderer: *const RendererBox) -> SafeVec<u8> {
+    let renderer = unsafe { &*((*renderer).renderer) };
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        bincode::serialize(&renderer.metadata())
+    }))
+    .ok()
+    .and_then(|data| data.ok())
+    .unwrap_or_default()
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use attr::{Attr, AttrClass};
+    use fixed::Fixed;
+    use layer::{Layer, LayerClass};
+    use renderer::{Metadata, Renderer, RendererBox};
+    use slice::ByteSlice;
+    use token::Token;
+    use variant::Value;
+
+    #[test]
+    fn render() {
+        #[derive(Clone)]
+        struct TestRenderer {}
+
+        impl Renderer for TestRenderer {
+            fn render(&self, attr: &Attr, layer: &Layer) -> Option<String> {
+                let value: u64 = attr.try_get(layer).ok()?.try_into().ok()?;
+                if value > 0 {
+                    Some(format!("0x{:x}", value))
+                } else {
+                    None
+                }
+            }
+
+            fn metadata(&self) -> Metadata {
+                Metadata {
+                    id: "hex".to_string(),
+                    targets: vec!["@int:hex".to_string()],
+                    ..Metadata::default()
+                }
+            }
+        }
+
+        let renderer = RendererBox::new(TestRenderer {});
+        assert_eq!(renderer.metadata().targets, vec!["@int:hex".to_string()]);
+
+        let class = Fixed::new(LayerClass::builder(Token::null()).build());
+        let layer = Layer::new(class, ByteSlice::new());
+        let class = Fixed::new(AttrClass::builder(Token::null()).build());
+        let attr = Attr::builder(class.clone()).value(255u64).build();
+        assert_eq!(renderer.render(&attr, &layer), Some("0xff".to_string()));
+        let attr = Attr::builder(class).value(0u64).build();
+        assert_eq!(renderer.render(&attr, &layer), None);
+    }
+}
@@ -1,5 +1,9 @@
+use env;
+use error;
 use std::{
-    io::{Error, ErrorKind, Result},
+    hash::{Hash, Hasher},
+    io::Result,
+    iter::FromIterator,
     marker::PhantomData,
     mem,
     ops::{Deref, Range, RangeFrom, RangeFull, RangeInclusive, RangeTo, RangeToInclusive},
@@ -22,8 +26,15 @@ macro_rules! impl_slice_index {
 
                 fn try_get(&self, index: $x) -> Result<ByteSlice> {
                     <[u8]>::get(self, index)
-                        .map(|s| unsafe { ByteSlice::from_raw_parts(s.as_ptr(), s.len()) })
-                        .ok_or_else(|| Error::new(ErrorKind::Other, "out of bounds"))
+                        .map(|s| unsafe {
+                            let offset = s.as_ptr() as usize - self.as_ptr() as usize;
+                            ByteSlice::from_raw_parts_with_offset(
+                                s.as_ptr(),
+                                s.len(),
+                                self.offset + offset,
+                            )
+                        })
+                        .ok_or_else(|| error::out_of_bounds())
                 }
             }
         )*
@@ -45,30 +56,57 @@ impl TryGet<usize> for ByteSlice {
     fn try_get(&self, index: usize) -> Result<u8> {
         <[u8]>::get(self, index)
             .cloned()
-            .ok_or_else(|| Error::new(ErrorKind::Other, "out of bounds"))
+            .ok_or_else(|| error::out_of_bounds())
     }
 }
 
 /// A fixed-lifetime slice object.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
-pub struct ByteSlice(&'static [u8]);
+///
+/// A ByteSlice keeps its offset within the original buffer,
+/// so the subslices can be mapped back to the absolute frame offsets.
+/// The offset is ignored in comparisons.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ByteSlice {
+    data: &'static [u8],
+    offset: usize,
+}
 
 impl ByteSlice {
     /// Creates a new empty ByteSlice.
     pub fn new() -> ByteSlice {
-        ByteSlice(&[])
+        ByteSlice::default()
     }
 
     /// Creates a new ByteSlice from a length and pointer.
     ///
     /// The pointer must be valid during the program execution.
     pub unsafe fn from_raw_parts(data: *const u8, len: usize) -> ByteSlice {
-        ByteSlice(slice::from_raw_parts(data, len))
+        Self::from_raw_parts_with_offset(data, len, 0)
+    }
+
+    /// Creates a new ByteSlice from a length, pointer and offset
+    /// within the original buffer.
+    ///
+    /// The pointer must be valid during the program execution.
+    pub unsafe fn from_raw_parts_with_offset(
+        data: *const u8,
+        len: usize,
+        offset: usize,
+    ) -> ByteSlice {
+        ByteSlice {
+            data: slice::from_raw_parts(data, len),
+            offset,
+        }
     }
 
     /// Returns the length of this ByteSlice.
     pub fn len(&self) -> usize {
-        self.0.len()
+        self.data.len()
+    }
+
+    /// Returns the offset of this ByteSlice within the original buffer.
+    pub fn offset(&self) -> usize {
+        self.offset
     }
 
     /// Returns true if this ByteSlice has a length of zero.
@@ -80,18 +118,37 @@ impl ByteSlice {
 
     /// Returns a raw pointer to the first byte in this ByteSlice.
     pub fn as_ptr(&self) -> *const u8 {
-        self.0.as_ptr()
+        self.data.as_ptr()
+    }
+}
+
+impl PartialEq for ByteSlice {
+    fn eq(&self, other: &ByteSlice) -> bool {
+        self.data == other.data
+    }
+}
+
+impl Eq for ByteSlice {}
+
+impl Hash for ByteSlice {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.data.hash(state);
     }
 }
 
 impl From<&'static [u8]> for ByteSlice {
     fn from(data: &'static [u8]) -> Self {
-        ByteSlice(data)
+        ByteSlice { data, offset: 0 }
     }
 }
 
 impl From<Box<[u8]>> for ByteSlice {
     fn from(data: Box<[u8]>) -> Self {
+        if !data.is_empty() {
+            if let Some(ptr) = env::spill(&data) {
+                return unsafe { ByteSlice::from_raw_parts(ptr, data.len()) };
+            }
+        }
         let s = unsafe { ByteSlice::from_raw_parts(data.as_ptr(), data.len()) };
         mem::forget(data);
         s
@@ -108,14 +165,127 @@ impl Deref for ByteSlice {
     type Target = [u8];
 
     fn deref(&self) -> &'static [u8] {
-        self.0
+        self.data
     }
 }
 
 impl AsRef<[u8]> for ByteSlice {
     #[inline]
     fn as_ref(&self) -> &[u8] {
-        &self.0
+        &self.data
+    }
+}
+
+/// A chain of non-contiguous ByteSlices.
+///
+/// A ByteChain represents reassembled data without copying the segments
+/// into one contiguous buffer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ByteChain {
+    segments: Vec<ByteSlice>,
+    len: usize,
+}
+
+impl ByteChain {
+    /// Creates a new empty ByteChain.
+    pub fn new() -> ByteChain {
+        ByteChain::default()
+    }
+
+    /// Appends a segment to the end of this ByteChain.
+    pub fn push<B: Into<ByteSlice>>(&mut self, data: B) {
+        let data = data.into();
+        if !data.is_empty() {
+            self.len += data.len();
+            self.segments.push(data);
+        }
+    }
+
+    /// Returns the total length of this ByteChain.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if this ByteChain has a length of zero.
+    ///
+    /// Returns false otherwise.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the segments of this ByteChain.
+    pub fn segments(&self) -> &[ByteSlice] {
+        &self.segments
+    }
+
+    /// Returns an iterator over the bytes of this ByteChain.
+    pub fn bytes<'a>(&'a self) -> impl Iterator<Item = u8> + 'a {
+        self.segments.iter().flat_map(|s| s.iter().cloned())
+    }
+
+    /// Returns the contiguous ByteSlice of this ByteChain.
+    ///
+    /// The segments are copied only if this ByteChain has two or more segments.
+    pub fn to_slice(&self) -> ByteSlice {
+        match self.segments.len() {
+            0 => ByteSlice::new(),
+            1 => self.segments[0],
+            _ => ByteSlice::from(self.bytes().collect::<Vec<_>>()),
+        }
+    }
+}
+
+impl From<ByteSlice> for ByteChain {
+    fn from(data: ByteSlice) -> Self {
+        let mut chain = ByteChain::new();
+        chain.push(data);
+        chain
+    }
+}
+
+impl FromIterator<ByteSlice> for ByteChain {
+    fn from_iter<I: IntoIterator<Item = ByteSlice>>(iter: I) -> Self {
+        let mut chain = ByteChain::new();
+        for data in iter {
+            chain.push(data);
+        }
+        chain
+    }
+}
+
+impl TryGet<usize> for ByteChain {
+    type Output = u8;
+
+    fn try_get(&self, index: usize) -> Result<u8> {
+        let mut index = index;
+        for data in &self.segments {
+            if index < data.len() {
+                return Ok(data[index]);
+            }
+            index -= data.len();
+        }
+        Err(error::out_of_bounds())
+    }
+}
+
+impl TryGet<Range<usize>> for ByteChain {
+    type Output = ByteChain;
+
+    fn try_get(&self, index: Range<usize>) -> Result<ByteChain> {
+        if index.start > index.end || index.end > self.len {
+            return Err(error::out_of_bounds());
+        }
+        let mut chain = ByteChain::new();
+        let mut offset = 0;
+        for data in &self.segments {
+            let start = index.start.max(offset);
+            let end = index.end.min(offset + data.len());
+            if start < end {
+                chain.push(data.try_get((start - offset)..(end - offset))?);
+            }
+            offset += data.len();
+        }
+        Ok(chain)
     }
 }
 
@@ -139,3 +309,44 @@ impl<'a, T: 'a> Deref for SafeByteSlice<'a, T> {
         self.as_slice()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use slice::{ByteChain, ByteSlice, TryGet};
+
+    #[test]
+    fn offset() {
+        let data = ByteSlice::from(&b"abcdefgh"[..]);
+        assert_eq!(data.offset(), 0);
+        let payload = data.try_get(2..).unwrap();
+        assert_eq!(payload.offset(), 2);
+        let field = payload.try_get(3..5).unwrap();
+        assert_eq!(field.offset(), 5);
+        assert_eq!(field, ByteSlice::from(&b"fg"[..]));
+        assert_eq!(payload.try_get(..).unwrap().offset(), 2);
+    }
+
+    #[test]
+    fn chain() {
+        let chain = vec![
+            ByteSlice::from(&b"abc"[..]),
+            ByteSlice::new(),
+            ByteSlice::from(&b"defg"[..]),
+        ]
+        .into_iter()
+        .collect::<ByteChain>();
+        assert_eq!(chain.len(), 7);
+        assert_eq!(chain.segments().len(), 2);
+        assert_eq!(chain.try_get(4).unwrap(), b'e');
+        assert!(chain.try_get(7).is_err());
+
+        let sub = chain.try_get(2..5).unwrap();
+        assert_eq!(sub.segments().len(), 2);
+        assert_eq!(sub.bytes().collect::<Vec<_>>(), b"cde".to_vec());
+        assert_eq!(sub.to_slice(), ByteSlice::from(&b"cde"[..]));
+        assert!(chain.try_get(5..8).is_err());
+
+        let single = chain.try_get(3..5).unwrap();
+        assert_eq!(single.to_slice().as_ptr(), chain.segments()[1].as_ptr());
+    }
+}
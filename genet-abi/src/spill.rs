@@ -0,0 +1,284 @@
+//! Spilling of the frame data to disk.
+//!
+//! The layers, the attributes and the buffers owned by ByteSlices are never freed.
+//! Each store owns a Pool limiting the bytes of them kept in the heap. Beyond the
+//! limit, new values are copied to temporary files mapped into memory, so that the
+//! OS can write the pages out and load them again on access.
+//!
+//! Only the allocations on the threads which have entered a pool are counted.
+
+use parking_lot::Mutex;
+use std::{
+    cell::RefCell,
+    io, ptr,
+    sync::{
+        atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT},
+        Arc,
+    },
+    usize,
+};
+
+/// Size of a temporary file mapped at once.
+const CHUNK_SIZE: usize = 64 * 1024 * 1024;
+
+/// Alignment of the spilled values.
+pub(crate) const ALIGN: usize = 16;
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<Pool>>> = RefCell::new(None);
+}
+
+struct Chunk {
+    ptr: *mut u8,
+    len: usize,
+    used: usize,
+}
+
+unsafe impl Send for Chunk {}
+
+/// A memory limit and the usage accounted against it.
+///
+/// The usage only grows since the values are never freed. The mapped files are kept
+/// until the process exits, even after the pool is dropped.
+pub struct Pool {
+    limit: AtomicUsize,
+    used: AtomicUsize,
+    spilled: AtomicUsize,
+    chunk: Mutex<Option<Chunk>>,
+}
+
+impl Default for Pool {
+    fn default() -> Pool {
+        Pool::new()
+    }
+}
+
+impl Pool {
+    /// Creates a new Pool without a limit.
+    pub fn new() -> Pool {
+        Pool {
+            limit: AtomicUsize::new(usize::MAX),
+            used: AtomicUsize::new(0),
+            spilled: AtomicUsize::new(0),
+            chunk: Mutex::new(None),
+        }
+    }
+
+    /// Sets the bytes kept in the heap before spilling to disk, or None for no limit.
+    pub fn set_limit(&self, limit: Option<usize>) {
+        self.limit
+            .store(limit.unwrap_or(usize::MAX), Ordering::Relaxed);
+    }
+
+    /// Returns the bytes kept in the heap before spilling to disk.
+    pub fn limit(&self) -> Option<usize> {
+        match self.limit.load(Ordering::Relaxed) {
+            usize::MAX => None,
+            limit => Some(limit),
+        }
+    }
+
+    /// Returns the bytes kept in the heap.
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// Returns the bytes spilled to disk.
+    pub fn spilled(&self) -> usize {
+        self.spilled.load(Ordering::Relaxed)
+    }
+
+    /// Copies the buffer to disk if the limit is exceeded.
+    ///
+    /// Returns null if the buffer should be kept in the heap.
+    fn store(&self, data: *const u8, len: usize) -> *const u8 {
+        if self.reserve(len) {
+            return ptr::null();
+        }
+        match self.spill(data, len) {
+            Ok(ptr) => {
+                self.spilled.fetch_add(len, Ordering::Relaxed);
+                ptr
+            }
+            Err(_) => {
+                self.used.fetch_add(len, Ordering::Relaxed);
+                ptr::null()
+            }
+        }
+    }
+
+    /// Accounts the bytes in the heap unless they exceed the limit.
+    fn reserve(&self, len: usize) -> bool {
+        let limit = self.limit.load(Ordering::Relaxed);
+        let mut used = self.used.load(Ordering::Relaxed);
+        loop {
+            let next = match used.checked_add(len) {
+                Some(next) if next <= limit => next,
+                _ => return false,
+            };
+            match self
+                .used
+                .compare_exchange_weak(used, next, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => return true,
+                Err(current) => used = current,
+            }
+        }
+    }
+
+    fn spill(&self, data: *const u8, len: usize) -> io::Result<*const u8> {
+        let mut chunk = self.chunk.lock();
+        let fits = chunk.as_ref().map_or(false, |c| {
+            align(c.used)
+                .checked_add(len)
+                .map_or(false, |end| end <= c.len)
+        });
+        if !fits {
+            let size = len.max(CHUNK_SIZE);
+            *chunk = Some(Chunk {
+                ptr: map(size)?,
+                len: size,
+                used: 0,
+            });
+        }
+        let chunk = chunk.as_mut().unwrap();
+        let offset = align(chunk.used);
+        unsafe {
+            let dst = chunk.ptr.add(offset);
+            ptr::copy_nonoverlapping(data, dst, len);
+            chunk.used = offset + len;
+            Ok(dst)
+        }
+    }
+}
+
+fn align(offset: usize) -> usize {
+    (offset + ALIGN - 1) & !(ALIGN - 1)
+}
+
+/// Accounts the allocations on the current thread against a pool.
+///
+/// The previous pool is restored when the scope is dropped.
+pub struct Scope {
+    prev: Option<Arc<Pool>>,
+}
+
+impl Scope {
+    pub fn enter(pool: &Arc<Pool>) -> Scope {
+        let prev = CURRENT.with(|current| current.borrow_mut().replace(pool.clone()));
+        Scope { prev }
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        let prev = self.prev.take();
+        CURRENT.with(|current| *current.borrow_mut() = prev);
+    }
+}
+
+/// Copies the buffer to disk if the limit of the current pool is exceeded.
+///
+/// Returns null if the buffer should be kept in the heap.
+pub extern "C" fn abi_genet_spill(data: *const u8, len: u64) -> *const u8 {
+    CURRENT.with(|current| {
+        current
+            .borrow()
+            .as_ref()
+            .map_or(ptr::null(), |pool| pool.store(data, len as usize))
+    })
+}
+
+/// Maps a new temporary file, which is removed when the process exits.
+#[cfg(unix)]
+fn map(len: usize) -> io::Result<*mut u8> {
+    use libc;
+    use std::{env, fs, os::unix::io::AsRawFd, process};
+
+    static COUNTER: AtomicUsize = ATOMIC_USIZE_INIT;
+    let path = env::temp_dir().join(format!(
+        "genet-spill-{}-{}",
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&path)?;
+    let _ = fs::remove_file(&path);
+    file.set_len(len as u64)?;
+    let ptr = unsafe {
+        libc::mmap(
+            ptr::null_mut(),
+            len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_SHARED,
+            file.as_raw_fd(),
+            0,
+        )
+    };
+    if ptr == libc::MAP_FAILED {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ptr as *mut u8)
+    }
+}
+
+#[cfg(not(unix))]
+fn map(_len: usize) -> io::Result<*mut u8> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "spilling is not supported on this platform",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use fixed::MutFixed;
+    use slice::ByteSlice;
+    use spill::{Pool, Scope};
+    use std::{mem, sync::Arc};
+
+    #[test]
+    fn limit() {
+        let pool = Pool::new();
+        assert_eq!(pool.limit(), None);
+        pool.set_limit(Some(0));
+        assert_eq!(pool.limit(), Some(0));
+        pool.set_limit(None);
+        assert_eq!(pool.limit(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn spill() {
+        let data = (0..=255).collect::<Vec<u8>>();
+        let pool = Arc::new(Pool::new());
+        pool.set_limit(Some(data.len()));
+        let scope = Scope::enter(&pool);
+
+        let vec = data.clone();
+        let ptr = vec.as_ptr();
+        let slice = ByteSlice::from(vec);
+        assert_eq!(slice.as_ptr(), ptr);
+        assert_eq!(pool.used(), data.len());
+
+        let vec = data.clone();
+        let ptr = vec.as_ptr();
+        let slice = ByteSlice::from(vec);
+        assert_ne!(slice.as_ptr(), ptr);
+        assert_eq!(&slice[..], &data[..]);
+        assert_eq!(pool.spilled(), data.len());
+
+        let value = MutFixed::new([0x5au64; 4]);
+        assert_eq!(*value, [0x5au64; 4]);
+        assert_eq!(value.as_ptr() as usize % mem::align_of::<[u64; 4]>(), 0);
+        assert_eq!(pool.spilled(), data.len() + mem::size_of::<[u64; 4]>());
+
+        drop(scope);
+        let slice = ByteSlice::from(data.clone());
+        assert_eq!(&slice[..], &data[..]);
+        assert_eq!(pool.used(), data.len());
+    }
+}
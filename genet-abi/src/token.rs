@@ -1,5 +1,8 @@
 use env;
-use std::fmt;
+use std::{
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 /// A token value.
 #[repr(C)]
@@ -12,12 +15,61 @@ impl Token {
         Token(0)
     }
 
+    /// Returns the registered token for the string without registering a new one.
+    pub fn lookup(id: &str) -> Option<Token> {
+        let token = env::lookup(id);
+        if token == Token::null() {
+            None
+        } else {
+            Some(token)
+        }
+    }
+
+    /// Returns an iterator over all registered tokens.
+    ///
+    /// Tokens registered after calling this function are not included.
+    pub fn all() -> impl Iterator<Item = Token> {
+        (1..=env::token_count()).map(Token)
+    }
+
+    /// Returns all registered tokens under the namespace in lexicographical order.
+    ///
+    /// Namespaces are separated by dots. For example,
+    /// `tcp.flags.syn` and `tcp.flags.ack` are under `tcp.flags`.
+    pub fn descendants(namespace: &str) -> Vec<Token> {
+        env::descendants(namespace)
+    }
+
+    /// Returns the namespace of self.
+    pub fn namespace(self) -> String {
+        let id = self.to_string();
+        match id.rfind('.') {
+            Some(index) => id[..index].to_string(),
+            None => String::new(),
+        }
+    }
+
     /// Returns the corresponded string.
     pub fn to_string(self) -> String {
         env::string(self)
     }
 }
 
+/// Returns the token cached in `cache`, registering it with `init` on the first use.
+///
+/// This backs the per-call-site caches of the `token!` macros.
+#[doc(hidden)]
+pub fn cached<F: FnOnce() -> Token>(cache: &AtomicUsize, init: F) -> Token {
+    match cache.load(Ordering::Relaxed) {
+        0 => {
+            let token = init();
+            cache.store(token.0 as usize, Ordering::Relaxed);
+            token
+        }
+        id => Token(id as u32),
+    }
+}
+
 impl Into<u32> for Token {
     fn into(self) -> u32 {
         self.0
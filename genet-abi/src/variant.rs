@@ -2,6 +2,8 @@ use slice::ByteSlice;
 use std::{
     convert::Into,
     io::{Error, ErrorKind, Result},
+    net::Ipv6Addr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 #[derive(Debug, Clone, PartialEq)]
@@ -15,6 +17,11 @@ pub enum Variant {
     BigInt(Box<[u8]>),
     Buffer(Box<[u8]>),
     Slice(ByteSlice),
+    Ipv6Addr(Ipv6Addr),
+    MacAddr([u8; 6]),
+
+    /// Nanoseconds since the UNIX epoch.
+    Timestamp(u64),
 }
 
 /// Variant value trait.
@@ -37,6 +44,8 @@ impl Value<Vec<u8>> for Variant {
             Variant::String(val) => Ok(val.to_string().into_bytes()),
             Variant::Buffer(val) => Ok(val.into_vec()),
             Variant::Slice(val) => Ok(val.as_ref().to_vec()),
+            Variant::Ipv6Addr(val) => Ok(val.octets().to_vec()),
+            Variant::MacAddr(val) => Ok(val.to_vec()),
             _ => Err(Error::new(ErrorKind::InvalidData, "wrong type")),
         }
     }
@@ -59,11 +68,21 @@ impl Value<u64> for Variant {
             Variant::Int64(val) => Ok(val as u64),
             Variant::UInt64(val) => Ok(val as u64),
             Variant::Float64(val) => Ok(val as u64),
+            Variant::Timestamp(val) => Ok(val),
             _ => Err(Error::new(ErrorKind::InvalidData, "wrong type")),
         }
     }
 }
 
+impl Value<bool> for Variant {
+    fn try_into(self) -> Result<bool> {
+        match self {
+            Variant::Bool(val) => Ok(val),
+            _ => Value::<u64>::try_into(self).map(|v| v != 0),
+        }
+    }
+}
+
 impl Value<usize> for Variant {
     fn try_into(self) -> Result<usize> {
         Value::<u64>::try_into(self).map(|v| v as usize)
@@ -96,6 +115,7 @@ impl Value<i64> for Variant {
             Variant::Int64(val) => Ok(val as i64),
             Variant::UInt64(val) => Ok(val as i64),
             Variant::Float64(val) => Ok(val as i64),
+            Variant::Timestamp(val) => Ok(val as i64),
             _ => Err(Error::new(ErrorKind::InvalidData, "wrong type")),
         }
     }
@@ -144,6 +164,55 @@ impl Value<f32> for Variant {
     }
 }
 
+impl Value<Ipv6Addr> for Variant {
+    fn try_into(self) -> Result<Ipv6Addr> {
+        match self {
+            Variant::Ipv6Addr(val) => Ok(val),
+            Variant::Buffer(ref val) if val.len() == 16 => {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(val);
+                Ok(Ipv6Addr::from(octets))
+            }
+            Variant::Slice(ref val) if val.len() == 16 => {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(val);
+                Ok(Ipv6Addr::from(octets))
+            }
+            _ => Err(Error::new(ErrorKind::InvalidData, "wrong type")),
+        }
+    }
+}
+
+impl Value<[u8; 6]> for Variant {
+    fn try_into(self) -> Result<[u8; 6]> {
+        match self {
+            Variant::MacAddr(val) => Ok(val),
+            Variant::Buffer(ref val) if val.len() == 6 => {
+                let mut octets = [0u8; 6];
+                octets.copy_from_slice(val);
+                Ok(octets)
+            }
+            Variant::Slice(ref val) if val.len() == 6 => {
+                let mut octets = [0u8; 6];
+                octets.copy_from_slice(val);
+                Ok(octets)
+            }
+            _ => Err(Error::new(ErrorKind::InvalidData, "wrong type")),
+        }
+    }
+}
+
+impl Value<SystemTime> for Variant {
+    fn try_into(self) -> Result<SystemTime> {
+        match self {
+            Variant::Timestamp(val) => {
+                Ok(UNIX_EPOCH + Duration::new(val / 1_000_000_000, (val % 1_000_000_000) as u32))
+            }
+            _ => Err(Error::new(ErrorKind::InvalidData, "wrong type")),
+        }
+    }
+}
+
 impl Into<Variant> for bool {
     fn into(self) -> Variant {
         Variant::Bool(self)
@@ -227,3 +296,22 @@ impl Into<Variant> for ByteSlice {
         Variant::Slice(self)
     }
 }
+
+impl Into<Variant> for Ipv6Addr {
+    fn into(self) -> Variant {
+        Variant::Ipv6Addr(self)
+    }
+}
+
+impl Into<Variant> for [u8; 6] {
+    fn into(self) -> Variant {
+        Variant::MacAddr(self)
+    }
+}
+
+impl Into<Variant> for SystemTime {
+    fn into(self) -> Variant {
+        let duration = self.duration_since(UNIX_EPOCH).unwrap_or_default();
+        Variant::Timestamp(duration.as_secs() * 1_000_000_000 + u64::from(duration.subsec_nanos()))
+    }
+}
@@ -0,0 +1,225 @@
+//! ABI version and feature negotiation.
+//!
+//! Every library built with the SDK exports `genet_abi_v1_info`, which
+//! describes the ABI version, the supported features and the sizes of
+//! the `#[repr(C)]` types shared with the kernel. The kernel refuses to
+//! load a library whose description is not compatible with its own.
+
+use attr::Attr;
+use context::Context;
+use decoder;
+use error::Error;
+use layer::{Layer, Payload};
+use reader;
+use renderer::RendererBox;
+use std::mem;
+use writer;
+
+/// Feature flags.
+pub mod feature {
+    /// `genet_abi_v1_register_get_tokens` is exported.
+    pub const GET_TOKENS: u64 = 1 << 0;
+
+    /// `genet_abi_v1_register_lookup_token` is exported.
+    pub const LOOKUP_TOKEN: u64 = 1 << 1;
+
+    /// `genet_abi_v1_register_token_count` is exported.
+    pub const TOKEN_COUNT: u64 = 1 << 2;
+
+    /// `genet_abi_v1_register_get_descendants` is exported.
+    pub const GET_DESCENDANTS: u64 = 1 << 3;
+
+    /// `WorkerBox` has the `probe` function.
+    pub const WORKER_PROBE: u64 = 1 << 4;
+
+    /// `Context` has the keyed state store.
+    pub const CONTEXT_STATE: u64 = 1 << 5;
+
+    /// `LayerClass` has the `summary` function.
+    pub const LAYER_SUMMARY: u64 = 1 << 6;
+
+    /// The features supported by this version.
+    pub const ALL: u64 = GET_TOKENS
+        | LOOKUP_TOKEN
+        | TOKEN_COUNT
+        | GET_DESCENDANTS
+        | WORKER_PROBE
+        | CONTEXT_STATE
+        | LAYER_SUMMARY;
+
+    /// The features the kernel cannot work without.
+    pub const REQUIRED: u64 = WORKER_PROBE | CONTEXT_STATE | LAYER_SUMMARY;
+
+    /// Returns the names of the features in the flags.
+    pub fn names(features: u64) -> Vec<&'static str> {
+        [
+            (GET_TOKENS, "get_tokens"),
+            (LOOKUP_TOKEN, "lookup_token"),
+            (TOKEN_COUNT, "token_count"),
+            (GET_DESCENDANTS, "get_descendants"),
+            (WORKER_PROBE, "worker_probe"),
+            (CONTEXT_STATE, "context_state"),
+            (LAYER_SUMMARY, "layer_summary"),
+        ]
+        .iter()
+        .filter(|(flag, _)| features & flag != 0)
+        .map(|(_, name)| *name)
+        .collect()
+    }
+}
+
+/// Returns the ABI version of this crate.
+///
+/// The upper 32 bits are the major version and the lower 32 bits are the minor version.
+pub fn current() -> u64 {
+    let major: u64 = env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap_or(0);
+    let minor: u64 = env!("CARGO_PKG_VERSION_MINOR").parse().unwrap_or(0);
+    major << 32 | minor
+}
+
+/// Returns true if the libraries built for the ABI versions can be used together.
+///
+/// In the initial development, minor version changes may break ABI.
+pub fn is_compatible(a: u64, b: u64) -> bool {
+    fn canonical(ver: u64) -> u64 {
+        if ver >> 32 == 0 {
+            ver
+        } else {
+            ver & (0xffff_ffff << 32)
+        }
+    }
+    canonical(a) == canonical(b)
+}
+
+/// Formats the ABI version as `<major>.<minor>`.
+pub fn format(ver: u64) -> String {
+    format!("{}.{}", ver >> 32, ver & 0xffff_ffff)
+}
+
+/// Sizes of the types shared across the ABI boundary.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    pub context: u32,
+    pub layer: u32,
+    pub attr: u32,
+    pub payload: u32,
+    pub decoder_box: u32,
+    pub decoder_worker_box: u32,
+    pub reader_box: u32,
+    pub reader_worker_box: u32,
+    pub writer_box: u32,
+    pub writer_worker_box: u32,
+    pub renderer_box: u32,
+}
+
+impl Layout {
+    /// Returns the layout of this crate.
+    pub fn current() -> Layout {
+        Layout {
+            context: mem::size_of::<Context>() as u32,
+            layer: mem::size_of::<Layer>() as u32,
+            attr: mem::size_of::<Attr>() as u32,
+            payload: mem::size_of::<Payload>() as u32,
+            decoder_box: mem::size_of::<decoder::DecoderBox>() as u32,
+            decoder_worker_box: mem::size_of::<decoder::WorkerBox>() as u32,
+            reader_box: mem::size_of::<reader::ReaderBox>() as u32,
+            reader_worker_box: mem::size_of::<reader::WorkerBox>() as u32,
+            writer_box: mem::size_of::<writer::WriterBox>() as u32,
+            writer_worker_box: mem::size_of::<writer::WorkerBox>() as u32,
+            renderer_box: mem::size_of::<RendererBox>() as u32,
+        }
+    }
+
+    fn fields(&self) -> [(&'static str, u32); 11] {
+        [
+            ("Context", self.context),
+            ("Layer", self.layer),
+            ("Attr", self.attr),
+            ("Payload", self.payload),
+            ("DecoderBox", self.decoder_box),
+            ("decoder::WorkerBox", self.decoder_worker_box),
+            ("ReaderBox", self.reader_box),
+            ("reader::WorkerBox", self.reader_worker_box),
+            ("WriterBox", self.writer_box),
+            ("writer::WorkerBox", self.writer_worker_box),
+            ("RendererBox", self.renderer_box),
+        ]
+    }
+}
+
+/// ABI description of a library.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AbiInfo {
+    pub version: u64,
+    pub features: u64,
+    pub layout: Layout,
+}
+
+impl AbiInfo {
+    /// Returns the description of this crate.
+    pub fn current() -> AbiInfo {
+        AbiInfo {
+            version: current(),
+            features: feature::ALL,
+            layout: Layout::current(),
+        }
+    }
+
+    /// Checks whether the library described by `lib` can be loaded.
+    pub fn check(&self, lib: &AbiInfo) -> Result<(), Error> {
+        if !is_compatible(self.version, lib.version) {
+            return Err(Error::new(&format!(
+                "abi version mismatch: library {}, expected {}",
+                format(lib.version),
+                format(self.version)
+            )));
+        }
+        let missing = feature::REQUIRED & !lib.features;
+        if missing != 0 {
+            return Err(Error::new(&format!(
+                "missing abi features: {}",
+                feature::names(missing).join(", ")
+            )));
+        }
+        for ((name, size), (_, expected)) in
+            lib.layout.fields().iter().zip(self.layout.fields().iter())
+        {
+            if size != expected {
+                return Err(Error::new(&format!(
+                    "abi layout mismatch: {} is {} bytes, expected {} bytes",
+                    name, size, expected
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use version::{feature, is_compatible, AbiInfo};
+
+    #[test]
+    fn check() {
+        let info = AbiInfo::current();
+        assert!(info.check(&info).is_ok());
+        assert!(is_compatible(3, 3));
+        assert!(!is_compatible(3, 4));
+        assert!(is_compatible(1 << 32 | 2, 1 << 32 | 5));
+
+        let mut lib = info;
+        lib.version += 1 << 32;
+        assert!(info.check(&lib).is_err());
+
+        let mut lib = info;
+        lib.features &= !feature::CONTEXT_STATE;
+        let err = info.check(&lib).unwrap_err();
+        assert_eq!(err.to_string(), "missing abi features: context_state");
+
+        let mut lib = info;
+        lib.layout.decoder_worker_box += 8;
+        assert!(info.check(&lib).is_err());
+    }
+}
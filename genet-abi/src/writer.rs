@@ -1,12 +1,16 @@
 use bincode;
 use context::Context;
-use error::Error;
+use error::{catch_unwind, Error};
 use file::FileType;
 use fixed::MutFixed;
 use layer::{Layer, LayerStack};
 use result::Result;
 use serde::ser::{Serialize, Serializer};
-use std::{fmt, mem, ptr, slice, str};
+use std::{
+    fmt, mem,
+    panic::{self, AssertUnwindSafe},
+    ptr, slice, str,
+};
 use vec::SafeVec;
 
 /// Writer metadata.
@@ -78,8 +82,9 @@ impl WriterBox {
         }
     }
 
+    /// Returns the metadata, or the default one if the writer panicked.
     pub fn metadata(&self) -> Metadata {
-        bincode::deserialize(&(self.metadata)(self)).unwrap()
+        bincode::deserialize(&(self.metadata)(self)).unwrap_or_default()
     }
 }
 
@@ -103,13 +108,13 @@ extern "C" fn abi_writer_new_worker(
     let writer = unsafe { &*writer };
     let ctx = unsafe { &*ctx };
     let arg = unsafe { str::from_utf8_unchecked(slice::from_raw_parts(arg, arg_len as usize)) };
-    match writer.new_worker(ctx, arg) {
+    match catch_unwind(|| writer.new_worker(ctx, arg)) {
         Ok(worker) => {
             unsafe { ptr::write(out, WorkerBox::new(worker)) };
             1
         }
         Err(e) => {
-            unsafe { *err = Error::new(e.description()) };
+            unsafe { *err = Error::new(&e.to_string()) };
             0
         }
     }
@@ -117,7 +122,11 @@ extern "C" fn abi_writer_new_worker(
 
 extern "C" fn abi_metadata(writer: *const WriterBox) -> SafeVec<u8> {
     let writer = unsafe { &*((*writer).writer) };
-    bincode::serialize(&writer.metadata()).unwrap().into()
+    panic::catch_unwind(AssertUnwindSafe(|| bincode::serialize(&writer.metadata())))
+        .ok()
+        .and_then(|data| data.ok())
+        .unwrap_or_default()
+        .into()
 }
 
 /// Writer worker trait.
@@ -196,10 +205,10 @@ extern "C" fn abi_writer_worker_write(
 ) -> u8 {
     let worker = unsafe { &mut *worker };
     let stack = unsafe { LayerStack::new(layers, len as usize) };
-    match worker.write(index, &stack) {
+    match catch_unwind(|| worker.write(index, &stack)) {
         Ok(()) => 1,
         Err(e) => {
-            unsafe { *err = Error::new(e.description()) };
+            unsafe { *err = Error::new(&e.to_string()) };
             0
         }
     }
@@ -207,10 +216,10 @@ extern "C" fn abi_writer_worker_write(
 
 extern "C" fn abi_writer_worker_end(worker: *mut Box<Worker>, err: *mut Error) -> u8 {
     let worker = unsafe { &mut *worker };
-    match worker.end() {
+    match catch_unwind(|| worker.end()) {
         Ok(()) => 1,
         Err(e) => {
-            unsafe { *err = Error::new(e.description()) };
+            unsafe { *err = Error::new(&e.to_string()) };
             0
         }
     }
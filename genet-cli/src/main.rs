@@ -0,0 +1,278 @@
+//! Command-line front-end for the genet kernel.
+//!
+//! Reads a capture file or a live capture, applies a filter,
+//! and prints the matching frames.
+//!
+//! ```text
+//! genet -r capture.pcap -Y 'tcp.dst == 80' -e ipv4.src -e tcp.src
+//! genet -i eth0 --pcap-cli ./pcap-cli -T json -c 10
+//! ```
+
+extern crate clap;
+extern crate genet_abi;
+extern crate genet_filter;
+extern crate genet_kernel;
+#[macro_use]
+extern crate serde_json;
+
+mod output;
+
+use clap::{App, Arg, ArgMatches};
+use genet_abi::token::Token;
+use genet_filter::{context::Context, Filter};
+use genet_kernel::{
+    profile::Profile,
+    session::{Callback, Event, Session},
+};
+use output::Format;
+use std::{
+    path::Path,
+    process,
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    time::Duration,
+};
+
+const POLL_INTERVAL_MS: u64 = 100;
+
+#[derive(Clone)]
+struct EventCallback {
+    sender: Sender<Event>,
+}
+
+impl Callback for EventCallback {
+    fn on_event(&self, event: Event) {
+        let _ = self.sender.send(event);
+    }
+}
+
+fn app<'a, 'b>() -> App<'a, 'b> {
+    App::new("genet")
+        .about("Reads packets and prints the decoded frames")
+        .arg(
+            Arg::with_name("read")
+                .short("r")
+                .value_name("FILE")
+                .help("Reads the capture file")
+                .takes_value(true)
+                .required_unless_one(&["interface", "reader"]),
+        )
+        .arg(
+            Arg::with_name("interface")
+                .short("i")
+                .value_name("DEVICE")
+                .help("Captures packets on the device")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("link")
+                .long("link")
+                .help("Sets the link type of the device")
+                .default_value("1"),
+        )
+        .arg(
+            Arg::with_name("pcap-cli")
+                .long("pcap-cli")
+                .value_name("PATH")
+                .help("Sets the capture helper of the pcap package")
+                .default_value("pcap-cli"),
+        )
+        .arg(
+            Arg::with_name("reader")
+                .long("reader")
+                .value_name("ID")
+                .help("Uses the reader with the JSON argument given by --reader-arg")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("reader-arg")
+                .long("reader-arg")
+                .value_name("JSON")
+                .default_value("{}"),
+        )
+        .arg(
+            Arg::with_name("filter")
+                .short("Y")
+                .value_name("FILTER")
+                .help("Prints only the frames matching the filter")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("field")
+                .short("e")
+                .value_name("ATTR")
+                .help("Prints the attribute value; may be given multiple times")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("separator")
+                .short("E")
+                .value_name("SEP")
+                .help("Sets the separator of the fields")
+                .default_value("\t"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .short("T")
+                .help("Sets the output format")
+                .possible_values(&["summary", "fields", "json"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("count")
+                .short("c")
+                .help("Stops after printing the number of frames")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("packages")
+                .long("packages")
+                .value_name("DIR")
+                .help("Loads the packages in the directory; may be given multiple times")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("library")
+                .long("library")
+                .value_name("PATH")
+                .help("Loads the plugin library; may be given multiple times")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+}
+
+/// Returns the reader ID and its argument.
+fn reader(matches: &ArgMatches) -> Result<(String, String), String> {
+    if let Some(id) = matches.value_of("reader") {
+        let arg = matches.value_of("reader-arg").unwrap();
+        return Ok((id.to_string(), arg.to_string()));
+    }
+    if let Some(device) = matches.value_of("interface") {
+        let link: u32 = matches
+            .value_of("link")
+            .unwrap()
+            .parse()
+            .map_err(|err| format!("invalid link type: {}", err))?;
+        let arg = json!({
+            "cmd": matches.value_of("pcap-cli").unwrap(),
+            "args": ["capture", device],
+            "link": link,
+        });
+        return Ok(("app.genet.reader.pcap".to_string(), arg.to_string()));
+    }
+    let file = matches.value_of("read").unwrap();
+    let ext = Path::new(file)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default();
+    let id = match ext {
+        "pcapng" => "app.genet.reader.pcapng-file",
+        "genet" => "app.genet.reader.genet-file",
+        _ => "app.genet.reader.pcap-file",
+    };
+    Ok((id.to_string(), json!({ "file": file }).to_string()))
+}
+
+fn format(matches: &ArgMatches) -> Format {
+    let ids = matches
+        .values_of("field")
+        .map(|ids| ids.map(Token::from).collect::<Vec<_>>())
+        .unwrap_or_default();
+    match matches.value_of("format") {
+        Some("json") => Format::Json,
+        Some("summary") => Format::Summary,
+        _ if ids.is_empty() => Format::Summary,
+        _ => Format::Fields {
+            ids,
+            separator: matches.value_of("separator").unwrap().to_string(),
+        },
+    }
+}
+
+fn run(matches: &ArgMatches) -> Result<(), String> {
+    let mut profile = Profile::new();
+    for dir in matches.values_of("packages").into_iter().flat_map(|v| v) {
+        let errors = profile
+            .load_packages(dir)
+            .map_err(|err| format!("{}: {}", dir, err))?;
+        for err in errors {
+            eprintln!("warning: {}", err);
+        }
+    }
+    for path in matches.values_of("library").into_iter().flat_map(|v| v) {
+        profile
+            .load_library(path)
+            .map_err(|err| format!("{}: {}", path, err))?;
+    }
+
+    let filter = match matches.value_of("filter") {
+        Some(filter) => Some(
+            Filter::compile_with(filter, profile.named_filters())
+                .map_err(|err| format!("invalid filter: {}", err))?,
+        ),
+        None => None,
+    };
+    let count = match matches.value_of("count") {
+        Some(count) => Some(
+            count
+                .parse::<usize>()
+                .map_err(|err| format!("invalid count: {}", err))?,
+        ),
+        None => None,
+    };
+    let format = format(matches);
+    let (id, arg) = reader(matches)?;
+
+    let (sender, receiver) = mpsc::channel();
+    let mut session = Session::new(profile, EventCallback { sender });
+    let handle = session.create_reader(&id, &arg);
+    if handle == 0 {
+        return Err(format!("failed to invoke reader: {}", id));
+    }
+
+    let mut cursor = 0;
+    let mut printed = 0;
+    let mut done = false;
+    loop {
+        match receiver.recv_timeout(Duration::from_millis(POLL_INTERVAL_MS)) {
+            // The readers report the end of the input as an error,
+            // so the error is not treated as a failure.
+            Ok(Event::Input(id, _)) if id == handle => done = true,
+            Ok(Event::Error(err)) => eprintln!("error: {}", err),
+            Ok(_) | Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => done = true,
+        }
+
+        // Check before reading the frames so that no frames are stored afterwards.
+        let finished = done && session.in_flight() == 0;
+        let len = session.len();
+        for frame in session.frames(cursor..len) {
+            let frame = unsafe { &*frame };
+            let links = session.links(frame.index());
+            let ctx = Context::new(frame.layers()).with_links(&links);
+            if filter.as_ref().map_or(true, |filter| filter.test(&ctx)) {
+                format.print(&session, frame);
+                printed += 1;
+                if count.map_or(false, |count| printed >= count) {
+                    return Ok(());
+                }
+            }
+        }
+        cursor = len;
+        if finished {
+            return Ok(());
+        }
+    }
+}
+
+fn main() {
+    let matches = app().get_matches();
+    if let Err(err) = run(&matches) {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
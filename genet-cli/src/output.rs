@@ -0,0 +1,119 @@
+use genet_abi::{attr::Attr, layer::Layer, token::Token, variant::Variant};
+use genet_kernel::session::{Frame, Session};
+use serde_json::{Map, Number, Value};
+
+/// The output format of the frames.
+pub enum Format {
+    /// The index, the length and the layer IDs.
+    Summary,
+    /// The values of the selected attributes.
+    Fields { ids: Vec<Token>, separator: String },
+    /// The layers and the attributes in NDJSON.
+    Json,
+}
+
+impl Format {
+    pub fn print(&self, session: &Session, frame: &Frame) {
+        match self {
+            Format::Summary => {
+                let length = frame.layers().first().map_or(0, |layer| layer.data().len());
+                let layers = frame
+                    .layers()
+                    .iter()
+                    .map(|layer| layer.id().to_string())
+                    .collect::<Vec<_>>();
+                println!("{}\t{}\t{}", frame.index() + 1, length, layers.join(":"));
+            }
+            Format::Fields { ids, separator } => {
+                let fields = ids
+                    .iter()
+                    .map(|id| field(session, frame, *id).unwrap_or_default())
+                    .collect::<Vec<_>>();
+                println!("{}", fields.join(separator));
+            }
+            Format::Json => {
+                let mut obj = Map::new();
+                obj.insert("index".into(), frame.index().into());
+                obj.insert(
+                    "layers".into(),
+                    frame
+                        .layers()
+                        .iter()
+                        .map(|layer| layer_json(session, layer))
+                        .collect::<Vec<_>>()
+                        .into(),
+                );
+                println!("{}", Value::Object(obj));
+            }
+        }
+    }
+}
+
+/// Finds the attribute in the innermost layer.
+fn find_attr(frame: &Frame, id: Token) -> Option<(&Attr, &Layer)> {
+    frame
+        .layers()
+        .iter()
+        .rev()
+        .filter_map(|layer| layer.attr(id).map(|attr| (attr, &**layer)))
+        .next()
+}
+
+fn field(session: &Session, frame: &Frame, id: Token) -> Option<String> {
+    let (attr, layer) = find_attr(frame, id)?;
+    if let Some(text) = session.render_attr(attr, layer) {
+        return Some(text);
+    }
+    if let Some(label) = attr.label(layer) {
+        return Some(label.to_string());
+    }
+    match variant_json(attr.try_get(layer).ok()?) {
+        Value::String(s) => Some(s),
+        Value::Null => None,
+        value => Some(value.to_string()),
+    }
+}
+
+fn layer_json(session: &Session, layer: &Layer) -> Value {
+    let mut attrs = Map::new();
+    for attr in layer.headers().iter().chain(layer.attrs().iter()) {
+        let value = match session.render_attr(attr, layer) {
+            Some(text) => text.into(),
+            None => attr.try_get(layer).map_or(Value::Null, variant_json),
+        };
+        attrs.insert(attr.id().to_string(), value);
+    }
+    let mut obj = Map::new();
+    obj.insert("id".into(), layer.id().to_string().into());
+    obj.insert("length".into(), layer.data().len().into());
+    obj.insert("attrs".into(), Value::Object(attrs));
+    Value::Object(obj)
+}
+
+fn hex(data: &[u8]) -> Value {
+    data.iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>()
+        .into()
+}
+
+fn variant_json(value: Variant) -> Value {
+    match value {
+        Variant::Nil => Value::Null,
+        Variant::Bool(v) => v.into(),
+        Variant::Int64(v) => v.into(),
+        Variant::UInt64(v) => v.into(),
+        Variant::Timestamp(v) => format!("{}.{:09}", v / 1_000_000_000, v % 1_000_000_000).into(),
+        Variant::Float64(v) => Number::from_f64(v).map_or(Value::Null, Value::Number),
+        Variant::String(v) => v.to_string().into(),
+        Variant::BigInt(v) | Variant::Buffer(v) => hex(&v),
+        Variant::Slice(v) => hex(&v),
+        Variant::Ipv6Addr(v) => v.to_string().into(),
+        Variant::MacAddr(v) => v
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(":")
+            .into(),
+    }
+}
@@ -0,0 +1,290 @@
+//! This crate provides derive macros for
+//! [genet-sdk](https://docs.rs/genet-sdk/).
+//!
+//! Use the re-exports in genet-sdk instead of depending on this crate directly.
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+#[macro_use]
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use syn::{Attribute, Data, DeriveInput, Error, Expr, Fields, Lit, Meta, NestedMeta, Type};
+
+/// Derives `genet_sdk::attr::AttrStruct` for a struct describing a header.
+///
+/// See the documentation of `genet_sdk::attr` for the available options.
+#[proc_macro_derive(Attr, attributes(attr))]
+pub fn derive_attr(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+#[derive(Default)]
+struct Options {
+    id: Option<String>,
+    typ: Option<String>,
+    name: Option<String>,
+    description: Option<String>,
+    cast: Option<Expr>,
+    size: Option<usize>,
+    bits: Option<usize>,
+    skip: usize,
+    little_endian: Option<bool>,
+}
+
+fn parse_options(attrs: &[Attribute]) -> Result<Options, Error> {
+    let mut opts = Options::default();
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("attr")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => return Err(Error::new_spanned(meta, "expected #[attr(...)]")),
+        };
+        for nested in list.nested {
+            let meta = match nested {
+                NestedMeta::Meta(Meta::NameValue(meta)) => meta,
+                nested => return Err(Error::new_spanned(nested, "expected `key = value`")),
+            };
+            let key = meta.ident.to_string();
+            match (key.as_str(), &meta.lit) {
+                ("id", Lit::Str(s)) => opts.id = Some(s.value()),
+                ("typ", Lit::Str(s)) => opts.typ = Some(s.value()),
+                ("name", Lit::Str(s)) => opts.name = Some(s.value()),
+                ("description", Lit::Str(s)) => opts.description = Some(s.value()),
+                ("cast", Lit::Str(s)) => opts.cast = Some(s.parse()?),
+                ("size", Lit::Int(i)) => opts.size = Some(i.value() as usize),
+                ("bits", Lit::Int(i)) => opts.bits = Some(i.value() as usize),
+                ("skip", Lit::Int(i)) => opts.skip = i.value() as usize,
+                ("endian", Lit::Str(s)) => {
+                    opts.little_endian = match s.value().as_str() {
+                        "big" => Some(false),
+                        "little" => Some(true),
+                        _ => return Err(Error::new_spanned(s, "expected \"big\" or \"little\"")),
+                    }
+                }
+                _ => return Err(Error::new_spanned(&meta, "unknown attribute")),
+            }
+        }
+    }
+    Ok(opts)
+}
+
+fn type_name(ty: &Type) -> String {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .iter()
+            .last()
+            .map(|seg| seg.ident.to_string())
+            .unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+fn camel_case(name: &str) -> String {
+    let mut result = String::new();
+    let mut upper = false;
+    for c in name.chars() {
+        if c == '_' {
+            upper = !result.is_empty();
+        } else if upper {
+            result.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// Returns the cast and the size in bytes for the primitive type.
+fn primitive_cast(name: &str, little_endian: bool) -> Option<(TokenStream2, usize)> {
+    let (cast, size) = match (name, little_endian) {
+        ("u8", _) => (quote!(UInt8), 1),
+        ("i8", _) => (quote!(Int8), 1),
+        ("u16", false) => (quote!(UInt16BE), 2),
+        ("u16", true) => (quote!(UInt16LE), 2),
+        ("i16", false) => (quote!(Int16BE), 2),
+        ("i16", true) => (quote!(Int16LE), 2),
+        ("u32", false) => (quote!(UInt32BE), 4),
+        ("u32", true) => (quote!(UInt32LE), 4),
+        ("i32", false) => (quote!(Int32BE), 4),
+        ("i32", true) => (quote!(Int32LE), 4),
+        ("f32", false) => (quote!(Float32BE), 4),
+        ("f32", true) => (quote!(Float32LE), 4),
+        ("u64", false) => (quote!(UInt64BE), 8),
+        ("u64", true) => (quote!(UInt64LE), 8),
+        ("i64", false) => (quote!(Int64BE), 8),
+        ("i64", true) => (quote!(Int64LE), 8),
+        ("f64", false) => (quote!(Float64BE), 8),
+        ("f64", true) => (quote!(Float64LE), 8),
+        _ => return None,
+    };
+    Some((quote!(::genet_sdk::cast::#cast()), size))
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let container = parse_options(&input.attrs)?;
+    let prefix = container.id.clone().ok_or_else(|| {
+        Error::new(
+            Span::call_site(),
+            "#[attr(id = \"...\")] is required on the struct",
+        )
+    })?;
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(Error::new_spanned(&input.ident, "expected named fields")),
+        },
+        _ => return Err(Error::new_spanned(&input.ident, "expected a struct")),
+    };
+
+    let mut offset = 0;
+    let mut headers = Vec::new();
+    let mut values = Vec::new();
+    let mut getters = Vec::new();
+    for (index, field) in fields.iter().enumerate() {
+        let opts = parse_options(&field.attrs)?;
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let vis = &field.vis;
+        let name = type_name(ty);
+        let little_endian = opts
+            .little_endian
+            .or(container.little_endian)
+            .unwrap_or(false);
+        offset += opts.skip * 8;
+
+        let (cast, bits) = if let Some(bits) = opts.bits {
+            let start = offset % 8;
+            let size = (start + bits + 7) / 8;
+            let cast = match &opts.cast {
+                Some(cast) => quote!(#cast),
+                None => {
+                    let reader = match size {
+                        1 => "u8",
+                        2 => "u16",
+                        4 => "u32",
+                        8 => "u64",
+                        _ => {
+                            return Err(Error::new_spanned(
+                                field,
+                                "bit field must fit in 1, 2, 4 or 8 bytes",
+                            ))
+                        }
+                    };
+                    let (reader, _) = primitive_cast(reader, little_endian).unwrap();
+                    let shift = (size * 8 - start - bits) as u32;
+                    let mask = if bits >= 64 {
+                        u64::max_value()
+                    } else {
+                        (1u64 << bits) - 1
+                    };
+                    if name == "bool" {
+                        quote!(::genet_sdk::cast::Map::map(#reader, |v| {
+                            (u64::from(v) >> #shift) & #mask != 0
+                        }))
+                    } else {
+                        quote!(::genet_sdk::cast::Map::map(#reader, |v| {
+                            ((u64::from(v) >> #shift) & #mask) as #ty
+                        }))
+                    }
+                }
+            };
+            (cast, bits)
+        } else {
+            if offset % 8 != 0 {
+                return Err(Error::new_spanned(
+                    field,
+                    "field must be byte-aligned; use `bits` for bit fields",
+                ));
+            }
+            let primitive = primitive_cast(&name, little_endian);
+            let size = opts
+                .size
+                .or_else(|| primitive.as_ref().map(|(_, size)| *size));
+            let size = match size {
+                Some(size) => size,
+                None => return Err(Error::new_spanned(field, "`size` or `bits` is required")),
+            };
+            let cast = match (&opts.cast, primitive, name.as_str()) {
+                (Some(cast), _, _) => quote!(#cast),
+                (None, Some((cast, _)), _) => cast,
+                (None, None, "ByteSlice") => quote!(::genet_sdk::cast::ByteSlice()),
+                _ => return Err(Error::new_spanned(field, "`cast` is required")),
+            };
+            (cast, size * 8)
+        };
+
+        let id = opts
+            .id
+            .clone()
+            .unwrap_or_else(|| format!("{}.{}", prefix, camel_case(&ident.to_string())));
+        let typ = opts.typ.as_ref().map(|typ| quote!(.typ(#typ)));
+        let attr_name = opts.name.as_ref().map(|name| quote!(.name(#name)));
+        let description = opts
+            .description
+            .as_ref()
+            .map(|desc| quote!(.description(#desc)));
+        let (start, end) = (offset, offset + bits);
+        offset += bits;
+
+        headers.push(quote! {
+            ::genet_sdk::attr::Attr::builder(::genet_sdk::fixed::Fixed::new(
+                ::genet_sdk::attr::AttrClass::builder(#id)
+                    #typ
+                    #attr_name
+                    #description
+                    .cast(#cast)
+                    .build(),
+            ))
+            .bit_range(0, #start..#end)
+            .build()
+        });
+        values.push(quote! {
+            #ident: ::genet_sdk::variant::Value::<#ty>::try_into(headers[#index].try_get(layer)?)?
+        });
+        getters.push(quote! {
+            /// Reads the field from the layer.
+            #vis fn #ident(layer: &::genet_sdk::layer::Layer) -> ::genet_sdk::result::Result<#ty> {
+                let headers = <Self as ::genet_sdk::attr::AttrStruct>::headers();
+                Ok(::genet_sdk::variant::Value::<#ty>::try_into(headers[#index].try_get(layer)?)?)
+            }
+        });
+    }
+
+    let ident = &input.ident;
+    let size = (offset + 7) / 8;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::genet_sdk::attr::AttrStruct for #ident #ty_generics #where_clause {
+            const SIZE: usize = #size;
+
+            fn headers() -> &'static [::genet_sdk::attr::Attr] {
+                ::genet_sdk::lazy_static! {
+                    static ref HEADERS: Vec<::genet_sdk::attr::Attr> = vec![#(#headers),*];
+                }
+                &HEADERS
+            }
+
+            fn parse(layer: &::genet_sdk::layer::Layer) -> ::genet_sdk::result::Result<Self> {
+                let headers = Self::headers();
+                Ok(#ident {
+                    #(#values),*
+                })
+            }
+        }
+
+        #[allow(dead_code)]
+        impl #impl_generics #ident #ty_generics #where_clause {
+            #(#getters)*
+        }
+    })
+}
@@ -1,14 +1,202 @@
 use context::Context;
-use genet_abi::{token::Token, variant::Variant};
-use variant::VariantExt;
+use genet_abi::{
+    attr::Attr,
+    layer::{Layer, Severity},
+    token::Token,
+    variant::Variant,
+};
+use regex::{self, bytes::Regex};
+use std::{fmt, net::IpAddr};
+use variant::{timestamp, VariantExt};
+
+/// Compiled regular expression for the `=~` operator.
+#[derive(Clone, Debug)]
+pub struct Pattern(Regex);
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Result<Pattern, regex::Error> {
+        Regex::new(pattern).map(Pattern)
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub fn is_match(&self, data: &[u8]) -> bool {
+        self.0.is_match(data)
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Pattern) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+/// IP network in CIDR notation for the `in` operator.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Network {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Network {
+    pub fn new(addr: IpAddr, prefix: u8) -> Option<Network> {
+        let bits = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix <= bits {
+            Some(Network { addr, prefix })
+        } else {
+            None
+        }
+    }
+
+    fn octets(&self) -> Vec<u8> {
+        match self.addr {
+            IpAddr::V4(addr) => addr.octets().to_vec(),
+            IpAddr::V6(addr) => addr.octets().to_vec(),
+        }
+    }
+
+    /// Returns true if the address has the same family and network prefix.
+    pub fn contains(&self, addr: &[u8]) -> bool {
+        let network = self.octets();
+        if addr.len() != network.len() {
+            return false;
+        }
+        let bytes = (self.prefix / 8) as usize;
+        let bits = self.prefix % 8;
+        if addr[..bytes] != network[..bytes] {
+            return false;
+        }
+        bits == 0 || {
+            let mask = 0xffu8 << (8 - bits);
+            addr[bytes] & mask == network[bytes] & mask
+        }
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// Built-in function for strings and byte sequences.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Function {
+    Contains,
+    StartsWith,
+    EndsWith,
+    Lower,
+    Upper,
+    Len,
+}
+
+impl Function {
+    pub fn from_name(name: &str) -> Option<Function> {
+        match name {
+            "contains" => Some(Function::Contains),
+            "startswith" => Some(Function::StartsWith),
+            "endswith" => Some(Function::EndsWith),
+            "lower" => Some(Function::Lower),
+            "upper" => Some(Function::Upper),
+            "len" => Some(Function::Len),
+            _ => None,
+        }
+    }
+
+    pub fn all() -> &'static [Function] {
+        &[
+            Function::Contains,
+            Function::StartsWith,
+            Function::EndsWith,
+            Function::Lower,
+            Function::Upper,
+            Function::Len,
+        ]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Function::Contains => "contains",
+            Function::StartsWith => "startswith",
+            Function::EndsWith => "endswith",
+            Function::Lower => "lower",
+            Function::Upper => "upper",
+            Function::Len => "len",
+        }
+    }
+
+    /// Returns the number of the arguments.
+    pub fn arity(self) -> usize {
+        match self {
+            Function::Contains | Function::StartsWith | Function::EndsWith => 2,
+            Function::Lower | Function::Upper | Function::Len => 1,
+        }
+    }
+
+    pub fn call(self, args: &[Variant]) -> Variant {
+        match self {
+            Function::Contains => test_bytes(&args[0], &args[1], |s, sub| {
+                sub.is_empty() || s.windows(sub.len()).any(|w| w == sub)
+            }),
+            Function::StartsWith => test_bytes(&args[0], &args[1], |s, sub| s.starts_with(sub)),
+            Function::EndsWith => test_bytes(&args[0], &args[1], |s, sub| s.ends_with(sub)),
+            Function::Lower => match &args[0] {
+                Variant::String(s) => Variant::String(s.to_lowercase().into_boxed_str()),
+                v => bytes(v).map_or(Variant::Nil, |b| {
+                    Variant::Buffer(b.to_ascii_lowercase().into_boxed_slice())
+                }),
+            },
+            Function::Upper => match &args[0] {
+                Variant::String(s) => Variant::String(s.to_uppercase().into_boxed_str()),
+                v => bytes(v).map_or(Variant::Nil, |b| {
+                    Variant::Buffer(b.to_ascii_uppercase().into_boxed_slice())
+                }),
+            },
+            Function::Len => match &args[0] {
+                Variant::String(s) => Variant::UInt64(s.chars().count() as u64),
+                v => bytes(v).map_or(Variant::Nil, |b| Variant::UInt64(b.len() as u64)),
+            },
+        }
+    }
+}
+
+/// Returns the content of the string or byte value.
+fn bytes(value: &Variant) -> Option<&[u8]> {
+    match value {
+        Variant::String(s) => Some(s.as_bytes()),
+        Variant::Buffer(b) => Some(&**b),
+        Variant::Slice(s) => Some(&**s),
+        _ => None,
+    }
+}
+
+fn test_bytes<F: Fn(&[u8], &[u8]) -> bool>(lhs: &Variant, rhs: &Variant, f: F) -> Variant {
+    match (bytes(lhs), bytes(rhs)) {
+        (Some(l), Some(r)) => Variant::Bool(f(l, r)),
+        _ => Variant::Nil,
+    }
+}
 
 #[derive(PartialEq, Clone, Debug)]
 pub enum Expr {
     Literal(Variant),
     Token(Token),
+    Indexed(Token, usize, Option<Token>),
     Macro(String),
+    Call(Function, Vec<Expr>),
     CmpEq(Box<Expr>, Box<Expr>),
     CmpNotEq(Box<Expr>, Box<Expr>),
+    CmpMatch(Box<Expr>, Pattern),
+    CmpIn(Box<Expr>, Network),
+
+    /// Tests whether the timestamp is in the range `start..end` in nanoseconds.
+    Within(Box<Expr>, u64, u64),
+
     CmpLt(Box<Expr>, Box<Expr>),
     CmpGt(Box<Expr>, Box<Expr>),
     CmpLte(Box<Expr>, Box<Expr>),
@@ -24,8 +212,11 @@ impl Expr {
     pub fn eval(&self, ctx: &Context) -> Variant {
         match self {
             Expr::Literal(v) => v.clone(),
-            Expr::CmpEq(l, r) => Variant::Bool(l.eval(ctx).op_eq(&r.eval(ctx))),
-            Expr::CmpNotEq(l, r) => Variant::Bool(!l.eval(ctx).op_eq(&r.eval(ctx))),
+            Expr::CmpEq(l, r) => Variant::Bool(cmp_eq(l, r, ctx)),
+            Expr::CmpNotEq(l, r) => Variant::Bool(!cmp_eq(l, r, ctx)),
+            Expr::CmpMatch(l, pattern) => Variant::Bool(cmp_match(l, pattern, ctx)),
+            Expr::CmpIn(l, network) => Variant::Bool(cmp_in(l, network, ctx)),
+            Expr::Within(l, start, end) => Variant::Bool(value_within(&l.eval(ctx), *start, *end)),
             Expr::CmpLt(l, r) => Variant::Bool(l.eval(ctx).op_lt(&r.eval(ctx))),
             Expr::CmpGt(l, r) => Variant::Bool(l.eval(ctx).op_gt(&r.eval(ctx))),
             Expr::CmpLte(l, r) => Variant::Bool(l.eval(ctx).op_lte(&r.eval(ctx))),
@@ -55,9 +246,191 @@ impl Expr {
                         }
                     }
                 }
+                link(*t, ctx)
+                    .or_else(|| frame_time(*t, ctx))
+                    .or_else(|| frame_note(*t, ctx))
+                    .unwrap_or_else(|| annotation(*t, ctx))
+            }
+            Expr::Indexed(id, index, child) => {
+                for layer in ctx.layers().iter().rev() {
+                    if let Some(attr) = indexed_attr(layer, *id, *index, *child) {
+                        if let Ok(val) = attr.try_get(layer) {
+                            return val;
+                        }
+                    }
+                }
                 Variant::Nil
             }
             Expr::Macro(_) => Variant::Nil,
+            Expr::Call(func, args) => {
+                let args = args.iter().map(|arg| arg.eval(ctx)).collect::<Vec<_>>();
+                func.call(&args)
+            }
+        }
+    }
+
+    /// Returns the symbolic name of the attribute value.
+    pub fn label(&self, ctx: &Context) -> Option<String> {
+        for layer in ctx.layers().iter().rev() {
+            let attr = match self {
+                Expr::Token(t) => layer
+                    .headers()
+                    .iter()
+                    .chain(layer.attrs().iter())
+                    .find(|a| a.id() == *t)
+                    .map(|a| &**a),
+                Expr::Indexed(id, index, child) => indexed_attr(layer, *id, *index, *child),
+                _ => return None,
+            };
+            if let Some(label) = attr.and_then(|attr| attr.label(layer)) {
+                return Some(label.to_string());
+            }
+        }
+        None
+    }
+}
+
+/// Returns the index of the linked frame.
+fn link(id: Token, ctx: &Context) -> Option<Variant> {
+    ctx.layers()
+        .iter()
+        .rev()
+        .flat_map(|layer| layer.links().iter())
+        .chain(ctx.links().iter())
+        .find(|link| link.id() == id)
+        .map(|link| Variant::UInt64(u64::from(link.frame())))
+}
+
+/// Evaluates the pseudo-attribute `frame.time`,
+/// which is the first timestamp attribute in the frame.
+fn frame_time(id: Token, ctx: &Context) -> Option<Variant> {
+    if id != token!("frame.time") {
+        return None;
+    }
+    let typ = token!("@datetime:unix");
+    ctx.layers()
+        .iter()
+        .flat_map(|layer| {
+            layer
+                .headers()
+                .iter()
+                .chain(layer.attrs().iter())
+                .filter(move |attr| attr.typ() == typ)
+                .filter_map(move |attr| attr.try_get(layer).ok())
+        })
+        .filter_map(|value| timestamp(&value))
+        .next()
+        .map(Variant::Timestamp)
+}
+
+/// Evaluates the pseudo-attributes `frame.marked` and `frame.comment`,
+/// which are attached to the frame by the user.
+fn frame_note(id: Token, ctx: &Context) -> Option<Variant> {
+    match id {
+        id if id == token!("frame.marked") => Some(Variant::Bool(ctx.marked())),
+        id if id == token!("frame.comment") && !ctx.comment().is_empty() => {
+            Some(Variant::String(ctx.comment().into()))
+        }
+        _ => None,
+    }
+}
+
+/// Evaluates the annotation pseudo-attributes `_.error`, `_.warn` and `_.note`.
+///
+/// The value is the first message of the given severity, or nil.
+fn annotation(id: Token, ctx: &Context) -> Variant {
+    let severity = match id {
+        id if id == token!("_.error") => Severity::Error,
+        id if id == token!("_.warn") => Severity::Warn,
+        id if id == token!("_.note") => Severity::Note,
+        _ => return Variant::Nil,
+    };
+    ctx.layers()
+        .iter()
+        .flat_map(|layer| layer.annotations().iter())
+        .find(|a| a.severity() == severity)
+        .map_or(Variant::Nil, |a| Variant::String(a.message().into()))
+}
+
+/// Compares the values, or the label of the attribute with the string literal.
+fn cmp_eq(lhs: &Expr, rhs: &Expr, ctx: &Context) -> bool {
+    value_eq(
+        &lhs.eval(ctx),
+        &rhs.eval(ctx),
+        || lhs.label(ctx),
+        || rhs.label(ctx),
+    )
+}
+
+/// Compares the values, or the label of one side with the string on the other side.
+///
+/// The labels are resolved only when needed.
+pub fn value_eq<L, R>(l: &Variant, r: &Variant, lhs_label: L, rhs_label: R) -> bool
+where
+    L: FnOnce() -> Option<String>,
+    R: FnOnce() -> Option<String>,
+{
+    if l.op_eq(r) {
+        return true;
+    }
+    match (l, r) {
+        (Variant::String(_), Variant::String(_)) => false,
+        (_, Variant::String(s)) => lhs_label().map_or(false, |label| *label == **s),
+        (Variant::String(s), _) => rhs_label().map_or(false, |label| *label == **s),
+        _ => false,
+    }
+}
+
+/// Matches the string or byte value, or the label of the attribute with the pattern.
+fn cmp_match(lhs: &Expr, pattern: &Pattern, ctx: &Context) -> bool {
+    value_match(&lhs.eval(ctx), pattern, || lhs.label(ctx))
+}
+
+/// Matches the string or byte value, or the label with the pattern.
+pub fn value_match<L>(value: &Variant, pattern: &Pattern, label: L) -> bool
+where
+    L: FnOnce() -> Option<String>,
+{
+    let matched = match value {
+        Variant::String(s) => pattern.is_match(s.as_bytes()),
+        Variant::Buffer(b) => pattern.is_match(b),
+        Variant::Slice(s) => pattern.is_match(s),
+        _ => false,
+    };
+    matched || label().map_or(false, |label| pattern.is_match(label.as_bytes()))
+}
+
+/// Tests whether the address value belongs to the network.
+fn cmp_in(lhs: &Expr, network: &Network, ctx: &Context) -> bool {
+    value_in(&lhs.eval(ctx), network)
+}
+
+/// Tests whether the address value belongs to the network.
+pub fn value_in(value: &Variant, network: &Network) -> bool {
+    match value {
+        Variant::Slice(s) => network.contains(s),
+        Variant::Buffer(b) => network.contains(b),
+        Variant::Ipv6Addr(addr) => network.contains(&addr.octets()),
+        _ => false,
+    }
+}
+
+/// Tests whether the timestamp value is in the range.
+pub fn value_within(value: &Variant, start: u64, end: u64) -> bool {
+    timestamp(value).map_or(false, |ns| ns >= start && ns < end)
+}
+
+fn indexed_attr(layer: &Layer, id: Token, index: usize, child: Option<Token>) -> Option<&Attr> {
+    match (layer.attr_at(id, index), child) {
+        (Some(attr), None) => Some(attr),
+        (Some(parent), Some(child)) => {
+            let range = parent.bit_range();
+            layer.attrs_by_id(child).find(|attr| {
+                let r = attr.bit_range();
+                r.start >= range.start && r.end <= range.end
+            })
         }
+        (None, Some(child)) => layer.attr_at(child, index),
+        (None, None) => None,
     }
 }
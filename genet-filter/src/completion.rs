@@ -0,0 +1,230 @@
+//! Completion candidates for partial filter expressions.
+
+use ast::Function;
+use genet_abi::token::Token;
+
+const OPERATORS: &[(&str, &str)] = &[
+    ("==", "Equal"),
+    ("!=", "Not equal"),
+    ("<", "Less than"),
+    ("<=", "Less than or equal"),
+    (">", "Greater than"),
+    (">=", "Greater than or equal"),
+    ("=~", "Matches the regular expression"),
+    ("in", "In the network"),
+    ("within", "In the time range"),
+    ("&&", "Logical AND"),
+    ("||", "Logical OR"),
+];
+
+const COMPARISONS: &[&str] = &["==", "!=", "<=", ">=", "<", ">"];
+
+const LITERALS: &[&str] = &["true", "false", "nil"];
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Attr,
+    Function,
+    Operator,
+    Value,
+}
+
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct Candidate {
+    pub kind: Kind,
+    pub text: String,
+    pub description: String,
+}
+
+impl Candidate {
+    fn new<T: Into<String>, D: Into<String>>(kind: Kind, text: T, description: D) -> Candidate {
+        Candidate {
+            kind,
+            text: text.into(),
+            description: description.into(),
+        }
+    }
+}
+
+/// A known value of an attribute, such as a decoder trigger.
+#[derive(Clone, Debug)]
+pub struct Hint {
+    pub attr: String,
+    pub value: String,
+    pub description: String,
+}
+
+/// Candidates to replace the byte range `start..end` of the filter.
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct Completion {
+    pub start: usize,
+    pub end: usize,
+    pub candidates: Vec<Candidate>,
+}
+
+fn is_word(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '[' || c == ']'
+}
+
+fn is_operator(c: char) -> bool {
+    "=!<>&|~".contains(c)
+}
+
+fn is_member(id: &str) -> bool {
+    id.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+/// Returns the candidates for the word at the cursor.
+///
+/// `cursor` is a byte offset in the filter.
+pub fn complete(filter: &str, cursor: usize, hints: &[Hint]) -> Completion {
+    let mut cursor = cursor.min(filter.len());
+    while !filter.is_char_boundary(cursor) {
+        cursor -= 1;
+    }
+    let before = &filter[..cursor];
+    let operator = before.ends_with(is_operator);
+    let class: fn(char) -> bool = if operator { is_operator } else { is_word };
+    let start = before.trim_end_matches(class).len();
+    let end = cursor + filter[cursor..].len() - filter[cursor..].trim_start_matches(class).len();
+    let prefix = &filter[start..cursor];
+    let prev = filter[..start].trim_end();
+
+    let candidates = if operator {
+        operators(prefix)
+    } else if prev.is_empty() || ["(", ",", "&&", "||"].iter().any(|op| prev.ends_with(op)) {
+        operands(prefix)
+    } else if let Some(op) = COMPARISONS.iter().find(|op| prev.ends_with(*op)) {
+        let lhs = prev[..prev.len() - op.len()].trim_end();
+        let attr = &lhs[lhs.trim_end_matches(is_word).len()..];
+        let mut candidates = values(attr, prefix, hints);
+        candidates.extend(operands(prefix));
+        candidates
+    } else if prev.ends_with('!') {
+        operands(prefix)
+    } else if prev.ends_with("=~") || prev.ends_with(" in") || prev.ends_with(" within") {
+        Vec::new()
+    } else {
+        operators(prefix)
+    };
+    Completion {
+        start,
+        end,
+        candidates,
+    }
+}
+
+fn operators(prefix: &str) -> Vec<Candidate> {
+    OPERATORS
+        .iter()
+        .filter(|(op, _)| op.starts_with(prefix))
+        .map(|(op, desc)| Candidate::new(Kind::Operator, *op, *desc))
+        .collect()
+}
+
+fn operands(prefix: &str) -> Vec<Candidate> {
+    let tokens = match prefix.rfind('.') {
+        Some(index) => Token::descendants(&prefix[..index]),
+        None => Token::all().collect(),
+    };
+    let mut attrs = tokens
+        .into_iter()
+        .map(|token| token.to_string())
+        .filter(|id| id.starts_with(prefix) && is_member(id))
+        .collect::<Vec<_>>();
+    attrs.sort();
+    attrs.dedup();
+
+    let functions = Function::all()
+        .iter()
+        .map(|func| func.name())
+        .filter(|name| name.starts_with(prefix))
+        .map(|name| Candidate::new(Kind::Function, format!("{}(", name), ""));
+    let literals = LITERALS
+        .iter()
+        .filter(|lit| lit.starts_with(prefix))
+        .map(|lit| Candidate::new(Kind::Value, *lit, ""));
+    attrs
+        .into_iter()
+        .filter(|id| !LITERALS.contains(&id.as_str()))
+        .map(|id| Candidate::new(Kind::Attr, id, ""))
+        .chain(functions)
+        .chain(literals)
+        .collect()
+}
+
+fn values(attr: &str, prefix: &str, hints: &[Hint]) -> Vec<Candidate> {
+    hints
+        .iter()
+        .filter(|hint| hint.attr == attr && hint.value.starts_with(prefix))
+        .map(|hint| Candidate::new(Kind::Value, hint.value.as_str(), hint.description.as_str()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use completion::*;
+    use genet_abi::token::Token;
+
+    fn texts(completion: &Completion) -> Vec<&str> {
+        completion
+            .candidates
+            .iter()
+            .map(|c| c.text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn attr() {
+        Token::from("c0a1e5.src");
+        Token::from("c0a1e5.flags.syn");
+        let completion = complete("c0a1e5.", 7, &[]);
+        assert_eq!((completion.start, completion.end), (0, 7));
+        assert!(texts(&completion).contains(&"c0a1e5.flags.syn"));
+        assert!(texts(&completion).contains(&"c0a1e5.src"));
+
+        let completion = complete("true && c0a1e5.s == 1", 16, &[]);
+        assert_eq!((completion.start, completion.end), (8, 16));
+        assert_eq!(texts(&completion), vec!["c0a1e5.src"]);
+
+        let completion = complete("le", 2, &[]);
+        assert!(texts(&completion).contains(&"len("));
+    }
+
+    #[test]
+    fn operator() {
+        let completion = complete("tcp.src ", 8, &[]);
+        assert_eq!(completion.candidates.len(), 11);
+        let completion = complete("tcp.src <", 9, &[]);
+        assert_eq!((completion.start, completion.end), (8, 9));
+        assert_eq!(texts(&completion), vec!["<", "<="]);
+        let completion = complete("ipv4.src i", 10, &[]);
+        assert_eq!(texts(&completion), vec!["in"]);
+    }
+
+    #[test]
+    fn value() {
+        let hints = vec![
+            Hint {
+                attr: "eth.type".into(),
+                value: "0x0800".into(),
+                description: "IPv4".into(),
+            },
+            Hint {
+                attr: "eth.type".into(),
+                value: "0x86dd".into(),
+                description: "IPv6".into(),
+            },
+        ];
+        let completion = complete("eth.type == 0x8", 15, &hints);
+        assert_eq!(texts(&completion), vec!["0x86dd"]);
+        assert_eq!(completion.candidates[0].kind, Kind::Value);
+        assert!(complete(r#"http.host =~ "#, 13, &hints)
+            .candidates
+            .is_empty());
+    }
+}
@@ -1,15 +1,51 @@
-use genet_abi::{fixed::MutFixed, layer::Layer};
+use genet_abi::{
+    fixed::MutFixed,
+    layer::{Layer, Link},
+};
 
 pub struct Context<'a> {
     layers: &'a [MutFixed<Layer>],
+    links: &'a [Link],
+    marked: bool,
+    comment: &'a str,
 }
 
 impl<'a> Context<'a> {
     pub fn new(layers: &'a [MutFixed<Layer>]) -> Self {
-        Context { layers }
+        Context {
+            layers,
+            links: &[],
+            marked: false,
+            comment: "",
+        }
+    }
+
+    /// Sets the links to the frame from the other frames.
+    pub fn with_links(mut self, links: &'a [Link]) -> Self {
+        self.links = links;
+        self
+    }
+
+    /// Sets the mark and the comment attached to the frame by the user.
+    pub fn with_note(mut self, marked: bool, comment: &'a str) -> Self {
+        self.marked = marked;
+        self.comment = comment;
+        self
     }
 
     pub fn layers(&self) -> &'a [MutFixed<Layer>] {
         self.layers
     }
+
+    pub fn links(&self) -> &'a [Link] {
+        self.links
+    }
+
+    pub fn marked(&self) -> bool {
+        self.marked
+    }
+
+    pub fn comment(&self) -> &'a str {
+        self.comment
+    }
 }
@@ -0,0 +1,130 @@
+//! Keys for indexing the attribute values.
+//!
+//! Two values compared equal by the `==` operator always have the same key,
+//! so an index lookup never misses a frame which the filter would match.
+
+use ast::Expr;
+use context::Context;
+use genet_abi::{token::Token, variant::Variant};
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+
+/// A hashable and ordered key of an attribute value.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IndexKey {
+    Bool(bool),
+
+    /// The integers and the byte sequences up to 64 bits.
+    Int(u64),
+
+    /// The byte sequences longer than 64 bits without the leading zeros.
+    Bytes(Box<[u8]>),
+    String(Box<str>),
+}
+
+impl IndexKey {
+    /// Returns the key of the value,
+    /// or None if the value cannot be indexed and needs to be tested directly.
+    pub fn new(value: &Variant) -> Option<IndexKey> {
+        match value {
+            Variant::Bool(v) => Some(IndexKey::Bool(*v)),
+            Variant::UInt64(v) | Variant::Timestamp(v) => Some(IndexKey::Int(*v)),
+            Variant::Int64(v) if *v >= 0 => Some(IndexKey::Int(*v as u64)),
+            Variant::String(s) => Some(IndexKey::String(s.clone())),
+            Variant::BigInt(v) => {
+                let v = BigInt::from_signed_bytes_be(v);
+                if let Some(v) = v.to_u64() {
+                    Some(IndexKey::Int(v))
+                } else if v.to_i64().is_some() {
+                    None
+                } else {
+                    v.to_biguint().map(|v| bytes(&v.to_bytes_be()))
+                }
+            }
+            Variant::Buffer(v) => Some(bytes(v)),
+            Variant::Slice(v) => Some(bytes(v)),
+            Variant::MacAddr(v) => Some(bytes(v)),
+            Variant::Ipv6Addr(v) => Some(bytes(&v.octets())),
+            _ => None,
+        }
+    }
+}
+
+fn bytes(data: &[u8]) -> IndexKey {
+    let data = &data[data.iter().take_while(|b| **b == 0).count()..];
+    if data.len() <= 8 {
+        IndexKey::Int(data.iter().fold(0, |acc, b| (acc << 8) | u64::from(*b)))
+    } else {
+        IndexKey::Bytes(data.to_vec().into_boxed_slice())
+    }
+}
+
+/// Evaluates the attribute as the filter does.
+pub fn attr_value(id: Token, ctx: &Context) -> Variant {
+    Expr::Token(id).eval(ctx)
+}
+
+/// Finds an equality between an attribute and a literal
+/// which the expression requires to be true.
+///
+/// The string literals are excluded since they may match the labels.
+pub fn hint(expr: &Expr) -> Option<(Token, IndexKey)> {
+    match expr {
+        Expr::CmpEq(l, r) => match (&**l, &**r) {
+            (Expr::Token(id), Expr::Literal(value)) | (Expr::Literal(value), Expr::Token(id)) => {
+                match value {
+                    Variant::String(_) => None,
+                    _ => IndexKey::new(value).map(|key| (*id, key)),
+                }
+            }
+            _ => None,
+        },
+        Expr::LogicalAnd(l, r) => hint(l).or_else(|| hint(r)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use genet_abi::{token::Token, variant::Variant};
+    use index::{hint, IndexKey};
+    use parser::parse;
+    use std::net::Ipv6Addr;
+    use variant::VariantExt;
+
+    #[test]
+    fn key() {
+        let values = vec![
+            Variant::UInt64(0x0a01_0203),
+            Variant::Int64(0x0a01_0203),
+            Variant::Buffer(vec![10, 1, 2, 3].into_boxed_slice()),
+            Variant::Buffer(vec![0, 10, 1, 2, 3].into_boxed_slice()),
+            Variant::BigInt(vec![10, 1, 2, 3].into_boxed_slice()),
+        ];
+        for a in &values {
+            for b in &values {
+                assert!(a.op_eq(b));
+                assert_eq!(IndexKey::new(a), IndexKey::new(b));
+            }
+        }
+
+        let addr = "2001:db8::1".parse::<Ipv6Addr>().unwrap();
+        assert_eq!(
+            IndexKey::new(&Variant::Ipv6Addr(addr)),
+            IndexKey::new(&Variant::Buffer(addr.octets().to_vec().into_boxed_slice()))
+        );
+        assert_eq!(IndexKey::new(&Variant::Int64(-1)), None);
+        assert_eq!(IndexKey::new(&Variant::Float64(1.0)), None);
+        assert_eq!(IndexKey::new(&Variant::Nil), None);
+    }
+
+    #[test]
+    fn hints() {
+        let key = IndexKey::new(&Variant::UInt64(0x0a01_0203));
+        let expr = parse("tcp && ipv4.src == 10.1.2.3").unwrap();
+        assert_eq!(hint(&expr), key.map(|key| (Token::from("ipv4.src"), key)));
+        assert_eq!(hint(&parse("ipv4.src == 10.1.2.3 || tcp").unwrap()), None);
+        assert_eq!(hint(&parse(r#"eth.type == "IPv4""#).unwrap()), None);
+        assert_eq!(hint(&parse("udp.src == -1").unwrap()), None);
+    }
+}
@@ -0,0 +1,263 @@
+//! Native code generation for filter programs with Cranelift.
+//!
+//! Only the control flow is compiled: the branches and the dispatch become
+//! native code, while every other instruction, including constants and
+//! comparisons, calls back into the interpreter. The speedup is therefore
+//! limited to the dispatch overhead of the interpreter loop.
+//!
+//! A panic in a callback stops the native code and is resumed by `Native::run`,
+//! so that it never unwinds through the generated frames.
+//!
+//! This module is available with the `jit` feature.
+
+use context::Context;
+use cranelift_codegen::{
+    ir::{condcodes::IntCC, types, AbiParam, Ebb, InstBuilder},
+    settings,
+};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_module::{Linkage, Module};
+use cranelift_simplejit::{SimpleJITBackend, SimpleJITBuilder};
+use genet_abi::variant::Variant;
+use std::{
+    any::Any,
+    cell::RefCell,
+    fmt, mem,
+    panic::{self, AssertUnwindSafe},
+    slice,
+};
+use variant::VariantExt;
+use vm::{Inst, Program};
+
+type NativeFn = extern "C" fn(*const Program, *const Context, *mut Variant);
+
+/// The return value of the callbacks when they panicked.
+const PANICKED: u8 = 2;
+
+thread_local! {
+    static PANIC: RefCell<Option<Box<Any + Send>>> = RefCell::new(None);
+}
+
+/// Calls the function and stores the payload if it panicked.
+fn guard<F: FnOnce() -> u8>(func: F) -> u8 {
+    panic::catch_unwind(AssertUnwindSafe(func)).unwrap_or_else(|payload| {
+        PANIC.with(|panic| *panic.borrow_mut() = Some(payload));
+        PANICKED
+    })
+}
+
+extern "C" fn step(
+    program: *const Program,
+    ctx: *const Context,
+    regs: *mut Variant,
+    pc: usize,
+) -> u8 {
+    guard(|| unsafe {
+        let program = &*program;
+        let regs = slice::from_raw_parts_mut(regs, program.registers);
+        program.step(pc, regs, &*ctx);
+        0
+    })
+}
+
+extern "C" fn truthy(regs: *const Variant, reg: usize) -> u8 {
+    guard(|| unsafe { (*regs.add(reg)).is_truthy() as u8 })
+}
+
+/// Native code compiled from a program.
+pub struct Native {
+    func: NativeFn,
+
+    /// Owns the executable memory of `func`.
+    _module: Module<SimpleJITBackend>,
+}
+
+// The compiled code is immutable after finalization.
+unsafe impl Send for Native {}
+unsafe impl Sync for Native {}
+
+impl Native {
+    /// Compiles the program, or returns None if the host is not supported.
+    pub fn compile(program: &Program) -> Option<Native> {
+        let isa = cranelift_native::builder()
+            .ok()?
+            .finish(settings::Flags::new(settings::builder()));
+        let mut module: Module<SimpleJITBackend> = Module::new(SimpleJITBuilder::with_isa(isa));
+        let ptr = module.target_config().pointer_type();
+
+        // The callbacks use the default calling convention of the host.
+        let mut ctx = module.make_context();
+        let mut step_sig = ctx.func.signature.clone();
+        step_sig.params = vec![AbiParam::new(ptr); 4];
+        step_sig.returns = vec![AbiParam::new(types::I8)];
+        let mut truthy_sig = ctx.func.signature.clone();
+        truthy_sig.params = vec![AbiParam::new(ptr); 2];
+        truthy_sig.returns = vec![AbiParam::new(types::I8)];
+        ctx.func.signature.params = vec![AbiParam::new(ptr); 3];
+
+        let mut builder_ctx = FunctionBuilderContext::new();
+        {
+            let mut builder = FunctionBuilder::new(&mut ctx.func, &mut builder_ctx);
+            let step_sig = builder.import_signature(step_sig);
+            let truthy_sig = builder.import_signature(truthy_sig);
+
+            // One block per instruction, and the last one returns.
+            let blocks = (0..=program.code.len())
+                .map(|_| builder.create_ebb())
+                .collect::<Vec<Ebb>>();
+            let exit = blocks[program.code.len()];
+            builder.append_ebb_params_for_function_params(blocks[0]);
+            builder.switch_to_block(blocks[0]);
+            let params = builder.ebb_params(blocks[0]).to_vec();
+            let (vm, cx, regs) = (params[0], params[1], params[2]);
+
+            for (pc, inst) in program.code.iter().enumerate() {
+                if pc > 0 {
+                    builder.switch_to_block(blocks[pc]);
+                }
+                match *inst {
+                    Inst::JumpIfFalse(reg, target) | Inst::JumpIfTrue(reg, target) => {
+                        let callee = builder.ins().iconst(ptr, truthy as usize as i64);
+                        let reg = builder.ins().iconst(ptr, reg as i64);
+                        let call = builder
+                            .ins()
+                            .call_indirect(truthy_sig, callee, &[regs, reg]);
+                        let value = builder.inst_results(call)[0];
+                        let panicked = builder.ins().icmp_imm(IntCC::Equal, value, PANICKED as i64);
+                        builder.ins().brnz(panicked, exit, &[]);
+                        if let Inst::JumpIfFalse(..) = *inst {
+                            builder.ins().brz(value, blocks[target], &[]);
+                        } else {
+                            builder.ins().brnz(value, blocks[target], &[]);
+                        }
+                    }
+                    _ => {
+                        let callee = builder.ins().iconst(ptr, step as usize as i64);
+                        let pc = builder.ins().iconst(ptr, pc as i64);
+                        let call =
+                            builder
+                                .ins()
+                                .call_indirect(step_sig, callee, &[vm, cx, regs, pc]);
+                        let value = builder.inst_results(call)[0];
+                        builder.ins().brnz(value, exit, &[]);
+                    }
+                }
+                builder.ins().jump(blocks[pc + 1], &[]);
+            }
+
+            builder.switch_to_block(exit);
+            builder.ins().return_(&[]);
+            builder.seal_all_blocks();
+            builder.finalize();
+        }
+
+        let id = module
+            .declare_function("filter", Linkage::Local, &ctx.func.signature)
+            .ok()?;
+        module.define_function(id, &mut ctx).ok()?;
+        module.clear_context(&mut ctx);
+        let code = module.finalize_function(id);
+        Some(Native {
+            func: unsafe { mem::transmute::<_, NativeFn>(code) },
+            _module: module,
+        })
+    }
+
+    pub fn run(&self, program: &Program, ctx: &Context) -> Variant {
+        let mut regs = vec![Variant::Nil; program.registers];
+        (self.func)(program, ctx, regs.as_mut_ptr());
+        if let Some(payload) = PANIC.with(|panic| panic.borrow_mut().take()) {
+            panic::resume_unwind(payload);
+        }
+        mem::replace(&mut regs[0], Variant::Nil)
+    }
+}
+
+impl fmt::Debug for Native {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Native({:p})", self.func as *const u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use context::Context;
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        cast::Cast,
+        fixed::{Fixed, MutFixed},
+        layer::{Layer, LayerClass},
+        slice::ByteSlice,
+        variant::Variant,
+    };
+    use jit::Native;
+    use parser::parse;
+    use std::io::Result;
+    use vm::Program;
+
+    #[test]
+    fn run() {
+        let ctx = Context::new(&[]);
+        for filter in &["x || false", "!(x && y) && 1 < 2", r#"len(x) == nil"#] {
+            let program = Program::compile(&parse(filter).unwrap());
+            let native = Native::compile(&program).unwrap();
+            assert_eq!(
+                native.run(&program, &ctx),
+                program.interpret(&ctx),
+                "{}",
+                filter
+            );
+        }
+    }
+
+    #[test]
+    fn layers() {
+        #[derive(Clone)]
+        struct U8Cast {}
+
+        impl Cast for U8Cast {
+            fn cast(&self, _attr: &Attr, data: &ByteSlice) -> Result<Variant> {
+                Ok(Variant::UInt64(u64::from(data[0])))
+            }
+        }
+
+        let attr = |id: &str, offset: usize| {
+            let class = Fixed::new(AttrClass::builder(id).cast(U8Cast {}).build());
+            Fixed::new(Attr::builder(class).range(offset..offset + 1).build())
+        };
+        let eth = Fixed::new(
+            LayerClass::builder("j1t")
+                .header(attr("j1t.type", 0))
+                .build(),
+        );
+        let ip = Fixed::new(
+            LayerClass::builder("j1tip")
+                .header(attr("j1tip.ttl", 0))
+                .header(attr("j1tip.proto", 1))
+                .build(),
+        );
+        let layers = vec![
+            MutFixed::new(Layer::new(eth, ByteSlice::from(&[8][..]))),
+            MutFixed::new(Layer::new(ip, ByteSlice::from(&[64, 6][..]))),
+        ];
+        let ctx = Context::new(&layers);
+        for filter in &[
+            "j1t",
+            "j1t.type == 8",
+            "j1tip.ttl > 32 && j1tip.proto == 6",
+            "j1tip.ttl < 32 || !j1tip.proto",
+            "j1tip.proto == 17 || j1t.type != 8",
+            "nope || j1tip",
+            "nope && j1tip",
+        ] {
+            let program = Program::compile(&parse(filter).unwrap());
+            let native = Native::compile(&program).unwrap();
+            assert_eq!(
+                native.run(&program, &ctx),
+                program.interpret(&ctx),
+                "{}",
+                filter
+            );
+        }
+    }
+}
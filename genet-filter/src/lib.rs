@@ -3,44 +3,111 @@ extern crate hwaddr;
 extern crate num_bigint;
 extern crate num_traits;
 extern crate pest;
+extern crate regex;
 extern crate serde;
 extern crate serde_json;
 
 #[macro_use]
 extern crate pest_derive;
 
+#[macro_use]
+extern crate serde_derive;
+
+#[cfg(feature = "jit")]
+extern crate cranelift_codegen;
+#[cfg(feature = "jit")]
+extern crate cranelift_frontend;
+#[cfg(feature = "jit")]
+extern crate cranelift_module;
+#[cfg(feature = "jit")]
+extern crate cranelift_native;
+#[cfg(feature = "jit")]
+extern crate cranelift_simplejit;
+
 #[macro_use]
 extern crate arrayref;
 
-use ast::Expr;
 use context::Context;
-use parser::parse;
+use genet_abi::{token::Token, variant::Variant};
+use index::IndexKey;
+use named::NamedFilters;
+use parser::parse_with;
 use result::Result;
 use std::fmt;
 use variant::VariantExt;
+use vm::Program;
+
+/// Returns the token for the string, which is registered on the first use and cached.
+macro_rules! token {
+    ($name:expr) => {{
+        use genet_abi::token::{cached, Token};
+        use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT};
+        static TOKEN: AtomicUsize = ATOMIC_USIZE_INIT;
+        cached(&TOKEN, || Token::from($name))
+    }};
+}
 
 pub mod ast;
+pub mod completion;
 pub mod context;
+pub mod index;
+#[cfg(feature = "jit")]
+pub mod jit;
+pub mod named;
 pub mod parser;
 pub mod result;
 pub mod unparser;
 pub mod variant;
+pub mod vm;
 
 #[derive(Clone, Debug)]
 pub struct Filter {
-    expr: Expr,
+    source: String,
+    program: Program,
+    hint: Option<(Token, IndexKey)>,
 }
 
 impl Filter {
     pub fn compile(filter: &str) -> Result<Filter> {
-        match parse(filter) {
-            Ok(expr) => Ok(Filter { expr }),
+        Self::compile_with(filter, &NamedFilters::new())
+    }
+
+    /// Compiles the filter which may reference the named filters.
+    pub fn compile_with(filter: &str, named: &NamedFilters) -> Result<Filter> {
+        match parse_with(filter, named) {
+            Ok(expr) => Ok(Filter {
+                source: filter.to_string(),
+                program: Program::compile(&expr),
+                hint: index::hint(&expr),
+            }),
             Err(err) => Err(Box::new(Error(format!("{}", err)))),
         }
     }
 
+    /// Returns the filter string which the filter is compiled from.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns true if the filter refers to the attribute.
+    pub fn refers<T: Into<Token>>(&self, id: T) -> bool {
+        self.program.refers(id.into())
+    }
+
     pub fn test(&self, ctx: &Context) -> bool {
-        self.expr.eval(ctx).is_truthy()
+        self.program.run(ctx).is_truthy()
+    }
+
+    /// Evaluates the filter as an expression and returns the value.
+    pub fn eval(&self, ctx: &Context) -> Variant {
+        self.program.run(ctx)
+    }
+
+    /// Returns the attribute value which the matching frames must have.
+    ///
+    /// An index on the attribute narrows down the frames to be tested.
+    pub fn index_hint(&self) -> Option<&(Token, IndexKey)> {
+        self.hint.as_ref()
     }
 }
 
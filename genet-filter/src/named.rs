@@ -0,0 +1,43 @@
+use parser;
+use result::Result;
+use std::collections::BTreeMap;
+use Error;
+
+/// Registry of the filter expressions referenced as `$name` in other filters.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct NamedFilters(BTreeMap<String, String>);
+
+fn is_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl NamedFilters {
+    pub fn new() -> NamedFilters {
+        Self::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(|filter| filter.as_str())
+    }
+
+    /// Registers the filter after checking that it can be parsed.
+    pub fn insert(&mut self, name: &str, filter: &str) -> Result<()> {
+        if !is_name(name) {
+            return Err(Box::new(Error(format!("invalid filter name: {}", name))));
+        }
+        if let Err(err) = parser::parse_with(filter, self) {
+            return Err(Box::new(Error(format!("{}", err))));
+        }
+        self.0.insert(name.to_string(), filter.to_string());
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        self.0.remove(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
@@ -1,30 +1,49 @@
-use ast::Expr;
+use ast::{Expr, Function, Network, Pattern};
 use genet_abi::{token::Token, variant::Variant};
 use hwaddr::HwAddr;
+use named::NamedFilters;
 use num_bigint::BigInt;
 use num_traits::Num;
 use pest::{
-    error::Error,
+    error::{Error, ErrorVariant},
     iterators::Pair,
     prec_climber::{Assoc, Operator, PrecClimber},
     Parser,
 };
 use serde_json;
-use std::net::{Ipv4Addr, Ipv6Addr};
-use variant::VariantExt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use variant::{parse_duration, parse_timestamp, VariantExt};
 
 #[derive(Parser)]
 #[grammar = "syntax.pest"]
 pub struct FilterParser;
 
+/// Named filters available in the expression.
+struct Scope<'a> {
+    named: &'a NamedFilters,
+
+    /// Names being expanded, to detect recursive references.
+    stack: Vec<String>,
+}
+
 pub fn parse(filter: &str) -> Result<Expr, Error<Rule>> {
-    let result = FilterParser::parse(Rule::filter, filter);
-    match result {
-        Ok(mut expr) => Ok(consume_expr(
-            expr.next().unwrap().into_inner().next().unwrap(),
-        )),
-        Err(e) => Err(e),
-    }
+    parse_with(filter, &NamedFilters::new())
+}
+
+/// Parses the filter, expanding the references to the named filters.
+pub fn parse_with(filter: &str, named: &NamedFilters) -> Result<Expr, Error<Rule>> {
+    parse_scoped(
+        filter,
+        &Scope {
+            named,
+            stack: Vec::new(),
+        },
+    )
+}
+
+fn parse_scoped(filter: &str, scope: &Scope) -> Result<Expr, Error<Rule>> {
+    let mut expr = FilterParser::parse(Rule::filter, filter)?;
+    consume_expr(expr.next().unwrap().into_inner().next().unwrap(), scope)
 }
 
 fn parse_macro(exp: String) -> Expr {
@@ -32,50 +51,133 @@ fn parse_macro(exp: String) -> Expr {
         return Expr::Literal(Variant::Buffer(addr.octets().to_vec().into_boxed_slice()));
     }
     if let Ok(addr) = exp.parse::<Ipv6Addr>() {
-        return Expr::Literal(Variant::Buffer(addr.octets().to_vec().into_boxed_slice()));
+        return Expr::Literal(Variant::Ipv6Addr(addr));
     }
     if let Ok(addr) = exp.parse::<HwAddr>() {
-        return Expr::Literal(Variant::Buffer(addr.octets().to_vec().into_boxed_slice()));
+        return Expr::Literal(Variant::MacAddr(addr.octets()));
+    }
+    if let Some(ns) = parse_timestamp(&exp) {
+        return Expr::Literal(Variant::Timestamp(ns));
     }
     Expr::Macro(exp)
 }
 
-fn consume_expr(pair: Pair<Rule>) -> Expr {
+fn consume_expr(pair: Pair<Rule>, scope: &Scope) -> Result<Expr, Error<Rule>> {
     let cmp = Operator::new(Rule::op_lt, Assoc::Left)
         | Operator::new(Rule::op_lte, Assoc::Left)
         | Operator::new(Rule::op_gt, Assoc::Left)
         | Operator::new(Rule::op_gte, Assoc::Left);
     let climber = PrecClimber::new(vec![
         cmp,
-        Operator::new(Rule::op_eq, Assoc::Left) | Operator::new(Rule::op_ne, Assoc::Left),
+        Operator::new(Rule::op_eq, Assoc::Left)
+            | Operator::new(Rule::op_ne, Assoc::Left)
+            | Operator::new(Rule::op_match, Assoc::Left),
         Operator::new(Rule::op_logical_and, Assoc::Left),
         Operator::new(Rule::op_logical_or, Assoc::Left),
     ]);
     let primary = |pair: Pair<Rule>| match pair.as_rule() {
-        Rule::primary => consume_primary(pair),
-        _ => Expr::Literal(Variant::Nil),
+        Rule::primary => consume_primary(pair, scope),
+        _ => Ok(Expr::Literal(Variant::Nil)),
     };
-    let infix = |lhs: Expr, op: Pair<Rule>, rhs: Expr| -> Expr {
-        match op.as_rule() {
-            Rule::op_lt => Expr::CmpLt(Box::new(lhs), Box::new(rhs)),
-            Rule::op_lte => Expr::CmpLte(Box::new(lhs), Box::new(rhs)),
-            Rule::op_gt => Expr::CmpGt(Box::new(lhs), Box::new(rhs)),
-            Rule::op_gte => Expr::CmpGte(Box::new(lhs), Box::new(rhs)),
-            Rule::op_eq => Expr::CmpEq(Box::new(lhs), Box::new(rhs)),
-            Rule::op_ne => Expr::CmpNotEq(Box::new(lhs), Box::new(rhs)),
-            Rule::op_logical_and => Expr::LogicalAnd(Box::new(lhs), Box::new(rhs)),
-            Rule::op_logical_or => Expr::LogicalOr(Box::new(lhs), Box::new(rhs)),
+    let infix = |lhs: Result<Expr, Error<Rule>>,
+                 op: Pair<Rule>,
+                 rhs: Result<Expr, Error<Rule>>|
+     -> Result<Expr, Error<Rule>> {
+        let (lhs, rhs) = (Box::new(lhs?), Box::new(rhs?));
+        Ok(match op.as_rule() {
+            Rule::op_lt => Expr::CmpLt(lhs, rhs),
+            Rule::op_lte => Expr::CmpLte(lhs, rhs),
+            Rule::op_gt => Expr::CmpGt(lhs, rhs),
+            Rule::op_gte => Expr::CmpGte(lhs, rhs),
+            Rule::op_eq => Expr::CmpEq(lhs, rhs),
+            Rule::op_ne => Expr::CmpNotEq(lhs, rhs),
+            Rule::op_match => Expr::CmpMatch(lhs, consume_pattern(&op, &rhs)?),
+            Rule::op_logical_and => Expr::LogicalAnd(lhs, rhs),
+            Rule::op_logical_or => Expr::LogicalOr(lhs, rhs),
             _ => Expr::Literal(Variant::Nil),
-        }
+        })
     };
     climber.climb(pair.into_inner(), primary, infix)
 }
 
-fn consume_primary(pair: Pair<Rule>) -> Expr {
+/// Compiles the right-hand side of `=~`, which must be a string literal.
+///
+/// The pattern is compiled once here and reused for every frame.
+fn consume_pattern(op: &Pair<Rule>, rhs: &Expr) -> Result<Pattern, Error<Rule>> {
+    let error =
+        |message: String| Error::new_from_span(ErrorVariant::CustomError { message }, op.as_span());
+    match rhs {
+        Expr::Literal(Variant::String(s)) => {
+            Pattern::new(s).map_err(|err| error(format!("invalid regular expression: {}", err)))
+        }
+        _ => Err(error(
+            "regular expression must be a string literal".to_string(),
+        )),
+    }
+}
+
+fn consume_network(pair: &Pair<Rule>) -> Result<Network, Error<Rule>> {
+    let mut parts = pair.as_str().splitn(2, '/');
+    let addr = parts.next().unwrap_or("").parse::<IpAddr>();
+    let prefix = parts.next().unwrap_or("").parse::<u8>();
+    match (addr, prefix) {
+        (Ok(addr), Ok(prefix)) => Network::new(addr, prefix),
+        _ => None,
+    }
+    .ok_or_else(|| {
+        Error::new_from_span(
+            ErrorVariant::CustomError {
+                message: format!("invalid network: {}", pair.as_str()),
+            },
+            pair.as_span(),
+        )
+    })
+}
+
+/// Parses the time range such as `2024-01-02T10:00..10:05`.
+///
+/// The end is an absolute time, a time on the same day or a duration from the start.
+fn consume_time_range(pair: &Pair<Rule>) -> Result<(u64, u64), Error<Rule>> {
+    let range = pair.as_str();
+    let pos = range.find("..").unwrap();
+    let (start, end) = (&range[..pos], &range[pos + 2..]);
+    let start = parse_timestamp(start);
+    let end = start.and_then(|start_ns| {
+        if end.starts_with('+') {
+            parse_duration(&end[1..]).and_then(|d| start_ns.checked_add(d))
+        } else if end.contains('-') {
+            parse_timestamp(end)
+        } else {
+            parse_timestamp(&format!("{}T{}", &range[..10], end))
+        }
+    });
+    match (start, end) {
+        (Some(start), Some(end)) if start <= end => Ok((start, end)),
+        _ => Err(Error::new_from_span(
+            ErrorVariant::CustomError {
+                message: format!("invalid time range: {}", range),
+            },
+            pair.as_span(),
+        )),
+    }
+}
+
+fn consume_primary(pair: Pair<Rule>, scope: &Scope) -> Result<Expr, Error<Rule>> {
     let mut result = None;
+    let mut network = None;
+    let mut range = None;
     for item in pair.into_inner().rev() {
         result = Some(match item.as_rule() {
-            Rule::expression => consume_expr(item),
+            Rule::ipv4_network | Rule::ipv6_network => {
+                network = Some(consume_network(&item)?);
+                continue;
+            }
+            Rule::time_range => {
+                range = Some(consume_time_range(&item)?);
+                continue;
+            }
+            Rule::op_in | Rule::op_within => continue,
+            Rule::expression => consume_expr(item, scope)?,
             Rule::op_unary_plus => Expr::UnaryPlus(Box::new(result.take().unwrap())),
             Rule::op_unary_negation => Expr::UnaryNegation(Box::new(result.take().unwrap())),
             Rule::op_logical_negation => Expr::LogicalNegation(Box::new(result.take().unwrap())),
@@ -98,15 +200,95 @@ fn consume_primary(pair: Pair<Rule>) -> Expr {
             Rule::string => Expr::Literal(Variant::String(
                 serde_json::from_str(item.as_str()).unwrap(),
             )),
+            Rule::mac_addr => Expr::Literal(
+                item.as_str()
+                    .parse::<HwAddr>()
+                    .map(|addr| Variant::MacAddr(addr.octets()))
+                    .unwrap_or(Variant::Nil),
+            ),
+            Rule::ipv6_addr => Expr::Literal(
+                item.as_str()
+                    .parse::<Ipv6Addr>()
+                    .map(Variant::Ipv6Addr)
+                    .unwrap_or(Variant::Nil),
+            ),
             Rule::macro_exp => parse_macro(item.as_str()[1..].to_string()),
             Rule::float => Expr::Literal(Variant::Float64(item.as_str().parse().unwrap())),
             Rule::nil => Expr::Literal(Variant::Nil),
             Rule::boolean => Expr::Literal(Variant::Bool(item.as_str() == "true")),
             Rule::member => Expr::Token(Token::from(item.as_str())),
+            Rule::indexed_member => consume_indexed_member(item),
+            Rule::function_call => consume_function_call(item, scope)?,
+            Rule::named_filter => consume_named_filter(&item, scope)?,
             _ => Expr::Literal(Variant::Nil),
         });
+
+        // `in` and `within` bind to the operand, before any unary operators.
+        if let Some(network) = network.take() {
+            result = result.map(|operand| Expr::CmpIn(Box::new(operand), network));
+        }
+        if let Some((start, end)) = range.take() {
+            result = result.map(|operand| Expr::Within(Box::new(operand), start, end));
+        }
     }
-    result.unwrap()
+    Ok(result.unwrap())
+}
+
+fn consume_function_call(pair: Pair<Rule>, scope: &Scope) -> Result<Expr, Error<Rule>> {
+    let span = pair.as_span();
+    let mut inner = pair.into_inner();
+    let name = inner.next().unwrap().as_str();
+    let args = inner
+        .map(|arg| consume_expr(arg, scope))
+        .collect::<Result<Vec<_>, _>>()?;
+    let message = match Function::from_name(name) {
+        Some(func) if func.arity() == args.len() => return Ok(Expr::Call(func, args)),
+        Some(func) => format!("{}() takes {} argument(s)", name, func.arity()),
+        None => format!("unknown function: {}", name),
+    };
+    Err(Error::new_from_span(
+        ErrorVariant::CustomError { message },
+        span,
+    ))
+}
+
+fn consume_named_filter(pair: &Pair<Rule>, scope: &Scope) -> Result<Expr, Error<Rule>> {
+    let name = &pair.as_str()[1..];
+    let error = |message: String| {
+        Error::new_from_span(ErrorVariant::CustomError { message }, pair.as_span())
+    };
+    if scope.stack.iter().any(|n| n == name) {
+        return Err(error(format!("recursive named filter: ${}", name)));
+    }
+    let filter = scope
+        .named
+        .get(name)
+        .ok_or_else(|| error(format!("unknown named filter: ${}", name)))?;
+    let mut stack = scope.stack.clone();
+    stack.push(name.to_string());
+    parse_scoped(
+        filter,
+        &Scope {
+            named: scope.named,
+            stack,
+        },
+    )
+    .map_err(|err| error(format!("invalid named filter ${}: {}", name, err)))
+}
+
+fn consume_indexed_member(pair: Pair<Rule>) -> Expr {
+    let mut inner = pair.into_inner();
+    let id = inner.next().unwrap().as_str();
+    let index = inner
+        .next()
+        .unwrap()
+        .as_str()
+        .parse()
+        .unwrap_or(usize::max_value());
+    let child = inner
+        .next()
+        .map(|child| Token::from(format!("{}.{}", id, child.as_str())));
+    Expr::Indexed(Token::from(id), index, child)
 }
 
 #[cfg(test)]
@@ -272,6 +454,188 @@ mod tests {
         );
     }
 
+    #[test]
+    fn address() {
+        use std::net::Ipv6Addr;
+        assert_eq!(
+            parse("ipv6.src == ::1"),
+            Ok(CmpEq(
+                Box::new(Token(Token::from("ipv6.src"))),
+                Box::new(Literal(Variant::Ipv6Addr(Ipv6Addr::new(
+                    0, 0, 0, 0, 0, 0, 0, 1
+                ))))
+            ))
+        );
+        assert_eq!(
+            parse("2001:db8::ff00:42:8329"),
+            Ok(Literal(Variant::Ipv6Addr(Ipv6Addr::new(
+                0x2001, 0xdb8, 0, 0, 0, 0xff00, 0x42, 0x8329
+            ))))
+        );
+        assert_eq!(
+            parse("00:1b:21:FF:fe:80"),
+            Ok(Literal(Variant::MacAddr([
+                0x00, 0x1b, 0x21, 0xff, 0xfe, 0x80
+            ])))
+        );
+        assert_eq!(
+            parse("@2018-01-01T00:00:00Z"),
+            Ok(Literal(Variant::Timestamp(1_514_764_800_000_000_000)))
+        );
+    }
+
+    #[test]
+    fn indexed() {
+        assert_eq!(
+            parse("tcp.options[1]"),
+            Ok(Indexed(Token::from("tcp.options"), 1, None))
+        );
+        assert_eq!(
+            parse("dns.answer[2].name"),
+            Ok(Indexed(
+                Token::from("dns.answer"),
+                2,
+                Some(Token::from("dns.answer.name"))
+            ))
+        );
+        assert!(parse("dns.answer[x]").is_err());
+        assert!(parse("dns.answer[]").is_err());
+    }
+
+    #[test]
+    fn regex() {
+        let expr = parse(r#"http.host =~ "^www\\.""#).unwrap();
+        assert_eq!(
+            expr,
+            CmpMatch(
+                Box::new(Token(Token::from("http.host"))),
+                Pattern::new(r"^www\.").unwrap()
+            )
+        );
+        assert_eq!(::unparser::unparse(&expr), r#"http.host =~ "^www\\.""#);
+        assert!(parse(r#"http.host =~ "(""#).is_err());
+        assert!(parse("http.host =~ http.path").is_err());
+        assert!(parse(r#"(http.host =~ "[") || true"#).is_err());
+    }
+
+    #[test]
+    fn network() {
+        let expr = parse("ipv4.src in 10.0.0.0/8").unwrap();
+        let network = match expr {
+            CmpIn(ref lhs, ref network) => {
+                assert_eq!(**lhs, Token(Token::from("ipv4.src")));
+                network.clone()
+            }
+            _ => panic!("unexpected expression: {:?}", expr),
+        };
+        assert!(network.contains(&[10, 1, 2, 3]));
+        assert!(!network.contains(&[11, 0, 0, 0]));
+        assert!(!network.contains(&[10; 16]));
+        assert_eq!(::unparser::unparse(&expr), "ipv4.src in 10.0.0.0/8");
+
+        let expr = parse("!ipv6.dst in 2001:db8::/33").unwrap();
+        let network = match expr {
+            LogicalNegation(ref inner) => match **inner {
+                CmpIn(_, ref network) => network.clone(),
+                _ => panic!("unexpected expression: {:?}", expr),
+            },
+            _ => panic!("unexpected expression: {:?}", expr),
+        };
+        let mut addr = [0u8; 16];
+        addr[..4].copy_from_slice(&[0x20, 0x01, 0x0d, 0xb8]);
+        assert!(network.contains(&addr));
+        addr[4] = 0x80;
+        assert!(!network.contains(&addr));
+        assert_eq!(::unparser::unparse(&expr), "!ipv6.dst in 2001:db8::/33");
+
+        assert!(parse("ipv4.src in 10.0.0.0/33").is_err());
+        assert!(parse("ipv4.src in 10.0.0.256/8").is_err());
+        assert!(parse("ipv4.src in 10.0.0.0").is_err());
+        assert!(parse("ipv4.src in ipv4.dst").is_err());
+        assert_eq!(parse("index"), Ok(Token(Token::from("index"))));
+    }
+
+    #[test]
+    fn function() {
+        let expr = parse(r#"contains(lower(http.host), "example")"#).unwrap();
+        assert_eq!(
+            expr,
+            Call(
+                Function::Contains,
+                vec![
+                    Call(Function::Lower, vec![Token(Token::from("http.host"))]),
+                    Literal(Variant::String("example".to_string().into_boxed_str())),
+                ]
+            )
+        );
+        assert_eq!(
+            ::unparser::unparse(&expr),
+            r#"contains(lower(http.host), "example")"#
+        );
+        assert_eq!(
+            parse("len( dns.query ) > 3"),
+            Ok(CmpGt(
+                Box::new(Call(Function::Len, vec![Token(Token::from("dns.query"))])),
+                Box::new(Literal(Variant::UInt64(3)))
+            ))
+        );
+        assert!(parse("len()").is_err());
+        assert!(parse(r#"len("a", "b")"#).is_err());
+        assert!(parse(r#"concat("a", "b")"#).is_err());
+    }
+
+    #[test]
+    fn within() {
+        let start = 1_704_189_600_000_000_000;
+        let expr = parse("frame.time within 2024-01-02T10:00..10:05").unwrap();
+        assert_eq!(
+            expr,
+            Within(
+                Box::new(Token(Token::from("frame.time"))),
+                start,
+                start + 300_000_000_000
+            )
+        );
+        assert_eq!(parse(&::unparser::unparse(&expr)), Ok(expr));
+        assert_eq!(
+            parse("frame.time within 2024-01-02T10:00..+5m"),
+            parse("frame.time within 2024-01-02T10:00:00Z..2024-01-02T10:05:00Z")
+        );
+        assert!(parse("frame.time within 2024-01-02T10:00..09:00").is_err());
+        assert!(parse("frame.time within 2024-01-02T10:00..+5x").is_err());
+        assert!(parse("frame.time within 2024-01-02").is_err());
+        assert_eq!(
+            parse("frame.time > @2024-01-02T10:00:00Z"),
+            Ok(CmpGt(
+                Box::new(Token(Token::from("frame.time"))),
+                Box::new(Literal(Variant::Timestamp(start)))
+            ))
+        );
+    }
+
+    #[test]
+    fn named() {
+        let mut named = NamedFilters::new();
+        named
+            .insert("web", "tcp.src == 80 || tcp.dst == 80")
+            .unwrap();
+        named
+            .insert("lan", "$web && ipv4.src in 10.0.0.0/8")
+            .unwrap();
+        assert_eq!(
+            parse_with("$lan", &named),
+            parse("(tcp.src == 80 || tcp.dst == 80) && ipv4.src in 10.0.0.0/8")
+        );
+        assert!(parse("$web").is_err());
+        assert!(named.insert("1st", "true").is_err());
+        assert!(named.insert("broken", "tcp.src ==").is_err());
+        assert!(named.insert("self", "$self").is_err());
+
+        // Redefinitions can make a cycle, which is detected on use.
+        named.insert("web", "$lan").unwrap();
+        assert!(parse_with("$web", &named).is_err());
+    }
+
     #[test]
     fn error() {
         assert!(parse("| 12.5").is_err());
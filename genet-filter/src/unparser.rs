@@ -1,8 +1,9 @@
 use ast::Expr;
 use genet_abi::{token::Token, variant::Variant};
 use hwaddr::HwAddr;
+use serde_json;
 use std::net::{Ipv4Addr, Ipv6Addr};
-use variant::VariantExt;
+use variant::{format_timestamp, VariantExt};
 
 pub fn unparse_attr(typ: Token, var: &Variant) -> Expr {
     let typ = typ.to_string();
@@ -29,9 +30,26 @@ pub fn unparse_attr(typ: Token, var: &Variant) -> Expr {
 
 pub fn unparse(expr: &Expr) -> String {
     match expr {
+        Expr::Literal(Variant::Timestamp(ns)) => format!("@{}", format_timestamp(*ns)),
         Expr::Literal(var) => var.to_string(),
         Expr::Token(t) => t.to_string(),
+        Expr::Indexed(id, index, child) => {
+            let id = id.to_string();
+            match child {
+                Some(child) => {
+                    let child = child.to_string();
+                    let name = child.get(id.len() + 1..).unwrap_or("");
+                    format!("{}[{}].{}", id, index, name)
+                }
+                None => format!("{}[{}]", id, index),
+            }
+        }
         Expr::Macro(expr) => format!("@{}", expr),
+        Expr::Call(func, args) => format!(
+            "{}({})",
+            func.name(),
+            args.iter().map(unparse).collect::<Vec<_>>().join(", ")
+        ),
         Expr::CmpEq(lhs, rhs) => match (lhs.as_ref(), rhs.as_ref()) {
             (lhs, &Expr::Literal(Variant::Bool(true))) => unparse(lhs),
             (lhs, &Expr::Literal(Variant::Bool(false))) => format!("!{}", unparse(lhs)),
@@ -46,6 +64,18 @@ pub fn unparse(expr: &Expr) -> String {
             (&Expr::Literal(Variant::Bool(true)), rhs) => format!("!{}", unparse(rhs)),
             (lhs, rhs) => format!("{} != {}", unparse(lhs), unparse(rhs)),
         },
+        Expr::CmpMatch(lhs, pattern) => format!(
+            "{} =~ {}",
+            unparse(lhs),
+            serde_json::to_string(pattern.as_str()).unwrap()
+        ),
+        Expr::CmpIn(lhs, network) => format!("{} in {}", unparse(lhs), network),
+        Expr::Within(lhs, start, end) => format!(
+            "{} within {}..{}",
+            unparse(lhs),
+            format_timestamp(*start),
+            format_timestamp(*end)
+        ),
         Expr::CmpLt(lhs, rhs) => format!("{} < {}", unparse(lhs), unparse(rhs)),
         Expr::CmpGt(lhs, rhs) => format!("{} > {}", unparse(lhs), unparse(rhs)),
         Expr::CmpLte(lhs, rhs) => format!("{} <= {}", unparse(lhs), unparse(rhs)),
@@ -62,32 +62,20 @@ impl VariantExt for Variant {
     }
 
     fn ord(&self, other: &Variant) -> Option<Ordering> {
-        let lhs = match self {
-            Variant::Buffer(v) => Variant::BigInt(
-                BigInt::from_bytes_be(Sign::Plus, &v)
-                    .to_signed_bytes_be()
-                    .into_boxed_slice(),
-            ),
-            Variant::Slice(v) => Variant::BigInt(
-                BigInt::from_bytes_be(Sign::Plus, &v)
-                    .to_signed_bytes_be()
-                    .into_boxed_slice(),
-            ),
-            _ => self.clone(),
-        };
-        let rhs = match other {
-            Variant::Buffer(v) => Variant::BigInt(
-                BigInt::from_bytes_be(Sign::Plus, &v)
-                    .to_signed_bytes_be()
-                    .into_boxed_slice(),
-            ),
-            Variant::Slice(v) => Variant::BigInt(
-                BigInt::from_bytes_be(Sign::Plus, &v)
-                    .to_signed_bytes_be()
-                    .into_boxed_slice(),
-            ),
-            _ => other.clone(),
-        };
+        match (self, other) {
+            (Variant::Ipv6Addr(a), Variant::Ipv6Addr(b)) => return a.partial_cmp(b),
+            (Variant::MacAddr(a), Variant::MacAddr(b)) => return a.partial_cmp(b),
+            (Variant::Timestamp(a), Variant::Timestamp(b)) => return a.partial_cmp(b),
+            (Variant::Timestamp(a), Variant::Float64(_)) => {
+                return timestamp(other).and_then(|b| a.partial_cmp(&b))
+            }
+            (Variant::Float64(_), Variant::Timestamp(b)) => {
+                return timestamp(self).and_then(|a| a.partial_cmp(b))
+            }
+            _ => {}
+        }
+        let lhs = normalize(self);
+        let rhs = normalize(other);
 
         match (&lhs, &rhs) {
             (Variant::String(a), Variant::String(b)) => a.partial_cmp(b),
@@ -178,6 +166,194 @@ impl VariantExt for Variant {
             Variant::Slice(b) => {
                 "0x".to_string() + &BigInt::from_bytes_be(Sign::Plus, &b).to_str_radix(16)
             }
+            Variant::Ipv6Addr(addr) => addr.to_string(),
+            Variant::MacAddr(addr) => addr
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<_>>()
+                .join(":"),
+            Variant::Timestamp(ns) => format_timestamp(*ns),
         }
     }
 }
+
+/// Converts byte sequences into integers for ordering.
+fn normalize(var: &Variant) -> Variant {
+    let bigint = |bytes: &[u8]| {
+        Variant::BigInt(
+            BigInt::from_bytes_be(Sign::Plus, bytes)
+                .to_signed_bytes_be()
+                .into_boxed_slice(),
+        )
+    };
+    match var {
+        Variant::Buffer(v) => bigint(&v),
+        Variant::Slice(v) => bigint(&v),
+        Variant::MacAddr(v) => bigint(v),
+        Variant::Ipv6Addr(v) => bigint(&v.octets()),
+        Variant::Timestamp(v) => Variant::UInt64(*v),
+        _ => var.clone(),
+    }
+}
+
+/// Converts the timestamp or the UNIX time in seconds into nanoseconds.
+///
+/// Timestamp attributes such as `link.timestamp` are stored as seconds in Float64.
+pub fn timestamp(var: &Variant) -> Option<u64> {
+    match var {
+        Variant::Timestamp(ns) => Some(*ns),
+        Variant::Float64(secs) if *secs >= 0.0 => Some((secs * 1_000_000_000f64) as u64),
+        _ => None,
+    }
+}
+
+/// Parses the duration such as `500ms`, `30s`, `5m`, `2h` or `1d` into nanoseconds.
+pub fn parse_duration(s: &str) -> Option<u64> {
+    let pos = s.find(|c: char| !c.is_ascii_digit())?;
+    let unit = match &s[pos..] {
+        "ms" => 1_000_000,
+        "s" => 1_000_000_000,
+        "m" => 60 * 1_000_000_000,
+        "h" => 3600 * 1_000_000_000,
+        "d" => 86400 * 1_000_000_000,
+        _ => return None,
+    };
+    s[..pos].parse::<u64>().ok()?.checked_mul(unit)
+}
+
+/// Formats the timestamp in RFC 3339 with nanosecond precision.
+pub fn format_timestamp(ns: u64) -> String {
+    let secs = ns / 1_000_000_000;
+    let days = (secs / 86400) as i64;
+    let time = secs % 86400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:09}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60,
+        ns % 1_000_000_000
+    )
+}
+
+/// Parses the RFC 3339 timestamp in UTC into nanoseconds since the UNIX epoch.
+pub fn parse_timestamp(s: &str) -> Option<u64> {
+    let s = s.trim_right_matches('Z');
+    let (date, time) = if let Some(pos) = s.find('T') {
+        (&s[..pos], &s[pos + 1..])
+    } else {
+        (s, "00:00:00")
+    };
+    let date = date
+        .split('-')
+        .map(|n| n.parse::<u32>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let (time, frac) = match time.find('.') {
+        Some(pos) => (&time[..pos], &time[pos + 1..]),
+        None => (time, ""),
+    };
+    let time = time
+        .split(':')
+        .map(|n| n.parse::<u32>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    if date.len() != 3 || time.len() < 2 || time.len() > 3 || frac.len() > 9 {
+        return None;
+    }
+    if date[1] < 1 || date[1] > 12 || date[2] < 1 || date[2] > 31 {
+        return None;
+    }
+    let sec = time.get(2).cloned().unwrap_or(0);
+    if time[0] > 23 || time[1] > 59 || sec > 60 || (time.len() == 2 && !frac.is_empty()) {
+        return None;
+    }
+    let nanos = if frac.is_empty() {
+        0
+    } else {
+        frac.parse::<u64>().ok()? * 10u64.pow(9 - frac.len() as u32)
+    };
+    let days = days_from_civil(date[0] as i64, date[1], date[2]);
+    if days < 0 {
+        return None;
+    }
+    let secs =
+        days as u64 * 86400 + u64::from(time[0]) * 3600 + u64::from(time[1]) * 60 + u64::from(sec);
+    Some(secs * 1_000_000_000 + nanos)
+}
+
+// http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(y: i64, m: u32, d: u32) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = (if y >= 0 { y } else { y - 399 }) / 400;
+    let yoe = y - era * 400;
+    let m = i64::from(m);
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + i64::from(d) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn civil_from_days(z: i64) -> (i64, u32, u32) {
+    let z = z + 719_468;
+    let era = (if z >= 0 { z } else { z - 146_096 }) / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let y = yoe + era * 400;
+    (if m <= 2 { y + 1 } else { y }, m, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use genet_abi::variant::Variant;
+    use std::net::Ipv6Addr;
+    use variant::{format_timestamp, parse_duration, parse_timestamp, VariantExt};
+
+    #[test]
+    fn timestamp() {
+        let ns = 1_514_764_800_000_000_001;
+        assert_eq!(format_timestamp(ns), "2018-01-01T00:00:00.000000001Z");
+        assert_eq!(parse_timestamp("2018-01-01T00:00:00.000000001Z"), Some(ns));
+        assert_eq!(parse_timestamp("1970-01-01"), Some(0));
+        assert_eq!(parse_timestamp("2018-13-01"), None);
+        assert_eq!(
+            parse_timestamp("2018-01-01T10:05"),
+            parse_timestamp("2018-01-01T10:05:00Z")
+        );
+        assert_eq!(parse_timestamp("2018-01-01T10"), None);
+        assert_eq!(parse_duration("5m"), Some(300_000_000_000));
+        assert_eq!(parse_duration("250ms"), Some(250_000_000));
+        assert_eq!(parse_duration("5"), None);
+        assert_eq!(parse_duration("m"), None);
+
+        let ts = Variant::Timestamp(1_514_764_800_000_000_000);
+        assert!(ts.op_eq(&Variant::Float64(1_514_764_800.0)));
+        assert!(ts.op_lt(&Variant::Float64(1_514_764_801.5)));
+        assert!(Variant::Float64(1_514_764_799.0).op_lt(&ts));
+        assert_eq!(
+            format_timestamp(951_782_400_000_000_000),
+            "2000-02-29T00:00:00.000000000Z"
+        );
+    }
+
+    #[test]
+    fn addr() {
+        let addr = Variant::Ipv6Addr(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1));
+        assert_eq!(addr.to_string(), "::1");
+        assert!(addr.op_eq(&Variant::Buffer(
+            Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)
+                .octets()
+                .to_vec()
+                .into_boxed_slice()
+        )));
+        assert!(addr.op_lt(&Variant::Ipv6Addr(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 2))));
+
+        let mac = Variant::MacAddr([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x0f]);
+        assert_eq!(mac.to_string(), "aa:bb:cc:dd:ee:0f");
+        assert!(mac.op_eq(&Variant::MacAddr([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x0f])));
+    }
+}
@@ -0,0 +1,531 @@
+//! Bytecode compiler and register machine for filter expressions.
+//!
+//! The expression tree is optimized and compiled once per filter,
+//! then the flat instruction list is executed for each frame.
+
+use ast::{self, Expr, Function, Network, Pattern};
+use context::Context;
+use genet_abi::{token::Token, variant::Variant};
+#[cfg(feature = "jit")]
+use jit::Native;
+use std::mem;
+#[cfg(feature = "jit")]
+use std::sync::Arc;
+use variant::VariantExt;
+
+type Reg = usize;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Inst {
+    /// Loads the constant.
+    Const(Reg, usize),
+
+    /// Loads the value of the attribute operand.
+    Load(Reg, usize),
+
+    /// Compares the registers, with the optional label operands for each side.
+    Eq(Reg, Reg, Reg, Option<usize>, Option<usize>),
+    NotEq(Reg, Reg, Reg, Option<usize>, Option<usize>),
+
+    Match(Reg, Reg, usize, Option<usize>),
+    In(Reg, Reg, usize),
+    Within(Reg, Reg, u64, u64),
+    Lt(Reg, Reg, Reg),
+    Gt(Reg, Reg, Reg),
+    Lte(Reg, Reg, Reg),
+    Gte(Reg, Reg, Reg),
+    Not(Reg, Reg),
+    Plus(Reg, Reg),
+    Neg(Reg, Reg),
+
+    /// Converts the value into a boolean.
+    Bool(Reg, Reg),
+
+    /// Calls the function with the arguments in the consecutive registers.
+    Call(Reg, Function, Reg, usize),
+
+    JumpIfFalse(Reg, usize),
+    JumpIfTrue(Reg, usize),
+}
+
+/// Compiled filter expression.
+#[derive(Clone, Debug)]
+pub struct Program {
+    pub(crate) code: Vec<Inst>,
+    consts: Vec<Variant>,
+    operands: Vec<Expr>,
+    patterns: Vec<Pattern>,
+    networks: Vec<Network>,
+    pub(crate) registers: usize,
+    #[cfg(feature = "jit")]
+    native: Option<Arc<Native>>,
+}
+
+impl Program {
+    pub fn compile(expr: &Expr) -> Program {
+        let mut program = Program {
+            code: Vec::new(),
+            consts: Vec::new(),
+            operands: Vec::new(),
+            patterns: Vec::new(),
+            networks: Vec::new(),
+            registers: 1,
+            #[cfg(feature = "jit")]
+            native: None,
+        };
+        program.emit(&optimize(expr), 0);
+        #[cfg(feature = "jit")]
+        {
+            program.native = Native::compile(&program).map(Arc::new);
+        }
+        program
+    }
+
+    /// Returns true if the program loads the attribute.
+    pub fn refers(&self, id: Token) -> bool {
+        self.operands.iter().any(|expr| match expr {
+            Expr::Token(t) | Expr::Indexed(t, ..) => *t == id,
+            _ => false,
+        })
+    }
+
+    /// Evaluates the program and returns the value of the expression.
+    ///
+    /// The native code is used if the program has been compiled with the JIT.
+    pub fn run(&self, ctx: &Context) -> Variant {
+        #[cfg(feature = "jit")]
+        {
+            if let Some(native) = &self.native {
+                return native.run(self, ctx);
+            }
+        }
+        self.interpret(ctx)
+    }
+
+    /// Evaluates the program with the interpreter.
+    pub fn interpret(&self, ctx: &Context) -> Variant {
+        let mut regs = vec![Variant::Nil; self.registers];
+        let mut pc = 0;
+        while let Some(inst) = self.code.get(pc) {
+            pc = match *inst {
+                Inst::JumpIfFalse(v, target) if !regs[v].is_truthy() => target,
+                Inst::JumpIfTrue(v, target) if regs[v].is_truthy() => target,
+                _ => {
+                    self.step(pc, &mut regs, ctx);
+                    pc + 1
+                }
+            };
+        }
+        mem::replace(&mut regs[0], Variant::Nil)
+    }
+
+    /// Executes the instruction at `pc`. The branches are ignored.
+    pub(crate) fn step(&self, pc: usize, regs: &mut [Variant], ctx: &Context) {
+        let value = match self.code[pc] {
+            Inst::Const(dst, i) => (dst, self.consts[i].clone()),
+            Inst::Load(dst, i) => (dst, self.operands[i].eval(ctx)),
+            Inst::Eq(dst, l, r, ll, rl) => {
+                (dst, Variant::Bool(self.eq(&regs[l], &regs[r], ll, rl, ctx)))
+            }
+            Inst::NotEq(dst, l, r, ll, rl) => (
+                dst,
+                Variant::Bool(!self.eq(&regs[l], &regs[r], ll, rl, ctx)),
+            ),
+            Inst::Match(dst, l, i, label) => (
+                dst,
+                Variant::Bool(ast::value_match(&regs[l], &self.patterns[i], || {
+                    label.and_then(|i| self.operands[i].label(ctx))
+                })),
+            ),
+            Inst::In(dst, l, i) => (
+                dst,
+                Variant::Bool(ast::value_in(&regs[l], &self.networks[i])),
+            ),
+            Inst::Within(dst, l, start, end) => {
+                (dst, Variant::Bool(ast::value_within(&regs[l], start, end)))
+            }
+            Inst::Lt(dst, l, r) => (dst, Variant::Bool(regs[l].op_lt(&regs[r]))),
+            Inst::Gt(dst, l, r) => (dst, Variant::Bool(regs[l].op_gt(&regs[r]))),
+            Inst::Lte(dst, l, r) => (dst, Variant::Bool(regs[l].op_lte(&regs[r]))),
+            Inst::Gte(dst, l, r) => (dst, Variant::Bool(regs[l].op_gte(&regs[r]))),
+            Inst::Not(dst, v) => (dst, Variant::Bool(!regs[v].is_truthy())),
+            Inst::Plus(dst, v) => (dst, regs[v].op_unary_plus()),
+            Inst::Neg(dst, v) => (dst, regs[v].op_unary_negation()),
+            Inst::Bool(dst, v) => (dst, Variant::Bool(regs[v].is_truthy())),
+            Inst::Call(dst, func, args, len) => (dst, func.call(&regs[args..args + len])),
+            Inst::JumpIfFalse(..) | Inst::JumpIfTrue(..) => return,
+        };
+        regs[value.0] = value.1;
+    }
+
+    fn eq(
+        &self,
+        l: &Variant,
+        r: &Variant,
+        lhs_label: Option<usize>,
+        rhs_label: Option<usize>,
+        ctx: &Context,
+    ) -> bool {
+        ast::value_eq(
+            l,
+            r,
+            || lhs_label.and_then(|i| self.operands[i].label(ctx)),
+            || rhs_label.and_then(|i| self.operands[i].label(ctx)),
+        )
+    }
+
+    fn constant(&mut self, value: Variant) -> usize {
+        self.consts.push(value);
+        self.consts.len() - 1
+    }
+
+    fn operand(&mut self, expr: &Expr) -> usize {
+        if let Some(i) = self.operands.iter().position(|e| e == expr) {
+            return i;
+        }
+        self.operands.push(expr.clone());
+        self.operands.len() - 1
+    }
+
+    /// Returns the operand index if the expression has a label.
+    fn label(&mut self, expr: &Expr) -> Option<usize> {
+        match expr {
+            Expr::Token(_) | Expr::Indexed(..) => Some(self.operand(expr)),
+            _ => None,
+        }
+    }
+
+    /// Emits the code which stores the value of the expression into `dst`.
+    ///
+    /// The registers above `dst` are used as temporaries.
+    fn emit(&mut self, expr: &Expr, dst: Reg) {
+        self.registers = self.registers.max(dst + 1);
+        let inst = match expr {
+            Expr::Literal(v) => Inst::Const(dst, self.constant(v.clone())),
+            Expr::Macro(_) => Inst::Const(dst, self.constant(Variant::Nil)),
+            Expr::Token(_) | Expr::Indexed(..) => Inst::Load(dst, self.operand(expr)),
+            Expr::CmpEq(l, r) => {
+                self.emit_binary(l, r, dst);
+                Inst::Eq(dst, dst, dst + 1, self.label(l), self.label(r))
+            }
+            Expr::CmpNotEq(l, r) => {
+                self.emit_binary(l, r, dst);
+                Inst::NotEq(dst, dst, dst + 1, self.label(l), self.label(r))
+            }
+            Expr::CmpMatch(l, pattern) => {
+                self.emit(l, dst);
+                self.patterns.push(pattern.clone());
+                Inst::Match(dst, dst, self.patterns.len() - 1, self.label(l))
+            }
+            Expr::CmpIn(l, network) => {
+                self.emit(l, dst);
+                self.networks.push(network.clone());
+                Inst::In(dst, dst, self.networks.len() - 1)
+            }
+            Expr::Within(l, start, end) => {
+                self.emit(l, dst);
+                Inst::Within(dst, dst, *start, *end)
+            }
+            Expr::CmpLt(l, r) => {
+                self.emit_binary(l, r, dst);
+                Inst::Lt(dst, dst, dst + 1)
+            }
+            Expr::CmpGt(l, r) => {
+                self.emit_binary(l, r, dst);
+                Inst::Gt(dst, dst, dst + 1)
+            }
+            Expr::CmpLte(l, r) => {
+                self.emit_binary(l, r, dst);
+                Inst::Lte(dst, dst, dst + 1)
+            }
+            Expr::CmpGte(l, r) => {
+                self.emit_binary(l, r, dst);
+                Inst::Gte(dst, dst, dst + 1)
+            }
+            Expr::LogicalAnd(..) | Expr::LogicalOr(..) => return self.emit_logical(expr, dst),
+            Expr::LogicalNegation(v) => {
+                self.emit(v, dst);
+                Inst::Not(dst, dst)
+            }
+            Expr::UnaryPlus(v) => {
+                self.emit(v, dst);
+                Inst::Plus(dst, dst)
+            }
+            Expr::UnaryNegation(v) => {
+                self.emit(v, dst);
+                Inst::Neg(dst, dst)
+            }
+            Expr::Call(func, args) => {
+                for (i, arg) in args.iter().enumerate() {
+                    self.emit(arg, dst + i);
+                }
+                Inst::Call(dst, *func, dst, args.len())
+            }
+        };
+        self.code.push(inst);
+    }
+
+    fn emit_binary(&mut self, lhs: &Expr, rhs: &Expr, dst: Reg) {
+        self.emit(lhs, dst);
+        self.emit(rhs, dst + 1);
+    }
+
+    /// Emits the chain of the same logical operator with a shared exit.
+    fn emit_logical(&mut self, expr: &Expr, dst: Reg) {
+        let and = is_and(expr);
+        let mut operands = Vec::new();
+        flatten(expr, and, &mut operands);
+        let mut jumps = Vec::new();
+        for (i, operand) in operands.iter().enumerate() {
+            self.emit(operand, dst);
+            self.code.push(Inst::Bool(dst, dst));
+            if i + 1 < operands.len() {
+                jumps.push(self.code.len());
+                self.code.push(Inst::JumpIfFalse(dst, 0));
+            }
+        }
+        let end = self.code.len();
+        for i in jumps {
+            self.code[i] = if and {
+                Inst::JumpIfFalse(dst, end)
+            } else {
+                Inst::JumpIfTrue(dst, end)
+            };
+        }
+    }
+}
+
+fn is_and(expr: &Expr) -> bool {
+    match expr {
+        Expr::LogicalAnd(..) => true,
+        _ => false,
+    }
+}
+
+/// Collects the operands of the nested logical operators of the same kind.
+fn flatten<'a>(expr: &'a Expr, and: bool, operands: &mut Vec<&'a Expr>) {
+    match (expr, and) {
+        (Expr::LogicalAnd(l, r), true) | (Expr::LogicalOr(l, r), false) => {
+            flatten(l, and, operands);
+            flatten(r, and, operands);
+        }
+        _ => operands.push(expr),
+    }
+}
+
+/// Folds the constant subexpressions and reorders the logical operands.
+fn optimize(expr: &Expr) -> Expr {
+    let binary = |l: &Expr, r: &Expr| (Box::new(optimize(l)), Box::new(optimize(r)));
+    let expr = match expr {
+        Expr::LogicalAnd(..) | Expr::LogicalOr(..) => return optimize_logical(expr),
+        Expr::CmpEq(l, r) => {
+            let (l, r) = binary(l, r);
+            Expr::CmpEq(l, r)
+        }
+        Expr::CmpNotEq(l, r) => {
+            let (l, r) = binary(l, r);
+            Expr::CmpNotEq(l, r)
+        }
+        Expr::CmpLt(l, r) => {
+            let (l, r) = binary(l, r);
+            Expr::CmpLt(l, r)
+        }
+        Expr::CmpGt(l, r) => {
+            let (l, r) = binary(l, r);
+            Expr::CmpGt(l, r)
+        }
+        Expr::CmpLte(l, r) => {
+            let (l, r) = binary(l, r);
+            Expr::CmpLte(l, r)
+        }
+        Expr::CmpGte(l, r) => {
+            let (l, r) = binary(l, r);
+            Expr::CmpGte(l, r)
+        }
+        Expr::CmpMatch(l, pattern) => Expr::CmpMatch(Box::new(optimize(l)), pattern.clone()),
+        Expr::CmpIn(l, network) => Expr::CmpIn(Box::new(optimize(l)), network.clone()),
+        Expr::Within(l, start, end) => Expr::Within(Box::new(optimize(l)), *start, *end),
+        Expr::LogicalNegation(v) => Expr::LogicalNegation(Box::new(optimize(v))),
+        Expr::UnaryPlus(v) => Expr::UnaryPlus(Box::new(optimize(v))),
+        Expr::UnaryNegation(v) => Expr::UnaryNegation(Box::new(optimize(v))),
+        Expr::Call(func, args) => Expr::Call(*func, args.iter().map(optimize).collect()),
+        Expr::Macro(_) => Expr::Literal(Variant::Nil),
+        Expr::Literal(_) | Expr::Token(_) | Expr::Indexed(..) => return expr.clone(),
+    };
+    if is_constant(&expr) {
+        Expr::Literal(expr.eval(&Context::new(&[])))
+    } else {
+        expr
+    }
+}
+
+/// Returns true if all the operands are already folded into literals.
+fn is_constant(expr: &Expr) -> bool {
+    let literal = |expr: &Expr| match expr {
+        Expr::Literal(_) => true,
+        _ => false,
+    };
+    match expr {
+        Expr::CmpEq(l, r)
+        | Expr::CmpNotEq(l, r)
+        | Expr::CmpLt(l, r)
+        | Expr::CmpGt(l, r)
+        | Expr::CmpLte(l, r)
+        | Expr::CmpGte(l, r) => literal(l) && literal(r),
+        Expr::CmpMatch(v, _)
+        | Expr::CmpIn(v, _)
+        | Expr::Within(v, ..)
+        | Expr::LogicalNegation(v)
+        | Expr::UnaryPlus(v)
+        | Expr::UnaryNegation(v) => literal(v),
+        Expr::Call(_, args) => args.iter().all(literal),
+        _ => false,
+    }
+}
+
+/// Drops the constant operands which do not affect the result,
+/// then sorts the rest so the cheap operands short-circuit the expensive ones.
+///
+/// The operands have no side effects, so the order does not change the result.
+fn optimize_logical(expr: &Expr) -> Expr {
+    let and = is_and(expr);
+    let mut operands = Vec::new();
+    flatten(expr, and, &mut operands);
+
+    let mut rest = Vec::new();
+    let mut dropped = false;
+    for operand in operands.into_iter().map(optimize) {
+        match operand {
+            // `false && x` is always false and `true || x` is always true.
+            Expr::Literal(ref v) if v.is_truthy() != and => {
+                return Expr::Literal(Variant::Bool(!and));
+            }
+            Expr::Literal(_) => dropped = true,
+            operand => rest.push(operand),
+        }
+    }
+    rest.sort_by_key(cost);
+
+    // A single operand still has to be converted into a boolean.
+    if rest.len() == 1 && dropped {
+        rest.push(Expr::Literal(Variant::Bool(and)));
+    }
+
+    let mut rest = rest.into_iter();
+    match rest.next() {
+        Some(first) => rest.fold(first, |l, r| {
+            if and {
+                Expr::LogicalAnd(Box::new(l), Box::new(r))
+            } else {
+                Expr::LogicalOr(Box::new(l), Box::new(r))
+            }
+        }),
+        None => Expr::Literal(Variant::Bool(and)),
+    }
+}
+
+/// Estimates the relative cost to evaluate the expression.
+fn cost(expr: &Expr) -> usize {
+    match expr {
+        Expr::Literal(_) | Expr::Macro(_) => 0,
+        Expr::Token(_) | Expr::Indexed(..) => 2,
+        Expr::CmpMatch(v, _) => cost(v) + 8,
+        Expr::CmpIn(v, _)
+        | Expr::Within(v, ..)
+        | Expr::LogicalNegation(v)
+        | Expr::UnaryPlus(v)
+        | Expr::UnaryNegation(v) => cost(v) + 1,
+        Expr::Call(_, args) => args.iter().map(cost).sum::<usize>() + 4,
+        Expr::CmpEq(l, r)
+        | Expr::CmpNotEq(l, r)
+        | Expr::CmpLt(l, r)
+        | Expr::CmpGt(l, r)
+        | Expr::CmpLte(l, r)
+        | Expr::CmpGte(l, r)
+        | Expr::LogicalAnd(l, r)
+        | Expr::LogicalOr(l, r) => cost(l) + cost(r) + 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use context::Context;
+    use genet_abi::{token::Token, variant::Variant};
+    use parser::parse;
+    use vm::{Inst, Program};
+
+    #[test]
+    fn eval() {
+        let ctx = Context::new(&[]);
+        for filter in &[
+            "nil",
+            "!(1 == 2)",
+            "1 < 2 && 3 > 4",
+            "false || (x && true)",
+            "!x || -y == 0",
+            r#"len(lower(x)) == len("abc")"#,
+            r#"contains("genet", "ne") && !startswith("genet", "x")"#,
+            r#""www.example.com" =~ "example\\.""#,
+            "x == nil || y != nil",
+            "x in 10.0.0.0/8",
+            "a && b || c && !d",
+        ] {
+            let expr = parse(filter).unwrap();
+            assert_eq!(
+                Program::compile(&expr).run(&ctx),
+                expr.eval(&ctx),
+                "{}",
+                filter
+            );
+        }
+    }
+
+    #[test]
+    fn constant_folding() {
+        let program = Program::compile(&parse(r#"!(1 == 2) && len("abc") == 3"#).unwrap());
+        assert_eq!(program.code, vec![Inst::Const(0, 0)]);
+        assert_eq!(program.consts, vec![Variant::Bool(true)]);
+
+        let program = Program::compile(&parse("x || false").unwrap());
+        assert_eq!(
+            program.code,
+            vec![
+                Inst::Load(0, 0),
+                Inst::Bool(0, 0),
+                Inst::JumpIfTrue(0, 5),
+                Inst::Const(0, 0),
+                Inst::Bool(0, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn short_circuit() {
+        let program = Program::compile(
+            &parse(r#"http.host =~ "^www\\." && (tcp.port == 80 && tcp)"#).unwrap(),
+        );
+        assert_eq!(program.operands[0], parse("tcp").unwrap());
+        assert_eq!(program.operands[1], parse("tcp.port").unwrap());
+        assert_eq!(
+            program.operands[2],
+            ::ast::Expr::Token(Token::from("http.host"))
+        );
+        let jumps = program
+            .code
+            .iter()
+            .filter(|inst| match inst {
+                Inst::JumpIfFalse(0, end) => *end == program.code.len(),
+                _ => false,
+            })
+            .count();
+        assert_eq!(jumps, 2);
+    }
+
+    #[test]
+    fn refers() {
+        let program = Program::compile(&parse("tcp.port == 80 || !frame.marked").unwrap());
+        assert!(program.refers(Token::from("frame.marked")));
+        assert!(program.refers(Token::from("tcp.port")));
+        assert!(!program.refers(Token::from("frame.comment")));
+    }
+}
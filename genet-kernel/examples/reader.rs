@@ -38,9 +38,9 @@ impl Reader for TestReader {
 struct TestWorker {}
 
 impl Worker for TestWorker {
-    fn read(&mut self) -> Result<Vec<Layer>> {
+    fn read(&mut self, max: usize) -> Result<Vec<Layer>> {
         let layers = iter::repeat(())
-            .take(1000)
+            .take(max.min(1000))
             .map(|_| Layer::new(&ETH_CLASS, ByteSlice::from(tcp_ipv4_pcap())))
             .collect();
         Ok(layers)
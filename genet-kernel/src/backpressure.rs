@@ -0,0 +1,85 @@
+use parking_lot::{Condvar, Mutex};
+use std::{fmt, sync::Arc, time::Duration};
+
+/// Limits the number of frames in flight between the inputs and the store.
+///
+/// The inputs stop reading while the decoders are behind,
+/// so a fast source does not grow the memory usage without bound.
+#[derive(Clone)]
+pub struct Backpressure {
+    state: Arc<(Mutex<usize>, Condvar)>,
+    capacity: usize,
+}
+
+impl Backpressure {
+    pub fn new(capacity: usize) -> Backpressure {
+        Backpressure {
+            state: Arc::new((Mutex::new(0), Condvar::new())),
+            capacity,
+        }
+    }
+
+    /// Waits until the frames in flight fall below the capacity,
+    /// and returns the number of frames which can be read.
+    ///
+    /// Returns 0 if the timeout expires.
+    pub fn wait(&self, timeout: Duration) -> usize {
+        let (ref lock, ref cvar) = *self.state;
+        let mut in_flight = lock.lock();
+        if *in_flight >= self.capacity {
+            cvar.wait_for(&mut in_flight, timeout);
+        }
+        self.capacity.saturating_sub(*in_flight)
+    }
+
+    /// Counts the frames which are read but not stored yet.
+    pub fn acquire(&self, len: usize) {
+        let (ref lock, _) = *self.state;
+        *lock.lock() += len;
+    }
+
+    /// Counts the frames which are stored.
+    pub fn release(&self, len: usize) {
+        let (ref lock, ref cvar) = *self.state;
+        {
+            let mut in_flight = lock.lock();
+            *in_flight = in_flight.saturating_sub(len);
+        }
+        cvar.notify_all();
+    }
+
+    pub fn in_flight(&self) -> usize {
+        let (ref lock, _) = *self.state;
+        *lock.lock()
+    }
+}
+
+impl fmt::Debug for Backpressure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Backpressure({}/{})", self.in_flight(), self.capacity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use backpressure::Backpressure;
+    use std::{thread, time::Duration};
+
+    #[test]
+    fn wait() {
+        let bp = Backpressure::new(100);
+        assert_eq!(bp.wait(Duration::from_millis(0)), 100);
+        bp.acquire(60);
+        assert_eq!(bp.wait(Duration::from_millis(0)), 40);
+        bp.acquire(60);
+        assert_eq!(bp.wait(Duration::from_millis(0)), 0);
+
+        let released = bp.clone();
+        let handle = thread::spawn(move || released.release(100));
+        handle.join().unwrap();
+        assert_eq!(bp.wait(Duration::from_millis(100)), 80);
+
+        bp.release(1000);
+        assert_eq!(bp.in_flight(), 0);
+    }
+}
@@ -9,7 +9,7 @@ use genet_napi::napi::{
 };
 use std::rc::Rc;
 
-fn variant_to_js<'env>(
+pub fn variant_to_js<'env>(
     env: &'env Env,
     value: &genet_abi::result::Result<Variant>,
 ) -> Result<&'env Value> {
@@ -35,6 +35,22 @@ fn variant_to_js<'env>(
             env.create_arraybuffer_from_slice(&v)?,
             0,
         ),
+        Ok(Variant::Ipv6Addr(v)) => {
+            let octets = v.octets();
+            env.create_typedarray(
+                TypedArrayType::Uint8Array,
+                octets.len(),
+                env.create_arraybuffer_copy(&octets)?,
+                0,
+            )
+        }
+        Ok(Variant::MacAddr(v)) => env.create_typedarray(
+            TypedArrayType::Uint8Array,
+            v.len(),
+            env.create_arraybuffer_copy(v)?,
+            0,
+        ),
+        Ok(Variant::Timestamp(v)) => env.create_double(*v as f64 / 1_000_000f64),
         _ => env.get_null(),
     }
 }
@@ -49,11 +65,11 @@ impl AttrWrapper {
         AttrWrapper { attr, layer }
     }
 
-    fn attr(&self) -> &Attr {
+    pub fn attr(&self) -> &Attr {
         unsafe { &*self.attr }
     }
 
-    fn layer(&self) -> &Layer {
+    pub fn layer(&self) -> &Layer {
         unsafe { &*self.layer }
     }
 }
@@ -91,11 +107,29 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
         Ok(array)
     }
 
+    fn attr_frame_range<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let wrapper = env.unwrap::<AttrWrapper>(info.this())?;
+        let offset = wrapper.layer().data().offset();
+        let range = wrapper.attr().range();
+        let array = env.create_array(2)?;
+        env.set_element(array, 0, env.create_uint32((offset + range.start) as u32)?)?;
+        env.set_element(array, 1, env.create_uint32((offset + range.end) as u32)?)?;
+        Ok(array)
+    }
+
     fn attr_value<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
         let wrapper = env.unwrap::<AttrWrapper>(info.this())?;
         variant_to_js(env, &wrapper.attr().try_get(wrapper.layer()))
     }
 
+    fn attr_label<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let wrapper = env.unwrap::<AttrWrapper>(info.this())?;
+        match wrapper.attr().label(wrapper.layer()) {
+            Some(label) => env.create_string(label),
+            None => env.get_null(),
+        }
+    }
+
     fn attr_filter_expression<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
         let wrapper = env.unwrap::<AttrWrapper>(info.this())?;
         let attr = wrapper.attr();
@@ -141,6 +175,13 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
                     attr_range,
                     false,
                 ),
+                PropertyDescriptor::new_property(
+                    env,
+                    "frameRange",
+                    PropertyAttributes::DEFAULT,
+                    attr_frame_range,
+                    false,
+                ),
                 PropertyDescriptor::new_property(
                     env,
                     "value",
@@ -148,6 +189,13 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
                     attr_value,
                     false,
                 ),
+                PropertyDescriptor::new_property(
+                    env,
+                    "label",
+                    PropertyAttributes::DEFAULT,
+                    attr_label,
+                    false,
+                ),
                 PropertyDescriptor::new_property(
                     env,
                     "filterExpression",
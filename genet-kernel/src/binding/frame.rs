@@ -5,6 +5,7 @@ use genet_napi::napi::{
     CallbackInfo, Env, PropertyAttributes, PropertyDescriptor, Result, Status, Value, ValueRef,
     ValueType,
 };
+use serde_json;
 use std::rc::Rc;
 
 pub fn wrapper(env: &Env) -> Rc<ValueRef> {
@@ -27,6 +28,29 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
         Ok(array)
     }
 
+    fn frame_color<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let frame = env.unwrap::<Frame>(info.this())?;
+        match frame.color() {
+            Some(color) => env.create_string(&color.to_string()),
+            None => env.get_null(),
+        }
+    }
+
+    fn frame_marked<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let frame = env.unwrap::<Frame>(info.this())?;
+        env.get_boolean(frame.note().marked)
+    }
+
+    fn frame_comment<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let frame = env.unwrap::<Frame>(info.this())?;
+        env.create_string(&frame.note().comment)
+    }
+
+    fn frame_trace<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let frame = env.unwrap::<Frame>(info.this())?;
+        env.create_string(&serde_json::to_string(frame.trace()).unwrap())
+    }
+
     fn frame_query<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
         let frame = env.unwrap::<Frame>(info.this())?;
         if let Some(id) = info.argv().get(0) {
@@ -94,6 +118,34 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
                     frame_tree_indices,
                     false,
                 ),
+                PropertyDescriptor::new_property(
+                    env,
+                    "color",
+                    PropertyAttributes::DEFAULT,
+                    frame_color,
+                    false,
+                ),
+                PropertyDescriptor::new_property(
+                    env,
+                    "marked",
+                    PropertyAttributes::DEFAULT,
+                    frame_marked,
+                    false,
+                ),
+                PropertyDescriptor::new_property(
+                    env,
+                    "comment",
+                    PropertyAttributes::DEFAULT,
+                    frame_comment,
+                    false,
+                ),
+                PropertyDescriptor::new_property(
+                    env,
+                    "trace",
+                    PropertyAttributes::DEFAULT,
+                    frame_trace,
+                    false,
+                ),
                 PropertyDescriptor::new_method(
                     env,
                     "query",
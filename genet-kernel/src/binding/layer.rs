@@ -1,5 +1,8 @@
 use binding::{attr::AttrWrapper, JsClass};
-use genet_abi::{layer::Layer, token::Token};
+use genet_abi::{
+    layer::{Layer, Severity},
+    token::Token,
+};
 use genet_filter::{ast::Expr, unparser::unparse};
 use genet_napi::napi::{
     CallbackInfo, Env, PropertyAttributes, PropertyDescriptor, Result, Status, TypedArrayType,
@@ -68,6 +71,16 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
                 "type",
                 env.create_string(&paylaod.typ().to_string())?,
             )?;
+            env.set_named_property(
+                object,
+                "tag",
+                env.create_string(&paylaod.tag().to_string())?,
+            )?;
+            env.set_named_property(
+                object,
+                "offset",
+                env.create_uint32(paylaod.data().offset() as u32)?,
+            )?;
             env.set_named_property(
                 object,
                 "data",
@@ -83,6 +96,42 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
         Ok(array)
     }
 
+    fn layer_annotations<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let layer = env.unwrap::<Layer>(info.this())?;
+        let annotations = layer.annotations();
+        let array = env.create_array(annotations.len())?;
+        for (i, annotation) in annotations.iter().enumerate() {
+            let severity = match annotation.severity() {
+                Severity::Note => "note",
+                Severity::Warn => "warn",
+                Severity::Error => "error",
+            };
+            let object = env.create_object()?;
+            env.set_named_property(object, "severity", env.create_string(severity)?)?;
+            env.set_named_property(
+                object,
+                "attr",
+                env.create_string(&annotation.attr().to_string())?,
+            )?;
+            env.set_named_property(object, "message", env.create_string(annotation.message())?)?;
+            env.set_element(array, i as u32, object)?;
+        }
+        Ok(array)
+    }
+
+    fn layer_links<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let layer = env.unwrap::<Layer>(info.this())?;
+        let links = layer.links();
+        let array = env.create_array(links.len())?;
+        for (i, link) in links.iter().enumerate() {
+            let object = env.create_object()?;
+            env.set_named_property(object, "id", env.create_string(&link.id().to_string())?)?;
+            env.set_named_property(object, "frame", env.create_uint32(link.frame())?)?;
+            env.set_element(array, i as u32, object)?;
+        }
+        Ok(array)
+    }
+
     fn layer_data<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
         let layer = env.unwrap::<Layer>(info.this())?;
         env.create_typedarray(
@@ -93,6 +142,11 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
         )
     }
 
+    fn layer_offset<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let layer = env.unwrap::<Layer>(info.this())?;
+        env.create_uint32(layer.data().offset() as u32)
+    }
+
     fn layer_filter_expression<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
         let layer = env.unwrap::<Layer>(info.this())?;
         env.create_string(&unparse(&Expr::Token(layer.id())))
@@ -130,6 +184,20 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
                     layer_payloads,
                     false,
                 ),
+                PropertyDescriptor::new_property(
+                    env,
+                    "annotations",
+                    PropertyAttributes::DEFAULT,
+                    layer_annotations,
+                    false,
+                ),
+                PropertyDescriptor::new_property(
+                    env,
+                    "links",
+                    PropertyAttributes::DEFAULT,
+                    layer_links,
+                    false,
+                ),
                 PropertyDescriptor::new_property(
                     env,
                     "data",
@@ -137,6 +205,13 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
                     layer_data,
                     false,
                 ),
+                PropertyDescriptor::new_property(
+                    env,
+                    "offset",
+                    PropertyAttributes::DEFAULT,
+                    layer_offset,
+                    false,
+                ),
                 PropertyDescriptor::new_property(
                     env,
                     "filterExpression",
@@ -1,17 +1,23 @@
-use binding::JsClass;
+use binding::{
+    attr::{variant_to_js, AttrWrapper},
+    JsClass,
+};
 use genet_filter::Filter;
 use genet_napi::{
     napi::{
         CallbackInfo, Env, HandleScope, PropertyAttributes, PropertyDescriptor, Result, Status,
-        Value, ValueRef,
+        Value, ValueRef, ValueType,
     },
     uv,
 };
 use parking_lot::Mutex;
 use profile::Profile;
 use serde_json;
-use session::{Callback, Event, Session};
-use std::{collections::VecDeque, rc::Rc, sync::Arc};
+use session::{
+    AttrChange, Callback, ChangeKind, ColoringRule, Direction, EndpointOrder, Event, ExportFormat,
+    IndexKind, Note, ProgressKind, Query, Selection, Session, Stream, StreamFormat,
+};
+use std::{collections::VecDeque, rc::Rc, sync::Arc, time::Duration};
 
 #[derive(Clone)]
 struct SessionCallback {
@@ -44,6 +50,49 @@ impl SessionCallback {
     }
 }
 
+fn stream_json(stream: &Stream, format: StreamFormat) -> serde_json::Value {
+    let segments = stream
+        .segments
+        .iter()
+        .map(|seg| {
+            let mut object = serde_json::Map::new();
+            object.insert("frame".into(), seg.frame.into());
+            object.insert(
+                "direction".into(),
+                serde_json::to_value(&seg.direction).unwrap(),
+            );
+            object.insert("offset".into(), seg.offset.into());
+            object.insert("data".into(), seg.render(format).into());
+            serde_json::Value::Object(object)
+        })
+        .collect::<Vec<_>>();
+    let mut object = serde_json::Map::new();
+    object.insert("flow".into(), serde_json::to_value(&stream.flow).unwrap());
+    object.insert("segments".into(), segments.into());
+    serde_json::Value::Object(object)
+}
+
+fn get_stream_format(env: &Env, value: &Value) -> Result<StreamFormat> {
+    serde_json::from_value::<StreamFormat>(serde_json::Value::String(env.get_value_string(value)?))
+        .map_err(|_| Status::InvalidArg)
+}
+
+fn get_export_format(env: &Env, value: &Value) -> Result<ExportFormat> {
+    serde_json::from_value::<ExportFormat>(serde_json::Value::String(env.get_value_string(value)?))
+        .map_err(|_| Status::InvalidArg)
+}
+
+fn get_direction(env: &Env, value: &Value) -> Result<Option<Direction>> {
+    match env.get_value_string(value)?.as_str() {
+        "" => Ok(None),
+        direction => {
+            serde_json::from_value::<Direction>(serde_json::Value::String(direction.into()))
+                .map(Some)
+                .map_err(|_| Status::InvalidArg)
+        }
+    }
+}
+
 pub fn init(env: &Env, exports: &Value) -> Result<()> {
     fn profile_ctor<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
         let profile = Profile::new();
@@ -70,7 +119,25 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
     fn profile_set_config<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
         let profile = env.unwrap::<Profile>(info.this())?;
         if let Some([key, value]) = info.argv().get(0..2) {
-            profile.set_config(&env.get_value_string(key)?, &env.get_value_string(value)?);
+            if let Err(err) =
+                profile.set_config(&env.get_value_string(key)?, &env.get_value_string(value)?)
+            {
+                env.throw_error("set_config", &err.to_string())?;
+            }
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn profile_set_config_schema<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let profile = env.unwrap::<Profile>(info.this())?;
+        if let Some([key, schema]) = info.argv().get(0..2) {
+            if let Err(err) = profile
+                .set_config_schema(&env.get_value_string(key)?, &env.get_value_string(schema)?)
+            {
+                env.throw_error("set_config_schema", &err.to_string())?;
+            }
             env.get_null()
         } else {
             Err(Status::InvalidArg)
@@ -89,6 +156,59 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
         }
     }
 
+    fn profile_load_packages<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let profile = env.unwrap::<Profile>(info.this())?;
+        if let Some(value) = info.argv().get(0) {
+            match profile.load_packages(&env.get_value_string(value)?) {
+                Ok(errors) => {
+                    let array = env.create_array(errors.len())?;
+                    for (i, err) in errors.iter().enumerate() {
+                        env.set_element(array, i as u32, env.create_string(&err.to_string())?)?;
+                    }
+                    Ok(array)
+                }
+                Err(err) => {
+                    env.throw_error("load_packages", &err.to_string())?;
+                    env.get_null()
+                }
+            }
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn profile_set_named_filter<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let profile = env.unwrap::<Profile>(info.this())?;
+        if let Some([name, filter]) = info.argv().get(0..2) {
+            let name = env.get_value_string(name)?;
+            let filter = env.get_value_string(filter)?;
+            if let Err(err) = profile.set_named_filter(&name, &filter) {
+                env.throw_error("set_named_filter", &err.to_string())?;
+            }
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn profile_set_coloring_rules<'env>(
+        env: &'env Env,
+        info: &CallbackInfo,
+    ) -> Result<&'env Value> {
+        let profile = env.unwrap::<Profile>(info.this())?;
+        if let Some(rules) = info.argv().get(0) {
+            let rules = env.get_value_string(rules)?;
+            let rules = serde_json::from_str::<Vec<ColoringRule>>(&rules)
+                .map_err(|_| Status::InvalidArg)?;
+            if let Err(err) = profile.set_coloring_rules(&rules) {
+                env.throw_error("set_coloring_rules", &err.to_string())?;
+            }
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
     fn profile_concurrency<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
         let profile = env.unwrap::<Profile>(info.this())?;
         if let Some(value) = info.argv().get(0) {
@@ -99,6 +219,71 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
         }
     }
 
+    fn profile_decode_timeout<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let profile = env.unwrap::<Profile>(info.this())?;
+        let mut budget = *profile.decode_budget();
+        if let Some(value) = info.argv().get(0) {
+            let millis = env.get_value_uint32(value)?;
+            budget.time = if millis > 0 {
+                Some(Duration::from_millis(u64::from(millis)))
+            } else {
+                None
+            };
+            profile.set_decode_budget(budget);
+            env.get_null()
+        } else {
+            let millis = budget.time.map_or(0, |time| {
+                time.as_secs() * 1000 + u64::from(time.subsec_millis())
+            });
+            env.create_uint32(millis as u32)
+        }
+    }
+
+    fn profile_decode_instructions<'env>(
+        env: &'env Env,
+        info: &CallbackInfo,
+    ) -> Result<&'env Value> {
+        let profile = env.unwrap::<Profile>(info.this())?;
+        let mut budget = *profile.decode_budget();
+        if let Some(value) = info.argv().get(0) {
+            let instructions = env.get_value_double(value)?;
+            budget.instructions = if instructions >= 1.0 {
+                Some(instructions as u64)
+            } else {
+                None
+            };
+            profile.set_decode_budget(budget);
+            env.get_null()
+        } else {
+            env.create_double(budget.instructions.unwrap_or(0) as f64)
+        }
+    }
+
+    fn profile_memory_limit<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let profile = env.unwrap::<Profile>(info.this())?;
+        if let Some(value) = info.argv().get(0) {
+            let bytes = env.get_value_double(value)?;
+            profile.set_memory_limit(if bytes >= 1.0 {
+                Some(bytes as u64)
+            } else {
+                None
+            });
+            env.get_null()
+        } else {
+            env.create_double(profile.memory_limit().unwrap_or(0) as f64)
+        }
+    }
+
+    fn profile_trace<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let profile = env.unwrap::<Profile>(info.this())?;
+        if let Some(value) = info.argv().get(0) {
+            profile.set_trace(env.get_value_bool(value)?);
+            env.get_null()
+        } else {
+            env.get_boolean(profile.trace())
+        }
+    }
+
     fn session_frames<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
         let session = env.unwrap::<Session>(info.this())?;
         if let Some([start, end]) = info.argv().get(0..2) {
@@ -118,6 +303,74 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
         }
     }
 
+    fn session_column_values<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([id, start, end]) = info.argv().get(0..3) {
+            let id = env.get_value_string(id)?;
+            let start = env.get_value_uint32(start)?;
+            let end = env.get_value_uint32(end)?;
+            match session.column_values(&id, start as usize..end as usize) {
+                Ok(values) => {
+                    let array = env.create_array(values.len())?;
+                    for (i, value) in values.into_iter().enumerate() {
+                        env.set_element(array, i as u32, variant_to_js(env, &Ok(value))?)?;
+                    }
+                    Ok(array)
+                }
+                Err(err) => {
+                    env.throw_error("column_values", &err.to_string())?;
+                    env.get_null()
+                }
+            }
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_summaries<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([start, end]) = info.argv().get(0..2) {
+            let start = env.get_value_uint32(start)?;
+            let end = env.get_value_uint32(end)?;
+            let summaries = session.summaries(start as usize..end as usize);
+            let array = env.create_array(summaries.len())?;
+            for (i, summary) in summaries.iter().enumerate() {
+                let value = match summary {
+                    Some(summary) => env.create_string(summary)?,
+                    None => env.get_null()?,
+                };
+                env.set_element(array, i as u32, value)?;
+            }
+            Ok(array)
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_set_note<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([index, marked, comment]) = info.argv().get(0..3) {
+            let note = Note {
+                marked: env.get_value_bool(marked)?,
+                comment: env.get_value_string(comment)?,
+            };
+            session.set_note(env.get_value_uint32(index)?, note);
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_marked_frames<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        let frames = session.marked_frames();
+        let array = env.create_array(frames.len())?;
+        for (i, item) in frames.iter().enumerate() {
+            env.set_element(array, i as u32, env.create_uint32(*item)?)?;
+        }
+        Ok(array)
+    }
+
     fn session_filtered_frames<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
         let session = env.unwrap::<Session>(info.this())?;
         if let Some([id, start, end]) = info.argv().get(0..3) {
@@ -139,20 +392,18 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
         let session = env.unwrap::<Session>(info.this())?;
         if let Some([id, filter]) = info.argv().get(0..2) {
             let filter = env.get_value_string(filter)?;
-            session.set_filter(
-                env.get_value_uint32(id)?,
-                if filter.is_empty() {
-                    None
-                } else {
-                    match Filter::compile(&filter) {
-                        Ok(filter) => Some(filter),
-                        Err(err) => {
-                            env.throw_error("load_library", &err.to_string())?;
-                            None
-                        }
+            let filter = if filter.is_empty() {
+                None
+            } else {
+                match Filter::compile_with(&filter, session.profile().named_filters()) {
+                    Ok(filter) => Some(filter),
+                    Err(err) => {
+                        env.throw_error("load_library", &err.to_string())?;
+                        None
                     }
-                },
-            );
+                }
+            };
+            session.set_filter(env.get_value_uint32(id)?, filter);
             env.get_null()
         } else {
             Err(Status::InvalidArg)
@@ -173,21 +424,27 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
     fn session_create_writer<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
         let session = env.unwrap::<Session>(info.this())?;
         if let Some([id, arg, filter]) = info.argv().get(0..3) {
-            let filter = env.get_value_string(filter)?;
-            let handle = session.create_writer(
-                &env.get_value_string(id)?,
-                &env.get_value_string(arg)?,
+            // A number refers to the results of the filter registered by setFilter.
+            let selection = if let ValueType::Number = env.type_of(filter)? {
+                Selection::Filtered(env.get_value_uint32(filter)?)
+            } else {
+                let filter = env.get_value_string(filter)?;
                 if filter.is_empty() {
-                    None
+                    Selection::All
                 } else {
-                    match Filter::compile(&filter) {
-                        Ok(filter) => Some(filter),
+                    match Filter::compile_with(&filter, session.profile().named_filters()) {
+                        Ok(filter) => Selection::Filter(filter),
                         Err(err) => {
                             env.throw_error("load_library", &err.to_string())?;
-                            None
+                            Selection::All
                         }
                     }
-                },
+                }
+            };
+            let handle = session.create_writer(
+                &env.get_value_string(id)?,
+                &env.get_value_string(arg)?,
+                selection,
             );
             env.create_uint32(handle)
         } else {
@@ -205,6 +462,352 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
         }
     }
 
+    fn session_reload_library<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([path, redecode]) = info.argv().get(0..2) {
+            let path = env.get_value_string(path)?;
+            if let Err(err) = session.reload_library(&path, env.get_value_bool(redecode)?) {
+                env.throw_error("reload_library", &err.to_string())?;
+            }
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_save<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(path) = info.argv().get(0) {
+            if let Err(err) = session.save(&env.get_value_string(path)?) {
+                env.throw_error("save", &err.to_string())?;
+            }
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_save_async<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(path) = info.argv().get(0) {
+            let id = session.save_async(&env.get_value_string(path)?);
+            env.create_uint32(id)
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_load<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(path) = info.argv().get(0) {
+            if let Err(err) = session.load(&env.get_value_string(path)?) {
+                env.throw_error("load", &err.to_string())?;
+            }
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_render_attr<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(attr) = info.argv().get(0) {
+            let wrapper = env.unwrap::<AttrWrapper>(attr)?;
+            match session.render_attr(wrapper.attr(), wrapper.layer()) {
+                Some(text) => env.create_string(&text),
+                None => env.get_null(),
+            }
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_links<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(index) = info.argv().get(0) {
+            let links = session.links(env.get_value_uint32(index)?);
+            let array = env.create_array(links.len())?;
+            for (i, link) in links.iter().enumerate() {
+                let object = env.create_object()?;
+                env.set_named_property(object, "id", env.create_string(&link.id().to_string())?)?;
+                env.set_named_property(object, "frame", env.create_uint32(link.frame())?)?;
+                env.set_element(array, i as u32, object)?;
+            }
+            Ok(array)
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_complete_filter<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let (Some(filter), Some(cursor)) = (info.argv().get(0), info.argv().get(1)) {
+            let filter = env.get_value_string(filter)?;
+            let cursor = env.get_value_uint32(cursor)? as usize;
+            let json = serde_json::to_string(&session.complete_filter(&filter, cursor)).unwrap();
+            env.create_string(&json)
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_set_named_filter<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([name, filter]) = info.argv().get(0..2) {
+            let name = env.get_value_string(name)?;
+            let filter = env.get_value_string(filter)?;
+            if let Err(err) = session.set_named_filter(&name, &filter) {
+                env.throw_error("set_named_filter", &err.to_string())?;
+            }
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_set_coloring_rules<'env>(
+        env: &'env Env,
+        info: &CallbackInfo,
+    ) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(rules) = info.argv().get(0) {
+            let rules = env.get_value_string(rules)?;
+            let rules = serde_json::from_str::<Vec<ColoringRule>>(&rules)
+                .map_err(|_| Status::InvalidArg)?;
+            if let Err(err) = session.set_coloring_rules(&rules) {
+                env.throw_error("set_coloring_rules", &err.to_string())?;
+            }
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_endpoints<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([order, limit]) = info.argv().get(0..2) {
+            let order = env.get_value_string(order)?;
+            let order = serde_json::from_value::<EndpointOrder>(serde_json::Value::String(order))
+                .map_err(|_| Status::InvalidArg)?;
+            let limit = match env.get_value_uint32(limit)? {
+                0 => None,
+                limit => Some(limit as usize),
+            };
+            let json = serde_json::to_string(&session.endpoints(order, limit)).unwrap();
+            env.create_string(&json)
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_io_graph<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([interval, filters]) = info.argv().get(0..2) {
+            let interval = env.get_value_int64(interval)?.max(1) as u64;
+            let filters = serde_json::from_str::<Vec<String>>(&env.get_value_string(filters)?)
+                .map_err(|_| Status::InvalidArg)?;
+            match session.io_graph(interval, &filters) {
+                Ok(graph) => env.create_string(&serde_json::to_string(&graph).unwrap()),
+                Err(err) => {
+                    env.throw_error("io_graph", &err.to_string())?;
+                    env.get_null()
+                }
+            }
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_io_graph_async<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([interval, filters]) = info.argv().get(0..2) {
+            let interval = env.get_value_int64(interval)?.max(1) as u64;
+            let filters = serde_json::from_str::<Vec<String>>(&env.get_value_string(filters)?)
+                .map_err(|_| Status::InvalidArg)?;
+            match session.io_graph_async(interval, &filters) {
+                Ok(id) => env.create_uint32(id),
+                Err(err) => {
+                    env.throw_error("io_graph", &err.to_string())?;
+                    env.get_null()
+                }
+            }
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_endpoints_async<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([order, limit]) = info.argv().get(0..2) {
+            let order = env.get_value_string(order)?;
+            let order = serde_json::from_value::<EndpointOrder>(serde_json::Value::String(order))
+                .map_err(|_| Status::InvalidArg)?;
+            let limit = match env.get_value_uint32(limit)? {
+                0 => None,
+                limit => Some(limit as usize),
+            };
+            let id = session.spawn_task(move |view, _, _| {
+                Ok(serde_json::to_value(&view.endpoints(order, limit)).unwrap())
+            });
+            env.create_uint32(id)
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_cancel<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([kind, id]) = info.argv().get(0..2) {
+            let kind = env.get_value_string(kind)?;
+            let kind = serde_json::from_value::<ProgressKind>(serde_json::Value::String(kind))
+                .map_err(|_| Status::InvalidArg)?;
+            session.cancel(kind, env.get_value_uint32(id)?);
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_cancel_redecode<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        session.cancel_redecode();
+        env.get_null()
+    }
+
+    fn session_search<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(filter) = info.argv().get(0) {
+            let filter = env.get_value_string(filter)?;
+            match Filter::compile_with(&filter, session.profile().named_filters()) {
+                Ok(filter) => env.create_uint32(session.search(filter)),
+                Err(err) => {
+                    env.throw_error("search", &err.to_string())?;
+                    env.get_null()
+                }
+            }
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_find_attr<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([id, value, from, backward]) = info.argv().get(0..4) {
+            let query = Query::attr(&env.get_value_string(id)?, &env.get_value_string(value)?);
+            let from = env.get_value_uint32(from)?;
+            let backward = env.get_value_bool(backward)?;
+            env.create_uint32(session.find(query, from, backward))
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_find_bytes<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([patterns, from, backward]) = info.argv().get(0..3) {
+            if !env.is_array(patterns)? {
+                return Err(Status::InvalidArg);
+            }
+            let mut query = Vec::new();
+            for i in 0..env.get_array_length(patterns)? {
+                let pattern = env.get_element(patterns, i)?;
+                if !env.is_typedarray(pattern)? {
+                    return Err(Status::InvalidArg);
+                }
+                query.push(env.get_uint8array(pattern)?.to_vec());
+            }
+            let query = Query::Bytes(query);
+            let from = env.get_value_uint32(from)?;
+            let backward = env.get_value_bool(backward)?;
+            env.create_uint32(session.find(query, from, backward))
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_stream<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([index, format]) = info.argv().get(0..2) {
+            let format = get_stream_format(env, format)?;
+            match session.stream(env.get_value_uint32(index)?) {
+                Some(stream) => env.create_string(&stream_json(&stream, format).to_string()),
+                None => env.get_null(),
+            }
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_stream_async<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([index, format]) = info.argv().get(0..2) {
+            let format = get_stream_format(env, format)?;
+            let index = env.get_value_uint32(index)?;
+            let id = session.spawn_task(move |view, _, _| {
+                Ok(view
+                    .stream(index)
+                    .map_or(serde_json::Value::Null, |stream| {
+                        stream_json(&stream, format)
+                    }))
+            });
+            env.create_uint32(id)
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_export_stream<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([index, path, format, direction]) = info.argv().get(0..4) {
+            let format = get_stream_format(env, format)?;
+            let direction = get_direction(env, direction)?;
+            let path = env.get_value_string(path)?;
+            if let Err(err) =
+                session.export_stream(env.get_value_uint32(index)?, &path, format, direction)
+            {
+                env.throw_error("export_stream", &err.to_string())?;
+            }
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_export_stream_async<'env>(
+        env: &'env Env,
+        info: &CallbackInfo,
+    ) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([index, path, format, direction]) = info.argv().get(0..4) {
+            let format = get_stream_format(env, format)?;
+            let direction = get_direction(env, direction)?;
+            let path = env.get_value_string(path)?;
+            let id =
+                session.export_stream_async(env.get_value_uint32(index)?, &path, format, direction);
+            env.create_uint32(id)
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_set_index<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([attr, kind]) = info.argv().get(0..2) {
+            let attr = env.get_value_string(attr)?;
+            let kind = match env.get_value_string(kind)?.as_str() {
+                "" => None,
+                kind => Some(
+                    serde_json::from_value::<IndexKind>(serde_json::Value::String(kind.into()))
+                        .map_err(|_| Status::InvalidArg)?,
+                ),
+            };
+            session.set_index(&attr, kind);
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
     fn session_length<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
         let session = env.unwrap::<Session>(info.this())?;
         env.create_uint32(session.len() as u32)
@@ -216,6 +819,103 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
         env.create_string(&json)
     }
 
+    fn session_trace<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(index) = info.argv().get(0) {
+            match session.trace(env.get_value_uint32(index)?) {
+                Some(trace) => env.create_string(&serde_json::to_string(&trace).unwrap()),
+                None => env.get_null(),
+            }
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_diff<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([a, b]) = info.argv().get(0..2) {
+            let a = env.get_value_uint32(a)?;
+            let b = env.get_value_uint32(b)?;
+            match session.diff(a, b) {
+                Some(changes) => {
+                    let array = env.create_array(changes.len())?;
+                    for (i, change) in changes.into_iter().enumerate() {
+                        env.set_element(array, i as u32, change_to_js(env, change)?)?;
+                    }
+                    Ok(array)
+                }
+                None => env.get_null(),
+            }
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_export_bytes<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([index, format, start, end]) = info.argv().get(0..4) {
+            let index = env.get_value_uint32(index)?;
+            let format = get_export_format(env, format)?;
+            let range = env.get_value_uint32(start)? as usize..env.get_value_uint32(end)? as usize;
+            match session.export_bytes(index, range, format) {
+                Some(data) => env.create_arraybuffer_copy(&data),
+                None => env.get_null(),
+            }
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn change_to_js<'env>(env: &'env Env, change: AttrChange) -> Result<&'env Value> {
+        let kind = match change.kind {
+            ChangeKind::Added => "added",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Changed => "changed",
+        };
+        let object = env.create_object()?;
+        env.set_named_property(object, "type", env.create_string(kind)?)?;
+        env.set_named_property(object, "path", env.create_string(&change.path)?)?;
+        env.set_named_property(object, "id", env.create_string(&change.id.to_string())?)?;
+        env.set_named_property(object, "index", env.create_uint32(change.index as u32)?)?;
+        for (name, value) in &[("old", change.old), ("new", change.new)] {
+            let value = match value {
+                Some(value) => variant_to_js(env, &Ok(value.clone()))?,
+                None => env.get_null()?,
+            };
+            env.set_named_property(object, name, value)?;
+        }
+        Ok(object)
+    }
+
+    fn session_decode_frame<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(index) = info.argv().get(0) {
+            match session.decode_frame(env.get_value_uint32(index)?) {
+                Some(frame) => {
+                    let frame_class = env.get_constructor(JsClass::Frame as usize).unwrap();
+                    let instance = env.new_instance(&frame_class, &[])?;
+                    env.wrap(instance, frame)?;
+                    Ok(instance)
+                }
+                None => env.get_null(),
+            }
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_statistics<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        let json = serde_json::to_string(&session.statistics()).unwrap();
+        env.create_string(&json)
+    }
+
+    fn session_flows<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        let json = serde_json::to_string(&session.flows()).unwrap();
+        env.create_string(&json)
+    }
+
     let session_class = env.define_class(
         "Session",
         session_ctor,
@@ -256,6 +956,177 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
                 PropertyAttributes::DEFAULT,
                 session_close_reader,
             ),
+            PropertyDescriptor::new_method(
+                env,
+                "reloadLibrary",
+                PropertyAttributes::DEFAULT,
+                session_reload_library,
+            ),
+            PropertyDescriptor::new_method(env, "save", PropertyAttributes::DEFAULT, session_save),
+            PropertyDescriptor::new_method(
+                env,
+                "saveAsync",
+                PropertyAttributes::DEFAULT,
+                session_save_async,
+            ),
+            PropertyDescriptor::new_method(env, "load", PropertyAttributes::DEFAULT, session_load),
+            PropertyDescriptor::new_method(
+                env,
+                "renderAttr",
+                PropertyAttributes::DEFAULT,
+                session_render_attr,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "links",
+                PropertyAttributes::DEFAULT,
+                session_links,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "endpoints",
+                PropertyAttributes::DEFAULT,
+                session_endpoints,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "ioGraph",
+                PropertyAttributes::DEFAULT,
+                session_io_graph,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "ioGraphAsync",
+                PropertyAttributes::DEFAULT,
+                session_io_graph_async,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "endpointsAsync",
+                PropertyAttributes::DEFAULT,
+                session_endpoints_async,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "search",
+                PropertyAttributes::DEFAULT,
+                session_search,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "findAttr",
+                PropertyAttributes::DEFAULT,
+                session_find_attr,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "findBytes",
+                PropertyAttributes::DEFAULT,
+                session_find_bytes,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "cancel",
+                PropertyAttributes::DEFAULT,
+                session_cancel,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "cancelRedecode",
+                PropertyAttributes::DEFAULT,
+                session_cancel_redecode,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "setIndex",
+                PropertyAttributes::DEFAULT,
+                session_set_index,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "stream",
+                PropertyAttributes::DEFAULT,
+                session_stream,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "exportStream",
+                PropertyAttributes::DEFAULT,
+                session_export_stream,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "streamAsync",
+                PropertyAttributes::DEFAULT,
+                session_stream_async,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "exportStreamAsync",
+                PropertyAttributes::DEFAULT,
+                session_export_stream_async,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "completeFilter",
+                PropertyAttributes::DEFAULT,
+                session_complete_filter,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "setNamedFilter",
+                PropertyAttributes::DEFAULT,
+                session_set_named_filter,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "setColoringRules",
+                PropertyAttributes::DEFAULT,
+                session_set_coloring_rules,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "columnValues",
+                PropertyAttributes::DEFAULT,
+                session_column_values,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "summaries",
+                PropertyAttributes::DEFAULT,
+                session_summaries,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "setNote",
+                PropertyAttributes::DEFAULT,
+                session_set_note,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "markedFrames",
+                PropertyAttributes::DEFAULT,
+                session_marked_frames,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "trace",
+                PropertyAttributes::DEFAULT,
+                session_trace,
+            ),
+            PropertyDescriptor::new_method(env, "diff", PropertyAttributes::DEFAULT, session_diff),
+            PropertyDescriptor::new_method(
+                env,
+                "exportBytes",
+                PropertyAttributes::DEFAULT,
+                session_export_bytes,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "decodeFrame",
+                PropertyAttributes::DEFAULT,
+                session_decode_frame,
+            ),
             PropertyDescriptor::new_property(
                 env,
                 "length",
@@ -270,6 +1141,20 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
                 session_profile,
                 true,
             ),
+            PropertyDescriptor::new_property(
+                env,
+                "statistics",
+                PropertyAttributes::DEFAULT,
+                session_statistics,
+                true,
+            ),
+            PropertyDescriptor::new_property(
+                env,
+                "flows",
+                PropertyAttributes::DEFAULT,
+                session_flows,
+                true,
+            ),
         ],
     )?;
 
@@ -283,12 +1168,36 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
                 PropertyAttributes::DEFAULT,
                 profile_set_config,
             ),
+            PropertyDescriptor::new_method(
+                env,
+                "setConfigSchema",
+                PropertyAttributes::DEFAULT,
+                profile_set_config_schema,
+            ),
             PropertyDescriptor::new_method(
                 env,
                 "loadLibrary",
                 PropertyAttributes::DEFAULT,
                 profile_load_library,
             ),
+            PropertyDescriptor::new_method(
+                env,
+                "loadPackages",
+                PropertyAttributes::DEFAULT,
+                profile_load_packages,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "setNamedFilter",
+                PropertyAttributes::DEFAULT,
+                profile_set_named_filter,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "setColoringRules",
+                PropertyAttributes::DEFAULT,
+                profile_set_coloring_rules,
+            ),
             PropertyDescriptor::new_property(
                 env,
                 "concurrency",
@@ -296,6 +1205,34 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
                 profile_concurrency,
                 true,
             ),
+            PropertyDescriptor::new_property(
+                env,
+                "decodeTimeout",
+                PropertyAttributes::DEFAULT,
+                profile_decode_timeout,
+                true,
+            ),
+            PropertyDescriptor::new_property(
+                env,
+                "decodeInstructions",
+                PropertyAttributes::DEFAULT,
+                profile_decode_instructions,
+                true,
+            ),
+            PropertyDescriptor::new_property(
+                env,
+                "memoryLimit",
+                PropertyAttributes::DEFAULT,
+                profile_memory_limit,
+                true,
+            ),
+            PropertyDescriptor::new_property(
+                env,
+                "trace",
+                PropertyAttributes::DEFAULT,
+                profile_trace,
+                true,
+            ),
         ],
     )?;
 
@@ -0,0 +1,45 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// A flag shared with a long-running operation to abort it mid-way.
+///
+/// The operation checks the flag between the units of work,
+/// so it stops shortly after the token is cancelled.
+#[derive(Clone, Default, Debug)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    /// Requests the operation to stop.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cancel::CancelToken;
+    use std::thread;
+
+    #[test]
+    fn cancel() {
+        let token = CancelToken::new();
+        assert!(!token.is_cancelled());
+
+        let shared = token.clone();
+        thread::spawn(move || shared.cancel()).join().unwrap();
+        assert!(token.is_cancelled());
+        assert!(!CancelToken::new().is_cancelled());
+    }
+}
@@ -0,0 +1,137 @@
+use frame::Frame;
+use genet_abi::token::Token;
+use genet_filter::{context::Context, result::Result as FilterResult, Filter};
+use link::LinkTable;
+use parking_lot::RwLock;
+use profile::Profile;
+use std::sync::Arc;
+
+/// A rule assigning the color tag to the frames matching the filter.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct ColoringRule {
+    pub filter: String,
+    pub color: String,
+}
+
+/// Shared list of the compiled coloring rules.
+///
+/// The rules are evaluated in order and the first matching rule
+/// determines the color tag of the frame.
+#[derive(Clone, Default)]
+pub struct ColoringTable {
+    rules: Arc<RwLock<Vec<(Filter, Token)>>>,
+    sources: Arc<RwLock<Vec<ColoringRule>>>,
+}
+
+impl ColoringTable {
+    pub fn new() -> ColoringTable {
+        ColoringTable::default()
+    }
+
+    /// Compiles the rules in the profile.
+    ///
+    /// Returns true if the rules have been changed.
+    pub fn configure(&self, profile: &Profile) -> bool {
+        let mut sources = self.sources.write();
+        if *sources == profile.coloring_rules() {
+            return false;
+        }
+        *self.rules.write() = compile(profile)
+            .into_iter()
+            .filter_map(|(rule, filter)| filter.ok().map(|filter| (filter, rule)))
+            .map(|(filter, rule)| (filter, Token::from(rule.color.as_str())))
+            .collect();
+        *sources = profile.coloring_rules().to_vec();
+        true
+    }
+
+    /// Returns the color tag of the first rule matching the frame.
+    pub fn color(&self, frame: &Frame, links: &LinkTable) -> Option<Token> {
+        let rules = self.rules.read();
+        if rules.is_empty() {
+            return None;
+        }
+        let links = links.get(frame.index());
+        let note = frame.note();
+        let ctx = Context::new(frame.layers())
+            .with_links(&links)
+            .with_note(note.marked, &note.comment);
+        rules
+            .iter()
+            .find(|(filter, _)| filter.test(&ctx))
+            .map(|(_, color)| *color)
+    }
+
+    /// Updates the color tag of the frame.
+    pub fn apply(&self, frame: &Frame, links: &LinkTable) {
+        frame.set_color(self.color(frame, links));
+    }
+}
+
+/// Compiles the coloring rules with the named filters in the profile.
+pub fn compile(profile: &Profile) -> Vec<(&ColoringRule, FilterResult<Filter>)> {
+    profile
+        .coloring_rules()
+        .iter()
+        .map(|rule| {
+            (
+                rule,
+                Filter::compile_with(&rule.filter, profile.named_filters()),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use coloring::{ColoringRule, ColoringTable};
+    use frame::Frame;
+    use genet_abi::{
+        fixed::{Fixed, MutFixed},
+        layer::{Layer, LayerClass},
+        slice::ByteSlice,
+        token::Token,
+    };
+    use link::LinkTable;
+    use profile::Profile;
+
+    fn rule(filter: &str, color: &str) -> ColoringRule {
+        ColoringRule {
+            filter: filter.to_string(),
+            color: color.to_string(),
+        }
+    }
+
+    #[test]
+    fn first_match() {
+        let class = Fixed::new(LayerClass::builder("c0l0r").build());
+        let frame = Frame::new(0, MutFixed::new(Layer::new(class, ByteSlice::new())));
+        let links = LinkTable::new();
+
+        let table = ColoringTable::new();
+        assert_eq!(table.color(&frame, &links), None);
+
+        let mut profile = Profile::new();
+        profile
+            .set_coloring_rules(&[
+                rule("false", "gray"),
+                rule("c0l0r", "green"),
+                rule("true", "red"),
+            ])
+            .unwrap();
+        assert!(table.configure(&profile));
+        assert!(!table.configure(&profile));
+        table.apply(&frame, &links);
+        assert_eq!(frame.color(), Some(Token::from("green")));
+
+        profile
+            .set_coloring_rules(&[rule("false", "gray")])
+            .unwrap();
+        assert!(table.configure(&profile));
+        table.apply(&frame, &links);
+        assert_eq!(frame.color(), None);
+
+        assert!(profile.set_coloring_rules(&[rule("(", "red")]).is_err());
+        assert_eq!(profile.coloring_rules(), &[rule("false", "gray")][..]);
+    }
+}
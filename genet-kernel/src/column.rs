@@ -0,0 +1,163 @@
+use array_vec::ArrayVec;
+use fnv::FnvHashMap;
+use frame::Frame;
+use genet_abi::variant::Variant;
+use genet_filter::{context::Context, named::NamedFilters, result::Result, Filter};
+use link::LinkTable;
+use parking_lot::RwLock;
+use std::{ops::Range, sync::Arc};
+
+/// A column of the frame table declared in the package manifest.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Column {
+    pub id: String,
+    pub name: String,
+
+    #[serde(default)]
+    pub description: String,
+
+    /// An attribute ID such as `http.host`, or an expression in the filter syntax.
+    pub value: String,
+}
+
+#[derive(Debug)]
+struct Entry {
+    filter: Filter,
+    values: Vec<Option<Variant>>,
+}
+
+/// Cache of the column values of the stored frames.
+///
+/// The values are computed on demand and kept until the frames are decoded again.
+#[derive(Clone, Default, Debug)]
+pub struct ColumnCache {
+    entries: Arc<RwLock<FnvHashMap<String, Entry>>>,
+}
+
+impl ColumnCache {
+    pub fn new() -> ColumnCache {
+        ColumnCache::default()
+    }
+
+    /// Returns the values of the column for the frames in the range.
+    ///
+    /// Frames without the value get `Variant::Nil`.
+    pub fn values(
+        &self,
+        column: &Column,
+        named: &NamedFilters,
+        frames: &ArrayVec<Frame>,
+        links: &LinkTable,
+        range: Range<usize>,
+    ) -> Result<Vec<Variant>> {
+        let end = range.end.min(frames.len());
+        let start = range.start.min(end);
+        let mut entries = self.entries.write();
+        let recompile = entries
+            .get(&column.id)
+            .map_or(true, |entry| entry.filter.source() != column.value);
+        if recompile {
+            let filter = Filter::compile_with(&column.value, named)?;
+            entries.insert(
+                column.id.clone(),
+                Entry {
+                    filter,
+                    values: Vec::new(),
+                },
+            );
+        }
+        let entry = entries.get_mut(&column.id).unwrap();
+        if entry.values.len() < end {
+            entry.values.resize(end, None);
+        }
+        let Entry { filter, values } = entry;
+        Ok((start..end)
+            .map(|index| {
+                values[index]
+                    .get_or_insert_with(|| {
+                        let frame = frames.get(index).unwrap();
+                        let links = links.get(frame.index());
+                        let note = frame.note();
+                        let ctx = Context::new(frame.layers())
+                            .with_links(&links)
+                            .with_note(note.marked, &note.comment);
+                        filter.eval(&ctx)
+                    })
+                    .clone()
+            })
+            .collect())
+    }
+
+    pub fn clear(&self) {
+        self.entries.write().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use array_vec::ArrayVec;
+    use column::{Column, ColumnCache};
+    use frame::Frame;
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        fixed::{Fixed, MutFixed},
+        layer::{Layer, LayerClass},
+        slice::ByteSlice,
+        variant::Variant,
+    };
+    use genet_filter::named::NamedFilters;
+    use link::LinkTable;
+
+    #[test]
+    fn values() {
+        let class = Fixed::new(LayerClass::builder("c0lumn").build());
+        let len = Fixed::new(AttrClass::builder("c0lumn.len").build());
+        let mut frames = ArrayVec::new();
+        for index in 0..10 {
+            let mut layer = Layer::new(class.clone(), ByteSlice::new());
+            if index % 2 == 0 {
+                layer.add_attr(Attr::builder(len.clone()).value(index as u64).build());
+            }
+            frames.push(Frame::new(index, MutFixed::new(layer)));
+        }
+        let links = LinkTable::new();
+        let named = NamedFilters::new();
+        let cache = ColumnCache::new();
+
+        let mut column = Column {
+            id: "c0lumn.len".to_string(),
+            name: "Length".to_string(),
+            description: String::new(),
+            value: "c0lumn.len".to_string(),
+        };
+        let values = cache
+            .values(&column, &named, &frames, &links, 2..5)
+            .unwrap();
+        assert_eq!(
+            values,
+            vec![Variant::UInt64(2), Variant::Nil, Variant::UInt64(4)]
+        );
+        let values = cache
+            .values(&column, &named, &frames, &links, 8..20)
+            .unwrap();
+        assert_eq!(values, vec![Variant::UInt64(8), Variant::Nil]);
+
+        column.value = "c0lumn.len > 4".to_string();
+        let values = cache
+            .values(&column, &named, &frames, &links, 4..7)
+            .unwrap();
+        assert_eq!(
+            values,
+            vec![
+                Variant::Bool(false),
+                Variant::Bool(false),
+                Variant::Bool(true)
+            ]
+        );
+
+        column.value = "c0lumn.len >".to_string();
+        assert!(cache
+            .values(&column, &named, &frames, &links, 2..3)
+            .is_err());
+    }
+}
@@ -0,0 +1,150 @@
+use serde_json::{self, Value};
+use std::{error, fmt};
+
+/// The type of a config value.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigType {
+    String,
+    Boolean,
+    Integer,
+    Number,
+    Array,
+    Object,
+}
+
+/// A config schema declared in the `configSchema` section of the package manifest.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ConfigSchema {
+    #[serde(rename = "type")]
+    pub typ: ConfigType,
+
+    #[serde(default)]
+    pub default: Option<Value>,
+
+    #[serde(default)]
+    pub minimum: Option<f64>,
+
+    #[serde(default)]
+    pub maximum: Option<f64>,
+
+    #[serde(default, rename = "enum")]
+    pub values: Option<Vec<Value>>,
+}
+
+impl ConfigSchema {
+    /// Parses the JSON schema and validates its default value.
+    pub fn parse(key: &str, schema: &str) -> Result<ConfigSchema, ConfigError> {
+        let schema: ConfigSchema =
+            serde_json::from_str(schema).map_err(|err| ConfigError::new(key, &err.to_string()))?;
+        if let Some(default) = &schema.default {
+            schema.validate(key, default)?;
+        }
+        Ok(schema)
+    }
+
+    /// Validates the value.
+    pub fn validate(&self, key: &str, value: &Value) -> Result<(), ConfigError> {
+        let valid = match self.typ {
+            ConfigType::String => value.is_string(),
+            ConfigType::Boolean => value.is_boolean(),
+            ConfigType::Integer => value.is_i64() || value.is_u64(),
+            ConfigType::Number => value.is_number(),
+            ConfigType::Array => value.is_array(),
+            ConfigType::Object => value.is_object(),
+        };
+        if !valid {
+            return Err(ConfigError::new(
+                key,
+                &format!("expected {:?}, found {}", self.typ, value),
+            ));
+        }
+        if let Some(num) = value.as_f64() {
+            if self.minimum.map_or(false, |min| num < min) {
+                return Err(ConfigError::new(
+                    key,
+                    &format!("{} is less than the minimum", value),
+                ));
+            }
+            if self.maximum.map_or(false, |max| num > max) {
+                return Err(ConfigError::new(
+                    key,
+                    &format!("{} is greater than the maximum", value),
+                ));
+            }
+        }
+        if let Some(values) = &self.values {
+            if !values.contains(value) {
+                return Err(ConfigError::new(
+                    key,
+                    &format!("{} is not one of the allowed values", value),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An error for an invalid config value or schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    key: String,
+    message: String,
+}
+
+impl ConfigError {
+    pub fn new(key: &str, message: &str) -> ConfigError {
+        ConfigError {
+            key: key.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Returns the key of the config.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid config {}: {}", self.key, self.message)
+    }
+}
+
+impl error::Error for ConfigError {
+    fn description(&self) -> &str {
+        &self.message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::ConfigSchema;
+    use serde_json::Value;
+
+    #[test]
+    fn validate() {
+        let schema = ConfigSchema::parse(
+            "a.snapshotLength",
+            r#"{"type": "integer", "minimum": 0, "default": 2048}"#,
+        )
+        .unwrap();
+        assert_eq!(schema.default, Some(Value::from(2048)));
+        assert!(schema.validate("a", &Value::from(0)).is_ok());
+        assert!(schema.validate("a", &Value::from(-1)).is_err());
+        assert!(schema.validate("a", &Value::from(1.5)).is_err());
+        assert!(schema.validate("a", &Value::from("2048")).is_err());
+
+        let schema = ConfigSchema::parse(
+            "a.mode",
+            r#"{"type": "string", "enum": ["fast", "full"], "default": "full"}"#,
+        )
+        .unwrap();
+        assert!(schema.validate("a", &Value::from("fast")).is_ok());
+        assert!(schema.validate("a", &Value::from("fats")).is_err());
+
+        assert!(ConfigSchema::parse("a", r#"{"type": "boolean", "default": 0}"#).is_err());
+        assert!(ConfigSchema::parse("a", r#"{"type": "bool"}"#).is_err());
+    }
+}
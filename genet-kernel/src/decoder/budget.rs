@@ -0,0 +1,138 @@
+use std::{
+    cell::Cell,
+    time::{Duration, Instant},
+};
+
+/// Instructions executed between the deadline checks.
+const CHECK_INTERVAL: u64 = 65_536;
+
+/// Limits on the work a worker may do on a single layer.
+///
+/// WebAssembly workers are metered and trapped as soon as either limit is exceeded.
+/// Native workers cannot be interrupted, so the time budget is checked after
+/// they return and the results of a slow worker are discarded. A native worker
+/// running past the deadline is reported as stalled in the statistics, but it
+/// still blocks the decoding until it returns.
+#[derive(Serialize, Clone, Copy, Default, Debug, PartialEq)]
+pub struct Budget {
+    pub time: Option<Duration>,
+    pub instructions: Option<u64>,
+}
+
+#[derive(Clone, Copy)]
+struct Limit {
+    deadline: Option<Instant>,
+    instructions: Option<u64>,
+    used: u64,
+    checked: u64,
+    exhausted: bool,
+}
+
+thread_local! {
+    static LIMIT: Cell<Option<Limit>> = Cell::new(None);
+}
+
+/// The budget of the current thread, active until dropped.
+pub struct Scope {
+    start: Instant,
+    time: Option<Duration>,
+}
+
+impl Scope {
+    pub fn enter(budget: &Budget) -> Scope {
+        let start = Instant::now();
+        LIMIT.with(|limit| {
+            limit.set(Some(Limit {
+                deadline: budget.time.map(|time| start + time),
+                instructions: budget.instructions,
+                used: 0,
+                checked: 0,
+                exhausted: false,
+            }))
+        });
+        Scope {
+            start,
+            time: budget.time,
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Returns the deadline of the time budget.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.time.map(|time| self.start + time)
+    }
+
+    /// Returns true if the budget has been exceeded since entering the scope.
+    pub fn exceeded(&self) -> bool {
+        let exhausted = LIMIT.with(|limit| limit.get().map_or(false, |l| l.exhausted));
+        exhausted || self.time.map_or(false, |time| self.elapsed() > time)
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        LIMIT.with(|limit| limit.set(None));
+    }
+}
+
+/// Charges the instructions to the budget of the current thread.
+///
+/// Returns false if the budget is exhausted.
+pub fn consume(instructions: u64) -> bool {
+    LIMIT.with(|cell| {
+        let mut limit = match cell.get() {
+            Some(limit) => limit,
+            None => return true,
+        };
+        limit.used += instructions;
+        if limit.instructions.map_or(false, |max| limit.used > max) {
+            limit.exhausted = true;
+        } else if limit.used >= limit.checked + CHECK_INTERVAL {
+            limit.checked = limit.used;
+            if limit
+                .deadline
+                .map_or(false, |deadline| Instant::now() > deadline)
+            {
+                limit.exhausted = true;
+            }
+        }
+        cell.set(Some(limit));
+        !limit.exhausted
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use decoder::budget::{consume, Budget, Scope};
+    use std::{thread, time::Duration};
+
+    #[test]
+    fn instructions() {
+        assert!(consume(1_000_000));
+        let scope = Scope::enter(&Budget {
+            time: None,
+            instructions: Some(100),
+        });
+        assert!(consume(60));
+        assert!(!scope.exceeded());
+        assert!(!consume(60));
+        assert!(scope.exceeded());
+        drop(scope);
+        assert!(consume(1_000_000));
+    }
+
+    #[test]
+    fn time() {
+        let scope = Scope::enter(&Budget {
+            time: Some(Duration::from_millis(1)),
+            instructions: None,
+        });
+        assert!(!scope.exceeded());
+        thread::sleep(Duration::from_millis(5));
+        assert!(scope.exceeded());
+        assert!(!consume(65_536));
+    }
+}
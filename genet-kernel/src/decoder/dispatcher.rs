@@ -1,37 +1,107 @@
+use decoder::{
+    budget::{Budget, Scope},
+    stats::{Counters, Statistics},
+    table::DispatchTable,
+    trace::{self, TraceEntry, TraceResult},
+};
+use flow::{self, Key};
+use fnv::FnvHashMap;
 use frame::Frame;
 use genet_abi::{
     context::Context,
-    decoder::{DecoderBox, ExecType, Metadata, WorkerBox},
+    decoder::{DecoderBox, ExecType, Metadata, Status, WorkerBox},
     fixed::MutFixed,
-    layer::{Layer, Parent},
+    layer::{Annotation, Layer, Parent, Severity},
 };
 use profile::Profile;
+use std::{sync::Arc, time::Duration};
+
+/// The states of the decoders not accessed within this number of frames are discarded.
+const STATE_TIMEOUT: u32 = 65_536;
+
+const STATE_EXPIRE_INTERVAL: u32 = 1024;
+
+/// Decodes the frame with new dispatchers for both passes.
+///
+/// The decoders start without the states built from the previous frames.
+pub fn decode(profile: &Profile, frame: &mut Frame) {
+    let stats = Statistics::new();
+    for typ in &[ExecType::ParallelSync, ExecType::SerialSync] {
+        Dispatcher::new(typ, profile, &stats).process_frame(frame);
+    }
+}
 
 pub struct Dispatcher {
     runners: Vec<Runner>,
+    table: DispatchTable,
+    expired: u32,
+    trace: bool,
 }
 
 impl Dispatcher {
-    pub fn new(typ: &ExecType, profile: &Profile) -> Dispatcher {
-        let runners = profile
-            .decoders()
-            .map(|d| Runner::new(typ, profile.context(), *d))
-            .collect();
-        Dispatcher { runners }
+    pub fn new(typ: &ExecType, profile: &Profile, stats: &Statistics) -> Dispatcher {
+        let decoders = profile.decoders().cloned().collect::<Vec<_>>();
+        Self::with_decoders(
+            typ,
+            &decoders,
+            &|| profile.context(),
+            profile.decode_budget(),
+            profile.trace(),
+            stats,
+        )
     }
 
-    pub fn runners(&mut self) -> Vec<OnceRunner> {
-        self.runners
-            .iter_mut()
-            .map(|r| OnceRunner::new(r))
-            .collect()
+    fn with_decoders(
+        typ: &ExecType,
+        decoders: &[DecoderBox],
+        context: &Fn() -> Context,
+        budget: &Budget,
+        trace: bool,
+        stats: &Statistics,
+    ) -> Dispatcher {
+        let runners = decoders
+            .iter()
+            .enumerate()
+            .map(|(index, d)| Runner::new(typ, context(), *d, budget, stats, index))
+            .collect::<Vec<_>>();
+        let table = DispatchTable::new(runners.iter().map(|r| &r.metadata));
+        Dispatcher {
+            runners,
+            table,
+            expired: 0,
+            trace,
+        }
     }
 
     pub fn process_frame(&mut self, frame: &mut Frame) {
+        if frame.is_retained() {
+            return;
+        }
+        let expire = frame.index() >= self.expired + STATE_EXPIRE_INTERVAL;
+        if expire {
+            self.expired = frame.index();
+        }
+        let frame_index = frame.index();
+        for runner in &mut self.runners {
+            runner.ctx.set_frame_index(frame_index);
+            if expire {
+                let threshold = frame_index.saturating_sub(STATE_TIMEOUT);
+                runner.ctx.expire_states(threshold);
+                runner.pending.retain(|_, index| *index >= threshold);
+            }
+        }
         let mut indices = frame.fetch_tree_indices();
         let mut layers = frame.fetch_layers();
+        let initial = layers.len();
         let mut offset = 0;
-        let mut runners = self.runners();
+        let mut entries = Vec::new();
+        let tracing = self.trace;
+        let table = &self.table;
+        let mut runners = self
+            .runners
+            .iter_mut()
+            .map(|r| OnceRunner::new(r))
+            .collect::<Vec<_>>();
         loop {
             let len = layers.len() - offset;
             for index in offset..layers.len() {
@@ -40,16 +110,121 @@ impl Dispatcher {
                         continue;
                     }
                 }
+                let mut mask = table.lookup(&layers[index]);
+                if index < initial {
+                    for (enabled, r) in mask.iter_mut().zip(runners.iter()) {
+                        *enabled = *enabled && !r.runner.secondary;
+                    }
+                }
+
+                // The workers waiting for more data of the flow are invoked again
+                // regardless of the triggers and the scores.
+                let mut key = None;
+                if runners.iter().any(|r| !r.runner.pending.is_empty()) {
+                    key = flow::layers_key(&layers[..=index]);
+                }
+                let resumed = runners
+                    .iter()
+                    .map(|r| {
+                        key.as_ref()
+                            .map_or(false, |key| r.runner.pending.contains_key(key))
+                    })
+                    .collect::<Vec<_>>();
+                for (enabled, resumed) in mask.iter_mut().zip(resumed.iter()) {
+                    *enabled = *enabled || *resumed;
+                }
+
+                let scores = runners
+                    .iter_mut()
+                    .zip(mask.iter())
+                    .map(|(r, enabled)| {
+                        if !*enabled {
+                            return 0;
+                        }
+                        let layer =
+                            Parent::from_mut_ref(unsafe { &mut *layers[index].as_mut_ptr() });
+                        r.probe(&layers, &indices, &layer)
+                    })
+                    .collect::<Vec<_>>();
+                // Only the highest-scoring worker runs, the first registered one on a tie.
+                // Workers scoring 0 do not compete.
+                let best = scores.iter().cloned().max().unwrap_or(0);
+                let winner = scores.iter().position(|score| *score == best);
+                let outscored = scores
+                    .iter()
+                    .enumerate()
+                    .map(|(i, score)| *score > 0 && Some(i) != winner && !resumed[i])
+                    .collect::<Vec<_>>();
+                let layer_id = if tracing {
+                    layers[index].id().to_string()
+                } else {
+                    String::new()
+                };
+                if tracing {
+                    for (((r, score), enabled), outscored) in runners
+                        .iter()
+                        .zip(scores.iter())
+                        .zip(mask.iter())
+                        .zip(outscored.iter())
+                    {
+                        if !*enabled || !r.runner.is_active() {
+                            continue;
+                        }
+                        let result = if *outscored {
+                            TraceResult::Outscored
+                        } else if r.used {
+                            TraceResult::Used
+                        } else {
+                            continue;
+                        };
+                        entries.push(r.runner.trace(
+                            index,
+                            &layer_id,
+                            *score,
+                            result,
+                            Duration::new(0, 0),
+                        ));
+                    }
+                }
                 let mut children = 0;
                 loop {
                     let mut executed = 0;
-                    for mut r in &mut runners.iter_mut() {
+                    for ((((r, score), enabled), outscored), resumed) in runners
+                        .iter_mut()
+                        .zip(scores.iter())
+                        .zip(mask.iter())
+                        .zip(outscored.iter())
+                        .zip(resumed.iter())
+                    {
+                        if !*enabled || *outscored {
+                            continue;
+                        }
                         let mut layer =
                             Parent::from_mut_ref(unsafe { &mut *layers[index].as_mut_ptr() });
-                        let done = r.execute(&layers, &mut layer);
-                        if done {
+                        let (status, result) = r.execute(&layers, &indices, &mut layer);
+                        if status == Status::Done {
                             executed += 1;
                         }
+                        if status == Status::Pending || (*resumed && status != Status::Skip) {
+                            if key.is_none() {
+                                key = flow::layers_key(&layers[..=index]);
+                            }
+                            if let Some(key) = &key {
+                                r.runner
+                                    .set_pending(key, status == Status::Pending, frame_index);
+                            }
+                        }
+                        let aborted = match &result {
+                            Some((TraceResult::Aborted, _)) => true,
+                            _ => false,
+                        };
+                        if let (true, Some((result, elapsed))) = (tracing, result) {
+                            entries.push(r.runner.trace(index, &layer_id, *score, result, elapsed));
+                        }
+                        if aborted {
+                            // The layers added by the aborted worker are discarded.
+                            continue;
+                        }
                         let mut results: Vec<MutFixed<Layer>> = layer
                             .children()
                             .iter()
@@ -73,6 +248,9 @@ impl Dispatcher {
 
         frame.set_layers(layers);
         frame.set_tree_indices(indices);
+        if tracing {
+            frame.extend_trace(entries);
+        }
     }
 }
 
@@ -82,34 +260,134 @@ struct Runner {
     decoder: DecoderBox,
     metadata: Metadata,
     worker: Option<WorkerBox>,
+    counters: Arc<Counters>,
+    budget: Budget,
+    secondary: bool,
+
+    /// The flows for which the worker returned `Status::Pending`,
+    /// with the index of the frame.
+    pending: FnvHashMap<Key, u32>,
 }
 
 impl Runner {
-    fn new(typ: &ExecType, ctx: Context, decoder: DecoderBox) -> Runner {
+    fn new(
+        typ: &ExecType,
+        ctx: Context,
+        decoder: DecoderBox,
+        budget: &Budget,
+        stats: &Statistics,
+        index: usize,
+    ) -> Runner {
+        let metadata = decoder.metadata();
+
+        // Layers created by serial decoders, e.g. reassembled datagrams, have
+        // not been seen by the parallel pass, so parallel decoders also run
+        // on them in the serial pass.
+        let secondary =
+            *typ == ExecType::SerialSync && metadata.exec_type == ExecType::ParallelSync;
         let mut runner = Runner {
             ctx,
             typ: typ.clone(),
             decoder,
-            metadata: decoder.metadata(),
+            counters: stats.counters(index, &metadata.id),
+            metadata,
             worker: None,
+            budget: *budget,
+            secondary,
+            pending: FnvHashMap::default(),
         };
         runner.reset();
         runner
     }
 
-    fn execute(&mut self, layers: &[MutFixed<Layer>], layer: &mut Parent) -> bool {
-        if let Some(worker) = &mut self.worker {
-            match worker.decode(&mut self.ctx, layers, layer) {
-                Ok(done) => done,
-                Err(_) => true,
+    fn execute(
+        &mut self,
+        layers: &[MutFixed<Layer>],
+        indices: &[u8],
+        layer: &mut Parent,
+    ) -> (Status, Option<(TraceResult, Duration)>) {
+        let (result, elapsed) = if let Some(worker) = &mut self.worker {
+            let scope = Scope::enter(&self.budget);
+            let counters = &self.counters;
+            let frame = self.ctx.frame_index();
+            let watch = scope
+                .deadline()
+                .map(|deadline| counters.watch(frame, deadline));
+            let result = worker.decode(&mut self.ctx, layers, indices, layer);
+            drop(watch);
+            let elapsed = scope.elapsed();
+            self.counters.record(&result, elapsed);
+            if scope.exceeded() {
+                (None, elapsed)
+            } else {
+                (Some(result), elapsed)
+            }
+        } else {
+            return (Status::Done, None);
+        };
+        match result {
+            Some(Ok(status)) => (status, Some((status.into(), elapsed))),
+            Some(Err(err)) => {
+                let message = err.to_string();
+                (
+                    Status::Done,
+                    Some((TraceResult::Error { message }, elapsed)),
+                )
             }
+            None => {
+                // The layer is not offered to the worker again, but the worker keeps
+                // its state so that the flows in progress survive a single slow frame.
+                self.counters.record_aborted();
+                layer.annotate(Annotation::new(
+                    Severity::Warn,
+                    &format!("{}: decode budget exceeded", self.metadata.id),
+                ));
+                (Status::Done, Some((TraceResult::Aborted, elapsed)))
+            }
+        }
+    }
+
+    fn set_pending(&mut self, key: &Key, pending: bool, index: u32) {
+        if pending {
+            self.pending.insert(key.clone(), index);
+        } else {
+            self.pending.remove(key);
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.worker.is_some()
+    }
+
+    fn trace(
+        &self,
+        layer: usize,
+        layer_id: &str,
+        score: u8,
+        result: TraceResult,
+        elapsed: Duration,
+    ) -> TraceEntry {
+        TraceEntry {
+            decoder: self.metadata.id.clone(),
+            exec_type: self.typ.clone(),
+            layer,
+            layer_id: layer_id.to_string(),
+            score,
+            result,
+            time_ns: trace::nanos(elapsed),
+        }
+    }
+
+    fn probe(&mut self, layers: &[MutFixed<Layer>], indices: &[u8], layer: &Parent) -> u8 {
+        if let Some(worker) = &mut self.worker {
+            worker.probe(&self.ctx, layers, indices, layer)
         } else {
-            true
+            0
         }
     }
 
     fn reset(&mut self) {
-        self.worker = if self.metadata.exec_type == self.typ {
+        self.worker = if self.metadata.exec_type == self.typ || self.secondary {
             Some(self.decoder.new_worker(&self.ctx))
         } else {
             None
@@ -130,15 +408,260 @@ impl<'a> OnceRunner<'a> {
         }
     }
 
-    fn execute(&mut self, layers: &[MutFixed<Layer>], layer: &mut Parent) -> bool {
+    fn execute(
+        &mut self,
+        layers: &[MutFixed<Layer>],
+        indices: &[u8],
+        layer: &mut Parent,
+    ) -> (Status, Option<(TraceResult, Duration)>) {
         if !self.used {
-            let done = self.runner.execute(layers, layer);
-            if done {
+            let (status, result) = self.runner.execute(layers, indices, layer);
+            // A pending worker has consumed the layer; it will be invoked again
+            // for the next layer of the flow, but not twice for the same layer.
+            if status != Status::Skip {
                 self.used = true;
             }
-            done
+            (status, result)
+        } else {
+            (Status::Skip, None)
+        }
+    }
+
+    fn probe(&mut self, layers: &[MutFixed<Layer>], indices: &[u8], layer: &Parent) -> u8 {
+        if !self.used {
+            self.runner.probe(layers, indices, layer)
         } else {
-            false
+            0
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use decoder::{budget::Budget, dispatcher::Dispatcher, stats::Statistics};
+    use fnv::FnvHashMap;
+    use frame::Frame;
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        context::Context,
+        decoder::{Decoder, DecoderBox, ExecType, Metadata, Status, Trigger, Worker},
+        fixed::{Fixed, MutFixed},
+        layer::{Layer, LayerClass, LayerStack, Parent},
+        result::Result,
+        slice::ByteSlice,
+        token::Token,
+        variant::Variant,
+    };
+    use std::{thread, time::Duration};
+
+    /// Adds a child layer with the ID of the decoder to the root layer `d15p`
+    /// when the status is `Done`.
+    #[derive(Clone)]
+    struct TestDecoder {
+        id: &'static str,
+        score: u8,
+        triggers: Vec<Trigger>,
+
+        /// The status returned by each call; the last one is repeated.
+        statuses: Vec<Status>,
+
+        /// The time spent on the first call.
+        delay: Duration,
+    }
+
+    impl TestDecoder {
+        fn new(id: &'static str) -> TestDecoder {
+            TestDecoder {
+                id,
+                score: 0,
+                triggers: Vec::new(),
+                statuses: vec![Status::Done],
+                delay: Duration::new(0, 0),
+            }
+        }
+    }
+
+    struct TestWorker {
+        decoder: TestDecoder,
+        calls: u32,
+    }
+
+    impl Worker for TestWorker {
+        fn decode(
+            &mut self,
+            _: &mut Context,
+            _: &LayerStack,
+            parent: &mut Parent,
+        ) -> Result<Status> {
+            if parent.id() != Token::from("d15p") {
+                return Ok(Status::Skip);
+            }
+            self.calls += 1;
+            if self.calls == 1 {
+                thread::sleep(self.decoder.delay);
+            }
+            let statuses = &self.decoder.statuses;
+            let status = statuses[(self.calls as usize - 1).min(statuses.len() - 1)];
+            if status == Status::Done {
+                let class = Fixed::new(LayerClass::builder(self.decoder.id).build());
+                parent.add_child(Layer::new(class, ByteSlice::new()));
+            }
+            Ok(status)
+        }
+
+        fn probe(&mut self, _: &Context, _: &LayerStack, parent: &Parent) -> u8 {
+            if parent.id() != Token::from("d15p") {
+                return 0;
+            }
+            self.decoder.score
+        }
+    }
+
+    impl Decoder for TestDecoder {
+        fn new_worker(&self, _: &Context) -> Box<Worker> {
+            Box::new(TestWorker {
+                decoder: self.clone(),
+                calls: 0,
+            })
+        }
+
+        fn metadata(&self) -> Metadata {
+            Metadata {
+                id: self.id.to_string(),
+                triggers: self.triggers.clone(),
+                ..Metadata::default()
+            }
+        }
+    }
+
+    fn dispatcher(decoders: Vec<TestDecoder>, budget: Budget) -> Dispatcher {
+        let decoders = decoders
+            .into_iter()
+            .map(DecoderBox::new)
+            .collect::<Vec<_>>();
+        Dispatcher::with_decoders(
+            &ExecType::ParallelSync,
+            &decoders,
+            &|| Context::new(FnvHashMap::default()),
+            &budget,
+            false,
+            &Statistics::new(),
+        )
+    }
+
+    /// Decodes a frame whose root layer `d15p` has the attributes,
+    /// and returns the IDs of the layers added by the decoders.
+    fn decode(dispatcher: &mut Dispatcher, index: u32, attrs: &[(&str, Variant)]) -> Vec<String> {
+        let class = Fixed::new(LayerClass::builder("d15p").build());
+        let mut root = Layer::new(class, ByteSlice::new());
+        for (id, value) in attrs {
+            let class = Fixed::new(AttrClass::builder(*id).build());
+            root.add_attr(Attr::builder(class).value(value.clone()).build());
+        }
+        let mut frame = Frame::new(index, MutFixed::new(root));
+        dispatcher.process_frame(&mut frame);
+        frame
+            .layers()
+            .iter()
+            .skip(1)
+            .map(|layer| layer.id().to_string())
+            .collect()
+    }
+
+    fn probe(id: &'static str, score: u8) -> TestDecoder {
+        TestDecoder {
+            score,
+            ..TestDecoder::new(id)
+        }
+    }
+
+    #[test]
+    fn outscored() {
+        let decoders = vec![
+            probe("d15p-a", 10),
+            probe("d15p-b", 90),
+            probe("d15p-c", 50),
+        ];
+        let mut dispatcher = dispatcher(decoders, Budget::default());
+        assert_eq!(decode(&mut dispatcher, 0, &[]), vec!["d15p-b"]);
+    }
+
+    #[test]
+    fn tied() {
+        let decoders = vec![
+            probe("d15p-a", 10),
+            probe("d15p-b", 50),
+            probe("d15p-c", 50),
+        ];
+        let mut dispatcher = dispatcher(decoders, Budget::default());
+        assert_eq!(decode(&mut dispatcher, 0, &[]), vec!["d15p-b"]);
+    }
+
+    #[test]
+    fn zero_score() {
+        let decoders = vec![probe("d15p-a", 0), probe("d15p-b", 50), probe("d15p-c", 0)];
+        let mut dispatcher = dispatcher(decoders, Budget::default());
+        assert_eq!(
+            decode(&mut dispatcher, 0, &[]),
+            vec!["d15p-a", "d15p-b", "d15p-c"]
+        );
+
+        let decoders = vec![probe("d15p-a", 0), probe("d15p-b", 0)];
+        let mut dispatcher = dispatcher(decoders, Budget::default());
+        assert_eq!(decode(&mut dispatcher, 0, &[]), vec!["d15p-a", "d15p-b"]);
+    }
+
+    #[test]
+    fn budget_exceeded() {
+        let slow = TestDecoder {
+            delay: Duration::from_millis(20),
+            ..TestDecoder::new("d15p-slow")
+        };
+        let budget = Budget {
+            time: Some(Duration::from_millis(5)),
+            instructions: None,
+        };
+        let mut dispatcher = dispatcher(vec![slow], budget);
+
+        // The layer added by the aborted worker is discarded.
+        assert!(decode(&mut dispatcher, 0, &[]).is_empty());
+
+        // The worker keeps its state and is fast from the second call.
+        assert_eq!(decode(&mut dispatcher, 1, &[]), vec!["d15p-slow"]);
+    }
+
+    #[test]
+    fn pending() {
+        let pending = TestDecoder {
+            triggers: vec![Trigger::new("d15p.type", 1)],
+            statuses: vec![Status::Pending, Status::Done],
+            ..TestDecoder::new("d15p-pend")
+        };
+        let mut dispatcher = dispatcher(vec![pending], Budget::default());
+        let attrs = |typ: u64, dst: u8| {
+            vec![
+                ("d15p.type", Variant::UInt64(typ)),
+                (
+                    "d15p.src",
+                    Variant::Buffer(vec![10, 0, 0, 1].into_boxed_slice()),
+                ),
+                (
+                    "d15p.dst",
+                    Variant::Buffer(vec![10, 0, 0, dst].into_boxed_slice()),
+                ),
+            ]
+        };
+
+        // The worker needs more data of the flow.
+        assert!(decode(&mut dispatcher, 0, &attrs(1, 2)).is_empty());
+
+        // Another flow does not match the trigger.
+        assert!(decode(&mut dispatcher, 1, &attrs(2, 3)).is_empty());
+
+        // The flow is resumed although the trigger does not match.
+        assert_eq!(decode(&mut dispatcher, 2, &attrs(2, 2)), vec!["d15p-pend"]);
+
+        // The worker is no longer pending.
+        assert!(decode(&mut dispatcher, 3, &attrs(2, 2)).is_empty());
+    }
+}
@@ -1,3 +1,7 @@
-mod dispatcher;
+pub mod budget;
+pub mod dispatcher;
 pub mod parallel;
 pub mod serial;
+pub mod stats;
+pub mod table;
+pub mod trace;
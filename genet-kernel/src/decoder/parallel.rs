@@ -1,9 +1,19 @@
 use crossbeam_channel;
-use decoder::dispatcher::Dispatcher;
+use decoder::{dispatcher::Dispatcher, stats::Statistics};
 use frame::Frame;
-use genet_abi::decoder::ExecType;
+use genet_abi::{decoder::ExecType, spill};
 use profile::Profile;
-use std::thread::{self, JoinHandle};
+use std::{
+    cmp,
+    sync::Arc,
+    thread::{self, JoinHandle},
+};
+
+/// The minimum number of frames in a batch sent to a worker.
+const MIN_BATCH_SIZE: usize = 16;
+
+/// The number of batches per worker a large input is split into.
+const BATCHES_PER_WORKER: usize = 4;
 
 pub trait Callback: Sync + Send + Clone {
     fn done(&self, result: Vec<Frame>);
@@ -15,11 +25,22 @@ pub struct Pool {
 }
 
 impl Pool {
-    pub fn new<C: 'static + Callback>(profile: &Profile, callback: &C) -> Pool {
+    pub fn new<C: 'static + Callback>(
+        profile: &Profile,
+        callback: &C,
+        stats: &Statistics,
+        spill: &Arc<spill::Pool>,
+    ) -> Pool {
         let (send, recv) = crossbeam_channel::unbounded::<Option<Vec<Frame>>>();
         let mut handles = Vec::new();
         for _ in 0..profile.concurrency() {
-            handles.push(Self::spawn(profile.clone(), callback.clone(), recv.clone()));
+            handles.push(Self::spawn(
+                profile.clone(),
+                callback.clone(),
+                stats.clone(),
+                spill.clone(),
+                recv.clone(),
+            ));
         }
         Pool {
             sender: send,
@@ -30,10 +51,13 @@ impl Pool {
     fn spawn<C: 'static + Callback>(
         profile: Profile,
         callback: C,
+        stats: Statistics,
+        spill: Arc<spill::Pool>,
         recv: crossbeam_channel::Receiver<Option<Vec<Frame>>>,
     ) -> JoinHandle<()> {
         thread::spawn(move || {
-            let mut disp = Dispatcher::new(&ExecType::ParallelSync, &profile);
+            let _spill = spill::Scope::enter(&spill);
+            let mut disp = Dispatcher::new(&ExecType::ParallelSync, &profile, &stats);
             loop {
                 if let Some(frames) = recv.recv() {
                     if let Some(mut frames) = frames {
@@ -51,7 +75,23 @@ impl Pool {
         })
     }
 
+    /// Queues frames for decoding.
+    ///
+    /// Large inputs are split into batches so that idle workers can take
+    /// over the rest of the frames while the others are busy. The serial
+    /// pool restores the order of the results.
     pub fn process(&mut self, frames: Vec<Frame>) {
+        let workers = cmp::max(self.handles.len(), 1);
+        let size = cmp::max(
+            MIN_BATCH_SIZE,
+            (frames.len() + workers * BATCHES_PER_WORKER - 1) / (workers * BATCHES_PER_WORKER),
+        );
+        let mut frames = frames;
+        while frames.len() > size {
+            let rest = frames.split_off(size);
+            self.sender.send(Some(frames));
+            frames = rest;
+        }
         self.sender.send(Some(frames));
     }
 }
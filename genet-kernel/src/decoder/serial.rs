@@ -1,10 +1,11 @@
 use crossbeam_channel;
-use decoder::dispatcher::Dispatcher;
+use decoder::{dispatcher::Dispatcher, stats::Statistics};
 use frame::Frame;
-use genet_abi::decoder::ExecType;
+use genet_abi::{decoder::ExecType, spill};
 use profile::Profile;
 use std::{
     collections::BTreeMap,
+    sync::Arc,
     thread::{self, JoinHandle},
 };
 
@@ -18,15 +19,32 @@ pub struct Pool {
 }
 
 impl Pool {
-    pub fn new<C: 'static + Callback>(profile: Profile, callback: C) -> Pool {
+    pub fn new<C: 'static + Callback>(
+        profile: Profile,
+        callback: C,
+        stats: Statistics,
+        spill: Arc<spill::Pool>,
+    ) -> Pool {
+        Self::with_start(profile, callback, stats, spill, 0)
+    }
+
+    /// Creates a pool which expects the first frame to have the index `start`.
+    pub fn with_start<C: 'static + Callback>(
+        profile: Profile,
+        callback: C,
+        stats: Statistics,
+        spill: Arc<spill::Pool>,
+        start: u32,
+    ) -> Pool {
         let callback = Box::new(callback);
         let (send, recv) = crossbeam_channel::unbounded::<Option<Vec<Frame>>>();
         let mut handles = Vec::new();
 
         let handle = thread::spawn(move || {
-            let mut disp = Dispatcher::new(&ExecType::SerialSync, &profile);
+            let _spill = spill::Scope::enter(&spill);
+            let mut disp = Dispatcher::new(&ExecType::SerialSync, &profile, &stats);
             let mut map = BTreeMap::new();
-            let mut next = 0;
+            let mut next = start as usize;
             loop {
                 if let Some(frames) = recv.recv() {
                     if let Some(frames) = frames {
@@ -0,0 +1,220 @@
+use fnv::FnvHashMap;
+use genet_abi::decoder::Status;
+use parking_lot::{Mutex, RwLock};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// Runtime statistics of a decoder.
+#[derive(Serialize, Clone, Default, Debug, PartialEq)]
+pub struct DecoderStats {
+    pub id: String,
+    pub decoded: u64,
+    pub pending: u64,
+    pub skipped: u64,
+    pub errors: u64,
+    pub aborted: u64,
+    pub time_ns: u64,
+
+    /// The frame a worker has been decoding beyond the time budget.
+    pub stalled: Option<u32>,
+}
+
+#[derive(Default, Debug)]
+pub struct Counters {
+    decoded: AtomicU64,
+    pending: AtomicU64,
+    skipped: AtomicU64,
+    errors: AtomicU64,
+    aborted: AtomicU64,
+    time_ns: AtomicU64,
+
+    /// The frames being decoded with the deadlines.
+    running: Mutex<Vec<(u32, Instant)>>,
+}
+
+impl Counters {
+    pub fn record<E>(&self, result: &Result<Status, E>, elapsed: Duration) {
+        let counter = match result {
+            Ok(Status::Done) => &self.decoded,
+            Ok(Status::Pending) => &self.pending,
+            Ok(Status::Skip) => &self.skipped,
+            Err(_) => &self.errors,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        let nanos = elapsed.as_secs() * 1_000_000_000 + u64::from(elapsed.subsec_nanos());
+        self.time_ns.fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    /// Records a worker aborted for exceeding the decode budget.
+    pub fn record_aborted(&self) {
+        self.aborted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Marks a worker as decoding the frame until the returned guard is dropped.
+    ///
+    /// The statistics report the worker as stalled once the deadline has passed,
+    /// even if it never returns.
+    pub fn watch(&self, frame: u32, deadline: Instant) -> Watch {
+        self.running.lock().push((frame, deadline));
+        Watch {
+            counters: self,
+            entry: (frame, deadline),
+        }
+    }
+
+    fn stalled(&self) -> Option<u32> {
+        let now = Instant::now();
+        self.running
+            .lock()
+            .iter()
+            .filter(|(_, deadline)| now > *deadline)
+            .map(|(frame, _)| *frame)
+            .min()
+    }
+}
+
+/// A worker call being watched for the deadline.
+pub struct Watch<'a> {
+    counters: &'a Counters,
+    entry: (u32, Instant),
+}
+
+impl<'a> Drop for Watch<'a> {
+    fn drop(&mut self) {
+        let mut running = self.counters.running.lock();
+        if let Some(pos) = running.iter().position(|entry| *entry == self.entry) {
+            running.swap_remove(pos);
+        }
+    }
+}
+
+/// Shared per-decoder counters.
+///
+/// The counters are keyed by the index of the decoder in the profile,
+/// so decoders sharing the same ID are counted separately.
+#[derive(Clone, Default, Debug)]
+pub struct Statistics {
+    counters: Arc<RwLock<FnvHashMap<usize, (String, Arc<Counters>)>>>,
+}
+
+impl Statistics {
+    pub fn new() -> Statistics {
+        Statistics::default()
+    }
+
+    /// Returns the counters for the decoder at the index, creating them if necessary.
+    ///
+    /// The counters are reset if the decoder at the index is replaced by another one.
+    pub fn counters(&self, index: usize, id: &str) -> Arc<Counters> {
+        if let Some((current, counters)) = self.counters.read().get(&index) {
+            if current == id {
+                return counters.clone();
+            }
+        }
+        let mut map = self.counters.write();
+        let entry = map
+            .entry(index)
+            .or_insert_with(|| (id.to_string(), Default::default()));
+        if entry.0 != id {
+            *entry = (id.to_string(), Default::default());
+        }
+        entry.1.clone()
+    }
+
+    /// Returns a snapshot of the counters sorted by the decoder index.
+    pub fn snapshot(&self) -> Vec<DecoderStats> {
+        let counters = self.counters.read();
+        let mut indices = counters.keys().cloned().collect::<Vec<_>>();
+        indices.sort();
+        indices
+            .into_iter()
+            .map(|index| &counters[&index])
+            .map(|(id, c)| DecoderStats {
+                id: id.clone(),
+                decoded: c.decoded.load(Ordering::Relaxed),
+                pending: c.pending.load(Ordering::Relaxed),
+                skipped: c.skipped.load(Ordering::Relaxed),
+                errors: c.errors.load(Ordering::Relaxed),
+                aborted: c.aborted.load(Ordering::Relaxed),
+                time_ns: c.time_ns.load(Ordering::Relaxed),
+                stalled: c.stalled(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use decoder::stats::{DecoderStats, Statistics};
+    use genet_abi::decoder::Status;
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn record() {
+        let stats = Statistics::new();
+        let counters = stats.counters(0, "eth");
+        counters.record::<()>(&Ok(Status::Done), Duration::new(1, 5));
+        counters.record::<()>(&Ok(Status::Skip), Duration::new(0, 10));
+        stats
+            .counters(0, "eth")
+            .record(&Err(()), Duration::new(0, 0));
+        stats.counters(0, "eth").record_aborted();
+        assert_eq!(
+            stats.snapshot(),
+            vec![DecoderStats {
+                id: "eth".to_string(),
+                decoded: 1,
+                pending: 0,
+                skipped: 1,
+                errors: 1,
+                aborted: 1,
+                time_ns: 1_000_000_015,
+                stalled: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn stalled() {
+        let stats = Statistics::new();
+        let counters = stats.counters(0, "eth");
+        let now = Instant::now();
+        let slow = counters.watch(3, now);
+        let fast = counters.watch(4, now + Duration::from_secs(60));
+        thread::sleep(Duration::from_millis(1));
+        assert_eq!(stats.snapshot()[0].stalled, Some(3));
+        drop(slow);
+        assert_eq!(stats.snapshot()[0].stalled, None);
+        drop(fast);
+    }
+
+    #[test]
+    fn index() {
+        let stats = Statistics::new();
+        stats
+            .counters(0, "")
+            .record::<()>(&Ok(Status::Done), Duration::new(0, 1));
+        stats
+            .counters(1, "")
+            .record::<()>(&Ok(Status::Skip), Duration::new(0, 2));
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!((snapshot[0].decoded, snapshot[0].skipped), (1, 0));
+        assert_eq!((snapshot[1].decoded, snapshot[1].skipped), (0, 1));
+
+        stats
+            .counters(1, "tcp")
+            .record::<()>(&Ok(Status::Done), Duration::new(0, 3));
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot[1].id, "tcp");
+        assert_eq!((snapshot[1].decoded, snapshot[1].time_ns), (1, 3));
+    }
+}
@@ -0,0 +1,123 @@
+use fnv::FnvHashMap;
+use genet_abi::{decoder::Metadata, layer::Layer, token::Token, variant::Value};
+
+pub struct DispatchTable {
+    len: usize,
+    attrs: Vec<Token>,
+    bindings: FnvHashMap<(Token, u64), Vec<usize>>,
+    payloads: FnvHashMap<Token, Vec<usize>>,
+    wildcard: Vec<usize>,
+}
+
+impl DispatchTable {
+    pub fn new<'a, I: Iterator<Item = &'a Metadata>>(metadata: I) -> DispatchTable {
+        let mut table = DispatchTable {
+            len: 0,
+            attrs: Vec::new(),
+            bindings: FnvHashMap::default(),
+            payloads: FnvHashMap::default(),
+            wildcard: Vec::new(),
+        };
+        for (index, meta) in metadata.enumerate() {
+            table.len += 1;
+            if meta.triggers.is_empty() {
+                table.wildcard.push(index);
+            }
+            for trigger in &meta.triggers {
+                if let Some(tag) = &trigger.payload {
+                    table
+                        .payloads
+                        .entry(Token::from(tag.as_str()))
+                        .or_insert_with(Vec::new)
+                        .push(index);
+                    continue;
+                }
+                let id = Token::from(trigger.attr.as_str());
+                if !table.attrs.contains(&id) {
+                    table.attrs.push(id);
+                }
+                table
+                    .bindings
+                    .entry((id, trigger.value))
+                    .or_insert_with(Vec::new)
+                    .push(index);
+            }
+        }
+        table
+    }
+
+    /// Returns a mask of the runners to be executed for the layer.
+    pub fn lookup(&self, layer: &Layer) -> Vec<bool> {
+        let mut mask = vec![false; self.len];
+        for index in &self.wildcard {
+            mask[*index] = true;
+        }
+        for id in &self.attrs {
+            let value = layer
+                .attr(*id)
+                .and_then(|attr| attr.try_get(layer).ok())
+                .and_then(|value| Value::<u64>::try_into(value).ok());
+            if let Some(value) = value {
+                if let Some(indices) = self.bindings.get(&(*id, value)) {
+                    for index in indices {
+                        mask[*index] = true;
+                    }
+                }
+            }
+        }
+        for payload in layer.payloads() {
+            if let Some(indices) = self.payloads.get(&payload.tag()) {
+                for index in indices {
+                    mask[*index] = true;
+                }
+            }
+        }
+        mask
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use decoder::table::DispatchTable;
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        decoder::{Metadata, Trigger},
+        fixed::Fixed,
+        layer::{Layer, LayerClass, Payload},
+        slice::ByteSlice,
+        token::Token,
+    };
+
+    #[test]
+    fn lookup() {
+        let metadata = vec![
+            Metadata::default(),
+            Metadata {
+                triggers: vec![Trigger::new("tcp.dst", 80)],
+                ..Metadata::default()
+            },
+            Metadata {
+                triggers: vec![Trigger::new("tcp.dst", 443)],
+                ..Metadata::default()
+            },
+            Metadata {
+                triggers: vec![Trigger::payload("encrypted")],
+                ..Metadata::default()
+            },
+        ];
+        let table = DispatchTable::new(metadata.iter());
+
+        let attr = Fixed::new(AttrClass::builder("tcp.dst").build());
+        let class = Fixed::new(LayerClass::builder(Token::from("tcp")).build());
+        let mut layer = Layer::new(class, ByteSlice::new());
+        layer.add_attr(Attr::builder(attr).value(80u16).build());
+        assert_eq!(table.lookup(&layer), vec![true, true, false, false]);
+
+        let class = Fixed::new(LayerClass::builder(Token::from("udp")).build());
+        let mut layer = Layer::new(class, ByteSlice::new());
+        assert_eq!(table.lookup(&layer), vec![true, false, false, false]);
+
+        layer.add_payload(Payload::new(ByteSlice::new(), "@data:esp").tagged("encrypted"));
+        assert_eq!(table.lookup(&layer), vec![true, false, false, true]);
+    }
+}
@@ -0,0 +1,82 @@
+use genet_abi::decoder::{ExecType, Status};
+use std::time::Duration;
+
+/// A record of a decoder being offered a layer.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct TraceEntry {
+    pub decoder: String,
+    pub exec_type: ExecType,
+
+    /// The index of the layer in the frame.
+    pub layer: usize,
+    pub layer_id: String,
+
+    /// The probe score, or 0 if the decoder has no probe.
+    pub score: u8,
+    pub result: TraceResult,
+    pub time_ns: u64,
+}
+
+/// The outcome of offering a layer to a decoder.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TraceResult {
+    Done,
+    Skip,
+    Pending,
+    Error {
+        message: String,
+    },
+
+    /// The worker exceeded the decode budget and was replaced.
+    Aborted,
+
+    /// Not invoked: another decoder scored higher in the probe.
+    Outscored,
+
+    /// Not invoked: the worker has already consumed a layer of the frame.
+    Used,
+}
+
+impl From<Status> for TraceResult {
+    fn from(status: Status) -> TraceResult {
+        match status {
+            Status::Done => TraceResult::Done,
+            Status::Skip => TraceResult::Skip,
+            Status::Pending => TraceResult::Pending,
+        }
+    }
+}
+
+pub fn nanos(duration: Duration) -> u64 {
+    duration.as_secs() * 1_000_000_000 + u64::from(duration.subsec_nanos())
+}
+
+#[cfg(test)]
+mod tests {
+    use decoder::trace::{TraceEntry, TraceResult};
+    use genet_abi::decoder::ExecType;
+    use serde_json;
+
+    #[test]
+    fn serialize() {
+        let entry = TraceEntry {
+            decoder: "ipv4".to_string(),
+            exec_type: ExecType::ParallelSync,
+            layer: 1,
+            layer_id: "eth".to_string(),
+            score: 0,
+            result: TraceResult::Error {
+                message: "too short".to_string(),
+            },
+            time_ns: 10,
+        };
+        assert_eq!(
+            serde_json::to_string(&entry).unwrap(),
+            concat!(
+                r#"{"decoder":"ipv4","exec_type":"ParallelSync","layer":1,"layer_id":"eth","#,
+                r#""score":0,"result":{"type":"error","message":"too short"},"time_ns":10}"#
+            )
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use fnv::FnvHashMap;
+use frame::Frame;
+use genet_abi::{layer::LayerStack, token::Token, variant::Variant};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// A difference of an attribute between two frames.
+#[derive(Clone, PartialEq, Debug)]
+pub struct AttrChange {
+    pub kind: ChangeKind,
+
+    /// The IDs of the layers from the root to the layer having the attribute,
+    /// e.g. `[link-1]/eth/ipv4`.
+    ///
+    /// Sibling layers with the same ID are distinguished by a suffix, e.g. `sctp/chunk#1`.
+    pub path: String,
+    pub id: Token,
+
+    /// The occurrence of the attribute in the layer, counted from 0.
+    pub index: usize,
+
+    /// The value in the first frame, or `None` if the attribute is added.
+    pub old: Option<Variant>,
+
+    /// The value in the second frame, or `None` if the attribute is removed.
+    pub new: Option<Variant>,
+}
+
+type Key = (String, Token, usize);
+
+/// Compares the decoded attributes of the frames.
+///
+/// The attributes are matched by the layer path, the attribute ID and the occurrence
+/// in the layer. The changes are ordered as the attributes in the first frame,
+/// followed by the attributes only found in the second frame.
+pub fn diff(a: &Frame, b: &Frame) -> Vec<AttrChange> {
+    let old = attrs(a);
+    let new = attrs(b);
+    let new_map = new.iter().cloned().collect::<FnvHashMap<_, _>>();
+    let old_map = old.iter().cloned().collect::<FnvHashMap<_, _>>();
+
+    let mut changes = Vec::new();
+    for (key, value) in old {
+        let (kind, new) = match new_map.get(&key) {
+            Some(new) if *new == value => continue,
+            Some(new) => (ChangeKind::Changed, Some(new.clone())),
+            None => (ChangeKind::Removed, None),
+        };
+        changes.push(change(kind, key, Some(value), new));
+    }
+    for (key, value) in new {
+        if !old_map.contains_key(&key) {
+            changes.push(change(ChangeKind::Added, key, None, Some(value)));
+        }
+    }
+    changes
+}
+
+fn change(kind: ChangeKind, key: Key, old: Option<Variant>, new: Option<Variant>) -> AttrChange {
+    let (path, id, index) = key;
+    AttrChange {
+        kind,
+        path,
+        id,
+        index,
+        old,
+        new,
+    }
+}
+
+fn attrs(frame: &Frame) -> Vec<(Key, Variant)> {
+    let stack = LayerStack::from_layers(frame.layers(), frame.tree_indices());
+    let mut paths = FnvHashMap::default();
+    let mut attrs = Vec::new();
+    for layer in stack.layers() {
+        let mut ids = stack
+            .ancestors(layer)
+            .map(|layer| layer.id().to_string())
+            .collect::<Vec<_>>();
+        ids.reverse();
+        ids.push(layer.id().to_string());
+        let mut path = ids.join("/");
+
+        let count = paths.entry(path.clone()).or_insert(0);
+        if *count > 0 {
+            path += &format!("#{}", count);
+        }
+        *count += 1;
+
+        let mut occurrences = FnvHashMap::default();
+        for attr in layer.headers().iter().chain(layer.attrs().iter()) {
+            let index = occurrences.entry(attr.id()).or_insert(0);
+            let value = attr.try_get(layer).unwrap_or(Variant::Nil);
+            attrs.push(((path.clone(), attr.id(), *index), value));
+            *index += 1;
+        }
+    }
+    attrs
+}
+
+#[cfg(test)]
+mod tests {
+    use diff::{diff, AttrChange, ChangeKind};
+    use frame::Frame;
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        fixed::{Fixed, MutFixed},
+        layer::{Layer, LayerClass},
+        slice::ByteSlice,
+        token::Token,
+        variant::Variant,
+    };
+
+    fn frame(index: u32, attrs: &[(&str, u64)]) -> Frame {
+        let eth = Fixed::new(LayerClass::builder("d1ffa").build());
+        let ip = Fixed::new(LayerClass::builder("d1ffb").build());
+        let mut frame = Frame::new(index, MutFixed::new(Layer::new(eth, ByteSlice::new())));
+        let mut layer = Layer::new(ip, ByteSlice::new());
+        for (id, value) in attrs {
+            let class = Fixed::new(AttrClass::builder(*id).build());
+            layer.add_attr(Attr::builder(class).value(*value).build());
+        }
+        let mut layers = frame.fetch_layers();
+        layers.push(MutFixed::new(layer));
+        frame.set_layers(layers);
+        frame.set_tree_indices(vec![1, 0]);
+        frame
+    }
+
+    #[test]
+    fn changes() {
+        let a = frame(0, &[("d1ffb.ttl", 64), ("d1ffb.id", 1), ("d1ffb.opt", 1)]);
+        let b = frame(1, &[("d1ffb.ttl", 63), ("d1ffb.opt", 1), ("d1ffb.opt", 2)]);
+        let change = |kind, id: &str, index, old: Option<u64>, new: Option<u64>| AttrChange {
+            kind,
+            path: "d1ffa/d1ffb".to_string(),
+            id: Token::from(id),
+            index,
+            old: old.map(Variant::UInt64),
+            new: new.map(Variant::UInt64),
+        };
+        assert_eq!(
+            diff(&a, &b),
+            vec![
+                change(ChangeKind::Changed, "d1ffb.ttl", 0, Some(64), Some(63)),
+                change(ChangeKind::Removed, "d1ffb.id", 0, Some(1), None),
+                change(ChangeKind::Added, "d1ffb.opt", 1, None, Some(2)),
+            ]
+        );
+        assert!(diff(&a, &a).is_empty());
+    }
+}
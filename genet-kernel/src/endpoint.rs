@@ -0,0 +1,226 @@
+use flow::{format_addr, src_dst};
+use fnv::FnvHashMap;
+use frame::Frame;
+use genet_abi::{token::Token, variant::Variant};
+use parking_lot::RwLock;
+use std::{cmp::Ordering, sync::Arc};
+
+/// Traffic counters of an address or a port.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct EndpointStats {
+    pub protocol: String,
+    pub addr: String,
+    pub port: Option<u64>,
+    pub tx_packets: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u64,
+    pub rx_bytes: u64,
+}
+
+impl EndpointStats {
+    pub fn packets(&self) -> u64 {
+        self.tx_packets + self.rx_packets
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.tx_bytes + self.rx_bytes
+    }
+}
+
+/// Sort order of the endpoints.
+///
+/// The counters are sorted in descending order.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Order {
+    Address,
+    Packets,
+    Bytes,
+    TxPackets,
+    TxBytes,
+    RxPackets,
+    RxBytes,
+}
+
+impl Default for Order {
+    fn default() -> Order {
+        Order::Bytes
+    }
+}
+
+impl Order {
+    fn compare(self, a: &EndpointStats, b: &EndpointStats) -> Ordering {
+        let key = |e: &EndpointStats| match self {
+            Order::Address => 0,
+            Order::Packets => e.packets(),
+            Order::Bytes => e.bytes(),
+            Order::TxPackets => e.tx_packets,
+            Order::TxBytes => e.tx_bytes,
+            Order::RxPackets => e.rx_packets,
+            Order::RxBytes => e.rx_bytes,
+        };
+        key(b)
+            .cmp(&key(a))
+            .then_with(|| a.protocol.cmp(&b.protocol))
+            .then_with(|| a.addr.cmp(&b.addr))
+            .then_with(|| a.port.cmp(&b.port))
+    }
+}
+
+type Key = (Token, String, Option<u64>);
+
+/// Shared table of the endpoint statistics.
+///
+/// The counters are updated as the frames are stored.
+#[derive(Clone, Default)]
+pub struct EndpointTable {
+    endpoints: Arc<RwLock<FnvHashMap<Key, EndpointStats>>>,
+}
+
+impl EndpointTable {
+    pub fn new() -> EndpointTable {
+        EndpointTable::default()
+    }
+
+    /// Counts the frame for each pair of the endpoints in the layers.
+    ///
+    /// The ports are counted with the addresses of the nearest layer below them.
+    pub fn insert(&self, frame: &Frame) {
+        let bytes = frame.layers().first().map_or(0, |root| root.data().len()) as u64;
+        let mut pairs = Vec::new();
+        let mut addrs = None;
+        for layer in frame.layers() {
+            match src_dst(layer) {
+                Some((Variant::UInt64(src), Variant::UInt64(dst))) => {
+                    if let Some((ref src_addr, ref dst_addr)) = addrs {
+                        pairs.push((
+                            (layer.id(), src_addr.clone(), Some(src)),
+                            (layer.id(), dst_addr.clone(), Some(dst)),
+                        ));
+                    }
+                }
+                Some((src, dst)) => {
+                    if let (Some(src), Some(dst)) = (format_addr(&src), format_addr(&dst)) {
+                        pairs.push((
+                            (layer.id(), src.clone(), None),
+                            (layer.id(), dst.clone(), None),
+                        ));
+                        addrs = Some((src, dst));
+                    }
+                }
+                None => {}
+            }
+        }
+        if pairs.is_empty() {
+            return;
+        }
+
+        let mut endpoints = self.endpoints.write();
+        for (src, dst) in pairs {
+            let stats = entry(&mut endpoints, src);
+            stats.tx_packets += 1;
+            stats.tx_bytes += bytes;
+            let stats = entry(&mut endpoints, dst);
+            stats.rx_packets += 1;
+            stats.rx_bytes += bytes;
+        }
+    }
+
+    /// Returns the endpoints sorted in the order, up to `limit` entries.
+    pub fn snapshot(&self, order: Order, limit: Option<usize>) -> Vec<EndpointStats> {
+        let mut endpoints = self.endpoints.read().values().cloned().collect::<Vec<_>>();
+        endpoints.sort_by(|a, b| order.compare(a, b));
+        if let Some(limit) = limit {
+            endpoints.truncate(limit);
+        }
+        endpoints
+    }
+
+    pub fn clear(&self) {
+        self.endpoints.write().clear();
+    }
+}
+
+fn entry(endpoints: &mut FnvHashMap<Key, EndpointStats>, key: Key) -> &mut EndpointStats {
+    let (protocol, addr, port) = key.clone();
+    endpoints.entry(key).or_insert_with(|| EndpointStats {
+        protocol: protocol.to_string(),
+        addr,
+        port,
+        tx_packets: 0,
+        tx_bytes: 0,
+        rx_packets: 0,
+        rx_bytes: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use endpoint::{EndpointTable, Order};
+    use frame::Frame;
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        fixed::{Fixed, MutFixed},
+        layer::{Layer, LayerClass},
+        slice::ByteSlice,
+    };
+
+    fn frame(len: usize, addrs: (&[u8], &[u8]), ports: (u64, u64)) -> Frame {
+        let ip = Fixed::new(LayerClass::builder("e1a").build());
+        let udp = Fixed::new(LayerClass::builder("e1b").build());
+        let ip_src = Fixed::new(AttrClass::builder("e1a.src").build());
+        let ip_dst = Fixed::new(AttrClass::builder("e1a.dst").build());
+        let udp_src = Fixed::new(AttrClass::builder("e1b.src").build());
+        let udp_dst = Fixed::new(AttrClass::builder("e1b.dst").build());
+
+        let mut root = Layer::new(ip, ByteSlice::from(vec![0u8; len]));
+        root.add_attr(
+            Attr::builder(ip_src)
+                .value(addrs.0.to_vec().into_boxed_slice())
+                .build(),
+        );
+        root.add_attr(
+            Attr::builder(ip_dst)
+                .value(addrs.1.to_vec().into_boxed_slice())
+                .build(),
+        );
+        let mut frame = Frame::new(0, MutFixed::new(root));
+
+        let mut layer = Layer::new(udp, ByteSlice::new());
+        layer.add_attr(Attr::builder(udp_src).value(ports.0).build());
+        layer.add_attr(Attr::builder(udp_dst).value(ports.1).build());
+        let mut layers = frame.fetch_layers();
+        layers.push(MutFixed::new(layer));
+        frame.set_layers(layers);
+        frame
+    }
+
+    #[test]
+    fn insert() {
+        let a = &[10, 0, 0, 1][..];
+        let b = &[10, 0, 0, 2][..];
+        let table = EndpointTable::new();
+        table.insert(&frame(100, (a, b), (1024, 53)));
+        table.insert(&frame(300, (b, a), (53, 1024)));
+        table.insert(&frame(50, (a, b), (1025, 53)));
+
+        let endpoints = table.snapshot(Order::Bytes, None);
+        assert_eq!(endpoints.len(), 5);
+        let top = &endpoints[0];
+        assert_eq!(
+            (top.protocol.as_str(), top.addr.as_str()),
+            ("e1a", "10.0.0.1")
+        );
+        assert_eq!((top.tx_packets, top.tx_bytes), (2, 150));
+        assert_eq!((top.rx_packets, top.rx_bytes), (1, 300));
+
+        let endpoints = table.snapshot(Order::TxBytes, Some(2));
+        assert_eq!(endpoints.len(), 2);
+        assert_eq!(endpoints[0].tx_bytes, 300);
+        assert_eq!(endpoints[0].port, None);
+        assert_eq!(endpoints[1].port, Some(53));
+
+        table.clear();
+        assert!(table.snapshot(Order::Address, None).is_empty());
+    }
+}
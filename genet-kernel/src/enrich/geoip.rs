@@ -0,0 +1,75 @@
+use enrich::{config_path, ip_addr, Enricher};
+use genet_abi::{layer::Layer, variant::Variant};
+use maxminddb::{geoip2, Reader};
+use parking_lot::RwLock;
+use profile::Profile;
+
+/// Paths to the MaxMind databases of the countries and the autonomous systems.
+const COUNTRY_KEY: &str = "_.decoder.geoip.country";
+const ASN_KEY: &str = "_.decoder.geoip.asn";
+
+#[derive(Default)]
+struct Databases {
+    country: Option<Reader<Vec<u8>>>,
+    asn: Option<Reader<Vec<u8>>>,
+}
+
+/// Provider of the geolocation of IP addresses.
+///
+/// The layers get the `geo.country` pseudo-attributes holding ISO 3166 country codes
+/// from a GeoIP2 Country or City database, and `geo.asn` and `geo.org`
+/// from a GeoIP2 ASN database.
+#[derive(Default)]
+pub struct GeoIp {
+    databases: RwLock<Databases>,
+}
+
+impl GeoIp {
+    pub fn new() -> GeoIp {
+        GeoIp::default()
+    }
+}
+
+impl Enricher for GeoIp {
+    fn configure(&self, profile: &Profile) {
+        let open =
+            |key| config_path(profile, key).and_then(|path| Reader::open_readfile(path).ok());
+        *self.databases.write() = Databases {
+            country: open(COUNTRY_KEY),
+            asn: open(ASN_KEY),
+        };
+    }
+
+    fn enrich(&self, _layer: &Layer, value: &Variant) -> Vec<(&'static str, Variant)> {
+        let mut attrs = Vec::new();
+        let databases = self.databases.read();
+        if databases.country.is_none() && databases.asn.is_none() {
+            return attrs;
+        }
+        let addr = match ip_addr(value) {
+            Some(addr) => addr,
+            None => return attrs,
+        };
+        if let Some(reader) = &databases.country {
+            let code = reader
+                .lookup::<geoip2::Country>(addr)
+                .ok()
+                .and_then(|country| country.country)
+                .and_then(|country| country.iso_code);
+            if let Some(code) = code {
+                attrs.push(("geo.country", Variant::String(code.into())));
+            }
+        }
+        if let Some(reader) = &databases.asn {
+            if let Ok(asn) = reader.lookup::<geoip2::Asn>(addr) {
+                if let Some(number) = asn.autonomous_system_number {
+                    attrs.push(("geo.asn", Variant::UInt64(u64::from(number))));
+                }
+                if let Some(org) = asn.autonomous_system_organization {
+                    attrs.push(("geo.org", Variant::String(org.into())));
+                }
+            }
+        }
+        attrs
+    }
+}
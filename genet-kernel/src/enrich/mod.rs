@@ -0,0 +1,222 @@
+mod geoip;
+mod resolver;
+
+use flow::src_dst;
+use fnv::FnvHashMap;
+use frame::Frame;
+use genet_abi::{
+    attr::{Attr, AttrClass},
+    fixed::Fixed,
+    layer::Layer,
+    variant::Variant,
+};
+use parking_lot::RwLock;
+use profile::Profile;
+use serde_json;
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::Arc,
+};
+
+pub use self::{geoip::GeoIp, resolver::Resolver};
+
+/// A provider of the pseudo-attributes derived from the addresses and the ports.
+pub trait Enricher: Send + Sync {
+    /// Loads the settings of the provider from the profile.
+    fn configure(&self, profile: &Profile);
+
+    /// Returns the pseudo-attributes for the `src` or `dst` attribute value of the layer,
+    /// as pairs of the name and the value.
+    fn enrich(&self, layer: &Layer, value: &Variant) -> Vec<(&'static str, Variant)>;
+
+    /// Learns from the frame before the frames are enriched.
+    fn observe(&self, _frame: &Frame) {}
+
+    /// Forgets the states learned from the frames.
+    fn clear(&self) {}
+}
+
+/// Shared list of the enrichment providers.
+///
+/// Each layer having the `src` and `dst` attributes gets the pseudo-attributes
+/// `<layer>.src.<name>` and `<layer>.dst.<name>` returned by the providers,
+/// e.g. `ipv4.src.geo.country`.
+#[derive(Clone)]
+pub struct Enrichment {
+    providers: Arc<Vec<Box<Enricher>>>,
+    classes: Arc<RwLock<FnvHashMap<String, Fixed<AttrClass>>>>,
+}
+
+impl Default for Enrichment {
+    fn default() -> Enrichment {
+        Enrichment::new(vec![Box::new(Resolver::new()), Box::new(GeoIp::new())])
+    }
+}
+
+impl Enrichment {
+    pub fn new(providers: Vec<Box<Enricher>>) -> Enrichment {
+        Enrichment {
+            providers: Arc::new(providers),
+            classes: Arc::new(RwLock::new(FnvHashMap::default())),
+        }
+    }
+
+    pub fn configure(&self, profile: &Profile) {
+        for provider in self.providers.iter() {
+            provider.configure(profile);
+        }
+    }
+
+    pub fn observe(&self, frame: &Frame) {
+        for provider in self.providers.iter() {
+            provider.observe(frame);
+        }
+    }
+
+    /// Adds the pseudo-attributes to the layers in the frame.
+    pub fn insert(&self, frame: &mut Frame) {
+        for layer in frame.layers_mut() {
+            let (src, dst) = match src_dst(layer) {
+                Some(pair) => pair,
+                None => continue,
+            };
+            let mut attrs = Vec::new();
+            for (dir, value) in &[("src", src), ("dst", dst)] {
+                for provider in self.providers.iter() {
+                    for (name, value) in provider.enrich(layer, value) {
+                        let id = format!("{}.{}.{}", layer.id(), dir, name);
+                        attrs.push((id, value));
+                    }
+                }
+            }
+            for (id, value) in attrs {
+                let class = self.class(id);
+                layer.add_attr(Attr::builder(class).value(value).build());
+            }
+        }
+    }
+
+    pub fn clear(&self) {
+        for provider in self.providers.iter() {
+            provider.clear();
+        }
+    }
+
+    fn class(&self, id: String) -> Fixed<AttrClass> {
+        self.classes
+            .write()
+            .entry(id.clone())
+            .or_insert_with(|| Fixed::new(AttrClass::builder(id.as_str()).build()))
+            .clone()
+    }
+}
+
+/// Returns the JSON config value in the profile.
+fn config(profile: &Profile, key: &str) -> Option<serde_json::Value> {
+    profile
+        .get_config(key)
+        .and_then(|value| serde_json::from_str(&value).ok())
+}
+
+/// Returns the non-empty path in the profile.
+fn config_path(profile: &Profile, key: &str) -> Option<String> {
+    config(profile, key)
+        .and_then(|value| value.as_str().map(String::from))
+        .filter(|path| !path.is_empty())
+}
+
+fn bytes(value: &Variant) -> Option<&[u8]> {
+    match value {
+        Variant::Buffer(data) => Some(&data[..]),
+        Variant::Slice(data) => Some(&data[..]),
+        _ => None,
+    }
+}
+
+fn ip_addr(value: &Variant) -> Option<IpAddr> {
+    if let Variant::Ipv6Addr(addr) = value {
+        return Some(IpAddr::V6(*addr));
+    }
+    let data = bytes(value)?;
+    match data.len() {
+        4 => Some(IpAddr::V4(Ipv4Addr::new(
+            data[0], data[1], data[2], data[3],
+        ))),
+        16 => {
+            let mut addr = [0u8; 16];
+            addr.copy_from_slice(data);
+            Some(IpAddr::V6(Ipv6Addr::from(addr)))
+        }
+        _ => None,
+    }
+}
+
+fn mac_addr(value: &Variant) -> Option<[u8; 6]> {
+    if let Variant::MacAddr(addr) = value {
+        return Some(*addr);
+    }
+    let data = bytes(value)?;
+    if data.len() == 6 {
+        let mut addr = [0u8; 6];
+        addr.copy_from_slice(data);
+        Some(addr)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use enrich::{ip_addr, Enricher, Enrichment};
+    use frame::Frame;
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        fixed::{Fixed, MutFixed},
+        layer::{Layer, LayerClass},
+        slice::ByteSlice,
+        token::Token,
+        variant::Variant,
+    };
+    use profile::Profile;
+
+    struct Private;
+
+    impl Enricher for Private {
+        fn configure(&self, _profile: &Profile) {}
+
+        fn enrich(&self, _layer: &Layer, value: &Variant) -> Vec<(&'static str, Variant)> {
+            match ip_addr(value) {
+                Some(ref addr) if addr.to_string().starts_with("10.") => {
+                    vec![("private", Variant::Bool(true))]
+                }
+                _ => Vec::new(),
+            }
+        }
+    }
+
+    #[test]
+    fn insert() {
+        let ip = Fixed::new(LayerClass::builder("en1").build());
+        let src = Fixed::new(AttrClass::builder("en1.src").build());
+        let dst = Fixed::new(AttrClass::builder("en1.dst").build());
+        let mut layer = Layer::new(ip, ByteSlice::new());
+        layer.add_attr(
+            Attr::builder(src)
+                .value(vec![10, 0, 0, 1].into_boxed_slice())
+                .build(),
+        );
+        layer.add_attr(
+            Attr::builder(dst)
+                .value(vec![192, 168, 0, 1].into_boxed_slice())
+                .build(),
+        );
+        let mut frame = Frame::new(0, MutFixed::new(layer));
+
+        let enrichment = Enrichment::new(vec![Box::new(Private)]);
+        enrichment.insert(&mut frame);
+        let root = &frame.layers()[0];
+        let attr = root.attr(Token::from("en1.src.private")).unwrap();
+        assert_eq!(attr.try_get(root).unwrap(), Variant::Bool(true));
+        assert!(root.attr(Token::from("en1.dst.private")).is_none());
+    }
+}
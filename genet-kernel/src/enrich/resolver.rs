@@ -0,0 +1,362 @@
+use enrich::{config, config_path, ip_addr, mac_addr, Enricher};
+use fnv::FnvHashMap;
+use frame::Frame;
+use genet_abi::{layer::Layer, token::Token, variant::Variant};
+use parking_lot::RwLock;
+use profile::Profile;
+use std::{fs, net::IpAddr, sync::Arc};
+
+/// Enables the name attributes. The value is a boolean.
+const ENABLED_KEY: &str = "_.decoder.resolveNames";
+
+/// Paths to the hosts file, the OUI list and the services file.
+const HOSTS_KEY: &str = "_.decoder.resolver.hosts";
+const OUI_KEY: &str = "_.decoder.resolver.oui";
+const SERVICES_KEY: &str = "_.decoder.resolver.services";
+
+const WELL_KNOWN_SERVICES: &[(u16, &str)] = &[
+    (20, "ftp-data"),
+    (21, "ftp"),
+    (22, "ssh"),
+    (23, "telnet"),
+    (25, "smtp"),
+    (53, "domain"),
+    (67, "bootps"),
+    (68, "bootpc"),
+    (80, "http"),
+    (110, "pop3"),
+    (123, "ntp"),
+    (143, "imap"),
+    (161, "snmp"),
+    (443, "https"),
+    (502, "modbus"),
+    (993, "imaps"),
+    (995, "pop3s"),
+    (1883, "mqtt"),
+    (4789, "vxlan"),
+    (6081, "geneve"),
+];
+
+#[derive(Default)]
+struct State {
+    enabled: bool,
+    hosts: FnvHashMap<IpAddr, String>,
+    observed: FnvHashMap<IpAddr, String>,
+    vendors: FnvHashMap<[u8; 3], String>,
+    services: FnvHashMap<(Token, u16), String>,
+}
+
+/// Shared table of the names of addresses and ports.
+///
+/// IP addresses are resolved with the hosts file and the DNS answers observed
+/// in the capture, MAC addresses with the OUI list, and ports with the services file.
+/// When enabled, the layers get the `name` pseudo-attributes for IP addresses,
+/// `vendor` for MAC addresses and `service` for ports.
+#[derive(Clone, Default)]
+pub struct Resolver {
+    state: Arc<RwLock<State>>,
+}
+
+impl Resolver {
+    pub fn new() -> Resolver {
+        Resolver::default()
+    }
+
+    /// Loads the hosts file.
+    pub fn load_hosts(&self, text: &str) {
+        let mut state = self.state.write();
+        for line in lines(text) {
+            let mut fields = line.split_whitespace();
+            if let (Some(addr), Some(name)) = (fields.next(), fields.next()) {
+                if let Ok(addr) = addr.parse::<IpAddr>() {
+                    state.hosts.entry(addr).or_insert_with(|| name.to_string());
+                }
+            }
+        }
+    }
+
+    /// Loads the OUI list.
+    ///
+    /// Each line has a 24-bit prefix such as `00:00:0C` or `00-00-0C`
+    /// followed by the vendor name.
+    pub fn load_oui(&self, text: &str) {
+        let mut state = self.state.write();
+        for line in lines(text) {
+            let line = line.trim();
+            let split = line.find(char::is_whitespace).unwrap_or_else(|| line.len());
+            let (prefix, name) = line.split_at(split);
+            let name = name.trim().trim_start_matches("(hex)").trim();
+            let name = name.split('\t').next().unwrap_or("").trim();
+            if name.is_empty() {
+                continue;
+            }
+            let bytes = prefix
+                .split(|c| c == ':' || c == '-')
+                .map(|b| u8::from_str_radix(b, 16))
+                .collect::<Result<Vec<_>, _>>();
+            if let Ok(bytes) = bytes {
+                if bytes.len() == 3 {
+                    state
+                        .vendors
+                        .entry([bytes[0], bytes[1], bytes[2]])
+                        .or_insert_with(|| name.to_string());
+                }
+            }
+        }
+    }
+
+    /// Loads the services file.
+    pub fn load_services(&self, text: &str) {
+        let mut state = self.state.write();
+        for line in lines(text) {
+            let mut fields = line.split_whitespace();
+            if let (Some(name), Some(port)) = (fields.next(), fields.next()) {
+                let mut port = port.splitn(2, '/');
+                if let (Some(Ok(port)), Some(protocol)) =
+                    (port.next().map(str::parse::<u16>), port.next())
+                {
+                    state
+                        .services
+                        .entry((Token::from(protocol), port))
+                        .or_insert_with(|| name.to_string());
+                }
+            }
+        }
+    }
+
+    /// Returns the host name of the address.
+    ///
+    /// The hosts file takes precedence over the observed DNS answers.
+    pub fn host(&self, addr: &IpAddr) -> Option<String> {
+        let state = self.state.read();
+        state
+            .hosts
+            .get(addr)
+            .or_else(|| state.observed.get(addr))
+            .cloned()
+    }
+
+    /// Returns the vendor name of the MAC address.
+    pub fn vendor(&self, addr: &[u8]) -> Option<String> {
+        if addr.len() < 3 {
+            return None;
+        }
+        self.state
+            .read()
+            .vendors
+            .get(&[addr[0], addr[1], addr[2]])
+            .cloned()
+    }
+
+    /// Returns the service name of the port.
+    pub fn service(&self, protocol: Token, port: u16) -> Option<String> {
+        self.state
+            .read()
+            .services
+            .get(&(protocol, port))
+            .cloned()
+            .or_else(|| {
+                WELL_KNOWN_SERVICES
+                    .iter()
+                    .find(|(p, _)| *p == port)
+                    .map(|(_, name)| name.to_string())
+            })
+    }
+
+    fn resolve(&self, layer: &Layer, value: &Variant) -> Option<(&'static str, String)> {
+        match value {
+            Variant::UInt64(port) if *port <= u64::from(u16::max_value()) => self
+                .service(layer.id(), *port as u16)
+                .map(|name| ("service", name)),
+            _ => {
+                if let Some(addr) = ip_addr(value) {
+                    self.host(&addr).map(|name| ("name", name))
+                } else {
+                    mac_addr(value)
+                        .and_then(|addr| self.vendor(&addr))
+                        .map(|name| ("vendor", name))
+                }
+            }
+        }
+    }
+}
+
+impl Enricher for Resolver {
+    /// Loads the name tables specified in the profile.
+    ///
+    /// The names observed in the capture are kept.
+    fn configure(&self, profile: &Profile) {
+        let file = |key| {
+            config_path(profile, key)
+                .and_then(|path| fs::read_to_string(path).ok())
+                .unwrap_or_default()
+        };
+        let enabled = config(profile, ENABLED_KEY)
+            .and_then(|value| value.as_bool())
+            .unwrap_or(false);
+
+        {
+            let mut state = self.state.write();
+            state.enabled = enabled;
+            state.hosts.clear();
+            state.vendors.clear();
+            state.services.clear();
+        }
+        if enabled {
+            self.load_hosts(&file(HOSTS_KEY));
+            self.load_oui(&file(OUI_KEY));
+            self.load_services(&file(SERVICES_KEY));
+        }
+    }
+
+    fn enrich(&self, layer: &Layer, value: &Variant) -> Vec<(&'static str, Variant)> {
+        if !self.state.read().enabled {
+            return Vec::new();
+        }
+        self.resolve(layer, value)
+            .map(|(name, value)| (name, Variant::String(value.into_boxed_str())))
+            .into_iter()
+            .collect()
+    }
+
+    /// Learns the host names from the DNS answers in the frame.
+    fn observe(&self, frame: &Frame) {
+        if !self.state.read().enabled {
+            return;
+        }
+        let dns = Token::from("dns");
+        let name_id = Token::from("dns.answer.name");
+        let a_id = Token::from("dns.answer.a");
+        let aaaa_id = Token::from("dns.answer.aaaa");
+        for layer in frame.layers().iter().filter(|layer| layer.id() == dns) {
+            let mut name = None;
+            let mut answers = Vec::new();
+            for attr in layer.attrs() {
+                let value = match attr.try_get(layer) {
+                    Ok(value) => value,
+                    Err(_) => continue,
+                };
+                if attr.id() == name_id {
+                    if let Variant::String(value) = value {
+                        name = Some(value.to_string());
+                    }
+                } else if attr.id() == a_id || attr.id() == aaaa_id {
+                    if let (Some(addr), Some(name)) = (ip_addr(&value), &name) {
+                        answers.push((addr, name.trim_end_matches('.').to_string()));
+                    }
+                }
+            }
+            if !answers.is_empty() {
+                self.state.write().observed.extend(answers);
+            }
+        }
+    }
+
+    /// Forgets the names observed in the capture.
+    fn clear(&self) {
+        self.state.write().observed.clear();
+    }
+}
+
+fn lines(text: &str) -> impl Iterator<Item = &str> {
+    text.lines()
+        .map(|line| line.split('#').next().unwrap_or(""))
+        .filter(|line| !line.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use enrich::{Enricher, Resolver};
+    use frame::Frame;
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        fixed::{Fixed, MutFixed},
+        layer::{Layer, LayerClass},
+        slice::ByteSlice,
+        token::Token,
+        variant::Variant,
+    };
+    use std::net::IpAddr;
+
+    fn enabled() -> Resolver {
+        let resolver = Resolver::new();
+        resolver.state.write().enabled = true;
+        resolver
+    }
+
+    #[test]
+    fn tables() {
+        let resolver = enabled();
+        resolver.load_hosts("# comment\n127.0.0.1 localhost\n::1\tip6-localhost # v6\n");
+        resolver
+            .load_oui("00:00:0C\tCisco\tCisco Systems, Inc\n00-50-56   (hex)\t\tVMware, Inc.\n");
+        resolver.load_services("ssh\t22/tcp\nmy-dns\t53/udp\tdomain\n");
+
+        let localhost = "127.0.0.1".parse::<IpAddr>().unwrap();
+        assert_eq!(resolver.host(&localhost), Some("localhost".to_string()));
+        let localhost = "::1".parse::<IpAddr>().unwrap();
+        assert_eq!(resolver.host(&localhost), Some("ip6-localhost".to_string()));
+        assert_eq!(
+            resolver.vendor(&[0x00, 0x00, 0x0c, 0x01, 0x02, 0x03]),
+            Some("Cisco".to_string())
+        );
+        assert_eq!(
+            resolver.vendor(&[0x00, 0x50, 0x56, 0x01, 0x02, 0x03]),
+            Some("VMware, Inc.".to_string())
+        );
+        assert_eq!(
+            resolver.service(Token::from("udp"), 53),
+            Some("my-dns".to_string())
+        );
+        assert_eq!(
+            resolver.service(Token::from("tcp"), 53),
+            Some("domain".to_string())
+        );
+        assert_eq!(resolver.service(Token::from("tcp"), 1), None);
+    }
+
+    #[test]
+    fn observe_and_enrich() {
+        let dns = Fixed::new(LayerClass::builder("dns").build());
+        let name = Fixed::new(AttrClass::builder("dns.answer.name").build());
+        let a = Fixed::new(AttrClass::builder("dns.answer.a").build());
+        let mut layer = Layer::new(dns, ByteSlice::new());
+        layer.add_attr(
+            Attr::builder(name)
+                .value("example.com.".to_string().into_boxed_str())
+                .build(),
+        );
+        layer.add_attr(
+            Attr::builder(a)
+                .value(vec![93, 184, 216, 34].into_boxed_slice())
+                .build(),
+        );
+
+        let frame = Frame::new(0, MutFixed::new(layer));
+        let resolver = Resolver::new();
+        resolver.observe(&frame);
+        assert!(resolver.host(&"93.184.216.34".parse().unwrap()).is_none());
+
+        let resolver = enabled();
+        resolver.observe(&frame);
+        assert_eq!(
+            resolver.host(&"93.184.216.34".parse().unwrap()),
+            Some("example.com".to_string())
+        );
+
+        let layer = Layer::new(
+            Fixed::new(LayerClass::builder("r1ip").build()),
+            ByteSlice::new(),
+        );
+        let addr = Variant::Buffer(vec![10, 0, 0, 1].into_boxed_slice());
+        assert!(resolver.enrich(&layer, &addr).is_empty());
+        let addr = Variant::Buffer(vec![93, 184, 216, 34].into_boxed_slice());
+        assert_eq!(
+            resolver.enrich(&layer, &addr),
+            vec![("name", Variant::String("example.com".into()))]
+        );
+
+        resolver.clear();
+        assert!(resolver.host(&"93.184.216.34".parse().unwrap()).is_none());
+    }
+}
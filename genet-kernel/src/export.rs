@@ -0,0 +1,111 @@
+use base64;
+
+const HEX_LINE_LEN: usize = 16;
+const C_LINE_LEN: usize = 12;
+
+/// Format of the exported bytes.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// The canonical hex dump, as `hexdump -C`.
+    Hex,
+
+    /// The bytes as they are.
+    Raw,
+    Base64,
+
+    /// A C array literal of `unsigned char`.
+    C,
+}
+
+pub fn export(data: &[u8], format: Format) -> Vec<u8> {
+    match format {
+        Format::Hex => hex(data).into_bytes(),
+        Format::Raw => data.to_vec(),
+        Format::Base64 => base64::encode(data).into_bytes(),
+        Format::C => c_array(data).into_bytes(),
+    }
+}
+
+fn hex(data: &[u8]) -> String {
+    let mut dump = String::new();
+    for (i, line) in data.chunks(HEX_LINE_LEN).enumerate() {
+        let hex = line
+            .chunks(HEX_LINE_LEN / 2)
+            .map(|half| {
+                half.iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect::<Vec<_>>()
+            .join("  ");
+        let text = line
+            .iter()
+            .map(|b| match *b {
+                0x20..=0x7e => *b as char,
+                _ => '.',
+            })
+            .collect::<String>();
+        dump += &format!(
+            "{:08x}  {:<width$}  |{}|\n",
+            i * HEX_LINE_LEN,
+            hex,
+            text,
+            width = HEX_LINE_LEN * 3
+        );
+    }
+    dump + &format!("{:08x}\n", data.len())
+}
+
+fn c_array(data: &[u8]) -> String {
+    let lines = data
+        .chunks(C_LINE_LEN)
+        .map(|line| {
+            let bytes = line
+                .iter()
+                .map(|b| format!("0x{:02x}", b))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("    {},\n", bytes)
+        })
+        .collect::<String>();
+    format!(
+        "const unsigned char data[{}] = {{\n{}}};\n",
+        data.len(),
+        lines
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use export::{export, Format};
+
+    #[test]
+    fn formats() {
+        let data = b"GET / HTTP/1.1\r\nHost";
+        assert_eq!(
+            String::from_utf8(export(data, Format::Hex)).unwrap(),
+            concat!(
+                "00000000  47 45 54 20 2f 20 48 54  54 50 2f 31 2e 31 0d 0a  |GET / HTTP/1.1..|\n",
+                "00000010  48 6f 73 74                                       |Host|\n",
+                "00000014\n"
+            )
+        );
+        assert_eq!(export(data, Format::Raw), data.to_vec());
+        assert_eq!(export(b"genet", Format::Base64), b"Z2VuZXQ=".to_vec());
+        assert_eq!(
+            String::from_utf8(export(&data[..14], Format::C)).unwrap(),
+            concat!(
+                "const unsigned char data[14] = {\n",
+                "    0x47, 0x45, 0x54, 0x20, 0x2f, 0x20, 0x48, 0x54, 0x54, 0x50, 0x2f, 0x31,\n",
+                "    0x2e, 0x31,\n",
+                "};\n"
+            )
+        );
+        assert_eq!(
+            String::from_utf8(export(&[], Format::Hex)).unwrap(),
+            "00000000\n"
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use array_vec::ArrayVec;
+use cancel::CancelToken;
+use crossbeam_channel;
+use frame::Frame;
+use genet_filter::{context::Context, Filter};
+use link::LinkTable;
+use parking_lot::RwLock;
+use std::{
+    ops::Range,
+    sync::Arc,
+    thread::{self, JoinHandle},
+};
+
+/// A range of the frame store to be tested against the filter.
+#[derive(Debug)]
+pub struct Task {
+    pub id: u32,
+    pub generation: u64,
+    pub filter: Filter,
+    pub range: Range<usize>,
+
+    /// The frames in the range to be tested, or None to test all of them.
+    pub candidates: Option<Vec<u32>>,
+
+    /// Skips the task if the filter has been replaced or removed before it starts.
+    pub cancel: CancelToken,
+}
+
+/// The indices of the matched frames in the range of a task.
+#[derive(Debug)]
+pub struct Chunk {
+    pub id: u32,
+    pub generation: u64,
+    pub range: Range<usize>,
+    pub indices: Vec<u32>,
+}
+
+pub trait Callback: Sync + Send + Clone {
+    fn done(&self, chunk: Chunk);
+}
+
+/// Worker threads evaluating the filters.
+///
+/// The chunks may complete in any order, so the caller merges them by the range.
+pub struct Pool {
+    sender: crossbeam_channel::Sender<Option<Task>>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl Pool {
+    pub fn new<C: 'static + Callback>(
+        concurrency: u32,
+        frames: &Arc<RwLock<ArrayVec<Frame>>>,
+        links: &LinkTable,
+        callback: &C,
+    ) -> Pool {
+        let (send, recv) = crossbeam_channel::unbounded::<Option<Task>>();
+        let mut handles = Vec::new();
+        for _ in 0..concurrency {
+            handles.push(Self::spawn(
+                frames.clone(),
+                links.clone(),
+                callback.clone(),
+                recv.clone(),
+            ));
+        }
+        Pool {
+            sender: send,
+            handles,
+        }
+    }
+
+    fn spawn<C: 'static + Callback>(
+        frames: Arc<RwLock<ArrayVec<Frame>>>,
+        links: LinkTable,
+        callback: C,
+        recv: crossbeam_channel::Receiver<Option<Task>>,
+    ) -> JoinHandle<()> {
+        thread::spawn(move || {
+            while let Some(Some(task)) = recv.recv() {
+                if task.cancel.is_cancelled() {
+                    continue;
+                }
+                let indices = {
+                    let frames = frames.read();
+                    let test = |frame: &&Frame| {
+                        let links = links.get(frame.index());
+                        let note = frame.note();
+                        let ctx = Context::new(frame.layers())
+                            .with_links(&links)
+                            .with_note(note.marked, &note.comment);
+                        task.filter.test(&ctx)
+                    };
+                    if let Some(candidates) = &task.candidates {
+                        candidates
+                            .iter()
+                            .filter_map(|index| frames.get(*index as usize))
+                            .filter(test)
+                            .map(|frame| frame.index())
+                            .collect()
+                    } else {
+                        frames
+                            .iter()
+                            .skip(task.range.start)
+                            .take(task.range.end - task.range.start)
+                            .filter(test)
+                            .map(|frame| frame.index())
+                            .collect()
+                    }
+                };
+                callback.done(Chunk {
+                    id: task.id,
+                    generation: task.generation,
+                    range: task.range,
+                    indices,
+                });
+            }
+        })
+    }
+
+    pub fn process(&mut self, task: Task) {
+        self.sender.send(Some(task));
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        for _ in 0..self.handles.len() {
+            self.sender.send(None);
+        }
+        while let Some(h) = self.handles.pop() {
+            h.join().unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use array_vec::ArrayVec;
+    use cancel::CancelToken;
+    use crossbeam_channel;
+    use filter::{Callback, Chunk, Pool, Task};
+    use frame::{Frame, Note};
+    use genet_abi::{
+        fixed::{Fixed, MutFixed},
+        layer::{Layer, LayerClass},
+        slice::ByteSlice,
+        token::Token,
+    };
+    use genet_filter::Filter;
+    use link::LinkTable;
+    use parking_lot::RwLock;
+    use std::sync::Arc;
+
+    #[derive(Clone)]
+    struct TestCallback {
+        sender: crossbeam_channel::Sender<Chunk>,
+    }
+
+    impl Callback for TestCallback {
+        fn done(&self, chunk: Chunk) {
+            self.sender.send(chunk);
+        }
+    }
+
+    #[test]
+    fn process() {
+        let even = Fixed::new(LayerClass::builder(Token::from("c0ffee")).build());
+        let odd = Fixed::new(LayerClass::builder(Token::null()).build());
+        let mut vec = ArrayVec::new();
+        for index in 0..100 {
+            let class = if index % 2 == 0 { &even } else { &odd };
+            let layer = Layer::new(class.clone(), ByteSlice::new());
+            vec.push(Frame::new(index, MutFixed::new(layer)));
+        }
+        let frames = Arc::new(RwLock::new(vec));
+
+        let (send, recv) = crossbeam_channel::unbounded();
+        let mut pool = Pool::new(
+            4,
+            &frames,
+            &LinkTable::new(),
+            &TestCallback { sender: send },
+        );
+        let filter = Filter::compile("c0ffee").unwrap();
+        for start in (0..100).step_by(10) {
+            pool.process(Task {
+                id: 1,
+                generation: 0,
+                filter: filter.clone(),
+                range: start..start + 10,
+                candidates: None,
+                cancel: CancelToken::new(),
+            });
+        }
+        drop(pool);
+
+        let mut chunks = Vec::new();
+        while let Some(chunk) = recv.try_recv() {
+            chunks.push(chunk);
+        }
+        assert_eq!(chunks.len(), 10);
+        chunks.sort_by_key(|chunk| chunk.range.start);
+        let indices = chunks
+            .into_iter()
+            .flat_map(|chunk| chunk.indices)
+            .collect::<Vec<_>>();
+        assert_eq!(indices, (0..100).step_by(2).collect::<Vec<_>>());
+
+        let (send, recv) = crossbeam_channel::unbounded();
+        let mut pool = Pool::new(
+            1,
+            &frames,
+            &LinkTable::new(),
+            &TestCallback { sender: send },
+        );
+        pool.process(Task {
+            id: 1,
+            generation: 0,
+            filter,
+            range: 0..100,
+            candidates: Some(vec![1, 2, 3, 4]),
+            cancel: CancelToken::new(),
+        });
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        pool.process(Task {
+            id: 2,
+            generation: 0,
+            filter: Filter::compile("c0ffee").unwrap(),
+            range: 0..100,
+            candidates: None,
+            cancel,
+        });
+        drop(pool);
+        assert_eq!(recv.try_recv().unwrap().indices, vec![2, 4]);
+        assert!(recv.try_recv().is_none());
+    }
+
+    #[test]
+    fn note() {
+        let class = Fixed::new(LayerClass::builder(Token::from("n0te")).build());
+        let mut vec = ArrayVec::new();
+        for index in 0..10 {
+            let frame = Frame::new(
+                index,
+                MutFixed::new(Layer::new(class.clone(), ByteSlice::new())),
+            );
+            if index % 3 == 0 {
+                frame.set_note(Note {
+                    marked: index > 0,
+                    comment: format!("frame {}", index),
+                });
+            }
+            vec.push(frame);
+        }
+        let frames = Arc::new(RwLock::new(vec));
+
+        let (send, recv) = crossbeam_channel::unbounded();
+        let mut pool = Pool::new(
+            1,
+            &frames,
+            &LinkTable::new(),
+            &TestCallback { sender: send },
+        );
+        for (id, filter) in [(1, "frame.marked"), (2, "frame.comment == \"frame 0\"")].iter() {
+            pool.process(Task {
+                id: *id,
+                generation: 0,
+                filter: Filter::compile(filter).unwrap(),
+                range: 0..10,
+                candidates: None,
+                cancel: CancelToken::new(),
+            });
+        }
+        drop(pool);
+        assert_eq!(recv.try_recv().unwrap().indices, vec![3, 6, 9]);
+        assert_eq!(recv.try_recv().unwrap().indices, vec![0]);
+    }
+}
@@ -0,0 +1,239 @@
+use frame::Frame;
+use genet_abi::{token::Token, variant::Variant};
+use genet_filter::{
+    ast::{value_eq, Expr},
+    parser,
+};
+use memchr::{memchr, memchr2, memchr3};
+
+/// What to find in the frames.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Query {
+    /// An attribute having the value, compared as in the filters.
+    Attr(Token, Variant),
+
+    /// Any of the byte sequences in the frame data or the payloads.
+    Bytes(Vec<Vec<u8>>),
+}
+
+impl Query {
+    /// Creates a query for the attribute value written as a filter literal,
+    /// e.g. `64`, `192.168.0.1` or `"GET"`.
+    ///
+    /// A value which is not a literal is compared as a string, which may match the labels.
+    pub fn attr(id: &str, value: &str) -> Query {
+        let value = match parser::parse(value) {
+            Ok(Expr::Literal(value)) => value,
+            _ => Variant::String(value.into()),
+        };
+        Query::Attr(Token::from(id), value)
+    }
+}
+
+/// A location matching the query.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Match {
+    pub frame: u32,
+
+    /// The ID of the layer containing the match.
+    pub layer: String,
+
+    /// The ID of the matched attribute or payload, or an empty string for the frame data.
+    pub id: String,
+
+    /// The offset of the byte sequence in the frame data or the payload.
+    pub offset: Option<usize>,
+}
+
+/// Returns the first location in the frame matching the query.
+pub fn find(frame: &Frame, query: &Query) -> Option<Match> {
+    let found = |layer: Token, id: Token, offset| Match {
+        frame: frame.index(),
+        layer: layer.to_string(),
+        id: id.to_string(),
+        offset,
+    };
+    match query {
+        Query::Attr(id, value) => {
+            for layer in frame.layers() {
+                for attr in layer.attrs_by_id(*id) {
+                    let attr_value = attr.try_get(layer).unwrap_or(Variant::Nil);
+                    let label = || attr.label(layer).map(|label| label.to_string());
+                    if value_eq(&attr_value, value, label, || None) {
+                        return Some(found(layer.id(), *id, None));
+                    }
+                }
+            }
+            None
+        }
+        Query::Bytes(patterns) => find_bytes(frame, &Finder::new(patterns)),
+    }
+}
+
+/// Returns the first location in the frame data or the payloads matching the finder.
+pub fn find_bytes(frame: &Frame, finder: &Finder) -> Option<Match> {
+    let found = |layer: Token, id: Token, offset| Match {
+        frame: frame.index(),
+        layer: layer.to_string(),
+        id: id.to_string(),
+        offset: Some(offset),
+    };
+    let root = frame.layers().first()?;
+    if let Some(offset) = finder.find(&root.data()) {
+        return Some(found(root.id(), Token::null(), offset));
+    }
+    for layer in frame.layers() {
+        for payload in layer.payloads() {
+            if let Some(offset) = finder.find(&payload.data()) {
+                return Some(found(layer.id(), payload.id(), offset));
+            }
+        }
+    }
+    None
+}
+
+/// A matcher for several byte sequences at once.
+///
+/// The candidate offsets are located by the first bytes of the patterns,
+/// with the vectorized `memchr` for up to three distinct first bytes.
+#[derive(Clone, Debug)]
+pub struct Finder {
+    patterns: Vec<Vec<u8>>,
+    first: Vec<u8>,
+    table: [bool; 256],
+}
+
+impl Finder {
+    /// Creates a finder for the patterns. Empty patterns never match.
+    pub fn new(patterns: &[Vec<u8>]) -> Finder {
+        let patterns = patterns
+            .iter()
+            .filter(|pattern| !pattern.is_empty())
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut first = patterns
+            .iter()
+            .map(|pattern| pattern[0])
+            .collect::<Vec<_>>();
+        first.sort();
+        first.dedup();
+        let mut table = [false; 256];
+        for byte in &first {
+            table[*byte as usize] = true;
+        }
+        Finder {
+            patterns,
+            first,
+            table,
+        }
+    }
+
+    /// Returns the offset of the first occurrence of any of the patterns.
+    pub fn find(&self, data: &[u8]) -> Option<usize> {
+        let mut start = 0;
+        while let Some(pos) = self.candidate(&data[start..]) {
+            let offset = start + pos;
+            let rest = &data[offset..];
+            if self
+                .patterns
+                .iter()
+                .any(|pattern| rest.starts_with(pattern))
+            {
+                return Some(offset);
+            }
+            start = offset + 1;
+        }
+        None
+    }
+
+    fn candidate(&self, data: &[u8]) -> Option<usize> {
+        match self.first[..] {
+            [] => None,
+            [a] => memchr(a, data),
+            [a, b] => memchr2(a, b, data),
+            [a, b, c] => memchr3(a, b, c, data),
+            _ => data.iter().position(|byte| self.table[*byte as usize]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use find::{find, Finder, Match, Query};
+    use frame::Frame;
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        fixed::{Fixed, MutFixed},
+        layer::{Layer, LayerClass, Payload},
+        slice::ByteSlice,
+        variant::Variant,
+    };
+
+    fn frame() -> Frame {
+        let class = Fixed::new(LayerClass::builder("f1nd").build());
+        let ttl = Fixed::new(AttrClass::builder("f1nd.ttl").build());
+        let proto = Fixed::new(
+            AttrClass::builder("f1nd.proto")
+                .labels(vec![(6, "TCP")])
+                .build(),
+        );
+        let mut layer = Layer::new(class, ByteSlice::from(&b"\x00\x01GET /"[..]));
+        layer.add_attr(Attr::builder(ttl).value(64u64).build());
+        layer.add_attr(Attr::builder(proto).value(6u64).build());
+        layer.add_payload(Payload::new(ByteSlice::from(&b"hello"[..]), "@data:f1nd"));
+        Frame::new(3, MutFixed::new(layer))
+    }
+
+    #[test]
+    fn attr() {
+        let frame = frame();
+        assert_eq!(
+            find(&frame, &Query::attr("f1nd.ttl", "64")),
+            Some(Match {
+                frame: 3,
+                layer: "f1nd".to_string(),
+                id: "f1nd.ttl".to_string(),
+                offset: None,
+            })
+        );
+        assert!(find(&frame, &Query::attr("f1nd.proto", "TCP")).is_some());
+        assert!(find(&frame, &Query::attr("f1nd.ttl", "63")).is_none());
+        assert_eq!(
+            Query::attr("f1nd.ttl", "hello"),
+            Query::Attr("f1nd.ttl".into(), Variant::String("hello".into()))
+        );
+    }
+
+    #[test]
+    fn bytes() {
+        let frame = frame();
+        let bytes = |patterns: &[&[u8]]| {
+            Query::Bytes(patterns.iter().map(|pattern| pattern.to_vec()).collect())
+        };
+        let found = find(&frame, &bytes(&[b"GET"])).unwrap();
+        assert_eq!((found.id.as_str(), found.offset), ("", Some(2)));
+        let found = find(&frame, &bytes(&[b"POST", b"llo"])).unwrap();
+        assert_eq!((found.id.as_str(), found.offset), ("@data:f1nd", Some(2)));
+        assert!(find(&frame, &bytes(&[b"POST"])).is_none());
+        assert!(find(&frame, &bytes(&[b""])).is_none());
+    }
+
+    #[test]
+    fn finder() {
+        let data = b"aababcabcd";
+        let finder = |patterns: &[&[u8]]| {
+            Finder::new(
+                &patterns
+                    .iter()
+                    .map(|pattern| pattern.to_vec())
+                    .collect::<Vec<_>>(),
+            )
+        };
+        assert_eq!(finder(&[b"abc"]).find(data), Some(3));
+        assert_eq!(finder(&[b"bcd", b"abc"]).find(data), Some(3));
+        assert_eq!(finder(&[b"cd", b"bcd", b"dd"]).find(data), Some(7));
+        assert_eq!(finder(&[b"x", b"y", b"z", b"cd"]).find(data), Some(8));
+        assert_eq!(finder(&[b"abcde"]).find(data), None);
+        assert_eq!(finder(&[]).find(data), None);
+    }
+}
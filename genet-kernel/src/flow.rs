@@ -0,0 +1,377 @@
+use fnv::FnvHashMap;
+use frame::Frame;
+use genet_abi::{
+    attr::{Attr, AttrClass},
+    fixed::{Fixed, MutFixed},
+    layer::Layer,
+    token::Token,
+    variant::Variant,
+};
+use genet_filter::variant::timestamp;
+use parking_lot::RwLock;
+use std::{
+    net::{Ipv4Addr, Ipv6Addr},
+    sync::Arc,
+};
+
+/// An address and an optional port of one side of a flow.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Endpoint {
+    pub addr: String,
+    pub port: Option<u64>,
+}
+
+/// Statistics of the frames exchanged between two endpoints.
+///
+/// `src` is the endpoint which sent the first frame of the flow.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Flow {
+    pub id: u32,
+    pub protocol: String,
+    pub src: Endpoint,
+    pub dst: Endpoint,
+    pub packets: u64,
+    pub bytes: u64,
+
+    /// The timestamps of the first and the last frames in nanoseconds.
+    pub start: Option<u64>,
+    pub end: Option<u64>,
+}
+
+impl Flow {
+    /// Returns the duration between the first and the last frames in nanoseconds.
+    pub fn duration(&self) -> u64 {
+        match (self.start, self.end) {
+            (Some(start), Some(end)) => end.saturating_sub(start),
+            _ => 0,
+        }
+    }
+}
+
+pub type Key = (Token, Endpoint, Endpoint);
+
+#[derive(Default)]
+struct State {
+    ids: FnvHashMap<Key, u32>,
+    flows: Vec<Flow>,
+    classes: FnvHashMap<Token, Fixed<AttrClass>>,
+}
+
+/// Shared table of the flows.
+///
+/// The frames are grouped by the protocol and the endpoints regardless of the direction.
+/// Each frame in a flow gets the `<protocol>.flow` attribute holding the flow ID.
+#[derive(Clone, Default)]
+pub struct FlowTable {
+    state: Arc<RwLock<State>>,
+}
+
+impl FlowTable {
+    pub fn new() -> FlowTable {
+        FlowTable::default()
+    }
+
+    /// Assigns the frame to a flow and returns the flow ID.
+    ///
+    /// The frames must be inserted in order.
+    pub fn insert(&self, frame: &mut Frame) -> Option<u32> {
+        let (index, protocol, id) = self.assign(frame)?;
+        let class = self
+            .state
+            .write()
+            .classes
+            .entry(protocol)
+            .or_insert_with(|| {
+                let id = format!("{}.flow", protocol);
+                Fixed::new(AttrClass::builder(id.as_str()).build())
+            })
+            .clone();
+        frame.layers_mut()[index].add_attr(Attr::builder(class).value(u64::from(id)).build());
+        Some(id)
+    }
+
+    /// Counts the retained frame which already has the flow ID attribute.
+    pub fn replay(&self, frame: &Frame) -> Option<u32> {
+        self.assign(frame).map(|(_, _, id)| id)
+    }
+
+    fn assign(&self, frame: &Frame) -> Option<(usize, Token, u32)> {
+        let (index, protocol, src, dst) = endpoints(frame)?;
+        let bytes = frame.layers().first().map_or(0, |root| root.data().len()) as u64;
+        let time = frame_time(frame);
+
+        let mut state = self.state.write();
+        let key = if src <= dst {
+            (protocol, src.clone(), dst.clone())
+        } else {
+            (protocol, dst.clone(), src.clone())
+        };
+        let next = state.flows.len() as u32;
+        let id = *state.ids.entry(key).or_insert(next);
+        if id == next {
+            state.flows.push(Flow {
+                id,
+                protocol: protocol.to_string(),
+                src,
+                dst,
+                packets: 0,
+                bytes: 0,
+                start: time,
+                end: time,
+            });
+        }
+        {
+            let flow = &mut state.flows[id as usize];
+            flow.packets += 1;
+            flow.bytes += bytes;
+            if time.is_some() {
+                flow.start = flow.start.or(time);
+                flow.end = time;
+            }
+        }
+        Some((index, protocol, id))
+    }
+
+    pub fn get(&self, id: u32) -> Option<Flow> {
+        self.state
+            .read()
+            .flows
+            .get(id as usize)
+            .filter(|flow| flow.packets > 0)
+            .cloned()
+    }
+
+    pub fn flows(&self) -> Vec<Flow> {
+        self.state
+            .read()
+            .flows
+            .iter()
+            .filter(|flow| flow.packets > 0)
+            .cloned()
+            .collect()
+    }
+
+    /// Resets the statistics of the flows.
+    ///
+    /// The flow IDs are kept, so that the retained frames
+    /// still have the valid `<protocol>.flow` attributes.
+    pub fn reset_stats(&self) {
+        let mut state = self.state.write();
+        for flow in &mut state.flows {
+            flow.packets = 0;
+            flow.bytes = 0;
+            flow.start = None;
+            flow.end = None;
+        }
+    }
+
+    pub fn clear(&self) {
+        let mut state = self.state.write();
+        state.ids.clear();
+        state.flows.clear();
+    }
+}
+
+/// Returns the key of the flow to which the frame belongs.
+pub fn key(frame: &Frame) -> Option<Key> {
+    layers_key(frame.layers())
+}
+
+/// Returns the key of the flow to which the layers belong.
+pub fn layers_key(layers: &[MutFixed<Layer>]) -> Option<Key> {
+    let (_, protocol, src, dst) = layers_endpoints(layers)?;
+    Some(if src <= dst {
+        (protocol, src, dst)
+    } else {
+        (protocol, dst, src)
+    })
+}
+
+/// Finds the endpoints of the frame.
+///
+/// The ports come from the topmost layer having the numeric `src` and `dst` attributes,
+/// and the addresses come from the nearest layer below it.
+pub fn endpoints(frame: &Frame) -> Option<(usize, Token, Endpoint, Endpoint)> {
+    layers_endpoints(frame.layers())
+}
+
+fn layers_endpoints(layers: &[MutFixed<Layer>]) -> Option<(usize, Token, Endpoint, Endpoint)> {
+    let mut ports = None;
+    for (index, layer) in layers.iter().enumerate().rev() {
+        let (src, dst) = match src_dst(layer) {
+            Some(pair) => pair,
+            None => continue,
+        };
+        match (src, dst, ports) {
+            (Variant::UInt64(src), Variant::UInt64(dst), None) => {
+                ports = Some((index, layer.id(), src, dst));
+            }
+            (src, dst, _) => {
+                let (src, dst) = (format_addr(&src)?, format_addr(&dst)?);
+                let (index, protocol, src_port, dst_port) = match ports {
+                    Some((index, protocol, src, dst)) => (index, protocol, Some(src), Some(dst)),
+                    None => (index, layer.id(), None, None),
+                };
+                return Some((
+                    index,
+                    protocol,
+                    Endpoint {
+                        addr: src,
+                        port: src_port,
+                    },
+                    Endpoint {
+                        addr: dst,
+                        port: dst_port,
+                    },
+                ));
+            }
+        }
+    }
+    None
+}
+
+/// Returns the `src` and `dst` attribute values of the layer.
+pub fn src_dst(layer: &Layer) -> Option<(Variant, Variant)> {
+    let attr = |name| {
+        let id = Token::lookup(&format!("{}.{}", layer.id(), name))?;
+        layer.attr(id)?.try_get(layer).ok()
+    };
+    Some((attr("src")?, attr("dst")?))
+}
+
+/// Formats the address value, or returns None if the value is not an address.
+pub fn format_addr(value: &Variant) -> Option<String> {
+    let bytes: &[u8] = match value {
+        Variant::Ipv6Addr(addr) => return Some(addr.to_string()),
+        Variant::MacAddr(addr) => addr,
+        Variant::Buffer(data) => data,
+        Variant::Slice(data) => data,
+        _ => return None,
+    };
+    match bytes.len() {
+        4 => Some(Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]).to_string()),
+        16 => {
+            let mut addr = [0u8; 16];
+            addr.copy_from_slice(bytes);
+            Some(Ipv6Addr::from(addr).to_string())
+        }
+        _ => Some(
+            bytes
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<_>>()
+                .join(":"),
+        ),
+    }
+}
+
+/// Returns the timestamp of the frame in nanoseconds.
+pub fn frame_time(frame: &Frame) -> Option<u64> {
+    let typ = Token::lookup("@datetime:unix")?;
+    frame
+        .layers()
+        .iter()
+        .flat_map(|layer| {
+            layer
+                .headers()
+                .iter()
+                .chain(layer.attrs().iter())
+                .filter(move |attr| attr.typ() == typ)
+                .filter_map(move |attr| attr.try_get(layer).ok())
+        })
+        .filter_map(|value| timestamp(&value))
+        .next()
+}
+
+#[cfg(test)]
+mod tests {
+    use flow::FlowTable;
+    use frame::Frame;
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        fixed::{Fixed, MutFixed},
+        layer::{Layer, LayerClass},
+        slice::ByteSlice,
+        token::Token,
+        variant::Variant,
+    };
+
+    fn frame(index: u32, addrs: (&[u8], &[u8]), ports: (u64, u64)) -> Frame {
+        let ip = Fixed::new(LayerClass::builder("f10a").build());
+        let udp = Fixed::new(LayerClass::builder("f10b").build());
+        let ip_src = Fixed::new(AttrClass::builder("f10a.src").build());
+        let ip_dst = Fixed::new(AttrClass::builder("f10a.dst").build());
+        let udp_src = Fixed::new(AttrClass::builder("f10b.src").build());
+        let udp_dst = Fixed::new(AttrClass::builder("f10b.dst").build());
+
+        let mut root = Layer::new(ip, ByteSlice::from(vec![0u8; 20]));
+        root.add_attr(
+            Attr::builder(ip_src)
+                .value(addrs.0.to_vec().into_boxed_slice())
+                .build(),
+        );
+        root.add_attr(
+            Attr::builder(ip_dst)
+                .value(addrs.1.to_vec().into_boxed_slice())
+                .build(),
+        );
+        let mut frame = Frame::new(index, MutFixed::new(root));
+
+        let mut layer = Layer::new(udp, ByteSlice::new());
+        layer.add_attr(Attr::builder(udp_src).value(ports.0).build());
+        layer.add_attr(Attr::builder(udp_dst).value(ports.1).build());
+        let mut layers = frame.fetch_layers();
+        layers.push(MutFixed::new(layer));
+        frame.set_layers(layers);
+        frame
+    }
+
+    #[test]
+    fn insert() {
+        let a = &[192, 168, 0, 1][..];
+        let b = &[192, 168, 0, 2][..];
+        let table = FlowTable::new();
+        let mut frames = vec![
+            frame(0, (a, b), (5353, 53)),
+            frame(1, (b, a), (53, 5353)),
+            frame(2, (a, b), (5354, 53)),
+        ];
+        let ids = frames
+            .iter_mut()
+            .map(|frame| table.insert(frame))
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![Some(0), Some(0), Some(1)]);
+
+        let flow = table.get(0).unwrap();
+        assert_eq!(flow.protocol, "f10b");
+        assert_eq!(flow.src.addr, "192.168.0.1");
+        assert_eq!(flow.src.port, Some(5353));
+        assert_eq!(flow.dst.addr, "192.168.0.2");
+        assert_eq!((flow.packets, flow.bytes), (2, 40));
+
+        let layer = &frames[1].layers()[1];
+        let attr = layer.attr(Token::from("f10b.flow")).unwrap();
+        assert_eq!(attr.try_get(layer).unwrap(), Variant::UInt64(0));
+
+        table.clear();
+        assert!(table.flows().is_empty());
+    }
+
+    #[test]
+    fn replay() {
+        let a = &[192, 168, 0, 1][..];
+        let b = &[192, 168, 0, 2][..];
+        let table = FlowTable::new();
+        let mut frames = vec![frame(0, (a, b), (5353, 53)), frame(1, (a, b), (5354, 53))];
+        for frame in &mut frames {
+            table.insert(frame);
+        }
+
+        table.reset_stats();
+        assert!(table.flows().is_empty());
+        assert_eq!(table.replay(&frames[1]), Some(1));
+        assert!(table.get(0).is_none());
+        assert_eq!(table.get(1).unwrap().packets, 1);
+        assert_eq!(table.flows().len(), 1);
+    }
+}
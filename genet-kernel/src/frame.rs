@@ -1,10 +1,32 @@
+use decoder::trace::TraceEntry;
 use genet_abi::{attr::Attr, fixed::MutFixed, layer::Layer, token::Token};
-use std::{fmt, mem};
+use parking_lot::RwLock;
+use std::{
+    fmt, mem,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// The mark and the comment attached to a frame by the user.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Eq, Debug)]
+pub struct Note {
+    pub marked: bool,
+    pub comment: String,
+}
+
+impl Note {
+    pub fn is_empty(&self) -> bool {
+        !self.marked && self.comment.is_empty()
+    }
+}
 
 pub struct Frame {
     index: u32,
     layers: Vec<MutFixed<Layer>>,
     tree_indices: Vec<u8>,
+    retained: bool,
+    color: AtomicUsize,
+    note: RwLock<Note>,
+    trace: Vec<TraceEntry>,
 }
 
 impl fmt::Debug for Frame {
@@ -21,17 +43,88 @@ impl Frame {
             index,
             layers: vec![root],
             tree_indices: Vec::new(),
+            retained: false,
+            color: AtomicUsize::new(0),
+            note: RwLock::new(Note::default()),
+            trace: Vec::new(),
         }
     }
 
+    /// Creates a frame sharing the decoded layers of the frame.
+    ///
+    /// The decoders skip the retained frame.
+    pub fn retain(frame: &Frame) -> Frame {
+        // The previous frames are never freed, so the layers can be shared.
+        Frame {
+            index: frame.index,
+            layers: frame
+                .layers
+                .iter()
+                .map(|layer| unsafe { MutFixed::from_ptr(layer.as_mut_ptr()) })
+                .collect(),
+            tree_indices: frame.tree_indices.clone(),
+            retained: true,
+            color: AtomicUsize::new(frame.color.load(Ordering::Relaxed)),
+            note: RwLock::new(frame.note()),
+            trace: frame.trace.clone(),
+        }
+    }
+
+    /// Returns true if the frame keeps the layers from the previous decoding.
+    pub fn is_retained(&self) -> bool {
+        self.retained
+    }
+
     pub fn index(&self) -> u32 {
         self.index
     }
 
+    /// Returns the color tag of the first coloring rule matching the frame.
+    pub fn color(&self) -> Option<Token> {
+        match self.color.load(Ordering::Relaxed) {
+            0 => None,
+            id => Some(Token::from(id as u32)),
+        }
+    }
+
+    /// Sets the color tag.
+    ///
+    /// The tag can be updated after the frame is stored.
+    pub fn set_color(&self, color: Option<Token>) {
+        let id: u32 = color.unwrap_or_else(Token::null).into();
+        self.color.store(id as usize, Ordering::Relaxed);
+    }
+
+    pub fn note(&self) -> Note {
+        self.note.read().clone()
+    }
+
+    /// Sets the note.
+    ///
+    /// The note can be updated after the frame is stored.
+    pub fn set_note(&self, note: Note) {
+        *self.note.write() = note;
+    }
+
+    /// Returns the decoders offered the layers of the frame, in the order of invocation.
+    ///
+    /// The trace is recorded only if tracing is enabled in the profile.
+    pub fn trace(&self) -> &[TraceEntry] {
+        &self.trace
+    }
+
+    pub fn extend_trace(&mut self, entries: Vec<TraceEntry>) {
+        self.trace.extend(entries);
+    }
+
     pub fn layers(&self) -> &[MutFixed<Layer>] {
         &self.layers
     }
 
+    pub fn layers_mut(&mut self) -> &mut [MutFixed<Layer>] {
+        &mut self.layers
+    }
+
     pub fn attr(&self, id: Token) -> Option<&Attr> {
         for layer in self.layers().iter().rev() {
             if let Some(attr) = layer.attr(id) {
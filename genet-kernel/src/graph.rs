@@ -0,0 +1,163 @@
+use flow::frame_time;
+use fnv::FnvHashMap;
+use frame::Frame;
+use genet_filter::{context::Context, Filter};
+use link::LinkTable;
+
+/// The frames in a time interval.
+#[derive(Serialize, Clone, Copy, Default, Debug, PartialEq)]
+pub struct Bucket {
+    pub frames: u64,
+    pub bytes: u64,
+}
+
+/// Time series of the frames.
+///
+/// The bucket `i` of each series covers `start + interval * i .. start + interval * (i + 1)`
+/// in nanoseconds.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct IoGraph {
+    pub start: u64,
+    pub interval: u64,
+    pub series: Vec<Vec<Bucket>>,
+}
+
+/// Buckets the frames into the intervals for each filter.
+///
+/// A series without a filter counts all the frames.
+/// The frames without a timestamp are not counted.
+/// The frames are added one by one, so that the caller does not have to
+/// keep all of them borrowed until the graph is built.
+pub struct Builder<'a> {
+    links: &'a LinkTable,
+    interval: u64,
+    filters: &'a [Option<Filter>],
+
+    /// The buckets of the filters, keyed by the time divided by the interval.
+    buckets: FnvHashMap<u64, Vec<Bucket>>,
+}
+
+impl<'a> Builder<'a> {
+    pub fn new(links: &'a LinkTable, interval: u64, filters: &'a [Option<Filter>]) -> Self {
+        Self {
+            links,
+            interval: interval.max(1),
+            filters,
+            buckets: FnvHashMap::default(),
+        }
+    }
+
+    pub fn add(&mut self, frame: &Frame) {
+        let time = match frame_time(frame) {
+            Some(time) => time,
+            None => return,
+        };
+        let bytes = frame.layers().first().map_or(0, |root| root.data().len()) as u64;
+        let links = self.links.get(frame.index());
+        let note = frame.note();
+        let ctx = Context::new(frame.layers())
+            .with_links(&links)
+            .with_note(note.marked, &note.comment);
+        let len = self.filters.len();
+        let buckets = self
+            .buckets
+            .entry(time / self.interval)
+            .or_insert_with(|| vec![Bucket::default(); len]);
+        for (bucket, filter) in buckets.iter_mut().zip(self.filters) {
+            if filter.as_ref().map_or(true, |filter| filter.test(&ctx)) {
+                bucket.frames += 1;
+                bucket.bytes += bytes;
+            }
+        }
+    }
+
+    pub fn build(self) -> IoGraph {
+        let first = self.buckets.keys().min().cloned().unwrap_or(0);
+        let len = self
+            .buckets
+            .keys()
+            .max()
+            .map_or(0, |last| (last - first) as usize + 1);
+        let mut series = vec![vec![Bucket::default(); len]; self.filters.len()];
+        for (key, buckets) in self.buckets {
+            for (series, bucket) in series.iter_mut().zip(buckets) {
+                series[(key - first) as usize] = bucket;
+            }
+        }
+        IoGraph {
+            start: first * self.interval,
+            interval: self.interval,
+            series,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use frame::Frame;
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        fixed::{Fixed, MutFixed},
+        layer::{Layer, LayerClass},
+        slice::ByteSlice,
+        variant::Variant,
+    };
+    use genet_filter::Filter;
+    use graph::{Bucket, Builder};
+    use link::LinkTable;
+
+    fn frame(index: u32, time: u64, len: usize) -> Frame {
+        let class = Fixed::new(LayerClass::builder("a9f0").build());
+        let ts = Fixed::new(AttrClass::builder("a9f0.ts").typ("@datetime:unix").build());
+        let mut layer = Layer::new(class, ByteSlice::from(vec![0u8; len]));
+        layer.add_attr(Attr::builder(ts).value(Variant::Timestamp(time)).build());
+        Frame::new(index, MutFixed::new(layer))
+    }
+
+    #[test]
+    fn series() {
+        let frames = vec![
+            frame(0, 1_500, 10),
+            frame(1, 1_900, 20),
+            frame(2, 4_100, 30),
+        ];
+        let filters = vec![
+            None,
+            Filter::compile("a9f0.ts > @1970-01-01T00:00:00.000002Z").ok(),
+        ];
+        let links = LinkTable::new();
+        let mut builder = Builder::new(&links, 1_000, &filters);
+        for frame in &frames {
+            builder.add(frame);
+        }
+        let graph = builder.build();
+        assert_eq!(graph.start, 1_000);
+        assert_eq!(graph.interval, 1_000);
+        assert_eq!(
+            graph.series[0],
+            vec![
+                Bucket {
+                    frames: 2,
+                    bytes: 30
+                },
+                Bucket::default(),
+                Bucket::default(),
+                Bucket {
+                    frames: 1,
+                    bytes: 30
+                },
+            ]
+        );
+        assert_eq!(graph.series[1][0], Bucket::default());
+        assert_eq!(
+            graph.series[1][3],
+            Bucket {
+                frames: 1,
+                bytes: 30
+            }
+        );
+
+        let graph = Builder::new(&links, 1_000, &filters).build();
+        assert_eq!(graph.series, vec![Vec::new(), Vec::new()]);
+    }
+}
@@ -0,0 +1,325 @@
+use array_vec::ArrayVec;
+use fnv::FnvHashMap;
+use frame::Frame;
+use genet_abi::{token::Token, variant::Variant};
+use genet_filter::{
+    context::Context,
+    index::{attr_value, IndexKey},
+};
+use std::{collections::BTreeMap, ops::Range};
+
+/// Data structure of an attribute index.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexKind {
+    /// Supports the equality lookups.
+    Hash,
+
+    /// Supports the equality and the range lookups.
+    BTree,
+}
+
+enum Map {
+    Hash(FnvHashMap<IndexKey, Vec<u32>>),
+    BTree(BTreeMap<IndexKey, Vec<u32>>),
+}
+
+impl Map {
+    fn new(kind: IndexKind) -> Map {
+        match kind {
+            IndexKind::Hash => Map::Hash(FnvHashMap::default()),
+            IndexKind::BTree => Map::BTree(BTreeMap::new()),
+        }
+    }
+
+    fn insert(&mut self, key: IndexKey, index: u32) {
+        match self {
+            Map::Hash(map) => map.entry(key).or_insert_with(Vec::new).push(index),
+            Map::BTree(map) => map.entry(key).or_insert_with(Vec::new).push(index),
+        }
+    }
+
+    fn get(&self, key: &IndexKey) -> &[u32] {
+        let indices = match self {
+            Map::Hash(map) => map.get(key),
+            Map::BTree(map) => map.get(key),
+        };
+        indices.map_or(&[][..], |v| v.as_slice())
+    }
+}
+
+struct AttrIndex {
+    kind: IndexKind,
+
+    /// None until the first lookup.
+    map: Option<Map>,
+
+    /// The number of the frames scanned.
+    scanned: usize,
+
+    /// The number of the frame indices in the index.
+    entries: usize,
+
+    /// The frames with the values which cannot be indexed.
+    unindexed: Vec<u32>,
+
+    /// True if the index exceeded the capacity and is no longer maintained.
+    overflow: bool,
+    last_used: u64,
+}
+
+impl AttrIndex {
+    fn new(kind: IndexKind) -> AttrIndex {
+        AttrIndex {
+            kind,
+            map: None,
+            scanned: 0,
+            entries: 0,
+            unindexed: Vec::new(),
+            overflow: false,
+            last_used: 0,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.map = None;
+        self.scanned = 0;
+        self.entries = 0;
+        self.unindexed = Vec::new();
+    }
+
+    fn update(&mut self, id: Token, frames: &ArrayVec<Frame>) {
+        let kind = self.kind;
+        let map = self.map.get_or_insert_with(|| Map::new(kind));
+        for frame in frames.iter().skip(self.scanned) {
+            let ctx = Context::new(frame.layers());
+            match attr_value(id, &ctx) {
+                Variant::Nil => continue,
+                value => match IndexKey::new(&value) {
+                    Some(key) => map.insert(key, frame.index()),
+                    None => self.unindexed.push(frame.index()),
+                },
+            }
+            self.entries += 1;
+        }
+        self.scanned = frames.len();
+    }
+
+    /// Merges the sorted frame indices with the unindexed ones in the range.
+    fn collect(&self, indices: &[u32], range: &Range<usize>) -> Vec<u32> {
+        let in_range =
+            |index: &&u32| range.start <= **index as usize && (**index as usize) < range.end;
+        let mut result = indices
+            .iter()
+            .chain(self.unindexed.iter())
+            .filter(in_range)
+            .cloned()
+            .collect::<Vec<_>>();
+        result.sort();
+        result.dedup();
+        result
+    }
+}
+
+/// Lazily built indexes on the attribute values.
+///
+/// An index is built on the first lookup and extended to the new frames
+/// on the later lookups. The frame indices are used as the positions in the store.
+///
+/// The total number of the entries is bounded by the capacity.
+/// The least recently used indexes are dropped to make room,
+/// and an index larger than the capacity is given up.
+pub struct IndexTable {
+    indexes: FnvHashMap<Token, AttrIndex>,
+    capacity: usize,
+    clock: u64,
+}
+
+impl IndexTable {
+    pub fn new(capacity: usize) -> IndexTable {
+        IndexTable {
+            indexes: FnvHashMap::default(),
+            capacity,
+            clock: 0,
+        }
+    }
+
+    /// Enables the index on the attribute, or disables it if `kind` is None.
+    pub fn set(&mut self, id: Token, kind: Option<IndexKind>) {
+        match kind {
+            Some(kind) => {
+                if self.indexes.get(&id).map(|index| index.kind) != Some(kind) {
+                    self.indexes.insert(id, AttrIndex::new(kind));
+                }
+            }
+            None => {
+                self.indexes.remove(&id);
+            }
+        }
+    }
+
+    /// Drops the built indexes, e.g. when the frames are decoded again.
+    pub fn reset(&mut self) {
+        for index in self.indexes.values_mut() {
+            index.clear();
+            index.overflow = false;
+        }
+    }
+
+    /// Returns the frames in the range which may have the value,
+    /// or None if the attribute has no index.
+    pub fn lookup(
+        &mut self,
+        id: Token,
+        key: &IndexKey,
+        frames: &ArrayVec<Frame>,
+        range: Range<usize>,
+    ) -> Option<Vec<u32>> {
+        let index = self.prepare(id, frames)?;
+        let map = index.map.as_ref()?;
+        Some(index.collect(map.get(key), &range))
+    }
+
+    /// Returns the frames in the range which may have a value in `keys`,
+    /// or None if the attribute has no B-tree index.
+    pub fn lookup_range(
+        &mut self,
+        id: Token,
+        keys: Range<IndexKey>,
+        frames: &ArrayVec<Frame>,
+        range: Range<usize>,
+    ) -> Option<Vec<u32>> {
+        let index = self.prepare(id, frames)?;
+        if let Some(Map::BTree(map)) = &index.map {
+            let indices = map
+                .range(keys)
+                .flat_map(|(_, v)| v.iter().cloned())
+                .collect::<Vec<_>>();
+            Some(index.collect(&indices, &range))
+        } else {
+            None
+        }
+    }
+
+    fn prepare(&mut self, id: Token, frames: &ArrayVec<Frame>) -> Option<&AttrIndex> {
+        self.clock += 1;
+        let clock = self.clock;
+        {
+            let index = self.indexes.get_mut(&id)?;
+            if index.overflow {
+                return None;
+            }
+            index.last_used = clock;
+            index.update(id, frames);
+        }
+        self.evict(id);
+        self.indexes.get(&id).filter(|index| !index.overflow)
+    }
+
+    /// Drops the least recently used indexes until the entries fit in the capacity.
+    fn evict(&mut self, current: Token) {
+        let mut total = self
+            .indexes
+            .values()
+            .map(|index| index.entries)
+            .sum::<usize>();
+        while total > self.capacity {
+            let victim = self
+                .indexes
+                .iter()
+                .filter(|(id, index)| **id != current && index.map.is_some())
+                .min_by_key(|(_, index)| index.last_used)
+                .map(|(id, _)| *id);
+            let index = match victim {
+                Some(id) => self.indexes.get_mut(&id).unwrap(),
+                None => {
+                    let index = self.indexes.get_mut(&current).unwrap();
+                    index.overflow = true;
+                    index
+                }
+            };
+            total -= index.entries;
+            index.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use array_vec::ArrayVec;
+    use frame::Frame;
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        fixed::{Fixed, MutFixed},
+        layer::{Layer, LayerClass},
+        slice::ByteSlice,
+        token::Token,
+        variant::Variant,
+    };
+    use genet_filter::index::IndexKey;
+    use index::{IndexKind, IndexTable};
+
+    fn frames(values: &[Variant]) -> ArrayVec<Frame> {
+        let class = Fixed::new(LayerClass::builder("d3a0").build());
+        let attr = Fixed::new(AttrClass::builder("d3a0.value").build());
+        let mut frames = ArrayVec::new();
+        for (index, value) in values.iter().enumerate() {
+            let mut layer = Layer::new(class, ByteSlice::new());
+            layer.add_attr(Attr::builder(attr).value(value.clone()).build());
+            frames.push(Frame::new(index as u32, MutFixed::new(layer)));
+        }
+        frames
+    }
+
+    #[test]
+    fn lookup() {
+        let id = Token::from("d3a0.value");
+        let frames = frames(&[
+            Variant::UInt64(1),
+            Variant::UInt64(2),
+            Variant::Float64(1.0),
+            Variant::UInt64(1),
+            Variant::UInt64(3),
+        ]);
+        let one = IndexKey::Int(1);
+
+        let mut table = IndexTable::new(100);
+        assert_eq!(table.lookup(id, &one, &frames, 0..5), None);
+
+        table.set(id, Some(IndexKind::Hash));
+        assert_eq!(table.lookup(id, &one, &frames, 0..5), Some(vec![0, 2, 3]));
+        assert_eq!(table.lookup(id, &one, &frames, 1..3), Some(vec![2]));
+        assert_eq!(
+            table.lookup_range(id, IndexKey::Int(2)..IndexKey::Int(4), &frames, 0..5),
+            None
+        );
+
+        table.set(id, Some(IndexKind::BTree));
+        assert_eq!(
+            table.lookup_range(id, IndexKey::Int(2)..IndexKey::Int(4), &frames, 0..5),
+            Some(vec![1, 2, 4])
+        );
+    }
+
+    #[test]
+    fn capacity() {
+        let a = Token::from("d3a0.value");
+        let b = Token::from("d3a0");
+        let frames = frames(&[Variant::UInt64(1), Variant::UInt64(2)]);
+        let one = IndexKey::Int(1);
+
+        let mut table = IndexTable::new(3);
+        table.set(a, Some(IndexKind::Hash));
+        table.set(b, Some(IndexKind::Hash));
+        assert_eq!(table.lookup(a, &one, &frames, 0..2), Some(vec![0]));
+
+        // The layer itself evaluates to true, and the index on `a` is dropped.
+        let key = IndexKey::Bool(true);
+        assert_eq!(table.lookup(b, &key, &frames, 0..2), Some(vec![0, 1]));
+        assert_eq!(table.lookup(a, &one, &frames, 0..2), Some(vec![0]));
+
+        let mut table = IndexTable::new(1);
+        table.set(a, Some(IndexKind::Hash));
+        assert_eq!(table.lookup(a, &one, &frames, 0..2), None);
+    }
+}
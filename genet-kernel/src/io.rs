@@ -8,5 +8,6 @@ pub trait Output: Send + Debug {
 }
 
 pub trait Input: Send + Debug {
-    fn read(&mut self) -> Result<Vec<MutFixed<Layer>>>;
+    /// Reads at most `max` layers.
+    fn read(&mut self, max: usize) -> Result<Vec<MutFixed<Layer>>>;
 }
@@ -1,25 +1,54 @@
+extern crate base64;
+extern crate bincode;
 extern crate crossbeam_channel;
 extern crate fnv;
 extern crate genet_abi;
 extern crate genet_filter;
+#[cfg(feature = "napi")]
 extern crate genet_napi;
 extern crate libc;
 extern crate libloading;
+extern crate maxminddb;
+extern crate memchr;
 extern crate num_cpus;
+extern crate parity_wasm;
 extern crate parking_lot;
+extern crate pwasm_utils;
 extern crate serde;
 extern crate serde_json;
+extern crate wasmi;
 
 #[macro_use]
 extern crate serde_derive;
 
+#[cfg(feature = "napi")]
 pub mod binding;
 pub mod profile;
 pub mod session;
 
 mod array_vec;
+mod backpressure;
+mod cancel;
+mod coloring;
+mod column;
+mod config;
 mod decoder;
+mod diff;
+mod endpoint;
+mod enrich;
+mod export;
+mod filter;
+mod find;
+mod flow;
 mod frame;
+mod graph;
+mod index;
 mod io;
+mod link;
+mod package;
 mod result;
+mod snapshot;
 mod store;
+mod stream;
+mod summary;
+mod wasm;
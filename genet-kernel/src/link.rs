@@ -0,0 +1,87 @@
+use fnv::{FnvHashMap, FnvHashSet};
+use frame::Frame;
+use genet_abi::{layer::Link, token::Token};
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+/// Shared table of the reverse links.
+///
+/// A layer can only link frames which are already decoded,
+/// so the links pointing back from the earlier frames are kept here.
+#[derive(Clone, Default, Debug)]
+pub struct LinkTable {
+    links: Arc<RwLock<FnvHashMap<u32, Vec<Link>>>>,
+    ids: Arc<RwLock<FnvHashSet<Token>>>,
+}
+
+impl LinkTable {
+    pub fn new() -> LinkTable {
+        LinkTable::default()
+    }
+
+    /// Registers the reverse links of the frame.
+    pub fn insert(&self, frame: &Frame) {
+        let reverse = frame
+            .layers()
+            .iter()
+            .flat_map(|layer| layer.links().iter())
+            .filter(|link| link.reverse() != Token::null())
+            .collect::<Vec<_>>();
+        if reverse.is_empty() {
+            return;
+        }
+        let mut links = self.links.write();
+        let mut ids = self.ids.write();
+        for link in reverse {
+            ids.insert(link.reverse());
+            links
+                .entry(link.frame())
+                .or_insert_with(Vec::new)
+                .push(Link::new(link.reverse(), frame.index()));
+        }
+    }
+
+    /// Returns the reverse links to the frame.
+    pub fn get(&self, index: u32) -> Vec<Link> {
+        self.links.read().get(&index).cloned().unwrap_or_default()
+    }
+
+    /// Returns true if any frame has a reverse link with the ID.
+    pub fn contains_id(&self, id: Token) -> bool {
+        self.ids.read().contains(&id)
+    }
+
+    pub fn clear(&self) {
+        self.links.write().clear();
+        self.ids.write().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use frame::Frame;
+    use genet_abi::{
+        fixed::{Fixed, MutFixed},
+        layer::{Layer, LayerClass, Link},
+        slice::ByteSlice,
+        token::Token,
+    };
+    use link::LinkTable;
+
+    #[test]
+    fn reverse() {
+        let class = Fixed::new(LayerClass::builder(Token::null()).build());
+        let mut layer = Layer::new(class, ByteSlice::new());
+        layer.add_link(Link::new("dns.response_to", 3).with_reverse("dns.response_in"));
+        layer.add_link(Link::new("dns.query", 2));
+
+        let table = LinkTable::new();
+        table.insert(&Frame::new(5, MutFixed::new(layer)));
+        assert_eq!(table.get(3), vec![Link::new("dns.response_in", 5)]);
+        assert!(table.get(2).is_empty());
+        assert!(table.contains_id(Token::from("dns.response_in")));
+
+        table.clear();
+        assert!(table.get(3).is_empty());
+    }
+}
@@ -0,0 +1,243 @@
+use column::Column;
+use serde_json::{self, Value};
+use std::{
+    collections::BTreeMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// A package manifest (`package.json`).
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Manifest {
+    pub name: String,
+
+    #[serde(default)]
+    pub version: String,
+
+    #[serde(default)]
+    pub description: String,
+
+    #[serde(default)]
+    pub genet: Option<PackageSpec>,
+}
+
+/// The `genet` section of the package manifest.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageSpec {
+    /// The ABI version of the libraries in the form of `<major>.<minor>`.
+    #[serde(default)]
+    pub abi_version: Option<String>,
+
+    #[serde(default)]
+    pub components: Vec<Component>,
+
+    /// The IDs of the decoders provided by the libraries.
+    #[serde(default)]
+    pub decoders: Vec<String>,
+
+    /// The IDs of the readers provided by the libraries.
+    #[serde(default)]
+    pub readers: Vec<String>,
+
+    /// The IDs of the writers provided by the libraries.
+    #[serde(default)]
+    pub writers: Vec<String>,
+
+    #[serde(default)]
+    pub config_schema: BTreeMap<String, Value>,
+
+    /// The columns of the frame table provided by the package.
+    #[serde(default)]
+    pub columns: Vec<Column>,
+}
+
+/// A component declared in the package manifest.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Component {
+    #[serde(rename = "type")]
+    pub typ: String,
+
+    #[serde(default)]
+    pub main: String,
+}
+
+/// A package found in the plugin directory.
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub path: PathBuf,
+    pub manifest: Manifest,
+}
+
+impl Package {
+    /// Reads the manifest in the package directory.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Package> {
+        let path = path.as_ref();
+        let json = fs::read_to_string(path.join("package.json"))?;
+        let manifest = serde_json::from_str(&json)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(Package {
+            path: path.to_path_buf(),
+            manifest,
+        })
+    }
+
+    /// Returns the package ID.
+    pub fn id(&self) -> &str {
+        &self.manifest.name
+    }
+
+    /// Returns the `genet` section of the manifest.
+    pub fn spec(&self) -> Option<&PackageSpec> {
+        self.manifest.genet.as_ref()
+    }
+
+    /// Returns the ABI version declared in the manifest.
+    pub fn abi_version(&self) -> io::Result<Option<u64>> {
+        match self.spec().and_then(|spec| spec.abi_version.as_ref()) {
+            Some(ver) => parse_version(ver).map(Some).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid abi version: {}", ver),
+                )
+            }),
+            None => Ok(None),
+        }
+    }
+
+    /// Resolves the paths of the `core:library` components.
+    ///
+    /// Native libraries are looked up in `target/$GENET_TARGET` (`release` by default).
+    pub fn libraries(&self) -> io::Result<Vec<PathBuf>> {
+        let target = env::var("GENET_TARGET").unwrap_or_else(|_| "release".to_string());
+        let dir = self.path.join("target").join(target);
+        self.spec()
+            .map_or(&[][..], |spec| &spec.components[..])
+            .iter()
+            .filter(|comp| comp.typ == "core:library")
+            .map(|comp| {
+                if comp.main.is_empty() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "main field required",
+                    ));
+                }
+                if comp.main.ends_with(".wasm") {
+                    return Ok(self.path.join(&comp.main));
+                }
+                ["lib", ""]
+                    .iter()
+                    .flat_map(|prefix| {
+                        ["so", "dylib", "dll"]
+                            .iter()
+                            .map(move |ext| format!("{}{}.{}", prefix, comp.main, ext))
+                    })
+                    .map(|name| dir.join(name))
+                    .find(|path| path.is_file())
+                    .ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::NotFound,
+                            format!("could not resolve {} in {}", comp.main, self.path.display()),
+                        )
+                    })
+            })
+            .collect()
+    }
+}
+
+/// Scans the directory for the packages having the `genet` section.
+///
+/// Scoped packages (`@scope/name`) are found in the subdirectories.
+/// The packages whose manifests cannot be read are returned as errors.
+pub fn discover<P: AsRef<Path>>(dir: P) -> io::Result<Vec<io::Result<Package>>> {
+    let mut packages = Vec::new();
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let scope = path
+            .file_name()
+            .map_or(false, |name| name.to_string_lossy().starts_with('@'));
+        if scope {
+            for entry in fs::read_dir(&path)? {
+                entries.push(entry?.path());
+            }
+        } else {
+            entries.push(path);
+        }
+    }
+    entries.sort();
+    for path in entries {
+        if !path.join("package.json").is_file() {
+            continue;
+        }
+        let package = Package::open(&path)
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)));
+        match package {
+            Ok(ref pkg) if pkg.spec().is_none() => {}
+            result => packages.push(result),
+        }
+    }
+    Ok(packages)
+}
+
+fn parse_version(ver: &str) -> Option<u64> {
+    let mut parts = ver.split('.');
+    let major: u64 = parts.next()?.parse().ok()?;
+    let minor: u64 = parts.next().map_or(Some(0), |minor| minor.parse().ok())?;
+    Some(major << 32 | minor)
+}
+
+#[cfg(test)]
+mod tests {
+    use package::{discover, parse_version, Package};
+    use std::{env, fs, process};
+
+    #[test]
+    fn version() {
+        assert_eq!(parse_version("0.3"), Some(3));
+        assert_eq!(parse_version("1.2"), Some(1 << 32 | 2));
+        assert_eq!(parse_version("1"), Some(1 << 32));
+        assert_eq!(parse_version("a.b"), None);
+    }
+
+    #[test]
+    fn discover_packages() {
+        let dir = env::temp_dir().join(format!("genet-package-test-{}", process::id()));
+        let write = |path: &str, json: &str| {
+            let path = dir.join(path);
+            fs::create_dir_all(&path).unwrap();
+            fs::write(path.join("package.json"), json).unwrap();
+        };
+        write(
+            "@genet/udp",
+            r#"{
+                "name": "@genet/udp",
+                "version": "0.1.0",
+                "genet": {
+                    "abiVersion": "0.3",
+                    "components": [{"type": "core:library", "main": "udp.wasm"}],
+                    "decoders": ["app.genet.decoder.udp"],
+                    "configSchema": {"@genet/udp.port": {"type": "integer"}}
+                }
+            }"#,
+        );
+        write("left-pad", r#"{"name": "left-pad", "version": "1.0.0"}"#);
+        write("broken", "{");
+
+        let packages = discover(&dir).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(packages.len(), 2);
+        assert!(packages[1].is_err());
+
+        let pkg: &Package = packages[0].as_ref().unwrap();
+        let spec = pkg.spec().unwrap();
+        assert_eq!(pkg.id(), "@genet/udp");
+        assert_eq!(pkg.abi_version().unwrap(), Some(3));
+        assert_eq!(spec.decoders, vec!["app.genet.decoder.udp"]);
+        assert!(spec.config_schema.contains_key("@genet/udp.port"));
+        assert_eq!(pkg.libraries().unwrap(), vec![pkg.path.join("udp.wasm")]);
+    }
+}
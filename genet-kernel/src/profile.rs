@@ -1,3 +1,7 @@
+use coloring::{self, ColoringRule};
+use column::Column;
+use config::{ConfigError, ConfigSchema};
+use decoder::budget::Budget;
 use fnv::FnvHashMap;
 use genet_abi::{
     context::Context,
@@ -5,20 +9,54 @@ use genet_abi::{
     env::{self, Allocator},
     fixed::Fixed,
     reader::ReaderBox,
+    renderer::RendererBox,
+    spill,
     token::Token,
+    version::{self, AbiInfo},
     writer::WriterBox,
 };
+use genet_filter::{named::NamedFilters, result::Result as FilterResult, Filter};
 use libloading::Library;
 use num_cpus;
-use std::{fmt, io, mem};
+use package::{self, Manifest, Package};
+use serde::Serialize;
+use serde_json;
+use std::{
+    env as stdenv, fmt, fs, io, mem,
+    path::Path,
+    process,
+    sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT},
+};
+use wasm::WasmDecoder;
 
 #[derive(Serialize, Clone, Default)]
 pub struct Profile {
     concurrency: u32,
+    decode_budget: Budget,
+    memory_limit: Option<u64>,
+    trace: bool,
     decoders: Vec<DecoderBox>,
     readers: Vec<ReaderBox>,
     writers: Vec<WriterBox>,
+    renderers: Vec<RendererBox>,
     config: FnvHashMap<String, String>,
+    #[serde(skip)]
+    schemas: FnvHashMap<String, ConfigSchema>,
+    named_filters: NamedFilters,
+    coloring_rules: Vec<ColoringRule>,
+    columns: Vec<Column>,
+    packages: Vec<Manifest>,
+    #[serde(skip)]
+    libraries: Vec<LibraryEntry>,
+}
+
+#[derive(Clone, Default)]
+struct LibraryEntry {
+    path: String,
+    decoders: usize,
+    readers: usize,
+    writers: usize,
+    renderers: usize,
 }
 
 impl fmt::Debug for Profile {
@@ -31,10 +69,20 @@ impl Profile {
     pub fn new() -> Profile {
         Profile {
             concurrency: 4,
+            decode_budget: Budget::default(),
+            memory_limit: None,
+            trace: false,
             decoders: Vec::new(),
             readers: Vec::new(),
             writers: Vec::new(),
+            renderers: Vec::new(),
             config: FnvHashMap::default(),
+            schemas: FnvHashMap::default(),
+            named_filters: NamedFilters::new(),
+            coloring_rules: Vec::new(),
+            columns: Vec::new(),
+            packages: Vec::new(),
+            libraries: Vec::new(),
         }
     }
 
@@ -50,14 +98,146 @@ impl Profile {
         self.concurrency
     }
 
+    /// Returns the limits on the work a decoder may do on a single layer.
+    pub fn decode_budget(&self) -> &Budget {
+        &self.decode_budget
+    }
+
+    /// Sets the limits on the work a decoder may do on a single layer.
+    ///
+    /// A decoder exceeding the budget is aborted and the layer is annotated with a warning.
+    /// Native decoders are only checked after they return; see `Budget`.
+    pub fn set_decode_budget(&mut self, budget: Budget) {
+        self.decode_budget = budget;
+    }
+
+    /// Returns the bytes of the frame data kept in memory before spilling to disk.
+    pub fn memory_limit(&self) -> Option<u64> {
+        self.memory_limit
+    }
+
+    /// Sets the bytes of the frame data kept in memory, or None for no limit.
+    ///
+    /// Beyond the limit, new payloads are copied to temporary files mapped into memory.
+    /// The limit applies to the whole process.
+    pub fn set_memory_limit(&mut self, limit: Option<u64>) {
+        self.memory_limit = limit;
+    }
+
+    /// Returns true if the decoders invoked for each frame are recorded.
+    pub fn trace(&self) -> bool {
+        self.trace
+    }
+
+    /// Enables recording the decoders invoked for each frame, their results and timing.
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    /// Returns the JSON config value, or the default value in the schema.
     pub fn get_config(&self, key: &str) -> Option<String> {
-        self.config.get(key).map(|s| s.to_string())
+        self.config.get(key).map(|s| s.to_string()).or_else(|| {
+            self.schemas
+                .get(key)
+                .and_then(|schema| schema.default.as_ref())
+                .map(|value| value.to_string())
+        })
     }
 
-    pub fn set_config(&mut self, key: &str, value: &str) {
+    /// Sets the JSON config value.
+    ///
+    /// The value is validated if the schema for the key has been registered.
+    pub fn set_config(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        if let Some(schema) = self.schemas.get(key) {
+            let json = serde_json::from_str(value)
+                .map_err(|err| ConfigError::new(key, &err.to_string()))?;
+            schema.validate(key, &json)?;
+        }
         self.config
             .entry(String::from(key))
             .or_insert_with(|| String::from(value));
+        Ok(())
+    }
+
+    /// Sets the config value serialized to JSON.
+    pub fn set_config_value<T: Serialize>(
+        &mut self,
+        key: &str,
+        value: &T,
+    ) -> Result<(), ConfigError> {
+        let value =
+            serde_json::to_string(value).map_err(|err| ConfigError::new(key, &err.to_string()))?;
+        self.set_config(key, &value)
+    }
+
+    /// Registers the config schema declared in the package manifest.
+    ///
+    /// If the current value does not conform to the schema,
+    /// it is replaced with the default value and an error is returned.
+    pub fn set_config_schema(&mut self, key: &str, schema: &str) -> Result<(), ConfigError> {
+        let schema = ConfigSchema::parse(key, schema)?;
+        let result = match self.config.get(key) {
+            Some(value) => serde_json::from_str(value)
+                .map_err(|err| ConfigError::new(key, &err.to_string()))
+                .and_then(|json| schema.validate(key, &json)),
+            None => Ok(()),
+        };
+        if result.is_err() {
+            self.config.remove(key);
+        }
+        self.schemas.insert(key.to_string(), schema);
+        result
+    }
+
+    /// Returns the filters which can be referenced as `$name` in other filters.
+    pub fn named_filters(&self) -> &NamedFilters {
+        &self.named_filters
+    }
+
+    /// Registers the named filter, or removes it if the filter is empty.
+    pub fn set_named_filter(&mut self, name: &str, filter: &str) -> FilterResult<()> {
+        if filter.is_empty() {
+            self.named_filters.remove(name);
+            Ok(())
+        } else {
+            self.named_filters.insert(name, filter)
+        }
+    }
+
+    /// Returns the rules assigning the color tags to the frames.
+    pub fn coloring_rules(&self) -> &[ColoringRule] {
+        &self.coloring_rules
+    }
+
+    /// Replaces the coloring rules after checking that all the filters can be compiled.
+    pub fn set_coloring_rules(&mut self, rules: &[ColoringRule]) -> FilterResult<()> {
+        let prev = mem::replace(&mut self.coloring_rules, rules.to_vec());
+        let result = coloring::compile(self)
+            .into_iter()
+            .map(|(_, filter)| filter.map(|_| ()))
+            .collect::<FilterResult<()>>();
+        if result.is_err() {
+            self.coloring_rules = prev;
+        }
+        result
+    }
+
+    /// Returns the columns of the frame table provided by the packages.
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// Returns the column with the ID.
+    pub fn column(&self, id: &str) -> Option<&Column> {
+        self.columns.iter().find(|column| column.id == id)
+    }
+
+    /// Registers the column after checking that the value expression can be compiled.
+    pub fn add_column(&mut self, column: Column) -> FilterResult<()> {
+        Filter::compile_with(&column.value, &self.named_filters)?;
+        self.columns.retain(|col| col.id != column.id);
+        self.columns.push(column);
+        Ok(())
     }
 
     pub fn decoders(&self) -> impl Iterator<Item = &DecoderBox> {
@@ -72,36 +252,270 @@ impl Profile {
         self.writers.iter()
     }
 
+    pub fn renderers(&self) -> impl Iterator<Item = &RendererBox> {
+        self.renderers.iter()
+    }
+
     pub fn context(&self) -> Context {
-        Context::new(self.config.clone())
+        let mut config = self.config.clone();
+        for (key, schema) in &self.schemas {
+            if let Some(default) = &schema.default {
+                config
+                    .entry(key.clone())
+                    .or_insert_with(|| default.to_string());
+            }
+        }
+        Context::new(config)
     }
 
     pub fn load_library(&mut self, path: &str) -> Result<(), io::Error> {
-        let lib = Library::new(path)?;
+        self.load_library_from(path, path)
+    }
+
+    /// Returns the manifests of the loaded packages.
+    pub fn packages(&self) -> &[Manifest] {
+        &self.packages
+    }
+
+    /// Loads the packages found in the plugin directory.
+    ///
+    /// A package failing to load does not prevent the others from loading;
+    /// the errors are returned instead.
+    pub fn load_packages(&mut self, dir: &str) -> Result<Vec<io::Error>, io::Error> {
+        let mut errors = Vec::new();
+        for package in package::discover(dir)? {
+            let result = package.and_then(|pkg| {
+                self.load_package(&pkg)
+                    .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", pkg.id(), err)))
+            });
+            if let Err(err) = result {
+                errors.push(err);
+            }
+        }
+        Ok(errors)
+    }
+
+    /// Loads the package described by the manifest.
+    ///
+    /// The config schema is registered and the libraries are loaded.
+    /// If the manifest lists the provided decoders, readers or writers,
+    /// the libraries are checked to actually provide them.
+    pub fn load_package(&mut self, package: &Package) -> Result<(), io::Error> {
+        if self.packages.iter().any(|pkg| pkg.name == package.id()) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "package already loaded",
+            ));
+        }
+        if let Some(ver) = package.abi_version()? {
+            if !version::is_compatible(version::current(), ver) {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!(
+                        "abi version mismatch: package {}, expected {}",
+                        version::format(ver),
+                        version::format(version::current())
+                    ),
+                ));
+            }
+        }
+        let libraries = package.libraries()?;
+        let spec = package.spec().cloned().unwrap_or_default();
+        for (key, schema) in &spec.config_schema {
+            if let Err(err) = self.set_config_schema(key, &schema.to_string()) {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, err.to_string()));
+            }
+        }
+        for column in &spec.columns {
+            if let Err(err) = Filter::compile_with(&column.value, &self.named_filters) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("column {}: {}", column.id, err),
+                ));
+            }
+        }
+        for path in &libraries {
+            self.load_library(&path.to_string_lossy())?;
+        }
+
+        let decoders = self
+            .decoders
+            .iter()
+            .map(|d| d.metadata().id)
+            .collect::<Vec<_>>();
+        let readers = self
+            .readers
+            .iter()
+            .map(|r| r.metadata().id)
+            .collect::<Vec<_>>();
+        let writers = self
+            .writers
+            .iter()
+            .map(|w| w.metadata().id)
+            .collect::<Vec<_>>();
+        let missing = spec
+            .decoders
+            .iter()
+            .filter(|id| !decoders.contains(id))
+            .chain(spec.readers.iter().filter(|id| !readers.contains(id)))
+            .chain(spec.writers.iter().filter(|id| !writers.contains(id)))
+            .next();
+        if let Some(id) = missing {
+            for path in &libraries {
+                self.unload_library(&path.to_string_lossy())?;
+            }
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is not provided", id),
+            ));
+        }
+
+        self.columns
+            .retain(|col| !spec.columns.iter().any(|column| column.id == col.id));
+        self.columns.extend(spec.columns.iter().cloned());
+        self.packages.push(package.manifest.clone());
+        Ok(())
+    }
+
+    /// Returns the decoders provided by the library.
+    pub fn library_decoders(&self, path: &str) -> &[DecoderBox] {
+        let mut offset = 0;
+        for lib in &self.libraries {
+            if lib.path == path {
+                return &self.decoders[offset..offset + lib.decoders];
+            }
+            offset += lib.decoders;
+        }
+        &[]
+    }
+
+    /// Unloads the decoders, readers, writers and renderers provided by the library.
+    ///
+    /// The library itself stays mapped because existing frames
+    /// may still refer to its layer classes.
+    pub fn unload_library(&mut self, path: &str) -> Result<(), io::Error> {
+        let index = self
+            .libraries
+            .iter()
+            .position(|lib| lib.path == path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "library not loaded"))?;
+        let (decoders, readers, writers, renderers) =
+            self.libraries
+                .iter()
+                .take(index)
+                .fold((0, 0, 0, 0), |(d, r, w, v), lib| {
+                    (
+                        d + lib.decoders,
+                        r + lib.readers,
+                        w + lib.writers,
+                        v + lib.renderers,
+                    )
+                });
+        let lib = self.libraries.remove(index);
+        self.decoders.drain(decoders..decoders + lib.decoders);
+        self.readers.drain(readers..readers + lib.readers);
+        self.writers.drain(writers..writers + lib.writers);
+        self.renderers.drain(renderers..renderers + lib.renderers);
+        Ok(())
+    }
+
+    /// Reloads the library from the file system.
+    ///
+    /// The file is loaded through a temporary copy, so that the dynamic loader
+    /// does not return the previously mapped image.
+    /// The previous library is replaced only if the new one loads successfully.
+    pub fn reload_library(&mut self, path: &str) -> Result<(), io::Error> {
+        static COUNTER: AtomicUsize = ATOMIC_USIZE_INIT;
+        if !self.libraries.iter().any(|lib| lib.path == path) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "library not loaded",
+            ));
+        }
+        let name = Path::new(path)
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid path"))?;
+        let copy = stdenv::temp_dir().join(format!(
+            "genet-{}-{}-{}",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed),
+            name.to_string_lossy()
+        ));
+        fs::copy(path, &copy)?;
+        let mut loaded = Profile::default();
+        let result = loaded.load_library_from(path, &copy.to_string_lossy());
+        let _ = fs::remove_file(&copy);
+        result?;
+        self.unload_library(path)?;
+        self.decoders.append(&mut loaded.decoders);
+        self.readers.append(&mut loaded.readers);
+        self.writers.append(&mut loaded.writers);
+        self.renderers.append(&mut loaded.renderers);
+        self.libraries.append(&mut loaded.libraries);
+        Ok(())
+    }
+
+    fn load_library_from(&mut self, path: &str, file: &str) -> Result<(), io::Error> {
+        let mut entry = LibraryEntry {
+            path: path.to_string(),
+            ..LibraryEntry::default()
+        };
+
+        if Path::new(path)
+            .extension()
+            .map_or(false, |ext| ext == "wasm")
+        {
+            self.decoders
+                .push(DecoderBox::new(WasmDecoder::load(file)?));
+            entry.decoders = 1;
+            self.libraries.push(entry);
+            return Ok(());
+        }
+
+        let lib = Library::new(file)?;
 
         type FnVersion = extern "C" fn() -> u64;
+        type FnInfo = extern "C" fn() -> AbiInfo;
         type FnRegisterGetToken = extern "C" fn(unsafe extern "C" fn(*const u8, u64) -> Token);
         type FnRegisterGetString =
             extern "C" fn(unsafe extern "C" fn(Token, *mut u64) -> *const u8);
+        type FnRegisterGetTokens =
+            extern "C" fn(unsafe extern "C" fn(*const *const u8, *const u64, u64, *mut Token));
+        type FnRegisterLookupToken = extern "C" fn(unsafe extern "C" fn(*const u8, u64) -> Token);
+        type FnRegisterTokenCount = extern "C" fn(unsafe extern "C" fn() -> u64);
+        type FnRegisterGetDescendants =
+            extern "C" fn(unsafe extern "C" fn(*const u8, u64, *mut Token, u64) -> u64);
+        type FnRegisterSpill = extern "C" fn(extern "C" fn(*const u8, u64) -> *const u8);
         type FnRegisterGetAllocator = extern "C" fn(extern "C" fn() -> Fixed<Allocator>);
         type FnGetDecoders = extern "C" fn(*mut u64) -> *const DecoderBox;
         type FnGetReaders = extern "C" fn(*mut u64) -> *const ReaderBox;
         type FnGetWriters = extern "C" fn(*mut u64) -> *const WriterBox;
+        type FnGetRenderers = extern "C" fn(*mut u64) -> *const RendererBox;
 
         {
             let func = unsafe { lib.get::<FnVersion>(b"genet_abi_version")? };
 
-            // In the initial development, minor version changes may break ABI.
-            fn canonical(ver: u64) -> u64 {
-                if ver >> 32 == 0 {
-                    ver
-                } else {
-                    ver & (0xffff_ffff << 32)
-                }
+            if !version::is_compatible(version::current(), func()) {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!(
+                        "abi version mismatch: library {}, expected {}",
+                        version::format(func()),
+                        version::format(version::current())
+                    ),
+                ));
             }
 
-            if canonical(env::genet_abi_version()) != canonical(func()) {
-                return Err(io::Error::new(io::ErrorKind::Other, "abi version mismatch"));
+            // Libraries built with older SDKs do not describe their layouts,
+            // which may differ from the current ones.
+            let func = unsafe { lib.get::<FnInfo>(b"genet_abi_v1_info") }.map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    "abi layout unknown: the library is built with an older SDK",
+                )
+            })?;
+            if let Err(err) = AbiInfo::current().check(&func()) {
+                return Err(io::Error::new(io::ErrorKind::Other, err.to_string()));
             }
 
             let func =
@@ -112,6 +526,36 @@ impl Profile {
                 unsafe { lib.get::<FnRegisterGetString>(b"genet_abi_v1_register_get_string")? };
             func(env::abi_genet_get_string);
 
+            // Optional in the libraries built with older SDKs.
+            if let Ok(func) =
+                unsafe { lib.get::<FnRegisterGetTokens>(b"genet_abi_v1_register_get_tokens") }
+            {
+                func(env::abi_genet_get_tokens);
+            }
+
+            if let Ok(func) =
+                unsafe { lib.get::<FnRegisterLookupToken>(b"genet_abi_v1_register_lookup_token") }
+            {
+                func(env::abi_genet_lookup_token);
+            }
+
+            if let Ok(func) =
+                unsafe { lib.get::<FnRegisterTokenCount>(b"genet_abi_v1_register_token_count") }
+            {
+                func(env::abi_genet_token_count);
+            }
+
+            if let Ok(func) = unsafe {
+                lib.get::<FnRegisterGetDescendants>(b"genet_abi_v1_register_get_descendants")
+            } {
+                func(env::abi_genet_get_descendants);
+            }
+
+            if let Ok(func) = unsafe { lib.get::<FnRegisterSpill>(b"genet_abi_v1_register_spill") }
+            {
+                func(spill::abi_genet_spill);
+            }
+
             let func = unsafe {
                 lib.get::<FnRegisterGetAllocator>(b"genet_abi_v1_register_get_allocator")?
             };
@@ -124,6 +568,7 @@ impl Profile {
             for i in 0..len {
                 self.decoders.push(unsafe { (*ptr.offset(i as isize)) });
             }
+            entry.decoders = len as usize;
         }
 
         if let Ok(func) = unsafe { lib.get::<FnGetReaders>(b"genet_abi_v1_get_readers") } {
@@ -132,6 +577,7 @@ impl Profile {
             for i in 0..len {
                 self.readers.push(unsafe { (*ptr.offset(i as isize)) });
             }
+            entry.readers = len as usize;
         }
 
         if let Ok(func) = unsafe { lib.get::<FnGetWriters>(b"genet_abi_v1_get_writers") } {
@@ -140,8 +586,19 @@ impl Profile {
             for i in 0..len {
                 self.writers.push(unsafe { (*ptr.offset(i as isize)) });
             }
+            entry.writers = len as usize;
+        }
+
+        if let Ok(func) = unsafe { lib.get::<FnGetRenderers>(b"genet_abi_v1_get_renderers") } {
+            let mut len = 0;
+            let ptr = func(&mut len);
+            for i in 0..len {
+                self.renderers.push(unsafe { (*ptr.offset(i as isize)) });
+            }
+            entry.renderers = len as usize;
         }
 
+        self.libraries.push(entry);
         mem::forget(lib);
         Ok(())
     }
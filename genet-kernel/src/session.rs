@@ -1,31 +1,84 @@
-use frame::Frame;
-use genet_abi::{self, fixed::MutFixed, layer::Layer, reader, writer};
-use genet_filter::Filter;
+pub use cancel::CancelToken;
+pub use coloring::ColoringRule;
+pub use column::Column;
+use decoder::dispatcher;
+pub use decoder::{stats::DecoderStats, trace::TraceEntry};
+pub use diff::{AttrChange, ChangeKind};
+pub use endpoint::{EndpointStats, Order as EndpointOrder};
+use export;
+pub use export::Format as ExportFormat;
+pub use find::{Match, Query};
+pub use flow::{Endpoint, Flow};
+use fnv::FnvHashMap;
+pub use frame::{Frame, Note};
+use genet_abi::{
+    self,
+    attr::Attr,
+    fixed::{Fixed, MutFixed},
+    layer::{Annotation, Layer, LayerClass, Link, Severity},
+    reader,
+    slice::ByteSlice,
+    token::Token,
+    variant::Variant,
+    writer,
+};
+use genet_filter::{
+    completion::{self, Completion, Hint},
+    result::Result as FilterResult,
+    Filter,
+};
+pub use graph::{Bucket, IoGraph};
+pub use index::IndexKind;
 use io::{Input, Output};
+use parking_lot::Mutex;
 use profile::Profile;
+use result::Result;
 use serde::ser::{Serialize, SerializeMap, Serializer};
-use std::{fmt, ops::Range};
-use store::{self, Store};
+use serde_json;
+use snapshot;
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter},
+    ops::Range,
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+    thread,
+};
+use store::{self, Redecode, Store};
+pub use store::{Progress, ProgressKind, Selection, StoreView};
+pub use stream::{Direction, Format as StreamFormat, Segment, Stream};
+
+/// The cancel tokens of the running outputs and tasks.
+type CancelMap = Arc<Mutex<FnvHashMap<(ProgressKind, u32), CancelToken>>>;
 
 pub struct Session {
     store: Store,
     callback: Box<Callback>,
     profile: Profile,
+    filters: FnvHashMap<u32, String>,
+    cancels: CancelMap,
     io_cnt: u32,
+    task_cnt: u32,
 }
 
 impl Session {
     pub fn new<C: 'static + Callback + Clone>(profile: Profile, callback: C) -> Session {
+        let cancels = CancelMap::default();
         Session {
             store: Store::new(
                 profile.clone(),
                 StoreCallback {
                     callback: Box::new(callback.clone()),
+                    cancels: cancels.clone(),
                 },
             ),
             callback: Box::new(callback),
             profile,
+            filters: FnvHashMap::default(),
+            cancels,
             io_cnt: 0,
+            task_cnt: 0,
         }
     }
 
@@ -38,6 +91,10 @@ impl Session {
     }
 
     pub fn set_filter(&mut self, id: u32, filter: Option<Filter>) {
+        match &filter {
+            Some(filter) => self.filters.insert(id, filter.source().to_string()),
+            None => self.filters.remove(&id),
+        };
         self.store.set_filter(id, filter);
     }
 
@@ -63,7 +120,7 @@ impl Session {
         0
     }
 
-    pub fn create_writer(&mut self, id: &str, arg: &str, filter: Option<Filter>) -> u32 {
+    pub fn create_writer<S: Into<Selection>>(&mut self, id: &str, arg: &str, selection: S) -> u32 {
         if let Some(writer) = self
             .profile
             .writers()
@@ -73,8 +130,16 @@ impl Session {
             let ctx = self.profile.context();
             match writer.new_worker(&ctx, arg) {
                 Ok(output) => {
-                    self.store
-                        .push_output(self.io_cnt, WorkerOutput::new(output), filter);
+                    let cancel = CancelToken::new();
+                    self.cancels
+                        .lock()
+                        .insert((ProgressKind::Output, self.io_cnt), cancel.clone());
+                    self.store.push_output(
+                        self.io_cnt,
+                        WorkerOutput::new(output),
+                        selection,
+                        cancel,
+                    );
                     return self.io_cnt;
                 }
                 Err(err) => {
@@ -90,10 +155,439 @@ impl Session {
         self.store.unset_input(handle);
     }
 
+    /// Reloads the decoder library and re-creates the decoder workers.
+    ///
+    /// If `redecode` is true, the stored frames to which the previous or the new
+    /// decoders of the library may apply are decoded again.
+    pub fn reload_library(&mut self, path: &str, redecode: bool) -> io::Result<()> {
+        let mut changed = self
+            .profile
+            .library_decoders(path)
+            .iter()
+            .map(|decoder| decoder.metadata())
+            .collect::<Vec<_>>();
+        self.profile.reload_library(path)?;
+        let redecode = if redecode {
+            changed.extend(
+                self.profile
+                    .library_decoders(path)
+                    .iter()
+                    .map(|decoder| decoder.metadata()),
+            );
+            Redecode::changed(&changed)
+        } else {
+            Redecode::None
+        };
+        self.store.set_profile(self.profile.clone(), redecode);
+        Ok(())
+    }
+
+    /// Saves the frames and the filters to the session file.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let (filters, named_filters) = self.saved_filters();
+        let writer = BufWriter::new(File::create(path)?);
+        self.store.save(writer, &filters, &named_filters)
+    }
+
+    /// Saves the session file in the background.
+    ///
+    /// Returns the task ID.
+    pub fn save_async(&mut self, path: &str) -> u32 {
+        let (filters, named_filters) = self.saved_filters();
+        let path = path.to_string();
+        self.spawn_task(move |view, _, cancel| {
+            let writer = BufWriter::new(File::create(&path).map_err(task_error)?);
+            view.save(writer, &filters, &named_filters)
+                .map_err(task_error)?;
+            if cancel.is_cancelled() {
+                let _ = fs::remove_file(&path);
+                return Err(cancelled());
+            }
+            Ok(serde_json::Value::Null)
+        })
+    }
+
+    fn saved_filters(&self) -> (Vec<(u32, String)>, Vec<(String, String)>) {
+        let mut filters = self
+            .filters
+            .iter()
+            .map(|(id, filter)| (*id, filter.clone()))
+            .collect::<Vec<_>>();
+        filters.sort();
+        let named_filters = self
+            .profile
+            .named_filters()
+            .iter()
+            .map(|(name, filter)| (name.to_string(), filter.to_string()))
+            .collect::<Vec<_>>();
+        (filters, named_filters)
+    }
+
+    /// Restores the frames and the filters from the session file
+    /// without decoding the frames again.
+    ///
+    /// The session must have no frames.
+    pub fn load(&mut self, path: &str) -> io::Result<()> {
+        let snapshot = snapshot::load(BufReader::new(File::open(path)?))?;
+        for (name, filter) in &snapshot.named_filters {
+            self.profile
+                .set_named_filter(name, filter)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+        }
+        self.store.restore(snapshot.frames);
+        for (id, filter) in &snapshot.filters {
+            let filter = Filter::compile_with(filter, self.profile.named_filters())
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+            self.set_filter(*id, Some(filter));
+        }
+        Ok(())
+    }
+
     pub fn len(&self) -> usize {
         self.store.len()
     }
 
+    /// Returns the number of frames which are read but not stored yet.
+    ///
+    /// All the frames of an input are stored when this returns 0
+    /// after the input is done.
+    pub fn in_flight(&self) -> usize {
+        self.store.in_flight()
+    }
+
+    /// Returns the values of the column for the frames in the range.
+    ///
+    /// The values are cached until the frames are decoded again.
+    pub fn column_values(&self, id: &str, range: Range<usize>) -> FilterResult<Vec<Variant>> {
+        let column = self.profile.column(id).ok_or_else(|| {
+            Box::new(Error(format!("unknown column: {}", id))) as Box<std::error::Error + Send>
+        })?;
+        self.store
+            .column_values(column, self.profile.named_filters(), range)
+    }
+
+    /// Returns the one-line summaries of the frames in the range.
+    ///
+    /// The summary of a frame is generated by the innermost layer providing one.
+    pub fn summaries(&self, range: Range<usize>) -> Vec<Option<String>> {
+        self.store.summaries(range)
+    }
+
+    /// Attaches the mark and the comment to the frame.
+    ///
+    /// The note is visible to the filters as `frame.marked` and `frame.comment`.
+    pub fn set_note(&mut self, index: u32, note: Note) {
+        self.store.set_note(index, note);
+    }
+
+    /// Returns the indices of the marked frames.
+    pub fn marked_frames(&self) -> Vec<u32> {
+        self.store.marked_frames()
+    }
+
+    /// Returns the decoders offered the layers of the frame.
+    ///
+    /// The trace is empty unless tracing is enabled in the profile.
+    pub fn trace(&self, index: u32) -> Option<Vec<TraceEntry>> {
+        self.store.trace(index)
+    }
+
+    /// Compares the decoded attributes of the frames.
+    ///
+    /// Returns `None` if either of the frames does not exist.
+    pub fn diff(&self, a: u32, b: u32) -> Option<Vec<AttrChange>> {
+        self.store.diff(a, b)
+    }
+
+    /// Exports the bytes of the frame in the range, which is clamped to the frame length.
+    ///
+    /// Pass the range of an attribute in the frame to export its bytes.
+    pub fn export_bytes(
+        &self,
+        index: u32,
+        range: Range<usize>,
+        format: ExportFormat,
+    ) -> Option<Vec<u8>> {
+        let data = self.store.root(index)?.data();
+        let end = range.end.min(data.len());
+        let start = range.start.min(end);
+        Some(export::export(&data[start..end], format))
+    }
+
+    /// Decodes the frame again with tracing enabled, leaving the stored frame untouched.
+    ///
+    /// The decoders start without the states built from the previous frames.
+    /// Like the stored frames, the layers of the returned frame are never freed.
+    pub fn decode_frame(&self, index: u32) -> Option<Frame> {
+        let root = self.store.root(index)?;
+        let mut profile = self.profile.clone();
+        profile.set_trace(true);
+        let mut frame = Frame::new(index, MutFixed::new(root));
+        dispatcher::decode(&profile, &mut frame);
+        Some(frame)
+    }
+
+    /// Returns the reverse links to the frame.
+    pub fn links(&self, index: u32) -> Vec<Link> {
+        self.store.links(index)
+    }
+
+    pub fn statistics(&self) -> Vec<DecoderStats> {
+        self.store.statistics()
+    }
+
+    /// Returns the flows in the order of the first frames.
+    pub fn flows(&self) -> Vec<Flow> {
+        self.store.flows()
+    }
+
+    pub fn flow(&self, id: u32) -> Option<Flow> {
+        self.store.flow(id)
+    }
+
+    /// Returns the ordered payloads of the stream which the frame belongs to.
+    pub fn stream(&self, index: u32) -> Option<Stream> {
+        self.store.stream(index)
+    }
+
+    /// Writes the stream data in the direction, or in both directions if None.
+    pub fn export_stream(
+        &self,
+        index: u32,
+        path: &str,
+        format: StreamFormat,
+        direction: Option<Direction>,
+    ) -> io::Result<()> {
+        let stream = self
+            .stream(index)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no stream for the frame"))?;
+        stream.write(BufWriter::new(File::create(path)?), format, direction)
+    }
+
+    /// Writes the stream data in the background.
+    ///
+    /// Returns the task ID.
+    pub fn export_stream_async(
+        &mut self,
+        index: u32,
+        path: &str,
+        format: StreamFormat,
+        direction: Option<Direction>,
+    ) -> u32 {
+        let path = path.to_string();
+        self.spawn_task(move |view, _, cancel| {
+            let stream = view
+                .stream(index)
+                .ok_or_else(|| task_error(Error("no stream for the frame".into())))?;
+            let writer = BufWriter::new(File::create(&path).map_err(task_error)?);
+            stream
+                .write(writer, format, direction)
+                .map_err(task_error)?;
+            if cancel.is_cancelled() {
+                let _ = fs::remove_file(&path);
+                return Err(cancelled());
+            }
+            Ok(serde_json::Value::Null)
+        })
+    }
+
+    /// Returns the endpoint statistics sorted in the order, up to `limit` entries.
+    pub fn endpoints(&self, order: EndpointOrder, limit: Option<usize>) -> Vec<EndpointStats> {
+        self.store.endpoints(order, limit)
+    }
+
+    /// Returns the I/O graph with a series for each filter.
+    ///
+    /// An empty filter counts all the frames.
+    pub fn io_graph(&self, interval: u64, filters: &[String]) -> FilterResult<IoGraph> {
+        let filters = self.compile_filters(filters)?;
+        Ok(self.store.io_graph(interval, &filters))
+    }
+
+    /// Builds the I/O graph in the background.
+    ///
+    /// The filters are compiled before returning the task ID.
+    pub fn io_graph_async(&mut self, interval: u64, filters: &[String]) -> FilterResult<u32> {
+        let filters = self.compile_filters(filters)?;
+        Ok(self.spawn_task(move |view, progress, cancel| {
+            let graph = view.io_graph(interval, &filters, progress, cancel);
+            if cancel.is_cancelled() {
+                return Err(cancelled());
+            }
+            serde_json::to_value(&graph).map_err(task_error)
+        }))
+    }
+
+    /// Finds the frames matching the filter in the background.
+    ///
+    /// The task results in the array of the frame indices.
+    pub fn search(&mut self, filter: Filter) -> u32 {
+        self.spawn_task(move |view, progress, cancel| {
+            let indices = view.search(&filter, progress, cancel);
+            if cancel.is_cancelled() {
+                return Err(cancelled());
+            }
+            serde_json::to_value(&indices).map_err(task_error)
+        })
+    }
+
+    /// Finds the first frame matching the query in the background, starting from the frame
+    /// `from` and moving forward, or backward if `backward` is true.
+    ///
+    /// The task results in the match, or null if no frame matches.
+    /// The next or previous match is found by starting from the adjacent frame.
+    pub fn find(&mut self, query: Query, from: u32, backward: bool) -> u32 {
+        self.spawn_task(move |view, progress, cancel| {
+            let found = view.find(&query, from, backward, progress, cancel);
+            if cancel.is_cancelled() {
+                return Err(cancelled());
+            }
+            serde_json::to_value(&found).map_err(task_error)
+        })
+    }
+
+    /// Runs the task on a new thread and reports the result as `Event::Task`.
+    ///
+    /// The task receives a read-only view of the frames,
+    /// a function reporting the number of the processed frames and the total,
+    /// and the token which is cancelled by `cancel`.
+    pub fn spawn_task<F>(&mut self, task: F) -> u32
+    where
+        F: 'static
+            + Send
+            + FnOnce(&StoreView, &Fn(u32, u32), &CancelToken) -> Result<serde_json::Value>,
+    {
+        self.task_cnt += 1;
+        let id = self.task_cnt;
+        let view = self.store.view();
+        let callback = self.callback.clone();
+        let cancel = CancelToken::new();
+        let cancels = self.cancels.clone();
+        cancels
+            .lock()
+            .insert((ProgressKind::Task, id), cancel.clone());
+        thread::spawn(move || {
+            let progress = |done, total| {
+                callback.on_event(Event::Progress(Progress {
+                    kind: ProgressKind::Task,
+                    id,
+                    done,
+                    total,
+                }))
+            };
+            let result = panic::catch_unwind(AssertUnwindSafe(|| task(&view, &progress, &cancel)))
+                .unwrap_or_else(|_| Err(Box::new(Error("task panicked".into()))));
+            cancels.lock().remove(&(ProgressKind::Task, id));
+            callback.on_event(Event::Task(id, result));
+        });
+        id
+    }
+
+    /// Aborts the operation reporting the progress of the kind.
+    ///
+    /// A cancelled filter is removed along with the partial results.
+    /// A cancelled output or task finishes with an error.
+    pub fn cancel(&mut self, kind: ProgressKind, id: u32) {
+        if kind == ProgressKind::Filter {
+            self.set_filter(id, None);
+        } else if let Some(cancel) = self.cancels.lock().get(&(kind, id)) {
+            cancel.cancel();
+        }
+    }
+
+    /// Aborts decoding the stored frames again after the decoders have changed.
+    ///
+    /// The frames which are not decoded yet keep the previous results.
+    pub fn cancel_redecode(&mut self) {
+        self.store.cancel_redecode();
+    }
+
+    fn compile_filters(&self, filters: &[String]) -> FilterResult<Vec<Option<Filter>>> {
+        filters
+            .iter()
+            .map(|filter| {
+                if filter.is_empty() {
+                    Ok(None)
+                } else {
+                    Filter::compile_with(filter, self.profile.named_filters()).map(Some)
+                }
+            })
+            .collect()
+    }
+
+    /// Enables the index on the attribute, or disables it if `kind` is None.
+    ///
+    /// The index is built on demand and speeds up the filters
+    /// comparing the attribute with a literal.
+    pub fn set_index(&self, attr: &str, kind: Option<IndexKind>) {
+        self.store.set_index(Token::from(attr), kind);
+    }
+
+    /// Returns the frames having the attribute value.
+    pub fn lookup(&self, attr: &str, value: &Variant) -> Vec<u32> {
+        self.store.lookup(Token::from(attr), value)
+    }
+
+    /// Renders the attribute value with the registered renderers.
+    ///
+    /// Renderers targeting the attribute ID take precedence over
+    /// those targeting the attribute type.
+    pub fn render_attr(&self, attr: &Attr, layer: &Layer) -> Option<String> {
+        let id = attr.id().to_string();
+        let typ = attr.typ().to_string();
+        let renderers = self
+            .profile
+            .renderers()
+            .map(|r| (r, r.metadata().targets))
+            .collect::<Vec<_>>();
+        [id, typ]
+            .iter()
+            .filter(|target| !target.is_empty())
+            .filter_map(|target| {
+                renderers
+                    .iter()
+                    .filter(|(_, targets)| targets.contains(target))
+                    .filter_map(|(r, _)| r.render(attr, layer))
+                    .next()
+            })
+            .next()
+    }
+
+    /// Returns the completion candidates for the partial filter at the cursor.
+    ///
+    /// The decoder triggers are suggested as the values of the attributes.
+    pub fn complete_filter(&self, filter: &str, cursor: usize) -> Completion {
+        let hints = self
+            .profile
+            .decoders()
+            .map(|decoder| decoder.metadata())
+            .flat_map(|meta| {
+                let name = meta.name;
+                meta.triggers
+                    .into_iter()
+                    .filter(|trigger| trigger.payload.is_none())
+                    .map(move |trigger| Hint {
+                        attr: trigger.attr,
+                        value: format!("0x{:x}", trigger.value),
+                        description: name.clone(),
+                    })
+            })
+            .collect::<Vec<_>>();
+        completion::complete(filter, cursor, &hints)
+    }
+
+    /// Registers the named filter for the filters compiled afterwards.
+    pub fn set_named_filter(&mut self, name: &str, filter: &str) -> FilterResult<()> {
+        self.profile.set_named_filter(name, filter)
+    }
+
+    /// Replaces the coloring rules and updates the color tags of the stored frames.
+    pub fn set_coloring_rules(&mut self, rules: &[ColoringRule]) -> FilterResult<()> {
+        self.profile.set_coloring_rules(rules)?;
+        self.store.set_coloring_rules(self.profile.clone());
+        Ok(())
+    }
+
     pub fn profile(&self) -> &Profile {
         &self.profile
     }
@@ -106,6 +600,14 @@ impl Session {
 #[derive(Debug)]
 struct Error(String);
 
+fn task_error<E: std::error::Error>(err: E) -> Box<std::error::Error + Send> {
+    Box::new(Error(err.to_string()))
+}
+
+fn cancelled() -> Box<std::error::Error + Send> {
+    Box::new(Error("cancelled".into()))
+}
+
 impl std::error::Error for Error {
     fn description(&self) -> &str {
         &self.0
@@ -121,6 +623,7 @@ impl fmt::Display for Error {
 #[derive(Clone)]
 struct StoreCallback {
     callback: Box<Callback>,
+    cancels: CancelMap,
 }
 
 impl store::Callback for StoreCallback {
@@ -137,6 +640,7 @@ impl store::Callback for StoreCallback {
     }
 
     fn on_output_done(&self, id: u32, error: Option<Box<::std::error::Error + Send>>) {
+        self.cancels.lock().remove(&(ProgressKind::Output, id));
         self.callback.on_event(Event::Output(id, error));
     }
 
@@ -147,6 +651,10 @@ impl store::Callback for StoreCallback {
     fn on_error(&self, error: Box<::std::error::Error + Send>) {
         self.callback.on_event(Event::Error(error));
     }
+
+    fn on_progress(&self, progress: Progress) {
+        self.callback.on_event(Event::Progress(progress));
+    }
 }
 
 #[derive(Debug)]
@@ -157,6 +665,8 @@ pub enum Event {
     Input(u32, Option<Box<::std::error::Error + Send>>),
     Output(u32, Option<Box<::std::error::Error + Send>>),
     Error(Box<::std::error::Error + Send>),
+    Progress(Progress),
+    Task(u32, Result<serde_json::Value>),
 }
 
 pub trait Callback: CallbackClone + Send {
@@ -227,6 +737,31 @@ impl Serialize for Event {
                 s.serialize_entry("error", &format!("{}", err))?;
                 s.end()
             }
+            Event::Progress(progress) => {
+                let mut s = serializer.serialize_map(Some(5))?;
+                s.serialize_entry("type", "progress")?;
+                s.serialize_entry("kind", &progress.kind)?;
+                s.serialize_entry("id", &progress.id)?;
+                s.serialize_entry("done", &progress.done)?;
+                s.serialize_entry("total", &progress.total)?;
+                s.end()
+            }
+            Event::Task(id, result) => {
+                let mut s = serializer.serialize_map(Some(4))?;
+                s.serialize_entry("type", "task")?;
+                s.serialize_entry("id", &id)?;
+                match result {
+                    Ok(value) => {
+                        s.serialize_entry("result", value)?;
+                        s.serialize_entry("error", &None::<String>)?;
+                    }
+                    Err(err) => {
+                        s.serialize_entry("result", &serde_json::Value::Null)?;
+                        s.serialize_entry("error", &Some(format!("{}", err)))?;
+                    }
+                }
+                s.end()
+            }
         }
     }
 }
@@ -234,18 +769,38 @@ impl Serialize for Event {
 #[derive(Debug)]
 struct WorkerOutput {
     worker: writer::WorkerBox,
+    note: Fixed<LayerClass>,
 }
 
 impl WorkerOutput {
     fn new(worker: writer::WorkerBox) -> WorkerOutput {
-        Self { worker }
+        Self {
+            worker,
+            note: Fixed::new(LayerClass::builder("frame").build()),
+        }
     }
 }
 
 impl Output for WorkerOutput {
     fn write(&mut self, frames: &[&Frame]) -> genet_abi::result::Result<()> {
         for frame in frames.iter() {
-            self.worker.write(frame.index(), frame.layers())?;
+            let comment = frame.note().comment;
+            if comment.is_empty() {
+                self.worker.write(frame.index(), frame.layers())?;
+                continue;
+            }
+
+            // The comment is passed to the writer as an annotation of an extra layer,
+            // which lives on the stack while writing.
+            let mut note = Layer::new(self.note.clone(), ByteSlice::new());
+            note.annotate(Annotation::new(Severity::Note, &comment).with_attr("frame.comment"));
+            let mut layers = frame
+                .layers()
+                .iter()
+                .map(|layer| unsafe { MutFixed::from_ptr(layer.as_mut_ptr()) })
+                .collect::<Vec<_>>();
+            layers.push(unsafe { MutFixed::from_ptr(&mut note) });
+            self.worker.write(frame.index(), &layers)?;
         }
         Ok(())
     }
@@ -267,7 +822,7 @@ impl WorkerInput {
 }
 
 impl Input for WorkerInput {
-    fn read(&mut self) -> genet_abi::result::Result<Vec<MutFixed<Layer>>> {
-        self.worker.read()
+    fn read(&mut self, max: usize) -> genet_abi::result::Result<Vec<MutFixed<Layer>>> {
+        self.worker.read(max)
     }
 }
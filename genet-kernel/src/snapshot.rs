@@ -0,0 +1,688 @@
+//! Native session files.
+//!
+//! A session file holds the decoded frames with their notes, so a capture can
+//! be reopened without decoding it again. The layer and attribute classes are re-created
+//! from their IDs, and each attribute keeps the value evaluated at saving.
+//! The header attributes of the layers are restored as the regular attributes.
+
+use array_vec::ArrayVec;
+use bincode;
+use fnv::FnvHashMap;
+use frame::{Frame, Note};
+use genet_abi::{
+    attr::{Attr, AttrClass},
+    fixed::{Fixed, MutFixed},
+    layer::{Annotation, Layer, LayerClass, Link, Payload, Severity},
+    slice::ByteSlice,
+    token::Token,
+    variant::{Value as VariantValue, Variant},
+};
+use std::{
+    collections::BTreeMap,
+    io::{self, Read, Write},
+    net::Ipv6Addr,
+};
+
+const MAGIC: [u8; 4] = *b"GNTS";
+const VERSION: u32 = 2;
+
+/// The session state restored from a file.
+pub struct Snapshot {
+    pub frames: Vec<Frame>,
+
+    /// The filters by the IDs.
+    pub filters: Vec<(u32, String)>,
+    pub named_filters: Vec<(String, String)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    magic: [u8; 4],
+    version: u32,
+    tokens: Vec<String>,
+    layers: Vec<LayerClassEntry>,
+    attrs: Vec<AttrClassEntry>,
+    filters: Vec<(u32, String)>,
+    named_filters: Vec<(String, String)>,
+    frames: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LayerClassEntry {
+    id: usize,
+    aliases: Vec<(usize, usize)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AttrClassEntry {
+    id: usize,
+    typ: usize,
+    repeated: bool,
+    labels: BTreeMap<u64, String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FrameEntry {
+    data: Vec<u8>,
+    tree_indices: Vec<u8>,
+    layers: Vec<LayerEntry>,
+    note: Note,
+}
+
+/// A byte sequence, which is stored as a range of the root layer if possible.
+#[derive(Serialize, Deserialize)]
+enum Data {
+    Root {
+        start: usize,
+        len: usize,
+        offset: usize,
+    },
+    Owned {
+        data: Vec<u8>,
+        offset: usize,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct LayerEntry {
+    class: usize,
+    data: Data,
+    attrs: Vec<AttrEntry>,
+    payloads: Vec<PayloadEntry>,
+    annotations: Vec<AnnotationEntry>,
+    links: Vec<LinkEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AttrEntry {
+    class: usize,
+    bit_range: (usize, usize),
+
+    /// None if the value could not be evaluated.
+    value: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Value {
+    Nil,
+    Bool(bool),
+    Int64(i64),
+    UInt64(u64),
+    Float64(f64),
+    String(String),
+    BigInt(Vec<u8>),
+    Buffer(Vec<u8>),
+    Slice(Data),
+    Ipv6Addr([u8; 16]),
+    MacAddr([u8; 6]),
+    Timestamp(u64),
+}
+
+#[derive(Serialize, Deserialize)]
+struct PayloadEntry {
+    id: usize,
+    typ: usize,
+    tag: usize,
+    data: Data,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AnnotationEntry {
+    severity: u8,
+    attr: usize,
+    message: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LinkEntry {
+    id: usize,
+    frame: u32,
+    reverse: usize,
+}
+
+/// The class tables shared by the frames in a file.
+#[derive(Default)]
+struct Tables {
+    tokens: FnvHashMap<Token, usize>,
+    token_list: Vec<String>,
+    layers: FnvHashMap<Token, usize>,
+    layer_list: Vec<LayerClassEntry>,
+    attrs: FnvHashMap<(Token, Token), usize>,
+    attr_list: Vec<AttrClassEntry>,
+    flow_attrs: FnvHashMap<Token, Token>,
+}
+
+impl Tables {
+    fn new() -> Tables {
+        let mut tables = Tables::default();
+        tables.tokens.insert(Token::null(), 0);
+        tables.token_list.push(String::new());
+        tables
+    }
+
+    fn token(&mut self, id: Token) -> usize {
+        let list = &mut self.token_list;
+        *self.tokens.entry(id).or_insert_with(|| {
+            list.push(id.to_string());
+            list.len() - 1
+        })
+    }
+
+    fn layer(&mut self, layer: &Layer) -> usize {
+        if let Some(index) = self.layers.get(&layer.id()) {
+            return *index;
+        }
+        let entry = LayerClassEntry {
+            id: self.token(layer.id()),
+            aliases: layer
+                .aliases()
+                .map(|(id, target)| (self.token(id), self.token(target)))
+                .collect(),
+        };
+        self.layer_list.push(entry);
+        self.layers.insert(layer.id(), self.layer_list.len() - 1);
+        self.layer_list.len() - 1
+    }
+
+    fn attr(&mut self, attr: &Attr) -> usize {
+        let key = (attr.id(), attr.typ());
+        if let Some(index) = self.attrs.get(&key) {
+            return *index;
+        }
+        let entry = AttrClassEntry {
+            id: self.token(attr.id()),
+            typ: self.token(attr.typ()),
+            repeated: attr.is_repeated(),
+            labels: BTreeMap::new(),
+        };
+        self.attr_list.push(entry);
+        self.attrs.insert(key, self.attr_list.len() - 1);
+        self.attr_list.len() - 1
+    }
+
+    /// Returns the attributes of the layer to be saved.
+    ///
+    /// The flow IDs are excluded since the store assigns them again on restoring.
+    fn saved_attrs<'a>(&mut self, layer: &'a Layer) -> Vec<&'a Attr> {
+        let flow = *self
+            .flow_attrs
+            .entry(layer.id())
+            .or_insert_with(|| Token::from(format!("{}.flow", layer.id())));
+        layer
+            .headers()
+            .iter()
+            .chain(layer.attrs().iter())
+            .map(|attr| attr.as_ref())
+            .filter(|attr| attr.id() != flow)
+            .collect()
+    }
+
+    /// Registers the classes and the labels of the frame.
+    fn collect(&mut self, frame: &Frame) {
+        for layer in frame.layers() {
+            self.layer(layer);
+            for attr in self.saved_attrs(layer) {
+                let index = self.attr(attr);
+                let label = attr.label(layer).map(|label| label.to_string());
+                let value = attr
+                    .try_get(layer)
+                    .ok()
+                    .and_then(|value| <Variant as VariantValue<u64>>::try_into(value).ok());
+                if let (Some(label), Some(value)) = (label, value) {
+                    self.attr_list[index].labels.insert(value, label);
+                }
+            }
+            for payload in layer.payloads() {
+                self.token(payload.id());
+                self.token(payload.typ());
+                self.token(payload.tag());
+            }
+            for annotation in layer.annotations() {
+                self.token(annotation.attr());
+            }
+            for link in layer.links() {
+                self.token(link.id());
+                self.token(link.reverse());
+            }
+        }
+    }
+
+    fn encode(&mut self, frame: &Frame) -> FrameEntry {
+        let root = frame
+            .layers()
+            .first()
+            .map_or_else(ByteSlice::new, |root| root.data());
+        let layers = frame
+            .layers()
+            .iter()
+            .map(|layer| LayerEntry {
+                class: self.layers[&layer.id()],
+                data: encode_data(&root, &layer.data()),
+                attrs: self
+                    .saved_attrs(layer)
+                    .into_iter()
+                    .map(|attr| {
+                        let range = attr.bit_range();
+                        AttrEntry {
+                            class: self.attrs[&(attr.id(), attr.typ())],
+                            bit_range: (range.start, range.end),
+                            value: attr
+                                .try_get(layer)
+                                .ok()
+                                .map(|value| encode_value(&root, value)),
+                        }
+                    })
+                    .collect(),
+                payloads: layer
+                    .payloads()
+                    .iter()
+                    .map(|payload| PayloadEntry {
+                        id: self.tokens[&payload.id()],
+                        typ: self.tokens[&payload.typ()],
+                        tag: self.tokens[&payload.tag()],
+                        data: encode_data(&root, &payload.data()),
+                    })
+                    .collect(),
+                annotations: layer
+                    .annotations()
+                    .iter()
+                    .map(|annotation| AnnotationEntry {
+                        severity: annotation.severity() as u8,
+                        attr: self.tokens[&annotation.attr()],
+                        message: annotation.message().to_string(),
+                    })
+                    .collect(),
+                links: layer
+                    .links()
+                    .iter()
+                    .map(|link| LinkEntry {
+                        id: self.tokens[&link.id()],
+                        frame: link.frame(),
+                        reverse: self.tokens[&link.reverse()],
+                    })
+                    .collect(),
+            })
+            .collect();
+        FrameEntry {
+            data: root.to_vec(),
+            tree_indices: frame.tree_indices().to_vec(),
+            layers,
+            note: frame.note(),
+        }
+    }
+}
+
+fn encode_data(root: &ByteSlice, data: &ByteSlice) -> Data {
+    let base = root.as_ptr() as usize;
+    let start = data.as_ptr() as usize;
+    if !root.is_empty() && start >= base && start + data.len() <= base + root.len() {
+        Data::Root {
+            start: start - base,
+            len: data.len(),
+            offset: data.offset(),
+        }
+    } else {
+        Data::Owned {
+            data: data.to_vec(),
+            offset: data.offset(),
+        }
+    }
+}
+
+fn decode_data(root: &ByteSlice, data: Data) -> io::Result<ByteSlice> {
+    match data {
+        Data::Root { start, len, offset } => {
+            if start.checked_add(len).map_or(true, |end| end > root.len()) {
+                return Err(invalid_data("data out of the root layer"));
+            }
+            Ok(unsafe {
+                ByteSlice::from_raw_parts_with_offset(root.as_ptr().add(start), len, offset)
+            })
+        }
+        Data::Owned { data, offset } => {
+            let data = ByteSlice::from(data);
+            Ok(unsafe { ByteSlice::from_raw_parts_with_offset(data.as_ptr(), data.len(), offset) })
+        }
+    }
+}
+
+fn encode_value(root: &ByteSlice, value: Variant) -> Value {
+    match value {
+        Variant::Nil => Value::Nil,
+        Variant::Bool(v) => Value::Bool(v),
+        Variant::Int64(v) => Value::Int64(v),
+        Variant::UInt64(v) => Value::UInt64(v),
+        Variant::Float64(v) => Value::Float64(v),
+        Variant::String(v) => Value::String(v.into()),
+        Variant::BigInt(v) => Value::BigInt(v.into()),
+        Variant::Buffer(v) => Value::Buffer(v.into()),
+        Variant::Slice(v) => Value::Slice(encode_data(root, &v)),
+        Variant::Ipv6Addr(v) => Value::Ipv6Addr(v.octets()),
+        Variant::MacAddr(v) => Value::MacAddr(v),
+        Variant::Timestamp(v) => Value::Timestamp(v),
+    }
+}
+
+fn decode_value(root: &ByteSlice, value: Value) -> io::Result<Variant> {
+    Ok(match value {
+        Value::Nil => Variant::Nil,
+        Value::Bool(v) => Variant::Bool(v),
+        Value::Int64(v) => Variant::Int64(v),
+        Value::UInt64(v) => Variant::UInt64(v),
+        Value::Float64(v) => Variant::Float64(v),
+        Value::String(v) => Variant::String(v.into_boxed_str()),
+        Value::BigInt(v) => Variant::BigInt(v.into_boxed_slice()),
+        Value::Buffer(v) => Variant::Buffer(v.into_boxed_slice()),
+        Value::Slice(v) => Variant::Slice(decode_data(root, v)?),
+        Value::Ipv6Addr(v) => Variant::Ipv6Addr(Ipv6Addr::from(v)),
+        Value::MacAddr(v) => Variant::MacAddr(v),
+        Value::Timestamp(v) => Variant::Timestamp(v),
+    })
+}
+
+fn severity(value: u8) -> Severity {
+    match value {
+        1 => Severity::Note,
+        2 => Severity::Warn,
+        _ => Severity::Error,
+    }
+}
+
+fn invalid_data<E: ToString>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+/// Writes the frames and the filters to the session file.
+///
+/// The frame indices must be the same as the positions.
+pub fn save<W: Write>(
+    writer: W,
+    frames: &ArrayVec<Frame>,
+    filters: &[(u32, String)],
+    named_filters: &[(String, String)],
+) -> io::Result<()> {
+    let mut writer = writer;
+    let mut tables = Tables::new();
+    for frame in frames.iter() {
+        tables.collect(frame);
+    }
+
+    let header = Header {
+        magic: MAGIC,
+        version: VERSION,
+        tokens: tables.token_list.clone(),
+        layers: tables.layer_list.drain(..).collect(),
+        attrs: tables.attr_list.drain(..).collect(),
+        filters: filters.to_vec(),
+        named_filters: named_filters.to_vec(),
+        frames: frames.len(),
+    };
+    bincode::serialize_into(&mut writer, &header).map_err(invalid_data)?;
+    for frame in frames.iter() {
+        bincode::serialize_into(&mut writer, &tables.encode(frame)).map_err(invalid_data)?;
+    }
+    writer.flush()
+}
+
+/// Reads the frames and the filters from the session file.
+pub fn load<R: Read>(reader: R) -> io::Result<Snapshot> {
+    let mut reader = reader;
+    let header: Header = bincode::deserialize_from(&mut reader).map_err(invalid_data)?;
+    if header.magic != MAGIC {
+        return Err(invalid_data("not a session file"));
+    }
+    if header.version != VERSION {
+        return Err(invalid_data("unsupported session file version"));
+    }
+
+    let tokens = header
+        .tokens
+        .iter()
+        .enumerate()
+        .map(|(index, id)| {
+            if index == 0 {
+                Token::null()
+            } else {
+                Token::from(id.as_str())
+            }
+        })
+        .collect::<Vec<_>>();
+    let token = |index: usize| {
+        tokens
+            .get(index)
+            .cloned()
+            .ok_or_else(|| invalid_data("invalid token index"))
+    };
+
+    let mut layers = Vec::new();
+    for entry in &header.layers {
+        let mut builder = LayerClass::builder(token(entry.id)?);
+        for (id, target) in &entry.aliases {
+            builder = builder.alias(token(*id)?, token(*target)?);
+        }
+        layers.push(Fixed::new(builder.build()));
+    }
+
+    let mut attrs = Vec::new();
+    for entry in header.attrs {
+        let mut builder = AttrClass::builder(token(entry.id)?)
+            .typ(token(entry.typ)?)
+            .labels(entry.labels);
+        if entry.repeated {
+            builder = builder.repeated();
+        }
+        attrs.push(Fixed::new(builder.build()));
+    }
+
+    // The count is not trusted for preallocation; a broken file fails at reading the frames.
+    let mut frames = Vec::new();
+    for index in 0..header.frames {
+        let entry: FrameEntry = bincode::deserialize_from(&mut reader).map_err(invalid_data)?;
+        let root = ByteSlice::from(entry.data);
+        let mut stack = Vec::with_capacity(entry.layers.len());
+        for entry in entry.layers {
+            let class = layers
+                .get(entry.class)
+                .cloned()
+                .ok_or_else(|| invalid_data("invalid layer class"))?;
+            let mut layer = Layer::new(class, decode_data(&root, entry.data)?);
+            for attr in entry.attrs {
+                let class = attrs
+                    .get(attr.class)
+                    .cloned()
+                    .ok_or_else(|| invalid_data("invalid attribute class"))?;
+                let mut builder =
+                    Attr::builder(class).bit_range(0, attr.bit_range.0..attr.bit_range.1);
+                if let Some(value) = attr.value {
+                    builder = builder.value(decode_value(&root, value)?);
+                }
+                layer.add_attr(builder.build());
+            }
+            for payload in entry.payloads {
+                layer.add_payload(
+                    Payload::with_typ(
+                        decode_data(&root, payload.data)?,
+                        token(payload.id)?,
+                        token(payload.typ)?,
+                    )
+                    .tagged(token(payload.tag)?),
+                );
+            }
+            for annotation in entry.annotations {
+                layer.annotate(
+                    Annotation::new(severity(annotation.severity), &annotation.message)
+                        .with_attr(token(annotation.attr)?),
+                );
+            }
+            for link in entry.links {
+                layer.add_link(
+                    Link::new(token(link.id)?, link.frame).with_reverse(token(link.reverse)?),
+                );
+            }
+            stack.push(MutFixed::new(layer));
+        }
+
+        let mut stack = stack.into_iter();
+        let root = stack
+            .next()
+            .ok_or_else(|| invalid_data("frame without layers"))?;
+        let mut frame = Frame::new(index as u32, root);
+        let mut layers = frame.fetch_layers();
+        layers.extend(stack);
+        frame.set_layers(layers);
+        frame.set_tree_indices(entry.tree_indices);
+        frame.set_note(entry.note);
+        frames.push(frame);
+    }
+
+    Ok(Snapshot {
+        frames,
+        filters: header.filters,
+        named_filters: header.named_filters,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use array_vec::ArrayVec;
+    use bincode;
+    use frame::{Frame, Note};
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        fixed::{Fixed, MutFixed},
+        layer::{Layer, LayerClass, Link, Payload},
+        slice::{ByteSlice, TryGet},
+        token::Token,
+        variant::Variant,
+    };
+    use snapshot::{
+        load, save, Data, FrameEntry, Header, LayerClassEntry, LayerEntry, MAGIC, VERSION,
+    };
+
+    #[test]
+    fn save_load() {
+        let eth = Fixed::new(
+            LayerClass::builder("5a7e")
+                .alias("_.src", "5a7e.src")
+                .header(
+                    Attr::builder(Fixed::new(AttrClass::builder("5a7e.len").build()))
+                        .value(4u64)
+                        .build(),
+                )
+                .build(),
+        );
+        let ip = Fixed::new(LayerClass::builder("5a7f").build());
+        let src = Fixed::new(AttrClass::builder("5a7e.src").typ("@5a7e:addr").build());
+        let typ = Fixed::new(
+            AttrClass::builder("5a7e.type")
+                .labels(vec![(1, "five")])
+                .build(),
+        );
+        let flow = Fixed::new(AttrClass::builder("5a7f.flow").build());
+
+        let data = ByteSlice::from(vec![1, 2, 3, 4]);
+        let mut root = Layer::new(eth, data);
+        root.add_attr(Attr::builder(src).range(0..2).value(0x0102u64).build());
+        root.add_attr(Attr::builder(typ).value(1u64).build());
+        root.add_payload(Payload::new(data.try_get(2..4).unwrap(), "@data"));
+        let mut frame = Frame::new(0, MutFixed::new(root));
+
+        let mut layer = Layer::new(ip, data.try_get(2..4).unwrap());
+        layer.add_attr(Attr::builder(flow).value(0u64).build());
+        layer.add_link(Link::new("5a7f.prev", 0));
+        let mut layers = frame.fetch_layers();
+        layers.push(MutFixed::new(layer));
+        frame.set_layers(layers);
+        frame.set_tree_indices(vec![1, 0]);
+        frame.set_note(Note {
+            marked: true,
+            comment: "retransmission".to_string(),
+        });
+
+        let mut frames = ArrayVec::new();
+        frames.push(frame);
+        let filters = vec![(1, "5a7e".to_string())];
+        let named = vec![("eth".to_string(), "5a7e".to_string())];
+        let mut file = Vec::new();
+        save(&mut file, &frames, &filters, &named).unwrap();
+
+        let snapshot = load(&file[..]).unwrap();
+        assert_eq!(snapshot.filters, filters);
+        assert_eq!(snapshot.named_filters, named);
+        assert_eq!(snapshot.frames.len(), 1);
+
+        let frame = &snapshot.frames[0];
+        assert_eq!(frame.tree_indices(), &[1, 0]);
+        assert!(frame.note().marked);
+        assert_eq!(frame.note().comment, "retransmission");
+        let layers = frame.layers();
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers[0].id(), Token::from("5a7e"));
+        assert_eq!(&layers[0].data()[..], &[1, 2, 3, 4]);
+
+        let attr = layers[0].attr("_.src").unwrap();
+        assert_eq!(attr.typ(), Token::from("@5a7e:addr"));
+        assert_eq!(attr.range(), 0..2);
+        assert_eq!(attr.try_get(&layers[0]).unwrap(), Variant::UInt64(0x0102));
+        let attr = layers[0].attr("5a7e.len").unwrap();
+        assert_eq!(attr.try_get(&layers[0]).unwrap(), Variant::UInt64(4));
+        let attr = layers[0].attr("5a7e.type").unwrap();
+        assert_eq!(attr.label(&layers[0]), Some("five"));
+
+        let payload = &layers[0].payloads()[0];
+        assert_eq!(payload.id(), Token::from("@data"));
+        assert_eq!(payload.data().as_ptr(), layers[1].data().as_ptr());
+        assert_eq!(layers[1].data().offset(), 2);
+
+        assert!(layers[1].attr("5a7f.flow").is_none());
+        assert_eq!(layers[1].links()[0].frame(), 0);
+    }
+
+    #[test]
+    fn load_broken() {
+        let data = ByteSlice::from(vec![1, 2, 3, 4]);
+        let root = Layer::new(Fixed::new(LayerClass::builder("5a80").build()), data);
+        let mut frames = ArrayVec::new();
+        frames.push(Frame::new(0, MutFixed::new(root)));
+        let mut file = Vec::new();
+        save(&mut file, &frames, &[], &[]).unwrap();
+        assert!(load(&file[..]).is_ok());
+        assert!(load(&file[..file.len() - 1]).is_err());
+
+        let header = Header {
+            magic: MAGIC,
+            version: VERSION,
+            tokens: vec![String::new(), "5a80".to_string()],
+            layers: vec![LayerClassEntry {
+                id: 1,
+                aliases: Vec::new(),
+            }],
+            attrs: Vec::new(),
+            filters: Vec::new(),
+            named_filters: Vec::new(),
+            frames: usize::max_value(),
+        };
+        let frame = |start, len| FrameEntry {
+            data: vec![1, 2, 3, 4],
+            tree_indices: Vec::new(),
+            layers: vec![LayerEntry {
+                class: 0,
+                data: Data::Root {
+                    start,
+                    len,
+                    offset: 0,
+                },
+                attrs: Vec::new(),
+                payloads: Vec::new(),
+                annotations: Vec::new(),
+                links: Vec::new(),
+            }],
+            note: Note::default(),
+        };
+        for (start, len) in vec![(2, 3), (5, 0), (1, usize::max_value())] {
+            let mut file = bincode::serialize(&header).unwrap();
+            file.extend(bincode::serialize(&frame(start, len)).unwrap());
+            assert!(load(&file[..]).is_err());
+        }
+    }
+}
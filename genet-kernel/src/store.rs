@@ -1,24 +1,73 @@
 use array_vec::ArrayVec;
+use backpressure::Backpressure;
+use cancel::CancelToken;
+use coloring::ColoringTable;
+use column::{Column, ColumnCache};
 use crossbeam_channel;
-use decoder::{parallel, serial};
-use fnv::FnvHashMap;
-use frame::Frame;
-use genet_abi::{fixed::MutFixed, layer::Layer};
-use genet_filter::{self, Filter};
+use decoder::{
+    parallel, serial,
+    stats::{DecoderStats, Statistics},
+    table::DispatchTable,
+    trace::TraceEntry,
+};
+use diff::{self, AttrChange};
+use endpoint::{EndpointStats, EndpointTable, Order};
+use enrich::Enrichment;
+use filter::{self, Chunk, Task};
+use find::{self, Finder, Match, Query};
+use flow::{self, Flow, FlowTable};
+use fnv::{FnvHashMap, FnvHashSet};
+use frame::{Frame, Note};
+use genet_abi::decoder::{Metadata, Trigger};
+use genet_abi::layer::Link;
+use genet_abi::spill;
+use genet_abi::{fixed::MutFixed, layer::Layer, token::Token, variant::Variant};
+use genet_filter::{
+    self,
+    context::Context,
+    index::{attr_value, IndexKey},
+    named::NamedFilters,
+    result::Result as FilterResult,
+    variant::VariantExt,
+    Filter,
+};
+use graph::{self, IoGraph};
+use index::{IndexKind, IndexTable};
 use io::{Input, Output};
-use parking_lot::RwLock;
+use link::LinkTable;
+use num_cpus;
+use parking_lot::{Mutex, RwLock};
 use profile::Profile;
 use result::Result;
+use snapshot;
 use std::{
+    collections::{BTreeMap, VecDeque},
     fmt,
+    io::{self, Write},
+    iter,
     ops::Range,
     panic::{self, AssertUnwindSafe},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
     thread::{self, JoinHandle},
+    time::Duration,
 };
+use stream::Stream;
+use summary::SummaryCache;
 
 const OUTPUT_BLOCK_SIZE: usize = 65536;
 const MAX_FILTER_SIZE: usize = 16384;
+const MAX_FRAMES_IN_FLIGHT: usize = 262_144;
+const MAX_INDEX_ENTRIES: usize = 16_777_216;
+const INPUT_POLL_INTERVAL_MS: u64 = 10;
+const INPUT_WAIT_TIMEOUT_MS: u64 = 100;
+const MAX_REDECODE_IN_FLIGHT: usize = 65536;
+
+/// The frames scanned under a single read lock, so that the event loop can store
+/// new frames while a long-running task is scanning the store.
+const SCAN_CHUNK_SIZE: usize = 4096;
 
 pub trait Callback: Send {
     fn on_frames_updated(&self, _frames: u32) {}
@@ -27,6 +76,82 @@ pub trait Callback: Send {
     fn on_output_done(&self, _id: u32, _error: Option<Box<::std::error::Error + Send>>) {}
     fn on_input_done(&self, _id: u32, _error: Option<Box<::std::error::Error + Send>>) {}
     fn on_error(&self, _error: Box<::std::error::Error + Send>) {}
+    fn on_progress(&self, _progress: Progress) {}
+}
+
+/// Operations reporting the progress.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ProgressKind {
+    /// Evaluating the filter registered by `set_filter`.
+    Filter,
+
+    /// Writing the frames to an output.
+    Output,
+
+    /// A background task spawned by the session.
+    Task,
+}
+
+/// The number of the frames processed by a long-running operation.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub kind: ProgressKind,
+    pub id: u32,
+    pub done: u32,
+    pub total: u32,
+}
+
+/// Frames to be decoded again when the decoders have changed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Redecode {
+    /// Keeps the decoded frames.
+    None,
+
+    /// Decodes all the frames again.
+    All,
+
+    /// Decodes the frames to which the decoders bound to the triggers may apply.
+    Triggers(Vec<Trigger>),
+}
+
+impl Redecode {
+    /// Returns the frames which may be affected by the changed decoders.
+    pub fn changed<'a, I: IntoIterator<Item = &'a Metadata>>(decoders: I) -> Redecode {
+        let mut triggers = Vec::new();
+        for meta in decoders {
+            if meta.triggers.is_empty() {
+                return Redecode::All;
+            }
+            triggers.extend(meta.triggers.iter().cloned());
+        }
+        if triggers.is_empty() {
+            Redecode::None
+        } else {
+            Redecode::Triggers(triggers)
+        }
+    }
+}
+
+/// Frames to be written to an output.
+#[derive(Debug)]
+pub enum Selection {
+    /// All the frames.
+    All,
+
+    /// The frames matching the filter.
+    Filter(Filter),
+
+    /// The frames currently matching the filter registered with the ID.
+    ///
+    /// The filtered results are reused instead of evaluating the filter again.
+    Filtered(u32),
+}
+
+impl From<Option<Filter>> for Selection {
+    fn from(filter: Option<Filter>) -> Selection {
+        filter.map_or(Selection::All, Selection::Filter)
+    }
 }
 
 #[derive(Debug)]
@@ -34,8 +159,14 @@ enum Command {
     PushFrames(Option<u32>, Result<Vec<MutFixed<Layer>>>),
     PushSerialFrames(Vec<Frame>),
     StoreFrames(Vec<Frame>),
+    StoreFiltered(Chunk),
     SetFilter(u32, Option<Filter>),
-    PushOutput(u32, Box<Output>, Option<Filter>),
+    PushOutput(u32, Box<Output>, Selection, CancelToken),
+    SetProfile(Profile, Redecode),
+    SetColoringRules(Profile),
+    SetNote(u32, Note),
+    CancelRedecode,
+    Restore(Vec<Frame>),
     Close,
 }
 
@@ -56,6 +187,7 @@ impl fmt::Display for Error {
 
 type FrameStore = Arc<RwLock<ArrayVec<Frame>>>;
 type FilteredFrameStore = Arc<RwLock<FnvHashMap<u32, Vec<u32>>>>;
+type IndexStore = Arc<Mutex<IndexTable>>;
 
 #[derive(Debug)]
 pub struct Store {
@@ -63,25 +195,77 @@ pub struct Store {
     ev: EventLoop,
     frames: FrameStore,
     filtered: FilteredFrameStore,
+    links: LinkTable,
+    flows: FlowTable,
+    endpoints: EndpointTable,
+    columns: ColumnCache,
+    summaries: SummaryCache,
+    indexes: IndexStore,
+    backpressure: Backpressure,
     inputs: FnvHashMap<u32, InputContext>,
     inputs_trash: Vec<InputContext>,
+    stats: Statistics,
+    spill: Arc<spill::Pool>,
 }
 
 impl Store {
     pub fn new<C: 'static + Callback + Clone>(profile: Profile, callback: C) -> Store {
         let frames = Arc::new(RwLock::new(ArrayVec::new()));
         let filtered = Arc::new(RwLock::new(FnvHashMap::default()));
-        let (ev, send) = EventLoop::new(profile, callback, frames.clone(), filtered.clone());
+        let links = LinkTable::new();
+        let flows = FlowTable::new();
+        let endpoints = EndpointTable::new();
+        let enrichment = Enrichment::default();
+        enrichment.configure(&profile);
+        let spill = Arc::new(spill::Pool::new());
+        spill.set_limit(profile.memory_limit().map(|limit| limit as usize));
+        let coloring = ColoringTable::new();
+        coloring.configure(&profile);
+        let columns = ColumnCache::new();
+        let summaries = SummaryCache::new();
+        let indexes = Arc::new(Mutex::new(IndexTable::new(MAX_INDEX_ENTRIES)));
+        let backpressure = Backpressure::new(MAX_FRAMES_IN_FLIGHT);
+        let stats = Statistics::new();
+        let (ev, send) = EventLoop::new(
+            profile,
+            callback,
+            frames.clone(),
+            filtered.clone(),
+            links.clone(),
+            flows.clone(),
+            endpoints.clone(),
+            enrichment,
+            coloring,
+            columns.clone(),
+            summaries.clone(),
+            indexes.clone(),
+            backpressure.clone(),
+            stats.clone(),
+            spill.clone(),
+        );
         Store {
             sender: send,
             ev,
             frames,
             filtered,
+            links,
+            flows,
+            endpoints,
+            columns,
+            summaries,
+            indexes,
+            backpressure,
             inputs: FnvHashMap::default(),
             inputs_trash: Vec::new(),
+            stats,
+            spill,
         }
     }
 
+    pub fn statistics(&self) -> Vec<DecoderStats> {
+        self.stats.snapshot()
+    }
+
     pub fn frames(&self, range: Range<usize>) -> Vec<*const Frame> {
         self.frames
             .read()
@@ -105,29 +289,232 @@ impl Store {
         }
     }
 
+    /// Returns the values of the column for the frames in the range.
+    pub fn column_values(
+        &self,
+        column: &Column,
+        named: &NamedFilters,
+        range: Range<usize>,
+    ) -> FilterResult<Vec<Variant>> {
+        self.columns
+            .values(column, named, &self.frames.read(), &self.links, range)
+    }
+
+    /// Returns the one-line summaries of the frames in the range.
+    pub fn summaries(&self, range: Range<usize>) -> Vec<Option<String>> {
+        self.summaries.get(&self.frames.read(), range)
+    }
+
+    /// Returns the reverse links to the frame.
+    pub fn links(&self, index: u32) -> Vec<Link> {
+        self.links.get(index)
+    }
+
+    /// Returns a shared read-only view, which can be used from other threads.
+    pub fn view(&self) -> StoreView {
+        StoreView {
+            frames: self.frames.clone(),
+            links: self.links.clone(),
+            flows: self.flows.clone(),
+            endpoints: self.endpoints.clone(),
+        }
+    }
+
+    /// Returns the flows in the order of the first frames.
+    pub fn flows(&self) -> Vec<Flow> {
+        self.flows.flows()
+    }
+
+    pub fn flow(&self, id: u32) -> Option<Flow> {
+        self.flows.get(id)
+    }
+
+    /// Returns the stream which the frame belongs to.
+    pub fn stream(&self, index: u32) -> Option<Stream> {
+        self.view().stream(index)
+    }
+
+    pub fn endpoints(&self, order: Order, limit: Option<usize>) -> Vec<EndpointStats> {
+        self.endpoints.snapshot(order, limit)
+    }
+
+    /// Enables the lazily built index on the attribute, or disables it if `kind` is None.
+    pub fn set_index(&self, id: Token, kind: Option<IndexKind>) {
+        self.indexes.lock().set(id, kind);
+    }
+
+    /// Returns the frames having the attribute value.
+    ///
+    /// The index on the attribute is used if available.
+    pub fn lookup(&self, id: Token, value: &Variant) -> Vec<u32> {
+        let frames = self.frames.read();
+        let candidates = IndexKey::new(value)
+            .filter(|_| !self.links.contains_id(id))
+            .and_then(|key| {
+                self.indexes
+                    .lock()
+                    .lookup(id, &key, &frames, 0..frames.len())
+            });
+        let matches = |frame: &Frame| {
+            let links = self.links.get(frame.index());
+            let note = frame.note();
+            let ctx = Context::new(frame.layers())
+                .with_links(&links)
+                .with_note(note.marked, &note.comment);
+            attr_value(id, &ctx).op_eq(value)
+        };
+        if let Some(candidates) = candidates {
+            candidates
+                .into_iter()
+                .filter(|index| frames.get(*index as usize).map_or(false, &matches))
+                .collect()
+        } else {
+            frames
+                .iter()
+                .filter(|frame| matches(frame))
+                .map(|frame| frame.index())
+                .collect()
+        }
+    }
+
+    /// Buckets the stored frames into the intervals in nanoseconds for each filter.
+    pub fn io_graph(&self, interval: u64, filters: &[Option<Filter>]) -> IoGraph {
+        self.view()
+            .io_graph(interval, filters, &|_, _| {}, &CancelToken::new())
+    }
+
     pub fn len(&self) -> usize {
         let frames = self.frames.read();
         frames.len()
     }
 
+    /// Returns the number of frames which are read but not stored yet.
+    pub fn in_flight(&self) -> usize {
+        self.backpressure.in_flight()
+    }
+
     pub fn set_filter(&mut self, id: u32, filter: Option<Filter>) {
         self.sender.send(Command::SetFilter(id, filter));
     }
 
-    pub fn push_output<O: 'static + Output>(&mut self, id: u32, output: O, filter: Option<Filter>) {
-        self.sender
-            .send(Command::PushOutput(id, Box::new(output), filter));
+    /// Writes the selected frames to the output.
+    ///
+    /// The writing stops when `cancel` is cancelled.
+    pub fn push_output<O: 'static + Output, S: Into<Selection>>(
+        &mut self,
+        id: u32,
+        output: O,
+        selection: S,
+        cancel: CancelToken,
+    ) {
+        self.sender.send(Command::PushOutput(
+            id,
+            Box::new(output),
+            selection.into(),
+            cancel,
+        ));
+    }
+
+    pub fn set_profile(&mut self, profile: Profile, redecode: Redecode) {
+        self.spill
+            .set_limit(profile.memory_limit().map(|limit| limit as usize));
+        self.sender.send(Command::SetProfile(profile, redecode));
+    }
+
+    /// Replaces the coloring rules with the ones in the profile and recolors the stored frames.
+    ///
+    /// Unlike `set_profile`, the decoders keep running with their states.
+    pub fn set_coloring_rules(&mut self, profile: Profile) {
+        self.sender.send(Command::SetColoringRules(profile));
+    }
+
+    /// Stops decoding the stored frames again.
+    ///
+    /// The frames which are not dispatched to the decoders yet keep the previous results.
+    pub fn cancel_redecode(&mut self) {
+        self.sender.send(Command::CancelRedecode);
+    }
+
+    /// Attaches the note to the frame.
+    pub fn set_note(&mut self, index: u32, note: Note) {
+        self.sender.send(Command::SetNote(index, note));
+    }
+
+    /// Returns a copy of the root layer of the frame as read from the input.
+    pub fn root(&self, index: u32) -> Option<Layer> {
+        self.frames
+            .read()
+            .get(index as usize)
+            .and_then(|frame| frame.layers().first().map(|root| root.shallow_copy()))
+    }
+
+    /// Compares the decoded attributes of the frames.
+    pub fn diff(&self, a: u32, b: u32) -> Option<Vec<AttrChange>> {
+        let frames = self.frames.read();
+        let a = frames.get(a as usize)?;
+        let b = frames.get(b as usize)?;
+        Some(diff::diff(a, b))
+    }
+
+    /// Returns the decode trace of the frame.
+    pub fn trace(&self, index: u32) -> Option<Vec<TraceEntry>> {
+        self.frames
+            .read()
+            .get(index as usize)
+            .map(|frame| frame.trace().to_vec())
+    }
+
+    /// Returns the indices of the marked frames.
+    pub fn marked_frames(&self) -> Vec<u32> {
+        self.frames
+            .read()
+            .iter()
+            .filter(|frame| frame.note().marked)
+            .map(|frame| frame.index())
+            .collect()
+    }
+
+    /// Writes the stored frames and the filters to the session file.
+    pub fn save<W: Write>(
+        &self,
+        writer: W,
+        filters: &[(u32, String)],
+        named_filters: &[(String, String)],
+    ) -> io::Result<()> {
+        self.view().save(writer, filters, named_filters)
+    }
+
+    /// Stores the frames restored from a session file.
+    ///
+    /// The session must have no frames.
+    pub fn restore(&mut self, frames: Vec<Frame>) {
+        self.sender.send(Command::Restore(frames));
     }
 
     pub fn set_input<I: 'static + Input>(&mut self, id: u32, input: I) {
         let holder = Arc::new(self.sender.clone());
         let sender = Arc::downgrade(&holder);
+        let backpressure = self.backpressure.clone();
+        let spill = self.spill.clone();
         let mut input = input;
         let handle = thread::spawn(move || {
-            while let Some(sender) = sender.upgrade() {
-                match input.read() {
+            let _spill = spill::Scope::enter(&spill);
+            loop {
+                // Stop reading until the decoders catch up.
+                let max = backpressure.wait(Duration::from_millis(INPUT_WAIT_TIMEOUT_MS));
+                let sender = match sender.upgrade() {
+                    Some(sender) => sender,
+                    None => break,
+                };
+                if max == 0 {
+                    continue;
+                }
+                match input.read(max) {
                     Ok(layers) => {
-                        if !layers.is_empty() {
+                        if layers.is_empty() {
+                            thread::sleep(Duration::from_millis(INPUT_POLL_INTERVAL_MS));
+                        } else {
+                            backpressure.acquire(layers.len());
                             sender.send(Command::PushFrames(Some(id), Ok(layers)));
                         }
                     }
@@ -156,6 +543,261 @@ impl Store {
     }
 }
 
+/// A read-only view of the stored frames.
+#[derive(Clone)]
+pub struct StoreView {
+    frames: FrameStore,
+    links: LinkTable,
+    flows: FlowTable,
+    endpoints: EndpointTable,
+}
+
+impl StoreView {
+    pub fn len(&self) -> usize {
+        self.frames.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the flows in the order of the first frames.
+    pub fn flows(&self) -> Vec<Flow> {
+        self.flows.flows()
+    }
+
+    /// Returns the stream which the frame belongs to.
+    pub fn stream(&self, index: u32) -> Option<Stream> {
+        let frames = self.frames.read();
+        let frame = frames.get(index as usize)?;
+        Stream::build(frame, &frames, &self.flows)
+    }
+
+    pub fn endpoints(&self, order: Order, limit: Option<usize>) -> Vec<EndpointStats> {
+        self.endpoints.snapshot(order, limit)
+    }
+
+    /// Buckets the stored frames into the intervals in nanoseconds for each filter.
+    ///
+    /// `progress` is called with the number of the processed frames and the total.
+    /// The graph is incomplete if `cancel` is cancelled in the middle.
+    pub fn io_graph(
+        &self,
+        interval: u64,
+        filters: &[Option<Filter>],
+        progress: &Fn(u32, u32),
+        cancel: &CancelToken,
+    ) -> IoGraph {
+        let total = self.len();
+        let mut builder = graph::Builder::new(&self.links, interval, filters);
+        for start in (0..total).step_by(SCAN_CHUNK_SIZE) {
+            if cancel.is_cancelled() {
+                break;
+            }
+            progress(start as u32, total as u32);
+            let frames = self.frames.read();
+            for i in start..(start + SCAN_CHUNK_SIZE).min(total) {
+                if let Some(frame) = frames.get(i) {
+                    builder.add(frame);
+                }
+            }
+        }
+        progress(total as u32, total as u32);
+        builder.build()
+    }
+
+    /// Returns the indices of the frames matching the filter.
+    ///
+    /// The search stops when `cancel` is cancelled.
+    pub fn search(
+        &self,
+        filter: &Filter,
+        progress: &Fn(u32, u32),
+        cancel: &CancelToken,
+    ) -> Vec<u32> {
+        let total = self.len();
+        let mut indices = Vec::new();
+        for start in (0..total).step_by(SCAN_CHUNK_SIZE) {
+            if cancel.is_cancelled() {
+                break;
+            }
+            progress(start as u32, total as u32);
+            let frames = self.frames.read();
+            for i in start..(start + SCAN_CHUNK_SIZE).min(total) {
+                let frame = match frames.get(i) {
+                    Some(frame) => frame,
+                    None => break,
+                };
+                let links = self.links.get(frame.index());
+                let note = frame.note();
+                let ctx = Context::new(frame.layers())
+                    .with_links(&links)
+                    .with_note(note.marked, &note.comment);
+                if filter.test(&ctx) {
+                    indices.push(frame.index());
+                }
+            }
+        }
+        progress(total as u32, total as u32);
+        indices
+    }
+
+    /// Finds the first frame matching the query, starting from the frame `from`
+    /// and moving forward, or backward if `backward` is true.
+    ///
+    /// The search stops when `cancel` is cancelled.
+    pub fn find(
+        &self,
+        query: &Query,
+        from: u32,
+        backward: bool,
+        progress: &Fn(u32, u32),
+        cancel: &CancelToken,
+    ) -> Option<Match> {
+        let from = from as usize;
+        let len = self.len();
+        let total = if backward {
+            (from + 1).min(len)
+        } else {
+            len.saturating_sub(from)
+        };
+        if let Query::Bytes(patterns) = query {
+            return self.find_bytes(
+                Finder::new(patterns),
+                from,
+                total,
+                backward,
+                progress,
+                cancel,
+            );
+        }
+
+        for start in (0..total).step_by(SCAN_CHUNK_SIZE) {
+            if cancel.is_cancelled() {
+                return None;
+            }
+            progress(start as u32, total as u32);
+            let frames = self.frames.read();
+            for i in start..(start + SCAN_CHUNK_SIZE).min(total) {
+                let index = if backward { from - i } else { from + i };
+                if let Some(found) = frames.get(index).and_then(|frame| find::find(frame, query)) {
+                    progress(total as u32, total as u32);
+                    return Some(found);
+                }
+            }
+        }
+        progress(total as u32, total as u32);
+        None
+    }
+
+    /// Scans the frames in chunks on all cores. The chunks are taken in the search order,
+    /// and a match is returned once all the preceding chunks have been scanned.
+    fn find_bytes(
+        &self,
+        finder: Finder,
+        from: usize,
+        total: usize,
+        backward: bool,
+        progress: &Fn(u32, u32),
+        cancel: &CancelToken,
+    ) -> Option<Match> {
+        let search = Arc::new(ByteSearch {
+            frames: self.frames.clone(),
+            finder,
+            from,
+            total,
+            backward,
+            cancel: cancel.clone(),
+            next: AtomicUsize::new(0),
+            scanned: AtomicUsize::new(0),
+            found: Mutex::new(None),
+        });
+        let chunks = (total + SCAN_CHUNK_SIZE - 1) / SCAN_CHUNK_SIZE;
+        let handles = (1..chunks.min(num_cpus::get()))
+            .map(|_| {
+                let search = search.clone();
+                thread::spawn(move || search.run(&|_, _| {}))
+            })
+            .collect::<Vec<_>>();
+        search.run(progress);
+        for handle in handles {
+            let _ = handle.join();
+        }
+        progress(total as u32, total as u32);
+        if cancel.is_cancelled() {
+            return None;
+        }
+        let found = search.found.lock().take();
+        found.map(|(_, found)| found)
+    }
+
+    /// Writes the stored frames and the filters to the session file.
+    pub fn save<W: Write>(
+        &self,
+        writer: W,
+        filters: &[(u32, String)],
+        named_filters: &[(String, String)],
+    ) -> io::Result<()> {
+        snapshot::save(writer, &self.frames.read(), filters, named_filters)
+    }
+}
+
+struct ByteSearch {
+    frames: FrameStore,
+    finder: Finder,
+    from: usize,
+    total: usize,
+    backward: bool,
+    cancel: CancelToken,
+    next: AtomicUsize,
+    scanned: AtomicUsize,
+
+    /// The earliest chunk having a match so far, and the match.
+    found: Mutex<Option<(usize, Match)>>,
+}
+
+impl ByteSearch {
+    fn run(&self, progress: &Fn(u32, u32)) {
+        loop {
+            let chunk = self.next.fetch_add(1, Ordering::Relaxed);
+            let start = chunk * SCAN_CHUNK_SIZE;
+            if start >= self.total || self.cancel.is_cancelled() {
+                return;
+            }
+            if let Some((found, _)) = *self.found.lock() {
+                if found < chunk {
+                    return;
+                }
+            }
+            let end = (start + SCAN_CHUNK_SIZE).min(self.total);
+            let found = {
+                let frames = self.frames.read();
+                (start..end)
+                    .filter_map(|i| {
+                        let index = if self.backward {
+                            self.from - i
+                        } else {
+                            self.from + i
+                        };
+                        frames
+                            .get(index)
+                            .and_then(|frame| find::find_bytes(frame, &self.finder))
+                    })
+                    .next()
+            };
+            if let Some(found) = found {
+                let mut earliest = self.found.lock();
+                if earliest.as_ref().map_or(true, |(c, _)| chunk < *c) {
+                    *earliest = Some((chunk, found));
+                }
+                return;
+            }
+            let scanned = self.scanned.fetch_add(end - start, Ordering::Relaxed) + end - start;
+            progress(scanned as u32, self.total as u32);
+        }
+    }
+}
+
 #[derive(Debug)]
 struct InputContext {
     handle: Option<JoinHandle<()>>,
@@ -191,9 +833,45 @@ impl serial::Callback for SerialCallback {
     }
 }
 
+#[derive(Clone)]
+struct FilterCallback {
+    sender: crossbeam_channel::Sender<Command>,
+}
+
+impl filter::Callback for FilterCallback {
+    fn done(&self, chunk: Chunk) {
+        self.sender.send(Command::StoreFiltered(chunk));
+    }
+}
+
 struct FilterContext {
     filter: Filter,
+
+    /// Distinguishes the results of the current filter from the stale ones.
+    generation: u64,
+
+    /// The end of the frames dispatched to the workers.
     offset: usize,
+
+    /// The end of the frames merged into the results.
+    merged: usize,
+
+    /// The chunks waiting for the preceding ones.
+    pending: BTreeMap<usize, Chunk>,
+
+    /// Skips the tasks dispatched for the previous generations.
+    cancel: CancelToken,
+}
+
+impl FilterContext {
+    fn reset(&mut self, generation: u64) {
+        self.cancel.cancel();
+        self.cancel = CancelToken::new();
+        self.generation = generation;
+        self.offset = 0;
+        self.merged = 0;
+        self.pending.clear();
+    }
 }
 
 struct EventLoop {
@@ -207,30 +885,58 @@ impl EventLoop {
         callback: C,
         frames: FrameStore,
         filtered: FilteredFrameStore,
+        links: LinkTable,
+        flows: FlowTable,
+        endpoints: EndpointTable,
+        enrichment: Enrichment,
+        coloring: ColoringTable,
+        columns: ColumnCache,
+        summaries: SummaryCache,
+        indexes: IndexStore,
+        backpressure: Backpressure,
+        stats: Statistics,
+        spill: Arc<spill::Pool>,
     ) -> (EventLoop, crossbeam_channel::Sender<Command>) {
         let (send, recv) = crossbeam_channel::unbounded();
         let sender = send.clone();
         let handle = thread::spawn(move || {
+            let _spill = spill::Scope::enter(&spill);
             let err_callback = callback.clone();
             let result = panic::catch_unwind(AssertUnwindSafe(move || {
                 let mut filter_map = FnvHashMap::default();
+                let mut generation = 0;
+                let mut backlog = VecDeque::new();
+                let mut redecoding = VecDeque::new();
+                let mut fpool = filter::Pool::new(
+                    profile.concurrency(),
+                    &frames,
+                    &links,
+                    &FilterCallback {
+                        sender: sender.clone(),
+                    },
+                );
                 let mut ppool = parallel::Pool::new(
                     &profile,
                     &ParallelCallback {
                         sender: sender.clone(),
                     },
+                    &stats,
+                    &spill,
                 );
                 let mut spool = serial::Pool::new(
                     profile.clone(),
                     SerialCallback {
                         sender: sender.clone(),
                     },
+                    stats.clone(),
+                    spill.clone(),
                 );
+                let mut profile = profile;
                 let mut cnt = 0;
                 callback.on_frames_updated(0);
                 callback.on_async_frames_updated(0);
                 loop {
-                    if let Some(cmd) = recv.recv() {
+                    if let Some(cmd) = backlog.pop_front().or_else(|| recv.recv()) {
                         match cmd {
                             Command::PushFrames(id, result) => {
                                 Self::process_input(id, result, &mut cnt, &mut ppool, &callback)
@@ -238,31 +944,191 @@ impl EventLoop {
                             Command::PushSerialFrames(vec) => {
                                 spool.process(vec);
                             }
-                            Command::StoreFrames(mut vec) => {
-                                let len = {
-                                    let mut frames = frames.write();
-                                    for f in vec {
-                                        frames.push(f);
-                                    }
-                                    frames.len()
-                                };
-                                callback.on_frames_updated(len as u32);
-                                callback.on_async_frames_updated(len as u32);
-                            }
-                            Command::SetFilter(id, filter) => Self::process_push_filter(
-                                id,
-                                filter,
+                            Command::StoreFrames(vec) => Self::process_store(
+                                vec,
+                                &frames,
+                                &links,
+                                &flows,
+                                &endpoints,
+                                &enrichment,
+                                &coloring,
+                                &backpressure,
+                                &callback,
+                            ),
+                            Command::StoreFiltered(chunk) => Self::process_filtered(
+                                chunk,
+                                &frames,
                                 &filtered,
                                 &mut filter_map,
                                 &callback,
                             ),
-                            Command::PushOutput(id, output, filter) => {
-                                Self::process_output(id, output, &filter, &frames, &callback)
+                            Command::SetFilter(id, filter) => {
+                                generation += 1;
+                                Self::process_push_filter(
+                                    id,
+                                    filter,
+                                    generation,
+                                    &frames,
+                                    &filtered,
+                                    &mut filter_map,
+                                    &callback,
+                                )
+                            }
+                            Command::PushOutput(id, output, selection, cancel) => {
+                                Self::process_output(
+                                    id, output, &selection, &cancel, &frames, &filtered, &links,
+                                    &callback,
+                                )
+                            }
+                            Command::SetProfile(new_profile, redecode) => {
+                                // Flush the frames in flight through the current workers.
+                                Self::dispatch_redecode(&mut redecoding, &mut ppool);
+                                drop(ppool);
+                                Self::flush(
+                                    &recv,
+                                    Some(&mut spool),
+                                    &frames,
+                                    &links,
+                                    &flows,
+                                    &endpoints,
+                                    &enrichment,
+                                    &coloring,
+                                    &backpressure,
+                                    &mut backlog,
+                                    &callback,
+                                );
+                                drop(spool);
+                                Self::flush(
+                                    &recv,
+                                    None,
+                                    &frames,
+                                    &links,
+                                    &flows,
+                                    &endpoints,
+                                    &enrichment,
+                                    &coloring,
+                                    &backpressure,
+                                    &mut backlog,
+                                    &callback,
+                                );
+
+                                let redecoded = if redecode != Redecode::None {
+                                    cnt = 0;
+                                    generation += 1;
+                                    Self::reset_frames(
+                                        &frames,
+                                        &filtered,
+                                        &links,
+                                        &flows,
+                                        &endpoints,
+                                        &enrichment,
+                                        &columns,
+                                        &summaries,
+                                        &indexes,
+                                        &redecode,
+                                        generation,
+                                        &mut filter_map,
+                                        &callback,
+                                    )
+                                } else {
+                                    Vec::new()
+                                };
+
+                                profile = new_profile;
+                                enrichment.configure(&profile);
+                                if coloring.configure(&profile) {
+                                    Self::recolor(&frames, &links, &coloring, &callback);
+                                }
+                                fpool = filter::Pool::new(
+                                    profile.concurrency(),
+                                    &frames,
+                                    &links,
+                                    &FilterCallback {
+                                        sender: sender.clone(),
+                                    },
+                                );
+                                ppool = parallel::Pool::new(
+                                    &profile,
+                                    &ParallelCallback {
+                                        sender: sender.clone(),
+                                    },
+                                    &stats,
+                                    &spill,
+                                );
+                                spool = serial::Pool::with_start(
+                                    profile.clone(),
+                                    SerialCallback {
+                                        sender: sender.clone(),
+                                    },
+                                    stats.clone(),
+                                    spill.clone(),
+                                    cnt,
+                                );
+                                if !redecoded.is_empty() {
+                                    cnt = redecoded.len() as u32;
+                                    backpressure.acquire(redecoded.len());
+                                    redecoding = redecoded.into_iter().collect();
+                                }
+                            }
+                            Command::SetColoringRules(new_profile) => {
+                                if coloring.configure(&new_profile) {
+                                    Self::recolor(&frames, &links, &coloring, &callback);
+                                }
+                            }
+                            Command::CancelRedecode => {
+                                Self::process_cancel_redecode(&mut redecoding, &mut ppool)
+                            }
+                            Command::Restore(vec) => {
+                                if cnt > 0 {
+                                    let err = Error("the session already has frames".to_string());
+                                    callback.on_error(Box::new(err));
+                                } else {
+                                    // The restored frames are stored without decoding,
+                                    // so the serial decoders start from the next frame.
+                                    cnt = vec.len() as u32;
+                                    spool = serial::Pool::with_start(
+                                        profile.clone(),
+                                        SerialCallback {
+                                            sender: sender.clone(),
+                                        },
+                                        stats.clone(),
+                                        spill.clone(),
+                                        cnt,
+                                    );
+                                    backpressure.acquire(vec.len());
+                                    Self::process_store(
+                                        vec,
+                                        &frames,
+                                        &links,
+                                        &flows,
+                                        &endpoints,
+                                        &enrichment,
+                                        &coloring,
+                                        &backpressure,
+                                        &callback,
+                                    );
+                                }
+                            }
+                            Command::SetNote(index, note) => {
+                                generation += 1;
+                                Self::process_note(
+                                    index,
+                                    note,
+                                    generation,
+                                    &frames,
+                                    &filtered,
+                                    &links,
+                                    &coloring,
+                                    &columns,
+                                    &mut filter_map,
+                                    &callback,
+                                )
                             }
                             Command::Close => return,
                         }
                     }
-                    Self::process_filters(&frames, &filtered, &mut filter_map, &callback);
+                    Self::process_redecode(&frames, &mut redecoding, &mut ppool);
+                    Self::process_filters(&frames, &links, &indexes, &mut fpool, &mut filter_map);
                 }
             }));
             if let Err(err) = result {
@@ -320,34 +1186,305 @@ impl EventLoop {
         }
     }
 
+    fn process_store(
+        vec: Vec<Frame>,
+        frames: &FrameStore,
+        links: &LinkTable,
+        flows: &FlowTable,
+        endpoints: &EndpointTable,
+        enrichment: &Enrichment,
+        coloring: &ColoringTable,
+        backpressure: &Backpressure,
+        callback: &Callback,
+    ) {
+        let stored = vec.len();
+        let len = {
+            let mut frames = frames.write();
+            for mut f in vec {
+                enrichment.observe(&f);
+                if f.is_retained() {
+                    flows.replay(&f);
+                } else {
+                    flows.insert(&mut f);
+                    enrichment.insert(&mut f);
+                }
+                endpoints.insert(&f);
+                links.insert(&f);
+                coloring.apply(&f, links);
+                frames.push(f);
+            }
+            frames.len()
+        };
+        // Release after storing so that no frames are missing while in_flight() is 0.
+        backpressure.release(stored);
+        callback.on_frames_updated(len as u32);
+        callback.on_async_frames_updated(len as u32);
+    }
+
+    fn flush(
+        recv: &crossbeam_channel::Receiver<Command>,
+        spool: Option<&mut serial::Pool>,
+        frames: &FrameStore,
+        links: &LinkTable,
+        flows: &FlowTable,
+        endpoints: &EndpointTable,
+        enrichment: &Enrichment,
+        coloring: &ColoringTable,
+        backpressure: &Backpressure,
+        backlog: &mut VecDeque<Command>,
+        callback: &Callback,
+    ) {
+        let mut spool = spool;
+        while let Some(cmd) = recv.try_recv() {
+            match (cmd, &mut spool) {
+                (Command::PushSerialFrames(vec), Some(spool)) => spool.process(vec),
+                (Command::StoreFrames(vec), _) => Self::process_store(
+                    vec,
+                    frames,
+                    links,
+                    flows,
+                    endpoints,
+                    enrichment,
+                    coloring,
+                    backpressure,
+                    callback,
+                ),
+                (cmd, _) => backlog.push_back(cmd),
+            }
+        }
+    }
+
+    /// Re-evaluates the coloring rules on the stored frames.
+    fn recolor(
+        frames: &FrameStore,
+        links: &LinkTable,
+        coloring: &ColoringTable,
+        callback: &Callback,
+    ) {
+        let len = {
+            let frames = frames.read();
+            for frame in frames.iter() {
+                coloring.apply(frame, links);
+            }
+            frames.len()
+        };
+        if len > 0 {
+            callback.on_frames_updated(len as u32);
+        }
+    }
+
+    /// Attaches the note to the frame and restarts the filters referring to the notes.
+    fn process_note(
+        index: u32,
+        note: Note,
+        generation: u64,
+        frames: &FrameStore,
+        filtered: &FilteredFrameStore,
+        links: &LinkTable,
+        coloring: &ColoringTable,
+        columns: &ColumnCache,
+        filter_map: &mut FnvHashMap<u32, FilterContext>,
+        callback: &Callback,
+    ) {
+        let len = {
+            let frames = frames.read();
+            match frames.get(index as usize) {
+                Some(frame) => {
+                    frame.set_note(note);
+                    coloring.apply(frame, links);
+                }
+                None => return,
+            }
+            frames.len()
+        };
+        columns.clear();
+        for (id, fctx) in filter_map.iter_mut() {
+            if fctx.filter.refers("frame.marked") || fctx.filter.refers("frame.comment") {
+                fctx.reset(generation);
+                filtered.write().remove(id);
+                callback.on_filtered_frames_updated(*id, 0);
+            }
+        }
+        callback.on_frames_updated(len as u32);
+    }
+
+    /// Dispatches the frames to be decoded again to the workers.
+    ///
+    /// At most `MAX_REDECODE_IN_FLIGHT` frames are in the workers at once,
+    /// so that the rest can be cancelled.
+    fn process_redecode(
+        frames: &FrameStore,
+        redecoding: &mut VecDeque<(Frame, Frame)>,
+        pool: &mut parallel::Pool,
+    ) {
+        let next = match redecoding.front() {
+            Some((frame, _)) => frame.index() as usize,
+            None => return,
+        };
+        let free = (frames.read().len() + MAX_REDECODE_IN_FLIGHT).saturating_sub(next);
+        if free < MAX_REDECODE_IN_FLIGHT / 2 && free < redecoding.len() {
+            return;
+        }
+        let len = free.min(redecoding.len());
+        pool.process(redecoding.drain(..len).map(|(frame, _)| frame).collect());
+    }
+
+    /// Dispatches all the remaining frames to be decoded again.
+    fn dispatch_redecode(redecoding: &mut VecDeque<(Frame, Frame)>, pool: &mut parallel::Pool) {
+        if !redecoding.is_empty() {
+            pool.process(redecoding.drain(..).map(|(frame, _)| frame).collect());
+        }
+    }
+
+    /// Stores the previous results of the frames which are not dispatched yet.
+    ///
+    /// The retained frames pass through the workers without being decoded,
+    /// so the frames in flight keep their order.
+    fn process_cancel_redecode(
+        redecoding: &mut VecDeque<(Frame, Frame)>,
+        pool: &mut parallel::Pool,
+    ) {
+        if !redecoding.is_empty() {
+            pool.process(redecoding.drain(..).map(|(_, previous)| previous).collect());
+        }
+    }
+
+    /// Returns the frames to be decoded again,
+    /// paired with the copies keeping the previous results.
+    fn take_frames(frames: &FrameStore, redecode: &Redecode) -> Vec<(Frame, Frame)> {
+        let frames = frames.read();
+        let affected = match redecode {
+            Redecode::Triggers(triggers) => affected_frames(&frames, triggers),
+            _ => vec![true; frames.len()],
+        };
+        // The previous frames are never freed, so the layers can be shared
+        // with the frames which are still referenced by the front-end.
+        frames
+            .iter()
+            .zip(affected)
+            .map(|(frame, affected)| {
+                let redecoded = match frame.layers().first() {
+                    Some(root) if affected => {
+                        let redecoded = Frame::new(frame.index(), unsafe {
+                            MutFixed::from_ptr(root.as_mut_ptr())
+                        });
+                        // The note is kept across the decoding.
+                        redecoded.set_note(frame.note());
+                        redecoded
+                    }
+                    _ => Frame::retain(frame),
+                };
+                (redecoded, Frame::retain(frame))
+            })
+            .collect()
+    }
+
+    fn reset_frames(
+        frames: &FrameStore,
+        filtered: &FilteredFrameStore,
+        links: &LinkTable,
+        flows: &FlowTable,
+        endpoints: &EndpointTable,
+        enrichment: &Enrichment,
+        columns: &ColumnCache,
+        summaries: &SummaryCache,
+        indexes: &IndexStore,
+        redecode: &Redecode,
+        generation: u64,
+        filter_map: &mut FnvHashMap<u32, FilterContext>,
+        callback: &Callback,
+    ) -> Vec<(Frame, Frame)> {
+        let vec = Self::take_frames(frames, redecode);
+        *frames.write() = ArrayVec::new();
+        filtered.write().clear();
+        links.clear();
+        if *redecode == Redecode::All {
+            flows.clear();
+        } else {
+            // The retained frames keep their flow IDs.
+            flows.reset_stats();
+        }
+        endpoints.clear();
+        enrichment.clear();
+        columns.clear();
+        summaries.clear();
+        indexes.lock().reset();
+        for (id, fctx) in filter_map.iter_mut() {
+            fctx.reset(generation);
+            callback.on_filtered_frames_updated(*id, 0);
+        }
+        callback.on_frames_updated(0);
+        callback.on_async_frames_updated(0);
+        vec
+    }
+
     fn process_output(
         id: u32,
         output: Box<Output>,
-        filter: &Option<Filter>,
+        selection: &Selection,
+        cancel: &CancelToken,
         frames: &FrameStore,
+        filtered: &FilteredFrameStore,
+        links: &LinkTable,
         callback: &Callback,
     ) {
         let frames = frames.read();
+        let indices = match selection {
+            Selection::Filtered(filter_id) => {
+                Some(filtered.read().get(filter_id).cloned().unwrap_or_default())
+            }
+            _ => None,
+        };
+        let total = indices
+            .as_ref()
+            .map_or(frames.len(), |indices| indices.len());
         let mut offset = 0;
         {
             let mut output = output;
-            while offset < frames.len() {
-                let len = OUTPUT_BLOCK_SIZE.min(frames.len() - offset);
-                let frames = frames
-                    .iter()
-                    .skip(offset)
-                    .take(len)
-                    .filter(|frame| {
-                        let ctx = genet_filter::context::Context::new(frame.layers());
-                        filter.as_ref().map_or(true, |f| f.test(&ctx))
-                    })
-                    .collect::<Vec<_>>();
+            while offset < total {
+                // The output is dropped without being finished.
+                if cancel.is_cancelled() {
+                    let err = Error("cancelled".to_string());
+                    callback.on_output_done(id, Some(Box::new(err)));
+                    return;
+                }
+                let len = OUTPUT_BLOCK_SIZE.min(total - offset);
+                let frames = if let Some(indices) = &indices {
+                    indices[offset..offset + len]
+                        .iter()
+                        .filter_map(|index| frames.get(*index as usize))
+                        .collect::<Vec<_>>()
+                } else {
+                    frames
+                        .iter()
+                        .skip(offset)
+                        .take(len)
+                        .filter(|frame| {
+                            if let Selection::Filter(filter) = selection {
+                                let links = links.get(frame.index());
+                                let note = frame.note();
+                                let ctx = genet_filter::context::Context::new(frame.layers())
+                                    .with_links(&links)
+                                    .with_note(note.marked, &note.comment);
+                                filter.test(&ctx)
+                            } else {
+                                true
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                };
                 if let Err(err) = output.write(frames.as_slice()) {
                     let err = Error(err.description().to_string());
                     callback.on_output_done(id, Some(Box::new(err)));
                     return;
                 }
                 offset += len;
+                callback.on_progress(Progress {
+                    kind: ProgressKind::Output,
+                    id,
+                    done: offset as u32,
+                    total: total as u32,
+                });
             }
             if let Err(err) = output.end() {
                 let err = Error(err.description().to_string());
@@ -361,66 +1498,134 @@ impl EventLoop {
     fn process_push_filter(
         id: u32,
         filter: Option<Filter>,
+        generation: u64,
+        frames: &FrameStore,
         filtered: &FilteredFrameStore,
         filter_map: &mut FnvHashMap<u32, FilterContext>,
         callback: &Callback,
     ) {
-        if let Some(filter) = filter {
-            filter_map.insert(
+        let prev = if let Some(filter) = filter {
+            let prev = filter_map.insert(
                 id,
                 FilterContext {
+                    filter,
+                    generation,
                     offset: 0,
-                    filter: filter.clone(),
+                    merged: 0,
+                    pending: BTreeMap::new(),
+                    cancel: CancelToken::new(),
                 },
             );
             callback.on_filtered_frames_updated(id, 0);
+            callback.on_progress(Progress {
+                kind: ProgressKind::Filter,
+                id,
+                done: 0,
+                total: frames.read().len() as u32,
+            });
+            prev
         } else {
-            filter_map.remove(&id);
+            filter_map.remove(&id)
+        };
+        // The queued tasks of the previous filter are no longer needed.
+        if let Some(prev) = prev {
+            prev.cancel.cancel();
         }
         filtered.write().remove(&id);
     }
 
+    /// Dispatches the frames which are not tested yet to the workers.
     fn process_filters(
         frames: &FrameStore,
-        filtered: &FilteredFrameStore,
+        links: &LinkTable,
+        indexes: &IndexStore,
+        pool: &mut filter::Pool,
         filter_map: &mut FnvHashMap<u32, FilterContext>,
-        callback: &Callback,
     ) {
+        let frames = frames.read();
+        let len = frames.len();
         for (id, fctx) in filter_map.iter_mut() {
-            loop {
-                let (mut indices, end) = {
-                    let frames = frames.read();
-                    let mut indices = frames
-                        .iter()
-                        .skip(fctx.offset)
-                        .take(MAX_FILTER_SIZE)
-                        .filter_map(|frame| {
-                            let ctx = genet_filter::context::Context::new(frame.layers());
-                            if fctx.filter.test(&ctx) {
-                                Some(frame.index())
-                            } else {
-                                None
-                            }
-                        })
-                        .collect::<Vec<_>>();
-                    fctx.offset = frames.len().min(fctx.offset + MAX_FILTER_SIZE);
-                    (indices, fctx.offset >= frames.len())
-                };
-                if !indices.is_empty() {
-                    let len = {
-                        let mut filtered = filtered.write();
-                        let mut frames = filtered.entry(*id).or_insert_with(Vec::new);
-                        frames.append(&mut indices);
-                        frames.len()
-                    };
-                    callback.on_filtered_frames_updated(*id, len as u32);
-                }
-                if end {
-                    break;
-                }
+            if fctx.offset >= len {
+                continue;
+            }
+
+            // Only the frames found in the index need to be tested.
+            // The reverse links are excluded since they are added after the frames are indexed.
+            let candidates = fctx
+                .filter
+                .index_hint()
+                .filter(|(attr, _)| !links.contains_id(*attr))
+                .and_then(|(attr, key)| {
+                    indexes.lock().lookup(*attr, key, &frames, fctx.offset..len)
+                });
+            if let Some(candidates) = candidates {
+                pool.process(Task {
+                    id: *id,
+                    generation: fctx.generation,
+                    filter: fctx.filter.clone(),
+                    range: fctx.offset..len,
+                    candidates: Some(candidates),
+                    cancel: fctx.cancel.clone(),
+                });
+                fctx.offset = len;
+            }
+
+            while fctx.offset < len {
+                let end = len.min(fctx.offset + MAX_FILTER_SIZE);
+                pool.process(Task {
+                    id: *id,
+                    generation: fctx.generation,
+                    filter: fctx.filter.clone(),
+                    range: fctx.offset..end,
+                    candidates: None,
+                    cancel: fctx.cancel.clone(),
+                });
+                fctx.offset = end;
             }
         }
     }
+
+    /// Merges the chunk in the order of the frames and publishes the match count.
+    fn process_filtered(
+        chunk: Chunk,
+        frames: &FrameStore,
+        filtered: &FilteredFrameStore,
+        filter_map: &mut FnvHashMap<u32, FilterContext>,
+        callback: &Callback,
+    ) {
+        let id = chunk.id;
+        let fctx = match filter_map.get_mut(&id) {
+            Some(fctx) => fctx,
+            None => return,
+        };
+        if chunk.generation != fctx.generation {
+            return;
+        }
+        fctx.pending.insert(chunk.range.start, chunk);
+        let mut indices = Vec::new();
+        let merged = fctx.merged;
+        while let Some(mut chunk) = fctx.pending.remove(&fctx.merged) {
+            fctx.merged = chunk.range.end;
+            indices.append(&mut chunk.indices);
+        }
+        if !indices.is_empty() {
+            let len = {
+                let mut filtered = filtered.write();
+                let frames = filtered.entry(id).or_insert_with(Vec::new);
+                frames.append(&mut indices);
+                frames.len()
+            };
+            callback.on_filtered_frames_updated(id, len as u32);
+        }
+        if fctx.merged > merged {
+            callback.on_progress(Progress {
+                kind: ProgressKind::Filter,
+                id,
+                done: fctx.merged as u32,
+                total: frames.read().len() as u32,
+            });
+        }
+    }
 }
 
 impl Drop for EventLoop {
@@ -436,16 +1641,106 @@ impl fmt::Debug for EventLoop {
     }
 }
 
+/// Returns the mask of the frames to which the decoders bound to the triggers may apply.
+///
+/// The frames sharing a flow with those frames are also included,
+/// so that the serial decoders see every frame of the flow.
+fn affected_frames(frames: &ArrayVec<Frame>, triggers: &[Trigger]) -> Vec<bool> {
+    if triggers.is_empty() {
+        return vec![false; frames.len()];
+    }
+    let metadata = Metadata {
+        triggers: triggers.to_vec(),
+        ..Metadata::default()
+    };
+    let table = DispatchTable::new(iter::once(&metadata));
+    let mut mask = frames
+        .iter()
+        .map(|frame| frame.layers().iter().any(|layer| table.lookup(layer)[0]))
+        .collect::<Vec<_>>();
+    let keys = frames
+        .iter()
+        .zip(mask.iter())
+        .filter(|(_, affected)| **affected)
+        .filter_map(|(frame, _)| flow::key(frame))
+        .collect::<FnvHashSet<_>>();
+    if !keys.is_empty() {
+        for (frame, affected) in frames.iter().zip(mask.iter_mut()) {
+            *affected = *affected || flow::key(frame).map_or(false, |key| keys.contains(&key));
+        }
+    }
+    mask
+}
+
 #[cfg(test)]
 mod tests {
+    use cancel::CancelToken;
+    use coloring::ColoringRule;
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        decoder::{Metadata, Trigger},
+        fixed::{Fixed, MutFixed},
+        layer::{Layer, LayerClass},
+        result::Result,
+        slice::ByteSlice,
+        variant::Variant,
+    };
     use genet_filter::Filter;
+    use graph::Bucket;
+    use io::Input;
     use profile::Profile;
-    use store::{Callback, Store};
+    use std::{
+        cell::Cell,
+        thread,
+        time::{Duration, Instant},
+    };
+    use store::{Callback, Redecode, Store, SCAN_CHUNK_SIZE};
 
     #[derive(Clone)]
     struct TestCallback {}
     impl Callback for TestCallback {}
 
+    #[derive(Debug)]
+    struct TestInput {
+        frames: usize,
+    }
+
+    impl Input for TestInput {
+        fn read(&mut self, max: usize) -> Result<Vec<MutFixed<Layer>>> {
+            let len = self.frames.min(max);
+            self.frames -= len;
+            let class = Fixed::new(LayerClass::builder("[link-1]").build());
+            let ts = Fixed::new(
+                AttrClass::builder("[link-1].ts")
+                    .typ("@datetime:unix")
+                    .build(),
+            );
+            Ok((0..len)
+                .map(|_| {
+                    let mut layer = Layer::new(class.clone(), ByteSlice::from(&[1, 2, 3][..]));
+                    layer.add_attr(
+                        Attr::builder(ts.clone())
+                            .value(Variant::Timestamp(0))
+                            .build(),
+                    );
+                    MutFixed::new(layer)
+                })
+                .collect())
+        }
+    }
+
+    /// Waits until the condition holds, and returns false on timeout.
+    fn wait<F: Fn() -> bool>(cond: F) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !cond() {
+            if Instant::now() > deadline {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        true
+    }
+
     #[test]
     fn drop() {
         let profile = Profile::new();
@@ -460,4 +1755,95 @@ mod tests {
         assert_eq!(store.frames(100..0).len(), 0);
         assert_eq!(store.filtered_frames(0, 100..0).len(), 0);
     }
+
+    #[test]
+    fn set_profile() {
+        let mut store = Store::new(Profile::new(), TestCallback {});
+        store.set_filter(0, Filter::compile("wasm_test").ok());
+        store.set_input(0, TestInput { frames: 3 });
+        assert!(wait(|| store.len() == 3 && store.in_flight() == 0));
+        assert!(store.filtered_frames(0, 0..3).is_empty());
+
+        let mut profile = Profile::new();
+        profile
+            .load_library(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/decoder.wasm"
+            ))
+            .unwrap();
+        store.set_profile(profile, Redecode::All);
+        assert!(wait(|| store.filtered_frames(0, 0..3) == vec![0, 1, 2]));
+        assert_eq!(store.len(), 3);
+    }
+
+    #[test]
+    fn io_graph_cancel() {
+        let total = SCAN_CHUNK_SIZE * 2 + 1;
+        let mut store = Store::new(Profile::new(), TestCallback {});
+        store.set_input(0, TestInput { frames: total });
+        assert!(wait(|| store.len() == total && store.in_flight() == 0));
+
+        let view = store.view();
+        let graph = view.io_graph(1_000, &[None], &|_, _| {}, &CancelToken::new());
+        assert_eq!(
+            graph.series,
+            vec![vec![Bucket {
+                frames: total as u64,
+                bytes: total as u64 * 3,
+            }]]
+        );
+
+        // Cancelled during the first chunk, the graph covers only that chunk.
+        let cancel = CancelToken::new();
+        let calls = Cell::new(0);
+        let graph = view.io_graph(
+            1_000,
+            &[None],
+            &|_, _| {
+                calls.set(calls.get() + 1);
+                cancel.cancel();
+            },
+            &cancel,
+        );
+        assert_eq!(graph.series[0][0].frames, SCAN_CHUNK_SIZE as u64);
+        assert_eq!(calls.get(), 2);
+
+        let graph = view.io_graph(1_000, &[None], &|_, _| {}, &cancel);
+        assert_eq!(graph.series, vec![Vec::new()]);
+    }
+
+    #[test]
+    fn set_coloring_rules() {
+        let mut profile = Profile::new();
+        let mut store = Store::new(profile.clone(), TestCallback {});
+        profile
+            .set_coloring_rules(&[ColoringRule {
+                filter: "tcp".to_string(),
+                color: "red".to_string(),
+            }])
+            .unwrap();
+        store.set_coloring_rules(profile);
+        assert_eq!(store.len(), 0);
+    }
+
+    #[test]
+    fn redecode_changed() {
+        let ipv4 = Metadata {
+            triggers: vec![Trigger::new("eth.type", 0x0800)],
+            ..Metadata::default()
+        };
+        let udp = Metadata {
+            triggers: vec![Trigger::payload("@data:udp")],
+            ..Metadata::default()
+        };
+        assert_eq!(
+            Redecode::changed(&[ipv4, udp]),
+            Redecode::Triggers(vec![
+                Trigger::new("eth.type", 0x0800),
+                Trigger::payload("@data:udp"),
+            ])
+        );
+        assert_eq!(Redecode::changed(&[Metadata::default()]), Redecode::All);
+        assert_eq!(Redecode::changed(&[]), Redecode::None);
+    }
 }
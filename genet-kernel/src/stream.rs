@@ -0,0 +1,409 @@
+use array_vec::ArrayVec;
+use flow::{self, Flow, FlowTable};
+use frame::Frame;
+use genet_abi::{layer::Layer, token::Token, variant::Variant};
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+const HEX_LINE_LEN: usize = 16;
+
+/// Direction of a segment relative to the flow.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// From the source to the destination of the flow.
+    Forward,
+    Backward,
+}
+
+/// Rendering format of the stream data.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// The bytes as they are.
+    Raw,
+
+    /// The printable ASCII characters, and dots for the other bytes.
+    Ascii,
+
+    /// The hex dump with the offsets.
+    Hex,
+}
+
+/// Payload bytes sent in a direction, contributed by a frame.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Segment {
+    pub frame: u32,
+    pub direction: Direction,
+
+    /// The offset of the data in the direction.
+    pub offset: usize,
+    pub data: Vec<u8>,
+}
+
+/// The ordered payload bytes of a flow.
+#[derive(Serialize, Clone, Debug)]
+pub struct Stream {
+    pub flow: Flow,
+    pub segments: Vec<Segment>,
+}
+
+struct Packet {
+    frame: u32,
+    direction: Direction,
+    seq: Option<u32>,
+    data: Vec<u8>,
+}
+
+impl Stream {
+    /// Collects the stream which the frame belongs to.
+    ///
+    /// If the layer of the flow has the `<protocol>.seq` attribute,
+    /// the payloads are ordered by the sequence numbers
+    /// and the retransmitted bytes are dropped.
+    /// Otherwise, the payloads are ordered by the frames.
+    pub fn build(frame: &Frame, frames: &ArrayVec<Frame>, flows: &FlowTable) -> Option<Stream> {
+        let (index, protocol, _, _) = flow::endpoints(frame)?;
+        let id = Token::lookup(&format!("{}.flow", protocol))?;
+        let layer = &frame.layers()[index];
+        let flow = match layer.attr(id)?.try_get(layer).ok()? {
+            Variant::UInt64(id) => flows.get(id as u32)?,
+            _ => return None,
+        };
+
+        let seq = Token::lookup(&format!("{}.seq", protocol));
+        let packets = frames
+            .iter()
+            .filter_map(|frame| {
+                let (index, id, src, dst) = flow::endpoints(frame)?;
+                let direction = if id != protocol {
+                    return None;
+                } else if src == flow.src && dst == flow.dst {
+                    Direction::Forward
+                } else if src == flow.dst && dst == flow.src {
+                    Direction::Backward
+                } else {
+                    return None;
+                };
+                let layer = &frame.layers()[index];
+                Some(Packet {
+                    frame: frame.index(),
+                    direction,
+                    seq: seq.and_then(|seq| sequence(layer, seq)),
+                    data: layer
+                        .payloads()
+                        .first()
+                        .map_or_else(Vec::new, |payload| payload.data().to_vec()),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let segments = reassemble(packets);
+        Some(Stream { flow, segments })
+    }
+
+    /// Returns the concatenated data in the direction, or in both directions if None.
+    pub fn data(&self, direction: Option<Direction>) -> Vec<u8> {
+        self.segments
+            .iter()
+            .filter(|seg| direction.map_or(true, |dir| seg.direction == dir))
+            .flat_map(|seg| seg.data.iter().cloned())
+            .collect()
+    }
+
+    /// Writes the data in the direction, or in both directions if None.
+    pub fn write<W: Write>(
+        &self,
+        writer: W,
+        format: Format,
+        direction: Option<Direction>,
+    ) -> io::Result<()> {
+        let mut writer = writer;
+        for seg in self
+            .segments
+            .iter()
+            .filter(|seg| direction.map_or(true, |dir| seg.direction == dir))
+        {
+            match format {
+                Format::Raw => writer.write_all(&seg.data)?,
+                _ => writer.write_all(seg.render(format).as_bytes())?,
+            }
+        }
+        writer.flush()
+    }
+}
+
+impl Segment {
+    /// Renders the data in the format.
+    ///
+    /// The raw data is decoded as UTF-8 with the invalid sequences replaced.
+    /// In the hex dump, the backward segments are indented.
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Raw => String::from_utf8_lossy(&self.data).into_owned(),
+            Format::Ascii => self.data.iter().cloned().map(ascii).collect(),
+            Format::Hex => {
+                let indent = if self.direction == Direction::Backward {
+                    "    "
+                } else {
+                    ""
+                };
+                self.data
+                    .chunks(HEX_LINE_LEN)
+                    .enumerate()
+                    .map(|(i, line)| {
+                        let hex = line
+                            .iter()
+                            .map(|b| format!("{:02x}", b))
+                            .collect::<Vec<_>>()
+                            .join(" ");
+                        let text = line
+                            .iter()
+                            .map(|b| match ascii(*b) {
+                                '\r' | '\n' | '\t' => '.',
+                                c => c,
+                            })
+                            .collect::<String>();
+                        format!(
+                            "{}{:08x}  {:<width$}  {}\n",
+                            indent,
+                            self.offset + i * HEX_LINE_LEN,
+                            hex,
+                            text,
+                            width = HEX_LINE_LEN * 3 - 1
+                        )
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+fn ascii(b: u8) -> char {
+    match b {
+        b'\r' | b'\n' | b'\t' | 0x20..=0x7e => b as char,
+        _ => '.',
+    }
+}
+
+fn sequence(layer: &Layer, id: Token) -> Option<u32> {
+    match layer.attr(id)?.try_get(layer).ok()? {
+        Variant::UInt64(seq) => Some(seq as u32),
+        _ => None,
+    }
+}
+
+/// Pending out-of-order data in a direction.
+#[derive(Default)]
+struct Reassembler {
+    /// The sequence number of the offset 0.
+    base: Option<u32>,
+    next: u64,
+    pending: BTreeMap<u64, (u32, Vec<u8>)>,
+}
+
+impl Reassembler {
+    /// Returns the contiguous data from the next offset.
+    fn push(&mut self, frame: u32, start: u64, data: Vec<u8>) -> Vec<(u32, u64, Vec<u8>)> {
+        if start + data.len() as u64 > self.next {
+            let entry = self.pending.entry(start).or_insert((frame, Vec::new()));
+            if data.len() > entry.1.len() {
+                *entry = (frame, data);
+            }
+        }
+
+        let mut ready = Vec::new();
+        while let Some(start) = self.pending.keys().next().cloned() {
+            if start > self.next {
+                break;
+            }
+            let (frame, data) = self.pending.remove(&start).unwrap();
+            let skip = (self.next - start) as usize;
+            if skip < data.len() {
+                ready.push((frame, self.next, data[skip..].to_vec()));
+                self.next = start + data.len() as u64;
+            }
+        }
+        ready
+    }
+
+    /// Returns the remaining data leaving the gaps.
+    fn flush(&mut self) -> Vec<(u32, u64, Vec<u8>)> {
+        let mut ready = Vec::new();
+        while let Some(start) = self.pending.keys().next().cloned() {
+            self.next = self.next.max(start);
+            ready.extend(self.push(0, start, Vec::new()));
+        }
+        ready
+    }
+}
+
+fn reassemble(packets: Vec<Packet>) -> Vec<Segment> {
+    let mut forward = Reassembler::default();
+    let mut backward = Reassembler::default();
+
+    // The earliest sequence number in each direction is the offset 0.
+    for packet in &packets {
+        let state = match packet.direction {
+            Direction::Forward => &mut forward,
+            Direction::Backward => &mut backward,
+        };
+        if let Some(seq) = packet.seq.filter(|_| !packet.data.is_empty()) {
+            let base = state.base.get_or_insert(seq);
+            if (seq.wrapping_sub(*base) as i32) < 0 {
+                *base = seq;
+            }
+        }
+    }
+
+    let mut offsets = (0u64, 0u64);
+    let mut segments = Vec::new();
+    for packet in packets {
+        if packet.data.is_empty() {
+            continue;
+        }
+        let (state, offset) = match packet.direction {
+            Direction::Forward => (&mut forward, &mut offsets.0),
+            Direction::Backward => (&mut backward, &mut offsets.1),
+        };
+        let ready = match (packet.seq, state.base) {
+            (Some(seq), Some(base)) => {
+                let start = u64::from(seq.wrapping_sub(base));
+                state.push(packet.frame, start, packet.data)
+            }
+            _ => {
+                let start = *offset;
+                *offset += packet.data.len() as u64;
+                vec![(packet.frame, start, packet.data)]
+            }
+        };
+        segments.extend(ready.into_iter().map(|(frame, offset, data)| Segment {
+            frame,
+            direction: packet.direction,
+            offset: offset as usize,
+            data,
+        }));
+    }
+
+    for (state, direction) in vec![
+        (&mut forward, Direction::Forward),
+        (&mut backward, Direction::Backward),
+    ] {
+        segments.extend(
+            state
+                .flush()
+                .into_iter()
+                .map(|(frame, offset, data)| Segment {
+                    frame,
+                    direction,
+                    offset: offset as usize,
+                    data,
+                }),
+        );
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use array_vec::ArrayVec;
+    use flow::FlowTable;
+    use frame::Frame;
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        fixed::{Fixed, MutFixed},
+        layer::{Layer, LayerClass, Payload},
+        slice::ByteSlice,
+    };
+    use stream::{Direction, Format, Stream};
+
+    fn frame(index: u32, forward: bool, seq: u64, data: &[u8]) -> Frame {
+        let ip = Fixed::new(LayerClass::builder("57a").build());
+        let tcp = Fixed::new(LayerClass::builder("57b").build());
+        let ip_src = Fixed::new(AttrClass::builder("57a.src").build());
+        let ip_dst = Fixed::new(AttrClass::builder("57a.dst").build());
+        let tcp_src = Fixed::new(AttrClass::builder("57b.src").build());
+        let tcp_dst = Fixed::new(AttrClass::builder("57b.dst").build());
+        let tcp_seq = Fixed::new(AttrClass::builder("57b.seq").build());
+
+        let (addrs, ports) = if forward {
+            (([10, 0, 0, 1], [10, 0, 0, 2]), (1234, 80))
+        } else {
+            (([10, 0, 0, 2], [10, 0, 0, 1]), (80, 1234))
+        };
+        let mut root = Layer::new(ip, ByteSlice::new());
+        root.add_attr(
+            Attr::builder(ip_src)
+                .value(addrs.0.to_vec().into_boxed_slice())
+                .build(),
+        );
+        root.add_attr(
+            Attr::builder(ip_dst)
+                .value(addrs.1.to_vec().into_boxed_slice())
+                .build(),
+        );
+        let mut frame = Frame::new(index, MutFixed::new(root));
+
+        let mut layer = Layer::new(tcp, ByteSlice::new());
+        layer.add_attr(Attr::builder(tcp_src).value(ports.0 as u64).build());
+        layer.add_attr(Attr::builder(tcp_dst).value(ports.1 as u64).build());
+        layer.add_attr(Attr::builder(tcp_seq).value(seq).build());
+        layer.add_payload(Payload::new(ByteSlice::from(data.to_vec()), "@data:57b"));
+        let mut layers = frame.fetch_layers();
+        layers.push(MutFixed::new(layer));
+        frame.set_layers(layers);
+        frame
+    }
+
+    #[test]
+    fn build() {
+        let flows = FlowTable::new();
+        let mut frames = ArrayVec::new();
+        for mut frame in vec![
+            frame(0, true, 4_294_967_290, b""),
+            frame(1, true, 4_294_967_294, b"GE"),
+            frame(2, false, 100, b"HTTP"),
+            frame(3, true, 2, b"/ "),
+            frame(4, true, 0, b"T "),
+            frame(5, true, 4_294_967_294, b"GE"),
+            frame(6, true, 4, b"HTTP"),
+        ] {
+            flows.insert(&mut frame);
+            frames.push(frame);
+        }
+
+        let stream = Stream::build(frames.get(2).unwrap(), &frames, &flows).unwrap();
+        assert_eq!(stream.flow.packets, 7);
+        let segments = stream
+            .segments
+            .iter()
+            .map(|seg| (seg.frame, seg.direction, seg.offset, &seg.data[..]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            segments,
+            vec![
+                (1, Direction::Forward, 0, &b"GE"[..]),
+                (2, Direction::Backward, 0, &b"HTTP"[..]),
+                (4, Direction::Forward, 2, &b"T "[..]),
+                (3, Direction::Forward, 4, &b"/ "[..]),
+                (6, Direction::Forward, 6, &b"HTTP"[..]),
+            ]
+        );
+        assert_eq!(
+            stream.data(Some(Direction::Forward)),
+            b"GET / HTTP".to_vec()
+        );
+
+        let mut hex = Vec::new();
+        stream
+            .write(&mut hex, Format::Hex, Some(Direction::Backward))
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(hex).unwrap(),
+            format!("    00000000  {:<47}  HTTP\n", "48 54 54 50")
+        );
+        assert_eq!(stream.segments[0].render(Format::Ascii), "GE");
+    }
+}
@@ -0,0 +1,88 @@
+use array_vec::ArrayVec;
+use frame::Frame;
+use genet_abi::layer::LayerStack;
+use parking_lot::RwLock;
+use std::{ops::Range, sync::Arc};
+
+/// Cache of the one-line summaries of the stored frames.
+///
+/// The summaries are generated on demand and kept until the frames are decoded again.
+#[derive(Clone, Default, Debug)]
+pub struct SummaryCache {
+    // The inner None means that no layer in the frame provides the summary.
+    summaries: Arc<RwLock<Vec<Option<Option<String>>>>>,
+}
+
+impl SummaryCache {
+    pub fn new() -> SummaryCache {
+        SummaryCache::default()
+    }
+
+    /// Returns the summaries of the frames in the range.
+    pub fn get(&self, frames: &ArrayVec<Frame>, range: Range<usize>) -> Vec<Option<String>> {
+        let end = range.end.min(frames.len());
+        let start = range.start.min(end);
+        let mut summaries = self.summaries.write();
+        if summaries.len() < end {
+            summaries.resize(end, None);
+        }
+        (start..end)
+            .map(|index| {
+                summaries[index]
+                    .get_or_insert_with(|| summary(frames.get(index).unwrap()))
+                    .clone()
+            })
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        self.summaries.write().clear();
+    }
+}
+
+/// Returns the summary of the innermost layer providing one.
+pub fn summary(frame: &Frame) -> Option<String> {
+    let stack = LayerStack::from_layers(frame.layers(), frame.tree_indices());
+    frame
+        .layers()
+        .iter()
+        .rev()
+        .filter_map(|layer| layer.summary(&stack))
+        .next()
+}
+
+#[cfg(test)]
+mod tests {
+    use array_vec::ArrayVec;
+    use frame::Frame;
+    use genet_abi::{
+        fixed::{Fixed, MutFixed},
+        layer::{Layer, LayerClass},
+        slice::ByteSlice,
+    };
+    use summary::SummaryCache;
+
+    #[test]
+    fn get() {
+        let plain = Fixed::new(LayerClass::builder("summ4ry.plain").build());
+        let class = Fixed::new(
+            LayerClass::builder("summ4ry")
+                .summary(|layer, _| Some(format!("{} bytes", layer.data().len())))
+                .build(),
+        );
+        let mut frames = ArrayVec::new();
+        for index in 0..4 {
+            let data = vec![0u8; index as usize].into_boxed_slice();
+            let class = if index % 2 == 0 { &class } else { &plain };
+            let layer = Layer::new(class.clone(), ByteSlice::from(data));
+            frames.push(Frame::new(index, MutFixed::new(layer)));
+        }
+
+        let cache = SummaryCache::new();
+        assert_eq!(
+            cache.get(&frames, 1..10),
+            vec![None, Some("2 bytes".to_string()), None]
+        );
+        assert_eq!(cache.get(&frames, 0..1), vec![Some("0 bytes".to_string())]);
+    }
+}
@@ -0,0 +1,482 @@
+//! WebAssembly decoder host.
+//!
+//! A WebAssembly decoder module is executed in a sandboxed interpreter
+//! and adapted to the `DecoderBox` interface.
+//!
+//! The module must export the following items:
+//!
+//! - `memory`: the linear memory.
+//! - `genet_metadata() -> i64`: returns the location of the JSON-encoded
+//!   decoder metadata as `(ptr << 32) | len`.
+//! - `genet_alloc(len: i32) -> i32`: returns a buffer to receive the layer data.
+//!   The buffer is owned by the module and may be reused across calls.
+//! - `genet_decode(ptr: i32, len: i32) -> i32`: decodes the layer data and
+//!   returns `2` (done), `1` (skip), `3` (pending) or `0` (error).
+//!
+//! The module may import the following functions from the `genet` namespace.
+//! Strings are passed as `(ptr, len)` pairs of UTF-8 bytes.
+//!
+//! - `add_layer(id_ptr, id_len, offset, len)`: adds a child layer
+//!   covering the given range of the parent data.
+//! - `add_attr(id_ptr, id_len, offset, len, value: i64)`: adds an integer
+//!   attribute to the last added layer.
+//! - `add_bytes_attr(id_ptr, id_len, offset, len)`: adds an attribute
+//!   referring to the given range of the last added layer.
+//! - `add_payload(id_ptr, id_len, offset, len)`: adds a payload to the
+//!   last added layer.
+//!
+//! The module is instrumented with a gas counter. The workers charge the
+//! executed instructions to the decode budget, while the start function and
+//! `genet_metadata` are limited to `START_GAS` and `METADATA_GAS` instructions,
+//! so a runaway module is trapped instead of blocking the pipeline.
+
+use decoder::budget::{self, Budget, Scope};
+use fnv::FnvHashMap;
+use genet_abi::{
+    attr::{Attr, AttrClass},
+    context::Context,
+    decoder::{Decoder, Metadata, Status, Worker},
+    error::Error,
+    fixed::Fixed,
+    layer::{Layer, LayerClass, LayerStack, Parent, Payload},
+    result::Result,
+    slice::{ByteSlice, TryGet},
+};
+use parity_wasm::{self, elements};
+use pwasm_utils::{self, rules};
+use serde_json;
+use std::{fmt, fs, io, ops::Range, sync::Arc};
+use wasmi::{
+    self, Externals, FuncInstance, FuncRef, HostError, ImportsBuilder, MemoryRef, Module,
+    ModuleImportResolver, ModuleInstance, ModuleRef, RuntimeArgs, RuntimeValue, Signature, Trap,
+    TrapKind, ValueType,
+};
+
+const ADD_LAYER: usize = 0;
+const ADD_ATTR: usize = 1;
+const ADD_BYTES_ATTR: usize = 2;
+const ADD_PAYLOAD: usize = 3;
+const GAS: usize = 4;
+
+/// Instructions the start function may execute.
+const START_GAS: u64 = 100_000;
+
+/// Instructions `genet_metadata` may execute.
+const METADATA_GAS: u64 = 100_000;
+
+#[derive(Clone)]
+pub struct WasmDecoder {
+    /// The module instrumented with the gas counter.
+    code: Arc<Vec<u8>>,
+    metadata: Arc<Vec<u8>>,
+}
+
+impl WasmDecoder {
+    /// Loads a decoder from the WebAssembly module file.
+    pub fn load(path: &str) -> io::Result<WasmDecoder> {
+        let code = metered(&fs::read(path)?)?;
+        let instance = instantiate(&code).map_err(invalid_data)?;
+        let memory = memory(&instance).map_err(invalid_data)?;
+        let result = {
+            let _scope = Scope::enter(&Budget {
+                time: None,
+                instructions: Some(METADATA_GAS),
+            });
+            instance.invoke_export("genet_metadata", &[], &mut Metered)
+        };
+        let loc = match result {
+            Ok(Some(RuntimeValue::I64(loc))) => loc as u64,
+            Ok(_) => return Err(invalid_data("genet_metadata must return i64")),
+            Err(err) => return Err(invalid_data(err)),
+        };
+        let metadata = memory
+            .get((loc >> 32) as u32, (loc & 0xffff_ffff) as usize)
+            .map_err(invalid_data)?;
+        serde_json::from_slice::<Metadata>(&metadata)?;
+        Ok(WasmDecoder {
+            code: Arc::new(code),
+            metadata: Arc::new(metadata),
+        })
+    }
+}
+
+impl Decoder for WasmDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(WasmWorker {
+            instance: instantiate(&self.code).map_err(|err| err.to_string()),
+            classes: ClassCache::default(),
+        })
+    }
+
+    fn metadata(&self) -> Metadata {
+        serde_json::from_slice(&self.metadata).unwrap()
+    }
+}
+
+struct WasmWorker {
+    instance: ::std::result::Result<ModuleRef, String>,
+    classes: ClassCache,
+}
+
+impl Worker for WasmWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let instance = match self.instance {
+            Ok(ref instance) => instance,
+            Err(ref err) => return Err(Box::new(Error::new(err))),
+        };
+        let mut host = Host {
+            memory: memory(instance)?,
+            data: parent.data(),
+            layers: Vec::new(),
+            classes: &mut self.classes,
+        };
+        let ptr = match instance.invoke_export(
+            "genet_alloc",
+            &[RuntimeValue::I32(host.data.len() as i32)],
+            &mut host,
+        )? {
+            Some(RuntimeValue::I32(ptr)) => ptr as u32,
+            _ => return Err(Box::new(Error::new("genet_alloc must return i32"))),
+        };
+        host.memory.set(ptr, &host.data)?;
+
+        let status = instance.invoke_export(
+            "genet_decode",
+            &[
+                RuntimeValue::I32(ptr as i32),
+                RuntimeValue::I32(host.data.len() as i32),
+            ],
+            &mut host,
+        )?;
+        for layer in host.layers {
+            parent.add_child(layer);
+        }
+        match status {
+            Some(RuntimeValue::I32(2)) => Ok(Status::Done),
+            Some(RuntimeValue::I32(1)) => Ok(Status::Skip),
+            Some(RuntimeValue::I32(3)) => Ok(Status::Pending),
+            _ => Err(Box::new(Error::new("failed to decode the layer"))),
+        }
+    }
+}
+
+#[derive(Default)]
+struct ClassCache {
+    layers: FnvHashMap<String, Fixed<LayerClass>>,
+    attrs: FnvHashMap<String, Fixed<AttrClass>>,
+}
+
+impl ClassCache {
+    fn layer(&mut self, id: String) -> Fixed<LayerClass> {
+        if let Some(class) = self.layers.get(&id) {
+            return class.clone();
+        }
+        let class = Fixed::new(LayerClass::builder(id.as_str()).build());
+        self.layers.insert(id, class.clone());
+        class
+    }
+
+    fn attr(&mut self, id: String) -> Fixed<AttrClass> {
+        if let Some(class) = self.attrs.get(&id) {
+            return class.clone();
+        }
+        let class = Fixed::new(AttrClass::builder(id.as_str()).build());
+        self.attrs.insert(id, class.clone());
+        class
+    }
+}
+
+struct Host<'a> {
+    memory: MemoryRef,
+    data: ByteSlice,
+    layers: Vec<Layer>,
+    classes: &'a mut ClassCache,
+}
+
+impl<'a> Host<'a> {
+    fn string(&self, args: &RuntimeArgs) -> ::std::result::Result<String, Trap> {
+        let ptr: u32 = args.nth_checked(0)?;
+        let len: u32 = args.nth_checked(1)?;
+        let bytes = self
+            .memory
+            .get(ptr, len as usize)
+            .map_err(|_| Trap::new(TrapKind::MemoryAccessOutOfBounds))?;
+        String::from_utf8(bytes).map_err(|_| Trap::new(TrapKind::Unreachable))
+    }
+
+    fn range(
+        args: &RuntimeArgs,
+        data: &ByteSlice,
+    ) -> ::std::result::Result<(Range<usize>, ByteSlice), Trap> {
+        let offset: u32 = args.nth_checked(2)?;
+        let len: u32 = args.nth_checked(3)?;
+        let range = offset as usize..offset as usize + len as usize;
+        let slice = data
+            .try_get(range.clone())
+            .map_err(|_| Trap::new(TrapKind::MemoryAccessOutOfBounds))?;
+        Ok((range, slice))
+    }
+
+    fn last_layer(&mut self) -> ::std::result::Result<&mut Layer, Trap> {
+        self.layers
+            .last_mut()
+            .ok_or_else(|| Trap::new(TrapKind::Unreachable))
+    }
+}
+
+impl<'a> Externals for Host<'a> {
+    fn invoke_index(
+        &mut self,
+        index: usize,
+        args: RuntimeArgs,
+    ) -> ::std::result::Result<Option<RuntimeValue>, Trap> {
+        if index == GAS {
+            return charge(&args);
+        }
+        let id = self.string(&args)?;
+        match index {
+            ADD_LAYER => {
+                let (_, data) = Self::range(&args, &self.data)?;
+                let class = self.classes.layer(id);
+                self.layers.push(Layer::new(class, data));
+            }
+            ADD_ATTR => {
+                let value: i64 = args.nth_checked(4)?;
+                let class = self.classes.attr(id);
+                let layer = self.last_layer()?;
+                let (range, _) = Self::range(&args, &layer.data())?;
+                layer.add_attr(Attr::builder(class).range(range).value(value).build());
+            }
+            ADD_BYTES_ATTR => {
+                let class = self.classes.attr(id);
+                let layer = self.last_layer()?;
+                let (range, data) = Self::range(&args, &layer.data())?;
+                layer.add_attr(Attr::builder(class).range(range).value(data).build());
+            }
+            ADD_PAYLOAD => {
+                let layer = self.last_layer()?;
+                let (_, data) = Self::range(&args, &layer.data())?;
+                layer.add_payload(Payload::new(data, id.as_str()));
+            }
+            _ => return Err(Trap::new(TrapKind::Unreachable)),
+        }
+        Ok(None)
+    }
+}
+
+/// Charges the gas to the budget of the current thread.
+fn charge(args: &RuntimeArgs) -> ::std::result::Result<Option<RuntimeValue>, Trap> {
+    let gas: u32 = args.nth_checked(0)?;
+    if !budget::consume(u64::from(gas)) {
+        return Err(Trap::new(TrapKind::Host(Box::new(BudgetExceeded))));
+    }
+    Ok(None)
+}
+
+/// Externals for the start function and `genet_metadata`, which may not call the host functions.
+struct Metered;
+
+impl Externals for Metered {
+    fn invoke_index(
+        &mut self,
+        index: usize,
+        args: RuntimeArgs,
+    ) -> ::std::result::Result<Option<RuntimeValue>, Trap> {
+        match index {
+            GAS => charge(&args),
+            _ => Err(Trap::new(TrapKind::Unreachable)),
+        }
+    }
+}
+
+/// Resolves the host functions in the `genet` namespace.
+struct Resolver;
+
+impl ModuleImportResolver for Resolver {
+    fn resolve_func(
+        &self,
+        field_name: &str,
+        _signature: &Signature,
+    ) -> ::std::result::Result<FuncRef, wasmi::Error> {
+        let (index, params): (usize, &'static [ValueType]) = match field_name {
+            "add_layer" => (ADD_LAYER, &[ValueType::I32; 4]),
+            "add_attr" => (
+                ADD_ATTR,
+                &[
+                    ValueType::I32,
+                    ValueType::I32,
+                    ValueType::I32,
+                    ValueType::I32,
+                    ValueType::I64,
+                ],
+            ),
+            "add_bytes_attr" => (ADD_BYTES_ATTR, &[ValueType::I32; 4]),
+            "add_payload" => (ADD_PAYLOAD, &[ValueType::I32; 4]),
+            _ => {
+                return Err(wasmi::Error::Instantiation(format!(
+                    "unknown import: {}",
+                    field_name
+                )))
+            }
+        };
+        Ok(FuncInstance::alloc_host(
+            Signature::new(params, None),
+            index,
+        ))
+    }
+}
+
+/// Resolves `env.gas`, which is imported by the injected gas counter.
+struct GasResolver;
+
+impl ModuleImportResolver for GasResolver {
+    fn resolve_func(
+        &self,
+        field_name: &str,
+        _signature: &Signature,
+    ) -> ::std::result::Result<FuncRef, wasmi::Error> {
+        if field_name != "gas" {
+            return Err(wasmi::Error::Instantiation(format!(
+                "unknown import: {}",
+                field_name
+            )));
+        }
+        Ok(FuncInstance::alloc_host(
+            Signature::new(&[ValueType::I32][..], None),
+            GAS,
+        ))
+    }
+}
+
+fn instantiate(code: &[u8]) -> ::std::result::Result<ModuleRef, wasmi::Error> {
+    let module = Module::from_buffer(code)?;
+    let imports = ImportsBuilder::new()
+        .with_resolver("genet", &Resolver)
+        .with_resolver("env", &GasResolver);
+    let _scope = Scope::enter(&Budget {
+        time: None,
+        instructions: Some(START_GAS),
+    });
+    ModuleInstance::new(&module, &imports)?
+        .run_start(&mut Metered)
+        .map_err(wasmi::Error::Trap)
+}
+
+/// Injects the calls to `env.gas` charging the instructions of each block.
+fn metered(code: &[u8]) -> io::Result<Vec<u8>> {
+    let module = parity_wasm::deserialize_buffer::<elements::Module>(code).map_err(invalid_data)?;
+    let module = pwasm_utils::inject_gas_counter(module, &rules::Set::default())
+        .map_err(|_| invalid_data("failed to inject the gas counter"))?;
+    parity_wasm::serialize(module).map_err(invalid_data)
+}
+
+fn memory(instance: &ModuleRef) -> ::std::result::Result<MemoryRef, wasmi::Error> {
+    instance
+        .export_by_name("memory")
+        .and_then(|ext| ext.as_memory().cloned())
+        .ok_or_else(|| wasmi::Error::Instantiation("memory is not exported".to_string()))
+}
+
+#[derive(Debug)]
+struct BudgetExceeded;
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "decode budget exceeded")
+    }
+}
+
+impl HostError for BudgetExceeded {}
+
+fn invalid_data<E: ToString>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use decoder::budget::{Budget, Scope};
+    use fnv::FnvHashMap;
+    use genet_abi::{
+        context::Context,
+        decoder::{Decoder, Status},
+        fixed::Fixed,
+        layer::{Layer, LayerClass, LayerStack, Parent},
+        result::Result,
+        slice::ByteSlice,
+        token::Token,
+    };
+    use std::io;
+    use wasm::WasmDecoder;
+
+    fn fixture(name: &str) -> String {
+        format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    /// Decodes the data with the fixture and returns the status and the number of children.
+    fn decode(data: &'static [u8]) -> (Result<Status>, usize) {
+        let decoder = WasmDecoder::load(&fixture("decoder.wasm")).unwrap();
+        let mut ctx = Context::new(FnvHashMap::default());
+        let mut worker = decoder.new_worker(&ctx);
+        let class = Fixed::new(LayerClass::builder("[wasm]").build());
+        let mut layer = Layer::new(class, ByteSlice::from(data));
+        let mut parent = Parent::from_mut_ref(&mut layer);
+        let status = worker.decode(&mut ctx, &LayerStack::from_layers(&[], &[]), &mut parent);
+        let children = parent.children();
+        if let Some(child) = children.first() {
+            let child = unsafe { &**child };
+            assert_eq!(child.id(), Token::from("wasm_test"));
+            assert_eq!(child.data().len(), data.len());
+        }
+        (status, children.len())
+    }
+
+    #[test]
+    fn load_invalid() {
+        let err = WasmDecoder::load(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"))
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn load() {
+        let decoder = WasmDecoder::load(&fixture("decoder.wasm")).unwrap();
+        assert_eq!(decoder.metadata().id, "wasm-test");
+    }
+
+    #[test]
+    fn load_spin() {
+        let err = WasmDecoder::load(&fixture("spin.wasm")).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn load_start() {
+        let err = WasmDecoder::load(&fixture("start.wasm")).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn decode_layer() {
+        let (status, children) = decode(&[1, 2, 3]);
+        assert_eq!(status.unwrap(), Status::Done);
+        assert_eq!(children, 1);
+    }
+
+    #[test]
+    fn decode_gas_limit() {
+        let scope = Scope::enter(&Budget {
+            time: None,
+            instructions: Some(10_000),
+        });
+        let (status, children) = decode(&[0xff]);
+        assert!(status.is_err());
+        assert_eq!(children, 0);
+        assert!(scope.exceeded());
+    }
+}
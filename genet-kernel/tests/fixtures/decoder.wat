@@ -0,0 +1,18 @@
+;; A decoder adding a `wasm_test` layer over the whole data.
+;; A layer starting with 0xff makes it loop forever.
+;;
+;; Assemble with `wat2wasm decoder.wat`.
+(module
+  (import "genet" "add_layer" (func $add_layer (param i32 i32 i32 i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "{\"id\":\"wasm-test\"}")
+  (data (i32.const 256) "wasm_test")
+  (func (export "genet_metadata") (result i64)
+    i64.const 18)
+  (func (export "genet_alloc") (param i32) (result i32)
+    i32.const 1024)
+  (func (export "genet_decode") (param $ptr i32) (param $len i32) (result i32)
+    (if (i32.eq (i32.load8_u (get_local $ptr)) (i32.const 255))
+      (then (loop $spin (br $spin))))
+    (call $add_layer (i32.const 256) (i32.const 9) (i32.const 0) (get_local $len))
+    i32.const 2))
@@ -0,0 +1,8 @@
+;; A module whose metadata never returns.
+;;
+;; Assemble with `wat2wasm spin.wat`.
+(module
+  (memory (export "memory") 1)
+  (func (export "genet_metadata") (result i64)
+    (loop $spin (br $spin))
+    i64.const 0))
@@ -0,0 +1,10 @@
+;; A module whose start function never returns.
+;;
+;; Assemble with `wat2wasm start.wat`.
+(module
+  (memory (export "memory") 1)
+  (func $spin
+    (loop $spin (br $spin)))
+  (func (export "genet_metadata") (result i64)
+    i64.const 0)
+  (start $spin))
@@ -5,6 +5,7 @@ use genet_abi::{
 use libc;
 use std::{
     cell::RefCell, convert::AsRef, ffi::CString, mem, ops::Deref, os::raw::c_char, ptr, rc::Rc,
+    slice,
 };
 
 thread_local! {
@@ -402,6 +403,37 @@ impl Env {
         }
     }
 
+    /// Returns the bytes of the Uint8Array, e.g. a Buffer, or an error for the other types.
+    pub fn get_uint8array(&self, value: &Value) -> Result<&[u8]> {
+        unsafe {
+            let mut array_type: u32 = mem::uninitialized();
+            let mut data: *const libc::c_void = mem::uninitialized();
+            let mut buffer: *const Value = mem::uninitialized();
+            let mut len: usize = mem::uninitialized();
+            let mut offset: usize = mem::uninitialized();
+            match napi_get_typedarray_info(
+                self,
+                value,
+                &mut array_type,
+                &mut len,
+                &mut data,
+                &mut buffer,
+                &mut offset,
+            ) {
+                // `data` already points at the byte offset in the ArrayBuffer.
+                Status::Ok if array_type == TypedArrayType::Uint8Array as u32 => {
+                    if len == 0 {
+                        Ok(&[])
+                    } else {
+                        Ok(slice::from_raw_parts(data as *const u8, len))
+                    }
+                }
+                Status::Ok => Err(Status::InvalidArg),
+                s => Err(s),
+            }
+        }
+    }
+
     pub fn get_value_double(&self, value: &Value) -> Result<f64> {
         unsafe {
             let mut result: f64 = mem::uninitialized();
@@ -535,6 +567,16 @@ impl Env {
         }
     }
 
+    pub fn get_element<'env>(&self, object: &Value, index: u32) -> Result<&'env Value> {
+        unsafe {
+            let mut result: *const Value = mem::uninitialized();
+            match napi_get_element(self, object, index, &mut result) {
+                Status::Ok => Ok(&*result),
+                s => Err(s),
+            }
+        }
+    }
+
     pub fn set_named_property(&self, object: &Value, utf8name: &str, value: &Value) -> Result<()> {
         unsafe {
             let name = CString::new(utf8name).unwrap();
@@ -1120,6 +1162,13 @@ extern "C" {
         value: *const Value,
     ) -> Status;
 
+    fn napi_get_element(
+        env: *const Env,
+        object: *const Value,
+        index: u32,
+        result: *mut *const Value,
+    ) -> Status;
+
     fn napi_set_named_property(
         env: *const Env,
         object: *const Value,
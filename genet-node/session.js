@@ -57,6 +57,24 @@ class Frame {
     return fistChild(this.root)
   }
 
+  // The color tag of the first matching coloring rule, or null.
+  get color () {
+    return this._frame.color
+  }
+
+  get marked () {
+    return this._frame.marked
+  }
+
+  get comment () {
+    return this._frame.comment
+  }
+
+  // The decoders offered the layers, recorded if profile.trace is enabled.
+  get trace () {
+    return JSON.parse(this._frame.trace)
+  }
+
   query (id) {
     return this._frame.query(id)
   }
@@ -82,10 +100,18 @@ class Session extends EventEmitter {
         case 'error':
           this.emit('error', event.error)
           break
+        case 'progress':
+          this.emit('progress', event)
+          break
+        case 'task':
+          this._completeTask(event)
+          break
         default:
       }
       this.emit('update', event)
     })
+    this._tasks = new Map()
+    this._filterTasks = new Map()
     this._streams = []
     this._streamReaders = new Set()
     this._status = {
@@ -110,17 +136,152 @@ class Session extends EventEmitter {
     return this._sess.filteredFrames(Token.get(id), start, end)
   }
 
+  // The columns provided by the packages: [{ id, name, description, value }, ...]
+  get columns () {
+    return this.profile.columns
+  }
+
+  // Returns the one-line summaries of the frames in the range.
+  // Frames without a summary get null.
+  summaries (start, end) {
+    return this._sess.summaries(start, end)
+  }
+
+  // Attaches the mark and the comment to the frame.
+  // The note is visible to the filters as `frame.marked` and `frame.comment`.
+  setNote (index, { marked = false, comment = '' } = {}) {
+    this._sess.setNote(index, marked, comment)
+  }
+
+  markedFrames () {
+    return this._sess.markedFrames()
+  }
+
+  // Returns the decoders offered the layers of the frame, with their results and timing.
+  // The trace is recorded only if profile.trace is enabled.
+  trace (index) {
+    const json = this._sess.trace(index)
+    return json === null ? null : JSON.parse(json)
+  }
+
+  // Compares the decoded attributes of the frames.
+  // Returns [{ type: 'added' | 'removed' | 'changed', path, id, index, old, new }, ...],
+  // or null if either of the frames does not exist.
+  diff (a, b) {
+    return this._sess.diff(a, b)
+  }
+
+  // Exports the bytes of the frame, or of the range such as the frameRange of an attribute.
+  // The format is 'hex' (hexdump -C), 'raw', 'base64' or 'c' (a C array literal).
+  // Returns a Buffer for 'raw' and a string for the others, or null if the frame does not exist.
+  exportBytes (index, format = 'hex', [start, end] = [0, 0xffffffff]) {
+    const data = this._sess.exportBytes(index, format, start, end)
+    if (data === null) {
+      return null
+    }
+    const buf = Buffer.from(data)
+    return format === 'raw' ? buf : buf.toString()
+  }
+
+  // Decodes the frame again with tracing enabled, leaving the stored frame untouched.
+  // The decoders start without the states built from the previous frames.
+  decodeFrame (index) {
+    const frame = this._sess.decodeFrame(index)
+    return frame === null ? null : new Frame(frame)
+  }
+
+  // Returns the values of the column for the frames in the range.
+  columnValues (id, start, end) {
+    return this._sess.columnValues(id, start, end)
+  }
+
   get status () {
     return this._status
   }
 
+  _task (id) {
+    return new Promise((res, rej) => {
+      this._tasks.set(id, { res, rej })
+    })
+  }
+
+  _completeTask (event) {
+    const task = this._tasks.get(event.id)
+    if (task) {
+      this._tasks.delete(event.id)
+      if (event.error === null) {
+        task.res(event.result)
+      } else {
+        task.rej(new Error(event.error))
+      }
+    }
+  }
+
+  // Resolves when the filter has been tested against all the frames received so far.
   setFilter (id, filter = '') {
-    this._sess.setFilter(Token.get(id), filter)
+    const token = Token.get(id)
+    this._sess.setFilter(token, filter)
     if (filter === '') {
       Reflect.deleteProperty(this._status.filters, id)
+      return Promise.resolve()
     }
+    return new Promise((res) => {
+      const listener = (event) => {
+        if (event.kind === 'filter' && event.id === token &&
+          event.done >= event.total) {
+          this.removeListener('progress', listener)
+          res()
+        }
+      }
+      const previous = this._filterTasks.get(token)
+      if (previous) {
+        this.removeListener('progress', previous)
+      }
+      this._filterTasks.set(token, listener)
+      this.on('progress', listener)
+    })
+  }
+
+  // Resolves to the indices of the frames matching the filter.
+  search (filter) {
+    return this._task(this._sess.search(filter))
+  }
+
+  // Resolves to the first match of the query, or null if no frame matches.
+  // The query is { attr, value } with the value written as a filter literal,
+  // or { bytes } with a Uint8Array or a string, or an array of them to find any,
+  // in the frame data and the payloads.
+  // The search starts from the frame `from` and moves forward, or backward if `backward` is true;
+  // the next or previous match is found by starting from the adjacent frame.
+  find (query, { from = 0, backward = false } = {}) {
+    if (typeof query.attr === 'string') {
+      return this._task(this._sess.findAttr(query.attr, String(query.value), from, backward))
+    }
+    const patterns = [].concat(query.bytes).map((bytes) =>
+      (typeof bytes === 'string' ? Buffer.from(bytes) : bytes))
+    return this._task(this._sess.findBytes(patterns, from, backward))
   }
 
+  // Aborts the operation reporting the progress. The kind is 'filter', 'output' or 'task'.
+  // A cancelled filter is removed, and a cancelled output or task fails with an error.
+  cancel (kind, id) {
+    if (kind === 'filter') {
+      const token = Token.get(id)
+      const listener = this._filterTasks.get(token)
+      if (listener) {
+        this.removeListener('progress', listener)
+        this._filterTasks.delete(token)
+      }
+      this.setFilter(id, '')
+    } else {
+      this._sess.cancel(kind, id)
+    }
+  }
+
+  // Aborts decoding the frames again after the decoders have changed.
+  cancelRedecode () {
+    this._sess.cancelRedecode()
+  }
   createReader (id, arg = {}) {
     const handle = this._sess.createReader(id, JSON.stringify(arg))
     if (handle === 0) {
@@ -144,8 +305,12 @@ class Session extends EventEmitter {
   }
 
   async createWriter (id, arg = {}, filter = '') {
+    // { id } selects the frames currently matching the filter set by setFilter.
+    const selection = (typeof filter === 'object')
+      ? Token.get(filter.id)
+      : filter
     const handle = this._sess.createWriter(
-      id, JSON.stringify(arg), filter)
+      id, JSON.stringify(arg), selection)
     if (handle === 0) {
       throw new Error(`failed to invoke writer: ${id}`)
     }
@@ -192,6 +357,33 @@ class Session extends EventEmitter {
     this._status.stream = false
   }
 
+  save (file) {
+    return this._task(this._sess.saveAsync(file))
+  }
+
+  load (file) {
+    this._sess.load(file)
+  }
+
+  setNamedFilter (name, filter) {
+    this._sess.setNamedFilter(name, filter)
+  }
+
+  // Rules are evaluated in order: [{ filter, color }, ...]
+  setColoringRules (rules) {
+    this._sess.setColoringRules(JSON.stringify(rules))
+  }
+
+  completeFilter (filter, cursor = filter.length) {
+    // The cursor is a byte offset on the native side.
+    const offset = Buffer.byteLength(filter.slice(0, cursor))
+    const { start, end, candidates } =
+      JSON.parse(this._sess.completeFilter(filter, offset))
+    const index = (byte) => Buffer.from(filter).slice(0, byte)
+      .toString().length
+    return { start: index(start), end: index(end), candidates }
+  }
+
   get length () {
     return this._sess.length
   }
@@ -199,6 +391,34 @@ class Session extends EventEmitter {
   get profile () {
     return JSON.parse(this._sess.profile)
   }
+
+  get flows () {
+    return JSON.parse(this._sess.flows)
+  }
+
+  endpoints (order = 'bytes', limit = 0) {
+    return this._task(this._sess.endpointsAsync(order, limit))
+  }
+
+  ioGraph (interval, filters = ['']) {
+    // The interval is given in milliseconds.
+    return this._task(this._sess.ioGraphAsync(
+      Math.round(interval * 1e6), JSON.stringify(filters)))
+  }
+
+  stream (index, format = 'ascii') {
+    return this._task(this._sess.streamAsync(index, format))
+  }
+
+  exportStream (index, file, format = 'raw', direction = '') {
+    return this._task(
+      this._sess.exportStreamAsync(index, file, format, direction))
+  }
+
+  setIndex (attr, kind = 'hash') {
+    // Pass null to remove the index.
+    this._sess.setIndex(attr, kind || '')
+  }
 }
 
 class Profile extends native.Session.Profile { }
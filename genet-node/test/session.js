@@ -112,6 +112,24 @@ describe('Session', () => {
             sess.close()
         })
     })
+  describe('#find', () => {
+        it('should accept a Buffer sliced from the pool', function() {
+            const profile = new Profile()
+            const sess = new Session(profile)
+            const bytes = Buffer.from('xxGET').slice(2)
+            assert.notEqual(bytes.byteOffset, 0)
+            return sess.find({ bytes }).then((index) => {
+                assert.equal(index, null)
+                sess.close()
+            })
+        })
+        it('should throw for non-byte arrays', () => {
+            const profile = new Profile()
+            const sess = new Session(profile)
+            assert.throws(() => sess.find({ bytes: new Uint16Array([0x4547]) }), TypeError)
+            sess.close()
+        })
+    })
 
   /*
     Describe('#setFilter', function() {
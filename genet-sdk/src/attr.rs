@@ -1,5 +1,93 @@
 //! Attribute values.
 //!
 //! Type Attr represents an attribute of a Layer.
+//!
+//! # Deriving header attributes
+//!
+//! `#[derive(Attr)]` generates the header attributes of a layer from a struct
+//! describing the wire format. The fields are laid out in order and
+//! the attribute IDs are made from the `id` of the struct and
+//! the field names in camelCase.
+//!
+//! ```
+//! #[macro_use]
+//! extern crate genet_sdk;
+//!
+//! use genet_sdk::{attr::AttrStruct, prelude::*};
+//!
+//! #[derive(Attr)]
+//! #[attr(id = "ipv4")]
+//! struct Ipv4Header {
+//!     #[attr(bits = 4)]
+//!     version: u8,
+//!     #[attr(bits = 4)]
+//!     header_length: u8,
+//!     tos: u8,
+//!     total_length: u16,
+//!     #[attr(skip = 4)]
+//!     ttl: u8,
+//!     #[attr(typ = "@enum")]
+//!     protocol: u8,
+//!     checksum: u16,
+//!     #[attr(size = 4, typ = "@ipv4:addr")]
+//!     src: ByteSlice,
+//!     #[attr(size = 4, typ = "@ipv4:addr")]
+//!     dst: ByteSlice,
+//! }
+//!
+//! def_layer_class!(IPV4_CLASS, "ipv4", headers: Ipv4Header::headers());
+//!
+//! # fn main() {
+//! let data = ByteSlice::from(
+//!     &[
+//!         0x45, 0x00, 0x00, 0x3c, 0x1c, 0x46, 0x40, 0x00, 0x40, 0x06, 0xb1, 0xe6, 0xac, 0x10,
+//!         0x0a, 0x63, 0xac, 0x10, 0x0a, 0x0c,
+//!     ][..],
+//! );
+//! let layer = Layer::new(&IPV4_CLASS, data);
+//! let header = Ipv4Header::parse(&layer).unwrap();
+//! assert_eq!(Ipv4Header::SIZE, 20);
+//! assert_eq!((header.version, header.header_length), (4, 5));
+//! assert_eq!(header.total_length, 60);
+//! assert_eq!(header.protocol, 6);
+//! assert_eq!(&header.dst[..], &[172, 16, 10, 12]);
+//! assert_eq!(Ipv4Header::ttl(&layer).unwrap(), 64);
+//! assert!(layer.attr(token!("ipv4.headerLength")).is_some());
+//! # }
+//! ```
+//!
+//! The struct takes the following options:
+//!
+//! - `id` - The prefix of the attribute IDs. Required.
+//! - `endian` - `"big"` (default) or `"little"`.
+//!
+//! The fields take the following options:
+//!
+//! - `id` - The attribute ID. Defaults to `<prefix>.<fieldName>`.
+//! - `typ`, `name`, `description` - Passed to the `AttrClass` builder.
+//! - `cast` - The cast expression, e.g. `"cast::UInt8().map(|v| v * 4)"`.
+//!   Defaults to the cast for the field type.
+//! - `size` - The size in bytes. Required unless the field is a primitive number.
+//! - `bits` - The size in bits of a bit field.
+//! - `skip` - The number of bytes skipped before the field.
+//! - `endian` - Overrides the byte order of the struct.
 
 pub use genet_abi::attr::{Attr, AttrClass, AttrClassBuilder};
+pub use genet_derive::Attr;
+
+use layer::Layer;
+use result::Result;
+
+/// A struct describing the header attributes of a layer.
+///
+/// This trait is implemented by `#[derive(Attr)]`.
+pub trait AttrStruct: Sized {
+    /// The size of the header in bytes.
+    const SIZE: usize;
+
+    /// Returns the header attributes.
+    fn headers() -> &'static [Attr];
+
+    /// Reads all the fields from the layer.
+    fn parse(layer: &Layer) -> Result<Self>;
+}
@@ -5,7 +5,10 @@ use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
 pub use genet_abi::cast::{Cast, Map, Typed};
 use genet_abi::slice::TryGet;
 use slice;
-use std::io::{Cursor, Error, ErrorKind, Result};
+use std::{
+    io::{Cursor, Error, ErrorKind, Result},
+    net,
+};
 
 /// Cast for 8bit unsigned integer.
 #[derive(Clone)]
@@ -250,3 +253,39 @@ impl Typed for ByteSlice {
         data.try_get(attr.range())
     }
 }
+
+/// Cast for IPv6 address.
+#[derive(Clone)]
+pub struct Ipv6Addr();
+
+impl Typed for Ipv6Addr {
+    type Output = net::Ipv6Addr;
+
+    fn cast(&self, attr: &Attr, data: &slice::ByteSlice) -> Result<net::Ipv6Addr> {
+        let data = data.try_get(attr.range())?;
+        if data.len() != 16 {
+            return Err(Error::new(ErrorKind::InvalidData, "Invalid IPv6 address"));
+        }
+        let mut octets = [0u8; 16];
+        octets.copy_from_slice(&data);
+        Ok(net::Ipv6Addr::from(octets))
+    }
+}
+
+/// Cast for MAC address.
+#[derive(Clone)]
+pub struct MacAddr();
+
+impl Typed for MacAddr {
+    type Output = [u8; 6];
+
+    fn cast(&self, attr: &Attr, data: &slice::ByteSlice) -> Result<[u8; 6]> {
+        let data = data.try_get(attr.range())?;
+        if data.len() != 6 {
+            return Err(Error::new(ErrorKind::InvalidData, "Invalid MAC address"));
+        }
+        let mut octets = [0u8; 6];
+        octets.copy_from_slice(&data);
+        Ok(octets)
+    }
+}
@@ -0,0 +1,185 @@
+//! Checksum validation.
+//!
+//! This module provides the Internet checksum (RFC 1071) with the IPv4 and IPv6
+//! pseudo-headers used by TCP and UDP, and the CRC-32 variants used by link and
+//! transport protocols.
+//!
+//! A decoder reports a mismatch as an error annotation created by
+//! `bad_checksum`, referring to the checksum attribute.
+//! Validation should be skipped unless `is_enabled` returns true, because
+//! checksum offloading makes every outgoing packet look corrupted in captures.
+//!
+//! # Example
+//!
+//! ```
+//! use genet_sdk::checksum;
+//!
+//! let header = [
+//!     0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0xb8, 0x61, 0xc0, 0xa8, 0x00,
+//!     0x01, 0xc0, 0xa8, 0x00, 0xc7,
+//! ];
+//! assert_eq!(checksum::internet(&header), 0);
+//! ```
+
+use context::Context;
+use layer::{Annotation, Severity};
+use token::Token;
+
+/// The config key to enable checksum validation.
+///
+/// The value is a boolean and defaults to true.
+pub const CONFIG_KEY: &str = "_.decoder.validateChecksums";
+
+/// Returns true if checksum validation is enabled in the current session.
+pub fn is_enabled(ctx: &Context) -> bool {
+    ctx.config::<bool>(CONFIG_KEY).unwrap_or(true)
+}
+
+/// Creates an error annotation for a checksum mismatch.
+pub fn bad_checksum<T: Into<Token>>(attr: T, expected: u64) -> Annotation {
+    Annotation::new(
+        Severity::Error,
+        &format!("bad checksum (expected 0x{:x})", expected),
+    )
+    .with_attr(attr)
+}
+
+/// An incremental Internet checksum.
+#[derive(Debug, Default, Clone)]
+pub struct Internet {
+    sum: u64,
+    odd: bool,
+}
+
+impl Internet {
+    /// Creates a new Internet checksum.
+    pub fn new() -> Internet {
+        Internet::default()
+    }
+
+    /// Creates a new Internet checksum initialized with the IPv4 pseudo-header.
+    pub fn ipv4_pseudo_header(src: &[u8], dst: &[u8], protocol: u8, len: u16) -> Internet {
+        let mut sum = Internet::new();
+        sum.update(src);
+        sum.update(dst);
+        sum.update(&[0, protocol, (len >> 8) as u8, len as u8]);
+        sum
+    }
+
+    /// Creates a new Internet checksum initialized with the IPv6 pseudo-header.
+    pub fn ipv6_pseudo_header(src: &[u8], dst: &[u8], next_header: u8, len: u32) -> Internet {
+        let mut sum = Internet::new();
+        sum.update(src);
+        sum.update(dst);
+        sum.update(&[
+            (len >> 24) as u8,
+            (len >> 16) as u8,
+            (len >> 8) as u8,
+            len as u8,
+            0,
+            0,
+            0,
+            next_header,
+        ]);
+        sum
+    }
+
+    /// Adds the data to the checksum.
+    ///
+    /// The data can be split at any position.
+    pub fn update(&mut self, data: &[u8]) {
+        for b in data {
+            if self.odd {
+                self.sum += u64::from(*b);
+            } else {
+                self.sum += u64::from(*b) << 8;
+            }
+            self.odd = !self.odd;
+        }
+    }
+
+    /// Returns the checksum.
+    ///
+    /// The result is zero if the data contains a valid checksum field.
+    pub fn finish(&self) -> u16 {
+        let mut sum = self.sum;
+        while sum > 0xffff {
+            sum = (sum & 0xffff) + (sum >> 16);
+        }
+        !(sum as u16)
+    }
+}
+
+/// Returns the Internet checksum of the data.
+pub fn internet(data: &[u8]) -> u16 {
+    let mut sum = Internet::new();
+    sum.update(data);
+    sum.finish()
+}
+
+fn crc32_reflected(poly: u32, data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for b in data {
+        crc ^= u32::from(*b);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ poly
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Returns the CRC-32 (IEEE 802.3) of the data.
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32_reflected(0xedb8_8320, data)
+}
+
+/// Returns the CRC-32C (Castagnoli) of the data.
+pub fn crc32c(data: &[u8]) -> u32 {
+    crc32_reflected(0x82f6_3b78, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use checksum::{crc32, crc32c, internet, Internet};
+
+    #[test]
+    fn internet_rfc1071() {
+        let data = [0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7];
+        assert_eq!(internet(&data), !0xddf2);
+    }
+
+    #[test]
+    fn internet_incremental() {
+        let data = [0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7, 0x12];
+        let mut sum = Internet::new();
+        sum.update(&data[..3]);
+        sum.update(&data[3..]);
+        assert_eq!(sum.finish(), internet(&data));
+    }
+
+    #[test]
+    fn internet_ipv4_udp() {
+        let segment = [0x00, 0x35, 0xc3, 0x50, 0x00, 0x0a, 0x00, 0x00, 0xab, 0xcd];
+        let mut sum = Internet::ipv4_pseudo_header(&[10, 0, 0, 1], &[10, 0, 0, 2], 17, 10);
+        sum.update(&segment);
+        let checksum = sum.finish();
+
+        let mut segment = segment;
+        segment[6] = (checksum >> 8) as u8;
+        segment[7] = checksum as u8;
+        let mut sum = Internet::ipv4_pseudo_header(&[10, 0, 0, 1], &[10, 0, 0, 2], 17, 10);
+        sum.update(&segment);
+        assert_eq!(sum.finish(), 0);
+    }
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32c(b"123456789"), 0xe306_9283);
+        assert_eq!(crc32(b""), 0);
+    }
+}
@@ -0,0 +1,117 @@
+//! Transparent decompression of input files.
+//!
+//! This module is available with the `compression` feature.
+//!
+//! ```ignore
+//! let mut file = File::open(path)?;
+//! let compression = Compression::detect(&mut file)?;
+//! let reader = compression.decompress(file)?;
+//! ```
+
+use flate2::read::MultiGzDecoder;
+use std::io::{self, BufRead, Read, Seek, SeekFrom};
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Compression format of an input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Detects the compression format from the magic number.
+    pub fn from_magic(data: &[u8]) -> Compression {
+        if data.starts_with(&GZIP_MAGIC) {
+            Compression::Gzip
+        } else if data.starts_with(&ZSTD_MAGIC) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Detects the compression format of the input,
+    /// then rewinds it to the original position.
+    pub fn detect<R: Read + Seek>(input: &mut R) -> io::Result<Compression> {
+        let pos = input.seek(SeekFrom::Current(0))?;
+        let mut magic = [0u8; 4];
+        let mut len = 0;
+        while len < magic.len() {
+            match input.read(&mut magic[len..])? {
+                0 => break,
+                n => len += n,
+            }
+        }
+        input.seek(SeekFrom::Start(pos))?;
+        Ok(Compression::from_magic(&magic[..len]))
+    }
+
+    /// Detects the compression format of the buffered input without consuming it.
+    ///
+    /// This works with non-seekable inputs such as the standard input.
+    pub fn detect_buffered<R: BufRead>(input: &mut R) -> io::Result<Compression> {
+        Ok(Compression::from_magic(input.fill_buf()?))
+    }
+
+    /// Wraps the input with the decompressor for the format.
+    pub fn decompress<R: 'static + Read + Send>(self, input: R) -> io::Result<Box<Read + Send>> {
+        Ok(match self {
+            Compression::None => Box::new(input),
+            Compression::Gzip => Box::new(MultiGzDecoder::new(input)),
+            Compression::Zstd => Box::new(ZstdDecoder::new(input)?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use compression::Compression;
+    use std::io::{Cursor, Read};
+
+    #[test]
+    fn detect() {
+        let mut input = Cursor::new(vec![0x28, 0xb5, 0x2f, 0xfd, 0x00]);
+        assert_eq!(Compression::detect(&mut input).unwrap(), Compression::Zstd);
+        assert_eq!(input.position(), 0);
+        assert_eq!(
+            Compression::detect_buffered(&mut input).unwrap(),
+            Compression::Zstd
+        );
+        assert_eq!(input.position(), 0);
+        assert_eq!(
+            Compression::from_magic(&[0x1f, 0x8b, 0x08]),
+            Compression::Gzip
+        );
+        assert_eq!(Compression::from_magic(&[0x1f]), Compression::None);
+        assert_eq!(
+            Compression::from_magic(&[0xd4, 0xc3, 0xb2, 0xa1]),
+            Compression::None
+        );
+    }
+
+    #[test]
+    fn gzip() {
+        use flate2::{write::GzEncoder, Compression as Level};
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(Vec::new(), Level::default());
+        encoder.write_all(b"genet").unwrap();
+        let data = encoder.finish().unwrap();
+
+        let mut input = Cursor::new(data);
+        let compression = Compression::detect(&mut input).unwrap();
+        assert_eq!(compression, Compression::Gzip);
+        let mut out = String::new();
+        compression
+            .decompress(input)
+            .unwrap()
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(out, "genet");
+    }
+}
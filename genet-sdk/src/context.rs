@@ -1,3 +1,3 @@
 //! Theread context.
 
-pub use genet_abi::context::Context;
+pub use genet_abi::context::{flow_key, state_key, Context};
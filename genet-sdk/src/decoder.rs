@@ -1,6 +1,8 @@
 //! Decoder traits.
 
-pub use genet_abi::decoder::{Decoder, ExecType, Metadata, Status, Worker};
+pub use genet_abi::decoder::{
+    Decoder, ExecType, Metadata, Overlap, Status, StreamBuffer, Trigger, Worker,
+};
 
 #[doc(hidden)]
 pub use genet_abi::decoder::DecoderBox;
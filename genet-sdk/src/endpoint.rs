@@ -0,0 +1,98 @@
+//! Transport endpoints.
+//!
+//! Application-layer decoders identify a connection by the addresses of
+//! the network layer and the ports of the transport layer.
+//! `endpoints` collects them from a TCP or UDP layer.
+
+use layer::{Layer, LayerStack};
+use slice::{ByteSlice, TryGet};
+use token::Token;
+use variant::Value;
+
+/// The address and the port of an endpoint.
+pub type Endpoint = (ByteSlice, u16);
+
+/// Returns the source and the destination endpoints of the TCP or UDP layer.
+///
+/// Returns None if the layer is neither TCP nor UDP,
+/// or the addresses or the ports are missing.
+pub fn endpoints(stack: &LayerStack, layer: &Layer) -> Option<(Endpoint, Endpoint)> {
+    let (src_port, dst_port) = match layer.id() {
+        id if id == token!("tcp") => (token!("tcp.src"), token!("tcp.dst")),
+        id if id == token!("udp") => (token!("udp.src"), token!("udp.dst")),
+        _ => return None,
+    };
+    let ip = stack.parent(layer)?;
+    let addr = |id| {
+        ip.attr(id)
+            .and_then(|attr| ip.data().try_get(attr.range()).ok())
+    };
+    let port = |id: Token| -> Option<u16> {
+        layer
+            .attr(id)?
+            .try_get(layer)
+            .ok()
+            .and_then(|value| Value::<u16>::try_into(value).ok())
+    };
+    Some((
+        (addr(token!("_.src"))?, port(src_port)?),
+        (addr(token!("_.dst"))?, port(dst_port)?),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use attr::{Attr, AttrClass};
+    use cast;
+    use endpoint::endpoints;
+    use fixed::{Fixed, MutFixed};
+    use layer::{Layer, LayerClass, LayerStack};
+    use slice::ByteSlice;
+
+    fn attr(id: &str, range: ::std::ops::Range<usize>) -> Fixed<Attr> {
+        let class = Fixed::new(AttrClass::builder(id).cast(cast::UInt16BE()).build());
+        Fixed::new(Attr::builder(class).range(range).build())
+    }
+
+    fn layers(transport: &str) -> Vec<MutFixed<Layer>> {
+        let ip = Fixed::new(
+            LayerClass::builder("ipv4")
+                .alias("_.src", "ipv4.src")
+                .alias("_.dst", "ipv4.dst")
+                .header(attr("ipv4.src", 0..2))
+                .header(attr("ipv4.dst", 2..4))
+                .build(),
+        );
+        let tcp = Fixed::new(
+            LayerClass::builder(transport)
+                .header(attr(&format!("{}.src", transport), 0..2))
+                .header(attr(&format!("{}.dst", transport), 2..4))
+                .build(),
+        );
+        vec![
+            MutFixed::new(Layer::new(ip, ByteSlice::from(&[10, 1, 10, 2][..]))),
+            MutFixed::new(Layer::new(tcp, ByteSlice::from(&[0, 80, 0xc0, 0][..]))),
+        ]
+    }
+
+    #[test]
+    fn tcp() {
+        let layers = layers("tcp");
+        let indices = [1];
+        let stack = LayerStack::from_layers(&layers, &indices);
+        let ((src, src_port), (dst, dst_port)) = endpoints(&stack, &layers[1]).unwrap();
+        assert_eq!(&src[..], &[10, 1]);
+        assert_eq!(src_port, 80);
+        assert_eq!(&dst[..], &[10, 2]);
+        assert_eq!(dst_port, 0xc000);
+    }
+
+    #[test]
+    fn missing() {
+        let layers = layers("sctp");
+        let indices = [1];
+        let stack = LayerStack::from_layers(&layers, &indices);
+        assert!(endpoints(&stack, &layers[1]).is_none());
+        assert!(endpoints(&stack, &layers[0]).is_none());
+    }
+}
@@ -1,3 +1,3 @@
 //! An error type.
 
-pub use genet_abi::error::Error;
+pub use genet_abi::error::{DecodeError, Error, ErrorKind};
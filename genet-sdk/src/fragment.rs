@@ -0,0 +1,311 @@
+//! IP fragment reassembly.
+//!
+//! `FragmentReassembler` collects the fragments of IPv4 and IPv6 datagrams
+//! and returns the original payload once every fragment has been received.
+//!
+//! # Example
+//!
+//! ```
+//! use genet_sdk::{fragment::*, prelude::*};
+//!
+//! let key = FragmentKey {
+//!     src: ByteSlice::from(&[192, 168, 0, 1][..]),
+//!     dst: ByteSlice::from(&[192, 168, 0, 2][..]),
+//!     id: 1,
+//!     protocol: 17,
+//! };
+//! let mut reassembler = FragmentReassembler::new();
+//! let fragment = Fragment {
+//!     frame: 0,
+//!     timestamp: 0.0,
+//!     offset: 0,
+//!     more: true,
+//!     data: ByteSlice::from(&b"hello, "[..]),
+//! };
+//! assert!(reassembler.push(key, fragment).is_none());
+//! let fragment = Fragment {
+//!     frame: 1,
+//!     timestamp: 0.1,
+//!     offset: 7,
+//!     more: false,
+//!     data: ByteSlice::from(&b"world"[..]),
+//! };
+//! let datagram = reassembler.push(key, fragment).unwrap();
+//! assert_eq!(&datagram.data[..], b"hello, world");
+//! assert_eq!(datagram.frames, vec![0, 1]);
+//! ```
+
+use slice::{ByteSlice, TryGet};
+use std::collections::{BTreeMap, HashMap};
+
+/// The default reassembly timeout in seconds.
+pub const DEFAULT_TIMEOUT: f64 = 30.0;
+
+const MAX_DATAGRAM_LEN: usize = 65_535;
+
+/// A key identifying the fragments of the same datagram.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FragmentKey {
+    /// Source address.
+    pub src: ByteSlice,
+
+    /// Destination address.
+    pub dst: ByteSlice,
+
+    /// Identification field.
+    pub id: u32,
+
+    /// Upper-layer protocol number.
+    pub protocol: u8,
+}
+
+/// A fragment of a datagram.
+#[derive(Debug, Clone, Copy)]
+pub struct Fragment {
+    /// Index of the frame containing the fragment.
+    pub frame: u32,
+
+    /// Capture time of the frame in seconds.
+    pub timestamp: f64,
+
+    /// Byte offset of the fragment in the datagram.
+    pub offset: usize,
+
+    /// True if the fragment is not the last one.
+    pub more: bool,
+
+    /// Payload of the fragment.
+    pub data: ByteSlice,
+}
+
+/// A reassembled datagram.
+#[derive(Debug, Clone)]
+pub struct Datagram {
+    /// Reassembled payload.
+    pub data: ByteSlice,
+
+    /// Indices of the frames containing the fragments, in arrival order.
+    pub frames: Vec<u32>,
+}
+
+#[derive(Debug)]
+struct Pending {
+    created: f64,
+    len: Option<usize>,
+    frames: Vec<u32>,
+    slices: BTreeMap<usize, ByteSlice>,
+}
+
+impl Pending {
+    fn new(created: f64) -> Pending {
+        Pending {
+            created,
+            len: None,
+            frames: Vec::new(),
+            slices: BTreeMap::new(),
+        }
+    }
+
+    fn insert(&mut self, offset: usize, data: ByteSlice) {
+        let end = offset + data.len();
+        let overlapped = self
+            .slices
+            .range(..end)
+            .rev()
+            .take_while(|(start, slice)| **start + slice.len() > offset)
+            .map(|(start, slice)| (*start, slice.len()))
+            .collect::<Vec<_>>();
+
+        // Bytes received first take precedence over overlapping ones.
+        let mut cursor = offset;
+        for (start, len) in overlapped.into_iter().rev() {
+            if start > cursor {
+                let gap = (cursor - offset)..(start - offset);
+                self.slices.insert(cursor, data.try_get(gap).unwrap());
+            }
+            cursor = cursor.max(start + len);
+        }
+        if cursor < end {
+            let rest = (cursor - offset)..;
+            self.slices.insert(cursor, data.try_get(rest).unwrap());
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        if let Some(len) = self.len {
+            let mut cursor = 0;
+            for (start, slice) in &self.slices {
+                if *start > cursor {
+                    break;
+                }
+                cursor = cursor.max(start + slice.len());
+            }
+            cursor >= len
+        } else {
+            false
+        }
+    }
+
+    fn into_datagram(self) -> Datagram {
+        let len = self.len.unwrap_or(0);
+        let mut data = Vec::with_capacity(len);
+        for slice in self.slices.values() {
+            data.extend_from_slice(slice);
+        }
+        data.truncate(len);
+        Datagram {
+            data: ByteSlice::from(data),
+            frames: self.frames,
+        }
+    }
+}
+
+/// A reassembler for fragmented datagrams.
+///
+/// Overlapping bytes are taken from the fragment received first.
+/// Incomplete datagrams are discarded when no fragment has completed them
+/// within the timeout after their first fragment.
+#[derive(Debug)]
+pub struct FragmentReassembler {
+    timeout: f64,
+    pending: HashMap<FragmentKey, Pending>,
+}
+
+impl Default for FragmentReassembler {
+    fn default() -> Self {
+        Self::with_timeout(DEFAULT_TIMEOUT)
+    }
+}
+
+impl FragmentReassembler {
+    /// Creates a new FragmentReassembler with the default timeout.
+    pub fn new() -> FragmentReassembler {
+        Self::default()
+    }
+
+    /// Creates a new FragmentReassembler with the given timeout in seconds.
+    pub fn with_timeout(timeout: f64) -> FragmentReassembler {
+        FragmentReassembler {
+            timeout,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Returns the number of incomplete datagrams.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Returns true if there is no incomplete datagram.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Processes a fragment and returns the datagram if it has been completed.
+    pub fn push(&mut self, key: FragmentKey, fragment: Fragment) -> Option<Datagram> {
+        self.expire(fragment.timestamp);
+
+        let end = fragment.offset + fragment.data.len();
+        if end > MAX_DATAGRAM_LEN {
+            return None;
+        }
+
+        let complete = {
+            let pending = self
+                .pending
+                .entry(key)
+                .or_insert_with(|| Pending::new(fragment.timestamp));
+            if !pending.frames.contains(&fragment.frame) {
+                pending.frames.push(fragment.frame);
+            }
+            if !fragment.more && pending.len.is_none() {
+                pending.len = Some(end);
+            }
+            if !fragment.data.is_empty() {
+                pending.insert(fragment.offset, fragment.data);
+            }
+            pending.is_complete()
+        };
+
+        if complete {
+            self.pending.remove(&key).map(Pending::into_datagram)
+        } else {
+            None
+        }
+    }
+
+    /// Discards the incomplete datagrams which have timed out.
+    pub fn expire(&mut self, now: f64) {
+        let timeout = self.timeout;
+        self.pending
+            .retain(|_, pending| now - pending.created <= timeout);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fragment::{Fragment, FragmentKey, FragmentReassembler};
+    use slice::ByteSlice;
+
+    fn key(id: u32) -> FragmentKey {
+        FragmentKey {
+            src: ByteSlice::from(&[10, 0, 0, 1][..]),
+            dst: ByteSlice::from(&[10, 0, 0, 2][..]),
+            id,
+            protocol: 17,
+        }
+    }
+
+    fn fragment(frame: u32, offset: usize, more: bool, data: &'static [u8]) -> Fragment {
+        Fragment {
+            frame,
+            timestamp: f64::from(frame),
+            offset,
+            more,
+            data: ByteSlice::from(data),
+        }
+    }
+
+    #[test]
+    fn out_of_order() {
+        let mut reassembler = FragmentReassembler::new();
+        assert!(reassembler
+            .push(key(1), fragment(0, 8, false, b"ijkl"))
+            .is_none());
+        assert!(reassembler
+            .push(key(2), fragment(1, 0, true, b"ABCDEFGH"))
+            .is_none());
+        let datagram = reassembler
+            .push(key(1), fragment(2, 0, true, b"abcdefgh"))
+            .unwrap();
+        assert_eq!(&datagram.data[..], b"abcdefghijkl");
+        assert_eq!(datagram.frames, vec![0, 2]);
+        assert_eq!(reassembler.len(), 1);
+    }
+
+    #[test]
+    fn overlap() {
+        let mut reassembler = FragmentReassembler::new();
+        assert!(reassembler
+            .push(key(1), fragment(0, 4, true, b"EFGH"))
+            .is_none());
+        let datagram = reassembler
+            .push(key(1), fragment(1, 0, false, b"abcdefghij"))
+            .unwrap();
+        assert_eq!(&datagram.data[..], b"abcdEFGHij");
+    }
+
+    #[test]
+    fn timeout() {
+        let mut reassembler = FragmentReassembler::with_timeout(10.0);
+        assert!(reassembler
+            .push(key(1), fragment(0, 0, true, b"abcdefgh"))
+            .is_none());
+        assert!(reassembler
+            .push(key(1), fragment(20, 8, false, b"ijkl"))
+            .is_none());
+        assert_eq!(reassembler.len(), 1);
+        reassembler.expire(40.0);
+        assert!(reassembler.is_empty());
+    }
+}
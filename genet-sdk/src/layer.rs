@@ -2,4 +2,6 @@
 //!
 //! Type Layer represents a layer of a protocol stack.
 
-pub use genet_abi::layer::{Layer, LayerClass, LayerClassBuilder, LayerStack, Parent, Payload};
+pub use genet_abi::layer::{
+    Annotation, Layer, LayerClass, LayerClassBuilder, LayerStack, Link, Parent, Payload, Severity,
+};
@@ -3,22 +3,43 @@
 
 extern crate byteorder;
 extern crate genet_abi;
+extern crate genet_derive;
 extern crate lazy_static;
 
+#[cfg(feature = "compression")]
+extern crate flate2;
+#[cfg(feature = "compression")]
+extern crate zstd;
+
+#[cfg(feature = "testing")]
+extern crate serde_json;
+
+#[macro_use]
+pub mod token;
+
 pub mod attr;
 pub mod cast;
+pub mod checksum;
+#[cfg(feature = "compression")]
+pub mod compression;
 pub mod context;
 pub mod decoder;
+pub mod endpoint;
 pub mod error;
 pub mod file;
 pub mod fixed;
+pub mod fragment;
 pub mod helper;
 pub mod layer;
 pub mod prelude;
 pub mod reader;
+pub mod renderer;
 pub mod result;
+pub mod simple;
 pub mod slice;
-pub mod token;
+pub mod tcp;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod variant;
 pub mod writer;
 
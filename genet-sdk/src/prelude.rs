@@ -5,9 +5,9 @@ pub use cast::Map;
 pub use context::Context;
 pub use file::FileType;
 pub use fixed::Fixed;
-pub use layer::{Layer, LayerClass, LayerStack, Parent, Payload};
+pub use layer::{Annotation, Layer, LayerClass, LayerStack, Link, Parent, Payload, Severity};
 pub use result::Result;
-pub use slice::{ByteSlice, TryGet};
+pub use slice::{ByteChain, ByteSlice, TryGet};
 pub use token::Token;
 pub use variant::Value;
 
@@ -19,8 +19,10 @@ pub use def_attr_class;
 pub use def_layer_class;
 pub use genet_decoders;
 pub use genet_readers;
+pub use genet_renderers;
 pub use genet_writers;
 pub use layer_class;
+pub use simple_decoder;
 pub use token;
 
 pub use lazy_static;
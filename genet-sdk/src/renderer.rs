@@ -0,0 +1,33 @@
+//! Renderer traits.
+
+pub use genet_abi::renderer::{Metadata, Renderer};
+
+#[doc(hidden)]
+pub use genet_abi::renderer::RendererBox;
+
+/// Registers renderer entries.
+#[macro_export]
+macro_rules! genet_renderers {
+    ( $( $x:expr ), * ) => {
+        thread_local! {
+            static RENDERERS: Vec<genet_sdk::renderer::RendererBox> = {
+                use genet_sdk::renderer::RendererBox;
+                let mut v = Vec::new();
+                $(
+                    v.push(RendererBox::new($x));
+                )*
+                v
+            };
+        }
+        #[cfg(not(feature = "genet-static"))]
+        #[no_mangle]
+        pub extern "C" fn genet_abi_v1_get_renderers(len: *mut u64) -> *const genet_sdk::renderer::RendererBox {
+            RENDERERS.with(|d| {
+                unsafe {
+                    *len = d.len() as u64;
+                }
+                d.as_ptr()
+            })
+        }
+    };
+}
@@ -0,0 +1,320 @@
+//! Declarative decoders for fixed-layout protocols.
+//!
+//! `SimpleDecoder` implements `Decoder` from a description of the layer,
+//! so protocols without imperative logic need no `Worker` implementation.
+//! The `simple_decoder!` macro passes each `key: arg` pair to
+//! the corresponding method of `SimpleDecoderBuilder`. Methods taking
+//! multiple arguments are written as `key: (arg1, arg2, ...)`.
+//!
+//! # Example
+//!
+//! ```
+//! #[macro_use]
+//! extern crate genet_sdk;
+//!
+//! use genet_sdk::{cast, prelude::*};
+//!
+//! def_layer_class!(UDP_CLASS, "udp",
+//!     header: attr!(&SRC_ATTR, range: 0..2),
+//!     header: attr!(&DST_ATTR, range: 2..4),
+//!     header: attr!(&LEN_ATTR, range: 4..6)
+//! );
+//!
+//! def_attr_class!(SRC_ATTR, "udp.src", cast: cast::UInt16BE());
+//! def_attr_class!(DST_ATTR, "udp.dst", cast: cast::UInt16BE());
+//! def_attr_class!(LEN_ATTR, "udp.length", cast: cast::UInt16BE());
+//! def_attr_class!(DNS_ATTR, "udp.dns", typ: "@novalue", value: true);
+//! def_attr!(DNS_ATTR_HEADER, &DNS_ATTR, range: 2..4);
+//!
+//! # fn main() {
+//! let _decoder = simple_decoder!("app.genet.decoder.udp",
+//!     name: "UDP",
+//!     input: "@data:udp",
+//!     layer: &UDP_CLASS,
+//!     header_len: 8,
+//!     length: "udp.length",
+//!     when: ("udp.dst", 53, &DNS_ATTR_HEADER),
+//!     next: ("udp.dst", 53, "@data:dns")
+//! );
+//! # }
+//! ```
+
+use attr::Attr;
+use context::Context;
+use decoder::{Decoder, ExecType, Metadata, Status, Worker};
+use fixed::Fixed;
+use layer::{Annotation, Layer, LayerClass, LayerStack, Parent, Payload, Severity};
+use result::Result;
+use slice::TryGet;
+use std::sync::Arc;
+use token::Token;
+use variant::Value;
+
+/// Creates a SimpleDecoder.
+#[macro_export]
+macro_rules! simple_decoder {
+    (@build $builder:expr $(,)*) => ($builder.build());
+    (@build $builder:expr, $key:ident : ($($arg:expr),*), $($rest:tt)*) => (
+        simple_decoder!(@build $builder.$key($($arg),*), $($rest)*)
+    );
+    (@build $builder:expr, $key:ident : $arg:expr, $($rest:tt)*) => (
+        simple_decoder!(@build $builder.$key($arg), $($rest)*)
+    );
+    ($id:expr, $($rest:tt)*) => (
+        simple_decoder!(@build ::genet_sdk::simple::SimpleDecoder::builder($id), $($rest)*,)
+    );
+}
+
+struct Condition {
+    attr: Token,
+    value: u64,
+}
+
+impl Condition {
+    fn matches(&self, layer: &Layer) -> bool {
+        layer
+            .attr(self.attr)
+            .and_then(|attr| attr.try_get(layer).ok())
+            .and_then(|value| Value::<u64>::try_into(value).ok())
+            .map_or(false, |value| value == self.value)
+    }
+}
+
+struct Spec {
+    id: String,
+    name: String,
+    description: String,
+    references: Vec<String>,
+    input: Token,
+    class: Option<Fixed<LayerClass>>,
+    header_len: usize,
+    header_length: Option<(Token, usize)>,
+    length: Option<Token>,
+    attrs: Vec<(Condition, Fixed<Attr>)>,
+    payloads: Vec<(Condition, Token)>,
+    payload: Token,
+}
+
+unsafe impl Send for Spec {}
+unsafe impl Sync for Spec {}
+
+/// A builder object for SimpleDecoder.
+pub struct SimpleDecoderBuilder {
+    spec: Spec,
+}
+
+impl SimpleDecoderBuilder {
+    /// Sets a name of the decoder.
+    pub fn name(mut self, name: &str) -> SimpleDecoderBuilder {
+        self.spec.name = name.to_string();
+        self
+    }
+
+    /// Sets a description of the decoder.
+    pub fn description(mut self, desc: &str) -> SimpleDecoderBuilder {
+        self.spec.description = desc.to_string();
+        self
+    }
+
+    /// Adds a reference URL of the protocol.
+    pub fn reference(mut self, url: &str) -> SimpleDecoderBuilder {
+        self.spec.references.push(url.to_string());
+        self
+    }
+
+    /// Sets the ID of the parent payload to decode, e.g. `@data:udp`.
+    pub fn input<T: Into<Token>>(mut self, id: T) -> SimpleDecoderBuilder {
+        self.spec.input = id.into();
+        self
+    }
+
+    /// Sets the class of the layer. The header attributes are defined in the class.
+    pub fn layer<T: Into<Fixed<LayerClass>>>(mut self, class: T) -> SimpleDecoderBuilder {
+        self.spec.class = Some(class.into());
+        self
+    }
+
+    /// Sets the minimum length of the header in bytes.
+    pub fn header_len(mut self, len: usize) -> SimpleDecoderBuilder {
+        self.spec.header_len = len;
+        self
+    }
+
+    /// Sets the attribute holding the length of the header.
+    ///
+    /// The header length is the attribute value multiplied by `scale`.
+    pub fn header_length<T: Into<Token>>(mut self, id: T, scale: usize) -> SimpleDecoderBuilder {
+        self.spec.header_length = Some((id.into(), scale));
+        self
+    }
+
+    /// Sets the attribute holding the total length of the layer.
+    pub fn length<T: Into<Token>>(mut self, id: T) -> SimpleDecoderBuilder {
+        self.spec.length = Some(id.into());
+        self
+    }
+
+    /// Adds the attribute if the attribute `id` has the value.
+    pub fn when<T: Into<Token>, A: Into<Fixed<Attr>>>(
+        mut self,
+        id: T,
+        value: u64,
+        attr: A,
+    ) -> SimpleDecoderBuilder {
+        let cond = Condition {
+            attr: id.into(),
+            value,
+        };
+        self.spec.attrs.push((cond, attr.into()));
+        self
+    }
+
+    /// Sets the payload ID if the attribute `id` has the value.
+    ///
+    /// The first matching entry is used.
+    pub fn next<T: Into<Token>, P: Into<Token>>(
+        mut self,
+        id: T,
+        value: u64,
+        payload: P,
+    ) -> SimpleDecoderBuilder {
+        let cond = Condition {
+            attr: id.into(),
+            value,
+        };
+        self.spec.payloads.push((cond, payload.into()));
+        self
+    }
+
+    /// Sets the default payload ID.
+    pub fn payload<T: Into<Token>>(mut self, id: T) -> SimpleDecoderBuilder {
+        self.spec.payload = id.into();
+        self
+    }
+
+    /// Builds a new SimpleDecoder.
+    pub fn build(self) -> SimpleDecoder {
+        SimpleDecoder {
+            spec: Arc::new(self.spec),
+        }
+    }
+}
+
+/// A decoder defined by a SimpleDecoderBuilder.
+#[derive(Clone)]
+pub struct SimpleDecoder {
+    spec: Arc<Spec>,
+}
+
+impl SimpleDecoder {
+    /// Creates a new builder object for SimpleDecoder.
+    pub fn builder(id: &str) -> SimpleDecoderBuilder {
+        SimpleDecoderBuilder {
+            spec: Spec {
+                id: id.to_string(),
+                name: String::new(),
+                description: String::new(),
+                references: Vec::new(),
+                input: Token::null(),
+                class: None,
+                header_len: 0,
+                header_length: None,
+                length: None,
+                attrs: Vec::new(),
+                payloads: Vec::new(),
+                payload: Token::from(""),
+            },
+        }
+    }
+}
+
+impl Decoder for SimpleDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(SimpleWorker {
+            spec: self.spec.clone(),
+        })
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: self.spec.id.clone(),
+            name: self.spec.name.clone(),
+            description: self.spec.description.clone(),
+            exec_type: ExecType::ParallelSync,
+            references: self.spec.references.clone(),
+            ..Metadata::default()
+        }
+    }
+}
+
+struct SimpleWorker {
+    spec: Arc<Spec>,
+}
+
+impl Worker for SimpleWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let spec = &self.spec;
+        let class = match &spec.class {
+            Some(class) => class.clone(),
+            None => return Ok(Status::Skip),
+        };
+        let data = match parent.payloads().iter().find(|p| p.id() == spec.input) {
+            Some(payload) => payload.data(),
+            None => return Ok(Status::Skip),
+        };
+
+        let mut layer = Layer::new(class, data);
+        if data.len() < spec.header_len {
+            layer.annotate(Annotation::new(Severity::Error, "truncated header"));
+            parent.add_child(layer);
+            return Ok(Status::Done);
+        }
+
+        for (cond, attr) in &spec.attrs {
+            if cond.matches(&layer) {
+                layer.add_attr(attr.clone());
+            }
+        }
+
+        let mut offset = spec.header_len;
+        if let Some((id, scale)) = spec.header_length {
+            if let Some(attr) = layer.attr(id) {
+                let len: usize = attr.try_get(&layer)?.try_into()?;
+                offset = len * scale;
+            }
+        }
+
+        let mut end = data.len();
+        if let Some(id) = spec.length {
+            if let Some(attr) = layer.attr(id) {
+                let len: usize = attr.try_get(&layer)?.try_into()?;
+                if len != data.len() {
+                    layer.annotate(
+                        Annotation::new(Severity::Warn, "length field mismatch").with_attr(id),
+                    );
+                }
+                end = len.min(data.len());
+            }
+        }
+
+        if offset <= end {
+            let id = spec
+                .payloads
+                .iter()
+                .find(|(cond, _)| cond.matches(&layer))
+                .map_or(spec.payload, |(_, id)| *id);
+            let payload = data.try_get(offset..end)?;
+            layer.add_payload(Payload::new(payload, id));
+        } else {
+            layer.annotate(Annotation::new(Severity::Error, "invalid header length"));
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
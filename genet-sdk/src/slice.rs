@@ -1,3 +1,3 @@
 //! Fixed-lifetime byte sequences.
 
-pub use genet_abi::slice::{ByteSlice, TryGet};
+pub use genet_abi::slice::{ByteChain, ByteSlice, TryGet};
@@ -0,0 +1,447 @@
+//! TCP stream reassembly.
+//!
+//! `TcpReassembler` tracks one direction of a TCP connection and turns
+//! segments into an in-order byte stream, so that application-layer
+//! decoders can embed it in their workers instead of re-implementing
+//! sequence number arithmetic.
+//!
+//! # Example
+//!
+//! ```
+//! use genet_sdk::{prelude::*, tcp::{self, TcpReassembler}};
+//!
+//! let mut stream = TcpReassembler::new();
+//! stream.push(1000, tcp::SYN, ByteSlice::new());
+//! assert!(stream.push(1006, 0, ByteSlice::from(&b"world"[..])).is_empty());
+//! let data = stream.push(1001, 0, ByteSlice::from(&b"hello"[..]));
+//! assert_eq!(data.bytes().collect::<Vec<u8>>(), b"helloworld");
+//! ```
+//!
+//! `PduBuffer` splits the reassembled stream into protocol data units
+//! which may span several segments.
+
+use decoder::StreamBuffer;
+use slice::{ByteChain, ByteSlice, TryGet};
+
+pub use decoder::Overlap;
+
+/// FIN flag of the TCP header.
+pub const FIN: u8 = 0x01;
+
+/// SYN flag of the TCP header.
+pub const SYN: u8 = 0x02;
+
+/// RST flag of the TCP header.
+pub const RST: u8 = 0x04;
+
+/// State of a TcpReassembler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    /// No segment has been received yet.
+    Idle,
+
+    /// The stream is receiving data.
+    Open,
+
+    /// All the data up to the FIN has been returned.
+    Closed,
+
+    /// The stream has been aborted by a RST.
+    Reset,
+}
+
+/// A reassembler for one direction of a TCP connection.
+///
+/// The first segment determines the initial sequence number: a SYN
+/// starts the stream at the next sequence number, and any other segment
+/// starts the stream at its own sequence number so that connections
+/// captured halfway can still be followed.
+#[derive(Debug, Default)]
+pub struct TcpReassembler {
+    stream: StreamBuffer,
+    next: Option<u32>,
+    fin: Option<u64>,
+    reset: bool,
+}
+
+impl TcpReassembler {
+    /// Creates a new TcpReassembler with the default overlap policy.
+    pub fn new() -> TcpReassembler {
+        Self::default()
+    }
+
+    /// Creates a new TcpReassembler with the given overlap policy.
+    pub fn with_overlap(overlap: Overlap) -> TcpReassembler {
+        TcpReassembler {
+            stream: StreamBuffer::with_overlap(overlap),
+            ..Self::default()
+        }
+    }
+
+    /// Returns the overlap policy.
+    pub fn overlap(&self) -> Overlap {
+        self.stream.overlap()
+    }
+
+    /// Returns the current state.
+    pub fn state(&self) -> State {
+        if self.reset {
+            State::Reset
+        } else if self.next.is_none() {
+            State::Idle
+        } else if self.fin.map_or(false, |fin| self.offset() >= fin) {
+            State::Closed
+        } else {
+            State::Open
+        }
+    }
+
+    /// Returns true if the stream has been closed by a FIN or a RST.
+    pub fn is_closed(&self) -> bool {
+        match self.state() {
+            State::Closed | State::Reset => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the number of bytes returned so far.
+    pub fn offset(&self) -> u64 {
+        self.stream.offset()
+    }
+
+    /// Returns the number of bytes buffered out of order.
+    pub fn pending_len(&self) -> usize {
+        self.stream.pending_len()
+    }
+
+    /// Processes a segment and returns the bytes that became contiguous.
+    ///
+    /// `flags` is the flags field of the TCP header; only `FIN`, `SYN`
+    /// and `RST` are taken into account.
+    pub fn push(&mut self, seq: u32, flags: u8, data: ByteSlice) -> ByteChain {
+        if self.is_closed() {
+            return ByteChain::new();
+        }
+        if flags & RST != 0 {
+            self.reset = true;
+            self.stream.clear();
+            return ByteChain::new();
+        }
+
+        let seq = if flags & SYN != 0 {
+            seq.wrapping_add(1)
+        } else {
+            seq
+        };
+        let next = *self.next.get_or_insert(seq);
+
+        // Sequence numbers are compared relative to the next expected byte
+        // so that streams longer than 4 GiB are handled correctly.
+        let pos = self.offset() as i64 + i64::from(seq.wrapping_sub(next) as i32);
+        if flags & FIN != 0 && self.fin.is_none() && pos >= 0 {
+            self.fin = Some(pos as u64 + data.len() as u64);
+        }
+        if pos >= 0 {
+            self.stream.put(pos as u64, data);
+        } else if let Ok(data) = data.try_get((-pos) as usize..) {
+            self.stream.put(0, data);
+        }
+
+        let chain = self.stream.fetch();
+        if let Some(next) = self.next {
+            self.next = Some(next.wrapping_add(chain.len() as u32));
+        }
+        chain
+    }
+}
+
+/// The default maximum number of bytes buffered by a PduBuffer.
+pub const DEFAULT_PDU_LIMIT: usize = 16 * 1024 * 1024;
+
+/// A buffer splitting a byte stream into protocol data units.
+///
+/// The segments of an incomplete unit are kept until the rest arrives.
+/// The units are returned as views of the segments; they are copied only
+/// if a unit spans two or more segments.
+#[derive(Debug)]
+pub struct PduBuffer {
+    buffer: ByteChain,
+    limit: usize,
+
+    /// A contiguous copy of the buffered bytes from `consumed`, which is appended
+    /// as the segments arrive while the length of the unit is not determined.
+    flat: Vec<u8>,
+    consumed: usize,
+}
+
+impl Default for PduBuffer {
+    fn default() -> Self {
+        Self::with_limit(DEFAULT_PDU_LIMIT)
+    }
+}
+
+impl PduBuffer {
+    /// Creates a new empty PduBuffer with the default limit.
+    pub fn new() -> PduBuffer {
+        Self::default()
+    }
+
+    /// Creates a new empty PduBuffer buffering at most `limit` bytes.
+    pub fn with_limit(limit: usize) -> PduBuffer {
+        PduBuffer {
+            buffer: ByteChain::new(),
+            limit,
+            flat: Vec::new(),
+            consumed: 0,
+        }
+    }
+
+    /// Returns the maximum number of bytes buffered.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Appends the stream data and returns the complete units.
+    ///
+    /// `len` returns the length of the unit at the beginning of the data,
+    /// or None if more data is needed to determine it.
+    /// A length of zero discards the buffered data, e.g. on a framing error.
+    /// The buffered data is also discarded if it exceeds the limit.
+    pub fn push<B, F>(&mut self, data: B, mut len: F) -> Vec<ByteSlice>
+    where
+        B: Into<ByteChain>,
+        F: FnMut(&[u8]) -> Option<usize>,
+    {
+        for segment in data.into().segments() {
+            self.buffer.push(segment.clone());
+        }
+        let mut units = Vec::new();
+        while !self.buffer.is_empty() {
+            let n = if self.flat.is_empty() {
+                match len(&self.buffer.segments()[0]) {
+                    None if self.buffer.segments().len() > 1 => {
+                        // The length is not determined by the first segment alone.
+                        self.flatten();
+                        len(&self.flat[self.consumed..])
+                    }
+                    n => n,
+                }
+            } else {
+                self.flatten();
+                len(&self.flat[self.consumed..])
+            };
+            match n {
+                Some(0) => {
+                    self.clear();
+                    break;
+                }
+                Some(n) if n <= self.buffer.len() => {
+                    units.push(self.buffer.try_get(0..n).unwrap().to_slice());
+                    let rest = n..self.buffer.len();
+                    self.buffer = self.buffer.try_get(rest).unwrap();
+                    self.consume(n);
+                }
+                _ => break,
+            }
+        }
+        if self.buffer.len() > self.limit {
+            self.clear();
+        }
+        units
+    }
+
+    /// Appends the buffered bytes not copied yet to `flat`.
+    fn flatten(&mut self) {
+        let copied = self.flat.len() - self.consumed;
+        let mut skip = copied;
+        for segment in self.buffer.segments() {
+            if skip >= segment.len() {
+                skip -= segment.len();
+            } else {
+                self.flat.extend_from_slice(&segment[skip..]);
+                skip = 0;
+            }
+        }
+    }
+
+    /// Drops the first `n` bytes from `flat`.
+    fn consume(&mut self, n: usize) {
+        self.consumed += n;
+        if self.consumed >= self.flat.len() {
+            self.flat.clear();
+            self.consumed = 0;
+        } else if self.consumed > self.flat.len() / 2 {
+            self.flat.drain(..self.consumed);
+            self.consumed = 0;
+        }
+    }
+
+    /// Returns the number of bytes buffered.
+    pub fn pending_len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Discards the buffered bytes.
+    pub fn clear(&mut self) {
+        self.buffer = ByteChain::new();
+        self.flat.clear();
+        self.consumed = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use slice::ByteSlice;
+    use tcp::{self, Overlap, PduBuffer, State, TcpReassembler};
+
+    fn bytes(data: &'static [u8]) -> ByteSlice {
+        ByteSlice::from(data)
+    }
+
+    fn push(stream: &mut TcpReassembler, seq: u32, flags: u8, data: &'static [u8]) -> Vec<u8> {
+        stream.push(seq, flags, bytes(data)).bytes().collect()
+    }
+
+    #[test]
+    fn in_order() {
+        let mut stream = TcpReassembler::new();
+        assert_eq!(stream.state(), State::Idle);
+        assert_eq!(push(&mut stream, 100, tcp::SYN, b""), b"");
+        assert_eq!(stream.state(), State::Open);
+        assert_eq!(push(&mut stream, 101, 0, b"abc"), b"abc");
+        assert_eq!(push(&mut stream, 104, 0, b"def"), b"def");
+        assert_eq!(stream.offset(), 6);
+    }
+
+    #[test]
+    fn out_of_order() {
+        let mut stream = TcpReassembler::new();
+        assert_eq!(push(&mut stream, 0, 0, b"abc"), b"abc");
+        assert_eq!(push(&mut stream, 6, 0, b"ghi"), b"");
+        assert_eq!(push(&mut stream, 3, 0, b"def"), b"defghi");
+        assert_eq!(push(&mut stream, 0, 0, b"abc"), b"");
+        assert_eq!(stream.pending_len(), 0);
+    }
+
+    #[test]
+    fn wrapping() {
+        let mut stream = TcpReassembler::new();
+        assert_eq!(push(&mut stream, 0xffff_fffe, tcp::SYN, b""), b"");
+        assert_eq!(push(&mut stream, 0xffff_ffff, 0, b"ab"), b"ab");
+        assert_eq!(push(&mut stream, 1, 0, b"cd"), b"cd");
+        assert_eq!(stream.offset(), 4);
+    }
+
+    #[test]
+    fn overlap() {
+        let mut stream = TcpReassembler::with_overlap(Overlap::First);
+        assert_eq!(push(&mut stream, 0, 0, b"a"), b"a");
+        assert_eq!(push(&mut stream, 3, 0, b"xy"), b"");
+        assert_eq!(push(&mut stream, 1, 0, b"bcdef"), b"bcxyf");
+
+        let mut stream = TcpReassembler::with_overlap(Overlap::Last);
+        assert_eq!(push(&mut stream, 0, 0, b"a"), b"a");
+        assert_eq!(push(&mut stream, 2, 0, b"wxyz"), b"");
+        assert_eq!(push(&mut stream, 3, 0, b"DE"), b"");
+        assert_eq!(push(&mut stream, 1, 0, b"b"), b"bwDEz");
+    }
+
+    #[test]
+    fn fin() {
+        let mut stream = TcpReassembler::new();
+        assert_eq!(push(&mut stream, 0, 0, b"abc"), b"abc");
+        assert_eq!(push(&mut stream, 6, tcp::FIN, b"ghi"), b"");
+        assert!(!stream.is_closed());
+        assert_eq!(push(&mut stream, 3, 0, b"def"), b"defghi");
+        assert_eq!(stream.state(), State::Closed);
+        assert_eq!(push(&mut stream, 10, 0, b"jkl"), b"");
+    }
+
+    #[test]
+    fn rst() {
+        let mut stream = TcpReassembler::new();
+        assert_eq!(push(&mut stream, 0, 0, b"abc"), b"abc");
+        assert_eq!(push(&mut stream, 6, 0, b"ghi"), b"");
+        assert_eq!(push(&mut stream, 9, tcp::RST, b""), b"");
+        assert_eq!(stream.state(), State::Reset);
+        assert_eq!(stream.pending_len(), 0);
+        assert_eq!(push(&mut stream, 3, 0, b"def"), b"");
+    }
+
+    #[test]
+    fn pdu() {
+        let len = |data: &[u8]| data.first().map(|n| *n as usize + 1);
+        let mut buffer = PduBuffer::new();
+        let units = buffer.push(bytes(b"\x02ab\x01"), len);
+        assert_eq!(units.len(), 1);
+        assert_eq!(&units[0][..], b"\x02ab");
+        assert_eq!(buffer.pending_len(), 1);
+        let units = buffer.push(bytes(b"c\x00"), len);
+        assert_eq!(units.len(), 2);
+        assert_eq!(&units[0][..], b"\x01c");
+        assert_eq!(&units[1][..], b"\x00");
+        assert_eq!(buffer.pending_len(), 0);
+
+        buffer.push(bytes(b"\x05abc"), |_| Some(0));
+        assert_eq!(buffer.pending_len(), 0);
+    }
+
+    #[test]
+    fn pdu_view() {
+        let data = bytes(b"\x02ab\x01c");
+        let mut buffer = PduBuffer::new();
+        let units = buffer.push(data.clone(), |data: &[u8]| {
+            data.first().map(|n| *n as usize + 1)
+        });
+        assert_eq!(units.len(), 2);
+        assert_eq!(units[0].as_ptr(), data.as_ptr());
+        assert_eq!(units[1].as_ptr(), data[3..].as_ptr());
+    }
+
+    #[test]
+    fn pdu_split_header() {
+        // The length is a 16-bit integer which may span two segments.
+        let len = |data: &[u8]| {
+            if data.len() < 2 {
+                None
+            } else {
+                Some((data[0] as usize) << 8 | data[1] as usize)
+            }
+        };
+        let mut buffer = PduBuffer::new();
+        assert!(buffer.push(bytes(b"\x00"), len).is_empty());
+        let units = buffer.push(bytes(b"\x04ab"), len);
+        assert_eq!(units.len(), 1);
+        assert_eq!(&units[0][..], b"\x00\x04ab");
+    }
+
+    #[test]
+    fn pdu_terminated() {
+        // The length is known only when the terminator arrives.
+        let len = |data: &[u8]| data.iter().position(|b| *b == b';').map(|n| n + 1);
+        let mut buffer = PduBuffer::new();
+        for segment in &[&b"ab"[..], b"cd", b"ef"] {
+            assert!(buffer.push(ByteSlice::from(*segment), len).is_empty());
+        }
+        assert_eq!(buffer.flat, b"abcdef");
+        let units = buffer.push(bytes(b"g;hi;j"), len);
+        assert_eq!(units.len(), 2);
+        assert_eq!(&units[0][..], b"abcdefg;");
+        assert_eq!(&units[1][..], b"hi;");
+        assert_eq!(buffer.pending_len(), 1);
+        assert_eq!(&buffer.flat[buffer.consumed..], b"j");
+        let units = buffer.push(bytes(b"k;"), len);
+        assert_eq!(&units[0][..], b"jk;");
+        assert!(buffer.flat.is_empty());
+    }
+
+    #[test]
+    fn pdu_limit() {
+        let len = |_: &[u8]| Some(100);
+        let mut buffer = PduBuffer::with_limit(8);
+        assert!(buffer.push(bytes(b"abcd"), len).is_empty());
+        assert_eq!(buffer.pending_len(), 4);
+        assert!(buffer.push(bytes(b"efghi"), len).is_empty());
+        assert_eq!(buffer.pending_len(), 0);
+    }
+}
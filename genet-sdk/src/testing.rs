@@ -0,0 +1,430 @@
+//! Golden-file tests for decoders.
+//!
+//! `Harness` feeds the frames in a pcap file through the decoders and
+//! compares the resulting layer trees with a JSON snapshot.
+//! Set `GENET_UPDATE_GOLDEN=1` to write the snapshots instead of comparing them.
+//!
+//! ```ignore
+//! #[test]
+//! fn golden() {
+//!     Harness::new()
+//!         .decoder(UdpDecoder {})
+//!         .input("@data:udp")
+//!         .assert_golden("tests/udp.pcap", "tests/udp.json");
+//! }
+//! ```
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use context::Context;
+use decoder::{Decoder, DecoderBox};
+use fixed::{Fixed, MutFixed};
+use layer::{Layer, LayerClass, Parent, Payload};
+use serde_json::{self, Map, Number, Value};
+use std::{
+    env, fs,
+    io::{self, Error, ErrorKind},
+    path::Path,
+};
+use token::Token;
+use variant::Variant;
+
+const HEADER_LEN: usize = 24;
+const RECORD_HEADER_LEN: usize = 16;
+
+/// The environment variable to write the snapshots.
+pub const UPDATE_ENV: &str = "GENET_UPDATE_GOLDEN";
+
+/// A frame read from a pcap file.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub link: u32,
+    pub data: Vec<u8>,
+}
+
+/// Reads all the frames in the pcap file.
+pub fn read_pcap<P: AsRef<Path>>(path: P) -> io::Result<Vec<Frame>> {
+    parse_pcap(&fs::read(path)?)
+}
+
+fn parse_pcap(data: &[u8]) -> io::Result<Vec<Frame>> {
+    if data.len() < HEADER_LEN {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            "unexpected end of file",
+        ));
+    }
+    let le = match BigEndian::read_u32(&data[0..4]) {
+        0xd4c3_b2a1 | 0x4d3c_b2a1 => true,
+        0xa1b2_c3d4 | 0xa1b2_3c4d => false,
+        _ => return Err(Error::new(ErrorKind::InvalidData, "wrong magic number")),
+    };
+    let read_u32 = |buf: &[u8]| {
+        if le {
+            LittleEndian::read_u32(buf)
+        } else {
+            BigEndian::read_u32(buf)
+        }
+    };
+    let link = read_u32(&data[20..24]);
+    let mut frames = Vec::new();
+    let mut offset = HEADER_LEN;
+    while offset < data.len() {
+        if offset + RECORD_HEADER_LEN > data.len() {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "unexpected end of file",
+            ));
+        }
+        let len = read_u32(&data[offset + 8..offset + 12]) as usize;
+        offset += RECORD_HEADER_LEN;
+        let end = (offset + len).min(data.len());
+        frames.push(Frame {
+            link,
+            data: data[offset..end].to_vec(),
+        });
+        offset = end;
+    }
+    Ok(frames)
+}
+
+/// A test harness running decoders on pcap fixtures.
+pub struct Harness {
+    decoders: Vec<DecoderBox>,
+    input: Option<Token>,
+    update: bool,
+}
+
+impl Default for Harness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Harness {
+    /// Creates a new Harness.
+    ///
+    /// The snapshots are written if `GENET_UPDATE_GOLDEN` is set.
+    pub fn new() -> Harness {
+        Harness {
+            decoders: Vec::new(),
+            input: None,
+            update: env::var(UPDATE_ENV).map_or(false, |v| !v.is_empty() && v != "0"),
+        }
+    }
+
+    /// Adds a decoder under test.
+    ///
+    /// The decoders are executed in the order of addition.
+    pub fn decoder<T: 'static + Decoder>(mut self, decoder: T) -> Harness {
+        self.decoders.push(DecoderBox::new(decoder));
+        self
+    }
+
+    /// Passes the frame data as a payload with the ID, e.g. `@data:udp`,
+    /// so that the fixture can start from the protocol under test.
+    pub fn input<T: Into<Token>>(mut self, id: T) -> Harness {
+        self.input = Some(id.into());
+        self
+    }
+
+    /// Sets whether to write the snapshots instead of comparing them.
+    pub fn update(mut self, update: bool) -> Harness {
+        self.update = update;
+        self
+    }
+
+    /// Decodes the frames and returns the layer trees as JSON.
+    ///
+    /// The workers are shared among the frames, so stateful decoders see
+    /// the frames in order as the kernel does.
+    pub fn decode(&mut self, frames: &[Frame]) -> Value {
+        let mut workers = self
+            .decoders
+            .iter_mut()
+            .map(|d| {
+                let ctx = Context::new(Default::default());
+                (d.new_worker(&ctx), ctx)
+            })
+            .collect::<Vec<_>>();
+        let mut result = Vec::new();
+        for (index, frame) in frames.iter().enumerate() {
+            let class = Fixed::new(LayerClass::builder(format!("[link-{}]", frame.link)).build());
+            let mut root = Layer::new(class, frame.data.clone());
+            if let Some(id) = self.input {
+                let data = root.data();
+                root.add_payload(Payload::new(data, id));
+            }
+
+            let mut layers = vec![MutFixed::new(root)];
+            let mut indices = Vec::new();
+            let mut children = Vec::new();
+            let mut current = 0;
+            while current < layers.len() {
+                let mut list = Vec::new();
+                for (worker, ctx) in &mut workers {
+                    ctx.set_frame_index(index as u32);
+                    let mut layer =
+                        Parent::from_mut_ref(unsafe { &mut *layers[current].as_mut_ptr() });
+                    if let Err(err) = worker.decode(ctx, &layers, &indices, &mut layer) {
+                        layer.error(&err.to_string());
+                    }
+                    let results = layer
+                        .children()
+                        .iter()
+                        .map(|v| unsafe { MutFixed::from_ptr(*v) })
+                        .collect::<Vec<_>>();
+                    for child in results {
+                        list.push(layers.len());
+                        layers.push(child);
+                    }
+                }
+                indices.push(list.len() as u8);
+                children.push(list);
+                current += 1;
+            }
+
+            let mut tree = Map::new();
+            tree.insert("index".into(), index.into());
+            tree.insert("layers".into(), layer_tree(&layers, &children, 0));
+            result.push(Value::Object(tree));
+        }
+        Value::Array(result)
+    }
+
+    /// Decodes the pcap file and compares the result with the snapshot.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the result does not match the snapshot or the files cannot be accessed.
+    pub fn assert_golden<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, pcap: P, golden: Q) {
+        let golden = golden.as_ref();
+        let frames =
+            read_pcap(&pcap).unwrap_or_else(|err| panic!("{}: {}", pcap.as_ref().display(), err));
+        let actual = serde_json::to_string_pretty(&self.decode(&frames)).unwrap() + "\n";
+        if self.update {
+            fs::write(golden, &actual)
+                .unwrap_or_else(|err| panic!("{}: {}", golden.display(), err));
+            return;
+        }
+        let expected = fs::read_to_string(golden).unwrap_or_else(|err| {
+            panic!(
+                "{}: {} (set {}=1 to create the snapshot)",
+                golden.display(),
+                err,
+                UPDATE_ENV
+            )
+        });
+        if expected != actual {
+            let line = expected
+                .lines()
+                .zip(actual.lines())
+                .position(|(e, a)| e != a)
+                .unwrap_or_else(|| expected.lines().count().min(actual.lines().count()));
+            panic!(
+                "{} does not match at line {}\n  expected: {}\n    actual: {}\n(set {}=1 to update the snapshot)",
+                golden.display(),
+                line + 1,
+                expected.lines().nth(line).unwrap_or(""),
+                actual.lines().nth(line).unwrap_or(""),
+                UPDATE_ENV
+            );
+        }
+    }
+}
+
+fn layer_tree(layers: &[MutFixed<Layer>], children: &[Vec<usize>], index: usize) -> Value {
+    Value::Array(
+        children[index]
+            .iter()
+            .map(|child| {
+                let mut obj = layer_json(&layers[*child]);
+                obj.insert("children".into(), layer_tree(layers, children, *child));
+                Value::Object(obj)
+            })
+            .collect(),
+    )
+}
+
+fn layer_json(layer: &Layer) -> Map<String, Value> {
+    let attrs = layer
+        .headers()
+        .iter()
+        .chain(layer.attrs().iter())
+        .map(|attr| {
+            let mut obj = Map::new();
+            obj.insert("id".into(), attr.id().to_string().into());
+            if attr.typ() != Token::null() {
+                obj.insert("typ".into(), attr.typ().to_string().into());
+            }
+            let range = attr.bit_range();
+            obj.insert("bitRange".into(), vec![range.start, range.end].into());
+            match attr.try_get(layer) {
+                Ok(value) => {
+                    obj.insert("value".into(), variant_json(value));
+                }
+                Err(err) => {
+                    obj.insert("error".into(), err.to_string().into());
+                }
+            }
+            if let Some(label) = attr.label(layer) {
+                obj.insert("label".into(), label.into());
+            }
+            Value::Object(obj)
+        })
+        .collect::<Vec<_>>();
+
+    let payloads = layer
+        .payloads()
+        .iter()
+        .map(|payload| {
+            let mut obj = Map::new();
+            obj.insert("id".into(), payload.id().to_string().into());
+            if payload.typ() != Token::null() {
+                obj.insert("typ".into(), payload.typ().to_string().into());
+            }
+            obj.insert("length".into(), payload.len().into());
+            Value::Object(obj)
+        })
+        .collect::<Vec<_>>();
+
+    let annotations = layer
+        .annotations()
+        .iter()
+        .map(|ann| {
+            let mut obj = Map::new();
+            obj.insert(
+                "severity".into(),
+                format!("{:?}", ann.severity()).to_lowercase().into(),
+            );
+            obj.insert("message".into(), ann.message().into());
+            if ann.attr() != Token::null() {
+                obj.insert("attr".into(), ann.attr().to_string().into());
+            }
+            Value::Object(obj)
+        })
+        .collect::<Vec<_>>();
+
+    let mut obj = Map::new();
+    obj.insert("id".into(), layer.id().to_string().into());
+    obj.insert("length".into(), layer.data().len().into());
+    obj.insert("attrs".into(), attrs.into());
+    if !payloads.is_empty() {
+        obj.insert("payloads".into(), payloads.into());
+    }
+    if !annotations.is_empty() {
+        obj.insert("annotations".into(), annotations.into());
+    }
+    obj
+}
+
+fn hex(data: &[u8]) -> Value {
+    data.iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>()
+        .into()
+}
+
+fn variant_json(value: Variant) -> Value {
+    match value {
+        Variant::Nil => Value::Null,
+        Variant::Bool(v) => v.into(),
+        Variant::Int64(v) => v.into(),
+        Variant::UInt64(v) | Variant::Timestamp(v) => v.into(),
+        Variant::Float64(v) => Number::from_f64(v).map_or(Value::Null, Value::Number),
+        Variant::String(v) => v.to_string().into(),
+        Variant::BigInt(v) | Variant::Buffer(v) => hex(&v),
+        Variant::Slice(v) => hex(&v),
+        Variant::Ipv6Addr(v) => v.to_string().into(),
+        Variant::MacAddr(v) => v
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(":")
+            .into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use attr::{Attr, AttrClass};
+    use cast;
+    use fixed::Fixed;
+    use layer::LayerClass;
+    use simple::SimpleDecoder;
+    use std::{env, fs, panic, process};
+    use testing::{parse_pcap, Harness};
+
+    fn pcap() -> Vec<u8> {
+        let mut data = vec![
+            0xd4, 0xc3, 0xb2, 0xa1, 0x02, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0xff, 0xff, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+        ];
+        for frame in &[
+            &[0x00, 0x35, 0xd4, 0x31, 0x00, 0x0a, 0x00, 0x00, 0xab, 0xcd][..],
+            &[0x00, 0x35, 0xd4, 0x31, 0x00, 0x20, 0x00, 0x00][..],
+        ] {
+            data.extend_from_slice(&[0; 8]);
+            data.extend_from_slice(&[frame.len() as u8, 0, 0, 0]);
+            data.extend_from_slice(&[frame.len() as u8, 0, 0, 0]);
+            data.extend_from_slice(frame);
+        }
+        data
+    }
+
+    fn harness() -> Harness {
+        let header = |id: &str, start: usize| {
+            let class = Fixed::new(AttrClass::builder(id).cast(cast::UInt16BE()).build());
+            Fixed::new(Attr::builder(class).range(start..start + 2).build())
+        };
+        let class = LayerClass::builder("udp")
+            .header(header("udp.src", 0))
+            .header(header("udp.dst", 2))
+            .header(header("udp.length", 4))
+            .build();
+        let decoder = SimpleDecoder::builder("udp")
+            .input("@data:udp")
+            .layer(Fixed::new(class))
+            .header_len(8)
+            .length("udp.length")
+            .build();
+        Harness::new().decoder(decoder).input("@data:udp")
+    }
+
+    #[test]
+    fn read() {
+        let frames = parse_pcap(&pcap()).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].link, 1);
+        assert_eq!(frames[1].data.len(), 8);
+        assert!(parse_pcap(&[0; 24]).is_err());
+    }
+
+    #[test]
+    fn golden() {
+        let dir = env::temp_dir().join(format!("genet-golden-test-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let pcap_path = dir.join("udp.pcap");
+        let golden_path = dir.join("udp.json");
+        fs::write(&pcap_path, pcap()).unwrap();
+
+        harness()
+            .update(true)
+            .assert_golden(&pcap_path, &golden_path);
+        let json = fs::read_to_string(&golden_path).unwrap();
+        assert!(json.contains("\"udp.dst\""));
+        assert!(json.contains("length field mismatch"));
+
+        harness()
+            .update(false)
+            .assert_golden(&pcap_path, &golden_path);
+
+        fs::write(&golden_path, json.replace("54321", "12345")).unwrap();
+        let result = panic::catch_unwind(|| {
+            harness()
+                .update(false)
+                .assert_golden(&pcap_path, &golden_path);
+        });
+        let _ = fs::remove_dir_all(&dir);
+        assert!(result.is_err());
+    }
+}
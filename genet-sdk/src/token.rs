@@ -5,6 +5,10 @@
 
 /// Returns a token corresponded with the given string.
 ///
+/// Token values are assigned by the kernel at runtime, so the token is
+/// interned at the first evaluation and cached in a static variable for each call site.
+/// Subsequent evaluations cost a single atomic load.
+///
 /// # Examples
 /// ```
 /// # #[macro_use] extern crate genet_sdk;
@@ -20,15 +24,16 @@ macro_rules! token {
     }};
     ($name:expr) => {{
         use $crate::token::Token;
-        thread_local!(
-            static TOKEN: Token = { Token::from($name) };
-        );
-        TOKEN.with(|&t| t)
+        static TOKEN: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::ATOMIC_USIZE_INIT;
+        $crate::token::cached(&TOKEN, || Token::from($name))
     }};
 }
 
 pub use genet_abi::token::Token;
 
+#[doc(hidden)]
+pub use genet_abi::token::cached;
+
 #[cfg(test)]
 mod tests {
     use token::Token;
@@ -48,4 +53,13 @@ mod tests {
         assert_eq!(token.to_string(), "dd31817d-1501-4b2b-bcf6-d02e148d3ab9");
         assert_eq!(Token::from(1000).to_string(), "");
     }
+
+    #[test]
+    fn cached() {
+        fn get() -> Token {
+            token!("ipv4.src")
+        }
+        assert_eq!(get(), Token::from("ipv4.src"));
+        assert_eq!(get(), get());
+    }
 }
@@ -0,0 +1,13 @@
+extern crate protoc_rust_grpc;
+
+fn main() {
+    protoc_rust_grpc::run(protoc_rust_grpc::Args {
+        out_dir: "src/proto",
+        includes: &["proto"],
+        input: &["proto/genet.proto"],
+        rust_protobuf: true,
+        ..Default::default()
+    })
+    .expect("failed to compile proto/genet.proto");
+    println!("cargo:rerun-if-changed=proto/genet.proto");
+}
@@ -0,0 +1,99 @@
+use genet_abi::{layer::Layer, token::Token, variant::Variant};
+use genet_kernel::session::Frame;
+use proto::genet;
+use protobuf::RepeatedField;
+use serde_json::{Number, Value};
+
+pub fn frame(frame: &Frame) -> genet::Frame {
+    let mut msg = genet::Frame::new();
+    msg.set_index(frame.index());
+    msg.set_layers(frame.layers().iter().map(|l| layer(l)).collect());
+    msg.set_tree_indices(frame.tree_indices().iter().map(|i| u32::from(*i)).collect());
+    msg
+}
+
+fn layer(layer: &Layer) -> genet::Layer {
+    let attrs = layer
+        .headers()
+        .iter()
+        .chain(layer.attrs().iter())
+        .map(|attr| {
+            let mut msg = genet::Attr::new();
+            msg.set_id(attr.id().to_string());
+            if attr.typ() != Token::null() {
+                msg.set_typ(attr.typ().to_string());
+            }
+            let range = attr.bit_range();
+            msg.set_bit_start(range.start as u32);
+            msg.set_bit_end(range.end as u32);
+            match attr.try_get(layer) {
+                Ok(value) => msg.set_value(variant(value).to_string()),
+                Err(err) => msg.set_error(err.to_string()),
+            }
+            msg
+        })
+        .collect();
+
+    let payloads = layer
+        .payloads()
+        .iter()
+        .map(|payload| {
+            let mut msg = genet::Payload::new();
+            msg.set_id(payload.id().to_string());
+            if payload.typ() != Token::null() {
+                msg.set_typ(payload.typ().to_string());
+            }
+            msg.set_length(payload.len() as u32);
+            msg
+        })
+        .collect();
+
+    let annotations = layer
+        .annotations()
+        .iter()
+        .map(|ann| {
+            let mut msg = genet::Annotation::new();
+            msg.set_severity(format!("{:?}", ann.severity()).to_lowercase());
+            msg.set_message(ann.message().to_string());
+            if ann.attr() != Token::null() {
+                msg.set_attr(ann.attr().to_string());
+            }
+            msg
+        })
+        .collect();
+
+    let mut msg = genet::Layer::new();
+    msg.set_id(layer.id().to_string());
+    msg.set_length(layer.data().len() as u32);
+    msg.set_attrs(RepeatedField::from_vec(attrs));
+    msg.set_payloads(RepeatedField::from_vec(payloads));
+    msg.set_annotations(RepeatedField::from_vec(annotations));
+    msg
+}
+
+fn hex(data: &[u8]) -> Value {
+    data.iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>()
+        .into()
+}
+
+fn variant(value: Variant) -> Value {
+    match value {
+        Variant::Nil => Value::Null,
+        Variant::Bool(v) => v.into(),
+        Variant::Int64(v) => v.into(),
+        Variant::UInt64(v) | Variant::Timestamp(v) => v.into(),
+        Variant::Float64(v) => Number::from_f64(v).map_or(Value::Null, Value::Number),
+        Variant::String(v) => v.to_string().into(),
+        Variant::BigInt(v) | Variant::Buffer(v) => hex(&v),
+        Variant::Slice(v) => hex(&v),
+        Variant::Ipv6Addr(v) => v.to_string().into(),
+        Variant::MacAddr(v) => v
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(":")
+            .into(),
+    }
+}
@@ -0,0 +1,90 @@
+//! gRPC server for remote control of the genet kernel.
+//!
+//! The service is defined in `proto/genet.proto`.
+//!
+//! ```text
+//! genet-server [--port <port>] [--packages <dir>]... [--library <path>]...
+//! ```
+
+extern crate futures;
+extern crate genet_abi;
+extern crate genet_filter;
+extern crate genet_kernel;
+extern crate grpc;
+extern crate parking_lot;
+extern crate protobuf;
+extern crate serde_json;
+
+mod convert;
+mod proto;
+mod service;
+
+use genet_kernel::profile::Profile;
+use proto::genet_grpc::GenetServer;
+use service::Service;
+use std::{env, process, thread};
+
+const DEFAULT_PORT: u16 = 50051;
+
+struct Options {
+    port: u16,
+    packages: Vec<String>,
+    libraries: Vec<String>,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut opts = Options {
+        port: DEFAULT_PORT,
+        packages: Vec::new(),
+        libraries: Vec::new(),
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} requires a value", arg));
+        match arg.as_str() {
+            "--port" => {
+                opts.port = value()?
+                    .parse()
+                    .map_err(|err| format!("invalid port: {}", err))?
+            }
+            "--packages" => opts.packages.push(value()?),
+            "--library" => opts.libraries.push(value()?),
+            _ => return Err(format!("unknown option: {}", arg)),
+        }
+    }
+    Ok(opts)
+}
+
+fn run() -> Result<(), String> {
+    let opts = parse_args()?;
+    let mut profile = Profile::new();
+    for dir in &opts.packages {
+        let errors = profile
+            .load_packages(dir)
+            .map_err(|err| format!("{}: {}", dir, err))?;
+        for err in errors {
+            eprintln!("warning: {}", err);
+        }
+    }
+    for path in &opts.libraries {
+        profile
+            .load_library(path)
+            .map_err(|err| format!("{}: {}", path, err))?;
+    }
+
+    let mut server = grpc::ServerBuilder::new_plain();
+    server.http.set_port(opts.port);
+    server.add_service(GenetServer::new_service_def(Service::new(profile)));
+    let _server = server.build().map_err(|err| err.to_string())?;
+    eprintln!("listening on port {}", opts.port);
+    loop {
+        thread::park();
+    }
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
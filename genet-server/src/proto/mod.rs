@@ -0,0 +1,6 @@
+//! Generated from `proto/genet.proto` by the build script.
+
+#![allow(clippy::all)]
+
+pub mod genet;
+pub mod genet_grpc;
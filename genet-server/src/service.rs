@@ -0,0 +1,332 @@
+use convert;
+use futures::{
+    sync::mpsc::{self, UnboundedSender},
+    Stream,
+};
+use genet_filter::Filter;
+use genet_kernel::{
+    profile::Profile,
+    session::{Callback, Event, Selection, Session},
+};
+use grpc::{
+    Error, GrpcMessageError, GrpcStatus, RequestOptions, SingleResponse, StreamingResponse,
+};
+use parking_lot::Mutex;
+use proto::{genet, genet_grpc};
+use protobuf::RepeatedField;
+use serde_json;
+use std::{collections::HashMap, ops::Range, sync::Arc};
+
+type Subscribers = Arc<Mutex<Vec<UnboundedSender<genet::Event>>>>;
+
+#[derive(Clone)]
+struct EventCallback {
+    subscribers: Subscribers,
+}
+
+impl Callback for EventCallback {
+    fn on_event(&self, event: Event) {
+        let mut msg = genet::Event::new();
+        let json = serde_json::to_value(&event).unwrap();
+        if let Some(typ) = json.get("type").and_then(|t| t.as_str()) {
+            msg.set_field_type(typ.to_string());
+        }
+        msg.set_json(json.to_string());
+        self.subscribers
+            .lock()
+            .retain(|tx| tx.unbounded_send(msg.clone()).is_ok());
+    }
+}
+
+struct Entry {
+    session: Session,
+    subscribers: Subscribers,
+}
+
+// Sessions are only accessed while holding the lock of the service.
+unsafe impl Send for Entry {}
+
+pub struct Service {
+    profile: Profile,
+    sessions: Mutex<HashMap<u32, Entry>>,
+    session_cnt: Mutex<u32>,
+}
+
+fn error(status: GrpcStatus, message: &str) -> Error {
+    Error::GrpcMessage(GrpcMessageError {
+        grpc_status: status as i32,
+        grpc_message: message.to_string(),
+    })
+}
+
+fn not_found(id: u32) -> Error {
+    error(GrpcStatus::NotFound, &format!("no such session: {}", id))
+}
+
+fn respond<T: Send + 'static>(result: Result<T, Error>) -> SingleResponse<T> {
+    match result {
+        Ok(value) => SingleResponse::completed(value),
+        Err(err) => SingleResponse::err(err),
+    }
+}
+
+impl Service {
+    pub fn new(profile: Profile) -> Service {
+        Service {
+            profile,
+            sessions: Mutex::new(HashMap::new()),
+            session_cnt: Mutex::new(0),
+        }
+    }
+
+    fn with_session<T, F>(&self, id: u32, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut Entry) -> Result<T, Error>,
+    {
+        let mut sessions = self.sessions.lock();
+        match sessions.get_mut(&id) {
+            Some(entry) => f(entry),
+            None => Err(not_found(id)),
+        }
+    }
+
+    fn compile(session: &Session, filter: &str) -> Result<Option<Filter>, Error> {
+        if filter.is_empty() {
+            return Ok(None);
+        }
+        Filter::compile_with(filter, session.profile().named_filters())
+            .map(Some)
+            .map_err(|err| error(GrpcStatus::InvalidArgument, &err.to_string()))
+    }
+
+    fn range(session: &Session, start: u32, end: u32) -> Range<usize> {
+        let len = session.len();
+        (start as usize).min(len)..(end as usize).min(len)
+    }
+}
+
+impl genet_grpc::Genet for Service {
+    fn create_session(
+        &self,
+        _o: RequestOptions,
+        req: genet::CreateSessionRequest,
+    ) -> SingleResponse<genet::SessionResponse> {
+        let mut profile = self.profile.clone();
+        for (key, value) in req.get_config() {
+            if let Err(err) = profile.set_config(key, value) {
+                return SingleResponse::err(error(
+                    GrpcStatus::InvalidArgument,
+                    &format!("{}: {}", key, err),
+                ));
+            }
+        }
+        let subscribers = Subscribers::default();
+        let session = Session::new(
+            profile,
+            EventCallback {
+                subscribers: subscribers.clone(),
+            },
+        );
+        let id = {
+            let mut cnt = self.session_cnt.lock();
+            *cnt += 1;
+            *cnt
+        };
+        self.sessions.lock().insert(
+            id,
+            Entry {
+                session,
+                subscribers,
+            },
+        );
+        let mut res = genet::SessionResponse::new();
+        res.set_session(id);
+        SingleResponse::completed(res)
+    }
+
+    fn close_session(
+        &self,
+        _o: RequestOptions,
+        req: genet::SessionRequest,
+    ) -> SingleResponse<genet::Empty> {
+        let id = req.get_session();
+        respond(
+            self.sessions
+                .lock()
+                .remove(&id)
+                .map(|_| genet::Empty::new())
+                .ok_or_else(|| not_found(id)),
+        )
+    }
+
+    fn list_sessions(
+        &self,
+        _o: RequestOptions,
+        _req: genet::Empty,
+    ) -> SingleResponse<genet::ListSessionsResponse> {
+        let mut sessions = self.sessions.lock().keys().cloned().collect::<Vec<_>>();
+        sessions.sort();
+        let mut res = genet::ListSessionsResponse::new();
+        res.set_sessions(sessions);
+        SingleResponse::completed(res)
+    }
+
+    fn list_components(
+        &self,
+        _o: RequestOptions,
+        _req: genet::Empty,
+    ) -> SingleResponse<genet::ListComponentsResponse> {
+        let mut res = genet::ListComponentsResponse::new();
+        res.set_decoders(RepeatedField::from_vec(
+            self.profile.decoders().map(|d| d.metadata().id).collect(),
+        ));
+        res.set_readers(RepeatedField::from_vec(
+            self.profile.readers().map(|r| r.metadata().id).collect(),
+        ));
+        res.set_writers(RepeatedField::from_vec(
+            self.profile.writers().map(|w| w.metadata().id).collect(),
+        ));
+        SingleResponse::completed(res)
+    }
+
+    fn create_reader(
+        &self,
+        _o: RequestOptions,
+        req: genet::CreateIoRequest,
+    ) -> SingleResponse<genet::HandleResponse> {
+        respond(self.with_session(req.get_session(), |entry| {
+            let mut res = genet::HandleResponse::new();
+            res.set_handle(entry.session.create_reader(req.get_id(), req.get_arg()));
+            Ok(res)
+        }))
+    }
+
+    fn create_writer(
+        &self,
+        _o: RequestOptions,
+        req: genet::CreateIoRequest,
+    ) -> SingleResponse<genet::HandleResponse> {
+        respond(self.with_session(req.get_session(), |entry| {
+            let selection = match Self::compile(&entry.session, req.get_filter())? {
+                Some(filter) => Selection::Filter(filter),
+                None => Selection::All,
+            };
+            let mut res = genet::HandleResponse::new();
+            res.set_handle(
+                entry
+                    .session
+                    .create_writer(req.get_id(), req.get_arg(), selection),
+            );
+            Ok(res)
+        }))
+    }
+
+    fn close_reader(
+        &self,
+        _o: RequestOptions,
+        req: genet::HandleRequest,
+    ) -> SingleResponse<genet::Empty> {
+        respond(self.with_session(req.get_session(), |entry| {
+            entry.session.close_reader(req.get_handle());
+            Ok(genet::Empty::new())
+        }))
+    }
+
+    fn get_frames(
+        &self,
+        _o: RequestOptions,
+        req: genet::GetFramesRequest,
+    ) -> SingleResponse<genet::GetFramesResponse> {
+        respond(self.with_session(req.get_session(), |entry| {
+            let range = Self::range(&entry.session, req.get_start(), req.get_end());
+            let frames = entry
+                .session
+                .frames(range)
+                .into_iter()
+                .map(|frame| convert::frame(unsafe { &*frame }))
+                .collect();
+            let mut res = genet::GetFramesResponse::new();
+            res.set_frames(RepeatedField::from_vec(frames));
+            Ok(res)
+        }))
+    }
+
+    fn set_filter(
+        &self,
+        _o: RequestOptions,
+        req: genet::SetFilterRequest,
+    ) -> SingleResponse<genet::Empty> {
+        respond(self.with_session(req.get_session(), |entry| {
+            let filter = Self::compile(&entry.session, req.get_filter())?;
+            entry.session.set_filter(req.get_id(), filter);
+            Ok(genet::Empty::new())
+        }))
+    }
+
+    fn get_filtered_frames(
+        &self,
+        _o: RequestOptions,
+        req: genet::GetFramesRequest,
+    ) -> SingleResponse<genet::GetFilteredFramesResponse> {
+        respond(self.with_session(req.get_session(), |entry| {
+            let range = req.get_start() as usize..req.get_end() as usize;
+            let mut res = genet::GetFilteredFramesResponse::new();
+            res.set_indices(entry.session.filtered_frames(req.get_filter(), range));
+            Ok(res)
+        }))
+    }
+
+    fn get_status(
+        &self,
+        _o: RequestOptions,
+        req: genet::SessionRequest,
+    ) -> SingleResponse<genet::StatusResponse> {
+        respond(self.with_session(req.get_session(), |entry| {
+            let mut res = genet::StatusResponse::new();
+            res.set_frames(entry.session.len() as u32);
+            Ok(res)
+        }))
+    }
+
+    fn get_statistics(
+        &self,
+        _o: RequestOptions,
+        req: genet::SessionRequest,
+    ) -> SingleResponse<genet::JsonResponse> {
+        respond(self.with_session(req.get_session(), |entry| {
+            let mut res = genet::JsonResponse::new();
+            res.set_json(serde_json::to_string(&entry.session.statistics()).unwrap());
+            Ok(res)
+        }))
+    }
+
+    fn get_flows(
+        &self,
+        _o: RequestOptions,
+        req: genet::SessionRequest,
+    ) -> SingleResponse<genet::JsonResponse> {
+        respond(self.with_session(req.get_session(), |entry| {
+            let mut res = genet::JsonResponse::new();
+            res.set_json(serde_json::to_string(&entry.session.flows()).unwrap());
+            Ok(res)
+        }))
+    }
+
+    fn watch_events(
+        &self,
+        _o: RequestOptions,
+        req: genet::SessionRequest,
+    ) -> StreamingResponse<genet::Event> {
+        let result = self.with_session(req.get_session(), |entry| {
+            let (tx, rx) = mpsc::unbounded();
+            entry.subscribers.lock().push(tx);
+            Ok(rx)
+        });
+        match result {
+            Ok(rx) => StreamingResponse::no_metadata(
+                rx.map_err(|_| error(GrpcStatus::Internal, "event stream closed")),
+            ),
+            Err(err) => StreamingResponse::err(err),
+        }
+    }
+}
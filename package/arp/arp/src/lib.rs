@@ -2,6 +2,11 @@ extern crate genet_sdk;
 
 use genet_sdk::{cast, decoder::*, prelude::*};
 
+const HEADER_LEN: usize = 8;
+
+const OP_REQUEST: u64 = 1;
+const OP_REPLY: u64 = 2;
+
 struct ArpWorker {}
 
 impl Worker for ArpWorker {
@@ -16,7 +21,7 @@ impl Worker for ArpWorker {
         if let Some(payload) = parent
             .payloads()
             .iter()
-            .find(|p| p.id() == token!("@data:arp"))
+            .find(|p| p.id() == token!("@data:arp") || p.id() == token!("@data:rarp"))
         {
             data = payload.data();
         } else {
@@ -24,6 +29,11 @@ impl Worker for ArpWorker {
         }
 
         let mut layer = Layer::new(&ARP_CLASS, data);
+        if data.len() < HEADER_LEN {
+            layer.error("truncated header");
+            parent.add_child(layer);
+            return Ok(Status::Done);
+        }
 
         let hw_type = HWTYPE_ATTR_HEADER.try_get(&layer)?.try_into()?;
         let hw = get_hw(hw_type);
@@ -45,9 +55,19 @@ impl Worker for ArpWorker {
             layer.add_attr(attr!(attr, range: 6..8));
         }
 
+        let end = HEADER_LEN + (hlen + plen) * 2;
+        if data.len() < end {
+            layer.error("truncated addresses");
+            parent.add_child(layer);
+            return Ok(Status::Done);
+        }
+
+        let spa_range = HEADER_LEN + hlen..HEADER_LEN + hlen + plen;
+        let tpa_range = end - plen..end;
+
         if let Some((_, sha, tha)) = hw {
             if let Some((_, spa, tpa)) = proto {
-                let mut offset = 8;
+                let mut offset = HEADER_LEN;
                 layer.add_attr(attr!(sha, range: offset..offset + hlen));
                 offset += hlen;
                 layer.add_attr(attr!(spa, range: offset..offset + plen));
@@ -58,6 +78,20 @@ impl Worker for ArpWorker {
             }
         }
 
+        // Gratuitous ARP announces the sender's own address, and an ARP probe
+        // (RFC 5227) asks for an address with an all-zero sender address.
+        if (op_type == OP_REQUEST || op_type == OP_REPLY) && plen > 0 {
+            let spa = &data[spa_range.clone()];
+            if spa == &data[tpa_range] {
+                layer.add_attr(attr!(&GRATUITOUS_ATTR, range: spa_range));
+                layer.annotate(
+                    Annotation::new(Severity::Note, "gratuitous ARP").with_attr("arp.gratuitous"),
+                );
+            } else if op_type == OP_REQUEST && spa.iter().all(|&b| b == 0) {
+                layer.add_attr(attr!(&PROBE_ATTR, range: spa_range));
+            }
+        }
+
         parent.add_child(layer);
         Ok(Status::Done)
     }
@@ -73,7 +107,16 @@ impl Decoder for ArpDecoder {
 
     fn metadata(&self) -> Metadata {
         Metadata {
+            id: "app.genet.decoder.arp".into(),
+            name: "ARP".into(),
+            description: "Address Resolution Protocol".into(),
             exec_type: ExecType::ParallelSync,
+            references: vec![
+                "https://tools.ietf.org/html/rfc826".into(),
+                "https://tools.ietf.org/html/rfc903".into(),
+                "https://tools.ietf.org/html/rfc2390".into(),
+                "https://tools.ietf.org/html/rfc5227".into(),
+            ],
             ..Metadata::default()
         }
     }
@@ -111,15 +154,33 @@ def_attr_class!(PLEN_ATTR, "arp.plen", cast: cast::UInt8());
 
 def_attr_class!(OP_ATTR, "arp.op",
     cast: cast::UInt16BE(),
-    typ: "@enum"
+    typ: "@enum",
+    labels: vec![
+        (1, "REQUEST"),
+        (2, "REPLY"),
+        (3, "RARP REQUEST"),
+        (4, "RARP REPLY"),
+        (8, "InARP REQUEST"),
+        (9, "InARP REPLY"),
+    ]
+);
+
+def_attr_class!(GRATUITOUS_ATTR, "arp.gratuitous",
+    typ: "@novalue",
+    value: true
+);
+
+def_attr_class!(PROBE_ATTR, "arp.probe",
+    typ: "@novalue",
+    value: true
 );
 
 fn get_hw(val: u64) -> Option<(&'static AttrClass, &'static AttrClass, &'static AttrClass)> {
     match val {
         0x0001 => Some((
             attr_class_lazy!("arp.hwtype.eth", typ: "@novalue", value: true),
-            attr_class_lazy!("arp.sha", typ: "@eth:mac", cast: cast::ByteSlice()),
-            attr_class_lazy!("arp.tha", typ: "@eth:mac", cast: cast::ByteSlice()),
+            attr_class_lazy!("arp.sha", typ: "@eth:mac", cast: cast::MacAddr()),
+            attr_class_lazy!("arp.tha", typ: "@eth:mac", cast: cast::MacAddr()),
         )),
         _ => None,
     }
@@ -134,8 +195,8 @@ fn get_proto(val: u64) -> Option<(&'static AttrClass, &'static AttrClass, &'stat
         )),
         0x86DD => Some((
             attr_class_lazy!("arp.protocol.ipv6", typ: "@novalue", value: true),
-            attr_class_lazy!("arp.spa", typ: "@ipv6:addr", cast: cast::ByteSlice()),
-            attr_class_lazy!("arp.tpa", typ: "@ipv6:addr", cast: cast::ByteSlice()),
+            attr_class_lazy!("arp.spa", typ: "@ipv6:addr", cast: cast::Ipv6Addr()),
+            attr_class_lazy!("arp.tpa", typ: "@ipv6:addr", cast: cast::Ipv6Addr()),
         )),
         _ => None,
     }
@@ -145,6 +206,10 @@ fn get_op(val: u64) -> Option<&'static AttrClass> {
     match val {
         0x0001 => Some(attr_class_lazy!("arp.op.request", typ: "@novalue", value: true)),
         0x0002 => Some(attr_class_lazy!("arp.op.reply", typ: "@novalue", value: true)),
+        0x0003 => Some(attr_class_lazy!("arp.op.rarpRequest", typ: "@novalue", value: true)),
+        0x0004 => Some(attr_class_lazy!("arp.op.rarpReply", typ: "@novalue", value: true)),
+        0x0008 => Some(attr_class_lazy!("arp.op.inarpRequest", typ: "@novalue", value: true)),
+        0x0009 => Some(attr_class_lazy!("arp.op.inarpReply", typ: "@novalue", value: true)),
         _ => None,
     }
 }
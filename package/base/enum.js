@@ -10,7 +10,8 @@ class Enum {
         return genet.session.tokenName(id)
       })
       .join(', ')
-    return m('span', [flags, ' (', value, ')'])
+    const { label } = vnode.attrs.attr
+    return m('span', [label || flags, ' (', value, ')'])
   }
 }
 module.exports = Enum
@@ -0,0 +1,158 @@
+//! CAN bus decoder for the Linux SocketCAN link type.
+//!
+//! The data bytes are added as a `@data:can` payload. Signal decoders, such
+//! as ones generated from DBC files, can be implemented as separate decoders
+//! which take the payload and look up `can.id` of the parent layer to select
+//! the message definition.
+
+extern crate genet_sdk;
+
+use genet_sdk::{cast, decoder::*, prelude::*};
+
+const HEADER_LEN: usize = 8;
+const MAX_DATA_LEN: usize = 8;
+const MAX_FD_DATA_LEN: usize = 64;
+
+const FLAG_EXTENDED: u32 = 0x8000_0000;
+const FLAG_REMOTE: u32 = 0x4000_0000;
+const FLAG_ERROR: u32 = 0x2000_0000;
+
+const FD_FLAG_FDF: u8 = 0x04;
+
+struct CanWorker {}
+
+impl Worker for CanWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        if parent.id() != token!("[link-227]") {
+            return Ok(Status::Skip);
+        }
+
+        let data = parent.data();
+        let mut layer = Layer::new(&CAN_CLASS, data);
+        if data.len() < HEADER_LEN {
+            layer.error("truncated header");
+            parent.add_child(layer);
+            return Ok(Status::Done);
+        }
+
+        let id = (data[0] as u32) << 24
+            | (data[1] as u32) << 16
+            | (data[2] as u32) << 8
+            | data[3] as u32;
+        let len = data[4] as usize;
+
+        // CAN FD frames are padded to 64 bytes of data.
+        let fd = data[5] & FD_FLAG_FDF != 0 || data.len() == HEADER_LEN + MAX_FD_DATA_LEN;
+        if fd {
+            layer.add_attr(attr!(&FD_ATTR));
+            layer.add_attr(attr!(&FD_FLAGS_ATTR, range: 5..6));
+            layer.add_attr(attr!(&FD_BRS_ATTR, bit_range: 5 7..8));
+            layer.add_attr(attr!(&FD_ESI_ATTR, bit_range: 5 6..7));
+        }
+
+        let max_len = if fd { MAX_FD_DATA_LEN } else { MAX_DATA_LEN };
+        if len > max_len {
+            layer.annotate(
+                Annotation::new(Severity::Error, "invalid data length").with_attr("can.length"),
+            );
+        } else if HEADER_LEN + len > data.len() {
+            layer.error("truncated data");
+        } else if id & FLAG_REMOTE == 0 {
+            let payload = data.try_get(HEADER_LEN..HEADER_LEN + len)?;
+            layer.add_attr(attr!(&DATA_ATTR, range: HEADER_LEN..HEADER_LEN + len));
+            if id & FLAG_ERROR != 0 {
+                layer.add_attr(attr!(&ERROR_CLASS_ATTR, range: 0..4));
+            } else {
+                layer.add_payload(Payload::new(payload, "@data:can"));
+            }
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct CanDecoder {}
+
+impl Decoder for CanDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(CanWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.decoder.can".into(),
+            name: "CAN".into(),
+            description: "Controller Area Network (SocketCAN)".into(),
+            exec_type: ExecType::ParallelSync,
+            references: vec![
+                "https://www.kernel.org/doc/Documentation/networking/can.txt".into(),
+                "https://www.tcpdump.org/linktypes/LINKTYPE_CAN_SOCKETCAN.html".into(),
+            ],
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(CAN_CLASS, "can",
+    header: attr!(&ID_ATTR, range: 0..4),
+    header: attr!(&FLAGS_EXTENDED_ATTR, bit_range: 0 0..1),
+    header: attr!(&FLAGS_REMOTE_ATTR, bit_range: 0 1..2),
+    header: attr!(&FLAGS_ERROR_ATTR, bit_range: 0 2..3),
+    header: attr!(&LENGTH_ATTR, range: 4..5)
+);
+
+def_attr_class!(ID_ATTR, "can.id",
+    cast: cast::UInt32BE().map(|v| if v & FLAG_EXTENDED != 0 {
+        v & 0x1fff_ffff
+    } else {
+        v & 0x7ff
+    })
+);
+
+def_attr_class!(FLAGS_EXTENDED_ATTR, "can.flags.extended",
+    cast: cast::UInt8().map(|v| v & 0b10000000 != 0)
+);
+
+def_attr_class!(FLAGS_REMOTE_ATTR, "can.flags.remote",
+    cast: cast::UInt8().map(|v| v & 0b01000000 != 0)
+);
+
+def_attr_class!(FLAGS_ERROR_ATTR, "can.flags.error",
+    cast: cast::UInt8().map(|v| v & 0b00100000 != 0)
+);
+
+def_attr_class!(LENGTH_ATTR, "can.length", cast: cast::UInt8());
+
+def_attr_class!(FD_ATTR, "can.fd",
+    typ: "@novalue",
+    value: true
+);
+
+def_attr_class!(FD_FLAGS_ATTR, "can.fd.flags",
+    typ: "@flags",
+    cast: cast::UInt8()
+);
+
+def_attr_class!(FD_BRS_ATTR, "can.fd.flags.brs",
+    cast: cast::UInt8().map(|v| v & 0b00000001 != 0)
+);
+
+def_attr_class!(FD_ESI_ATTR, "can.fd.flags.esi",
+    cast: cast::UInt8().map(|v| v & 0b00000010 != 0)
+);
+
+def_attr_class!(ERROR_CLASS_ATTR, "can.errorClass",
+    typ: "@flags",
+    cast: cast::UInt32BE().map(|v| v & 0x1fff_ffff)
+);
+
+def_attr_class!(DATA_ATTR, "can.data", cast: cast::ByteSlice());
+
+genet_decoders!(CanDecoder {});
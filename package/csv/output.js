@@ -0,0 +1,43 @@
+const m = require('mithril')
+const genet = require('@genet/api')
+const { remote: { dialog } } = require('electron')
+class OutputView {
+  view (vnode) {
+    const columns = genet.config.get('@genet/csv.columns') || []
+    return m('ul', [
+      m('li', [
+        m('input', {
+          type: 'text',
+          name: 'columns',
+          placeholder: 'Columns (e.g. frame.index, ipv4.src, tcp.dst)',
+          value: columns.join(', '),
+        })
+      ]),
+      m('li', [
+        m('input', {
+          type: 'button',
+          value: 'Export',
+          onclick: () => {
+            const file = dialog.showSaveDialog({
+              properties: ['openFile'],
+              filters: [{
+                name: 'CSV File',
+                extensions: ['csv'],
+              }],
+            })
+            if (typeof file !== 'undefined') {
+              const list = vnode.dom.querySelector('[name=columns]').value
+                .split(',')
+                .map((id) => id.trim())
+                .filter((id) => id.length > 0)
+              vnode.attrs.callback('app.genet.writer.csv',
+                { file, columns: list })
+            }
+          },
+        })
+      ])
+    ])
+  }
+}
+
+module.exports = OutputView
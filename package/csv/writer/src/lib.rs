@@ -0,0 +1,161 @@
+extern crate genet_sdk;
+extern crate serde;
+extern crate serde_json;
+
+#[macro_use]
+extern crate serde_derive;
+
+use genet_sdk::{prelude::*, variant::Variant, writer::*};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+#[derive(Deserialize)]
+struct Arg {
+    file: String,
+    columns: Vec<String>,
+}
+
+#[derive(Clone)]
+struct CsvWriter {}
+
+impl Writer for CsvWriter {
+    fn new_worker(&self, _ctx: &Context, arg: &str) -> Result<Box<Worker>> {
+        let arg: Arg = serde_json::from_str(arg)?;
+        let file = File::create(&arg.file)?;
+        let mut worker = CsvWorker {
+            writer: BufWriter::new(file),
+            columns: arg
+                .columns
+                .iter()
+                .map(|id| match id.as_str() {
+                    "frame.index" => Column::Index,
+                    _ => Column::Attr(Token::from(id.as_str())),
+                })
+                .collect(),
+        };
+        worker.write_row(&arg.columns)?;
+        Ok(Box::new(worker))
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.writer.csv".into(),
+            filters: vec![FileType::new("CSV File", &["csv"])],
+            ..Metadata::default()
+        }
+    }
+}
+
+enum Column {
+    /// The frame index, which is not an attribute.
+    Index,
+    Attr(Token),
+}
+
+struct CsvWorker {
+    writer: BufWriter<File>,
+    columns: Vec<Column>,
+}
+
+/// Quotes the field if it contains a delimiter, a quote or a line break.
+fn escape(field: &str) -> String {
+    if field.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn mac(data: &[u8]) -> String {
+    data.iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+fn format(attr: &Attr, layer: &Layer) -> String {
+    let value = match attr.try_get(layer) {
+        Ok(value) => value,
+        Err(_) => return String::new(),
+    };
+    let typ = attr.typ();
+    match value {
+        Variant::Nil => String::new(),
+        Variant::Bool(b) => b.to_string(),
+        Variant::Int64(v) => v.to_string(),
+        Variant::UInt64(v) => v.to_string(),
+        Variant::Float64(v) => v.to_string(),
+        Variant::String(s) => s.into(),
+        Variant::BigInt(b) => format!("0x{}", hex(&b)),
+        Variant::Buffer(b) => hex(&b),
+        Variant::Slice(s) => {
+            if typ == token!("@ipv4:addr") && s.len() == 4 {
+                Ipv4Addr::new(s[0], s[1], s[2], s[3]).to_string()
+            } else if typ == token!("@ipv6:addr") && s.len() == 16 {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(&s);
+                Ipv6Addr::from(octets).to_string()
+            } else if typ == token!("@eth:mac") {
+                mac(&s)
+            } else {
+                hex(&s)
+            }
+        }
+        Variant::Ipv6Addr(addr) => addr.to_string(),
+        Variant::MacAddr(addr) => mac(&addr),
+        Variant::Timestamp(ns) => ns.to_string(),
+    }
+}
+
+impl CsvWorker {
+    fn write_row<T: AsRef<str>>(&mut self, fields: &[T]) -> Result<()> {
+        let row = fields
+            .iter()
+            .map(|field| escape(field.as_ref()))
+            .collect::<Vec<_>>()
+            .join(",");
+        self.writer.write_all(row.as_bytes())?;
+        self.writer.write_all(b"\r\n")?;
+        Ok(())
+    }
+}
+
+impl Worker for CsvWorker {
+    fn write(&mut self, index: u32, stack: &LayerStack) -> Result<()> {
+        let row = self
+            .columns
+            .iter()
+            .map(|column| match column {
+                Column::Index => index.to_string(),
+                Column::Attr(id) => stack
+                    .layers()
+                    .rev()
+                    .find(|layer| layer.attr(*id).is_some())
+                    .map(|layer| {
+                        // Repeated attributes are joined into a single field.
+                        layer
+                            .attrs_by_id(*id)
+                            .map(|attr| format(attr, layer))
+                            .collect::<Vec<_>>()
+                            .join(";")
+                    })
+                    .unwrap_or_default(),
+            })
+            .collect::<Vec<_>>();
+        self.write_row(&row)
+    }
+
+    fn end(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+genet_writers!(CsvWriter {});
@@ -0,0 +1,389 @@
+extern crate genet_sdk;
+
+use genet_sdk::{cast, decoder::*, prelude::*};
+
+const SERVER_PORT: u16 = 67;
+const CLIENT_PORT: u16 = 68;
+
+const BOOTP_LEN: usize = 236;
+const MAGIC_COOKIE: [u8; 4] = [0x63, 0x82, 0x53, 0x63];
+const OPTIONS_OFFSET: usize = BOOTP_LEN + 4;
+
+const OPTION_PAD: u8 = 0;
+const OPTION_END: u8 = 255;
+
+struct DhcpWorker {}
+
+impl Worker for DhcpWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        if parent.id() != token!("udp") {
+            return Ok(Status::Skip);
+        }
+
+        let data;
+
+        if let Some(payload) = parent.payloads().iter().next() {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let mut layer = Layer::new(&DHCP_CLASS, data);
+        if data.len() < BOOTP_LEN {
+            layer.error("truncated header");
+            parent.add_child(layer);
+            return Ok(Status::Done);
+        }
+
+        let hlen: usize = HLEN_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        if hlen > 16 {
+            layer.annotate(
+                Annotation::new(Severity::Error, "invalid hardware address length")
+                    .with_attr("dhcp.hlen"),
+            );
+        } else if hlen == 6 {
+            layer.add_attr(attr!(&CHADDR_MAC_ATTR, range: 28..34));
+        } else {
+            layer.add_attr(attr!(&CHADDR_ATTR, range: 28..28 + hlen));
+        }
+        if data[44] != 0 {
+            layer.add_attr(attr!(&SNAME_ATTR, range: 44..44 + cstr_len(&data[44..108])));
+        }
+        if data[108] != 0 {
+            layer.add_attr(attr!(&FILE_ATTR, range: 108..108 + cstr_len(&data[108..236])));
+        }
+
+        // Plain BOOTP messages may have no magic cookie.
+        if data.get(BOOTP_LEN..OPTIONS_OFFSET) == Some(&MAGIC_COOKIE[..]) {
+            layer.add_attr(attr!(&COOKIE_ATTR, range: BOOTP_LEN..OPTIONS_OFFSET));
+            decode_options(&mut layer);
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+/// Returns the length of the NUL-terminated string.
+fn cstr_len(data: &[u8]) -> usize {
+    data.iter().position(|&b| b == 0).unwrap_or(data.len())
+}
+
+fn decode_options(layer: &mut Layer) {
+    let data = layer.data();
+    let mut pos = OPTIONS_OFFSET;
+    loop {
+        match data.get(pos) {
+            None => {
+                layer.warn("missing end option");
+                return;
+            }
+            Some(&OPTION_END) => return,
+            Some(&OPTION_PAD) => {
+                pos += 1;
+                continue;
+            }
+            Some(_) => {}
+        }
+        let len = match data.get(pos + 1) {
+            Some(&len) if pos + 2 + len as usize <= data.len() => len as usize,
+            _ => {
+                layer.error("truncated option");
+                return;
+            }
+        };
+        let code = data[pos];
+        let value = pos + 2..pos + 2 + len;
+        layer.add_attr(attr!(&OPTION_ATTR, range: pos..value.end));
+        layer.add_attr(attr!(&OPTION_CODE_ATTR, range: pos..pos + 1));
+        layer.add_attr(attr!(&OPTION_LENGTH_ATTR, range: pos + 1..pos + 2));
+        layer.add_attr(attr!(&OPTION_DATA_ATTR, range: value.clone()));
+
+        if let Some((class, unit)) = get_option(code) {
+            if unit == 0 {
+                layer.add_attr(attr!(class, range: value.clone()));
+            } else if len == 0 || len % unit != 0 {
+                layer.annotate(
+                    Annotation::new(Severity::Error, "invalid option length")
+                        .with_attr("dhcp.option.length"),
+                );
+            } else if len != unit && !attr!(class).is_repeated() {
+                layer.annotate(
+                    Annotation::new(Severity::Error, "invalid option length")
+                        .with_attr("dhcp.option.length"),
+                );
+            } else {
+                for offset in value.clone().step_by(unit) {
+                    layer.add_attr(attr!(class, range: offset..offset + unit));
+                }
+            }
+        }
+        pos = value.end;
+    }
+}
+
+/// Returns the attribute class of the option and the length of its value.
+///
+/// The length of zero means a variable-length value.
+fn get_option(code: u8) -> Option<(&'static AttrClass, usize)> {
+    match code {
+        1 => Some((
+            attr_class_lazy!("dhcp.subnetMask", typ: "@ipv4:addr", cast: cast::ByteSlice()),
+            4,
+        )),
+        3 => Some((
+            attr_class_lazy!("dhcp.router",
+                typ: "@ipv4:addr",
+                repeated: ,
+                cast: cast::ByteSlice()
+            ),
+            4,
+        )),
+        6 => Some((
+            attr_class_lazy!("dhcp.dnsServer",
+                typ: "@ipv4:addr",
+                repeated: ,
+                cast: cast::ByteSlice()
+            ),
+            4,
+        )),
+        12 => Some((attr_class_lazy!("dhcp.hostname", cast: cast::Utf8()), 0)),
+        15 => Some((attr_class_lazy!("dhcp.domainName", cast: cast::Utf8()), 0)),
+        28 => Some((
+            attr_class_lazy!("dhcp.broadcastAddress", typ: "@ipv4:addr", cast: cast::ByteSlice()),
+            4,
+        )),
+        42 => Some((
+            attr_class_lazy!("dhcp.ntpServer",
+                typ: "@ipv4:addr",
+                repeated: ,
+                cast: cast::ByteSlice()
+            ),
+            4,
+        )),
+        50 => Some((
+            attr_class_lazy!("dhcp.requestedAddress", typ: "@ipv4:addr", cast: cast::ByteSlice()),
+            4,
+        )),
+        51 => Some((
+            attr_class_lazy!("dhcp.leaseTime", cast: cast::UInt32BE()),
+            4,
+        )),
+        53 => Some((&MESSAGE_TYPE_ATTR, 1)),
+        54 => Some((
+            attr_class_lazy!("dhcp.serverIdentifier", typ: "@ipv4:addr", cast: cast::ByteSlice()),
+            4,
+        )),
+        55 => Some((
+            attr_class_lazy!("dhcp.parameterRequest",
+                typ: "@enum",
+                repeated: ,
+                cast: cast::UInt8(),
+                labels: option_labels()
+            ),
+            1,
+        )),
+        56 => Some((attr_class_lazy!("dhcp.message", cast: cast::Utf8()), 0)),
+        57 => Some((
+            attr_class_lazy!("dhcp.maxMessageSize", cast: cast::UInt16BE()),
+            2,
+        )),
+        58 => Some((
+            attr_class_lazy!("dhcp.renewalTime", cast: cast::UInt32BE()),
+            4,
+        )),
+        59 => Some((
+            attr_class_lazy!("dhcp.rebindingTime", cast: cast::UInt32BE()),
+            4,
+        )),
+        60 => Some((
+            attr_class_lazy!("dhcp.vendorClassIdentifier", cast: cast::Utf8()),
+            0,
+        )),
+        61 => Some((
+            attr_class_lazy!("dhcp.clientIdentifier", cast: cast::ByteSlice()),
+            0,
+        )),
+        _ => None,
+    }
+}
+
+fn option_labels() -> Vec<(u64, &'static str)> {
+    vec![
+        (1, "Subnet Mask"),
+        (2, "Time Offset"),
+        (3, "Router"),
+        (6, "Domain Name Server"),
+        (12, "Host Name"),
+        (15, "Domain Name"),
+        (26, "Interface MTU"),
+        (28, "Broadcast Address"),
+        (42, "NTP Servers"),
+        (43, "Vendor-Specific Information"),
+        (44, "NetBIOS Name Server"),
+        (50, "Requested IP Address"),
+        (51, "IP Address Lease Time"),
+        (52, "Option Overload"),
+        (53, "DHCP Message Type"),
+        (54, "Server Identifier"),
+        (55, "Parameter Request List"),
+        (56, "Message"),
+        (57, "Maximum DHCP Message Size"),
+        (58, "Renewal Time Value"),
+        (59, "Rebinding Time Value"),
+        (60, "Vendor Class Identifier"),
+        (61, "Client Identifier"),
+        (66, "TFTP Server Name"),
+        (67, "Bootfile Name"),
+        (81, "Client FQDN"),
+        (82, "Relay Agent Information"),
+        (119, "Domain Search"),
+        (121, "Classless Static Route"),
+        (255, "End"),
+    ]
+}
+
+#[derive(Clone)]
+struct DhcpDecoder {}
+
+impl Decoder for DhcpDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(DhcpWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.decoder.dhcp".into(),
+            name: "DHCP".into(),
+            description: "Dynamic Host Configuration Protocol".into(),
+            exec_type: ExecType::ParallelSync,
+            triggers: vec![
+                Trigger::new("udp.dst", SERVER_PORT.into()),
+                Trigger::new("udp.dst", CLIENT_PORT.into()),
+            ],
+            references: vec![
+                "https://tools.ietf.org/html/rfc951".into(),
+                "https://tools.ietf.org/html/rfc2131".into(),
+                "https://tools.ietf.org/html/rfc2132".into(),
+            ],
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(DHCP_CLASS, "dhcp",
+    header: attr!(&OP_ATTR, range: 0..1),
+    header: attr!(&HTYPE_ATTR, range: 1..2),
+    header: &HLEN_ATTR_HEADER,
+    header: attr!(&HOPS_ATTR, range: 3..4),
+    header: attr!(&XID_ATTR, range: 4..8),
+    header: attr!(&SECS_ATTR, range: 8..10),
+    header: attr!(&FLAGS_ATTR, range: 10..12),
+    header: attr!(&BROADCAST_ATTR, bit_range: 10 0..1),
+    header: attr!(&CIADDR_ATTR, range: 12..16),
+    header: attr!(&YIADDR_ATTR, range: 16..20),
+    header: attr!(&SIADDR_ATTR, range: 20..24),
+    header: attr!(&GIADDR_ATTR, range: 24..28)
+);
+
+def_attr!(HLEN_ATTR_HEADER, &HLEN_ATTR, range: 2..3);
+
+def_attr_class!(OP_ATTR, "dhcp.op",
+    typ: "@enum",
+    cast: cast::UInt8(),
+    labels: vec![(1, "BOOTREQUEST"), (2, "BOOTREPLY")]
+);
+
+def_attr_class!(HTYPE_ATTR, "dhcp.htype",
+    typ: "@enum",
+    cast: cast::UInt8(),
+    labels: vec![(1, "Ethernet"), (6, "IEEE 802"), (32, "InfiniBand")]
+);
+
+def_attr_class!(HLEN_ATTR, "dhcp.hlen", cast: cast::UInt8());
+
+def_attr_class!(HOPS_ATTR, "dhcp.hops", cast: cast::UInt8());
+
+def_attr_class!(XID_ATTR, "dhcp.xid", cast: cast::UInt32BE());
+
+def_attr_class!(SECS_ATTR, "dhcp.secs", cast: cast::UInt16BE());
+
+def_attr_class!(FLAGS_ATTR, "dhcp.flags",
+    typ: "@flags",
+    cast: cast::UInt16BE()
+);
+
+def_attr_class!(BROADCAST_ATTR, "dhcp.flags.broadcast",
+    cast: cast::UInt8().map(|v| (v & 0b1000_0000) != 0)
+);
+
+def_attr_class!(CIADDR_ATTR, "dhcp.ciaddr",
+    typ: "@ipv4:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(YIADDR_ATTR, "dhcp.yiaddr",
+    typ: "@ipv4:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(SIADDR_ATTR, "dhcp.siaddr",
+    typ: "@ipv4:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(GIADDR_ATTR, "dhcp.giaddr",
+    typ: "@ipv4:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(CHADDR_MAC_ATTR, "dhcp.chaddr",
+    typ: "@eth:mac",
+    cast: cast::MacAddr()
+);
+
+def_attr_class!(CHADDR_ATTR, "dhcp.chaddr", cast: cast::ByteSlice());
+
+def_attr_class!(SNAME_ATTR, "dhcp.sname", cast: cast::Utf8());
+
+def_attr_class!(FILE_ATTR, "dhcp.file", cast: cast::Utf8());
+
+def_attr_class!(COOKIE_ATTR, "dhcp.magicCookie", cast: cast::ByteSlice());
+
+def_attr_class!(OPTION_ATTR, "dhcp.option",
+    typ: "@nested",
+    repeated: ,
+    value: true
+);
+
+def_attr_class!(OPTION_CODE_ATTR, "dhcp.option.code",
+    typ: "@enum",
+    cast: cast::UInt8(),
+    labels: option_labels()
+);
+
+def_attr_class!(OPTION_LENGTH_ATTR, "dhcp.option.length", cast: cast::UInt8());
+
+def_attr_class!(OPTION_DATA_ATTR, "dhcp.option.data", cast: cast::ByteSlice());
+
+def_attr_class!(MESSAGE_TYPE_ATTR, "dhcp.messageType",
+    typ: "@enum",
+    cast: cast::UInt8(),
+    labels: vec![
+        (1, "DHCPDISCOVER"),
+        (2, "DHCPOFFER"),
+        (3, "DHCPREQUEST"),
+        (4, "DHCPDECLINE"),
+        (5, "DHCPACK"),
+        (6, "DHCPNAK"),
+        (7, "DHCPRELEASE"),
+        (8, "DHCPINFORM"),
+    ]
+);
+
+genet_decoders!(DhcpDecoder {});
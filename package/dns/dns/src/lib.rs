@@ -0,0 +1,646 @@
+extern crate genet_sdk;
+
+use genet_sdk::{
+    cast::{self, Typed},
+    context::state_key,
+    decoder::*,
+    prelude::*,
+};
+use std::io;
+
+const PORT: u16 = 53;
+const HEADER_LEN: usize = 12;
+const MAX_POINTERS: usize = 64;
+
+const TYPE_A: u16 = 1;
+const TYPE_NS: u16 = 2;
+const TYPE_CNAME: u16 = 5;
+const TYPE_PTR: u16 = 12;
+const TYPE_MX: u16 = 15;
+const TYPE_TXT: u16 = 16;
+const TYPE_AAAA: u16 = 28;
+const TYPE_SRV: u16 = 33;
+const TYPE_OPT: u16 = 41;
+
+struct Query {
+    frame: u32,
+    timestamp: f64,
+}
+
+struct DnsWorker {}
+
+impl Worker for DnsWorker {
+    fn decode(
+        &mut self,
+        ctx: &mut Context,
+        stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        if parent.id() != token!("udp") {
+            return Ok(Status::Skip);
+        }
+
+        let data;
+
+        if let Some(payload) = parent.payloads().iter().next() {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let src: u16 = parent
+            .attr(token!("udp.src"))
+            .unwrap()
+            .try_get(parent)?
+            .try_into()?;
+
+        let dst: u16 = parent
+            .attr(token!("udp.dst"))
+            .unwrap()
+            .try_get(parent)?
+            .try_into()?;
+
+        let mut layer = Layer::new(&DNS_CLASS, data);
+        if data.len() < HEADER_LEN {
+            layer.error("truncated header");
+            parent.add_child(layer);
+            return Ok(Status::Done);
+        }
+
+        if parse_sections(&mut layer).is_err() {
+            layer.error("malformed message");
+        }
+        link(ctx, stack, parent, &mut layer, (src, dst))?;
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+/// Links the responses to the queries with the same ID and endpoints.
+fn link(
+    ctx: &mut Context,
+    stack: &LayerStack,
+    parent: &Parent,
+    layer: &mut Layer,
+    ports: (u16, u16),
+) -> Result<()> {
+    let ip = match stack.parent(parent) {
+        Some(ip) => ip,
+        None => return Ok(()),
+    };
+    let addr = |id| {
+        ip.attr(id)
+            .and_then(|attr| ip.data().try_get(attr.range()).ok())
+    };
+    let (src, dst) = match (addr(token!("_.src")), addr(token!("_.dst"))) {
+        (Some(src), Some(dst)) => ((src, ports.0), (dst, ports.1)),
+        _ => return Ok(()),
+    };
+
+    let id: u16 = ID_ATTR_HEADER.try_get(layer)?.try_into()?;
+    let response: bool = QR_ATTR_HEADER.try_get(layer)?.try_into()?;
+    let timestamp: f64 = stack
+        .bottom()
+        .and_then(|link| {
+            link.attr(token!("link.timestamp"))
+                .and_then(|attr| attr.try_get(link).ok())
+        })
+        .and_then(|value| value.try_into().ok())
+        .unwrap_or(0.0);
+
+    if response {
+        let key = state_key(&(id, &(dst.0)[..], dst.1, &(src.0)[..], src.1));
+        let query = ctx
+            .state::<Query>(key)
+            .map(|query| (query.frame, query.timestamp));
+        if let Some((frame, query_timestamp)) = query {
+            layer.add_link(Link::new("dns.query", frame).with_reverse("dns.response"));
+            layer.add_attr(attr!(&TIME_ATTR, value: timestamp - query_timestamp));
+        }
+    } else {
+        let key = state_key(&(id, &(src.0)[..], src.1, &(dst.0)[..], dst.1));
+        let frame = ctx.frame_index();
+        ctx.set_state(key, Query { frame, timestamp });
+    }
+    Ok(())
+}
+
+fn parse_sections(layer: &mut Layer) -> Result<()> {
+    let data = layer.data();
+    let header = data.try_get(0..HEADER_LEN)?;
+    let count =
+        |offset: usize| (usize::from(header[offset]) << 8) | usize::from(header[offset + 1]);
+    let (questions, answers, authorities, additionals) = (count(4), count(6), count(8), count(10));
+
+    let mut offset = HEADER_LEN;
+    for _ in 0..questions {
+        let (_, len) = read_name(&data, offset)?;
+        let end = offset + len + 4;
+        data.try_get(offset..end)?;
+        layer.add_attr(attr!(&QUESTION_ATTR, range: offset..end));
+        layer.add_attr(attr!(&QUESTION_NAME_ATTR, range: offset..offset + len));
+        layer.add_attr(attr!(&QUESTION_TYPE_ATTR, range: end - 4..end - 2));
+        layer.add_attr(attr!(&QUESTION_CLASS_ATTR, range: end - 2..end));
+        offset = end;
+    }
+
+    let sections = [
+        (answers, &*ANSWER),
+        (authorities, &*AUTHORITY),
+        (additionals, &*ADDITIONAL),
+    ];
+    for &(count, section) in sections.iter() {
+        for _ in 0..count {
+            offset = add_record(layer, &data, section, offset)?;
+        }
+    }
+    Ok(())
+}
+
+fn add_record(
+    layer: &mut Layer,
+    data: &ByteSlice,
+    section: &'static RecordClass,
+    offset: usize,
+) -> Result<usize> {
+    let (_, len) = read_name(data, offset)?;
+    let fixed = offset + len;
+    let header = data.try_get(fixed..fixed + 10)?;
+    let typ = u16::from(header[0]) << 8 | u16::from(header[1]);
+    let rdlen = (usize::from(header[8]) << 8) | usize::from(header[9]);
+    let rdata = fixed + 10;
+    let end = rdata + rdlen;
+    data.try_get(rdata..end)?;
+
+    if typ == TYPE_OPT {
+        add_opt(layer, data, offset, fixed, end)?;
+        return Ok(end);
+    }
+
+    layer.add_attr(attr!(&section.record, range: offset..end));
+    layer.add_attr(attr!(&section.name, range: offset..fixed));
+    layer.add_attr(attr!(&section.typ, range: fixed..fixed + 2));
+    layer.add_attr(attr!(&section.class, range: fixed + 2..fixed + 4));
+    layer.add_attr(attr!(&section.ttl, range: fixed + 4..fixed + 8));
+    layer.add_attr(attr!(&section.length, range: fixed + 8..fixed + 10));
+
+    match typ {
+        TYPE_A => layer.add_attr(attr!(&section.a, range: rdata..end)),
+        TYPE_AAAA => layer.add_attr(attr!(&section.aaaa, range: rdata..end)),
+        TYPE_NS => layer.add_attr(attr!(&section.ns, range: rdata..end)),
+        TYPE_CNAME => layer.add_attr(attr!(&section.cname, range: rdata..end)),
+        TYPE_PTR => layer.add_attr(attr!(&section.ptr, range: rdata..end)),
+        TYPE_MX => {
+            layer.add_attr(attr!(&section.mx, range: rdata..end));
+            layer.add_attr(attr!(&section.mx_preference, range: rdata..rdata + 2));
+            layer.add_attr(attr!(&section.mx_exchange, range: rdata + 2..end));
+        }
+        TYPE_TXT => {
+            let mut pos = rdata;
+            while pos < end {
+                let len = data.try_get(pos)? as usize;
+                data.try_get(pos + 1..pos + 1 + len)?;
+                layer.add_attr(attr!(&section.txt, range: pos + 1..pos + 1 + len));
+                pos += 1 + len;
+            }
+        }
+        TYPE_SRV => {
+            layer.add_attr(attr!(&section.srv, range: rdata..end));
+            layer.add_attr(attr!(&section.srv_priority, range: rdata..rdata + 2));
+            layer.add_attr(attr!(&section.srv_weight, range: rdata + 2..rdata + 4));
+            layer.add_attr(attr!(&section.srv_port, range: rdata + 4..rdata + 6));
+            layer.add_attr(attr!(&section.srv_target, range: rdata + 6..end));
+        }
+        _ => layer.add_attr(attr!(&section.data, range: rdata..end)),
+    }
+    Ok(end)
+}
+
+/// Adds the EDNS0 attributes of the OPT pseudo-record.
+fn add_opt(
+    layer: &mut Layer,
+    data: &ByteSlice,
+    offset: usize,
+    fixed: usize,
+    end: usize,
+) -> Result<()> {
+    layer.add_attr(attr!(&EDNS_ATTR, range: offset..end));
+    layer.add_attr(attr!(&EDNS_UDP_SIZE_ATTR, range: fixed + 2..fixed + 4));
+    layer.add_attr(attr!(&EDNS_RCODE_ATTR, range: fixed + 4..fixed + 5));
+    layer.add_attr(attr!(&EDNS_VERSION_ATTR, range: fixed + 5..fixed + 6));
+    layer.add_attr(attr!(&EDNS_DO_ATTR, bit_range: fixed + 6 0..1));
+
+    let mut pos = fixed + 10;
+    while pos + 4 <= end {
+        let header = data.try_get(pos..pos + 4)?;
+        let len = (usize::from(header[2]) << 8) | usize::from(header[3]);
+        let option_end = pos + 4 + len;
+        data.try_get(pos..option_end)?;
+        layer.add_attr(attr!(&EDNS_OPTION_ATTR, range: pos..option_end));
+        layer.add_attr(attr!(&EDNS_OPTION_CODE_ATTR, range: pos..pos + 2));
+        layer.add_attr(attr!(&EDNS_OPTION_DATA_ATTR, range: pos + 4..option_end));
+        pos = option_end;
+    }
+    Ok(())
+}
+
+/// Reads the domain name at the offset, following the compression pointers.
+///
+/// Returns the name and the number of bytes the name occupies at the offset.
+fn read_name(data: &ByteSlice, offset: usize) -> io::Result<(String, usize)> {
+    let mut name = String::new();
+    let mut pos = offset;
+    let mut len = None;
+    let mut pointers = 0;
+    loop {
+        let n = data.try_get(pos)? as usize;
+        match n & 0xc0 {
+            0x00 if n == 0 => break,
+            0x00 => {
+                let label = data.try_get(pos + 1..pos + 1 + n)?;
+                if !name.is_empty() {
+                    name.push('.');
+                }
+                name.push_str(&String::from_utf8_lossy(&label));
+                pos += 1 + n;
+            }
+            0xc0 => {
+                if len.is_none() {
+                    len = Some(pos + 2 - offset);
+                }
+                pointers += 1;
+                if pointers > MAX_POINTERS {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "too many compression pointers",
+                    ));
+                }
+                pos = ((n & 0x3f) << 8) | data.try_get(pos + 1)? as usize;
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unknown label type",
+                ))
+            }
+        }
+    }
+    if name.is_empty() {
+        name.push('.');
+    }
+    Ok((name, len.unwrap_or(pos + 1 - offset)))
+}
+
+/// Cast for a domain name, which may be compressed.
+#[derive(Clone)]
+struct Name();
+
+impl Typed for Name {
+    type Output = Box<str>;
+
+    fn cast(&self, attr: &Attr, data: &ByteSlice) -> io::Result<Box<str>> {
+        read_name(data, attr.range().start).map(|(name, _)| name.into_boxed_str())
+    }
+}
+
+/// The attribute classes of the records in a section.
+struct RecordClass {
+    record: AttrClass,
+    name: AttrClass,
+    typ: AttrClass,
+    class: AttrClass,
+    ttl: AttrClass,
+    length: AttrClass,
+    a: AttrClass,
+    aaaa: AttrClass,
+    ns: AttrClass,
+    cname: AttrClass,
+    ptr: AttrClass,
+    mx: AttrClass,
+    mx_preference: AttrClass,
+    mx_exchange: AttrClass,
+    txt: AttrClass,
+    srv: AttrClass,
+    srv_priority: AttrClass,
+    srv_weight: AttrClass,
+    srv_port: AttrClass,
+    srv_target: AttrClass,
+    data: AttrClass,
+}
+
+impl RecordClass {
+    fn new(prefix: &str) -> RecordClass {
+        let id = |name: &str| format!("{}.{}", prefix, name);
+        RecordClass {
+            record: AttrClass::builder(prefix)
+                .typ("@nested")
+                .repeated()
+                .value(true)
+                .build(),
+            name: AttrClass::builder(id("name")).cast(Name()).build(),
+            typ: AttrClass::builder(id("type"))
+                .typ("@enum")
+                .cast(cast::UInt16BE())
+                .labels(type_labels())
+                .build(),
+            class: AttrClass::builder(id("class"))
+                .typ("@enum")
+                .cast(cast::UInt16BE())
+                .labels(class_labels())
+                .build(),
+            ttl: AttrClass::builder(id("ttl")).cast(cast::UInt32BE()).build(),
+            length: AttrClass::builder(id("length"))
+                .cast(cast::UInt16BE())
+                .build(),
+            a: AttrClass::builder(id("a"))
+                .typ("@ipv4:addr")
+                .cast(cast::ByteSlice())
+                .build(),
+            aaaa: AttrClass::builder(id("aaaa"))
+                .typ("@ipv6:addr")
+                .cast(cast::Ipv6Addr())
+                .build(),
+            ns: AttrClass::builder(id("ns")).cast(Name()).build(),
+            cname: AttrClass::builder(id("cname")).cast(Name()).build(),
+            ptr: AttrClass::builder(id("ptr")).cast(Name()).build(),
+            mx: AttrClass::builder(id("mx"))
+                .typ("@nested")
+                .value(true)
+                .build(),
+            mx_preference: AttrClass::builder(id("mx.preference"))
+                .cast(cast::UInt16BE())
+                .build(),
+            mx_exchange: AttrClass::builder(id("mx.exchange")).cast(Name()).build(),
+            txt: AttrClass::builder(id("txt"))
+                .repeated()
+                .cast(cast::Utf8())
+                .build(),
+            srv: AttrClass::builder(id("srv"))
+                .typ("@nested")
+                .value(true)
+                .build(),
+            srv_priority: AttrClass::builder(id("srv.priority"))
+                .cast(cast::UInt16BE())
+                .build(),
+            srv_weight: AttrClass::builder(id("srv.weight"))
+                .cast(cast::UInt16BE())
+                .build(),
+            srv_port: AttrClass::builder(id("srv.port"))
+                .cast(cast::UInt16BE())
+                .build(),
+            srv_target: AttrClass::builder(id("srv.target")).cast(Name()).build(),
+            data: AttrClass::builder(id("data"))
+                .cast(cast::ByteSlice())
+                .build(),
+        }
+    }
+}
+
+/// Returns the ports, the message type and the first question,
+/// e.g. `53 → 49152 Standard query response A example.com`.
+fn summary(layer: &Layer, stack: &LayerStack) -> Option<String> {
+    let mut parts = Vec::new();
+    if let Some(parent) = stack.parent(layer) {
+        let port =
+            |id: Token| -> Option<u64> { parent.attr(id)?.try_get(parent).ok()?.try_into().ok() };
+        let ports = [
+            (token!("udp.src"), token!("udp.dst")),
+            (token!("tcp.src"), token!("tcp.dst")),
+        ];
+        if let Some((src, dst)) = ports
+            .iter()
+            .filter_map(|(src, dst)| Some((port(*src)?, port(*dst)?)))
+            .next()
+        {
+            parts.push(format!("{} \u{2192} {}", src, dst));
+        }
+    }
+
+    let opcode = layer.attr(token!("dns.flags.opcode"))?;
+    let value: u64 = opcode.try_get(layer).ok()?.try_into().ok()?;
+    parts.push(match value {
+        0 => "Standard query".to_string(),
+        value => opcode
+            .label(layer)
+            .map_or_else(|| format!("Opcode {}", value), String::from),
+    });
+    let response: bool = layer
+        .attr(token!("dns.flags.response"))?
+        .try_get(layer)
+        .ok()?
+        .try_into()
+        .ok()?;
+    if response {
+        parts.push("response".to_string());
+        let rcode = layer.attr(token!("dns.flags.rcode"));
+        if let Some(label) = rcode.and_then(|attr| attr.label(layer)) {
+            if label != "NoError" {
+                parts.push(label.to_string());
+            }
+        }
+    }
+
+    if let Some(typ) = layer
+        .attr(token!("dns.question.type"))
+        .and_then(|attr| attr.label(layer))
+    {
+        parts.push(typ.to_string());
+    }
+    let name: Option<String> = layer
+        .attr(token!("dns.question.name"))
+        .and_then(|attr| attr.try_get(layer).ok())
+        .and_then(|value| value.try_into().ok());
+    parts.extend(name);
+    Some(parts.join(" "))
+}
+
+fn type_labels() -> Vec<(u64, &'static str)> {
+    vec![
+        (1, "A"),
+        (2, "NS"),
+        (5, "CNAME"),
+        (6, "SOA"),
+        (12, "PTR"),
+        (15, "MX"),
+        (16, "TXT"),
+        (28, "AAAA"),
+        (33, "SRV"),
+        (41, "OPT"),
+        (43, "DS"),
+        (46, "RRSIG"),
+        (47, "NSEC"),
+        (48, "DNSKEY"),
+        (65, "HTTPS"),
+        (255, "ANY"),
+    ]
+}
+
+fn class_labels() -> Vec<(u64, &'static str)> {
+    vec![(1, "IN"), (3, "CH"), (4, "HS"), (255, "ANY")]
+}
+
+lazy_static! {
+    static ref ANSWER: RecordClass = RecordClass::new("dns.answer");
+    static ref AUTHORITY: RecordClass = RecordClass::new("dns.authority");
+    static ref ADDITIONAL: RecordClass = RecordClass::new("dns.additional");
+}
+
+#[derive(Clone)]
+struct DnsDecoder {}
+
+impl Decoder for DnsDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(DnsWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.decoder.dns".into(),
+            name: "DNS".into(),
+            description: "Domain Name System".into(),
+            exec_type: ExecType::SerialSync,
+            triggers: vec![
+                Trigger::new("udp.src", PORT.into()),
+                Trigger::new("udp.dst", PORT.into()),
+            ],
+            references: vec![
+                "https://tools.ietf.org/html/rfc1035".into(),
+                "https://tools.ietf.org/html/rfc6891".into(),
+            ],
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(DNS_CLASS, "dns",
+    summary: summary,
+    header: &ID_ATTR_HEADER,
+    header: attr!(&FLAGS_ATTR, range: 2..4),
+    header: &QR_ATTR_HEADER,
+    header: attr!(&OPCODE_ATTR, bit_range: 2 1..5),
+    header: attr!(&AA_ATTR, bit_range: 2 5..6),
+    header: attr!(&TC_ATTR, bit_range: 2 6..7),
+    header: attr!(&RD_ATTR, bit_range: 2 7..8),
+    header: attr!(&RA_ATTR, bit_range: 3 0..1),
+    header: attr!(&AD_ATTR, bit_range: 3 2..3),
+    header: attr!(&CD_ATTR, bit_range: 3 3..4),
+    header: attr!(&RCODE_ATTR, bit_range: 3 4..8),
+    header: attr!(&QDCOUNT_ATTR, range: 4..6),
+    header: attr!(&ANCOUNT_ATTR, range: 6..8),
+    header: attr!(&NSCOUNT_ATTR, range: 8..10),
+    header: attr!(&ARCOUNT_ATTR, range: 10..12)
+);
+
+def_attr!(ID_ATTR_HEADER, &ID_ATTR, range: 0..2);
+def_attr!(QR_ATTR_HEADER, &QR_ATTR, bit_range: 2 0..1);
+
+def_attr_class!(ID_ATTR, "dns.id", cast: cast::UInt16BE());
+
+def_attr_class!(FLAGS_ATTR, "dns.flags",
+    typ: "@flags",
+    cast: cast::UInt16BE()
+);
+
+def_attr_class!(QR_ATTR, "dns.flags.response",
+    cast: cast::UInt8().map(|v| (v & 0b1000_0000) != 0)
+);
+
+def_attr_class!(OPCODE_ATTR, "dns.flags.opcode",
+    typ: "@enum",
+    cast: cast::UInt8().map(|v| (v >> 3) & 0b1111),
+    labels: vec![(0, "QUERY"), (1, "IQUERY"), (2, "STATUS"), (4, "NOTIFY"), (5, "UPDATE")]
+);
+
+def_attr_class!(AA_ATTR, "dns.flags.authoritative",
+    cast: cast::UInt8().map(|v| (v & 0b0000_0100) != 0)
+);
+
+def_attr_class!(TC_ATTR, "dns.flags.truncated",
+    cast: cast::UInt8().map(|v| (v & 0b0000_0010) != 0)
+);
+
+def_attr_class!(RD_ATTR, "dns.flags.recursionDesired",
+    cast: cast::UInt8().map(|v| (v & 0b0000_0001) != 0)
+);
+
+def_attr_class!(RA_ATTR, "dns.flags.recursionAvailable",
+    cast: cast::UInt8().map(|v| (v & 0b1000_0000) != 0)
+);
+
+def_attr_class!(AD_ATTR, "dns.flags.authenticData",
+    cast: cast::UInt8().map(|v| (v & 0b0010_0000) != 0)
+);
+
+def_attr_class!(CD_ATTR, "dns.flags.checkingDisabled",
+    cast: cast::UInt8().map(|v| (v & 0b0001_0000) != 0)
+);
+
+def_attr_class!(RCODE_ATTR, "dns.flags.rcode",
+    typ: "@enum",
+    cast: cast::UInt8().map(|v| v & 0b1111),
+    labels: vec![
+        (0, "NoError"),
+        (1, "FormErr"),
+        (2, "ServFail"),
+        (3, "NXDomain"),
+        (4, "NotImp"),
+        (5, "Refused"),
+    ]
+);
+
+def_attr_class!(QDCOUNT_ATTR, "dns.questionCount", cast: cast::UInt16BE());
+def_attr_class!(ANCOUNT_ATTR, "dns.answerCount", cast: cast::UInt16BE());
+def_attr_class!(NSCOUNT_ATTR, "dns.authorityCount", cast: cast::UInt16BE());
+def_attr_class!(ARCOUNT_ATTR, "dns.additionalCount", cast: cast::UInt16BE());
+
+def_attr_class!(QUESTION_ATTR, "dns.question",
+    typ: "@nested",
+    repeated: ,
+    value: true
+);
+
+def_attr_class!(QUESTION_NAME_ATTR, "dns.question.name", cast: Name());
+
+def_attr_class!(QUESTION_TYPE_ATTR, "dns.question.type",
+    typ: "@enum",
+    cast: cast::UInt16BE(),
+    labels: type_labels()
+);
+
+def_attr_class!(QUESTION_CLASS_ATTR, "dns.question.class",
+    typ: "@enum",
+    cast: cast::UInt16BE(),
+    labels: class_labels()
+);
+
+def_attr_class!(EDNS_ATTR, "dns.edns",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(EDNS_UDP_SIZE_ATTR, "dns.edns.udpPayloadSize", cast: cast::UInt16BE());
+def_attr_class!(EDNS_RCODE_ATTR, "dns.edns.extendedRcode", cast: cast::UInt8());
+def_attr_class!(EDNS_VERSION_ATTR, "dns.edns.version", cast: cast::UInt8());
+
+def_attr_class!(EDNS_DO_ATTR, "dns.edns.dnssecOk",
+    cast: cast::UInt8().map(|v| (v & 0b1000_0000) != 0)
+);
+
+def_attr_class!(EDNS_OPTION_ATTR, "dns.edns.option",
+    typ: "@nested",
+    repeated: ,
+    value: true
+);
+
+def_attr_class!(EDNS_OPTION_CODE_ATTR, "dns.edns.option.code",
+    typ: "@enum",
+    cast: cast::UInt16BE(),
+    labels: vec![(3, "NSID"), (8, "Client Subnet"), (10, "Cookie"), (12, "Padding")]
+);
+
+def_attr_class!(EDNS_OPTION_DATA_ATTR, "dns.edns.option.data", cast: cast::ByteSlice());
+
+def_attr_class!(TIME_ATTR, "dns.responseTime");
+
+genet_decoders!(DnsDecoder {});
@@ -11,25 +11,75 @@ impl Worker for EthWorker {
         _stack: &LayerStack,
         parent: &mut Parent,
     ) -> Result<Status> {
+        let data;
+
         if parent.id() == token!("[link-1]") {
-            let mut layer = Layer::new(&ETH_CLASS, parent.data());
-            let len = LEN_ATTR_HEADER.try_get(&layer)?.try_into()?;
-            if len <= 1500 {
-                layer.add_attr(&LEN_ATTR_HEADER);
-            } else {
-                layer.add_attr(&TYPE_ATTR_HEADER);
-            }
-            if let Some((typ, attr)) = get_type(len) {
-                layer.add_attr(attr!(attr, range: 12..14));
-                let payload = parent.data().try_get(14..)?;
-                layer.add_payload(Payload::new(payload, typ));
-            }
+            data = parent.data();
+        } else if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:eth"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
 
-            parent.add_child(layer);
-            Ok(Status::Done)
+        let mut layer = Layer::new(&ETH_CLASS, data);
+        let len = LEN_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        if len <= 1500 {
+            layer.add_attr(&LEN_ATTR_HEADER);
         } else {
-            Ok(Status::Skip)
+            layer.add_attr(&TYPE_ATTR_HEADER);
         }
+        if let Some((typ, attr)) = get_type(len) {
+            layer.add_attr(attr!(attr, range: 12..14));
+            let payload = data.try_get(14..)?;
+            layer.add_payload(Payload::new(payload, typ));
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+struct VlanWorker {}
+
+impl Worker for VlanWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let data;
+
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:vlan"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let mut layer = Layer::new(&VLAN_CLASS, data);
+        if data.len() < VLAN_TAG_LEN {
+            layer.error("truncated tag");
+            parent.add_child(layer);
+            return Ok(Status::Done);
+        }
+
+        // Stacked tags (QinQ) are decoded as nested vlan layers.
+        let typ = VLAN_TYPE_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        if let Some((typ, _)) = get_type(typ) {
+            let payload = data.try_get(VLAN_TAG_LEN..)?;
+            layer.add_payload(Payload::new(payload, typ));
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
     }
 }
 
@@ -43,7 +93,31 @@ impl Decoder for EthDecoder {
 
     fn metadata(&self) -> Metadata {
         Metadata {
+            id: "app.genet.decoder.eth".into(),
+            name: "Ethernet".into(),
+            description: "Ethernet II frame".into(),
+            exec_type: ExecType::ParallelSync,
+            references: vec!["https://standards.ieee.org/standard/802_3-2018.html".into()],
+            ..Metadata::default()
+        }
+    }
+}
+
+#[derive(Clone)]
+struct VlanDecoder {}
+
+impl Decoder for VlanDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(VlanWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.decoder.vlan".into(),
+            name: "VLAN".into(),
+            description: "IEEE 802.1Q VLAN tag".into(),
             exec_type: ExecType::ParallelSync,
+            references: vec!["https://standards.ieee.org/standard/802_1Q-2018.html".into()],
             ..Metadata::default()
         }
     }
@@ -58,25 +132,88 @@ def_layer_class!(ETH_CLASS, "eth",
 
 def_attr_class!(SRC_ATTR, "eth.src",
             typ: "@eth:mac",
-            cast: cast::ByteSlice()
+            cast: cast::MacAddr()
         );
 
 def_attr_class!(DST_ATTR, "eth.dst",
             typ: "@eth:mac",
-            cast: cast::ByteSlice()
+            cast: cast::MacAddr()
         );
 
 def_attr_class!(LEN_ATTR, "eth.len", cast: cast::UInt16BE());
 
 def_attr_class!(TYPE_ATTR, "eth.type",
             typ: "@enum",
-            cast: cast::UInt16BE()
+            cast: cast::UInt16BE(),
+            labels: vec![
+                (0x0800, "IPv4"),
+                (0x0806, "ARP"),
+                (0x0842, "WoL"),
+                (0x8035, "RARP"),
+                (0x8100, "802.1Q"),
+                (0x86DD, "IPv6"),
+                (0x8847, "MPLS"),
+                (0x8848, "MPLS multicast"),
+                (0x888E, "EAP"),
+                (0x88A8, "802.1ad"),
+                (0x9100, "QinQ"),
+            ]
         );
 
 def_attr!(LEN_ATTR_HEADER,  &LEN_ATTR, range: 12..14);
 
 def_attr!(TYPE_ATTR_HEADER,  &TYPE_ATTR, range: 12..14);
 
+const VLAN_TAG_LEN: usize = 4;
+
+def_layer_class!(VLAN_CLASS, "vlan",
+            header: attr!(&VLAN_PRIORITY_ATTR, bit_range: 0 0..3),
+            header: attr!(&VLAN_DEI_ATTR, bit_range: 0 3..4),
+            header: attr!(&VLAN_ID_ATTR, bit_range: 0 4..16),
+            header: &VLAN_TYPE_ATTR_HEADER
+        );
+
+def_attr_class!(VLAN_PRIORITY_ATTR, "vlan.priority",
+            typ: "@enum",
+            cast: cast::UInt8().map(|v| v >> 5),
+            labels: vec![
+                (0, "Best Effort"),
+                (1, "Background"),
+                (2, "Excellent Effort"),
+                (3, "Critical Applications"),
+                (4, "Video"),
+                (5, "Voice"),
+                (6, "Internetwork Control"),
+                (7, "Network Control"),
+            ]
+        );
+
+def_attr_class!(VLAN_DEI_ATTR, "vlan.dei",
+            cast: cast::UInt8().map(|v| v & 0b00010000 != 0)
+        );
+
+def_attr_class!(VLAN_ID_ATTR, "vlan.id",
+            cast: cast::UInt16BE().map(|v| v & 0x0fff)
+        );
+
+def_attr_class!(VLAN_TYPE_ATTR, "vlan.type",
+            typ: "@enum",
+            cast: cast::UInt16BE(),
+            labels: vec![
+                (0x0800, "IPv4"),
+                (0x0806, "ARP"),
+                (0x0842, "WoL"),
+                (0x8035, "RARP"),
+                (0x8100, "802.1Q"),
+                (0x86DD, "IPv6"),
+                (0x8847, "MPLS"),
+                (0x8848, "MPLS multicast"),
+                (0x888E, "EAP"),
+            ]
+        );
+
+def_attr!(VLAN_TYPE_ATTR_HEADER,  &VLAN_TYPE_ATTR, range: 2..4);
+
 fn get_type(val: u64) -> Option<(Token, &'static AttrClass)> {
     match val {
         0x0800 => Some((
@@ -91,16 +228,28 @@ fn get_type(val: u64) -> Option<(Token, &'static AttrClass)> {
             token!("@data:wol"),
             attr_class_lazy!("eth.type.wol", typ: "@novalue", value: true),
         )),
+        0x8035 => Some((
+            token!("@data:rarp"),
+            attr_class_lazy!("eth.type.rarp", typ: "@novalue", value: true),
+        )),
         0x86DD => Some((
             token!("@data:ipv6"),
             attr_class_lazy!("eth.type.ipv6", typ: "@novalue", value: true),
         )),
+        0x8847 | 0x8848 => Some((
+            token!("@data:mpls"),
+            attr_class_lazy!("eth.type.mpls", typ: "@novalue", value: true),
+        )),
         0x888E => Some((
             token!("@data:eap"),
             attr_class_lazy!("eth.type.eap", typ: "@novalue", value: true),
         )),
+        0x8100 | 0x88A8 | 0x9100 => Some((
+            token!("@data:vlan"),
+            attr_class_lazy!("eth.type.vlan", typ: "@novalue", value: true),
+        )),
         _ => None,
     }
 }
 
-genet_decoders!(EthDecoder {});
+genet_decoders!(EthDecoder {}, VlanDecoder {});
@@ -22,6 +22,9 @@ pub enum Value {
     Float64(f64),
     String(Box<str>),
     Buffer(Box<[u8]>),
+    Ipv6Addr([u8; 16]),
+    MacAddr([u8; 6]),
+    Timestamp(u64),
 }
 
 impl From<Variant> for Value {
@@ -33,6 +36,9 @@ impl From<Variant> for Value {
             Variant::Float64(x) => Value::Float64(x),
             Variant::String(x) => Value::String(x),
             Variant::Buffer(x) => Value::Buffer(x),
+            Variant::Ipv6Addr(x) => Value::Ipv6Addr(x.octets()),
+            Variant::MacAddr(x) => Value::MacAddr(x),
+            Variant::Timestamp(x) => Value::Timestamp(x),
             _ => Value::Nil,
         }
     }
@@ -47,6 +53,9 @@ impl Into<Variant> for Value {
             Value::Float64(x) => Variant::Float64(x),
             Value::String(x) => Variant::String(x),
             Value::Buffer(x) => Variant::Buffer(x),
+            Value::Ipv6Addr(x) => Variant::Ipv6Addr(x.into()),
+            Value::MacAddr(x) => Variant::MacAddr(x),
+            Value::Timestamp(x) => Variant::Timestamp(x),
             _ => Variant::Nil,
         }
     }
@@ -12,7 +12,7 @@ use genet_sdk::{prelude::*, reader::*, variant::Variant};
 use std::{
     collections::HashMap,
     fs::File,
-    io::{BufReader, Read},
+    io::{BufReader, Error, ErrorKind, Read},
 };
 
 #[derive(Deserialize)]
@@ -82,9 +82,13 @@ struct GenetFileWorker {
 }
 
 impl Worker for GenetFileWorker {
-    fn read(&mut self) -> Result<Vec<Layer>> {
-        let mut layers = Vec::new();
-        for _ in 0..self.header.entries {
+    fn read(&mut self, max: usize) -> Result<Vec<Layer>> {
+        if self.header.entries == 0 {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "end of file").into());
+        }
+        let len = self.header.entries.min(max);
+        let mut layers = Vec::with_capacity(len);
+        for _ in 0..len {
             let mut frame_buf = vec![0; read_usize(&mut self.reader)?];
             self.reader.read_exact(&mut frame_buf)?;
             let frame: genet_format::Frame = bincode::deserialize(&frame_buf)?;
@@ -103,7 +107,7 @@ impl Worker for GenetFileWorker {
             }
             layers.push(layer);
         }
-        self.header.entries = 0;
+        self.header.entries -= len;
         Ok(layers)
     }
 }
@@ -0,0 +1,186 @@
+extern crate genet_sdk;
+
+use genet_sdk::{cast, decoder::*, prelude::*};
+
+const PORT: u16 = 6081;
+const HEADER_LEN: usize = 8;
+const OPTION_HEADER_LEN: usize = 4;
+
+struct GeneveWorker {}
+
+impl Worker for GeneveWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        if parent.id() != token!("udp") {
+            return Ok(Status::Skip);
+        }
+
+        let data;
+
+        if let Some(payload) = parent.payloads().iter().next() {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let mut layer = Layer::new(&GENEVE_CLASS, data);
+        if data.len() < HEADER_LEN {
+            layer.error("truncated header");
+            parent.add_child(layer);
+            return Ok(Status::Done);
+        }
+
+        let opt_len: usize = OPT_LEN_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        let len = HEADER_LEN + opt_len * 4;
+        if data.len() < len {
+            layer.error("truncated options");
+            parent.add_child(layer);
+            return Ok(Status::Done);
+        }
+
+        let mut pos = HEADER_LEN;
+        while pos < len {
+            if pos + OPTION_HEADER_LEN > len {
+                layer.error("malformed option");
+                break;
+            }
+            let end = pos + OPTION_HEADER_LEN + (data[pos + 3] & 0b0001_1111) as usize * 4;
+            if end > len {
+                layer.error("malformed option");
+                break;
+            }
+            layer.add_attr(attr!(&OPTION_ATTR, range: pos..end));
+            layer.add_attr(attr!(&OPTION_CLASS_ATTR, range: pos..pos + 2));
+            layer.add_attr(attr!(&OPTION_TYPE_ATTR, range: pos + 2..pos + 3));
+            layer.add_attr(attr!(&OPTION_CRITICAL_ATTR, bit_range: pos + 2 0..1));
+            layer.add_attr(attr!(&OPTION_LENGTH_ATTR, bit_range: pos + 3 3..8));
+            layer.add_attr(attr!(&OPTION_DATA_ATTR, range: pos + OPTION_HEADER_LEN..end));
+            pos = end;
+        }
+
+        let protocol = PROTOCOL_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        if let Some((typ, attr)) = get_protocol(protocol) {
+            layer.add_attr(attr!(attr, range: 2..4));
+            let payload = data.try_get(len..)?;
+            layer.add_payload(Payload::new(payload, typ));
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct GeneveDecoder {}
+
+impl Decoder for GeneveDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(GeneveWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.decoder.geneve".into(),
+            name: "Geneve".into(),
+            description: "Generic Network Virtualization Encapsulation".into(),
+            exec_type: ExecType::ParallelSync,
+            // The source port is derived from a hash of the inner frame.
+            triggers: vec![Trigger::new("udp.dst", PORT.into())],
+            references: vec!["https://tools.ietf.org/html/rfc8926".into()],
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(GENEVE_CLASS, "geneve",
+    header: attr!(&VERSION_ATTR, bit_range: 0 0..2),
+    header: &OPT_LEN_ATTR_HEADER,
+    header: attr!(&FLAGS_ATTR, bit_range: 1 0..2),
+    header: attr!(&FLAGS_OAM_ATTR, bit_range: 1 0..1),
+    header: attr!(&FLAGS_CRITICAL_ATTR, bit_range: 1 1..2),
+    header: &PROTOCOL_ATTR_HEADER,
+    header: attr!(&VNI_ATTR, range: 4..7)
+);
+
+def_attr!(OPT_LEN_ATTR_HEADER, &OPT_LEN_ATTR, bit_range: 0 2..8);
+def_attr!(PROTOCOL_ATTR_HEADER, &PROTOCOL_ATTR, range: 2..4);
+
+def_attr_class!(VERSION_ATTR, "geneve.version",
+    cast: cast::UInt8().map(|v| v >> 6)
+);
+
+def_attr_class!(OPT_LEN_ATTR, "geneve.optionsLength",
+    cast: cast::UInt8().map(|v| v & 0b00111111)
+);
+
+def_attr_class!(FLAGS_ATTR, "geneve.flags",
+    typ: "@flags",
+    cast: cast::UInt8().map(|v| v >> 6)
+);
+
+def_attr_class!(FLAGS_OAM_ATTR, "geneve.flags.oam",
+    cast: cast::UInt8().map(|v| v & 0b10000000 != 0)
+);
+
+def_attr_class!(FLAGS_CRITICAL_ATTR, "geneve.flags.critical",
+    cast: cast::UInt8().map(|v| v & 0b01000000 != 0)
+);
+
+def_attr_class!(PROTOCOL_ATTR, "geneve.protocol",
+    typ: "@enum",
+    cast: cast::UInt16BE(),
+    labels: vec![
+        (0x0800, "IPv4"),
+        (0x6558, "Transparent Ethernet Bridging"),
+        (0x86DD, "IPv6"),
+    ]
+);
+
+def_attr_class!(VNI_ATTR, "geneve.vni",
+    cast: cast::ByteSlice()
+        .map(|v| ((v[0] as u32) << 16) | ((v[1] as u32) << 8) | v[2] as u32)
+);
+
+def_attr_class!(OPTION_ATTR, "geneve.option",
+    typ: "@nested",
+    repeated: ,
+    value: true
+);
+
+def_attr_class!(OPTION_CLASS_ATTR, "geneve.option.class", cast: cast::UInt16BE());
+
+def_attr_class!(OPTION_TYPE_ATTR, "geneve.option.type", cast: cast::UInt8());
+
+def_attr_class!(OPTION_CRITICAL_ATTR, "geneve.option.critical",
+    cast: cast::UInt8().map(|v| v & 0b10000000 != 0)
+);
+
+def_attr_class!(OPTION_LENGTH_ATTR, "geneve.option.length",
+    cast: cast::UInt8().map(|v| v & 0b00011111)
+);
+
+def_attr_class!(OPTION_DATA_ATTR, "geneve.option.data", cast: cast::ByteSlice());
+
+fn get_protocol(val: u64) -> Option<(Token, &'static AttrClass)> {
+    match val {
+        0x0800 => Some((
+            token!("@data:ipv4"),
+            attr_class_lazy!("geneve.protocol.ipv4", typ: "@novalue", value: true),
+        )),
+        0x6558 => Some((
+            token!("@data:eth"),
+            attr_class_lazy!("geneve.protocol.eth", typ: "@novalue", value: true),
+        )),
+        0x86DD => Some((
+            token!("@data:ipv6"),
+            attr_class_lazy!("geneve.protocol.ipv6", typ: "@novalue", value: true),
+        )),
+        _ => None,
+    }
+}
+
+genet_decoders!(GeneveDecoder {});
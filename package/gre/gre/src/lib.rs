@@ -0,0 +1,226 @@
+extern crate genet_sdk;
+
+use genet_sdk::{cast, decoder::*, prelude::*};
+
+const HEADER_LEN: usize = 4;
+
+const FLAG_CHECKSUM: u8 = 0b1000_0000;
+const FLAG_KEY: u8 = 0b0010_0000;
+const FLAG_SEQUENCE: u8 = 0b0001_0000;
+const FLAG_ACK: u8 = 0b1000_0000;
+
+const TRANSPARENT_ETHERNET_BRIDGING: u64 = 0x6558;
+
+struct GreWorker {}
+
+impl Worker for GreWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let data;
+
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:gre"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let mut layer = Layer::new(&GRE_CLASS, data);
+        if data.len() < HEADER_LEN {
+            layer.error("truncated header");
+            parent.add_child(layer);
+            return Ok(Status::Done);
+        }
+
+        let flags = data[0];
+        let version = data[1] & 0b0000_0111;
+        let protocol = PROTOCOL_ATTR_HEADER.try_get(&layer)?.try_into()?;
+
+        let mut len = HEADER_LEN;
+        if flags & FLAG_CHECKSUM != 0 {
+            len += 4;
+        }
+        if flags & FLAG_KEY != 0 {
+            len += 4;
+        }
+        if flags & FLAG_SEQUENCE != 0 {
+            len += 4;
+        }
+        if version == 1 && data[1] & FLAG_ACK != 0 {
+            len += 4;
+        }
+        if data.len() < len {
+            layer.error("truncated header");
+            parent.add_child(layer);
+            return Ok(Status::Done);
+        }
+
+        let mut pos = HEADER_LEN;
+        if flags & FLAG_CHECKSUM != 0 {
+            layer.add_attr(attr!(&CHECKSUM_ATTR, range: pos..pos + 2));
+            pos += 4;
+        }
+        if flags & FLAG_KEY != 0 {
+            layer.add_attr(attr!(&KEY_ATTR, range: pos..pos + 4));
+            if version == 1 {
+                // Enhanced GRE used by PPTP splits the key field.
+                layer.add_attr(attr!(&PAYLOAD_LENGTH_ATTR, range: pos..pos + 2));
+                layer.add_attr(attr!(&CALL_ID_ATTR, range: pos + 2..pos + 4));
+            } else if protocol == TRANSPARENT_ETHERNET_BRIDGING {
+                // NVGRE carries the Virtual Subnet ID and the FlowID in the key.
+                layer.add_attr(attr!(&VSID_ATTR, range: pos..pos + 3));
+                layer.add_attr(attr!(&FLOW_ID_ATTR, range: pos + 3..pos + 4));
+            }
+            pos += 4;
+        }
+        if flags & FLAG_SEQUENCE != 0 {
+            layer.add_attr(attr!(&SEQUENCE_ATTR, range: pos..pos + 4));
+            pos += 4;
+        }
+        if version == 1 && data[1] & FLAG_ACK != 0 {
+            layer.add_attr(attr!(&ACK_ATTR, range: pos..pos + 4));
+        }
+
+        if let Some((typ, attr)) = get_protocol(protocol) {
+            layer.add_attr(attr!(attr, range: 2..4));
+            let payload = data.try_get(len..)?;
+            layer.add_payload(Payload::new(payload, typ));
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct GreDecoder {}
+
+impl Decoder for GreDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(GreWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.decoder.gre".into(),
+            name: "GRE".into(),
+            description: "Generic Routing Encapsulation".into(),
+            exec_type: ExecType::ParallelSync,
+            references: vec![
+                "https://tools.ietf.org/html/rfc2784".into(),
+                "https://tools.ietf.org/html/rfc2890".into(),
+                "https://tools.ietf.org/html/rfc2637".into(),
+                "https://tools.ietf.org/html/rfc7637".into(),
+            ],
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(GRE_CLASS, "gre",
+    header: attr!(&FLAGS_ATTR, bit_range: 0 0..5),
+    header: attr!(&FLAGS_CHECKSUM_ATTR, bit_range: 0 0..1),
+    header: attr!(&FLAGS_KEY_ATTR, bit_range: 0 2..3),
+    header: attr!(&FLAGS_SEQUENCE_ATTR, bit_range: 0 3..4),
+    header: attr!(&VERSION_ATTR, bit_range: 1 5..8),
+    header: &PROTOCOL_ATTR_HEADER
+);
+
+def_attr!(PROTOCOL_ATTR_HEADER, &PROTOCOL_ATTR, range: 2..4);
+
+def_attr_class!(FLAGS_ATTR, "gre.flags",
+    typ: "@flags",
+    cast: cast::UInt8().map(|v| v >> 3)
+);
+
+def_attr_class!(FLAGS_CHECKSUM_ATTR, "gre.flags.checksum",
+    cast: cast::UInt8().map(|v| v & 0b10000000 != 0)
+);
+
+def_attr_class!(FLAGS_KEY_ATTR, "gre.flags.key",
+    cast: cast::UInt8().map(|v| v & 0b00100000 != 0)
+);
+
+def_attr_class!(FLAGS_SEQUENCE_ATTR, "gre.flags.sequence",
+    cast: cast::UInt8().map(|v| v & 0b00010000 != 0)
+);
+
+def_attr_class!(VERSION_ATTR, "gre.version",
+    cast: cast::UInt8().map(|v| v & 0b00000111)
+);
+
+def_attr_class!(PROTOCOL_ATTR, "gre.protocol",
+    typ: "@enum",
+    cast: cast::UInt16BE(),
+    labels: vec![
+        (0x0800, "IPv4"),
+        (0x0806, "ARP"),
+        (0x6558, "Transparent Ethernet Bridging"),
+        (0x8100, "802.1Q"),
+        (0x86DD, "IPv6"),
+        (0x8847, "MPLS"),
+        (0x880B, "PPP"),
+    ]
+);
+
+def_attr_class!(CHECKSUM_ATTR, "gre.checksum", cast: cast::UInt16BE());
+
+def_attr_class!(KEY_ATTR, "gre.key", cast: cast::UInt32BE());
+
+def_attr_class!(VSID_ATTR, "gre.vsid",
+    cast: cast::ByteSlice()
+        .map(|v| ((v[0] as u32) << 16) | ((v[1] as u32) << 8) | v[2] as u32)
+);
+
+def_attr_class!(FLOW_ID_ATTR, "gre.flowId", cast: cast::UInt8());
+
+def_attr_class!(PAYLOAD_LENGTH_ATTR, "gre.payloadLength", cast: cast::UInt16BE());
+
+def_attr_class!(CALL_ID_ATTR, "gre.callId", cast: cast::UInt16BE());
+
+def_attr_class!(SEQUENCE_ATTR, "gre.sequence", cast: cast::UInt32BE());
+
+def_attr_class!(ACK_ATTR, "gre.ack", cast: cast::UInt32BE());
+
+fn get_protocol(val: u64) -> Option<(Token, &'static AttrClass)> {
+    match val {
+        0x0800 => Some((
+            token!("@data:ipv4"),
+            attr_class_lazy!("gre.protocol.ipv4", typ: "@novalue", value: true),
+        )),
+        0x0806 => Some((
+            token!("@data:arp"),
+            attr_class_lazy!("gre.protocol.arp", typ: "@novalue", value: true),
+        )),
+        0x6558 => Some((
+            token!("@data:eth"),
+            attr_class_lazy!("gre.protocol.eth", typ: "@novalue", value: true),
+        )),
+        0x8100 => Some((
+            token!("@data:vlan"),
+            attr_class_lazy!("gre.protocol.vlan", typ: "@novalue", value: true),
+        )),
+        0x86DD => Some((
+            token!("@data:ipv6"),
+            attr_class_lazy!("gre.protocol.ipv6", typ: "@novalue", value: true),
+        )),
+        0x8847 => Some((
+            token!("@data:mpls"),
+            attr_class_lazy!("gre.protocol.mpls", typ: "@novalue", value: true),
+        )),
+        0x880B => Some((
+            token!("@data:ppp"),
+            attr_class_lazy!("gre.protocol.ppp", typ: "@novalue", value: true),
+        )),
+        _ => None,
+    }
+}
+
+genet_decoders!(GreDecoder {});
@@ -0,0 +1,510 @@
+extern crate genet_sdk;
+
+use genet_sdk::{
+    cast::{self, Typed},
+    context::{flow_key, state_key},
+    decoder::*,
+    endpoint::endpoints,
+    prelude::*,
+    tcp::PduBuffer,
+};
+use std::{collections::VecDeque, io, str};
+
+const MIN_LEN: usize = 8;
+const MAX_HEAD_LEN: usize = 64 * 1024;
+
+const METHODS: &[&[u8]] = &[
+    b"GET ",
+    b"HEAD ",
+    b"POST ",
+    b"PUT ",
+    b"DELETE ",
+    b"CONNECT ",
+    b"OPTIONS ",
+    b"TRACE ",
+    b"PATCH ",
+];
+
+/// The state of one direction of a TCP connection.
+struct Stream {
+    http: bool,
+    buffer: PduBuffer,
+}
+
+/// The requests waiting for the responses, shared by both directions.
+#[derive(Default)]
+struct Pending {
+    requests: VecDeque<Request>,
+}
+
+struct Request {
+    frame: u32,
+    timestamp: f64,
+}
+
+enum Body {
+    None,
+    Length(usize),
+    Chunked,
+    Close,
+}
+
+struct Head {
+    len: usize,
+    body: Body,
+}
+
+struct HttpWorker {}
+
+impl Worker for HttpWorker {
+    fn decode(
+        &mut self,
+        ctx: &mut Context,
+        stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        if parent.id() != token!("tcp") {
+            return Ok(Status::Skip);
+        }
+
+        let data;
+
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@stream:tcp"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let (src, dst) = match endpoints(stack, parent) {
+            Some(endpoints) => endpoints,
+            None => return Ok(Status::Skip),
+        };
+        let src = (&(src.0)[..], src.1);
+        let dst = (&(dst.0)[..], dst.1);
+
+        // The first data of the stream determines whether the stream is HTTP.
+        let messages = {
+            let stream = ctx.state_or_insert_with(state_key(&(&src, &dst)), || Stream {
+                http: is_message(&data),
+                buffer: PduBuffer::new(),
+            });
+            if !stream.http {
+                return Ok(Status::Skip);
+            }
+            stream.buffer.push(data, message_len)
+        };
+
+        let frame = ctx.frame_index();
+        let timestamp = timestamp(stack);
+        let key = flow_key(src, dst);
+        for message in messages {
+            let mut layer = Layer::new(&HTTP_CLASS, message);
+            let response = decode_message(&mut layer)?;
+            let pending = ctx.state_or_insert_with(key, Pending::default);
+            if let Some(status) = response {
+                // Informational responses precede the final response.
+                if status >= 200 {
+                    if let Some(request) = pending.requests.pop_front() {
+                        layer.add_link(
+                            Link::new("http.request", request.frame).with_reverse("http.response"),
+                        );
+                        layer.add_attr(attr!(&TIME_ATTR, value: timestamp - request.timestamp));
+                    }
+                }
+            } else {
+                pending.requests.push_back(Request { frame, timestamp });
+            }
+            parent.add_child(layer);
+        }
+        Ok(Status::Done)
+    }
+}
+
+fn timestamp(stack: &LayerStack) -> f64 {
+    stack
+        .bottom()
+        .and_then(|link| {
+            link.attr(token!("link.timestamp"))
+                .and_then(|attr| attr.try_get(link).ok())
+        })
+        .and_then(|value| value.try_into().ok())
+        .unwrap_or(0.0)
+}
+
+/// Returns the request line or the status line.
+fn summary(layer: &Layer, _stack: &LayerStack) -> Option<String> {
+    let text =
+        |id: Token| -> Option<String> { layer.attr(id)?.try_get(layer).ok()?.try_into().ok() };
+    let version = text(token!("http.version"))?;
+    if let (Some(method), Some(target)) = (text(token!("http.method")), text(token!("http.target")))
+    {
+        return Some(format!("{} {} {}", method, target, version));
+    }
+    let status: u64 = layer
+        .attr(token!("http.status"))?
+        .try_get(layer)
+        .ok()?
+        .try_into()
+        .ok()?;
+    let reason = text(token!("http.reason")).unwrap_or_default();
+    Some(
+        format!("{} {} {}", version, status, reason)
+            .trim_end()
+            .to_string(),
+    )
+}
+
+fn is_message(data: &[u8]) -> bool {
+    data.starts_with(b"HTTP/1.") || METHODS.iter().any(|m| data.starts_with(m))
+}
+
+fn find(data: &[u8], needle: &[u8]) -> Option<usize> {
+    data.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Returns the ranges of the lines in the message head, excluding CRLF.
+fn lines(data: &[u8], len: usize) -> Vec<(usize, usize)> {
+    let mut lines = Vec::new();
+    let mut pos = 0;
+    while let Some(end) = find(&data[pos..len], b"\r\n") {
+        if end == 0 {
+            break;
+        }
+        lines.push((pos, pos + end));
+        pos += end + 2;
+    }
+    lines
+}
+
+fn split_header(line: &[u8]) -> Option<(usize, usize)> {
+    let colon = line.iter().position(|&b| b == b':')?;
+    let value = line[colon + 1..]
+        .iter()
+        .position(|&b| b != b' ' && b != b'\t')
+        .map(|pos| colon + 1 + pos)
+        .unwrap_or_else(|| line.len());
+    Some((colon, value))
+}
+
+fn trim_end(line: &[u8]) -> &[u8] {
+    let len = line
+        .iter()
+        .rposition(|&b| b != b' ' && b != b'\t')
+        .map(|pos| pos + 1)
+        .unwrap_or(0);
+    &line[..len]
+}
+
+fn parse_decimal(data: &[u8]) -> Option<usize> {
+    str::from_utf8(data).ok()?.parse().ok()
+}
+
+fn parse_status(line: &[u8]) -> Option<usize> {
+    line.get(9..12).and_then(parse_decimal)
+}
+
+/// Parses the message head, or returns None if it is incomplete.
+fn parse_head(data: &[u8]) -> Option<Head> {
+    let len = find(data, b"\r\n\r\n")? + 4;
+    let lines = lines(data, len);
+    let (start, end) = lines[0];
+    let response = data.starts_with(b"HTTP/");
+    if response {
+        match parse_status(&data[start..end]) {
+            Some(status) if status < 200 || status == 204 || status == 304 => {
+                return Some(Head {
+                    len,
+                    body: Body::None,
+                })
+            }
+            _ => {}
+        }
+    }
+
+    let mut body = if response { Body::Close } else { Body::None };
+    for &(start, end) in &lines[1..] {
+        let line = &data[start..end];
+        if let Some((colon, value)) = split_header(line) {
+            let name = &line[..colon];
+            let value = trim_end(&line[value..]);
+            if name.eq_ignore_ascii_case(b"transfer-encoding") {
+                if value.len() >= 7 && value[value.len() - 7..].eq_ignore_ascii_case(b"chunked") {
+                    body = Body::Chunked;
+                    break;
+                }
+            } else if name.eq_ignore_ascii_case(b"content-length") {
+                if let Some(len) = parse_decimal(value) {
+                    body = Body::Length(len);
+                }
+            }
+        }
+    }
+    Some(Head { len, body })
+}
+
+/// Returns the end of the chunked body starting at `pos`, or None if it is incomplete.
+///
+/// A malformed chunk size terminates the body at the chunk.
+/// An overflowing chunk size returns zero to discard the data.
+fn chunked_end(data: &[u8], pos: usize) -> Option<usize> {
+    let mut pos = pos;
+    loop {
+        let line = find(&data[pos..], b"\r\n")?;
+        let size = match parse_chunk_size(&data[pos..pos + line]) {
+            Some(size) => size,
+            None => return Some(pos),
+        };
+        pos += line + 2;
+        if size == 0 {
+            // Skip the trailer fields.
+            loop {
+                let line = find(&data[pos..], b"\r\n")?;
+                pos += line + 2;
+                if line == 0 {
+                    return Some(pos);
+                }
+            }
+        }
+        pos = match pos.checked_add(size).and_then(|pos| pos.checked_add(2)) {
+            Some(pos) => pos,
+            None => return Some(0),
+        };
+        if pos > data.len() {
+            return None;
+        }
+    }
+}
+
+fn message_len(data: &[u8]) -> Option<usize> {
+    if data.len() < MIN_LEN {
+        None
+    } else if !is_message(data) {
+        // Lost synchronization with the message boundaries.
+        Some(0)
+    } else {
+        match parse_head(data) {
+            None if data.len() > MAX_HEAD_LEN => Some(0),
+            None => None,
+            // An overflowing length is malformed.
+            Some(Head {
+                len,
+                body: Body::Length(body),
+            }) => Some(len.checked_add(body).unwrap_or(0)),
+            Some(Head {
+                len,
+                body: Body::Chunked,
+            }) => chunked_end(data, len),
+            Some(Head { len, .. }) => Some(len),
+        }
+    }
+}
+
+/// Decodes the message and returns the status code if it is a response.
+fn decode_message(layer: &mut Layer) -> Result<Option<usize>> {
+    let data = layer.data();
+    let head = parse_head(&data).unwrap();
+    let lines = lines(&data, head.len);
+
+    let (start, end) = lines[0];
+    let line = &data[start..end];
+    let response = if line.starts_with(b"HTTP/") {
+        let status = parse_status(line);
+        if status.is_none() || line.get(12).map_or(false, |&b| b != b' ') {
+            layer.error("malformed status line");
+        } else {
+            layer.add_attr(attr!(&VERSION_ATTR, range: 0..8));
+            layer.add_attr(attr!(&STATUS_ATTR, range: 9..12));
+            if end > 13 {
+                layer.add_attr(attr!(&REASON_ATTR, range: 13..end));
+            }
+        }
+        Some(status.unwrap_or(0))
+    } else {
+        let method = line.iter().position(|&b| b == b' ').unwrap();
+        let target = line[method + 1..].iter().position(|&b| b == b' ');
+        match target {
+            Some(target) if line[method + target + 2..].starts_with(b"HTTP/") => {
+                let target = method + 1 + target;
+                layer.add_attr(attr!(&METHOD_ATTR, range: 0..method));
+                layer.add_attr(attr!(&TARGET_ATTR, range: method + 1..target));
+                layer.add_attr(attr!(&VERSION_ATTR, range: target + 1..end));
+            }
+            _ => layer.error("malformed request line"),
+        }
+        None
+    };
+
+    let mut media_type = String::new();
+    for &(start, end) in &lines[1..] {
+        match split_header(&data[start..end]) {
+            Some((colon, value)) => {
+                let value = start + value;
+                let value_end = value + trim_end(&data[value..end]).len();
+                layer.add_attr(attr!(&HEADER_ATTR, range: start..end));
+                layer.add_attr(attr!(&HEADER_NAME_ATTR, range: start..start + colon));
+                layer.add_attr(attr!(&HEADER_VALUE_ATTR, range: value..value_end));
+                let name = &data[start..start + colon];
+                if name.eq_ignore_ascii_case(b"content-length") {
+                    if parse_decimal(&data[value..value_end]).is_some() {
+                        layer.add_attr(attr!(&CONTENT_LENGTH_ATTR, range: value..value_end));
+                    } else {
+                        layer.annotate(
+                            Annotation::new(Severity::Error, "invalid content length")
+                                .with_attr("http.header.value"),
+                        );
+                    }
+                } else if name.eq_ignore_ascii_case(b"content-type") {
+                    layer.add_attr(attr!(&CONTENT_TYPE_ATTR, range: value..value_end));
+                    media_type = media_type_of(&data[value..value_end]);
+                }
+            }
+            None => layer.warn("malformed header line"),
+        }
+    }
+
+    let body = match head.body {
+        Body::Chunked => {
+            let mut body = Vec::new();
+            let mut pos = head.len;
+            let mut last = false;
+            while let Some(size) = chunk_size(&data, pos) {
+                let line = find(&data[pos..], b"\r\n").unwrap();
+                pos += line + 2;
+                if size == 0 {
+                    last = true;
+                    break;
+                }
+                layer.add_attr(attr!(&CHUNK_ATTR, range: pos..pos + size));
+                body.extend_from_slice(&data[pos..pos + size]);
+                pos += size + 2;
+            }
+            if !last {
+                layer.error("malformed chunk");
+            }
+            ByteSlice::from(body)
+        }
+        Body::Close => {
+            layer.note("body delimited by connection close is not decoded");
+            ByteSlice::new()
+        }
+        _ => data.try_get(head.len..)?,
+    };
+
+    if !body.is_empty() {
+        layer.add_attr(attr!(&BODY_ATTR, range: head.len..data.len()));
+        layer.add_payload(Payload::with_typ(body, "@data:http", media_type).tagged("body"));
+    }
+    Ok(response)
+}
+
+fn parse_chunk_size(line: &[u8]) -> Option<usize> {
+    let size = line.split(|&b| b == b';').next().unwrap_or(line);
+    str::from_utf8(trim_end(size))
+        .ok()
+        .and_then(|s| usize::from_str_radix(s, 16).ok())
+}
+
+/// Returns the size of the chunk at `pos` if the chunk is complete.
+fn chunk_size(data: &[u8], pos: usize) -> Option<usize> {
+    let line = find(&data[pos..], b"\r\n")?;
+    parse_chunk_size(&data[pos..pos + line]).filter(|&size| {
+        (pos + line + 2)
+            .checked_add(size)
+            .map_or(false, |end| end <= data.len())
+    })
+}
+
+/// Returns the media type of the Content-Type value without the parameters.
+fn media_type_of(value: &[u8]) -> String {
+    String::from_utf8_lossy(value)
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase()
+}
+
+/// Cast for decimal numbers in ASCII.
+#[derive(Clone)]
+struct Decimal();
+
+impl Typed for Decimal {
+    type Output = u64;
+
+    fn cast(&self, attr: &Attr, data: &ByteSlice) -> io::Result<u64> {
+        str::from_utf8(&data.try_get(attr.range())?)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid decimal"))
+    }
+}
+
+#[derive(Clone)]
+struct HttpDecoder {}
+
+impl Decoder for HttpDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(HttpWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.decoder.http".into(),
+            name: "HTTP".into(),
+            description: "Hypertext Transfer Protocol".into(),
+            exec_type: ExecType::SerialSync,
+            triggers: vec![
+                Trigger::new("tcp.src", 80),
+                Trigger::new("tcp.dst", 80),
+                Trigger::new("tcp.src", 8080),
+                Trigger::new("tcp.dst", 8080),
+            ],
+            references: vec![
+                "https://tools.ietf.org/html/rfc7230".into(),
+                "https://tools.ietf.org/html/rfc7231".into(),
+            ],
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(HTTP_CLASS, "http", summary: summary);
+
+def_attr_class!(METHOD_ATTR, "http.method", cast: cast::Utf8());
+
+def_attr_class!(TARGET_ATTR, "http.target", cast: cast::Utf8());
+
+def_attr_class!(VERSION_ATTR, "http.version", cast: cast::Utf8());
+
+def_attr_class!(STATUS_ATTR, "http.status", cast: Decimal());
+
+def_attr_class!(REASON_ATTR, "http.reason", cast: cast::Utf8());
+
+def_attr_class!(HEADER_ATTR, "http.header",
+    typ: "@nested",
+    repeated: ,
+    value: true
+);
+
+def_attr_class!(HEADER_NAME_ATTR, "http.header.name", cast: cast::Utf8());
+
+def_attr_class!(HEADER_VALUE_ATTR, "http.header.value", cast: cast::Utf8());
+
+def_attr_class!(CONTENT_LENGTH_ATTR, "http.contentLength", cast: Decimal());
+
+def_attr_class!(CONTENT_TYPE_ATTR, "http.contentType", cast: cast::Utf8());
+
+def_attr_class!(CHUNK_ATTR, "http.chunk",
+    repeated: ,
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(BODY_ATTR, "http.body", cast: cast::ByteSlice());
+
+def_attr_class!(TIME_ATTR, "http.responseTime");
+
+genet_decoders!(HttpDecoder {});
@@ -0,0 +1,561 @@
+//! HPACK header decompression.
+//!
+//! See RFC 7541 for the details of the format.
+
+use genet_sdk::prelude::lazy_static;
+use std::{
+    collections::{HashMap, VecDeque},
+    error, fmt,
+};
+
+const ENTRY_OVERHEAD: usize = 32;
+const DEFAULT_TABLE_SIZE: usize = 4096;
+const EOS: u16 = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    Truncated,
+    InvalidIndex,
+    IntegerOverflow,
+    InvalidHuffmanCode,
+    TableSizeExceeded,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            Error::Truncated => "truncated header block",
+            Error::InvalidIndex => "invalid table index",
+            Error::IntegerOverflow => "integer overflow",
+            Error::InvalidHuffmanCode => "invalid Huffman code",
+            Error::TableSizeExceeded => "dynamic table size update exceeds the limit",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        "HPACK decoding error"
+    }
+}
+
+/// A header field decoded from a header block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub name: String,
+    pub value: String,
+}
+
+/// The decoding context of one direction of a connection.
+///
+/// The dynamic table is shared by all header blocks in the direction,
+/// so that the blocks must be decoded in order.
+#[derive(Debug)]
+pub struct Decoder {
+    table: VecDeque<Field>,
+    size: usize,
+    max_size: usize,
+    limit: usize,
+}
+
+impl Default for Decoder {
+    fn default() -> Decoder {
+        Decoder {
+            table: VecDeque::new(),
+            size: 0,
+            max_size: DEFAULT_TABLE_SIZE,
+            limit: DEFAULT_TABLE_SIZE,
+        }
+    }
+}
+
+impl Decoder {
+    /// Sets the upper limit of the dynamic table size,
+    /// i.e. SETTINGS_HEADER_TABLE_SIZE of the decoding endpoint.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    /// Decodes the complete header block.
+    pub fn decode(&mut self, block: &[u8]) -> Result<Vec<Field>, Error> {
+        let mut fields = Vec::new();
+        let mut pos = 0;
+        while pos < block.len() {
+            let data = &block[pos..];
+            if data[0] & 0x80 != 0 {
+                // Indexed Header Field
+                let (index, len) = integer(data, 7)?;
+                fields.push(self.get(index)?);
+                pos += len;
+            } else if data[0] & 0x40 != 0 {
+                // Literal Header Field with Incremental Indexing
+                let (field, len) = self.literal(data, 6)?;
+                self.insert(field.clone());
+                fields.push(field);
+                pos += len;
+            } else if data[0] & 0x20 != 0 {
+                // Dynamic Table Size Update
+                let (size, len) = integer(data, 5)?;
+                if size > self.limit {
+                    return Err(Error::TableSizeExceeded);
+                }
+                self.max_size = size;
+                self.evict(0);
+                pos += len;
+            } else {
+                // Literal Header Field without Indexing / Never Indexed
+                let (field, len) = self.literal(data, 4)?;
+                fields.push(field);
+                pos += len;
+            }
+        }
+        Ok(fields)
+    }
+
+    fn literal(&self, data: &[u8], prefix: u8) -> Result<(Field, usize), Error> {
+        let (index, mut pos) = integer(data, prefix)?;
+        let name = if index == 0 {
+            let (name, len) = string(&data[pos..])?;
+            pos += len;
+            name
+        } else {
+            self.get(index)?.name
+        };
+        let (value, len) = string(&data[pos..])?;
+        Ok((Field { name, value }, pos + len))
+    }
+
+    fn get(&self, index: usize) -> Result<Field, Error> {
+        if index == 0 {
+            Err(Error::InvalidIndex)
+        } else if index <= STATIC_TABLE.len() {
+            let (name, value) = STATIC_TABLE[index - 1];
+            Ok(Field {
+                name: name.into(),
+                value: value.into(),
+            })
+        } else {
+            self.table
+                .get(index - STATIC_TABLE.len() - 1)
+                .cloned()
+                .ok_or(Error::InvalidIndex)
+        }
+    }
+
+    fn insert(&mut self, field: Field) {
+        let size = field.name.len() + field.value.len() + ENTRY_OVERHEAD;
+        self.evict(size);
+        if size <= self.max_size {
+            self.size += size;
+            self.table.push_front(field);
+        }
+    }
+
+    /// Evicts the entries until `additional` bytes fit into the table.
+    fn evict(&mut self, additional: usize) {
+        while self.size + additional > self.max_size {
+            match self.table.pop_back() {
+                Some(field) => self.size -= field.name.len() + field.value.len() + ENTRY_OVERHEAD,
+                None => break,
+            }
+        }
+    }
+}
+
+/// Decodes the integer with the N-bit prefix and returns it with its length.
+fn integer(data: &[u8], prefix: u8) -> Result<(usize, usize), Error> {
+    let mask = (1 << prefix) - 1;
+    let value = (data[0] & mask) as usize;
+    if value < mask as usize {
+        return Ok((value, 1));
+    }
+    let mut value = value;
+    for (i, &b) in data[1..].iter().enumerate() {
+        if i >= 4 {
+            return Err(Error::IntegerOverflow);
+        }
+        value += ((b & 0x7f) as usize) << (i * 7);
+        if b & 0x80 == 0 {
+            return Ok((value, i + 2));
+        }
+    }
+    Err(Error::Truncated)
+}
+
+/// Decodes the string literal and returns it with its length.
+fn string(data: &[u8]) -> Result<(String, usize), Error> {
+    if data.is_empty() {
+        return Err(Error::Truncated);
+    }
+    let (len, pos) = integer(data, 7)?;
+    let bytes = data.get(pos..pos + len).ok_or(Error::Truncated)?;
+    let bytes = if data[0] & 0x80 != 0 {
+        huffman(bytes)?
+    } else {
+        bytes.to_vec()
+    };
+    Ok((String::from_utf8_lossy(&bytes).into_owned(), pos + len))
+}
+
+lazy_static! {
+    static ref HUFFMAN_SYMBOLS: HashMap<(u8, u32), u16> = HUFFMAN_CODES
+        .iter()
+        .enumerate()
+        .map(|(sym, &(code, len))| ((len, code), sym as u16))
+        .collect();
+}
+
+fn huffman(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut output = Vec::with_capacity(data.len() * 8 / 5);
+    let mut code = 0u32;
+    let mut len = 0u8;
+    for &b in data {
+        for i in (0..8).rev() {
+            code = code << 1 | u32::from(b >> i & 1);
+            len += 1;
+            match HUFFMAN_SYMBOLS.get(&(len, code)) {
+                Some(&EOS) => return Err(Error::InvalidHuffmanCode),
+                Some(&sym) => {
+                    output.push(sym as u8);
+                    code = 0;
+                    len = 0;
+                }
+                None if len >= 30 => return Err(Error::InvalidHuffmanCode),
+                None => {}
+            }
+        }
+    }
+
+    // The padding must be the most significant bits of EOS.
+    if len > 7 || code != (1 << len) - 1 {
+        return Err(Error::InvalidHuffmanCode);
+    }
+    Ok(output)
+}
+
+/// The static table defined in RFC 7541 Appendix A.
+const STATIC_TABLE: &[(&str, &str)] = &[
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+/// The Huffman codes and their bit lengths defined in RFC 7541 Appendix B.
+const HUFFMAN_CODES: &[(u32, u8)] = &[
+    (0x1ff8, 13),
+    (0x7fffd8, 23),
+    (0xfffffe2, 28),
+    (0xfffffe3, 28),
+    (0xfffffe4, 28),
+    (0xfffffe5, 28),
+    (0xfffffe6, 28),
+    (0xfffffe7, 28),
+    (0xfffffe8, 28),
+    (0xffffea, 24),
+    (0x3ffffffc, 30),
+    (0xfffffe9, 28),
+    (0xfffffea, 28),
+    (0x3ffffffd, 30),
+    (0xfffffeb, 28),
+    (0xfffffec, 28),
+    (0xfffffed, 28),
+    (0xfffffee, 28),
+    (0xfffffef, 28),
+    (0xffffff0, 28),
+    (0xffffff1, 28),
+    (0xffffff2, 28),
+    (0x3ffffffe, 30),
+    (0xffffff3, 28),
+    (0xffffff4, 28),
+    (0xffffff5, 28),
+    (0xffffff6, 28),
+    (0xffffff7, 28),
+    (0xffffff8, 28),
+    (0xffffff9, 28),
+    (0xffffffa, 28),
+    (0xffffffb, 28),
+    (0x14, 6),
+    (0x3f8, 10),
+    (0x3f9, 10),
+    (0xffa, 12),
+    (0x1ff9, 13),
+    (0x15, 6),
+    (0xf8, 8),
+    (0x7fa, 11),
+    (0x3fa, 10),
+    (0x3fb, 10),
+    (0xf9, 8),
+    (0x7fb, 11),
+    (0xfa, 8),
+    (0x16, 6),
+    (0x17, 6),
+    (0x18, 6),
+    (0x0, 5),
+    (0x1, 5),
+    (0x2, 5),
+    (0x19, 6),
+    (0x1a, 6),
+    (0x1b, 6),
+    (0x1c, 6),
+    (0x1d, 6),
+    (0x1e, 6),
+    (0x1f, 6),
+    (0x5c, 7),
+    (0xfb, 8),
+    (0x7ffc, 15),
+    (0x20, 6),
+    (0xffb, 12),
+    (0x3fc, 10),
+    (0x1ffa, 13),
+    (0x21, 6),
+    (0x5d, 7),
+    (0x5e, 7),
+    (0x5f, 7),
+    (0x60, 7),
+    (0x61, 7),
+    (0x62, 7),
+    (0x63, 7),
+    (0x64, 7),
+    (0x65, 7),
+    (0x66, 7),
+    (0x67, 7),
+    (0x68, 7),
+    (0x69, 7),
+    (0x6a, 7),
+    (0x6b, 7),
+    (0x6c, 7),
+    (0x6d, 7),
+    (0x6e, 7),
+    (0x6f, 7),
+    (0x70, 7),
+    (0x71, 7),
+    (0x72, 7),
+    (0xfc, 8),
+    (0x73, 7),
+    (0xfd, 8),
+    (0x1ffb, 13),
+    (0x7fff0, 19),
+    (0x1ffc, 13),
+    (0x3ffc, 14),
+    (0x22, 6),
+    (0x7ffd, 15),
+    (0x3, 5),
+    (0x23, 6),
+    (0x4, 5),
+    (0x24, 6),
+    (0x5, 5),
+    (0x25, 6),
+    (0x26, 6),
+    (0x27, 6),
+    (0x6, 5),
+    (0x74, 7),
+    (0x75, 7),
+    (0x28, 6),
+    (0x29, 6),
+    (0x2a, 6),
+    (0x7, 5),
+    (0x2b, 6),
+    (0x76, 7),
+    (0x2c, 6),
+    (0x8, 5),
+    (0x9, 5),
+    (0x2d, 6),
+    (0x77, 7),
+    (0x78, 7),
+    (0x79, 7),
+    (0x7a, 7),
+    (0x7b, 7),
+    (0x7ffe, 15),
+    (0x7fc, 11),
+    (0x3ffd, 14),
+    (0x1ffd, 13),
+    (0xffffffc, 28),
+    (0xfffe6, 20),
+    (0x3fffd2, 22),
+    (0xfffe7, 20),
+    (0xfffe8, 20),
+    (0x3fffd3, 22),
+    (0x3fffd4, 22),
+    (0x3fffd5, 22),
+    (0x7fffd9, 23),
+    (0x3fffd6, 22),
+    (0x7fffda, 23),
+    (0x7fffdb, 23),
+    (0x7fffdc, 23),
+    (0x7fffdd, 23),
+    (0x7fffde, 23),
+    (0xffffeb, 24),
+    (0x7fffdf, 23),
+    (0xffffec, 24),
+    (0xffffed, 24),
+    (0x3fffd7, 22),
+    (0x7fffe0, 23),
+    (0xffffee, 24),
+    (0x7fffe1, 23),
+    (0x7fffe2, 23),
+    (0x7fffe3, 23),
+    (0x7fffe4, 23),
+    (0x1fffdc, 21),
+    (0x3fffd8, 22),
+    (0x7fffe5, 23),
+    (0x3fffd9, 22),
+    (0x7fffe6, 23),
+    (0x7fffe7, 23),
+    (0xffffef, 24),
+    (0x3fffda, 22),
+    (0x1fffdd, 21),
+    (0xfffe9, 20),
+    (0x3fffdb, 22),
+    (0x3fffdc, 22),
+    (0x7fffe8, 23),
+    (0x7fffe9, 23),
+    (0x1fffde, 21),
+    (0x7fffea, 23),
+    (0x3fffdd, 22),
+    (0x3fffde, 22),
+    (0xfffff0, 24),
+    (0x1fffdf, 21),
+    (0x3fffdf, 22),
+    (0x7fffeb, 23),
+    (0x7fffec, 23),
+    (0x1fffe0, 21),
+    (0x1fffe1, 21),
+    (0x3fffe0, 22),
+    (0x1fffe2, 21),
+    (0x7fffed, 23),
+    (0x3fffe1, 22),
+    (0x7fffee, 23),
+    (0x7fffef, 23),
+    (0xfffea, 20),
+    (0x3fffe2, 22),
+    (0x3fffe3, 22),
+    (0x3fffe4, 22),
+    (0x7ffff0, 23),
+    (0x3fffe5, 22),
+    (0x3fffe6, 22),
+    (0x7ffff1, 23),
+    (0x3ffffe0, 26),
+    (0x3ffffe1, 26),
+    (0xfffeb, 20),
+    (0x7fff1, 19),
+    (0x3fffe7, 22),
+    (0x7ffff2, 23),
+    (0x3fffe8, 22),
+    (0x1ffffec, 25),
+    (0x3ffffe2, 26),
+    (0x3ffffe3, 26),
+    (0x3ffffe4, 26),
+    (0x7ffffde, 27),
+    (0x7ffffdf, 27),
+    (0x3ffffe5, 26),
+    (0xfffff1, 24),
+    (0x1ffffed, 25),
+    (0x7fff2, 19),
+    (0x1fffe3, 21),
+    (0x3ffffe6, 26),
+    (0x7ffffe0, 27),
+    (0x7ffffe1, 27),
+    (0x3ffffe7, 26),
+    (0x7ffffe2, 27),
+    (0xfffff2, 24),
+    (0x1fffe4, 21),
+    (0x1fffe5, 21),
+    (0x3ffffe8, 26),
+    (0x3ffffe9, 26),
+    (0xffffffd, 28),
+    (0x7ffffe3, 27),
+    (0x7ffffe4, 27),
+    (0x7ffffe5, 27),
+    (0xfffec, 20),
+    (0xfffff3, 24),
+    (0xfffed, 20),
+    (0x1fffe6, 21),
+    (0x3fffe9, 22),
+    (0x1fffe7, 21),
+    (0x1fffe8, 21),
+    (0x7ffff3, 23),
+    (0x3fffea, 22),
+    (0x3fffeb, 22),
+    (0x1ffffee, 25),
+    (0x1ffffef, 25),
+    (0xfffff4, 24),
+    (0xfffff5, 24),
+    (0x3ffffea, 26),
+    (0x7ffff4, 23),
+    (0x3ffffeb, 26),
+    (0x7ffffe6, 27),
+    (0x3ffffec, 26),
+    (0x3ffffed, 26),
+    (0x7ffffe7, 27),
+    (0x7ffffe8, 27),
+    (0x7ffffe9, 27),
+    (0x7ffffea, 27),
+    (0x7ffffeb, 27),
+    (0xffffffe, 28),
+    (0x7ffffec, 27),
+    (0x7ffffed, 27),
+    (0x7ffffee, 27),
+    (0x7ffffef, 27),
+    (0x7fffff0, 27),
+    (0x3ffffee, 26),
+    (0x3fffffff, 30),
+];
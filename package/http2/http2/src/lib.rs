@@ -0,0 +1,701 @@
+extern crate genet_sdk;
+
+mod hpack;
+
+use genet_sdk::{
+    cast,
+    context::{flow_key, state_key},
+    decoder::*,
+    endpoint::endpoints,
+    prelude::*,
+    tcp::PduBuffer,
+};
+use std::collections::HashMap;
+
+const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+const FRAME_HEADER_LEN: usize = 9;
+
+const DATA: u8 = 0;
+const HEADERS: u8 = 1;
+const PRIORITY: u8 = 2;
+const RST_STREAM: u8 = 3;
+const SETTINGS: u8 = 4;
+const PUSH_PROMISE: u8 = 5;
+const PING: u8 = 6;
+const GOAWAY: u8 = 7;
+const WINDOW_UPDATE: u8 = 8;
+const CONTINUATION: u8 = 9;
+
+const FLAG_END_STREAM: u8 = 0x1;
+const FLAG_ACK: u8 = 0x1;
+const FLAG_END_HEADERS: u8 = 0x4;
+const FLAG_PADDED: u8 = 0x8;
+const FLAG_PRIORITY: u8 = 0x20;
+
+const SETTINGS_HEADER_TABLE_SIZE: u16 = 1;
+
+/// The state of one direction of a TCP connection.
+#[derive(Default)]
+struct Stream {
+    http2: bool,
+    buffer: PduBuffer,
+    hpack: hpack::Decoder,
+    fragment: Option<Fragment>,
+    content_types: HashMap<u32, String>,
+}
+
+/// A header block waiting for CONTINUATION frames.
+struct Fragment {
+    frame_stream_id: u32,
+    stream_id: u32,
+    block: Vec<u8>,
+}
+
+/// The requests waiting for the responses, shared by both directions.
+#[derive(Default)]
+struct Connection {
+    requests: HashMap<u32, Request>,
+}
+
+struct Request {
+    frame: u32,
+    timestamp: f64,
+}
+
+struct Http2Worker {}
+
+impl Worker for Http2Worker {
+    fn decode(
+        &mut self,
+        ctx: &mut Context,
+        stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        if parent.id() != token!("tcp") {
+            return Ok(Status::Skip);
+        }
+
+        let data;
+
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@stream:tcp"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let (src, dst) = match endpoints(stack, parent) {
+            Some(endpoints) => endpoints,
+            None => return Ok(Status::Skip),
+        };
+        let src = (&(src.0)[..], src.1);
+        let dst = (&(dst.0)[..], dst.1);
+        let key = state_key(&(src, dst));
+        let peer_key = state_key(&(dst, src));
+        let conn_key = flow_key(src, dst);
+
+        // The connection preface of the client determines whether the connection is HTTP/2.
+        let preface = ctx.state::<Connection>(conn_key).is_some();
+        let frames = {
+            let stream = ctx.state_or_insert_with(key, || Stream {
+                http2: preface || data.starts_with(PREFACE) || is_settings(&data),
+                ..Stream::default()
+            });
+            if !stream.http2 {
+                return Ok(Status::Skip);
+            }
+            stream.buffer.push(data, frame_len)
+        };
+        if !preface {
+            ctx.set_state(conn_key, Connection::default());
+        }
+
+        let frame = ctx.frame_index();
+        let timestamp = timestamp(stack);
+        for data in frames {
+            if data.starts_with(PREFACE) {
+                let mut layer = Layer::new(&PREFACE_CLASS, data);
+                layer.add_attr(attr!(&PREFACE_ATTR, range: 0..PREFACE.len()));
+                parent.add_child(layer);
+                continue;
+            }
+
+            let mut layer = Layer::new(&HTTP2_CLASS, data);
+            let message = {
+                let stream = ctx.state::<Stream>(key).unwrap();
+                decode_frame(&mut layer, stream)?
+            };
+            if let Some(size) = table_size(&layer.data()) {
+                let peer = ctx.state_or_insert_with(peer_key, Stream::default);
+                peer.http2 = true;
+                peer.hpack.set_limit(size);
+            }
+            if let Some(mut message) = message {
+                let conn = ctx.state::<Connection>(conn_key).unwrap();
+                link(conn, &mut message, frame, timestamp)?;
+                parent.add_child(layer);
+                parent.add_child(message);
+            } else {
+                parent.add_child(layer);
+            }
+        }
+        Ok(Status::Done)
+    }
+}
+
+fn timestamp(stack: &LayerStack) -> f64 {
+    stack
+        .bottom()
+        .and_then(|link| {
+            link.attr(token!("link.timestamp"))
+                .and_then(|attr| attr.try_get(link).ok())
+        })
+        .and_then(|value| value.try_into().ok())
+        .unwrap_or(0.0)
+}
+
+/// Returns true if the data begins with a SETTINGS frame,
+/// which is the connection preface of the server.
+fn is_settings(data: &[u8]) -> bool {
+    data.len() >= FRAME_HEADER_LEN && data[3] == SETTINGS && data[5..9] == [0, 0, 0, 0]
+}
+
+fn u24(data: &[u8]) -> usize {
+    (data[0] as usize) << 16 | (data[1] as usize) << 8 | data[2] as usize
+}
+
+fn u31(data: &[u8]) -> u32 {
+    (u32::from(data[0]) << 24
+        | u32::from(data[1]) << 16
+        | u32::from(data[2]) << 8
+        | u32::from(data[3]))
+        & 0x7fff_ffff
+}
+
+fn frame_len(data: &[u8]) -> Option<usize> {
+    if data[0] == PREFACE[0] && PREFACE.starts_with(&data[..data.len().min(PREFACE.len())]) {
+        Some(PREFACE.len())
+    } else if data.len() < FRAME_HEADER_LEN {
+        None
+    } else {
+        Some(FRAME_HEADER_LEN + u24(data))
+    }
+}
+
+/// Decodes the frame and returns the message layer if the frame completes
+/// a header block or carries data.
+fn decode_frame(layer: &mut Layer, stream: &mut Stream) -> Result<Option<Layer>> {
+    let data = layer.data();
+    let typ = data[3];
+    let flags = data[4];
+    let stream_id = u31(&data[5..9]);
+    let end = data.len();
+
+    match typ {
+        DATA | HEADERS => {
+            add_flag(layer, &END_STREAM_ATTR, flags & FLAG_END_STREAM != 0);
+        }
+        SETTINGS | PING => {
+            add_flag(layer, &ACK_ATTR, flags & FLAG_ACK != 0);
+        }
+        _ => {}
+    }
+    match typ {
+        HEADERS | PUSH_PROMISE | CONTINUATION => {
+            add_flag(layer, &END_HEADERS_ATTR, flags & FLAG_END_HEADERS != 0);
+        }
+        _ => {}
+    }
+
+    // Strip the padding.
+    let mut pos = FRAME_HEADER_LEN;
+    let mut end = end;
+    if typ == DATA || typ == HEADERS || typ == PUSH_PROMISE {
+        add_flag(layer, &PADDED_ATTR, flags & FLAG_PADDED != 0);
+        if flags & FLAG_PADDED != 0 {
+            let pad = match data.get(pos) {
+                Some(&pad) if pos + 1 + (pad as usize) <= end => pad as usize,
+                _ => {
+                    layer.error("invalid padding");
+                    return Ok(None);
+                }
+            };
+            layer.add_attr(attr!(&PAD_LENGTH_ATTR, range: pos..pos + 1));
+            layer.add_attr(attr!(&PADDING_ATTR, range: end - pad..end));
+            pos += 1;
+            end -= pad;
+        }
+    }
+
+    if stream_id == 0
+        && [
+            DATA,
+            HEADERS,
+            PRIORITY,
+            RST_STREAM,
+            PUSH_PROMISE,
+            CONTINUATION,
+        ]
+        .contains(&typ)
+    {
+        layer.error("frame on stream 0");
+        return Ok(None);
+    }
+    if stream.fragment.is_some() && typ != CONTINUATION {
+        layer.error("header block interrupted");
+        stream.fragment = None;
+    }
+
+    match typ {
+        DATA => {
+            layer.add_attr(attr!(&DATA_ATTR, range: pos..end));
+            if pos == end {
+                return Ok(None);
+            }
+            let mut message = Layer::new(&MESSAGE_CLASS, data.try_get(pos..end)?);
+            message.add_attr(attr!(&STREAM_ID_ATTR, value: stream_id));
+            message.add_attr(attr!(&BODY_ATTR, range: 0..end - pos));
+            let typ = stream
+                .content_types
+                .get(&stream_id)
+                .cloned()
+                .unwrap_or_default();
+            message.add_payload(
+                Payload::with_typ(data.try_get(pos..end)?, "@data:http", typ).tagged("body"),
+            );
+            if flags & FLAG_END_STREAM != 0 {
+                stream.content_types.remove(&stream_id);
+            }
+            return Ok(Some(message));
+        }
+        HEADERS => {
+            if flags & FLAG_PRIORITY != 0 {
+                add_flag(layer, &PRIORITY_FLAG_ATTR, true);
+                if pos + 5 > end {
+                    layer.error("truncated priority");
+                    return Ok(None);
+                }
+                add_priority(layer, pos);
+                pos += 5;
+            }
+            layer.add_attr(attr!(&HEADER_BLOCK_ATTR, range: pos..end));
+            return Ok(header_block(
+                layer,
+                stream,
+                (stream_id, stream_id),
+                &data[pos..end],
+                flags,
+            ));
+        }
+        PRIORITY => {
+            if end - pos != 5 {
+                layer.error("invalid frame size");
+            } else {
+                add_priority(layer, pos);
+            }
+        }
+        RST_STREAM => {
+            if end - pos != 4 {
+                layer.error("invalid frame size");
+            } else {
+                layer.add_attr(attr!(&ERROR_CODE_ATTR, range: pos..pos + 4));
+            }
+        }
+        SETTINGS => {
+            if (end - pos) % 6 != 0 || (flags & FLAG_ACK != 0 && end > pos) {
+                layer.error("invalid frame size");
+            } else {
+                for pos in (pos..end).step_by(6) {
+                    layer.add_attr(attr!(&SETTING_ATTR, range: pos..pos + 6));
+                    layer.add_attr(attr!(&SETTING_ID_ATTR, range: pos..pos + 2));
+                    layer.add_attr(attr!(&SETTING_VALUE_ATTR, range: pos + 2..pos + 6));
+                }
+            }
+        }
+        PUSH_PROMISE => {
+            if pos + 4 > end {
+                layer.error("invalid frame size");
+                return Ok(None);
+            }
+            layer.add_attr(attr!(&PROMISED_STREAM_ID_ATTR, range: pos..pos + 4));
+            let promised = u31(&data[pos..pos + 4]);
+            layer.add_attr(attr!(&HEADER_BLOCK_ATTR, range: pos + 4..end));
+            return Ok(header_block(
+                layer,
+                stream,
+                (stream_id, promised),
+                &data[pos + 4..end],
+                flags,
+            ));
+        }
+        PING => {
+            if end - pos != 8 {
+                layer.error("invalid frame size");
+            } else {
+                layer.add_attr(attr!(&OPAQUE_DATA_ATTR, range: pos..end));
+            }
+        }
+        GOAWAY => {
+            if end - pos < 8 {
+                layer.error("invalid frame size");
+            } else {
+                layer.add_attr(attr!(&LAST_STREAM_ID_ATTR, range: pos..pos + 4));
+                layer.add_attr(attr!(&ERROR_CODE_ATTR, range: pos + 4..pos + 8));
+                if end > pos + 8 {
+                    layer.add_attr(attr!(&DEBUG_DATA_ATTR, range: pos + 8..end));
+                }
+            }
+        }
+        WINDOW_UPDATE => {
+            if end - pos != 4 {
+                layer.error("invalid frame size");
+            } else {
+                layer.add_attr(attr!(&WINDOW_SIZE_INCREMENT_ATTR, range: pos..pos + 4));
+                if u31(&data[pos..pos + 4]) == 0 {
+                    layer.annotate(
+                        Annotation::new(Severity::Error, "zero window size increment")
+                            .with_attr("http2.windowSizeIncrement"),
+                    );
+                }
+            }
+        }
+        CONTINUATION => {
+            layer.add_attr(attr!(&HEADER_BLOCK_ATTR, range: pos..end));
+            let target = match stream.fragment {
+                Some(ref fragment) if fragment.frame_stream_id == stream_id => fragment.stream_id,
+                _ => {
+                    layer.error("unexpected continuation");
+                    return Ok(None);
+                }
+            };
+            return Ok(header_block(
+                layer,
+                stream,
+                (stream_id, target),
+                &data[pos..end],
+                flags,
+            ));
+        }
+        _ => {}
+    }
+    Ok(None)
+}
+
+fn add_flag<C: Into<Fixed<AttrClass>>>(layer: &mut Layer, class: C, value: bool) {
+    layer.add_attr(attr!(class, range: 4..5, value: value));
+}
+
+fn add_priority(layer: &mut Layer, pos: usize) {
+    layer.add_attr(attr!(&EXCLUSIVE_ATTR, range: pos..pos + 1));
+    layer.add_attr(attr!(&STREAM_DEPENDENCY_ATTR, range: pos..pos + 4));
+    layer.add_attr(attr!(&WEIGHT_ATTR, range: pos + 4..pos + 5));
+}
+
+/// Appends the header block fragment and decodes the block if it is complete.
+///
+/// `ids` are the stream identifier of the frame and the stream which
+/// the header block belongs to, which differ in PUSH_PROMISE.
+fn header_block(
+    layer: &mut Layer,
+    stream: &mut Stream,
+    ids: (u32, u32),
+    fragment: &[u8],
+    flags: u8,
+) -> Option<Layer> {
+    let mut block = match stream.fragment.take() {
+        Some(fragment) => fragment.block,
+        None => Vec::new(),
+    };
+    block.extend_from_slice(fragment);
+    let (frame_stream_id, stream_id) = ids;
+    if flags & FLAG_END_HEADERS == 0 {
+        stream.fragment = Some(Fragment {
+            frame_stream_id,
+            stream_id,
+            block,
+        });
+        return None;
+    }
+
+    let fields = match stream.hpack.decode(&block) {
+        Ok(fields) => fields,
+        Err(err) => {
+            layer.error(&format!("header decompression failed: {}", err));
+            return None;
+        }
+    };
+
+    let mut message = Layer::new(&MESSAGE_CLASS, ByteSlice::from(block));
+    message.add_attr(attr!(&STREAM_ID_ATTR, value: stream_id));
+    message.add_attr(attr!(&VERSION_ATTR, value: Box::<str>::from("HTTP/2")));
+    for field in fields {
+        let value = field.value.clone();
+        match field.name.as_str() {
+            ":method" => message.add_attr(attr!(&METHOD_ATTR, value: value.into_boxed_str())),
+            ":scheme" => message.add_attr(attr!(&SCHEME_ATTR, value: value.into_boxed_str())),
+            ":authority" => message.add_attr(attr!(&AUTHORITY_ATTR, value: value.into_boxed_str())),
+            ":path" => message.add_attr(attr!(&TARGET_ATTR, value: value.into_boxed_str())),
+            ":status" => match value.parse::<u64>() {
+                Ok(status) => message.add_attr(attr!(&STATUS_ATTR, value: status)),
+                Err(_) => message.error("invalid status"),
+            },
+            "content-length" => match value.parse::<u64>() {
+                Ok(len) => message.add_attr(attr!(&CONTENT_LENGTH_ATTR, value: len)),
+                Err(_) => message.error("invalid content length"),
+            },
+            "content-type" => {
+                let media = value.split(';').next().unwrap_or("").trim();
+                stream
+                    .content_types
+                    .insert(stream_id, media.to_ascii_lowercase());
+                message.add_attr(attr!(&CONTENT_TYPE_ATTR, value: value.into_boxed_str()));
+            }
+            _ => {}
+        }
+        message.add_attr(attr!(&HEADER_ATTR));
+        message.add_attr(attr!(&HEADER_NAME_ATTR, value: field.name.into_boxed_str()));
+        message.add_attr(attr!(&HEADER_VALUE_ATTR, value: field.value.into_boxed_str()));
+    }
+    Some(message)
+}
+
+/// Links the response to the request on the same stream.
+fn link(conn: &mut Connection, message: &mut Layer, frame: u32, timestamp: f64) -> Result<()> {
+    let stream_id: u32 = match message.attr(token!("http.streamId")) {
+        Some(attr) => attr.try_get(message)?.try_into()?,
+        None => return Ok(()),
+    };
+    if message.attr(token!("http.method")).is_some() {
+        conn.requests
+            .insert(stream_id, Request { frame, timestamp });
+    } else if let Some(attr) = message.attr(token!("http.status")) {
+        let status: u64 = attr.try_get(message)?.try_into()?;
+        // Informational responses precede the final response.
+        if status >= 200 {
+            if let Some(request) = conn.requests.remove(&stream_id) {
+                message.add_link(
+                    Link::new("http.request", request.frame).with_reverse("http.response"),
+                );
+                message.add_attr(attr!(&TIME_ATTR, value: timestamp - request.timestamp));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns SETTINGS_HEADER_TABLE_SIZE in the SETTINGS frame.
+fn table_size(data: &[u8]) -> Option<usize> {
+    if data[3] != SETTINGS || data[4] & FLAG_ACK != 0 {
+        return None;
+    }
+    data[FRAME_HEADER_LEN..]
+        .chunks(6)
+        .filter(|setting| setting.len() == 6)
+        .filter(|setting| {
+            (u16::from(setting[0]) << 8 | u16::from(setting[1])) == SETTINGS_HEADER_TABLE_SIZE
+        })
+        .map(|setting| u31(&setting[2..]) as usize)
+        .last()
+}
+
+#[derive(Clone)]
+struct Http2Decoder {}
+
+impl Decoder for Http2Decoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(Http2Worker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.decoder.http2".into(),
+            name: "HTTP/2".into(),
+            description: "Hypertext Transfer Protocol Version 2".into(),
+            exec_type: ExecType::SerialSync,
+            triggers: vec![
+                Trigger::new("tcp.src", 80),
+                Trigger::new("tcp.dst", 80),
+                Trigger::new("tcp.src", 8080),
+                Trigger::new("tcp.dst", 8080),
+            ],
+            references: vec![
+                "https://tools.ietf.org/html/rfc7540".into(),
+                "https://tools.ietf.org/html/rfc7541".into(),
+            ],
+            ..Metadata::default()
+        }
+    }
+}
+
+fn error_code_labels() -> Vec<(u64, &'static str)> {
+    vec![
+        (0x0, "NO_ERROR"),
+        (0x1, "PROTOCOL_ERROR"),
+        (0x2, "INTERNAL_ERROR"),
+        (0x3, "FLOW_CONTROL_ERROR"),
+        (0x4, "SETTINGS_TIMEOUT"),
+        (0x5, "STREAM_CLOSED"),
+        (0x6, "FRAME_SIZE_ERROR"),
+        (0x7, "REFUSED_STREAM"),
+        (0x8, "CANCEL"),
+        (0x9, "COMPRESSION_ERROR"),
+        (0xa, "CONNECT_ERROR"),
+        (0xb, "ENHANCE_YOUR_CALM"),
+        (0xc, "INADEQUATE_SECURITY"),
+        (0xd, "HTTP_1_1_REQUIRED"),
+    ]
+}
+
+def_layer_class!(PREFACE_CLASS, "http2");
+
+def_layer_class!(HTTP2_CLASS, "http2",
+    header: attr!(&LENGTH_ATTR, range: 0..3),
+    header: attr!(&TYPE_ATTR, range: 3..4),
+    header: attr!(&FLAGS_ATTR, range: 4..5),
+    header: attr!(&STREAM_ID_HEADER_ATTR, range: 5..9)
+);
+
+def_attr_class!(PREFACE_ATTR, "http2.preface", cast: cast::Utf8());
+
+def_attr_class!(LENGTH_ATTR, "http2.length",
+    cast: cast::ByteSlice().map(|v| u32::from(v[0]) << 16 | u32::from(v[1]) << 8 | u32::from(v[2]))
+);
+
+def_attr_class!(TYPE_ATTR, "http2.type",
+    typ: "@enum",
+    cast: cast::UInt8(),
+    labels: vec![
+        (0x0, "DATA"),
+        (0x1, "HEADERS"),
+        (0x2, "PRIORITY"),
+        (0x3, "RST_STREAM"),
+        (0x4, "SETTINGS"),
+        (0x5, "PUSH_PROMISE"),
+        (0x6, "PING"),
+        (0x7, "GOAWAY"),
+        (0x8, "WINDOW_UPDATE"),
+        (0x9, "CONTINUATION"),
+    ]
+);
+
+def_attr_class!(FLAGS_ATTR, "http2.flags",
+    typ: "@flags",
+    cast: cast::UInt8()
+);
+
+def_attr_class!(END_STREAM_ATTR, "http2.flags.endStream");
+
+def_attr_class!(ACK_ATTR, "http2.flags.ack");
+
+def_attr_class!(END_HEADERS_ATTR, "http2.flags.endHeaders");
+
+def_attr_class!(PADDED_ATTR, "http2.flags.padded");
+
+def_attr_class!(PRIORITY_FLAG_ATTR, "http2.flags.priority");
+
+def_attr_class!(STREAM_ID_HEADER_ATTR, "http2.streamId",
+    cast: cast::UInt32BE().map(|v| v & 0x7fff_ffff)
+);
+
+def_attr_class!(PAD_LENGTH_ATTR, "http2.padLength", cast: cast::UInt8());
+
+def_attr_class!(PADDING_ATTR, "http2.padding", cast: cast::ByteSlice());
+
+def_attr_class!(DATA_ATTR, "http2.data", cast: cast::ByteSlice());
+
+def_attr_class!(HEADER_BLOCK_ATTR, "http2.headerBlock", cast: cast::ByteSlice());
+
+def_attr_class!(EXCLUSIVE_ATTR, "http2.exclusive",
+    cast: cast::UInt8().map(|v| (v & 0b1000_0000) != 0)
+);
+
+def_attr_class!(STREAM_DEPENDENCY_ATTR, "http2.streamDependency",
+    cast: cast::UInt32BE().map(|v| v & 0x7fff_ffff)
+);
+
+def_attr_class!(WEIGHT_ATTR, "http2.weight",
+    cast: cast::UInt8().map(|v| u16::from(v) + 1)
+);
+
+def_attr_class!(ERROR_CODE_ATTR, "http2.errorCode",
+    typ: "@enum",
+    cast: cast::UInt32BE(),
+    labels: error_code_labels()
+);
+
+def_attr_class!(SETTING_ATTR, "http2.setting",
+    typ: "@nested",
+    repeated: ,
+    value: true
+);
+
+def_attr_class!(SETTING_ID_ATTR, "http2.setting.identifier",
+    typ: "@enum",
+    cast: cast::UInt16BE(),
+    labels: vec![
+        (0x1, "SETTINGS_HEADER_TABLE_SIZE"),
+        (0x2, "SETTINGS_ENABLE_PUSH"),
+        (0x3, "SETTINGS_MAX_CONCURRENT_STREAMS"),
+        (0x4, "SETTINGS_INITIAL_WINDOW_SIZE"),
+        (0x5, "SETTINGS_MAX_FRAME_SIZE"),
+        (0x6, "SETTINGS_MAX_HEADER_LIST_SIZE"),
+        (0x8, "SETTINGS_ENABLE_CONNECT_PROTOCOL"),
+    ]
+);
+
+def_attr_class!(SETTING_VALUE_ATTR, "http2.setting.value", cast: cast::UInt32BE());
+
+def_attr_class!(PROMISED_STREAM_ID_ATTR, "http2.promisedStreamId",
+    cast: cast::UInt32BE().map(|v| v & 0x7fff_ffff)
+);
+
+def_attr_class!(OPAQUE_DATA_ATTR, "http2.opaqueData", cast: cast::ByteSlice());
+
+def_attr_class!(LAST_STREAM_ID_ATTR, "http2.lastStreamId",
+    cast: cast::UInt32BE().map(|v| v & 0x7fff_ffff)
+);
+
+def_attr_class!(DEBUG_DATA_ATTR, "http2.debugData", cast: cast::ByteSlice());
+
+def_attr_class!(WINDOW_SIZE_INCREMENT_ATTR, "http2.windowSizeIncrement",
+    cast: cast::UInt32BE().map(|v| v & 0x7fff_ffff)
+);
+
+def_layer_class!(MESSAGE_CLASS, "http");
+
+def_attr_class!(STREAM_ID_ATTR, "http.streamId");
+
+def_attr_class!(VERSION_ATTR, "http.version");
+
+def_attr_class!(METHOD_ATTR, "http.method");
+
+def_attr_class!(SCHEME_ATTR, "http.scheme");
+
+def_attr_class!(AUTHORITY_ATTR, "http.authority");
+
+def_attr_class!(TARGET_ATTR, "http.target");
+
+def_attr_class!(STATUS_ATTR, "http.status");
+
+def_attr_class!(HEADER_ATTR, "http.header",
+    typ: "@nested",
+    repeated: ,
+    value: true
+);
+
+def_attr_class!(HEADER_NAME_ATTR, "http.header.name");
+
+def_attr_class!(HEADER_VALUE_ATTR, "http.header.value");
+
+def_attr_class!(CONTENT_LENGTH_ATTR, "http.contentLength");
+
+def_attr_class!(CONTENT_TYPE_ATTR, "http.contentType");
+
+def_attr_class!(BODY_ATTR, "http.body", cast: cast::ByteSlice());
+
+def_attr_class!(TIME_ATTR, "http.responseTime");
+
+genet_decoders!(Http2Decoder {});
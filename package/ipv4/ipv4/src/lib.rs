@@ -1,13 +1,13 @@
 extern crate genet_sdk;
 
-use genet_sdk::{cast, decoder::*, prelude::*};
+use genet_sdk::{cast, checksum, decoder::*, fragment::*, prelude::*};
 
 struct IPv4Worker {}
 
 impl Worker for IPv4Worker {
     fn decode(
         &mut self,
-        _ctx: &mut Context,
+        ctx: &mut Context,
         _stack: &LayerStack,
         parent: &mut Parent,
     ) -> Result<Status> {
@@ -24,11 +24,19 @@ impl Worker for IPv4Worker {
         }
 
         let mut layer = Layer::new(&IPV4_CLASS, data);
-        let proto = PROTO_ATTR_HEADER.try_get(&layer)?.try_into()?;
-        if let Some((typ, attr)) = get_proto(proto) {
-            layer.add_attr(attr!(attr, range: 9..10));
-            let payload = layer.data().try_get(20..)?;
-            layer.add_payload(Payload::new(payload, typ));
+        if checksum::is_enabled(ctx) {
+            validate_checksum(&mut layer)?;
+        }
+
+        let more: u64 = FLAGS_MF_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        let offset: u64 = OFFSET_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        if more != 0 || offset > 0 {
+            let hlen: u64 = HLEN_ATTR_HEADER.try_get(&layer)?.try_into()?;
+            let len: u64 = LENGTH_ATTR_HEADER.try_get(&layer)?.try_into()?;
+            let payload = layer.data().try_get((hlen as usize * 4)..(len as usize))?;
+            layer.add_payload(Payload::new(payload, "@fragment:ipv4"));
+        } else {
+            add_proto_payload(&mut layer)?;
         }
 
         parent.add_child(layer);
@@ -36,6 +44,30 @@ impl Worker for IPv4Worker {
     }
 }
 
+fn validate_checksum(layer: &mut Layer) -> Result<()> {
+    let hlen: usize = HLEN_ATTR_HEADER.try_get(layer)?.try_into()?;
+    let header = layer.data().try_get(0..hlen * 4)?;
+    let mut sum = checksum::Internet::new();
+    sum.update(&header[..10]);
+    sum.update(&header[12..]);
+    let expected = sum.finish();
+    let actual = (u16::from(header[10]) << 8) | u16::from(header[11]);
+    if actual != expected {
+        layer.annotate(checksum::bad_checksum("ipv4.checksum", u64::from(expected)));
+    }
+    Ok(())
+}
+
+fn add_proto_payload(layer: &mut Layer) -> Result<()> {
+    let proto = PROTO_ATTR_HEADER.try_get(layer)?.try_into()?;
+    if let Some((typ, attr)) = get_proto(proto) {
+        layer.add_attr(attr!(attr, range: 9..10));
+        let payload = layer.data().try_get(20..)?;
+        layer.add_payload(Payload::new(payload, typ));
+    }
+    Ok(())
+}
+
 #[derive(Clone)]
 struct IPv4Decoder {}
 
@@ -46,7 +78,107 @@ impl Decoder for IPv4Decoder {
 
     fn metadata(&self) -> Metadata {
         Metadata {
+            id: "app.genet.decoder.ipv4".into(),
+            name: "IPv4".into(),
+            description: "Internet Protocol version 4".into(),
             exec_type: ExecType::ParallelSync,
+            references: vec!["https://tools.ietf.org/html/rfc791".into()],
+            ..Metadata::default()
+        }
+    }
+}
+
+struct IPv4FragmentWorker {
+    reassembler: FragmentReassembler,
+}
+
+impl Worker for IPv4FragmentWorker {
+    fn decode(
+        &mut self,
+        ctx: &mut Context,
+        stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        if parent.id() != token!("ipv4") {
+            return Ok(Status::Skip);
+        }
+
+        let data;
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@fragment:ipv4"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let key = FragmentKey {
+            src: SRC_ATTR_HEADER.try_get(parent)?.try_into()?,
+            dst: DST_ATTR_HEADER.try_get(parent)?.try_into()?,
+            id: ID_ATTR_HEADER.try_get(parent)?.try_into()?,
+            protocol: PROTO_ATTR_HEADER.try_get(parent)?.try_into()?,
+        };
+        let more: u64 = FLAGS_MF_ATTR_HEADER.try_get(parent)?.try_into()?;
+        let offset: u64 = OFFSET_ATTR_HEADER.try_get(parent)?.try_into()?;
+        let timestamp: f64 = stack
+            .attr(token!("link.timestamp"))
+            .and_then(|attr| attr.try_get(parent).ok())
+            .and_then(|value| value.try_into().ok())
+            .unwrap_or(0.0);
+        let fragment = Fragment {
+            frame: ctx.frame_index(),
+            timestamp,
+            offset: offset as usize * 8,
+            more: more != 0,
+            data,
+        };
+
+        if let Some(datagram) = self.reassembler.push(key, fragment) {
+            for frame in datagram.frames {
+                if frame != fragment.frame {
+                    parent.add_link(
+                        Link::new("ipv4.fragment", frame).with_reverse("ipv4.reassembledIn"),
+                    );
+                }
+            }
+
+            let mut data = parent.data().try_get(0..20)?.to_vec();
+            let len = data.len() + datagram.data.len();
+            data[0] = (data[0] & 0xf0) | 5;
+            data[2] = (len >> 8) as u8;
+            data[3] = len as u8;
+            data[6] &= 0b0100_0000;
+            data[7] = 0;
+            data.extend_from_slice(&datagram.data);
+
+            let mut layer = Layer::new(&IPV4_CLASS, ByteSlice::from(data));
+            layer.add_attr(attr!(&REASSEMBLED_ATTR));
+            add_proto_payload(&mut layer)?;
+            parent.add_child(layer);
+        }
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct IPv4FragmentDecoder {}
+
+impl Decoder for IPv4FragmentDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(IPv4FragmentWorker {
+            reassembler: FragmentReassembler::new(),
+        })
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.decoder.ipv4-fragment".into(),
+            name: "IPv4 Fragment".into(),
+            description: "IPv4 fragment reassembly".into(),
+            exec_type: ExecType::SerialSync,
+            references: vec!["https://tools.ietf.org/html/rfc791".into()],
             ..Metadata::default()
         }
     }
@@ -56,23 +188,30 @@ def_layer_class!(IPV4_CLASS, "ipv4",
     alias: "_.src" "ipv4.src",
     alias: "_.dst" "ipv4.dst",
     header: attr!(&VERSION_ATTR, bit_range: 0 0..4),
-    header: attr!(&HLEN_ATTR, bit_range: 0 4..8),
+    header: &HLEN_ATTR_HEADER,
     header: attr!(&TOS_ATTR, range: 1..2),
-    header: attr!(&LENGTH_ATTR, range: 2..4),
-    header: attr!(&ID_ATTR, range: 4..6),
+    header: &LENGTH_ATTR_HEADER,
+    header: &ID_ATTR_HEADER,
     header: attr!(&FLAGS_ATTR, bit_range: 6 0..1),
     header: attr!(&FLAGS_RV_ATTR, bit_range: 6 1..2),
     header: attr!(&FLAGS_DF_ATTR, bit_range: 6 2..3),
-    header: attr!(&FLAGS_MF_ATTR, bit_range: 6 3..4),
-    header: attr!(&OFFSET_ATTR, bit_range: 6 4..16),
+    header: &FLAGS_MF_ATTR_HEADER,
+    header: &OFFSET_ATTR_HEADER,
     header: attr!(&TTL_ATTR, range: 8..9),
     header: &PROTO_ATTR_HEADER,
     header: attr!(&CHECKSUM_ATTR, range: 10..12),
-    header: attr!(&SRC_ATTR, range: 12..16),
-    header: attr!(&DST_ATTR, range: 16..20)
+    header: &SRC_ATTR_HEADER,
+    header: &DST_ATTR_HEADER
 );
 
+def_attr!(HLEN_ATTR_HEADER, &HLEN_ATTR, bit_range: 0 4..8);
+def_attr!(LENGTH_ATTR_HEADER, &LENGTH_ATTR, range: 2..4);
+def_attr!(ID_ATTR_HEADER, &ID_ATTR, range: 4..6);
+def_attr!(FLAGS_MF_ATTR_HEADER, &FLAGS_MF_ATTR, bit_range: 6 3..4);
+def_attr!(OFFSET_ATTR_HEADER, &OFFSET_ATTR, bit_range: 6 4..16);
 def_attr!(PROTO_ATTR_HEADER,  &PROTO_ATTR, range: 9..10);
+def_attr!(SRC_ATTR_HEADER, &SRC_ATTR, range: 12..16);
+def_attr!(DST_ATTR_HEADER, &DST_ATTR, range: 16..20);
 
 def_attr_class!(VERSION_ATTR, "ipv4.version",
     cast: cast::UInt8().map(|v| v >> 4)
@@ -111,9 +250,15 @@ def_attr_class!(OFFSET_ATTR, "ipv4.fragmentOffset",
 
 def_attr_class!(TTL_ATTR, "ipv4.ttl", cast: cast::UInt8());
 
+def_attr_class!(REASSEMBLED_ATTR, "ipv4.reassembled",
+    typ: "@novalue",
+    value: true
+);
+
 def_attr_class!(PROTO_ATTR, "ipv4.protocol",
     cast: cast::UInt8(),
-    typ: "@enum"
+    typ: "@enum",
+    labels: vec![(0x01, "ICMP"), (0x02, "IGMP"), (0x06, "TCP"), (0x11, "UDP"), (0x2f, "GRE"), (0x84, "SCTP")]
 );
 
 def_attr_class!(CHECKSUM_ATTR, "ipv4.checksum", cast: cast::UInt16BE());
@@ -146,8 +291,16 @@ fn get_proto(val: u64) -> Option<(Token, &'static AttrClass)> {
             token!("@data:udp"),
             attr_class_lazy!("ipv4.protocol.udp", typ: "@novalue", value: true),
         )),
+        0x2f => Some((
+            token!("@data:gre"),
+            attr_class_lazy!("ipv4.protocol.gre", typ: "@novalue", value: true),
+        )),
+        0x84 => Some((
+            token!("@data:sctp"),
+            attr_class_lazy!("ipv4.protocol.sctp", typ: "@novalue", value: true),
+        )),
         _ => None,
     }
 }
 
-genet_decoders!(IPv4Decoder {});
+genet_decoders!(IPv4Decoder {}, IPv4FragmentDecoder {});
@@ -1,6 +1,7 @@
 extern crate genet_sdk;
 
-use genet_sdk::{cast, decoder::*, prelude::*};
+use genet_sdk::{cast, decoder::*, fragment::*, prelude::*};
+use std::ops::Range;
 
 struct IPv6Worker {}
 
@@ -31,7 +32,6 @@ impl Worker for IPv6Worker {
                 // TODO:
                 // case 0 | 60 # Hop-by-Hop Options, Destination Options
                 // case 43  # Routing
-                // case 44  # Fragment
                 // case 51  # Authentication Header
                 // case 50  # Encapsulating Security Payload
                 // case 135 # Mobility
@@ -41,14 +41,24 @@ impl Worker for IPv6Worker {
             }
         }
 
-        let range = NHEADER_ATTR_HEADER.range();
-        let proto_attr = attr!(&PROTOCOL_ATTR, range: range.clone());
-        let proto = proto_attr.try_get(&layer)?.try_into()?;
-        layer.add_attr(proto_attr);
-        if let Some((typ, attr)) = get_proto(proto) {
-            layer.add_attr(attr!(attr, range: range.clone()));
-            let payload = layer.data().try_get(40..)?;
-            layer.add_payload(Payload::new(payload, typ));
+        if nheader == 44 {
+            let more: u64 = FRAG_MORE_ATTR_HEADER.try_get(&layer)?.try_into()?;
+            let offset: u64 = FRAG_OFFSET_ATTR_HEADER.try_get(&layer)?.try_into()?;
+            layer.add_attr(attr!(&FRAGMENT_ATTR, range: 40..48));
+            layer.add_attr(attr!(&FRAG_NHEADER_ATTR, range: 40..41));
+            layer.add_attr(&FRAG_OFFSET_ATTR_HEADER);
+            layer.add_attr(&FRAG_MORE_ATTR_HEADER);
+            layer.add_attr(&FRAG_ID_ATTR_HEADER);
+            if more != 0 || offset > 0 {
+                let len: u64 = LENGTH_ATTR_HEADER.try_get(&layer)?.try_into()?;
+                layer.add_attr(attr!(&PROTOCOL_ATTR, range: 40..41));
+                let payload = layer.data().try_get(48..(40 + len as usize))?;
+                layer.add_payload(Payload::new(payload, "@fragment:ipv6"));
+            } else {
+                add_proto_payload(&mut layer, 40..41, 48)?;
+            }
+        } else {
+            add_proto_payload(&mut layer, NHEADER_ATTR_HEADER.range(), 40)?;
         }
 
         parent.add_child(layer);
@@ -56,6 +66,18 @@ impl Worker for IPv6Worker {
     }
 }
 
+fn add_proto_payload(layer: &mut Layer, range: Range<usize>, offset: usize) -> Result<()> {
+    let proto_attr = attr!(&PROTOCOL_ATTR, range: range.clone());
+    let proto = proto_attr.try_get(layer)?.try_into()?;
+    layer.add_attr(proto_attr);
+    if let Some((typ, attr)) = get_proto(proto) {
+        layer.add_attr(attr!(attr, range: range.clone()));
+        let payload = layer.data().try_get(offset..)?;
+        layer.add_payload(Payload::new(payload, typ));
+    }
+    Ok(())
+}
+
 #[derive(Clone)]
 struct IPv6Decoder {}
 
@@ -66,7 +88,107 @@ impl Decoder for IPv6Decoder {
 
     fn metadata(&self) -> Metadata {
         Metadata {
+            id: "app.genet.decoder.ipv6".into(),
+            name: "IPv6".into(),
+            description: "Internet Protocol version 6".into(),
             exec_type: ExecType::ParallelSync,
+            references: vec!["https://tools.ietf.org/html/rfc8200".into()],
+            ..Metadata::default()
+        }
+    }
+}
+
+struct IPv6FragmentWorker {
+    reassembler: FragmentReassembler,
+}
+
+impl Worker for IPv6FragmentWorker {
+    fn decode(
+        &mut self,
+        ctx: &mut Context,
+        stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        if parent.id() != token!("ipv6") {
+            return Ok(Status::Skip);
+        }
+
+        let data;
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@fragment:ipv6"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let key = FragmentKey {
+            src: attr!(&SRC_ATTR, range: 8..24).try_get(parent)?.try_into()?,
+            dst: attr!(&DST_ATTR, range: 24..40).try_get(parent)?.try_into()?,
+            id: FRAG_ID_ATTR_HEADER.try_get(parent)?.try_into()?,
+            protocol: attr!(&FRAG_NHEADER_ATTR, range: 40..41)
+                .try_get(parent)?
+                .try_into()?,
+        };
+        let more: u64 = FRAG_MORE_ATTR_HEADER.try_get(parent)?.try_into()?;
+        let offset: u64 = FRAG_OFFSET_ATTR_HEADER.try_get(parent)?.try_into()?;
+        let timestamp: f64 = stack
+            .attr(token!("link.timestamp"))
+            .and_then(|attr| attr.try_get(parent).ok())
+            .and_then(|value| value.try_into().ok())
+            .unwrap_or(0.0);
+        let fragment = Fragment {
+            frame: ctx.frame_index(),
+            timestamp,
+            offset: offset as usize * 8,
+            more: more != 0,
+            data,
+        };
+
+        if let Some(datagram) = self.reassembler.push(key, fragment) {
+            for frame in datagram.frames {
+                if frame != fragment.frame {
+                    parent.add_link(
+                        Link::new("ipv6.fragment", frame).with_reverse("ipv6.reassembledIn"),
+                    );
+                }
+            }
+
+            let mut data = parent.data().try_get(0..40)?.to_vec();
+            let len = datagram.data.len();
+            data[4] = (len >> 8) as u8;
+            data[5] = len as u8;
+            data[6] = key.protocol;
+            data.extend_from_slice(&datagram.data);
+
+            let mut layer = Layer::new(&IPV6_CLASS, ByteSlice::from(data));
+            layer.add_attr(attr!(&REASSEMBLED_ATTR));
+            add_proto_payload(&mut layer, NHEADER_ATTR_HEADER.range(), 40)?;
+            parent.add_child(layer);
+        }
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct IPv6FragmentDecoder {}
+
+impl Decoder for IPv6FragmentDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(IPv6FragmentWorker {
+            reassembler: FragmentReassembler::new(),
+        })
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.decoder.ipv6-fragment".into(),
+            name: "IPv6 Fragment".into(),
+            description: "IPv6 fragment reassembly".into(),
+            exec_type: ExecType::SerialSync,
+            references: vec!["https://tools.ietf.org/html/rfc8200".into()],
             ..Metadata::default()
         }
     }
@@ -78,14 +200,18 @@ def_layer_class!(IPV6_CLASS, "ipv6",
     header: attr!(&VERSION_ATTR, bit_range: 0 0..4),
     header: attr!(&TRAFFIC_ATTR, bit_range: 0 4..12),
     header: attr!(&FLOW_ATTR, bit_range: 1 4..24),
-    header: attr!(&LENGTH_ATTR, range: 4..6),
+    header: &LENGTH_ATTR_HEADER,
     header: &NHEADER_ATTR_HEADER,
     header: attr!(&HLIMIT_ATTR, range: 7..8),
     header: attr!(&SRC_ATTR, range: 8..24),
     header: attr!(&DST_ATTR, range: 24..40)
 );
 
+def_attr!(LENGTH_ATTR_HEADER,  &LENGTH_ATTR, range: 4..6);
 def_attr!(NHEADER_ATTR_HEADER,  &NHEADER_ATTR, range: 6..7);
+def_attr!(FRAG_OFFSET_ATTR_HEADER,  &FRAG_OFFSET_ATTR, range: 42..44);
+def_attr!(FRAG_MORE_ATTR_HEADER,  &FRAG_MORE_ATTR, range: 43..44);
+def_attr!(FRAG_ID_ATTR_HEADER,  &FRAG_ID_ATTR, range: 44..48);
 
 def_attr_class!(VERSION_ATTR, "ipv6.version",
     cast: cast::UInt8().map(|v| v >> 4)
@@ -101,25 +227,48 @@ def_attr_class!(FLOW_ATTR, "ipv6.flowLabel",
             .map(|v| (((v[2] as u32) & 0xf) << 16) | ((v[1] as u32) << 8) | v[2] as u32)
 );
 
-def_attr_class!(LENGTH_ATTR, "ipv6.payloadLength", cast: cast::UInt8());
+def_attr_class!(LENGTH_ATTR, "ipv6.payloadLength", cast: cast::UInt16BE());
 
 def_attr_class!(NHEADER_ATTR, "ipv6.nextHeader", cast: cast::UInt8());
 
 def_attr_class!(HLIMIT_ATTR, "ipv6.hopLimit", cast: cast::UInt8());
 
+def_attr_class!(FRAGMENT_ATTR, "ipv6.fragmentHeader",
+    typ: "@novalue",
+    value: true
+);
+
+def_attr_class!(FRAG_NHEADER_ATTR, "ipv6.fragmentHeader.nextHeader", cast: cast::UInt8());
+
+def_attr_class!(FRAG_OFFSET_ATTR, "ipv6.fragmentHeader.offset",
+    cast: cast::UInt16BE().map(|v| v >> 3)
+);
+
+def_attr_class!(FRAG_MORE_ATTR, "ipv6.fragmentHeader.moreFragments",
+    cast: cast::UInt8().map(|v| v & 0b00000001 != 0)
+);
+
+def_attr_class!(FRAG_ID_ATTR, "ipv6.fragmentHeader.id", cast: cast::UInt32BE());
+
+def_attr_class!(REASSEMBLED_ATTR, "ipv6.reassembled",
+    typ: "@novalue",
+    value: true
+);
+
 def_attr_class!(SRC_ATTR, "ipv6.src",
     typ: "@ipv6:addr",
-    cast: cast::ByteSlice()
+    cast: cast::Ipv6Addr()
 );
 
 def_attr_class!(DST_ATTR, "ipv6.dst",
     typ: "@ipv6:addr",
-    cast: cast::ByteSlice()
+    cast: cast::Ipv6Addr()
 );
 
 def_attr_class!(PROTOCOL_ATTR, "ipv6.protocol",
     typ: "@enum",
-    cast: cast::UInt8()
+    cast: cast::UInt8(),
+    labels: vec![(0x02, "IGMP"), (0x06, "TCP"), (0x11, "UDP"), (0x2f, "GRE"), (0x3a, "ICMPv6"), (0x84, "SCTP")]
 );
 
 fn get_proto(val: u64) -> Option<(Token, &'static AttrClass)> {
@@ -136,12 +285,20 @@ fn get_proto(val: u64) -> Option<(Token, &'static AttrClass)> {
             token!("@data:udp"),
             attr_class_lazy!("ipv6.protocol.udp", typ: "@novalue", value: true),
         )),
+        0x2f => Some((
+            token!("@data:gre"),
+            attr_class_lazy!("ipv6.protocol.gre", typ: "@novalue", value: true),
+        )),
         0x3a => Some((
             token!("@data:icmp"),
             attr_class_lazy!("ipv6.protocol.icmp", typ: "@novalue", value: true),
         )),
+        0x84 => Some((
+            token!("@data:sctp"),
+            attr_class_lazy!("ipv6.protocol.sctp", typ: "@novalue", value: true),
+        )),
         _ => None,
     }
 }
 
-genet_decoders!(IPv6Decoder {});
+genet_decoders!(IPv6Decoder {}, IPv6FragmentDecoder {});
@@ -0,0 +1,299 @@
+extern crate genet_sdk;
+
+use genet_sdk::{
+    cast, context::state_key, decoder::*, endpoint::endpoints, prelude::*, tcp::PduBuffer,
+};
+
+const PORT: u16 = 502;
+const MBAP_LEN: usize = 7;
+const MAX_ADU_LEN: usize = 260;
+
+const READ_COILS: u8 = 1;
+const READ_DISCRETE_INPUTS: u8 = 2;
+const READ_HOLDING_REGISTERS: u8 = 3;
+const READ_INPUT_REGISTERS: u8 = 4;
+const WRITE_SINGLE_COIL: u8 = 5;
+const WRITE_SINGLE_REGISTER: u8 = 6;
+const WRITE_MULTIPLE_COILS: u8 = 15;
+const WRITE_MULTIPLE_REGISTERS: u8 = 16;
+const MASK_WRITE_REGISTER: u8 = 22;
+const READ_WRITE_MULTIPLE_REGISTERS: u8 = 23;
+
+const EXCEPTION: u8 = 0x80;
+
+struct ModbusWorker {}
+
+impl Worker for ModbusWorker {
+    fn decode(
+        &mut self,
+        ctx: &mut Context,
+        stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        if parent.id() != token!("tcp") {
+            return Ok(Status::Skip);
+        }
+
+        let data;
+
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@stream:tcp"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let ((src, src_port), (dst, dst_port)) = match endpoints(stack, parent) {
+            Some(endpoints) => endpoints,
+            None => return Ok(Status::Skip),
+        };
+        let request = dst_port == PORT;
+
+        let key = state_key(&(&src[..], src_port, &dst[..], dst_port));
+        let buffer = ctx.state_or_insert_with(key, PduBuffer::new);
+        for adu in buffer.push(data, adu_len) {
+            let mut layer = Layer::new(&MODBUS_CLASS, adu);
+            if decode_pdu(&mut layer, request).is_none() {
+                layer.error("malformed PDU");
+            }
+            parent.add_child(layer);
+        }
+        Ok(Status::Done)
+    }
+}
+
+fn adu_len(data: &[u8]) -> Option<usize> {
+    if data.len() < MBAP_LEN {
+        return None;
+    }
+    let protocol = (data[2] as usize) << 8 | data[3] as usize;
+    let len = 6 + ((data[4] as usize) << 8 | data[5] as usize);
+    if protocol != 0 || len <= MBAP_LEN || len > MAX_ADU_LEN {
+        // Lost synchronization with the ADU boundaries.
+        Some(0)
+    } else {
+        Some(len)
+    }
+}
+
+/// Decodes the PDU and returns None if it is malformed.
+fn decode_pdu(layer: &mut Layer, request: bool) -> Option<()> {
+    let data = layer.data();
+    let function = data[MBAP_LEN];
+    let pos = MBAP_LEN + 1;
+    let end = data.len();
+    if function & EXCEPTION != 0 {
+        data.get(pos)?;
+        layer.add_attr(attr!(&EXCEPTION_CODE_ATTR, range: pos..pos + 1));
+        return if pos + 1 == end { Some(()) } else { None };
+    }
+
+    let len = match (function, request) {
+        (READ_COILS, true)
+        | (READ_DISCRETE_INPUTS, true)
+        | (READ_HOLDING_REGISTERS, true)
+        | (READ_INPUT_REGISTERS, true) => {
+            add_address_quantity(layer, &START_ATTR, &QUANTITY_ATTR, pos)?
+        }
+        (READ_COILS, false) | (READ_DISCRETE_INPUTS, false) => add_coils(layer, pos)?,
+        (READ_HOLDING_REGISTERS, false)
+        | (READ_INPUT_REGISTERS, false)
+        | (READ_WRITE_MULTIPLE_REGISTERS, false) => add_registers(layer, pos)?,
+        (WRITE_SINGLE_COIL, _) | (WRITE_SINGLE_REGISTER, _) => {
+            add_address_quantity(layer, &ADDRESS_ATTR, &VALUE_ATTR, pos)?
+        }
+        (WRITE_MULTIPLE_COILS, true) => {
+            let pos = add_address_quantity(layer, &START_ATTR, &QUANTITY_ATTR, pos)?;
+            add_coils(layer, pos)?
+        }
+        (WRITE_MULTIPLE_REGISTERS, true) => {
+            let pos = add_address_quantity(layer, &START_ATTR, &QUANTITY_ATTR, pos)?;
+            add_registers(layer, pos)?
+        }
+        (WRITE_MULTIPLE_COILS, false) | (WRITE_MULTIPLE_REGISTERS, false) => {
+            add_address_quantity(layer, &START_ATTR, &QUANTITY_ATTR, pos)?
+        }
+        (MASK_WRITE_REGISTER, _) => {
+            data.get(pos + 5)?;
+            layer.add_attr(attr!(&ADDRESS_ATTR, range: pos..pos + 2));
+            layer.add_attr(attr!(&AND_MASK_ATTR, range: pos + 2..pos + 4));
+            layer.add_attr(attr!(&OR_MASK_ATTR, range: pos + 4..pos + 6));
+            pos + 6
+        }
+        (READ_WRITE_MULTIPLE_REGISTERS, true) => {
+            let pos = add_address_quantity(layer, &START_ATTR, &QUANTITY_ATTR, pos)?;
+            let pos = add_address_quantity(layer, &WRITE_START_ATTR, &WRITE_QUANTITY_ATTR, pos)?;
+            add_registers(layer, pos)?
+        }
+        _ => {
+            layer.add_attr(attr!(&DATA_ATTR, range: pos..end));
+            end
+        }
+    };
+
+    if len == end {
+        Some(())
+    } else {
+        None
+    }
+}
+
+fn add_address_quantity(
+    layer: &mut Layer,
+    address: &'static AttrClass,
+    quantity: &'static AttrClass,
+    pos: usize,
+) -> Option<usize> {
+    layer.data().get(pos + 3)?;
+    layer.add_attr(attr!(address, range: pos..pos + 2));
+    layer.add_attr(attr!(quantity, range: pos + 2..pos + 4));
+    Some(pos + 4)
+}
+
+/// Adds the byte count and the packed coil values.
+fn add_coils(layer: &mut Layer, pos: usize) -> Option<usize> {
+    let count = *layer.data().get(pos)? as usize;
+    let end = pos + 1 + count;
+    if end > layer.data().len() {
+        return None;
+    }
+    layer.add_attr(attr!(&BYTE_COUNT_ATTR, range: pos..pos + 1));
+    layer.add_attr(attr!(&COILS_ATTR, range: pos + 1..end));
+    Some(end)
+}
+
+/// Adds the byte count and the register values.
+fn add_registers(layer: &mut Layer, pos: usize) -> Option<usize> {
+    let count = *layer.data().get(pos)? as usize;
+    let end = pos + 1 + count;
+    if count % 2 != 0 || end > layer.data().len() {
+        return None;
+    }
+    layer.add_attr(attr!(&BYTE_COUNT_ATTR, range: pos..pos + 1));
+    for offset in (pos + 1..end).step_by(2) {
+        layer.add_attr(attr!(&REGISTER_ATTR, range: offset..offset + 2));
+    }
+    Some(end)
+}
+
+#[derive(Clone)]
+struct ModbusDecoder {}
+
+impl Decoder for ModbusDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(ModbusWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.decoder.modbus".into(),
+            name: "Modbus/TCP".into(),
+            description: "Modbus Application Protocol over TCP".into(),
+            exec_type: ExecType::SerialSync,
+            triggers: vec![
+                Trigger::new("tcp.src", PORT.into()),
+                Trigger::new("tcp.dst", PORT.into()),
+            ],
+            references: vec![
+                "https://modbus.org/docs/Modbus_Application_Protocol_V1_1b3.pdf".into(),
+                "https://modbus.org/docs/Modbus_Messaging_Implementation_Guide_V1_0b.pdf".into(),
+            ],
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(MODBUS_CLASS, "modbus",
+    header: attr!(&TRANSACTION_ID_ATTR, range: 0..2),
+    header: attr!(&PROTOCOL_ID_ATTR, range: 2..4),
+    header: attr!(&LENGTH_ATTR, range: 4..6),
+    header: attr!(&UNIT_ID_ATTR, range: 6..7),
+    header: attr!(&FUNCTION_ATTR, range: 7..8),
+    header: attr!(&EXCEPTION_ATTR, bit_range: 7 0..1)
+);
+
+def_attr_class!(TRANSACTION_ID_ATTR, "modbus.transactionId", cast: cast::UInt16BE());
+
+def_attr_class!(PROTOCOL_ID_ATTR, "modbus.protocolId", cast: cast::UInt16BE());
+
+def_attr_class!(LENGTH_ATTR, "modbus.length", cast: cast::UInt16BE());
+
+def_attr_class!(UNIT_ID_ATTR, "modbus.unitId", cast: cast::UInt8());
+
+def_attr_class!(FUNCTION_ATTR, "modbus.function",
+    typ: "@enum",
+    cast: cast::UInt8().map(|v| v & 0x7f),
+    labels: vec![
+        (1, "Read Coils"),
+        (2, "Read Discrete Inputs"),
+        (3, "Read Holding Registers"),
+        (4, "Read Input Registers"),
+        (5, "Write Single Coil"),
+        (6, "Write Single Register"),
+        (7, "Read Exception Status"),
+        (8, "Diagnostics"),
+        (11, "Get Comm Event Counter"),
+        (12, "Get Comm Event Log"),
+        (15, "Write Multiple Coils"),
+        (16, "Write Multiple Registers"),
+        (17, "Report Server ID"),
+        (20, "Read File Record"),
+        (21, "Write File Record"),
+        (22, "Mask Write Register"),
+        (23, "Read/Write Multiple Registers"),
+        (24, "Read FIFO Queue"),
+        (43, "Encapsulated Interface Transport"),
+    ]
+);
+
+def_attr_class!(EXCEPTION_ATTR, "modbus.exception",
+    cast: cast::UInt8().map(|v| v & 0b10000000 != 0)
+);
+
+def_attr_class!(EXCEPTION_CODE_ATTR, "modbus.exceptionCode",
+    typ: "@enum",
+    cast: cast::UInt8(),
+    labels: vec![
+        (1, "Illegal Function"),
+        (2, "Illegal Data Address"),
+        (3, "Illegal Data Value"),
+        (4, "Server Device Failure"),
+        (5, "Acknowledge"),
+        (6, "Server Device Busy"),
+        (8, "Memory Parity Error"),
+        (10, "Gateway Path Unavailable"),
+        (11, "Gateway Target Device Failed to Respond"),
+    ]
+);
+
+def_attr_class!(START_ATTR, "modbus.startAddress", cast: cast::UInt16BE());
+
+def_attr_class!(QUANTITY_ATTR, "modbus.quantity", cast: cast::UInt16BE());
+
+def_attr_class!(WRITE_START_ATTR, "modbus.writeStartAddress", cast: cast::UInt16BE());
+
+def_attr_class!(WRITE_QUANTITY_ATTR, "modbus.writeQuantity", cast: cast::UInt16BE());
+
+def_attr_class!(ADDRESS_ATTR, "modbus.address", cast: cast::UInt16BE());
+
+def_attr_class!(VALUE_ATTR, "modbus.value", cast: cast::UInt16BE());
+
+def_attr_class!(AND_MASK_ATTR, "modbus.andMask", cast: cast::UInt16BE());
+
+def_attr_class!(OR_MASK_ATTR, "modbus.orMask", cast: cast::UInt16BE());
+
+def_attr_class!(BYTE_COUNT_ATTR, "modbus.byteCount", cast: cast::UInt8());
+
+def_attr_class!(COILS_ATTR, "modbus.coils", cast: cast::ByteSlice());
+
+def_attr_class!(REGISTER_ATTR, "modbus.register",
+    repeated: ,
+    cast: cast::UInt16BE()
+);
+
+def_attr_class!(DATA_ATTR, "modbus.data", cast: cast::ByteSlice());
+
+genet_decoders!(ModbusDecoder {});
@@ -0,0 +1,128 @@
+extern crate genet_sdk;
+
+use genet_sdk::{cast, decoder::*, prelude::*};
+
+const LABEL_LEN: usize = 4;
+const CONTROL_WORD_LEN: usize = 4;
+
+struct MplsWorker {}
+
+impl Worker for MplsWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let data;
+
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:mpls"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let mut layer = Layer::new(&MPLS_CLASS, data);
+        let mut pos = 0;
+        loop {
+            if pos + LABEL_LEN > data.len() {
+                layer.error("truncated label stack");
+                parent.add_child(layer);
+                return Ok(Status::Done);
+            }
+            layer.add_attr(attr!(&LABEL_ATTR, range: pos..pos + LABEL_LEN));
+            layer.add_attr(attr!(&LABEL_VALUE_ATTR, bit_range: pos 0..20));
+            layer.add_attr(attr!(&LABEL_TC_ATTR, bit_range: pos + 2 4..7));
+            layer.add_attr(attr!(&LABEL_BOTTOM_ATTR, bit_range: pos + 2 7..8));
+            layer.add_attr(attr!(&LABEL_TTL_ATTR, range: pos + 3..pos + 4));
+            let bottom = data[pos + 2] & 0b0000_0001 != 0;
+            pos += LABEL_LEN;
+            if bottom {
+                break;
+            }
+        }
+
+        // MPLS has no field indicating the payload type, so the first nibble
+        // of the payload is examined instead.
+        let payload = data.try_get(pos..)?;
+        match payload.get(0).map(|b| b >> 4) {
+            Some(4) => layer.add_payload(Payload::new(payload, "@data:ipv4")),
+            Some(6) => layer.add_payload(Payload::new(payload, "@data:ipv6")),
+            Some(0) if payload.len() >= CONTROL_WORD_LEN => {
+                layer.add_attr(attr!(&CONTROL_WORD_ATTR, range: pos..pos + CONTROL_WORD_LEN));
+                let payload = data.try_get(pos + CONTROL_WORD_LEN..)?;
+                layer.add_payload(Payload::new(payload, "@data:eth"));
+            }
+            Some(_) => layer.add_payload(Payload::new(payload, "@data:eth")),
+            None => {}
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct MplsDecoder {}
+
+impl Decoder for MplsDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(MplsWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.decoder.mpls".into(),
+            name: "MPLS".into(),
+            description: "Multiprotocol Label Switching".into(),
+            exec_type: ExecType::ParallelSync,
+            references: vec![
+                "https://tools.ietf.org/html/rfc3032".into(),
+                "https://tools.ietf.org/html/rfc4448".into(),
+            ],
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(MPLS_CLASS, "mpls");
+
+def_attr_class!(LABEL_ATTR, "mpls.label",
+    typ: "@nested",
+    repeated: ,
+    value: true
+);
+
+def_attr_class!(LABEL_VALUE_ATTR, "mpls.label.value",
+    typ: "@enum",
+    cast: cast::ByteSlice()
+        .map(|v| ((v[0] as u32) << 12) | ((v[1] as u32) << 4) | (v[2] as u32) >> 4),
+    labels: vec![
+        (0, "IPv4 Explicit NULL"),
+        (1, "Router Alert"),
+        (2, "IPv6 Explicit NULL"),
+        (3, "Implicit NULL"),
+        (7, "Entropy Label Indicator"),
+        (13, "Generic Associated Channel"),
+        (14, "OAM Alert"),
+        (15, "Extension"),
+    ]
+);
+
+def_attr_class!(LABEL_TC_ATTR, "mpls.label.tc",
+    cast: cast::UInt8().map(|v| (v >> 1) & 0b111)
+);
+
+def_attr_class!(LABEL_BOTTOM_ATTR, "mpls.label.bottom",
+    cast: cast::UInt8().map(|v| v & 0b00000001 != 0)
+);
+
+def_attr_class!(LABEL_TTL_ATTR, "mpls.label.ttl", cast: cast::UInt8());
+
+def_attr_class!(CONTROL_WORD_ATTR, "mpls.controlWord", cast: cast::UInt32BE());
+
+genet_decoders!(MplsDecoder {});
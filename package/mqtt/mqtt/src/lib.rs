@@ -0,0 +1,525 @@
+extern crate genet_sdk;
+
+use genet_sdk::{
+    cast,
+    context::{flow_key, state_key},
+    decoder::*,
+    endpoint::endpoints,
+    prelude::*,
+    tcp::PduBuffer,
+};
+
+const PORT: u16 = 1883;
+const MAX_VARINT_LEN: usize = 4;
+
+const CONNECT: u8 = 1;
+const CONNACK: u8 = 2;
+const PUBLISH: u8 = 3;
+const PUBACK: u8 = 4;
+const PUBREC: u8 = 5;
+const PUBREL: u8 = 6;
+const PUBCOMP: u8 = 7;
+const SUBSCRIBE: u8 = 8;
+const SUBACK: u8 = 9;
+const UNSUBSCRIBE: u8 = 10;
+const UNSUBACK: u8 = 11;
+const DISCONNECT: u8 = 14;
+const AUTH: u8 = 15;
+
+const MQTT_5: u8 = 5;
+
+const CONNECT_USERNAME: u8 = 0b1000_0000;
+const CONNECT_PASSWORD: u8 = 0b0100_0000;
+const CONNECT_WILL: u8 = 0b0000_0100;
+
+/// The state shared by both directions of a TCP connection.
+struct Session {
+    version: u8,
+}
+
+struct MqttWorker {}
+
+impl Worker for MqttWorker {
+    fn decode(
+        &mut self,
+        ctx: &mut Context,
+        stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        if parent.id() != token!("tcp") {
+            return Ok(Status::Skip);
+        }
+
+        let data;
+
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@stream:tcp"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let ((src, src_port), (dst, dst_port)) = match endpoints(stack, parent) {
+            Some(endpoints) => endpoints,
+            None => return Ok(Status::Skip),
+        };
+
+        let packets = {
+            let key = state_key(&(&src[..], src_port, &dst[..], dst_port));
+            let buffer = ctx.state_or_insert_with(key, PduBuffer::new);
+            buffer.push(data, packet_len)
+        };
+
+        let key = flow_key((&src[..], src_port), (&dst[..], dst_port));
+        let session = ctx.state_or_insert_with(key, || Session { version: 4 });
+        for packet in packets {
+            let mut layer = Layer::new(&MQTT_CLASS, packet);
+            if decode_packet(&mut layer, session).is_none() {
+                layer.error("malformed packet");
+            }
+            parent.add_child(layer);
+        }
+        Ok(Status::Done)
+    }
+}
+
+/// Decodes a Variable Byte Integer and returns the value and its length.
+///
+/// Returns None if more data is needed, or Some((0, 0)) if the encoding is
+/// longer than 4 bytes.
+fn varint(data: &[u8]) -> Option<(usize, usize)> {
+    let mut value = 0;
+    for (i, &b) in data.iter().take(MAX_VARINT_LEN).enumerate() {
+        value |= ((b & 0x7f) as usize) << (7 * i);
+        if b & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    if data.len() >= MAX_VARINT_LEN {
+        Some((0, 0))
+    } else {
+        None
+    }
+}
+
+fn packet_len(data: &[u8]) -> Option<usize> {
+    if data.is_empty() {
+        return None;
+    }
+    if data[0] >> 4 == 0 {
+        // Lost synchronization with the packet boundaries.
+        return Some(0);
+    }
+    match varint(&data[1..]) {
+        Some((_, 0)) => Some(0),
+        Some((len, n)) => Some(1 + n + len),
+        None => None,
+    }
+}
+
+/// Decodes the control packet and returns None if it is malformed.
+fn decode_packet(layer: &mut Layer, session: &mut Session) -> Option<()> {
+    let data = layer.data();
+    let typ = data[0] >> 4;
+    let flags = data[0] & 0x0f;
+    let (len, n) = varint(&data[1..])?;
+    layer.add_attr(attr!(&LENGTH_ATTR, range: 1..1 + n, value: len as u64));
+
+    let mut pos = 1 + n;
+    let end = data.len();
+    match typ {
+        CONNECT => {
+            pos = add_string(layer, &PROTOCOL_NAME_ATTR, pos)?;
+            let version = *data.get(pos)?;
+            let connect_flags = *data.get(pos + 1)?;
+            data.get(pos + 2..pos + 4)?;
+            session.version = version;
+            layer.add_attr(attr!(&PROTOCOL_LEVEL_ATTR, range: pos..pos + 1));
+            layer.add_attr(attr!(&CONNECT_FLAGS_ATTR, range: pos + 1..pos + 2));
+            layer.add_attr(attr!(&CONNECT_USERNAME_ATTR, bit_range: pos + 1 0..1));
+            layer.add_attr(attr!(&CONNECT_PASSWORD_ATTR, bit_range: pos + 1 1..2));
+            layer.add_attr(attr!(&CONNECT_WILL_RETAIN_ATTR, bit_range: pos + 1 2..3));
+            layer.add_attr(attr!(&CONNECT_WILL_QOS_ATTR, bit_range: pos + 1 3..5));
+            layer.add_attr(attr!(&CONNECT_WILL_ATTR, bit_range: pos + 1 5..6));
+            layer.add_attr(attr!(&CONNECT_CLEAN_START_ATTR, bit_range: pos + 1 6..7));
+            layer.add_attr(attr!(&KEEP_ALIVE_ATTR, range: pos + 2..pos + 4));
+            pos = add_properties(layer, pos + 4, session)?;
+
+            pos = add_string(layer, &CLIENT_ID_ATTR, pos)?;
+            if connect_flags & CONNECT_WILL != 0 {
+                pos = add_properties(layer, pos, session)?;
+                pos = add_string(layer, &WILL_TOPIC_ATTR, pos)?;
+                pos = add_string(layer, &WILL_MESSAGE_ATTR, pos)?;
+            }
+            if connect_flags & CONNECT_USERNAME != 0 {
+                pos = add_string(layer, &USERNAME_ATTR, pos)?;
+            }
+            if connect_flags & CONNECT_PASSWORD != 0 {
+                pos = add_string(layer, &PASSWORD_ATTR, pos)?;
+            }
+        }
+        CONNACK => {
+            data.get(pos + 1)?;
+            layer.add_attr(attr!(&SESSION_PRESENT_ATTR, bit_range: pos 7..8));
+            layer.add_attr(attr!(&REASON_CODE_ATTR, range: pos + 1..pos + 2));
+            pos = add_properties(layer, pos + 2, session)?;
+        }
+        PUBLISH => {
+            let qos = (flags >> 1) & 0b11;
+            layer.add_attr(attr!(&DUP_ATTR, bit_range: 0 4..5));
+            layer.add_attr(attr!(&QOS_ATTR, bit_range: 0 5..7));
+            layer.add_attr(attr!(&RETAIN_ATTR, bit_range: 0 7..8));
+            if qos == 0b11 {
+                layer.annotate(
+                    Annotation::new(Severity::Error, "invalid QoS").with_attr("mqtt.qos"),
+                );
+            }
+            pos = add_string(layer, &TOPIC_ATTR, pos)?;
+            if qos > 0 {
+                pos = add_packet_id(layer, pos)?;
+            }
+            pos = add_properties(layer, pos, session)?;
+            let payload = data.try_get(pos..end).ok()?;
+            layer.add_attr(attr!(&PAYLOAD_ATTR, range: pos..end));
+            layer.add_payload(Payload::new(payload, "@data:mqtt"));
+            pos = end;
+        }
+        PUBACK | PUBREC | PUBREL | PUBCOMP | UNSUBACK => {
+            pos = add_packet_id(layer, pos)?;
+            if typ == UNSUBACK && session.version >= MQTT_5 {
+                pos = add_properties(layer, pos, session)?;
+                while pos < end {
+                    layer.add_attr(attr!(&SUBACK_CODE_ATTR, range: pos..pos + 1));
+                    pos += 1;
+                }
+            } else if pos < end && session.version >= MQTT_5 {
+                layer.add_attr(attr!(&REASON_CODE_ATTR, range: pos..pos + 1));
+                pos = add_properties(layer, pos + 1, session)?;
+            }
+        }
+        SUBSCRIBE | UNSUBSCRIBE => {
+            pos = add_packet_id(layer, pos)?;
+            pos = add_properties(layer, pos, session)?;
+            while pos < end {
+                let start = pos;
+                let topic = pos;
+                pos = string_end(&data, pos)?;
+                if typ == SUBSCRIBE {
+                    data.get(pos)?;
+                    pos += 1;
+                }
+                layer.add_attr(attr!(&SUBSCRIPTION_ATTR, range: start..pos));
+                layer.add_attr(
+                    attr!(&SUBSCRIPTION_TOPIC_ATTR, range: topic + 2..string_end(&data, topic)?),
+                );
+                if typ == SUBSCRIBE {
+                    layer.add_attr(attr!(&SUBSCRIPTION_QOS_ATTR, bit_range: pos - 1 6..8));
+                }
+            }
+        }
+        SUBACK => {
+            pos = add_packet_id(layer, pos)?;
+            pos = add_properties(layer, pos, session)?;
+            while pos < end {
+                layer.add_attr(attr!(&SUBACK_CODE_ATTR, range: pos..pos + 1));
+                pos += 1;
+            }
+        }
+        DISCONNECT | AUTH => {
+            if pos < end {
+                layer.add_attr(attr!(&REASON_CODE_ATTR, range: pos..pos + 1));
+                pos = add_properties(layer, pos + 1, session)?;
+            }
+        }
+        _ => {}
+    }
+
+    if pos != end {
+        None
+    } else {
+        Some(())
+    }
+}
+
+/// Returns the end of the length-prefixed string at `pos`.
+fn string_end(data: &[u8], pos: usize) -> Option<usize> {
+    let len = data.get(pos..pos + 2)?;
+    let end = pos + 2 + ((len[0] as usize) << 8 | len[1] as usize);
+    if end > data.len() {
+        None
+    } else {
+        Some(end)
+    }
+}
+
+fn add_string(layer: &mut Layer, class: &'static AttrClass, pos: usize) -> Option<usize> {
+    let end = string_end(&layer.data(), pos)?;
+    layer.add_attr(attr!(class, range: pos + 2..end));
+    Some(end)
+}
+
+fn add_packet_id(layer: &mut Layer, pos: usize) -> Option<usize> {
+    layer.data().get(pos..pos + 2)?;
+    layer.add_attr(attr!(&PACKET_ID_ATTR, range: pos..pos + 2));
+    Some(pos + 2)
+}
+
+/// Adds the properties of MQTT 5.0, which are absent in earlier versions.
+fn add_properties(layer: &mut Layer, pos: usize, session: &Session) -> Option<usize> {
+    if session.version < MQTT_5 {
+        return Some(pos);
+    }
+    let data = layer.data();
+    let (len, n) = match varint(data.get(pos..)?) {
+        Some((_, 0)) | None => return None,
+        Some(v) => v,
+    };
+    let end = pos + n + len;
+    if end > data.len() {
+        return None;
+    }
+    layer.add_attr(attr!(&PROPERTIES_ATTR, range: pos + n..end));
+    Some(end)
+}
+
+#[derive(Clone)]
+struct MqttDecoder {}
+
+impl Decoder for MqttDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(MqttWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.decoder.mqtt".into(),
+            name: "MQTT".into(),
+            description: "Message Queuing Telemetry Transport".into(),
+            exec_type: ExecType::SerialSync,
+            triggers: vec![
+                Trigger::new("tcp.src", PORT.into()),
+                Trigger::new("tcp.dst", PORT.into()),
+            ],
+            references: vec![
+                "http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/mqtt-v3.1.1.html".into(),
+                "https://docs.oasis-open.org/mqtt/mqtt/v5.0/mqtt-v5.0.html".into(),
+            ],
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(MQTT_CLASS, "mqtt",
+    header: attr!(&TYPE_ATTR, bit_range: 0 0..4),
+    header: attr!(&FLAGS_ATTR, bit_range: 0 4..8)
+);
+
+def_attr_class!(TYPE_ATTR, "mqtt.type",
+    typ: "@enum",
+    cast: cast::UInt8().map(|v| v >> 4),
+    labels: vec![
+        (1, "CONNECT"),
+        (2, "CONNACK"),
+        (3, "PUBLISH"),
+        (4, "PUBACK"),
+        (5, "PUBREC"),
+        (6, "PUBREL"),
+        (7, "PUBCOMP"),
+        (8, "SUBSCRIBE"),
+        (9, "SUBACK"),
+        (10, "UNSUBSCRIBE"),
+        (11, "UNSUBACK"),
+        (12, "PINGREQ"),
+        (13, "PINGRESP"),
+        (14, "DISCONNECT"),
+        (15, "AUTH"),
+    ]
+);
+
+def_attr_class!(FLAGS_ATTR, "mqtt.flags",
+    typ: "@flags",
+    cast: cast::UInt8().map(|v| v & 0x0f)
+);
+
+def_attr_class!(DUP_ATTR, "mqtt.flags.dup",
+    cast: cast::UInt8().map(|v| v & 0b00001000 != 0)
+);
+
+def_attr_class!(QOS_ATTR, "mqtt.qos",
+    typ: "@enum",
+    cast: cast::UInt8().map(|v| (v >> 1) & 0b11),
+    labels: vec![
+        (0, "At most once"),
+        (1, "At least once"),
+        (2, "Exactly once"),
+    ]
+);
+
+def_attr_class!(RETAIN_ATTR, "mqtt.flags.retain",
+    cast: cast::UInt8().map(|v| v & 0b00000001 != 0)
+);
+
+def_attr_class!(LENGTH_ATTR, "mqtt.length");
+
+def_attr_class!(PROTOCOL_NAME_ATTR, "mqtt.protocolName", cast: cast::Utf8());
+
+def_attr_class!(PROTOCOL_LEVEL_ATTR, "mqtt.protocolLevel",
+    typ: "@enum",
+    cast: cast::UInt8(),
+    labels: vec![(3, "3.1"), (4, "3.1.1"), (5, "5.0")]
+);
+
+def_attr_class!(CONNECT_FLAGS_ATTR, "mqtt.connectFlags",
+    typ: "@flags",
+    cast: cast::UInt8()
+);
+
+def_attr_class!(CONNECT_USERNAME_ATTR, "mqtt.connectFlags.username",
+    cast: cast::UInt8().map(|v| v & 0b10000000 != 0)
+);
+
+def_attr_class!(CONNECT_PASSWORD_ATTR, "mqtt.connectFlags.password",
+    cast: cast::UInt8().map(|v| v & 0b01000000 != 0)
+);
+
+def_attr_class!(CONNECT_WILL_RETAIN_ATTR, "mqtt.connectFlags.willRetain",
+    cast: cast::UInt8().map(|v| v & 0b00100000 != 0)
+);
+
+def_attr_class!(CONNECT_WILL_QOS_ATTR, "mqtt.connectFlags.willQos",
+    cast: cast::UInt8().map(|v| (v >> 3) & 0b11)
+);
+
+def_attr_class!(CONNECT_WILL_ATTR, "mqtt.connectFlags.will",
+    cast: cast::UInt8().map(|v| v & 0b00000100 != 0)
+);
+
+def_attr_class!(CONNECT_CLEAN_START_ATTR, "mqtt.connectFlags.cleanStart",
+    cast: cast::UInt8().map(|v| v & 0b00000010 != 0)
+);
+
+def_attr_class!(KEEP_ALIVE_ATTR, "mqtt.keepAlive", cast: cast::UInt16BE());
+
+def_attr_class!(PROPERTIES_ATTR, "mqtt.properties", cast: cast::ByteSlice());
+
+def_attr_class!(CLIENT_ID_ATTR, "mqtt.clientId", cast: cast::Utf8());
+
+def_attr_class!(WILL_TOPIC_ATTR, "mqtt.willTopic", cast: cast::Utf8());
+
+def_attr_class!(WILL_MESSAGE_ATTR, "mqtt.willMessage", cast: cast::ByteSlice());
+
+def_attr_class!(USERNAME_ATTR, "mqtt.username", cast: cast::Utf8());
+
+def_attr_class!(PASSWORD_ATTR, "mqtt.password", cast: cast::ByteSlice());
+
+def_attr_class!(SESSION_PRESENT_ATTR, "mqtt.sessionPresent",
+    cast: cast::UInt8().map(|v| v & 0b00000001 != 0)
+);
+
+def_attr_class!(REASON_CODE_ATTR, "mqtt.reasonCode",
+    typ: "@enum",
+    cast: cast::UInt8(),
+    labels: reason_code_labels()
+);
+
+def_attr_class!(TOPIC_ATTR, "mqtt.topic", cast: cast::Utf8());
+
+def_attr_class!(PACKET_ID_ATTR, "mqtt.packetId", cast: cast::UInt16BE());
+
+def_attr_class!(PAYLOAD_ATTR, "mqtt.payload", cast: cast::ByteSlice());
+
+def_attr_class!(SUBSCRIPTION_ATTR, "mqtt.subscription",
+    typ: "@nested",
+    repeated: ,
+    value: true
+);
+
+def_attr_class!(SUBSCRIPTION_TOPIC_ATTR, "mqtt.subscription.topic", cast: cast::Utf8());
+
+def_attr_class!(SUBSCRIPTION_QOS_ATTR, "mqtt.subscription.qos",
+    typ: "@enum",
+    cast: cast::UInt8().map(|v| v & 0b11),
+    labels: vec![
+        (0, "At most once"),
+        (1, "At least once"),
+        (2, "Exactly once"),
+    ]
+);
+
+def_attr_class!(SUBACK_CODE_ATTR, "mqtt.subscriptionReasonCode",
+    typ: "@enum",
+    repeated: ,
+    cast: cast::UInt8(),
+    labels: vec![
+        (0x00, "Granted QoS 0"),
+        (0x01, "Granted QoS 1"),
+        (0x02, "Granted QoS 2"),
+        (0x11, "No subscription existed"),
+        (0x80, "Unspecified error"),
+        (0x83, "Implementation specific error"),
+        (0x87, "Not authorized"),
+        (0x8F, "Topic Filter invalid"),
+        (0x91, "Packet Identifier in use"),
+        (0x97, "Quota exceeded"),
+        (0x9E, "Shared Subscriptions not supported"),
+        (0xA1, "Subscription Identifiers not supported"),
+        (0xA2, "Wildcard Subscriptions not supported"),
+    ]
+);
+
+/// Returns the labels of the return codes of MQTT 3.1.1 and the reason codes
+/// of MQTT 5.0, whose values do not overlap except for success.
+fn reason_code_labels() -> Vec<(u64, &'static str)> {
+    vec![
+        (0x00, "Success"),
+        (0x01, "Unacceptable protocol version"),
+        (0x02, "Identifier rejected"),
+        (0x03, "Server unavailable"),
+        (0x04, "Bad user name or password"),
+        (0x05, "Not authorized"),
+        (0x10, "No matching subscribers"),
+        (0x11, "No subscription existed"),
+        (0x18, "Continue authentication"),
+        (0x19, "Re-authenticate"),
+        (0x80, "Unspecified error"),
+        (0x81, "Malformed Packet"),
+        (0x82, "Protocol Error"),
+        (0x83, "Implementation specific error"),
+        (0x84, "Unsupported Protocol Version"),
+        (0x85, "Client Identifier not valid"),
+        (0x86, "Bad User Name or Password"),
+        (0x87, "Not authorized"),
+        (0x88, "Server unavailable"),
+        (0x89, "Server busy"),
+        (0x8A, "Banned"),
+        (0x8B, "Server shutting down"),
+        (0x8C, "Bad authentication method"),
+        (0x8D, "Keep Alive timeout"),
+        (0x8E, "Session taken over"),
+        (0x8F, "Topic Filter invalid"),
+        (0x90, "Topic Name invalid"),
+        (0x91, "Packet Identifier in use"),
+        (0x92, "Packet Identifier not found"),
+        (0x93, "Receive Maximum exceeded"),
+        (0x94, "Topic Alias invalid"),
+        (0x95, "Packet too large"),
+        (0x96, "Message rate too high"),
+        (0x97, "Quota exceeded"),
+        (0x98, "Administrative action"),
+        (0x99, "Payload format invalid"),
+        (0x9A, "Retain not supported"),
+        (0x9B, "QoS not supported"),
+        (0x9C, "Use another server"),
+        (0x9D, "Server moved"),
+        (0x9E, "Shared Subscriptions not supported"),
+        (0x9F, "Connection rate exceeded"),
+        (0xA0, "Maximum connect time"),
+        (0xA1, "Subscription Identifiers not supported"),
+        (0xA2, "Wildcard Subscriptions not supported"),
+    ]
+}
+
+genet_decoders!(MqttDecoder {});
@@ -0,0 +1,43 @@
+const m = require('mithril')
+const genet = require('@genet/api')
+const { remote: { dialog } } = require('electron')
+class OutputView {
+  view (vnode) {
+    const attrs = genet.config.get('@genet/ndjson.attributes') || []
+    return m('ul', [
+      m('li', [
+        m('input', {
+          type: 'text',
+          name: 'attrs',
+          placeholder: 'Attributes (e.g. ipv4.src, tcp)',
+          value: attrs.join(', '),
+        })
+      ]),
+      m('li', [
+        m('input', {
+          type: 'button',
+          value: 'Export',
+          onclick: () => {
+            const file = dialog.showSaveDialog({
+              properties: ['openFile'],
+              filters: [{
+                name: 'NDJSON File',
+                extensions: ['ndjson', 'jsonl'],
+              }],
+            })
+            if (typeof file !== 'undefined') {
+              const whitelist = vnode.dom.querySelector('[name=attrs]').value
+                .split(',')
+                .map((id) => id.trim())
+                .filter((id) => id.length > 0)
+              vnode.attrs.callback('app.genet.writer.ndjson',
+                { file, attrs: whitelist })
+            }
+          },
+        })
+      ])
+    ])
+  }
+}
+
+module.exports = OutputView
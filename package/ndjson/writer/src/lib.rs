@@ -0,0 +1,139 @@
+extern crate genet_sdk;
+extern crate serde;
+
+#[macro_use]
+extern crate serde_json;
+
+#[macro_use]
+extern crate serde_derive;
+
+use genet_sdk::{prelude::*, variant::Variant, writer::*};
+use serde_json::{Map as JsonMap, Value as Json};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+#[derive(Deserialize)]
+struct Arg {
+    file: String,
+
+    /// Attribute IDs or namespaces to export, or empty for all attributes.
+    #[serde(default)]
+    attrs: Vec<String>,
+}
+
+#[derive(Clone)]
+struct NdjsonWriter {}
+
+impl Writer for NdjsonWriter {
+    fn new_worker(&self, _ctx: &Context, arg: &str) -> Result<Box<Worker>> {
+        let arg: Arg = serde_json::from_str(arg)?;
+        let file = File::create(&arg.file)?;
+        Ok(Box::new(NdjsonWorker {
+            writer: BufWriter::new(file),
+            attrs: arg.attrs,
+        }))
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.writer.ndjson".into(),
+            filters: vec![FileType::new("NDJSON File", &["ndjson", "jsonl"])],
+            ..Metadata::default()
+        }
+    }
+}
+
+struct NdjsonWorker {
+    writer: BufWriter<File>,
+    attrs: Vec<String>,
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn variant(value: Variant) -> Json {
+    match value {
+        Variant::Nil => Json::Null,
+        Variant::Bool(b) => json!(b),
+        Variant::Int64(v) => json!(v),
+        Variant::UInt64(v) => json!(v),
+        Variant::Float64(v) => json!(v),
+        Variant::String(s) => json!(s),
+        Variant::BigInt(b) => json!(format!("0x{}", hex(&b))),
+        Variant::Buffer(b) => json!(hex(&b)),
+        Variant::Slice(s) => json!(hex(&s)),
+        Variant::Ipv6Addr(addr) => json!(addr.to_string()),
+        Variant::MacAddr(addr) => json!(addr
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(":")),
+        Variant::Timestamp(ns) => json!(ns),
+    }
+}
+
+impl NdjsonWorker {
+    fn exported(&self, id: &str) -> bool {
+        self.attrs.is_empty()
+            || self.attrs.iter().any(|prefix| {
+                id.starts_with(prefix.as_str())
+                    && (id.len() == prefix.len() || id[prefix.len()..].starts_with('.'))
+            })
+    }
+
+    fn layer(&self, stack: &LayerStack, layer: &Layer) -> Json {
+        let mut attrs = JsonMap::new();
+        for attr in layer.headers().iter().chain(layer.attrs().iter()) {
+            let id = attr.id().to_string();
+            if !self.exported(&id) {
+                continue;
+            }
+            let value = attr.try_get(layer).map(variant).unwrap_or(Json::Null);
+
+            // Repeated attributes are collected into an array.
+            if let Some(prev) = attrs.get_mut(&id) {
+                if let Json::Array(values) = prev {
+                    values.push(value);
+                    continue;
+                }
+                let first = prev.take();
+                *prev = Json::Array(vec![first, value]);
+                continue;
+            }
+            attrs.insert(id, value);
+        }
+        let children = stack
+            .children(layer)
+            .map(|child| self.layer(stack, child))
+            .collect::<Vec<_>>();
+        json!({
+            "id": layer.id().to_string(),
+            "attrs": attrs,
+            "children": children,
+        })
+    }
+}
+
+impl Worker for NdjsonWorker {
+    fn write(&mut self, index: u32, stack: &LayerStack) -> Result<()> {
+        if let Some(layer) = stack.bottom() {
+            let frame = json!({
+                "index": index,
+                "layer": self.layer(stack, layer),
+            });
+            serde_json::to_writer(&mut self.writer, &frame)?;
+            self.writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+genet_writers!(NdjsonWriter {});
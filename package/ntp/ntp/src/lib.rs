@@ -76,7 +76,11 @@ impl Decoder for NtpDecoder {
 
     fn metadata(&self) -> Metadata {
         Metadata {
+            id: "app.genet.decoder.ntp".into(),
+            name: "NTP".into(),
+            description: "Network Time Protocol".into(),
             exec_type: ExecType::ParallelSync,
+            references: vec!["https://tools.ietf.org/html/rfc5905".into()],
             ..Metadata::default()
         }
     }
@@ -1,5 +1,5 @@
 module.exports = (sess, arg) => {
-  if (arg.file.endsWith('.pcap')) {
+  if (arg.file === '-' || /\.pcap(\.gz|\.zst)?$/.test(arg.file)) {
     sess.createReader('app.genet.reader.pcap-file', arg)
     return true
   }
@@ -1,5 +1,6 @@
 extern crate byteorder;
 extern crate genet_sdk;
+extern crate memmap;
 extern crate serde;
 extern crate serde_json;
 
@@ -7,12 +8,19 @@ extern crate serde_json;
 extern crate serde_derive;
 
 use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
-use genet_sdk::{prelude::*, reader::*};
+use genet_sdk::{compression::Compression, prelude::*, reader::*};
+use memmap::Mmap;
 use std::{
     fs::File,
     io::{self, BufReader, Error, ErrorKind, Read},
 };
 
+const HEADER_LEN: usize = 24;
+const RECORD_HEADER_LEN: usize = 16;
+
+/// File name to read the standard input.
+const STDIN: &str = "-";
+
 #[derive(Deserialize)]
 struct Arg {
     file: String,
@@ -24,8 +32,16 @@ struct PcapFileReader {}
 impl Reader for PcapFileReader {
     fn new_worker(&self, _ctx: &Context, arg: &str) -> Result<Box<Worker>> {
         let arg: Arg = serde_json::from_str(arg)?;
-        let file = File::open(&arg.file)?;
-        let mut reader = BufReader::new(file);
+        let (mut reader, file) = if arg.file == STDIN {
+            let mut stdin = BufReader::new(io::stdin());
+            let compression = Compression::detect_buffered(&mut stdin)?;
+            (BufReader::new(compression.decompress(stdin)?), None)
+        } else {
+            let mut file = File::open(&arg.file)?;
+            let compression = Compression::detect(&mut file)?;
+            let reader = BufReader::new(compression.decompress(file.try_clone()?)?);
+            (reader, Some((file, compression)))
+        };
 
         let magic_number = reader.read_u32::<BigEndian>()?;
 
@@ -62,10 +78,27 @@ impl Reader for PcapFileReader {
             header: attr!(&TYPE_CLASS, value: i64::from(network))
         ));
 
+        // The mapping is never unmapped because the frames refer to it
+        // during the program execution, as well as the heap-allocated packets.
+        let map = match file {
+            Some((ref file, Compression::None)) => unsafe { Mmap::map(file) }.ok(),
+            _ => None,
+        };
+        let source = match map {
+            Some(map) => {
+                let map = Fixed::new(map);
+                Source::Mapped {
+                    data: unsafe { ByteSlice::from_raw_parts(map.as_ptr(), map.len()) },
+                    offset: HEADER_LEN,
+                }
+            }
+            None => Source::Stream(reader),
+        };
+
         Ok(Box::new(PcapFileWorker {
             le,
             nsec,
-            reader,
+            source,
             link_class,
         }))
     }
@@ -73,34 +106,113 @@ impl Reader for PcapFileReader {
     fn metadata(&self) -> Metadata {
         Metadata {
             id: "app.genet.reader.pcap-file".into(),
-            filters: vec![FileType::new("Pcap File", &["pcap"])],
+            filters: vec![FileType::new("Pcap File", &["pcap", "gz", "zst"])],
             ..Metadata::default()
         }
     }
 }
 
+enum Source {
+    /// The memory-mapped file and the offset of the next record.
+    Mapped {
+        data: ByteSlice,
+        offset: usize,
+    },
+    Stream(BufReader<Box<Read + Send>>),
+}
+
+impl Source {
+    fn read_header(&mut self) -> io::Result<[u8; RECORD_HEADER_LEN]> {
+        let mut header = [0u8; RECORD_HEADER_LEN];
+        if let Source::Stream(reader) = self {
+            reader.read_exact(&mut header)?;
+            return Ok(header);
+        }
+        header.copy_from_slice(&self.read_exact(header.len())?);
+        Ok(header)
+    }
+
+    /// Reads the packet data, which may be truncated at the end of the input.
+    ///
+    /// Returns the data and whether the data is truncated.
+    fn read_payload(&mut self, len: usize) -> io::Result<(ByteSlice, bool)> {
+        match self {
+            Source::Mapped { data, offset } => {
+                let end = (*offset + len).min(data.len());
+                if *offset >= end && len > 0 {
+                    return Err(Error::new(
+                        ErrorKind::UnexpectedEof,
+                        "unexpected end of file",
+                    ));
+                }
+                let slice = data.try_get(*offset..end).unwrap_or_default();
+                *offset = end;
+                Ok((
+                    unsafe { ByteSlice::from_raw_parts(slice.as_ptr(), slice.len()) },
+                    slice.len() < len,
+                ))
+            }
+            Source::Stream(reader) => {
+                let mut data = Vec::with_capacity(len);
+                reader.take(len as u64).read_to_end(&mut data)?;
+                if data.is_empty() && len > 0 {
+                    return Err(Error::new(
+                        ErrorKind::UnexpectedEof,
+                        "unexpected end of file",
+                    ));
+                }
+                let truncated = data.len() < len;
+                Ok((ByteSlice::from(data), truncated))
+            }
+        }
+    }
+
+    fn read_exact(&mut self, len: usize) -> io::Result<ByteSlice> {
+        match self {
+            Source::Mapped { data, offset } => {
+                let slice = data
+                    .try_get(*offset..*offset + len)
+                    .map_err(|_| Error::new(ErrorKind::UnexpectedEof, "unexpected end of file"))?;
+                *offset += len;
+                // The frame offsets start from the beginning of the packet.
+                Ok(unsafe { ByteSlice::from_raw_parts(slice.as_ptr(), slice.len()) })
+            }
+            Source::Stream(reader) => {
+                let mut data = Vec::<u8>::with_capacity(len);
+                unsafe {
+                    data.set_len(len);
+                }
+                reader.read_exact(&mut data)?;
+                Ok(ByteSlice::from(data))
+            }
+        }
+    }
+}
+
 struct PcapFileWorker {
     le: bool,
     nsec: bool,
-    reader: BufReader<File>,
+    source: Source,
     link_class: Fixed<LayerClass>,
 }
 
 impl PcapFileWorker {
     fn read_one(&mut self) -> io::Result<Layer> {
+        let header = self.source.read_header()?;
+        let mut header = &header[..];
         let (ts_sec, mut ts_usec, inc_len, orig_len) = if self.le {
             (
-                self.reader.read_u32::<LittleEndian>()?,
-                self.reader.read_u32::<LittleEndian>()?,
-                self.reader.read_u32::<LittleEndian>()?,
-                self.reader.read_u32::<LittleEndian>()?,
+                header.read_u32::<LittleEndian>()?,
+                header.read_u32::<LittleEndian>()?,
+                header.read_u32::<LittleEndian>()?,
+                header.read_u32::<LittleEndian>()?,
             )
         } else {
             (
-                self.reader.read_u32::<BigEndian>()?,
-                self.reader.read_u32::<BigEndian>()?,
-                self.reader.read_u32::<BigEndian>()?,
-                self.reader.read_u32::<BigEndian>()?,
+                header.read_u32::<BigEndian>()?,
+                header.read_u32::<BigEndian>()?,
+                header.read_u32::<BigEndian>()?,
+                header.read_u32::<BigEndian>()?,
             )
         };
 
@@ -108,14 +220,11 @@ impl PcapFileWorker {
             ts_usec *= 1000;
         }
 
-        let mut data = Vec::<u8>::with_capacity(inc_len as usize);
-        unsafe {
-            data.set_len(inc_len as usize);
-        }
-        self.reader.read_exact(&mut data)?;
-
-        let payload = ByteSlice::from(data);
+        let (payload, truncated) = self.source.read_payload(inc_len as usize)?;
         let mut layer = Layer::new(self.link_class.clone(), payload);
+        if truncated {
+            layer.warn("truncated packet");
+        }
 
         layer.add_attr(attr!(&LENGTH_CLASS, value: u64::from(orig_len)));
         layer.add_attr(attr!(
@@ -132,9 +241,10 @@ impl PcapFileWorker {
 const BLOCK_SIZE: usize = 65535;
 
 impl Worker for PcapFileWorker {
-    fn read(&mut self) -> Result<Vec<Layer>> {
-        let mut layers = Vec::with_capacity(BLOCK_SIZE);
-        for _ in 0..BLOCK_SIZE {
+    fn read(&mut self, max: usize) -> Result<Vec<Layer>> {
+        let len = BLOCK_SIZE.min(max);
+        let mut layers = Vec::with_capacity(len);
+        for _ in 0..len {
             match self.read_one() {
                 Ok(layer) => layers.push(layer),
                 Err(err) => {
@@ -29,6 +29,10 @@ class PcapView {
     if (Number.isInteger(snaplen)) {
       args.push('-l', `${snaplen}`)
     }
+    const filter = genet.config.get('@genet/pcap.captureFilter')
+    if (typeof filter === 'string' && filter.trim().length > 0) {
+      args.push('-f', filter.trim())
+    }
     const stream = {
       cmd: cli,
       args,
@@ -25,6 +25,12 @@ fn main() {
                 .short("l")
                 .help("Sets the snapshot length")
                 .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("filter")
+                .short("f")
+                .help("Sets the BPF capture filter")
+                .takes_value(true),
         );
 
     let status = SubCommand::with_name("devices");
@@ -62,7 +68,11 @@ fn main() {
             .value_of("snaplen")
             .and_then(|v| v.parse().ok())
             .unwrap_or(2048);
-        let recv = match pcap.start(&matches.value_of("DEVICE").unwrap(), snaplen) {
+        let recv = match pcap.start(
+            &matches.value_of("DEVICE").unwrap(),
+            snaplen,
+            matches.value_of("filter"),
+        ) {
             Ok(r) => r,
             Err(e) => {
                 eprintln!("error: {:?}", e);
@@ -63,7 +63,7 @@ struct PcapWorker {
 }
 
 impl Worker for PcapWorker {
-    fn read(&mut self) -> Result<Vec<Layer>> {
+    fn read(&mut self, _max: usize) -> Result<Vec<Layer>> {
         let mut header = String::new();
         self.reader.read_line(&mut header)?;
         let header = header.trim();
@@ -91,6 +91,10 @@ impl Worker for PcapWorker {
             &TS_USEC_CLASS,
             value: u64::from(header.ts_usec)
         ));
+        layer.add_attr(attr!(
+            &DROPS_CLASS,
+            value: u64::from(header.drops)
+        ));
         Ok(vec![layer])
     }
 }
@@ -108,5 +112,6 @@ def_attr_class!(TS_CLASS, "link.timestamp",
 );
 def_attr_class!(TS_SEC_CLASS, "link.timestamp.sec");
 def_attr_class!(TS_USEC_CLASS, "link.timestamp.usec");
+def_attr_class!(DROPS_CLASS, "link.capture.drops");
 
 genet_readers!(PcapReader {});
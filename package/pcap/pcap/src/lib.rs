@@ -17,6 +17,7 @@ use std::{
 
 const PCAP_IF_LOOPBACK: u32 = 0x0000_0001;
 const PCAP_ERRBUF_SIZE: usize = 256;
+const PCAP_NETMASK_UNKNOWN: u32 = 0xffff_ffff;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Header {
@@ -24,6 +25,10 @@ pub struct Header {
     pub actlen: u32,
     pub ts_sec: u32,
     pub ts_usec: u32,
+
+    /// Number of packets dropped by the kernel since the capture started.
+    #[serde(default)]
+    pub drops: u32,
 }
 
 #[derive(Debug)]
@@ -32,6 +37,7 @@ pub enum Error {
     DLLNotFound,
     DLLFuncNotFound,
     OpenFailed(String),
+    FilterFailed(String),
 }
 
 pub type FrameReceiver = Receiver<(Header, Box<[u8]>)>;
@@ -70,10 +76,22 @@ impl Pcap {
         true
     }
 
-    pub fn start(&mut self, ifs: &str, snaplen: u32) -> Result<FrameReceiver, Error> {
-        use std::{ffi::CString, slice};
+    pub fn start(
+        &mut self,
+        ifs: &str,
+        snaplen: u32,
+        filter: Option<&str>,
+    ) -> Result<FrameReceiver, Error> {
+        use std::{ffi::CString, mem, slice};
         let (send, recv) = channel();
         let ifs = CString::new(ifs).unwrap();
+        let filter = match filter {
+            Some(filter) => Some(
+                CString::new(filter)
+                    .map_err(|_| Error::FilterFailed("invalid filter string".to_string()))?,
+            ),
+            None => None,
+        };
 
         #[derive(Clone)]
         struct PcapHolder {
@@ -103,6 +121,29 @@ impl Pcap {
                 return Err(Error::OpenFailed(msg));
             }
 
+            if let Some(filter) = filter {
+                let mut prog: ffi::BpfProgram = mem::zeroed();
+                let result = (self.syms.pcap_compile)(
+                    pcap,
+                    &mut prog,
+                    filter.as_ptr(),
+                    1,
+                    PCAP_NETMASK_UNKNOWN,
+                );
+                if result < 0 {
+                    let msg = ffi::getstr((self.syms.pcap_geterr)(pcap));
+                    (self.syms.pcap_close)(pcap);
+                    return Err(Error::FilterFailed(msg));
+                }
+                let result = (self.syms.pcap_setfilter)(pcap, &mut prog);
+                (self.syms.pcap_freecode)(&mut prog);
+                if result < 0 {
+                    let msg = ffi::getstr((self.syms.pcap_geterr)(pcap));
+                    (self.syms.pcap_close)(pcap);
+                    return Err(Error::FilterFailed(msg));
+                }
+            }
+
             self.handles.push(pcap);
 
             extern "C" fn handler(
@@ -114,11 +155,18 @@ impl Pcap {
                     let holder = &*(user as *const PcapHolder);
                     let h = &*h;
                     let data = slice::from_raw_parts(data, h.caplen as usize);
+                    let mut stat: ffi::PcapStat = mem::zeroed();
+                    let drops = if (holder.syms.pcap_stats)(holder.pcap, &mut stat) == 0 {
+                        stat.ps_drop
+                    } else {
+                        0
+                    };
                     let header = Header {
                         datalen: data.len() as u32,
                         actlen: h.len,
                         ts_sec: h.ts.tv_sec as u32,
                         ts_usec: h.ts.tv_usec as u32,
+                        drops,
                     };
                     if holder.sender.send((header, data.into())).is_err() {
                         (holder.syms.pcap_breakloop)(holder.pcap);
@@ -476,7 +524,7 @@ mod platform {
 mod ffi {
     extern crate libc;
     use std::{
-        os::raw::{c_char, c_int, c_uchar},
+        os::raw::{c_char, c_int, c_uchar, c_uint},
         sync::Arc,
     };
 
@@ -510,6 +558,17 @@ mod ffi {
         ) -> c_int,
         pub pcap_breakloop: unsafe extern "C" fn(pcap: *mut Pcap),
         pub pcap_close: unsafe extern "C" fn(pcap: *mut Pcap),
+        pub pcap_compile: unsafe extern "C" fn(
+            pcap: *mut Pcap,
+            fp: *mut BpfProgram,
+            s: *const c_char,
+            optimize: c_int,
+            netmask: c_uint,
+        ) -> c_int,
+        pub pcap_setfilter: unsafe extern "C" fn(pcap: *mut Pcap, fp: *mut BpfProgram) -> c_int,
+        pub pcap_freecode: unsafe extern "C" fn(fp: *mut BpfProgram),
+        pub pcap_stats: unsafe extern "C" fn(pcap: *mut Pcap, ps: *mut PcapStat) -> c_int,
+        pub pcap_geterr: unsafe extern "C" fn(pcap: *mut Pcap) -> *mut c_char,
     }
 
     impl Symbols {
@@ -524,6 +583,11 @@ mod ffi {
                 pcap_loop,
                 pcap_breakloop,
                 pcap_close,
+                pcap_compile,
+                pcap_setfilter,
+                pcap_freecode,
+                pcap_stats,
+                pcap_geterr,
             })
         }
 
@@ -541,6 +605,11 @@ mod ffi {
             let pcap_loop;
             let pcap_breakloop;
             let pcap_close;
+            let pcap_compile;
+            let pcap_setfilter;
+            let pcap_freecode;
+            let pcap_stats;
+            let pcap_geterr;
 
             {
                 let pcap_findalldevs_: libloading::Symbol<
@@ -575,6 +644,27 @@ mod ffi {
                 let pcap_close_: libloading::Symbol<
                     unsafe extern "C" fn(pcap: *mut Pcap),
                 >;
+                let pcap_compile_: libloading::Symbol<
+                    unsafe extern "C" fn(
+                        pcap: *mut Pcap,
+                        fp: *mut BpfProgram,
+                        s: *const c_char,
+                        optimize: c_int,
+                        netmask: c_uint,
+                    ) -> c_int,
+                >;
+                let pcap_setfilter_: libloading::Symbol<
+                    unsafe extern "C" fn(pcap: *mut Pcap, fp: *mut BpfProgram) -> c_int,
+                >;
+                let pcap_freecode_: libloading::Symbol<
+                    unsafe extern "C" fn(fp: *mut BpfProgram),
+                >;
+                let pcap_stats_: libloading::Symbol<
+                    unsafe extern "C" fn(pcap: *mut Pcap, ps: *mut PcapStat) -> c_int,
+                >;
+                let pcap_geterr_: libloading::Symbol<
+                    unsafe extern "C" fn(pcap: *mut Pcap) -> *mut c_char,
+                >;
 
                 unsafe {
                     pcap_findalldevs_ = lib
@@ -598,6 +688,21 @@ mod ffi {
                     pcap_breakloop_ = lib
                         .get(b"pcap_breakloop")
                         .map_err(|_| super::Error::DLLFuncNotFound)?;
+                    pcap_compile_ = lib
+                        .get(b"pcap_compile")
+                        .map_err(|_| super::Error::DLLFuncNotFound)?;
+                    pcap_setfilter_ = lib
+                        .get(b"pcap_setfilter")
+                        .map_err(|_| super::Error::DLLFuncNotFound)?;
+                    pcap_freecode_ = lib
+                        .get(b"pcap_freecode")
+                        .map_err(|_| super::Error::DLLFuncNotFound)?;
+                    pcap_stats_ = lib
+                        .get(b"pcap_stats")
+                        .map_err(|_| super::Error::DLLFuncNotFound)?;
+                    pcap_geterr_ = lib
+                        .get(b"pcap_geterr")
+                        .map_err(|_| super::Error::DLLFuncNotFound)?;
                 }
 
                 pcap_findalldevs = *pcap_findalldevs_.deref();
@@ -607,6 +712,11 @@ mod ffi {
                 pcap_loop = *pcap_loop_.deref();
                 pcap_breakloop = *pcap_breakloop_.deref();
                 pcap_close = *pcap_close_.deref();
+                pcap_compile = *pcap_compile_.deref();
+                pcap_setfilter = *pcap_setfilter_.deref();
+                pcap_freecode = *pcap_freecode_.deref();
+                pcap_stats = *pcap_stats_.deref();
+                pcap_geterr = *pcap_geterr_.deref();
             }
 
             Ok(Symbols {
@@ -618,6 +728,11 @@ mod ffi {
                 pcap_loop,
                 pcap_breakloop,
                 pcap_close,
+                pcap_compile,
+                pcap_setfilter,
+                pcap_freecode,
+                pcap_stats,
+                pcap_geterr,
             })
         }
     }
@@ -653,6 +768,21 @@ mod ffi {
         pub comment: *mut c_char,
     }
 
+    pub(crate) enum BpfInsn {}
+
+    #[repr(C)]
+    pub(crate) struct BpfProgram {
+        pub bf_len: c_uint,
+        pub bf_insns: *mut BpfInsn,
+    }
+
+    #[repr(C)]
+    pub(crate) struct PcapStat {
+        pub ps_recv: c_uint,
+        pub ps_drop: c_uint,
+        pub ps_ifdrop: c_uint,
+    }
+
     pub(crate) type PcapHandler = extern "C" fn(*mut c_uchar, *const PcapPkthdr, *const c_uchar);
 
     #[cfg(not(target_os = "windows"))]
@@ -676,5 +806,16 @@ mod ffi {
         ) -> c_int;
         fn pcap_breakloop(pcap: *mut Pcap);
         fn pcap_close(pcap: *mut Pcap);
+        fn pcap_compile(
+            pcap: *mut Pcap,
+            fp: *mut BpfProgram,
+            s: *const c_char,
+            optimize: c_int,
+            netmask: c_uint,
+        ) -> c_int;
+        fn pcap_setfilter(pcap: *mut Pcap, fp: *mut BpfProgram) -> c_int;
+        fn pcap_freecode(fp: *mut BpfProgram);
+        fn pcap_stats(pcap: *mut Pcap, ps: *mut PcapStat) -> c_int;
+        fn pcap_geterr(pcap: *mut Pcap) -> *mut c_char;
     }
 }
@@ -0,0 +1,28 @@
+const m = require('mithril')
+const { remote: { dialog } } = require('electron')
+class OutputView {
+  view (vnode) {
+    return m('ul', [
+      m('li', [
+        m('input', {
+          type: 'button',
+          value: 'Export',
+          onclick: () => {
+            const file = dialog.showSaveDialog({
+              properties: ['openFile'],
+              filters: [{
+                name: 'Pcapng File',
+                extensions: ['pcapng'],
+              }],
+            })
+            if (typeof file !== 'undefined') {
+              vnode.attrs.callback('app.genet.writer.pcapng-file', { file })
+            }
+          },
+        })
+      ])
+    ])
+  }
+}
+
+module.exports = OutputView
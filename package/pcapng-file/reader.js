@@ -0,0 +1,6 @@
+module.exports = (sess, arg) => {
+  if (/\.pcapng(\.gz|\.zst)?$/.test(arg.file)) {
+    sess.createReader('app.genet.reader.pcapng-file', arg)
+    return true
+  }
+}
@@ -0,0 +1,355 @@
+extern crate byteorder;
+extern crate genet_sdk;
+extern crate serde;
+extern crate serde_json;
+
+#[macro_use]
+extern crate serde_derive;
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use genet_sdk::{compression::Compression, prelude::*, reader::*};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader, Error, ErrorKind, Read},
+    net::IpAddr,
+};
+
+const SECTION_HEADER_BLOCK: u32 = 0x0a0d_0d0a;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x0000_0001;
+const SIMPLE_PACKET_BLOCK: u32 = 0x0000_0003;
+const NAME_RESOLUTION_BLOCK: u32 = 0x0000_0004;
+const ENHANCED_PACKET_BLOCK: u32 = 0x0000_0006;
+
+const OPT_END: u16 = 0;
+const OPT_IF_NAME: u16 = 2;
+const OPT_IF_DESCRIPTION: u16 = 3;
+const OPT_IF_TSRESOL: u16 = 9;
+
+const NRB_RECORD_IPV4: u16 = 1;
+const NRB_RECORD_IPV6: u16 = 2;
+
+/// The maximum length of a block, which bounds the allocation for a corrupt length.
+const MAX_BLOCK_LENGTH: usize = 16 * 1024 * 1024;
+
+/// File name to read the standard input.
+const STDIN: &str = "-";
+
+#[derive(Deserialize)]
+struct Arg {
+    file: String,
+}
+
+#[derive(Clone)]
+struct PcapngFileReader {}
+
+impl Reader for PcapngFileReader {
+    fn new_worker(&self, _ctx: &Context, arg: &str) -> Result<Box<Worker>> {
+        let arg: Arg = serde_json::from_str(arg)?;
+        let input = if arg.file == STDIN {
+            let mut stdin = BufReader::new(io::stdin());
+            let compression = Compression::detect_buffered(&mut stdin)?;
+            compression.decompress(stdin)?
+        } else {
+            let mut file = File::open(&arg.file)?;
+            let compression = Compression::detect(&mut file)?;
+            compression.decompress(file)?
+        };
+        let mut worker = PcapngFileWorker {
+            le: true,
+            reader: BufReader::new(input),
+            interfaces: Vec::new(),
+            names: HashMap::new(),
+        };
+        let block = worker.read_block()?;
+        if block.typ != SECTION_HEADER_BLOCK {
+            return Err(Error::new(ErrorKind::InvalidData, "wrong magic number").into());
+        }
+        Ok(Box::new(worker))
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.reader.pcapng-file".into(),
+            filters: vec![FileType::new("Pcapng File", &["pcapng", "gz", "zst"])],
+            ..Metadata::default()
+        }
+    }
+}
+
+struct Block {
+    typ: u32,
+    body: Vec<u8>,
+}
+
+/// Timestamp resolution of an interface.
+#[derive(Clone, Copy)]
+enum Resolution {
+    /// 10^-n seconds.
+    Decimal(u8),
+
+    /// 2^-n seconds.
+    Binary(u8),
+}
+
+impl Resolution {
+    fn from_option(value: u8) -> Resolution {
+        if value & 0x80 == 0 {
+            Resolution::Decimal(value)
+        } else {
+            Resolution::Binary(value & 0x7f)
+        }
+    }
+
+    fn to_nanos(self, ts: u64) -> u64 {
+        match self {
+            Resolution::Decimal(n) if n <= 9 => ts.saturating_mul(10u64.pow(9 - u32::from(n))),
+            Resolution::Decimal(n) => ts / 10u64.pow(u32::from(n.min(19)) - 9),
+            Resolution::Binary(n) => (ts as f64 * 1e9 / 2f64.powi(i32::from(n))) as u64,
+        }
+    }
+}
+
+struct Interface {
+    class: Fixed<LayerClass>,
+    resolution: Resolution,
+}
+
+struct PcapngFileWorker {
+    le: bool,
+    reader: BufReader<Box<Read + Send>>,
+    interfaces: Vec<Interface>,
+
+    /// Names from the name resolution blocks.
+    names: HashMap<IpAddr, Vec<String>>,
+}
+
+impl PcapngFileWorker {
+    fn u16(&self, data: &[u8]) -> u16 {
+        if self.le {
+            LittleEndian::read_u16(data)
+        } else {
+            BigEndian::read_u16(data)
+        }
+    }
+
+    fn u32(&self, data: &[u8]) -> u32 {
+        if self.le {
+            LittleEndian::read_u32(data)
+        } else {
+            BigEndian::read_u32(data)
+        }
+    }
+
+    fn read_block(&mut self) -> io::Result<Block> {
+        let mut header = [0u8; 8];
+        self.reader.read_exact(&mut header)?;
+
+        // The type of the section header block is a palindrome,
+        // so the byte order is determined by the following magic number.
+        let mut magic = [0u8; 4];
+        if LittleEndian::read_u32(&header[0..4]) == SECTION_HEADER_BLOCK {
+            self.reader.read_exact(&mut magic)?;
+            self.le = match magic {
+                [0x4d, 0x3c, 0x2b, 0x1a] => true,
+                [0x1a, 0x2b, 0x3c, 0x4d] => false,
+                _ => return Err(Error::new(ErrorKind::InvalidData, "wrong byte-order magic")),
+            };
+            self.interfaces.clear();
+        }
+
+        let typ = self.u32(&header[0..4]);
+        let len = self.u32(&header[4..8]) as usize;
+        let min = if typ == SECTION_HEADER_BLOCK { 16 } else { 12 };
+        if len < min || len > MAX_BLOCK_LENGTH || len % 4 != 0 {
+            return Err(Error::new(ErrorKind::InvalidData, "wrong block length"));
+        }
+
+        let mut body = Vec::new();
+        if typ == SECTION_HEADER_BLOCK {
+            body.extend_from_slice(&magic);
+        }
+        let rest = len - 12 - body.len();
+        body.resize(body.len() + rest, 0);
+        let offset = body.len() - rest;
+        self.reader.read_exact(&mut body[offset..])?;
+
+        let mut trailer = [0u8; 4];
+        self.reader.read_exact(&mut trailer)?;
+        if self.u32(&trailer) as usize != len {
+            return Err(Error::new(ErrorKind::InvalidData, "block length mismatch"));
+        }
+        Ok(Block { typ, body })
+    }
+
+    fn options<'a>(&self, data: &'a [u8]) -> Vec<(u16, &'a [u8])> {
+        let mut options = Vec::new();
+        let mut data = data;
+        while data.len() >= 4 {
+            let code = self.u16(&data[0..2]);
+            let len = self.u16(&data[2..4]) as usize;
+            if code == OPT_END || data.len() < 4 + len {
+                break;
+            }
+            options.push((code, &data[4..4 + len]));
+            data = &data[(4 + (len + 3) / 4 * 4).min(data.len())..];
+        }
+        options
+    }
+
+    fn add_interface(&mut self, body: &[u8]) -> io::Result<()> {
+        if body.len() < 8 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "truncated interface block",
+            ));
+        }
+        let link = self.u16(&body[0..2]);
+        let id = self.interfaces.len() as u32;
+        let mut resolution = Resolution::Decimal(6);
+        let mut class = LayerClass::builder(format!("[link-{}]", link))
+            .header(attr!(&TYPE_CLASS, value: i64::from(link)))
+            .header(attr!(&INTERFACE_CLASS, value: id));
+        for (code, value) in self.options(&body[8..]) {
+            match code {
+                OPT_IF_NAME => {
+                    let name = String::from_utf8_lossy(value).into_owned();
+                    class = class.header(attr!(&NAME_CLASS, value: name.into_boxed_str()));
+                }
+                OPT_IF_DESCRIPTION => {
+                    let desc = String::from_utf8_lossy(value).into_owned();
+                    class = class.header(attr!(&DESCRIPTION_CLASS, value: desc.into_boxed_str()));
+                }
+                OPT_IF_TSRESOL if !value.is_empty() => {
+                    resolution = Resolution::from_option(value[0]);
+                }
+                _ => {}
+            }
+        }
+        self.interfaces.push(Interface {
+            class: Fixed::new(class.build()),
+            resolution,
+        });
+        Ok(())
+    }
+
+    fn add_names(&mut self, body: &[u8]) {
+        let mut data = body;
+        while data.len() >= 4 {
+            let typ = self.u16(&data[0..2]);
+            let len = self.u16(&data[2..4]) as usize;
+            if typ == OPT_END || data.len() < 4 + len {
+                break;
+            }
+            let value = &data[4..4 + len];
+            let (addr, names) = match typ {
+                NRB_RECORD_IPV4 if len >= 4 => {
+                    let mut octets = [0u8; 4];
+                    octets.copy_from_slice(&value[..4]);
+                    (Some(IpAddr::from(octets)), &value[4..])
+                }
+                NRB_RECORD_IPV6 if len >= 16 => {
+                    let mut octets = [0u8; 16];
+                    octets.copy_from_slice(&value[..16]);
+                    (Some(IpAddr::from(octets)), &value[16..])
+                }
+                _ => (None, value),
+            };
+            if let Some(addr) = addr {
+                let names = names
+                    .split(|b| *b == 0)
+                    .filter(|name| !name.is_empty())
+                    .map(|name| String::from_utf8_lossy(name).into_owned());
+                self.names
+                    .entry(addr)
+                    .or_insert_with(Vec::new)
+                    .extend(names);
+            }
+            data = &data[(4 + (len + 3) / 4 * 4).min(data.len())..];
+        }
+    }
+
+    fn packet(&self, interface: u32, ts: u64, orig_len: u32, data: &[u8]) -> io::Result<Layer> {
+        let interface = self
+            .interfaces
+            .get(interface as usize)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "unknown interface"))?;
+
+        let payload = ByteSlice::from(data.to_vec());
+        let mut layer = Layer::new(interface.class.clone(), payload);
+
+        let nanos = interface.resolution.to_nanos(ts);
+        let ts_sec = nanos / 1_000_000_000;
+        let ts_nsec = nanos % 1_000_000_000;
+        layer.add_attr(attr!(&LENGTH_CLASS, value: u64::from(orig_len)));
+        layer.add_attr(attr!(
+            &TS_CLASS,
+            value: ts_sec as f64 + ts_nsec as f64 / 1_000_000_000f64
+        ));
+        layer.add_attr(attr!(&TS_SEC_CLASS, value: ts_sec));
+        layer.add_attr(attr!(&TS_USEC_CLASS, value: ts_nsec));
+        Ok(layer)
+    }
+
+    fn read_one(&mut self) -> io::Result<Option<Layer>> {
+        let block = self.read_block()?;
+        let body = &block.body;
+        match block.typ {
+            INTERFACE_DESCRIPTION_BLOCK => self.add_interface(body)?,
+            NAME_RESOLUTION_BLOCK => self.add_names(body),
+            ENHANCED_PACKET_BLOCK if body.len() >= 20 => {
+                let interface = self.u32(&body[0..4]);
+                let ts =
+                    (u64::from(self.u32(&body[4..8])) << 32) | u64::from(self.u32(&body[8..12]));
+                let inc_len = self.u32(&body[12..16]) as usize;
+                let orig_len = self.u32(&body[16..20]);
+                let data = body
+                    .get(20..20 + inc_len)
+                    .ok_or_else(|| Error::new(ErrorKind::InvalidData, "truncated packet"))?;
+                return self.packet(interface, ts, orig_len, data).map(Some);
+            }
+            SIMPLE_PACKET_BLOCK if body.len() >= 4 => {
+                let orig_len = self.u32(&body[0..4]);
+                let len = (orig_len as usize).min(body.len() - 4);
+                return self.packet(0, 0, orig_len, &body[4..4 + len]).map(Some);
+            }
+            _ => {}
+        }
+        Ok(None)
+    }
+}
+
+const BLOCK_SIZE: usize = 65535;
+
+impl Worker for PcapngFileWorker {
+    fn read(&mut self, max: usize) -> Result<Vec<Layer>> {
+        let len = BLOCK_SIZE.min(max);
+        let mut layers = Vec::with_capacity(len);
+        while layers.len() < len {
+            match self.read_one() {
+                Ok(Some(layer)) => layers.push(layer),
+                Ok(None) => {}
+                Err(err) => {
+                    if layers.is_empty() {
+                        return Err(err.into());
+                    }
+                    break;
+                }
+            }
+        }
+        Ok(layers)
+    }
+}
+
+def_attr_class!(TYPE_CLASS, "link.type");
+def_attr_class!(INTERFACE_CLASS, "link.interface");
+def_attr_class!(NAME_CLASS, "link.interface.name");
+def_attr_class!(DESCRIPTION_CLASS, "link.interface.description");
+def_attr_class!(LENGTH_CLASS, "link.length");
+def_attr_class!(TS_CLASS, "link.timestamp",
+    typ: "@datetime:unix"
+);
+def_attr_class!(TS_SEC_CLASS, "link.timestamp.sec");
+def_attr_class!(TS_USEC_CLASS, "link.timestamp.usec");
+
+genet_readers!(PcapngFileReader {});
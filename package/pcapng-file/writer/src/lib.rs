@@ -0,0 +1,236 @@
+extern crate byteorder;
+extern crate genet_sdk;
+extern crate serde;
+extern crate serde_json;
+
+#[macro_use]
+extern crate serde_derive;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use genet_sdk::{prelude::*, writer::*};
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+const SECTION_HEADER_BLOCK: u32 = 0x0a0d_0d0a;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x0000_0001;
+const ENHANCED_PACKET_BLOCK: u32 = 0x0000_0006;
+
+const OPT_END: u16 = 0;
+const OPT_COMMENT: u16 = 1;
+const OPT_IF_NAME: u16 = 2;
+const OPT_IF_DESCRIPTION: u16 = 3;
+const OPT_IF_TSRESOL: u16 = 9;
+
+/// Nanosecond timestamp resolution.
+const TSRESOL_NSEC: u8 = 9;
+
+#[derive(Deserialize)]
+struct Arg {
+    file: String,
+}
+
+#[derive(Clone)]
+struct PcapngFileWriter {}
+
+impl Writer for PcapngFileWriter {
+    fn new_worker(&self, _ctx: &Context, arg: &str) -> Result<Box<Worker>> {
+        let arg: Arg = serde_json::from_str(arg)?;
+        let file = File::create(&arg.file)?;
+        let mut worker = PcapngFileWorker {
+            writer: BufWriter::new(file),
+            interfaces: HashMap::new(),
+        };
+        worker.write_section_header()?;
+        Ok(Box::new(worker))
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.writer.pcapng-file".into(),
+            filters: vec![FileType::new("Pcapng File", &["pcapng"])],
+            ..Metadata::default()
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct Interface {
+    link: u16,
+    id: u32,
+    name: String,
+    description: String,
+}
+
+struct PcapngFileWorker {
+    writer: BufWriter<File>,
+    interfaces: HashMap<Interface, u32>,
+}
+
+fn padded(len: usize) -> usize {
+    (len + 3) / 4 * 4
+}
+
+fn options_len(options: &[(u16, &[u8])]) -> usize {
+    if options.is_empty() {
+        0
+    } else {
+        options
+            .iter()
+            .map(|(_, value)| 4 + padded(value.len()))
+            .sum::<usize>()
+            + 4
+    }
+}
+
+impl PcapngFileWorker {
+    fn write_options(&mut self, options: &[(u16, &[u8])]) -> Result<()> {
+        if options.is_empty() {
+            return Ok(());
+        }
+        for (code, value) in options {
+            self.writer.write_u16::<LittleEndian>(*code)?;
+            self.writer.write_u16::<LittleEndian>(value.len() as u16)?;
+            self.writer.write_all(value)?;
+            self.write_padding(value.len())?;
+        }
+        self.writer.write_u16::<LittleEndian>(OPT_END)?;
+        self.writer.write_u16::<LittleEndian>(0)?;
+        Ok(())
+    }
+
+    fn write_padding(&mut self, len: usize) -> Result<()> {
+        self.writer.write_all(&[0u8; 3][..padded(len) - len])?;
+        Ok(())
+    }
+
+    fn write_section_header(&mut self) -> Result<()> {
+        let len = 28u32;
+        self.writer
+            .write_u32::<LittleEndian>(SECTION_HEADER_BLOCK)?;
+        self.writer.write_u32::<LittleEndian>(len)?;
+        self.writer.write_u32::<LittleEndian>(0x1a2b_3c4d)?;
+        self.writer.write_u16::<LittleEndian>(1)?;
+        self.writer.write_u16::<LittleEndian>(0)?;
+        self.writer.write_i64::<LittleEndian>(-1)?;
+        self.writer.write_u32::<LittleEndian>(len)?;
+        Ok(())
+    }
+
+    /// Returns the index of the interface, writing the description block if necessary.
+    fn interface(&mut self, interface: Interface) -> Result<u32> {
+        if let Some(index) = self.interfaces.get(&interface) {
+            return Ok(*index);
+        }
+        let index = self.interfaces.len() as u32;
+        {
+            let tsresol = [TSRESOL_NSEC];
+            let mut options: Vec<(u16, &[u8])> = vec![(OPT_IF_TSRESOL, &tsresol[..])];
+            if !interface.name.is_empty() {
+                options.push((OPT_IF_NAME, interface.name.as_bytes()));
+            }
+            if !interface.description.is_empty() {
+                options.push((OPT_IF_DESCRIPTION, interface.description.as_bytes()));
+            }
+            let len = (20 + options_len(&options)) as u32;
+            self.writer
+                .write_u32::<LittleEndian>(INTERFACE_DESCRIPTION_BLOCK)?;
+            self.writer.write_u32::<LittleEndian>(len)?;
+            self.writer.write_u16::<LittleEndian>(interface.link)?;
+            self.writer.write_u16::<LittleEndian>(0)?;
+            self.writer.write_u32::<LittleEndian>(0)?;
+            self.write_options(&options)?;
+            self.writer.write_u32::<LittleEndian>(len)?;
+        }
+        self.interfaces.insert(interface, index);
+        Ok(index)
+    }
+}
+
+impl Worker for PcapngFileWorker {
+    fn write(&mut self, _index: u32, stack: &LayerStack) -> Result<()> {
+        if let Some(layer) = stack.bottom() {
+            let data = layer.data();
+            let mut orig_len = data.len() as u64;
+            let mut ts_sec = 0u64;
+            let mut ts_nsec = 0u64;
+            let mut interface = Interface {
+                link: 0,
+                id: 0,
+                name: String::new(),
+                description: String::new(),
+            };
+
+            if let Some(attr) = layer.attr(token!("link.length")) {
+                orig_len = attr.try_get(layer)?.try_into()?;
+            }
+            if let Some(attr) = layer.attr(token!("link.type")) {
+                interface.link = attr.try_get(layer)?.try_into()?;
+            }
+            if let Some(attr) = layer.attr(token!("link.interface")) {
+                interface.id = attr.try_get(layer)?.try_into()?;
+            }
+            if let Some(attr) = layer.attr(token!("link.interface.name")) {
+                interface.name = attr.try_get(layer)?.try_into()?;
+            }
+            if let Some(attr) = layer.attr(token!("link.interface.description")) {
+                interface.description = attr.try_get(layer)?.try_into()?;
+            }
+            if let Some(attr) = layer.attr(token!("link.timestamp.sec")) {
+                ts_sec = attr.try_get(layer)?.try_into()?;
+            }
+            if let Some(attr) = layer.attr(token!("link.timestamp.usec")) {
+                ts_nsec = attr.try_get(layer)?.try_into()?;
+            }
+
+            let index = self.interface(interface)?;
+            let ts = ts_sec * 1_000_000_000 + ts_nsec;
+
+            let comments = stack
+                .layers()
+                .flat_map(|layer| layer.annotations().iter())
+                .map(|a| {
+                    // The comment attached to the frame by the user.
+                    if a.attr() == token!("frame.comment") {
+                        return a.message().to_string();
+                    }
+                    let severity = match a.severity() {
+                        Severity::Note => "note",
+                        Severity::Warn => "warn",
+                        Severity::Error => "error",
+                    };
+                    format!("[{}] {}", severity, a.message())
+                })
+                .collect::<Vec<_>>();
+            let options = comments
+                .iter()
+                .map(|c| (OPT_COMMENT, c.as_bytes()))
+                .collect::<Vec<_>>();
+
+            let len = (32 + padded(data.len()) + options_len(&options)) as u32;
+            self.writer
+                .write_u32::<LittleEndian>(ENHANCED_PACKET_BLOCK)?;
+            self.writer.write_u32::<LittleEndian>(len)?;
+            self.writer.write_u32::<LittleEndian>(index)?;
+            self.writer.write_u32::<LittleEndian>((ts >> 32) as u32)?;
+            self.writer.write_u32::<LittleEndian>(ts as u32)?;
+            self.writer.write_u32::<LittleEndian>(data.len() as u32)?;
+            self.writer.write_u32::<LittleEndian>(orig_len as u32)?;
+            self.writer.write_all(&data)?;
+            self.write_padding(data.len())?;
+            self.write_options(&options)?;
+            self.writer.write_u32::<LittleEndian>(len)?;
+        }
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+genet_writers!(PcapngFileWriter {});
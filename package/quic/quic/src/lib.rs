@@ -0,0 +1,622 @@
+extern crate genet_sdk;
+extern crate ring;
+
+use genet_sdk::{
+    cast::{self, Typed},
+    context::flow_key,
+    decoder::*,
+    endpoint::endpoints,
+    prelude::*,
+};
+use ring::{aead, hkdf};
+use std::{io, ops::Range};
+
+const PORT: u16 = 443;
+
+const VERSION_NEGOTIATION: u32 = 0;
+const VERSION_1: u32 = 0x0000_0001;
+const VERSION_2: u32 = 0x6b33_43cf;
+const DRAFT_29: u32 = 0xff00_001d;
+
+const INITIAL_SALT_V1: [u8; 20] = [
+    0x38, 0x76, 0x2c, 0xf7, 0xf5, 0x59, 0x34, 0xb3, 0x4d, 0x17, 0x9a, 0xe6, 0xa4, 0xc8, 0x0c, 0xad,
+    0xcc, 0xbb, 0x7f, 0x0a,
+];
+const INITIAL_SALT_DRAFT_29: [u8; 20] = [
+    0xaf, 0xbf, 0xec, 0x28, 0x99, 0x93, 0xd2, 0x4c, 0x9e, 0x97, 0x86, 0xf1, 0x9c, 0x61, 0x11, 0xe0,
+    0x43, 0x90, 0xa8, 0x99,
+];
+
+const INITIAL: u8 = 0;
+const ZERO_RTT: u8 = 1;
+const HANDSHAKE: u8 = 2;
+const RETRY: u8 = 3;
+
+const RETRY_INTEGRITY_TAG_LEN: usize = 16;
+const SAMPLE_LEN: usize = 16;
+
+const PADDING: u64 = 0x00;
+const PING: u64 = 0x01;
+const ACK: u64 = 0x02;
+const ACK_ECN: u64 = 0x03;
+const CRYPTO: u64 = 0x06;
+const CONNECTION_CLOSE: u64 = 0x1c;
+
+/// The state of a connection, shared by both directions of a UDP flow.
+struct Connection {
+    client: (Vec<u8>, u16),
+    original_dcid: Vec<u8>,
+    client_cid_len: Option<usize>,
+    server_cid_len: Option<usize>,
+}
+
+/// The fields of a long header.
+struct LongHeader {
+    version: u32,
+    typ: Option<u8>,
+    dcid: Range<usize>,
+    scid: Range<usize>,
+    token: Option<(Range<usize>, Range<usize>)>,
+    length: Option<Range<usize>>,
+    len: usize,
+}
+
+struct QuicWorker {}
+
+impl Worker for QuicWorker {
+    fn decode(
+        &mut self,
+        ctx: &mut Context,
+        stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        if parent.id() != token!("udp") {
+            return Ok(Status::Skip);
+        }
+
+        let data;
+
+        if let Some(payload) = parent.payloads().iter().next() {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let (src, dst) = match endpoints(stack, parent) {
+            Some(endpoints) => endpoints,
+            None => return Ok(Status::Skip),
+        };
+        let src = (&(src.0)[..], src.1);
+        let dst = (&(dst.0)[..], dst.1);
+        let key = flow_key(src, dst);
+
+        if data.is_empty() || (data[0] & 0x40 == 0 && !is_version_negotiation(&data)) {
+            return Ok(Status::Skip);
+        }
+
+        // A datagram may contain several coalesced packets.
+        let mut pos = 0;
+        while pos < data.len() {
+            let packet = data.try_get(pos..)?;
+            let mut layer;
+            if packet[0] & 0x80 != 0 {
+                match parse_long(&packet) {
+                    Ok(header) => {
+                        layer = Layer::new(&QUIC_CLASS, packet.try_get(..header.len)?);
+                        decode_long(ctx, key, src, &mut layer, &header);
+                    }
+                    Err(_) => {
+                        layer = Layer::new(&QUIC_CLASS, packet);
+                        layer.error("truncated header");
+                    }
+                }
+            } else {
+                layer = Layer::new(&QUIC_CLASS, packet);
+                decode_short(ctx, key, src, &mut layer);
+            }
+            pos += layer.data().len();
+            parent.add_child(layer);
+        }
+        Ok(Status::Done)
+    }
+}
+
+fn is_version_negotiation(data: &[u8]) -> bool {
+    data.len() >= 5 && data[0] & 0x80 != 0 && data[1..5] == [0, 0, 0, 0]
+}
+
+fn truncated() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "truncated")
+}
+
+/// Decodes the variable-length integer and returns it with its length.
+fn varint(data: &[u8], pos: usize) -> io::Result<(u64, usize)> {
+    let first = *data.get(pos).ok_or_else(truncated)?;
+    let len = 1 << (first >> 6);
+    let bytes = data.get(pos..pos + len).ok_or_else(truncated)?;
+    let value = bytes[1..]
+        .iter()
+        .fold(u64::from(first & 0x3f), |acc, &b| acc << 8 | u64::from(b));
+    Ok((value, len))
+}
+
+fn range(data: &[u8], range: Range<usize>) -> io::Result<Range<usize>> {
+    data.get(range.clone()).map(|_| range).ok_or_else(truncated)
+}
+
+fn parse_long(data: &[u8]) -> io::Result<LongHeader> {
+    let version = data
+        .get(1..5)
+        .ok_or_else(truncated)?
+        .iter()
+        .fold(0, |acc, &b| acc << 8 | u32::from(b));
+    let dcid_len = *data.get(5).ok_or_else(truncated)? as usize;
+    let dcid = range(data, 6..6 + dcid_len)?;
+    let scid_len = *data.get(dcid.end).ok_or_else(truncated)? as usize;
+    let scid = range(data, dcid.end + 1..dcid.end + 1 + scid_len)?;
+
+    let bits = (data[0] >> 4) & 0b11;
+    let typ = match version {
+        VERSION_1 | DRAFT_29 => Some(bits),
+        VERSION_2 => Some([RETRY, INITIAL, ZERO_RTT, HANDSHAKE][bits as usize]),
+        _ => None,
+    };
+    let mut header = LongHeader {
+        version,
+        typ,
+        dcid,
+        scid,
+        token: None,
+        length: None,
+        len: data.len(),
+    };
+
+    // Retry and Version Negotiation packets, and the packets of unknown
+    // versions extend to the end of the datagram.
+    let mut pos = header.scid.end;
+    match typ {
+        Some(RETRY) | None => return Ok(header),
+        Some(INITIAL) => {
+            let (len, n) = varint(data, pos)?;
+            let token = range(data, pos + n..pos + n + len as usize)?;
+            header.token = Some((pos..pos + n, token.clone()));
+            pos = token.end;
+        }
+        _ => {}
+    }
+    let (len, n) = varint(data, pos)?;
+    header.length = Some(pos..pos + n);
+    header.len = range(data, 0..pos + n + len as usize)?.end;
+    Ok(header)
+}
+
+fn decode_long(
+    ctx: &mut Context,
+    key: u64,
+    src: (&[u8], u16),
+    layer: &mut Layer,
+    header: &LongHeader,
+) {
+    let data = layer.data();
+    layer.add_attr(attr!(&VERSION_ATTR, range: 1..5));
+    layer.add_attr(attr!(&DCID_LENGTH_ATTR, range: 5..6));
+    layer.add_attr(attr!(&DCID_ATTR, range: header.dcid.clone()));
+    layer.add_attr(attr!(&SCID_LENGTH_ATTR, range: header.dcid.end..header.scid.start));
+    layer.add_attr(attr!(&SCID_ATTR, range: header.scid.clone()));
+
+    if header.version == VERSION_NEGOTIATION {
+        if (data.len() - header.scid.end) % 4 != 0 {
+            layer.error("invalid version list");
+        }
+        let end = data.len() - (data.len() - header.scid.end) % 4;
+        for pos in (header.scid.end..end).step_by(4) {
+            layer.add_attr(attr!(&SUPPORTED_VERSION_ATTR, range: pos..pos + 4));
+        }
+        return;
+    }
+
+    let typ = match header.typ {
+        Some(typ) => typ,
+        None => {
+            layer.note("unknown version");
+            return;
+        }
+    };
+    let typ_attr = match header.version {
+        VERSION_2 => attr!(&LONG_TYPE_V2_ATTR, bit_range: 0 2..4),
+        _ => attr!(&LONG_TYPE_ATTR, bit_range: 0 2..4),
+    };
+    layer.add_attr(typ_attr);
+
+    let (client, original_dcid) = {
+        let conn = ctx.state_or_insert_with(key, || Connection {
+            client: (src.0.to_vec(), src.1),
+            original_dcid: data[header.dcid.clone()].to_vec(),
+            client_cid_len: None,
+            server_cid_len: None,
+        });
+        let client = conn.client.0 == src.0 && conn.client.1 == src.1;
+        if client {
+            conn.client_cid_len = Some(header.scid.len());
+        } else {
+            conn.server_cid_len = Some(header.scid.len());
+        }
+        (client, conn.original_dcid.clone())
+    };
+
+    if typ == RETRY {
+        if data.len() < header.scid.end + RETRY_INTEGRITY_TAG_LEN {
+            layer.error("truncated packet");
+            return;
+        }
+        let tag = data.len() - RETRY_INTEGRITY_TAG_LEN;
+        layer.add_attr(attr!(&RETRY_TOKEN_ATTR, range: header.scid.end..tag));
+        layer.add_attr(attr!(&RETRY_INTEGRITY_TAG_ATTR, range: tag..data.len()));
+        return;
+    }
+
+    if let Some((ref len, ref token)) = header.token {
+        layer.add_attr(attr!(&TOKEN_LENGTH_ATTR, range: len.clone()));
+        layer.add_attr(attr!(&TOKEN_ATTR, range: token.clone()));
+    }
+    let pn_offset = match header.length {
+        Some(ref length) => {
+            layer.add_attr(attr!(&LENGTH_ATTR, range: length.clone()));
+            length.end
+        }
+        None => return,
+    };
+    layer.add_attr(attr!(&PROTECTED_PAYLOAD_ATTR, range: pn_offset..data.len()));
+
+    // Initial packets are protected with the keys derived from
+    // the Destination Connection ID of the first Initial packet of the client.
+    if typ == INITIAL {
+        let decrypted = Keys::initial(header.version, &original_dcid, client)
+            .and_then(|keys| keys.decrypt(&data, pn_offset));
+        match decrypted {
+            Some((pn, pn_len, payload)) => {
+                layer.add_attr(
+                    attr!(&PACKET_NUMBER_ATTR, range: pn_offset..pn_offset + pn_len, value: pn),
+                );
+                if decode_frames(layer, pn_offset + pn_len..data.len(), &payload).is_err() {
+                    layer.error("malformed frame");
+                }
+            }
+            None => layer.warn("decryption failed"),
+        }
+    }
+}
+
+fn decode_short(ctx: &mut Context, key: u64, src: (&[u8], u16), layer: &mut Layer) {
+    let data = layer.data();
+    layer.add_attr(attr!(&SPIN_BIT_ATTR, bit_range: 0 2..3));
+
+    // The length of the Destination Connection ID is not encoded in short headers.
+    let dcid_len = ctx.state::<Connection>(key).and_then(|conn| {
+        if conn.client.0 == src.0 && conn.client.1 == src.1 {
+            conn.server_cid_len
+        } else {
+            conn.client_cid_len
+        }
+    });
+    match dcid_len {
+        Some(len) if 1 + len <= data.len() => {
+            layer.add_attr(attr!(&DCID_ATTR, range: 1..1 + len));
+            layer.add_attr(attr!(&PROTECTED_PAYLOAD_ATTR, range: 1 + len..data.len()));
+        }
+        Some(_) => layer.error("truncated header"),
+        None => layer.add_attr(attr!(&PROTECTED_PAYLOAD_ATTR, range: 1..data.len())),
+    }
+}
+
+/// Decodes the frames in the decrypted payload.
+///
+/// The frames are not a part of the layer data,
+/// so that the attributes refer to the whole protected payload.
+fn decode_frames(layer: &mut Layer, range: Range<usize>, payload: &[u8]) -> io::Result<()> {
+    let mut crypto = Vec::new();
+    let mut pos = 0;
+    while pos < payload.len() {
+        let (typ, n) = varint(payload, pos)?;
+        layer.add_attr(attr!(&FRAME_ATTR, range: range.clone()));
+        layer.add_attr(attr!(&FRAME_TYPE_ATTR, range: range.clone(), value: typ));
+        pos += n;
+        match typ {
+            PADDING => {
+                while payload.get(pos) == Some(&0) {
+                    pos += 1;
+                }
+            }
+            PING => {}
+            ACK | ACK_ECN => {
+                let (largest, n) = varint(payload, pos)?;
+                layer.add_attr(attr!(&ACK_LARGEST_ATTR, range: range.clone(), value: largest));
+                pos += n;
+                let (_delay, n) = varint(payload, pos)?;
+                pos += n;
+                let (count, n) = varint(payload, pos)?;
+                pos += n;
+                let fields = 1 + count * 2 + if typ == ACK_ECN { 3 } else { 0 };
+                for _ in 0..fields {
+                    pos += varint(payload, pos)?.1;
+                }
+            }
+            CRYPTO => {
+                let (offset, n) = varint(payload, pos)?;
+                pos += n;
+                let (len, n) = varint(payload, pos)?;
+                pos += n;
+                let data = payload.get(pos..pos + len as usize).ok_or_else(truncated)?;
+                layer.add_attr(attr!(&CRYPTO_OFFSET_ATTR, range: range.clone(), value: offset));
+                layer.add_attr(attr!(&CRYPTO_LENGTH_ATTR, range: range.clone(), value: len));
+                if offset as usize == crypto.len() {
+                    crypto.extend_from_slice(data);
+                }
+                pos += len as usize;
+            }
+            CONNECTION_CLOSE => {
+                let (code, n) = varint(payload, pos)?;
+                layer.add_attr(attr!(&ERROR_CODE_ATTR, range: range.clone(), value: code));
+                pos += n;
+                pos += varint(payload, pos)?.1;
+                let (len, n) = varint(payload, pos)?;
+                pos += n + len as usize;
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unexpected frame",
+                ))
+            }
+        }
+        if pos > payload.len() {
+            return Err(truncated());
+        }
+    }
+
+    // The beginning of the TLS handshake messages, e.g. ClientHello.
+    if !crypto.is_empty() {
+        layer.add_payload(Payload::new(crypto, "@crypto:quic"));
+    }
+    Ok(())
+}
+
+/// The packet protection keys of one direction.
+struct Keys {
+    key: aead::LessSafeKey,
+    iv: [u8; 12],
+    hp: aead::quic::HeaderProtectionKey,
+}
+
+struct Len(usize);
+
+impl hkdf::KeyType for Len {
+    fn len(&self) -> usize {
+        self.0
+    }
+}
+
+/// HKDF-Expand-Label defined in RFC 8446.
+fn expand_label(prk: &hkdf::Prk, label: &[u8], len: usize) -> Option<Vec<u8>> {
+    let out_len = [(len >> 8) as u8, len as u8];
+    let label_len = [(6 + label.len()) as u8];
+    let info = [
+        &out_len[..],
+        &label_len[..],
+        &b"tls13 "[..],
+        label,
+        &[0][..],
+    ];
+    let mut out = vec![0; len];
+    prk.expand(&info, Len(len)).ok()?.fill(&mut out).ok()?;
+    Some(out)
+}
+
+impl Keys {
+    /// Derives the keys of Initial packets defined in RFC 9001.
+    fn initial(version: u32, dcid: &[u8], client: bool) -> Option<Keys> {
+        let salt = match version {
+            VERSION_1 => &INITIAL_SALT_V1,
+            DRAFT_29 => &INITIAL_SALT_DRAFT_29,
+            _ => return None,
+        };
+        let initial = hkdf::Salt::new(hkdf::HKDF_SHA256, salt).extract(dcid);
+        let label: &[u8] = if client { b"client in" } else { b"server in" };
+        let secret = expand_label(&initial, label, 32)?;
+        let secret = hkdf::Prk::new_less_safe(hkdf::HKDF_SHA256, &secret);
+
+        let key = expand_label(&secret, b"quic key", 16)?;
+        let key = aead::UnboundKey::new(&aead::AES_128_GCM, &key).ok()?;
+        let mut iv = [0; 12];
+        iv.copy_from_slice(&expand_label(&secret, b"quic iv", 12)?);
+        let hp = expand_label(&secret, b"quic hp", 16)?;
+        let hp = aead::quic::HeaderProtectionKey::new(&aead::quic::AES_128, &hp).ok()?;
+        Some(Keys {
+            key: aead::LessSafeKey::new(key),
+            iv,
+            hp,
+        })
+    }
+
+    /// Removes the header protection and decrypts the payload.
+    ///
+    /// Returns the truncated packet number, its length and the plaintext.
+    fn decrypt(&self, packet: &[u8], pn_offset: usize) -> Option<(u64, usize, Vec<u8>)> {
+        let sample = packet.get(pn_offset + 4..pn_offset + 4 + SAMPLE_LEN)?;
+        let mask = self.hp.new_mask(sample).ok()?;
+
+        let mut header = packet[..pn_offset + 4].to_vec();
+        header[0] ^= mask[0] & if header[0] & 0x80 != 0 { 0x0f } else { 0x1f };
+        let pn_len = (header[0] & 0b11) as usize + 1;
+        let mut pn = 0;
+        for i in 0..pn_len {
+            header[pn_offset + i] ^= mask[1 + i];
+            pn = pn << 8 | u64::from(header[pn_offset + i]);
+        }
+        header.truncate(pn_offset + pn_len);
+
+        let mut nonce = self.iv;
+        for i in 0..8 {
+            nonce[4 + i] ^= (pn >> (56 - i * 8)) as u8;
+        }
+        let mut payload = packet[pn_offset + pn_len..].to_vec();
+        let len = self
+            .key
+            .open_in_place(
+                aead::Nonce::assume_unique_for_key(nonce),
+                aead::Aad::from(&header[..]),
+                &mut payload,
+            )
+            .ok()?
+            .len();
+        payload.truncate(len);
+        Some((pn, pn_len, payload))
+    }
+}
+
+/// Cast for variable-length integers.
+#[derive(Clone)]
+struct VarInt();
+
+impl Typed for VarInt {
+    type Output = u64;
+
+    fn cast(&self, attr: &Attr, data: &ByteSlice) -> io::Result<u64> {
+        varint(&data.try_get(attr.range())?, 0).map(|(value, _)| value)
+    }
+}
+
+#[derive(Clone)]
+struct QuicDecoder {}
+
+impl Decoder for QuicDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(QuicWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.decoder.quic".into(),
+            name: "QUIC".into(),
+            description: "QUIC transport protocol".into(),
+            exec_type: ExecType::SerialSync,
+            // A connection is decoded while the server keeps the port, so client
+            // migration and NAT rebinding are followed. The state is keyed by the
+            // UDP flow, however, so the short headers of a rebound client are
+            // decoded without the connection ID length. Servers on other ports
+            // are not decoded.
+            triggers: vec![
+                Trigger::new("udp.src", PORT.into()),
+                Trigger::new("udp.dst", PORT.into()),
+            ],
+            references: vec![
+                "https://tools.ietf.org/html/rfc9000".into(),
+                "https://tools.ietf.org/html/rfc9001".into(),
+            ],
+            ..Metadata::default()
+        }
+    }
+}
+
+fn version_labels() -> Vec<(u64, &'static str)> {
+    vec![
+        (u64::from(VERSION_NEGOTIATION), "Version Negotiation"),
+        (u64::from(VERSION_1), "QUIC v1"),
+        (u64::from(VERSION_2), "QUIC v2"),
+        (u64::from(DRAFT_29), "draft-29"),
+    ]
+}
+
+def_layer_class!(QUIC_CLASS, "quic",
+    header: attr!(&HEADER_FORM_ATTR, bit_range: 0 0..1),
+    header: attr!(&FIXED_BIT_ATTR, bit_range: 0 1..2)
+);
+
+def_attr_class!(HEADER_FORM_ATTR, "quic.headerForm",
+    typ: "@enum",
+    cast: cast::UInt8().map(|v| v >> 7),
+    labels: vec![(0, "Short"), (1, "Long")]
+);
+
+def_attr_class!(FIXED_BIT_ATTR, "quic.fixedBit",
+    cast: cast::UInt8().map(|v| (v & 0b0100_0000) != 0)
+);
+
+def_attr_class!(LONG_TYPE_ATTR, "quic.longPacketType",
+    typ: "@enum",
+    cast: cast::UInt8().map(|v| (v >> 4) & 0b11),
+    labels: vec![(0, "Initial"), (1, "0-RTT"), (2, "Handshake"), (3, "Retry")]
+);
+
+def_attr_class!(LONG_TYPE_V2_ATTR, "quic.longPacketType",
+    typ: "@enum",
+    cast: cast::UInt8().map(|v| (v >> 4) & 0b11),
+    labels: vec![(0, "Retry"), (1, "Initial"), (2, "0-RTT"), (3, "Handshake")]
+);
+
+def_attr_class!(SPIN_BIT_ATTR, "quic.spinBit",
+    cast: cast::UInt8().map(|v| (v & 0b0010_0000) != 0)
+);
+
+def_attr_class!(VERSION_ATTR, "quic.version",
+    typ: "@enum",
+    cast: cast::UInt32BE(),
+    labels: version_labels()
+);
+
+def_attr_class!(DCID_LENGTH_ATTR, "quic.dcidLength", cast: cast::UInt8());
+
+def_attr_class!(DCID_ATTR, "quic.dcid", cast: cast::ByteSlice());
+
+def_attr_class!(SCID_LENGTH_ATTR, "quic.scidLength", cast: cast::UInt8());
+
+def_attr_class!(SCID_ATTR, "quic.scid", cast: cast::ByteSlice());
+
+def_attr_class!(SUPPORTED_VERSION_ATTR, "quic.supportedVersion",
+    typ: "@enum",
+    repeated: ,
+    cast: cast::UInt32BE(),
+    labels: version_labels()
+);
+
+def_attr_class!(TOKEN_LENGTH_ATTR, "quic.tokenLength", cast: VarInt());
+
+def_attr_class!(TOKEN_ATTR, "quic.token", cast: cast::ByteSlice());
+
+def_attr_class!(LENGTH_ATTR, "quic.length", cast: VarInt());
+
+def_attr_class!(PACKET_NUMBER_ATTR, "quic.packetNumber");
+
+def_attr_class!(PROTECTED_PAYLOAD_ATTR, "quic.protectedPayload", cast: cast::ByteSlice());
+
+def_attr_class!(RETRY_TOKEN_ATTR, "quic.retryToken", cast: cast::ByteSlice());
+
+def_attr_class!(RETRY_INTEGRITY_TAG_ATTR, "quic.retryIntegrityTag", cast: cast::ByteSlice());
+
+def_attr_class!(FRAME_ATTR, "quic.frame",
+    typ: "@nested",
+    repeated: ,
+    value: true
+);
+
+def_attr_class!(FRAME_TYPE_ATTR, "quic.frame.type",
+    typ: "@enum",
+    labels: vec![
+        (0x00, "PADDING"),
+        (0x01, "PING"),
+        (0x02, "ACK"),
+        (0x03, "ACK"),
+        (0x06, "CRYPTO"),
+        (0x1c, "CONNECTION_CLOSE"),
+    ]
+);
+
+def_attr_class!(ACK_LARGEST_ATTR, "quic.frame.largestAcknowledged");
+
+def_attr_class!(CRYPTO_OFFSET_ATTR, "quic.frame.offset");
+
+def_attr_class!(CRYPTO_LENGTH_ATTR, "quic.frame.length");
+
+def_attr_class!(ERROR_CODE_ATTR, "quic.frame.errorCode");
+
+genet_decoders!(QuicDecoder {});
@@ -0,0 +1,413 @@
+extern crate genet_sdk;
+
+use genet_sdk::{cast, context::state_key, decoder::*, prelude::*, tcp};
+
+const HEADER_LEN: usize = 12;
+const CHUNK_HEADER_LEN: usize = 4;
+
+const CHUNK_DATA: u8 = 0;
+const CHUNK_INIT: u8 = 1;
+const CHUNK_INIT_ACK: u8 = 2;
+const CHUNK_SACK: u8 = 3;
+const CHUNK_HEARTBEAT: u8 = 4;
+const CHUNK_HEARTBEAT_ACK: u8 = 5;
+
+const FLAG_UNORDERED: u8 = 0b0000_0100;
+const FLAG_BEGINNING: u8 = 0b0000_0010;
+const FLAG_ENDING: u8 = 0b0000_0001;
+
+/// A user message being reassembled from DATA chunk fragments.
+struct Message {
+    next_tsn: u32,
+    ppid: u32,
+    frames: Vec<u32>,
+    data: Vec<u8>,
+}
+
+struct SctpWorker {}
+
+impl Worker for SctpWorker {
+    fn decode(
+        &mut self,
+        ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let data;
+
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:sctp"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let addr = |id| {
+            parent
+                .attr(id)
+                .and_then(|attr| parent.data().try_get(attr.range()).ok())
+        };
+        let addrs = (addr(token!("_.src")), addr(token!("_.dst")));
+
+        let mut layer = Layer::new(&SCTP_CLASS, data);
+        if data.len() < HEADER_LEN {
+            layer.error("truncated header");
+            parent.add_child(layer);
+            return Ok(Status::Done);
+        }
+
+        let src: u16 = SRC_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        let dst: u16 = DST_ATTR_HEADER.try_get(&layer)?.try_into()?;
+
+        let mut pos = HEADER_LEN;
+        while pos < data.len() {
+            if pos + CHUNK_HEADER_LEN > data.len() {
+                layer.error("truncated chunk");
+                break;
+            }
+            let typ = data[pos];
+            let flags = data[pos + 1];
+            let len = (data[pos + 2] as usize) << 8 | data[pos + 3] as usize;
+            if len < CHUNK_HEADER_LEN || pos + len > data.len() {
+                layer.error("malformed chunk");
+                break;
+            }
+            let end = pos + len;
+
+            layer.add_attr(attr!(&CHUNK_ATTR, range: pos..end));
+            layer.add_attr(attr!(&CHUNK_TYPE_ATTR, range: pos..pos + 1));
+            layer.add_attr(attr!(&CHUNK_FLAGS_ATTR, range: pos + 1..pos + 2));
+            layer.add_attr(attr!(&CHUNK_LENGTH_ATTR, range: pos + 2..pos + 4));
+
+            match typ {
+                CHUNK_DATA if len >= 16 => {
+                    layer.add_attr(attr!(&UNORDERED_ATTR, bit_range: pos + 1 5..6));
+                    layer.add_attr(attr!(&BEGINNING_ATTR, bit_range: pos + 1 6..7));
+                    layer.add_attr(attr!(&ENDING_ATTR, bit_range: pos + 1 7..8));
+                    layer.add_attr(attr!(&TSN_ATTR, range: pos + 4..pos + 8));
+                    layer.add_attr(attr!(&STREAM_ID_ATTR, range: pos + 8..pos + 10));
+                    layer.add_attr(attr!(&STREAM_SEQ_ATTR, range: pos + 10..pos + 12));
+                    layer.add_attr(attr!(&PPID_ATTR, range: pos + 12..pos + 16));
+
+                    let tsn = read_u32(&data[pos + 4..]);
+                    let stream_id = read_u16(&data[pos + 8..]);
+                    let ppid = read_u32(&data[pos + 12..]);
+                    let user_data = data.try_get(pos + 16..end)?;
+
+                    if flags & (FLAG_BEGINNING | FLAG_ENDING) == FLAG_BEGINNING | FLAG_ENDING {
+                        layer.add_payload(Payload::new(user_data, get_ppid_type(ppid)));
+                    } else if let (Some(src_addr), Some(dst_addr)) = addrs {
+                        let key = state_key(&(
+                            &src_addr[..],
+                            src,
+                            &dst_addr[..],
+                            dst,
+                            stream_id,
+                            flags & FLAG_UNORDERED != 0,
+                        ));
+                        reassemble(ctx, &mut layer, key, flags, tsn, ppid, &user_data);
+                    }
+                }
+                CHUNK_INIT | CHUNK_INIT_ACK if len >= 20 => {
+                    layer.add_attr(attr!(&INITIATE_TAG_ATTR, range: pos + 4..pos + 8));
+                    layer.add_attr(attr!(&A_RWND_ATTR, range: pos + 8..pos + 12));
+                    layer.add_attr(attr!(&OUTBOUND_STREAMS_ATTR, range: pos + 12..pos + 14));
+                    layer.add_attr(attr!(&INBOUND_STREAMS_ATTR, range: pos + 14..pos + 16));
+                    layer.add_attr(attr!(&INITIAL_TSN_ATTR, range: pos + 16..pos + 20));
+                }
+                CHUNK_SACK if len >= 16 => {
+                    layer.add_attr(attr!(&CUMULATIVE_TSN_ACK_ATTR, range: pos + 4..pos + 8));
+                    layer.add_attr(attr!(&A_RWND_ATTR, range: pos + 8..pos + 12));
+                    layer.add_attr(attr!(&GAP_BLOCKS_ATTR, range: pos + 12..pos + 14));
+                    layer.add_attr(attr!(&DUPLICATE_TSNS_ATTR, range: pos + 14..pos + 16));
+
+                    let gaps = read_u16(&data[pos + 12..]) as usize;
+                    let dups = read_u16(&data[pos + 14..]) as usize;
+                    if pos + 16 + gaps * 4 + dups * 4 > end {
+                        layer.annotate(
+                            Annotation::new(Severity::Error, "truncated SACK chunk")
+                                .with_attr("sctp.chunk"),
+                        );
+                    } else {
+                        let mut offset = pos + 16;
+                        for _ in 0..gaps {
+                            layer.add_attr(attr!(&GAP_BLOCK_ATTR, range: offset..offset + 4));
+                            layer.add_attr(attr!(&GAP_BLOCK_START_ATTR, range: offset..offset + 2));
+                            layer.add_attr(
+                                attr!(&GAP_BLOCK_END_ATTR, range: offset + 2..offset + 4),
+                            );
+                            offset += 4;
+                        }
+                        for _ in 0..dups {
+                            layer.add_attr(attr!(&DUPLICATE_TSN_ATTR, range: offset..offset + 4));
+                            offset += 4;
+                        }
+                    }
+                }
+                CHUNK_HEARTBEAT | CHUNK_HEARTBEAT_ACK if len >= 8 => {
+                    layer.add_attr(attr!(&HEARTBEAT_INFO_ATTR, range: pos + 8..end));
+                }
+                CHUNK_DATA | CHUNK_INIT | CHUNK_INIT_ACK | CHUNK_SACK | CHUNK_HEARTBEAT
+                | CHUNK_HEARTBEAT_ACK => {
+                    layer.annotate(
+                        Annotation::new(Severity::Error, "invalid chunk length")
+                            .with_attr("sctp.chunk.length"),
+                    );
+                }
+                _ => {}
+            }
+
+            // Chunks are padded to a multiple of 4 bytes.
+            pos = end + (4 - len % 4) % 4;
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+/// Appends the fragment to the user message and adds the payload once the
+/// last fragment arrives.
+///
+/// Messages longer than `tcp::DEFAULT_PDU_LIMIT` are dropped.
+fn reassemble(
+    ctx: &mut Context,
+    layer: &mut Layer,
+    key: u64,
+    flags: u8,
+    tsn: u32,
+    ppid: u32,
+    data: &[u8],
+) {
+    let frame = ctx.frame_index();
+    let message = ctx.state_or_insert_with(key, || None::<Message>);
+    if flags & FLAG_BEGINNING != 0 {
+        *message = Some(Message {
+            next_tsn: tsn,
+            ppid,
+            frames: Vec::new(),
+            data: Vec::new(),
+        });
+    }
+
+    let exceeded = message.as_ref().map_or(false, |msg| {
+        msg.next_tsn == tsn && msg.data.len() + data.len() > tcp::DEFAULT_PDU_LIMIT
+    });
+    if exceeded {
+        *message = None;
+        layer.warn("user message exceeds the reassembly limit");
+        return;
+    }
+
+    let complete = match *message {
+        Some(ref mut msg) if msg.next_tsn == tsn => {
+            msg.next_tsn = tsn.wrapping_add(1);
+            if msg.frames.last() != Some(&frame) {
+                msg.frames.push(frame);
+            }
+            msg.data.extend_from_slice(data);
+            flags & FLAG_ENDING != 0
+        }
+        Some(_) => {
+            *message = None;
+            layer.warn("missing DATA chunk fragment");
+            return;
+        }
+        None => return,
+    };
+
+    if complete {
+        if let Some(msg) = message.take() {
+            for f in msg.frames {
+                if f != frame {
+                    layer
+                        .add_link(Link::new("sctp.fragment", f).with_reverse("sctp.reassembledIn"));
+                }
+            }
+            layer.add_attr(attr!(&REASSEMBLED_ATTR));
+            layer.add_payload(Payload::new(msg.data, get_ppid_type(msg.ppid)));
+        }
+    }
+}
+
+fn read_u16(data: &[u8]) -> u16 {
+    (data[0] as u16) << 8 | data[1] as u16
+}
+
+fn read_u32(data: &[u8]) -> u32 {
+    (data[0] as u32) << 24 | (data[1] as u32) << 16 | (data[2] as u32) << 8 | data[3] as u32
+}
+
+/// Returns the payload type of the Payload Protocol Identifier.
+fn get_ppid_type(ppid: u32) -> Token {
+    match ppid {
+        3 => token!("@data:m3ua"),
+        18 => token!("@data:s1ap"),
+        27 => token!("@data:x2ap"),
+        46 => token!("@data:diameter"),
+        60 => token!("@data:ngap"),
+        _ => token!("@data:sctp:message"),
+    }
+}
+
+#[derive(Clone)]
+struct SctpDecoder {}
+
+impl Decoder for SctpDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(SctpWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.decoder.sctp".into(),
+            name: "SCTP".into(),
+            description: "Stream Control Transmission Protocol".into(),
+            exec_type: ExecType::SerialSync,
+            references: vec!["https://tools.ietf.org/html/rfc4960".into()],
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(SCTP_CLASS, "sctp",
+    alias: "_.src" "sctp.src",
+    alias: "_.dst" "sctp.dst",
+    header: &SRC_ATTR_HEADER,
+    header: &DST_ATTR_HEADER,
+    header: attr!(&VERIFICATION_TAG_ATTR, range: 4..8),
+    header: attr!(&CHECKSUM_ATTR, range: 8..12)
+);
+
+def_attr!(SRC_ATTR_HEADER, &SRC_ATTR, range: 0..2);
+def_attr!(DST_ATTR_HEADER, &DST_ATTR, range: 2..4);
+
+def_attr_class!(SRC_ATTR, "sctp.src",
+    typ: "@sctp:port",
+    cast: cast::UInt16BE()
+);
+
+def_attr_class!(DST_ATTR, "sctp.dst",
+    typ: "@sctp:port",
+    cast: cast::UInt16BE()
+);
+
+def_attr_class!(VERIFICATION_TAG_ATTR, "sctp.verificationTag", cast: cast::UInt32BE());
+
+def_attr_class!(CHECKSUM_ATTR, "sctp.checksum", cast: cast::UInt32BE());
+
+def_attr_class!(REASSEMBLED_ATTR, "sctp.reassembled",
+    typ: "@novalue",
+    value: true
+);
+
+def_attr_class!(CHUNK_ATTR, "sctp.chunk",
+    typ: "@nested",
+    repeated: ,
+    value: true
+);
+
+def_attr_class!(CHUNK_TYPE_ATTR, "sctp.chunk.type",
+    typ: "@enum",
+    cast: cast::UInt8(),
+    labels: vec![
+        (0, "DATA"),
+        (1, "INIT"),
+        (2, "INIT ACK"),
+        (3, "SACK"),
+        (4, "HEARTBEAT"),
+        (5, "HEARTBEAT ACK"),
+        (6, "ABORT"),
+        (7, "SHUTDOWN"),
+        (8, "SHUTDOWN ACK"),
+        (9, "ERROR"),
+        (10, "COOKIE ECHO"),
+        (11, "COOKIE ACK"),
+        (12, "ECNE"),
+        (13, "CWR"),
+        (14, "SHUTDOWN COMPLETE"),
+        (15, "AUTH"),
+        (64, "I-DATA"),
+        (128, "ASCONF ACK"),
+        (130, "RE-CONFIG"),
+        (132, "PAD"),
+        (192, "FORWARD TSN"),
+        (193, "ASCONF"),
+    ]
+);
+
+def_attr_class!(CHUNK_FLAGS_ATTR, "sctp.chunk.flags",
+    typ: "@flags",
+    cast: cast::UInt8()
+);
+
+def_attr_class!(CHUNK_LENGTH_ATTR, "sctp.chunk.length", cast: cast::UInt16BE());
+
+def_attr_class!(UNORDERED_ATTR, "sctp.chunk.flags.unordered",
+    cast: cast::UInt8().map(|v| v & FLAG_UNORDERED != 0)
+);
+
+def_attr_class!(BEGINNING_ATTR, "sctp.chunk.flags.beginning",
+    cast: cast::UInt8().map(|v| v & FLAG_BEGINNING != 0)
+);
+
+def_attr_class!(ENDING_ATTR, "sctp.chunk.flags.ending",
+    cast: cast::UInt8().map(|v| v & FLAG_ENDING != 0)
+);
+
+def_attr_class!(TSN_ATTR, "sctp.chunk.tsn", cast: cast::UInt32BE());
+
+def_attr_class!(STREAM_ID_ATTR, "sctp.chunk.streamId", cast: cast::UInt16BE());
+
+def_attr_class!(STREAM_SEQ_ATTR, "sctp.chunk.streamSeq", cast: cast::UInt16BE());
+
+def_attr_class!(PPID_ATTR, "sctp.chunk.ppid",
+    typ: "@enum",
+    cast: cast::UInt32BE(),
+    labels: vec![
+        (3, "M3UA"),
+        (18, "S1AP"),
+        (27, "X2AP"),
+        (46, "Diameter"),
+        (47, "Diameter DTLS"),
+        (60, "NGAP"),
+    ]
+);
+
+def_attr_class!(INITIATE_TAG_ATTR, "sctp.chunk.initiateTag", cast: cast::UInt32BE());
+
+def_attr_class!(A_RWND_ATTR, "sctp.chunk.aRwnd", cast: cast::UInt32BE());
+
+def_attr_class!(OUTBOUND_STREAMS_ATTR, "sctp.chunk.outboundStreams", cast: cast::UInt16BE());
+
+def_attr_class!(INBOUND_STREAMS_ATTR, "sctp.chunk.inboundStreams", cast: cast::UInt16BE());
+
+def_attr_class!(INITIAL_TSN_ATTR, "sctp.chunk.initialTsn", cast: cast::UInt32BE());
+
+def_attr_class!(CUMULATIVE_TSN_ACK_ATTR, "sctp.chunk.cumulativeTsnAck", cast: cast::UInt32BE());
+
+def_attr_class!(GAP_BLOCKS_ATTR, "sctp.chunk.gapBlocks", cast: cast::UInt16BE());
+
+def_attr_class!(DUPLICATE_TSNS_ATTR, "sctp.chunk.duplicateTsns", cast: cast::UInt16BE());
+
+def_attr_class!(GAP_BLOCK_ATTR, "sctp.chunk.gapBlock",
+    typ: "@nested",
+    repeated: ,
+    value: true
+);
+
+def_attr_class!(GAP_BLOCK_START_ATTR, "sctp.chunk.gapBlock.start", cast: cast::UInt16BE());
+
+def_attr_class!(GAP_BLOCK_END_ATTR, "sctp.chunk.gapBlock.end", cast: cast::UInt16BE());
+
+def_attr_class!(DUPLICATE_TSN_ATTR, "sctp.chunk.duplicateTsn",
+    repeated: ,
+    cast: cast::UInt32BE()
+);
+
+def_attr_class!(HEARTBEAT_INFO_ATTR, "sctp.chunk.heartbeatInfo", cast: cast::ByteSlice());
+
+genet_decoders!(SctpDecoder {});
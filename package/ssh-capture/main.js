@@ -0,0 +1,68 @@
+const m = require('mithril')
+const genet = require('@genet/api')
+class SSHCaptureView {
+  async create (host, ifs, filter) {
+    const sess = await genet.session.create()
+    const stream = {
+      cmd: genet.config.get('@genet/ssh-capture.command'),
+      host,
+      interface: ifs,
+      filter,
+      sudo: genet.config.get('@genet/ssh-capture.sudo'),
+      compression: genet.config.get('@genet/ssh-capture.compression'),
+      reconnect: genet.config.get('@genet/ssh-capture.reconnect'),
+      bandwidth: genet.config.get('@genet/ssh-capture.bandwidthLimit'),
+    }
+    const snaplen = genet.config.get('@genet/ssh-capture.snapshotLength')
+    if (Number.isInteger(snaplen)) {
+      stream.snaplen = snaplen
+    }
+    const name = 'app.genet.reader.ssh-capture'
+    genet.resumer.set('core:session:stream-reader', {
+      name,
+      stream,
+    })
+    sess.regiterStreamReader(name, stream)
+    sess.startStream()
+    genet.workspace.set('_.ssh-capture.host', host)
+    genet.workspace.set('_.ssh-capture.interface', ifs)
+    genet.workspace.set('_.ssh-capture.filter', filter)
+    genet.action.emit('core:session:created', sess)
+  }
+
+  view (vnode) {
+    const field = (name, placeholder) => m('li', [
+      m('input', {
+        type: 'text',
+        name,
+        placeholder,
+        value: genet.workspace.get(`_.ssh-capture.${name}`) || '',
+      })
+    ])
+    return m('div', { class: 'ssh-capture-view' }, [
+      m('ul', [
+        field('host', 'user@example.com'),
+        field('interface', 'any'),
+        field('filter', 'Capture filter (e.g. tcp port 80)'),
+        m('li', [
+          m('input', {
+            type: 'button',
+            value: 'Start Remote Capture',
+            onclick: () => {
+              const value = (name) =>
+                vnode.dom.querySelector(`[name=${name}]`).value.trim()
+              const host = value('host')
+              if (host.length === 0) {
+                return
+              }
+              this.create(host, value('interface') || 'any', value('filter'))
+              vnode.attrs.callback()
+            },
+          })
+        ])
+      ])
+    ])
+  }
+}
+
+module.exports = SSHCaptureView
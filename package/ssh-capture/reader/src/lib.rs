@@ -0,0 +1,293 @@
+extern crate byteorder;
+extern crate genet_sdk;
+extern crate serde;
+extern crate serde_json;
+
+#[macro_use]
+extern crate serde_derive;
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use genet_sdk::{prelude::*, reader::*};
+use std::{
+    io::{self, BufReader, Error, ErrorKind, Read},
+    process::{Child, ChildStdout, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+const HEADER_LEN: usize = 24;
+const RECORD_HEADER_LEN: usize = 16;
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct Arg {
+    /// SSH client command.
+    cmd: String,
+    host: String,
+    port: Option<u16>,
+    interface: String,
+    filter: String,
+    snaplen: Option<u32>,
+
+    /// Runs tcpdump with `sudo -n`.
+    sudo: bool,
+    compression: bool,
+
+    /// Maximum number of consecutive reconnection attempts.
+    reconnect: u32,
+
+    /// Maximum read rate in bytes per second, or 0 for unlimited.
+    bandwidth: u64,
+}
+
+impl Default for Arg {
+    fn default() -> Self {
+        Arg {
+            cmd: "ssh".into(),
+            host: String::new(),
+            port: None,
+            interface: "any".into(),
+            filter: String::new(),
+            snaplen: None,
+            sudo: false,
+            compression: true,
+            reconnect: 3,
+            bandwidth: 0,
+        }
+    }
+}
+
+/// Quotes the argument for the remote POSIX shell.
+fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+impl Arg {
+    fn remote_command(&self) -> String {
+        let mut cmd = Vec::new();
+        if self.sudo {
+            cmd.push("sudo".to_string());
+            cmd.push("-n".to_string());
+        }
+        cmd.push("tcpdump".to_string());
+        cmd.push("-i".to_string());
+        cmd.push(quote(&self.interface));
+        if let Some(snaplen) = self.snaplen {
+            cmd.push("-s".to_string());
+            cmd.push(snaplen.to_string());
+        }
+        cmd.push("-U".to_string());
+        cmd.push("-w".to_string());
+        cmd.push("-".to_string());
+        if !self.filter.is_empty() {
+            cmd.push(quote(&self.filter));
+        }
+        cmd.join(" ")
+    }
+
+    fn spawn(&self) -> io::Result<(Child, BufReader<ChildStdout>)> {
+        let mut command = Command::new(&self.cmd);
+        command.args(&["-o", "BatchMode=yes"]);
+        if let Some(port) = self.port {
+            command.arg("-p").arg(port.to_string());
+        }
+        if self.compression {
+            command.arg("-C");
+        }
+        let mut child = command
+            .arg(&self.host)
+            .arg(self.remote_command())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let reader = BufReader::new(
+            child
+                .stdout
+                .take()
+                .ok_or_else(|| Error::new(ErrorKind::Other, "no stdout"))?,
+        );
+        Ok((child, reader))
+    }
+}
+
+#[derive(Clone)]
+struct SSHCaptureReader {}
+
+impl Reader for SSHCaptureReader {
+    fn new_worker(&self, _ctx: &Context, arg: &str) -> Result<Box<Worker>> {
+        let arg: Arg = serde_json::from_str(arg)?;
+        if arg.host.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "no host specified").into());
+        }
+        let (child, reader) = arg.spawn()?;
+        let mut worker = SSHCaptureWorker {
+            arg,
+            child,
+            reader,
+            le: true,
+            nsec: false,
+            link_class: Fixed::new(LayerClass::builder("[link-0]").build()),
+            attempts: 0,
+            limiter: Limiter::new(),
+        };
+        worker.read_header()?;
+        Ok(Box::new(worker))
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.reader.ssh-capture".into(),
+            ..Metadata::default()
+        }
+    }
+}
+
+/// Throttles the reads to the given rate.
+///
+/// The pipe is left unread while waiting,
+/// so the SSH connection slows down the remote side.
+struct Limiter {
+    start: Instant,
+    bytes: u64,
+}
+
+impl Limiter {
+    fn new() -> Limiter {
+        Limiter {
+            start: Instant::now(),
+            bytes: 0,
+        }
+    }
+
+    fn consume(&mut self, len: usize, rate: u64) {
+        if rate == 0 {
+            return;
+        }
+        self.bytes += len as u64;
+        let expected = Duration::from_millis(self.bytes * 1000 / rate);
+        let elapsed = self.start.elapsed();
+        if expected > elapsed {
+            thread::sleep(expected - elapsed);
+        }
+    }
+}
+
+struct SSHCaptureWorker {
+    arg: Arg,
+    child: Child,
+    reader: BufReader<ChildStdout>,
+    le: bool,
+    nsec: bool,
+    link_class: Fixed<LayerClass>,
+    attempts: u32,
+    limiter: Limiter,
+}
+
+impl SSHCaptureWorker {
+    fn u32(&self, data: &[u8]) -> u32 {
+        if self.le {
+            LittleEndian::read_u32(data)
+        } else {
+            BigEndian::read_u32(data)
+        }
+    }
+
+    fn read_header(&mut self) -> io::Result<()> {
+        let mut header = [0u8; HEADER_LEN];
+        self.reader.read_exact(&mut header)?;
+        let (le, nsec) = match BigEndian::read_u32(&header[0..4]) {
+            0xd4c3_b2a1 => Ok((true, false)),
+            0xa1b2_c3d4 => Ok((false, false)),
+            0x4d3c_b2a1 => Ok((true, true)),
+            0xa1b2_3c4d => Ok((false, true)),
+            _ => Err(Error::new(ErrorKind::InvalidData, "wrong magic number")),
+        }?;
+        self.le = le;
+        self.nsec = nsec;
+        let network = self.u32(&header[20..24]);
+        self.link_class = Fixed::new(layer_class!(
+            format!("[link-{}]", network),
+            header: attr!(&TYPE_CLASS, value: i64::from(network))
+        ));
+        Ok(())
+    }
+
+    fn read_one(&mut self) -> io::Result<Layer> {
+        let mut header = [0u8; RECORD_HEADER_LEN];
+        self.reader.read_exact(&mut header)?;
+        let ts_sec = self.u32(&header[0..4]);
+        let mut ts_usec = self.u32(&header[4..8]);
+        let inc_len = self.u32(&header[8..12]);
+        let orig_len = self.u32(&header[12..16]);
+
+        if !self.nsec {
+            ts_usec *= 1000;
+        }
+
+        let mut data = vec![0u8; inc_len as usize];
+        self.reader.read_exact(&mut data)?;
+        self.limiter
+            .consume(RECORD_HEADER_LEN + data.len(), self.arg.bandwidth);
+
+        let mut layer = Layer::new(self.link_class.clone(), ByteSlice::from(data));
+        layer.add_attr(attr!(&LENGTH_CLASS, value: u64::from(orig_len)));
+        layer.add_attr(attr!(
+            &TS_CLASS,
+            value: f64::from(ts_sec) + f64::from(ts_usec) / 1_000_000_000f64
+        ));
+        layer.add_attr(attr!(&TS_SEC_CLASS, value: u64::from(ts_sec)));
+        layer.add_attr(attr!(&TS_USEC_CLASS, value: u64::from(ts_usec)));
+        Ok(layer)
+    }
+
+    /// Restarts the remote capture after the connection is lost.
+    fn reconnect(&mut self) -> io::Result<()> {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        self.attempts += 1;
+        thread::sleep(Duration::from_secs(u64::from(self.attempts)));
+        let (child, reader) = self.arg.spawn()?;
+        self.child = child;
+        self.reader = reader;
+        self.read_header()
+    }
+}
+
+impl Worker for SSHCaptureWorker {
+    fn read(&mut self, _max: usize) -> Result<Vec<Layer>> {
+        loop {
+            match self.read_one() {
+                Ok(layer) => {
+                    self.attempts = 0;
+                    return Ok(vec![layer]);
+                }
+                Err(err) => {
+                    if self.attempts >= self.arg.reconnect {
+                        return Err(err.into());
+                    }
+                    if let Err(err) = self.reconnect() {
+                        if self.attempts >= self.arg.reconnect {
+                            return Err(err.into());
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Drop for SSHCaptureWorker {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+    }
+}
+
+def_attr_class!(TYPE_CLASS, "link.type");
+def_attr_class!(LENGTH_CLASS, "link.length");
+def_attr_class!(TS_CLASS, "link.timestamp",
+    typ: "@datetime:unix"
+);
+def_attr_class!(TS_SEC_CLASS, "link.timestamp.sec");
+def_attr_class!(TS_USEC_CLASS, "link.timestamp.usec");
+
+genet_readers!(SSHCaptureReader {});
@@ -1,13 +1,13 @@
 extern crate genet_sdk;
 
-use genet_sdk::{cast, decoder::*, prelude::*};
+use genet_sdk::{cast, checksum, decoder::*, prelude::*};
 
 struct TcpWorker {}
 
 impl Worker for TcpWorker {
     fn decode(
         &mut self,
-        _ctx: &mut Context,
+        ctx: &mut Context,
         _stack: &LayerStack,
         parent: &mut Parent,
     ) -> Result<Status> {
@@ -24,6 +24,9 @@ impl Worker for TcpWorker {
         }
 
         let mut layer = Layer::new(&TCP_CLASS, data);
+        if checksum::is_enabled(ctx) {
+            validate_checksum(&mut layer, parent)?;
+        }
 
         let data_offset: usize = OFFSET_ATTR_HEADER.try_get(&layer)?.try_into()?;
         let data_offset = data_offset * 4;
@@ -71,6 +74,47 @@ impl Worker for TcpWorker {
     }
 }
 
+fn validate_checksum(layer: &mut Layer, parent: &Parent) -> Result<()> {
+    let header = parent.data();
+    let data = layer.data();
+    let (mut sum, len) = if parent.id() == token!("ipv4") {
+        let total: usize = ((header.try_get(2)? as usize) << 8) | header.try_get(3)? as usize;
+        let hlen = (header.try_get(0)? & 0x0f) as usize * 4;
+        let len = total.saturating_sub(hlen);
+        let sum = checksum::Internet::ipv4_pseudo_header(
+            &header.try_get(12..16)?,
+            &header.try_get(16..20)?,
+            6,
+            len as u16,
+        );
+        (sum, len)
+    } else if parent.id() == token!("ipv6") {
+        let sum = checksum::Internet::ipv6_pseudo_header(
+            &header.try_get(8..24)?,
+            &header.try_get(24..40)?,
+            6,
+            data.len() as u32,
+        );
+        (sum, data.len())
+    } else {
+        return Ok(());
+    };
+
+    // Skip segments truncated by the snapshot length.
+    if len < 20 || len > data.len() {
+        return Ok(());
+    }
+    let segment = data.try_get(0..len)?;
+    sum.update(&segment[..16]);
+    sum.update(&segment[18..]);
+    let expected = sum.finish();
+    let actual = (u16::from(segment[16]) << 8) | u16::from(segment[17]);
+    if actual != expected {
+        layer.annotate(checksum::bad_checksum("tcp.checksum", u64::from(expected)));
+    }
+    Ok(())
+}
+
 #[derive(Clone)]
 struct TcpDecoder {}
 
@@ -81,7 +125,11 @@ impl Decoder for TcpDecoder {
 
     fn metadata(&self) -> Metadata {
         Metadata {
+            id: "app.genet.decoder.tcp".into(),
+            name: "TCP".into(),
+            description: "Transmission Control Protocol".into(),
             exec_type: ExecType::ParallelSync,
+            references: vec!["https://tools.ietf.org/html/rfc793".into()],
             ..Metadata::default()
         }
     }
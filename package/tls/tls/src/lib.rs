@@ -0,0 +1,573 @@
+extern crate genet_sdk;
+
+use genet_sdk::{cast, context::state_key, decoder::*, prelude::*, tcp::PduBuffer};
+use std::io;
+
+const RECORD_HEADER_LEN: usize = 5;
+const MAX_RECORD_LEN: usize = (1 << 14) + 2048;
+
+const CHANGE_CIPHER_SPEC: u8 = 20;
+const ALERT: u8 = 21;
+const HANDSHAKE: u8 = 22;
+const APPLICATION_DATA: u8 = 23;
+const HEARTBEAT: u8 = 24;
+
+const CLIENT_HELLO: u8 = 1;
+const SERVER_HELLO: u8 = 2;
+const CERTIFICATE: u8 = 11;
+
+const EXT_SERVER_NAME: usize = 0;
+const EXT_ALPN: usize = 16;
+const EXT_SUPPORTED_VERSIONS: usize = 43;
+
+const TLS_1_2: usize = 0x0303;
+
+/// The state of one direction of a TCP connection.
+struct Stream {
+    tls: bool,
+    buffer: PduBuffer,
+    encrypted: bool,
+}
+
+struct TlsWorker {}
+
+impl Worker for TlsWorker {
+    fn decode(
+        &mut self,
+        ctx: &mut Context,
+        stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        if parent.id() == token!("quic") {
+            return decode_quic(parent);
+        }
+        if parent.id() != token!("tcp") {
+            return Ok(Status::Skip);
+        }
+
+        let data;
+
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@stream:tcp"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let key = match stream_key(stack, parent)? {
+            Some(key) => key,
+            None => return Ok(Status::Skip),
+        };
+
+        // The first data of the stream determines whether the stream is TLS.
+        let stream = ctx.state_or_insert_with(key, || Stream {
+            tls: is_record(&data),
+            buffer: PduBuffer::new(),
+            encrypted: false,
+        });
+        if !stream.tls {
+            return Ok(Status::Skip);
+        }
+
+        for record in stream.buffer.push(data, record_len) {
+            let mut layer = Layer::new(&TLS_CLASS, record);
+            decode_record(&mut layer, stream)?;
+            parent.add_child(layer);
+        }
+        Ok(Status::Done)
+    }
+}
+
+/// Decodes the handshake messages in QUIC CRYPTO frames,
+/// which are carried without the record layer.
+fn decode_quic(parent: &mut Parent) -> Result<Status> {
+    let data;
+
+    if let Some(payload) = parent
+        .payloads()
+        .iter()
+        .find(|p| p.id() == token!("@crypto:quic"))
+    {
+        data = payload.data();
+    } else {
+        return Ok(Status::Skip);
+    }
+
+    let mut layer = Layer::new(&HANDSHAKE_CLASS, data);
+    decode_handshakes(&mut layer, &data, 0);
+    parent.add_child(layer);
+    Ok(Status::Done)
+}
+
+/// Returns the key of the state for the direction of the TCP segment.
+fn stream_key(stack: &LayerStack, parent: &Parent) -> Result<Option<u64>> {
+    let ip = match stack.parent(parent) {
+        Some(ip) => ip,
+        None => return Ok(None),
+    };
+    let addr = |id| {
+        ip.attr(id)
+            .and_then(|attr| ip.data().try_get(attr.range()).ok())
+    };
+    let (src, dst) = match (addr(token!("_.src")), addr(token!("_.dst"))) {
+        (Some(src), Some(dst)) => (src, dst),
+        _ => return Ok(None),
+    };
+    let src_port: u16 = parent
+        .attr(token!("tcp.src"))
+        .unwrap()
+        .try_get(parent)?
+        .try_into()?;
+    let dst_port: u16 = parent
+        .attr(token!("tcp.dst"))
+        .unwrap()
+        .try_get(parent)?
+        .try_into()?;
+    Ok(Some(state_key(&(&src[..], src_port, &dst[..], dst_port))))
+}
+
+fn is_record(data: &[u8]) -> bool {
+    data.len() >= 3
+        && data[0] >= CHANGE_CIPHER_SPEC
+        && data[0] <= HEARTBEAT
+        && data[1] == 3
+        && data[2] <= 4
+}
+
+fn record_len(data: &[u8]) -> Option<usize> {
+    if data.len() < RECORD_HEADER_LEN {
+        None
+    } else if !is_record(data) {
+        // Lost synchronization with the record boundaries.
+        Some(0)
+    } else {
+        Some(RECORD_HEADER_LEN + ((data[3] as usize) << 8 | data[4] as usize))
+    }
+}
+
+fn u16_at(data: &ByteSlice, pos: usize) -> io::Result<usize> {
+    let bytes = data.try_get(pos..pos + 2)?;
+    Ok((bytes[0] as usize) << 8 | bytes[1] as usize)
+}
+
+fn u24_at(data: &ByteSlice, pos: usize) -> io::Result<usize> {
+    let bytes = data.try_get(pos..pos + 3)?;
+    Ok((bytes[0] as usize) << 16 | (bytes[1] as usize) << 8 | bytes[2] as usize)
+}
+
+fn decode_record(layer: &mut Layer, stream: &mut Stream) -> Result<()> {
+    let data = layer.data();
+    let end = data.len();
+    if end - RECORD_HEADER_LEN > MAX_RECORD_LEN {
+        layer.annotate(Annotation::new(Severity::Warn, "record overflow").with_attr("tls.length"));
+    }
+    if u16_at(&data, 1)? < 0x0301 {
+        layer.annotate(
+            Annotation::new(Severity::Warn, "deprecated protocol version").with_attr("tls.version"),
+        );
+    }
+
+    match data[0] {
+        CHANGE_CIPHER_SPEC => {
+            layer.add_attr(attr!(&CHANGE_CIPHER_SPEC_ATTR, range: RECORD_HEADER_LEN..end));
+            stream.encrypted = true;
+        }
+        APPLICATION_DATA | HEARTBEAT => {
+            layer.add_attr(attr!(&ENCRYPTED_ATTR, range: RECORD_HEADER_LEN..end));
+        }
+        _ if stream.encrypted => {
+            layer.add_attr(attr!(&ENCRYPTED_ATTR, range: RECORD_HEADER_LEN..end));
+        }
+        ALERT => {
+            data.try_get(RECORD_HEADER_LEN..RECORD_HEADER_LEN + 2)?;
+            layer.add_attr(attr!(&ALERT_ATTR, range: RECORD_HEADER_LEN..end));
+            layer.add_attr(attr!(&ALERT_LEVEL_ATTR, range: 5..6));
+            layer.add_attr(attr!(&ALERT_DESCRIPTION_ATTR, range: 6..7));
+            if data[5] == 2 {
+                layer.annotate(
+                    Annotation::new(Severity::Error, "fatal alert")
+                        .with_attr("tls.alert.description"),
+                );
+            }
+        }
+        HANDSHAKE => decode_handshakes(layer, &data, RECORD_HEADER_LEN),
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+fn decode_handshakes(layer: &mut Layer, data: &ByteSlice, pos: usize) {
+    let mut pos = pos;
+    while pos < data.len() {
+        let len = match u24_at(data, pos + 1) {
+            Ok(len) => len,
+            Err(_) => {
+                layer.warn("fragmented handshake message");
+                return;
+            }
+        };
+        let end = pos + 4 + len;
+        if end > data.len() {
+            layer.warn("fragmented handshake message");
+            return;
+        }
+        layer.add_attr(attr!(&HANDSHAKE_ATTR, range: pos..end));
+        layer.add_attr(attr!(&HANDSHAKE_TYPE_ATTR, range: pos..pos + 1));
+        layer.add_attr(attr!(&HANDSHAKE_LENGTH_ATTR, range: pos + 1..pos + 4));
+        let result = match data[pos] {
+            CLIENT_HELLO => decode_hello(layer, data, pos + 4, end, true),
+            SERVER_HELLO => decode_hello(layer, data, pos + 4, end, false),
+            CERTIFICATE => decode_certificates(layer, data, pos + 4, end),
+            _ => Ok(()),
+        };
+        if result.is_err() {
+            layer.error("malformed handshake message");
+        }
+        pos = end;
+    }
+}
+
+fn decode_hello(
+    layer: &mut Layer,
+    data: &ByteSlice,
+    pos: usize,
+    end: usize,
+    client: bool,
+) -> io::Result<()> {
+    let data = data.try_get(..end)?;
+    let mut version = u16_at(&data, pos)?;
+    layer.add_attr(attr!(&HANDSHAKE_VERSION_ATTR, range: pos..pos + 2));
+    layer.add_attr(attr!(&HANDSHAKE_RANDOM_ATTR, range: pos + 2..pos + 34));
+    let mut pos = pos + 34;
+
+    let len = data.try_get(pos)? as usize;
+    data.try_get(pos + 1..pos + 1 + len)?;
+    layer.add_attr(attr!(&HANDSHAKE_SESSION_ID_ATTR, range: pos + 1..pos + 1 + len));
+    pos += 1 + len;
+
+    if client {
+        let len = u16_at(&data, pos)?;
+        data.try_get(pos + 2..pos + 2 + len)?;
+        for suite in (pos + 2..pos + 2 + len).step_by(2) {
+            layer.add_attr(attr!(&HANDSHAKE_CIPHER_SUITE_ATTR, range: suite..suite + 2));
+        }
+        pos += 2 + len;
+        let len = data.try_get(pos)? as usize;
+        data.try_get(pos + 1..pos + 1 + len)?;
+        for method in pos + 1..pos + 1 + len {
+            layer.add_attr(attr!(&HANDSHAKE_COMPRESSION_ATTR, range: method..method + 1));
+        }
+        pos += 1 + len;
+    } else {
+        data.try_get(pos..pos + 3)?;
+        layer.add_attr(attr!(&HANDSHAKE_CIPHER_SUITE_ATTR, range: pos..pos + 2));
+        layer.add_attr(attr!(&HANDSHAKE_COMPRESSION_ATTR, range: pos + 2..pos + 3));
+        pos += 3;
+    }
+
+    if pos < end {
+        let len = u16_at(&data, pos)?;
+        pos += 2;
+        let ext_end = pos + len;
+        data.try_get(pos..ext_end)?;
+        while pos < ext_end {
+            let typ = u16_at(&data, pos)?;
+            let len = u16_at(&data, pos + 2)?;
+            let body = pos + 4;
+            let next = body + len;
+            data.try_get(pos..next)?;
+            layer.add_attr(attr!(&EXTENSION_ATTR, range: pos..next));
+            layer.add_attr(attr!(&EXTENSION_TYPE_ATTR, range: pos..pos + 2));
+            match typ {
+                EXT_SERVER_NAME if client => {
+                    let mut pos = body + 2;
+                    while pos + 3 <= next {
+                        let len = u16_at(&data, pos + 1)?;
+                        if data[pos] == 0 {
+                            layer.add_attr(attr!(&SERVER_NAME_ATTR, range: pos + 3..pos + 3 + len));
+                        }
+                        pos += 3 + len;
+                    }
+                }
+                EXT_ALPN => {
+                    let mut pos = body + 2;
+                    while pos < next {
+                        let len = data.try_get(pos)? as usize;
+                        layer.add_attr(attr!(&ALPN_ATTR, range: pos + 1..pos + 1 + len));
+                        pos += 1 + len;
+                    }
+                }
+                EXT_SUPPORTED_VERSIONS if client => {
+                    for pos in (body + 1..next).step_by(2) {
+                        layer.add_attr(attr!(&SUPPORTED_VERSION_ATTR, range: pos..pos + 2));
+                    }
+                }
+                EXT_SUPPORTED_VERSIONS => {
+                    layer.add_attr(attr!(&SUPPORTED_VERSION_ATTR, range: body..body + 2));
+                    version = u16_at(&data, body)?;
+                }
+                _ => {}
+            }
+            pos = next;
+        }
+    }
+
+    if !client && version < TLS_1_2 {
+        layer.annotate(
+            Annotation::new(Severity::Warn, "deprecated protocol version")
+                .with_attr("tls.handshake.version"),
+        );
+    }
+    Ok(())
+}
+
+fn decode_certificates(
+    layer: &mut Layer,
+    data: &ByteSlice,
+    pos: usize,
+    end: usize,
+) -> io::Result<()> {
+    let data = data.try_get(..end)?;
+    let list_end = pos + 3 + u24_at(&data, pos)?;
+    data.try_get(pos..list_end)?;
+    let mut pos = pos + 3;
+    while pos < list_end {
+        let len = u24_at(&data, pos)?;
+        data.try_get(pos + 3..pos + 3 + len)?;
+        layer.add_attr(attr!(&CERTIFICATE_ATTR, range: pos + 3..pos + 3 + len));
+        pos += 3 + len;
+    }
+    Ok(())
+}
+
+#[derive(Clone)]
+struct TlsDecoder {}
+
+impl Decoder for TlsDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(TlsWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.decoder.tls".into(),
+            name: "TLS".into(),
+            description: "Transport Layer Security".into(),
+            exec_type: ExecType::SerialSync,
+            references: vec![
+                "https://tools.ietf.org/html/rfc5246".into(),
+                "https://tools.ietf.org/html/rfc8446".into(),
+            ],
+            ..Metadata::default()
+        }
+    }
+}
+
+fn version_labels() -> Vec<(u64, &'static str)> {
+    vec![
+        (0x0300, "SSL 3.0"),
+        (0x0301, "TLS 1.0"),
+        (0x0302, "TLS 1.1"),
+        (0x0303, "TLS 1.2"),
+        (0x0304, "TLS 1.3"),
+    ]
+}
+
+def_layer_class!(TLS_CLASS, "tls",
+    header: attr!(&TYPE_ATTR, range: 0..1),
+    header: attr!(&VERSION_ATTR, range: 1..3),
+    header: attr!(&LENGTH_ATTR, range: 3..5)
+);
+
+def_layer_class!(HANDSHAKE_CLASS, "tls");
+
+def_attr_class!(TYPE_ATTR, "tls.type",
+    typ: "@enum",
+    cast: cast::UInt8(),
+    labels: vec![
+        (20, "ChangeCipherSpec"),
+        (21, "Alert"),
+        (22, "Handshake"),
+        (23, "ApplicationData"),
+        (24, "Heartbeat"),
+    ]
+);
+
+def_attr_class!(VERSION_ATTR, "tls.version",
+    typ: "@enum",
+    cast: cast::UInt16BE(),
+    labels: version_labels()
+);
+
+def_attr_class!(LENGTH_ATTR, "tls.length", cast: cast::UInt16BE());
+
+def_attr_class!(CHANGE_CIPHER_SPEC_ATTR, "tls.changeCipherSpec",
+    typ: "@novalue",
+    value: true
+);
+
+def_attr_class!(ENCRYPTED_ATTR, "tls.encryptedData", cast: cast::ByteSlice());
+
+def_attr_class!(ALERT_ATTR, "tls.alert",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(ALERT_LEVEL_ATTR, "tls.alert.level",
+    typ: "@enum",
+    cast: cast::UInt8(),
+    labels: vec![(1, "Warning"), (2, "Fatal")]
+);
+
+def_attr_class!(ALERT_DESCRIPTION_ATTR, "tls.alert.description",
+    typ: "@enum",
+    cast: cast::UInt8(),
+    labels: vec![
+        (0, "close_notify"),
+        (10, "unexpected_message"),
+        (20, "bad_record_mac"),
+        (22, "record_overflow"),
+        (40, "handshake_failure"),
+        (42, "bad_certificate"),
+        (43, "unsupported_certificate"),
+        (44, "certificate_revoked"),
+        (45, "certificate_expired"),
+        (46, "certificate_unknown"),
+        (47, "illegal_parameter"),
+        (48, "unknown_ca"),
+        (49, "access_denied"),
+        (50, "decode_error"),
+        (51, "decrypt_error"),
+        (70, "protocol_version"),
+        (71, "insufficient_security"),
+        (80, "internal_error"),
+        (86, "inappropriate_fallback"),
+        (90, "user_canceled"),
+        (109, "missing_extension"),
+        (110, "unsupported_extension"),
+        (112, "unrecognized_name"),
+        (116, "certificate_required"),
+        (120, "no_application_protocol"),
+    ]
+);
+
+def_attr_class!(HANDSHAKE_ATTR, "tls.handshake",
+    typ: "@nested",
+    repeated: ,
+    value: true
+);
+
+def_attr_class!(HANDSHAKE_TYPE_ATTR, "tls.handshake.type",
+    typ: "@enum",
+    cast: cast::UInt8(),
+    labels: vec![
+        (0, "HelloRequest"),
+        (1, "ClientHello"),
+        (2, "ServerHello"),
+        (4, "NewSessionTicket"),
+        (8, "EncryptedExtensions"),
+        (11, "Certificate"),
+        (12, "ServerKeyExchange"),
+        (13, "CertificateRequest"),
+        (14, "ServerHelloDone"),
+        (15, "CertificateVerify"),
+        (16, "ClientKeyExchange"),
+        (20, "Finished"),
+    ]
+);
+
+def_attr_class!(HANDSHAKE_LENGTH_ATTR, "tls.handshake.length",
+    cast: cast::ByteSlice().map(|v| u32::from(v[0]) << 16 | u32::from(v[1]) << 8 | u32::from(v[2]))
+);
+
+def_attr_class!(HANDSHAKE_VERSION_ATTR, "tls.handshake.version",
+    typ: "@enum",
+    cast: cast::UInt16BE(),
+    labels: version_labels()
+);
+
+def_attr_class!(HANDSHAKE_RANDOM_ATTR, "tls.handshake.random", cast: cast::ByteSlice());
+
+def_attr_class!(HANDSHAKE_SESSION_ID_ATTR, "tls.handshake.sessionId", cast: cast::ByteSlice());
+
+def_attr_class!(HANDSHAKE_CIPHER_SUITE_ATTR, "tls.handshake.cipherSuite",
+    typ: "@enum",
+    repeated: ,
+    cast: cast::UInt16BE(),
+    labels: vec![
+        (0x000a, "TLS_RSA_WITH_3DES_EDE_CBC_SHA"),
+        (0x002f, "TLS_RSA_WITH_AES_128_CBC_SHA"),
+        (0x0035, "TLS_RSA_WITH_AES_256_CBC_SHA"),
+        (0x009c, "TLS_RSA_WITH_AES_128_GCM_SHA256"),
+        (0x009d, "TLS_RSA_WITH_AES_256_GCM_SHA384"),
+        (0x00ff, "TLS_EMPTY_RENEGOTIATION_INFO_SCSV"),
+        (0x1301, "TLS_AES_128_GCM_SHA256"),
+        (0x1302, "TLS_AES_256_GCM_SHA384"),
+        (0x1303, "TLS_CHACHA20_POLY1305_SHA256"),
+        (0xc013, "TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA"),
+        (0xc014, "TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA"),
+        (0xc02b, "TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256"),
+        (0xc02c, "TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384"),
+        (0xc02f, "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256"),
+        (0xc030, "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384"),
+        (0xcca8, "TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256"),
+        (0xcca9, "TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256"),
+    ]
+);
+
+def_attr_class!(HANDSHAKE_COMPRESSION_ATTR, "tls.handshake.compressionMethod",
+    typ: "@enum",
+    repeated: ,
+    cast: cast::UInt8(),
+    labels: vec![(0, "null"), (1, "DEFLATE")]
+);
+
+def_attr_class!(EXTENSION_ATTR, "tls.handshake.extension",
+    typ: "@nested",
+    repeated: ,
+    value: true
+);
+
+def_attr_class!(EXTENSION_TYPE_ATTR, "tls.handshake.extension.type",
+    typ: "@enum",
+    cast: cast::UInt16BE(),
+    labels: vec![
+        (0, "server_name"),
+        (5, "status_request"),
+        (10, "supported_groups"),
+        (11, "ec_point_formats"),
+        (13, "signature_algorithms"),
+        (16, "application_layer_protocol_negotiation"),
+        (23, "extended_master_secret"),
+        (35, "session_ticket"),
+        (41, "pre_shared_key"),
+        (43, "supported_versions"),
+        (45, "psk_key_exchange_modes"),
+        (51, "key_share"),
+        (65281, "renegotiation_info"),
+    ]
+);
+
+def_attr_class!(SERVER_NAME_ATTR, "tls.handshake.extension.serverName", cast: cast::Utf8());
+
+def_attr_class!(ALPN_ATTR, "tls.handshake.extension.alpn",
+    repeated: ,
+    cast: cast::Utf8()
+);
+
+def_attr_class!(SUPPORTED_VERSION_ATTR, "tls.handshake.extension.supportedVersion",
+    typ: "@enum",
+    repeated: ,
+    cast: cast::UInt16BE(),
+    labels: version_labels()
+);
+
+def_attr_class!(CERTIFICATE_ATTR, "tls.handshake.certificate",
+    repeated: ,
+    cast: cast::ByteSlice()
+);
+
+genet_decoders!(TlsDecoder {});
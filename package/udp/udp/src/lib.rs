@@ -24,6 +24,15 @@ impl Worker for UdpWorker {
         }
 
         let mut layer = Layer::new(&UDP_CLASS, data);
+        let len: usize = match layer.attr(token!("udp.length")) {
+            Some(attr) => attr.try_get(&layer)?.try_into()?,
+            None => data.len(),
+        };
+        if len != data.len() {
+            layer.annotate(
+                Annotation::new(Severity::Warn, "length field mismatch").with_attr("udp.length"),
+            );
+        }
         let payload = data.try_get(8..)?;
         layer.add_payload(Payload::new(payload, ""));
 
@@ -42,7 +51,11 @@ impl Decoder for UdpDecoder {
 
     fn metadata(&self) -> Metadata {
         Metadata {
+            id: "app.genet.decoder.udp".into(),
+            name: "UDP".into(),
+            description: "User Datagram Protocol".into(),
             exec_type: ExecType::ParallelSync,
+            references: vec!["https://tools.ietf.org/html/rfc768".into()],
             ..Metadata::default()
         }
     }
@@ -0,0 +1,95 @@
+extern crate genet_sdk;
+
+use genet_sdk::{cast, decoder::*, prelude::*};
+
+const PORT: u16 = 4789;
+const HEADER_LEN: usize = 8;
+
+struct VxlanWorker {}
+
+impl Worker for VxlanWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        if parent.id() != token!("udp") {
+            return Ok(Status::Skip);
+        }
+
+        let data;
+
+        if let Some(payload) = parent.payloads().iter().next() {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let mut layer = Layer::new(&VXLAN_CLASS, data);
+        if data.len() < HEADER_LEN {
+            layer.error("truncated header");
+            parent.add_child(layer);
+            return Ok(Status::Done);
+        }
+
+        let valid: bool = FLAGS_VNI_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        if !valid {
+            layer.annotate(
+                Annotation::new(Severity::Warn, "VNI flag is not set").with_attr("vxlan.flags.vni"),
+            );
+        }
+
+        let payload = data.try_get(HEADER_LEN..)?;
+        layer.add_payload(Payload::new(payload, "@data:eth"));
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct VxlanDecoder {}
+
+impl Decoder for VxlanDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(VxlanWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.decoder.vxlan".into(),
+            name: "VXLAN".into(),
+            description: "Virtual eXtensible Local Area Network".into(),
+            exec_type: ExecType::ParallelSync,
+            // The source port is derived from a hash of the inner frame.
+            triggers: vec![Trigger::new("udp.dst", PORT.into())],
+            references: vec!["https://tools.ietf.org/html/rfc7348".into()],
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(VXLAN_CLASS, "vxlan",
+    header: attr!(&FLAGS_ATTR, range: 0..1),
+    header: &FLAGS_VNI_ATTR_HEADER,
+    header: attr!(&VNI_ATTR, range: 4..7)
+);
+
+def_attr!(FLAGS_VNI_ATTR_HEADER, &FLAGS_VNI_ATTR, bit_range: 0 4..5);
+
+def_attr_class!(FLAGS_ATTR, "vxlan.flags",
+    typ: "@flags",
+    cast: cast::UInt8()
+);
+
+def_attr_class!(FLAGS_VNI_ATTR, "vxlan.flags.vni",
+    cast: cast::UInt8().map(|v| v & 0b00001000 != 0)
+);
+
+def_attr_class!(VNI_ATTR, "vxlan.vni",
+    cast: cast::ByteSlice()
+        .map(|v| ((v[0] as u32) << 16) | ((v[1] as u32) << 8) | v[2] as u32)
+);
+
+genet_decoders!(VxlanDecoder {});
@@ -0,0 +1,533 @@
+extern crate aes;
+extern crate genet_sdk;
+extern crate ring;
+
+mod radiotap;
+mod wpa;
+
+use genet_sdk::{cast, decoder::*, prelude::*};
+
+const MANAGEMENT: u8 = 0;
+const CONTROL: u8 = 1;
+const DATA: u8 = 2;
+
+const BEACON: u8 = 8;
+const PROBE_REQUEST: u8 = 4;
+const PROBE_RESPONSE: u8 = 5;
+const ASSOCIATION_REQUEST: u8 = 0;
+const ASSOCIATION_RESPONSE: u8 = 1;
+const REASSOCIATION_REQUEST: u8 = 2;
+const REASSOCIATION_RESPONSE: u8 = 3;
+const DISASSOCIATION: u8 = 10;
+const AUTHENTICATION: u8 = 11;
+const DEAUTHENTICATION: u8 = 12;
+
+const CTS: u8 = 12;
+const ACK: u8 = 13;
+
+const QOS: u8 = 0b1000;
+const NO_DATA: u8 = 0b0100;
+
+const FLAG_TO_DS: u8 = 0b0000_0001;
+const FLAG_FROM_DS: u8 = 0b0000_0010;
+const FLAG_PROTECTED: u8 = 0b0100_0000;
+const FLAG_ORDER: u8 = 0b1000_0000;
+
+const TAG_SSID: u8 = 0;
+const TAG_DS_PARAMETER: u8 = 3;
+
+const LLC_SNAP: [u8; 3] = [0xaa, 0xaa, 0x03];
+const LLC_SNAP_LEN: usize = 8;
+
+struct WlanWorker {}
+
+impl Worker for WlanWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let data;
+
+        if parent.id() == token!("[link-105]") {
+            data = parent.data();
+        } else if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:wlan"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let mut layer = Layer::new(&WLAN_CLASS, data);
+        if data.len() < 10 {
+            layer.error("truncated header");
+            parent.add_child(layer);
+            return Ok(Status::Done);
+        }
+
+        let typ = (data[0] >> 2) & 0b11;
+        let subtype = data[0] >> 4;
+        let flags = data[1];
+
+        let len = match typ {
+            MANAGEMENT => decode_management_header(&mut layer),
+            CONTROL => decode_control_header(&mut layer, subtype),
+            DATA => decode_data_header(&mut layer, subtype, flags),
+            _ => None,
+        };
+        let len = match len {
+            Some(len) => len,
+            None => {
+                layer.error("truncated header");
+                parent.add_child(layer);
+                return Ok(Status::Done);
+            }
+        };
+
+        let body = data.try_get(len..)?;
+        if typ == MANAGEMENT {
+            decode_management_body(&mut layer, subtype, len);
+        } else if typ == DATA && subtype & NO_DATA == 0 && !body.is_empty() {
+            if flags & FLAG_PROTECTED != 0 {
+                layer.add_payload(Payload::new(body, "@protected:wlan"));
+            } else if body.len() >= LLC_SNAP_LEN && body[0..3] == LLC_SNAP {
+                layer.add_attr(attr!(&LLC_ATTR, range: len..len + 3));
+                layer.add_attr(attr!(&SNAP_OUI_ATTR, range: len + 3..len + 6));
+                layer.add_attr(attr!(&SNAP_TYPE_ATTR, range: len + 6..len + 8));
+                let ethertype = (body[6] as u64) << 8 | body[7] as u64;
+                if let Some(typ) = get_type(ethertype) {
+                    let payload = body.try_get(LLC_SNAP_LEN..)?;
+                    layer.add_payload(Payload::new(payload, typ));
+                }
+            }
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+/// Adds the addresses and the sequence control, and returns the header length.
+fn decode_management_header(layer: &mut Layer) -> Option<usize> {
+    if layer.data().len() < 24 {
+        return None;
+    }
+    layer.add_attr(attr!(&RA_ATTR, range: 4..10));
+    layer.add_attr(attr!(&DA_ATTR, range: 4..10));
+    layer.add_attr(attr!(&TA_ATTR, range: 10..16));
+    layer.add_attr(attr!(&SA_ATTR, range: 10..16));
+    layer.add_attr(attr!(&BSSID_ATTR, range: 16..22));
+    layer.add_attr(attr!(&FRAGMENT_ATTR, range: 22..24));
+    layer.add_attr(attr!(&SEQUENCE_ATTR, range: 22..24));
+    Some(24)
+}
+
+fn decode_control_header(layer: &mut Layer, subtype: u8) -> Option<usize> {
+    layer.add_attr(attr!(&RA_ATTR, range: 4..10));
+    if subtype == CTS || subtype == ACK {
+        return Some(10);
+    }
+    if layer.data().len() < 16 {
+        return None;
+    }
+    layer.add_attr(attr!(&TA_ATTR, range: 10..16));
+    Some(16)
+}
+
+fn decode_data_header(layer: &mut Layer, subtype: u8, flags: u8) -> Option<usize> {
+    let ds = flags & (FLAG_TO_DS | FLAG_FROM_DS);
+    let mut len = 24;
+    if ds == FLAG_TO_DS | FLAG_FROM_DS {
+        len += 6;
+    }
+    if subtype & QOS != 0 {
+        len += 2;
+        if flags & FLAG_ORDER != 0 {
+            len += 4;
+        }
+    }
+    if layer.data().len() < len {
+        return None;
+    }
+
+    layer.add_attr(attr!(&RA_ATTR, range: 4..10));
+    layer.add_attr(attr!(&TA_ATTR, range: 10..16));
+    match ds {
+        0 => {
+            layer.add_attr(attr!(&DA_ATTR, range: 4..10));
+            layer.add_attr(attr!(&SA_ATTR, range: 10..16));
+            layer.add_attr(attr!(&BSSID_ATTR, range: 16..22));
+        }
+        FLAG_TO_DS => {
+            layer.add_attr(attr!(&BSSID_ATTR, range: 4..10));
+            layer.add_attr(attr!(&SA_ATTR, range: 10..16));
+            layer.add_attr(attr!(&DA_ATTR, range: 16..22));
+        }
+        FLAG_FROM_DS => {
+            layer.add_attr(attr!(&DA_ATTR, range: 4..10));
+            layer.add_attr(attr!(&BSSID_ATTR, range: 10..16));
+            layer.add_attr(attr!(&SA_ATTR, range: 16..22));
+        }
+        _ => {
+            layer.add_attr(attr!(&DA_ATTR, range: 16..22));
+            layer.add_attr(attr!(&SA_ATTR, range: 24..30));
+        }
+    }
+    layer.add_attr(attr!(&FRAGMENT_ATTR, range: 22..24));
+    layer.add_attr(attr!(&SEQUENCE_ATTR, range: 22..24));
+
+    if subtype & QOS != 0 {
+        let pos = if ds == FLAG_TO_DS | FLAG_FROM_DS {
+            30
+        } else {
+            24
+        };
+        layer.add_attr(attr!(&QOS_TID_ATTR, bit_range: pos 4..8));
+    }
+    Some(len)
+}
+
+/// Decodes the fixed parameters and the tagged parameters.
+fn decode_management_body(layer: &mut Layer, subtype: u8, pos: usize) {
+    let data = layer.data();
+    let fixed_len = match subtype {
+        BEACON | PROBE_RESPONSE => 12,
+        ASSOCIATION_REQUEST => 4,
+        ASSOCIATION_RESPONSE | REASSOCIATION_RESPONSE => 6,
+        REASSOCIATION_REQUEST => 10,
+        AUTHENTICATION => 6,
+        DISASSOCIATION | DEAUTHENTICATION => 2,
+        PROBE_REQUEST => 0,
+        _ => return,
+    };
+    if data.len() < pos + fixed_len {
+        layer.error("truncated fixed parameters");
+        return;
+    }
+
+    match subtype {
+        BEACON | PROBE_RESPONSE => {
+            layer.add_attr(attr!(&TIMESTAMP_ATTR, range: pos..pos + 8));
+            layer.add_attr(attr!(&BEACON_INTERVAL_ATTR, range: pos + 8..pos + 10));
+            layer.add_attr(attr!(&CAPABILITY_ATTR, range: pos + 10..pos + 12));
+        }
+        ASSOCIATION_REQUEST | REASSOCIATION_REQUEST => {
+            layer.add_attr(attr!(&CAPABILITY_ATTR, range: pos..pos + 2));
+            layer.add_attr(attr!(&LISTEN_INTERVAL_ATTR, range: pos + 2..pos + 4));
+            if subtype == REASSOCIATION_REQUEST {
+                layer.add_attr(attr!(&CURRENT_AP_ATTR, range: pos + 4..pos + 10));
+            }
+        }
+        ASSOCIATION_RESPONSE | REASSOCIATION_RESPONSE => {
+            layer.add_attr(attr!(&CAPABILITY_ATTR, range: pos..pos + 2));
+            layer.add_attr(attr!(&STATUS_ATTR, range: pos + 2..pos + 4));
+            layer.add_attr(attr!(&AID_ATTR, range: pos + 4..pos + 6));
+        }
+        AUTHENTICATION => {
+            layer.add_attr(attr!(&AUTH_ALGORITHM_ATTR, range: pos..pos + 2));
+            layer.add_attr(attr!(&AUTH_SEQUENCE_ATTR, range: pos + 2..pos + 4));
+            layer.add_attr(attr!(&STATUS_ATTR, range: pos + 4..pos + 6));
+        }
+        DISASSOCIATION | DEAUTHENTICATION => {
+            layer.add_attr(attr!(&REASON_ATTR, range: pos..pos + 2));
+            return;
+        }
+        _ => {}
+    }
+
+    let mut pos = pos + fixed_len;
+    while pos < data.len() {
+        if pos + 2 > data.len() || pos + 2 + data[pos + 1] as usize > data.len() {
+            layer.error("malformed tagged parameter");
+            return;
+        }
+        let number = data[pos];
+        let end = pos + 2 + data[pos + 1] as usize;
+        layer.add_attr(attr!(&TAG_ATTR, range: pos..end));
+        layer.add_attr(attr!(&TAG_NUMBER_ATTR, range: pos..pos + 1));
+        layer.add_attr(attr!(&TAG_LENGTH_ATTR, range: pos + 1..pos + 2));
+        layer.add_attr(attr!(&TAG_DATA_ATTR, range: pos + 2..end));
+        match number {
+            TAG_SSID => layer.add_attr(attr!(&SSID_ATTR, range: pos + 2..end)),
+            TAG_DS_PARAMETER if end == pos + 3 => {
+                layer.add_attr(attr!(&CHANNEL_ATTR, range: pos + 2..end))
+            }
+            _ => {}
+        }
+        pos = end;
+    }
+}
+
+fn get_type(val: u64) -> Option<Token> {
+    match val {
+        0x0800 => Some(token!("@data:ipv4")),
+        0x0806 => Some(token!("@data:arp")),
+        0x8100 => Some(token!("@data:vlan")),
+        0x86DD => Some(token!("@data:ipv6")),
+        0x888E => Some(token!("@data:eap")),
+        _ => None,
+    }
+}
+
+#[derive(Clone)]
+struct WlanDecoder {}
+
+impl Decoder for WlanDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(WlanWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.decoder.wlan".into(),
+            name: "IEEE 802.11".into(),
+            description: "IEEE 802.11 wireless LAN frame".into(),
+            exec_type: ExecType::ParallelSync,
+            references: vec!["https://standards.ieee.org/standard/802_11-2016.html".into()],
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(WLAN_CLASS, "wlan",
+    alias: "_.src" "wlan.ta",
+    alias: "_.dst" "wlan.ra",
+    header: attr!(&SUBTYPE_ATTR, range: 0..1),
+    header: attr!(&TYPE_ATTR, bit_range: 0 4..6),
+    header: attr!(&VERSION_ATTR, bit_range: 0 6..8),
+    header: attr!(&FLAGS_ATTR, range: 1..2),
+    header: attr!(&FLAGS_ORDER_ATTR, bit_range: 1 0..1),
+    header: attr!(&FLAGS_PROTECTED_ATTR, bit_range: 1 1..2),
+    header: attr!(&FLAGS_MORE_DATA_ATTR, bit_range: 1 2..3),
+    header: attr!(&FLAGS_POWER_MANAGEMENT_ATTR, bit_range: 1 3..4),
+    header: attr!(&FLAGS_RETRY_ATTR, bit_range: 1 4..5),
+    header: attr!(&FLAGS_MORE_FRAGMENTS_ATTR, bit_range: 1 5..6),
+    header: attr!(&FLAGS_FROM_DS_ATTR, bit_range: 1 6..7),
+    header: attr!(&FLAGS_TO_DS_ATTR, bit_range: 1 7..8),
+    header: attr!(&DURATION_ATTR, range: 2..4)
+);
+
+def_attr_class!(TYPE_ATTR, "wlan.type",
+    typ: "@enum",
+    cast: cast::UInt8().map(|v| (v >> 2) & 0b11),
+    labels: vec![(0, "Management"), (1, "Control"), (2, "Data"), (3, "Extension")]
+);
+
+def_attr_class!(SUBTYPE_ATTR, "wlan.subtype",
+    typ: "@enum",
+    cast: cast::UInt8().map(|v| ((v >> 2) & 0b11) << 4 | v >> 4),
+    labels: vec![
+        (0x00, "Association Request"),
+        (0x01, "Association Response"),
+        (0x02, "Reassociation Request"),
+        (0x03, "Reassociation Response"),
+        (0x04, "Probe Request"),
+        (0x05, "Probe Response"),
+        (0x06, "Timing Advertisement"),
+        (0x08, "Beacon"),
+        (0x09, "ATIM"),
+        (0x0a, "Disassociation"),
+        (0x0b, "Authentication"),
+        (0x0c, "Deauthentication"),
+        (0x0d, "Action"),
+        (0x0e, "Action No Ack"),
+        (0x14, "Beamforming Report Poll"),
+        (0x15, "VHT NDP Announcement"),
+        (0x17, "Control Wrapper"),
+        (0x18, "Block Ack Request"),
+        (0x19, "Block Ack"),
+        (0x1a, "PS-Poll"),
+        (0x1b, "RTS"),
+        (0x1c, "CTS"),
+        (0x1d, "ACK"),
+        (0x1e, "CF-End"),
+        (0x1f, "CF-End + CF-Ack"),
+        (0x20, "Data"),
+        (0x24, "Null"),
+        (0x28, "QoS Data"),
+        (0x2c, "QoS Null"),
+    ]
+);
+
+def_attr_class!(VERSION_ATTR, "wlan.version",
+    cast: cast::UInt8().map(|v| v & 0b11)
+);
+
+def_attr_class!(FLAGS_ATTR, "wlan.flags",
+    typ: "@flags",
+    cast: cast::UInt8()
+);
+
+def_attr_class!(FLAGS_ORDER_ATTR, "wlan.flags.order",
+    cast: cast::UInt8().map(|v| v & 0b10000000 != 0)
+);
+
+def_attr_class!(FLAGS_PROTECTED_ATTR, "wlan.flags.protected",
+    cast: cast::UInt8().map(|v| v & 0b01000000 != 0)
+);
+
+def_attr_class!(FLAGS_MORE_DATA_ATTR, "wlan.flags.moreData",
+    cast: cast::UInt8().map(|v| v & 0b00100000 != 0)
+);
+
+def_attr_class!(FLAGS_POWER_MANAGEMENT_ATTR, "wlan.flags.powerManagement",
+    cast: cast::UInt8().map(|v| v & 0b00010000 != 0)
+);
+
+def_attr_class!(FLAGS_RETRY_ATTR, "wlan.flags.retry",
+    cast: cast::UInt8().map(|v| v & 0b00001000 != 0)
+);
+
+def_attr_class!(FLAGS_MORE_FRAGMENTS_ATTR, "wlan.flags.moreFragments",
+    cast: cast::UInt8().map(|v| v & 0b00000100 != 0)
+);
+
+def_attr_class!(FLAGS_FROM_DS_ATTR, "wlan.flags.fromDs",
+    cast: cast::UInt8().map(|v| v & 0b00000010 != 0)
+);
+
+def_attr_class!(FLAGS_TO_DS_ATTR, "wlan.flags.toDs",
+    cast: cast::UInt8().map(|v| v & 0b00000001 != 0)
+);
+
+def_attr_class!(DURATION_ATTR, "wlan.duration", cast: cast::UInt16LE());
+
+def_attr_class!(RA_ATTR, "wlan.ra",
+    typ: "@eth:mac",
+    cast: cast::MacAddr()
+);
+
+def_attr_class!(TA_ATTR, "wlan.ta",
+    typ: "@eth:mac",
+    cast: cast::MacAddr()
+);
+
+def_attr_class!(DA_ATTR, "wlan.da",
+    typ: "@eth:mac",
+    cast: cast::MacAddr()
+);
+
+def_attr_class!(SA_ATTR, "wlan.sa",
+    typ: "@eth:mac",
+    cast: cast::MacAddr()
+);
+
+def_attr_class!(BSSID_ATTR, "wlan.bssid",
+    typ: "@eth:mac",
+    cast: cast::MacAddr()
+);
+
+def_attr_class!(FRAGMENT_ATTR, "wlan.fragment",
+    cast: cast::UInt16LE().map(|v| v & 0x000f)
+);
+
+def_attr_class!(SEQUENCE_ATTR, "wlan.sequence",
+    cast: cast::UInt16LE().map(|v| v >> 4)
+);
+
+def_attr_class!(QOS_TID_ATTR, "wlan.qos.tid",
+    cast: cast::UInt8().map(|v| v & 0b00001111)
+);
+
+def_attr_class!(TIMESTAMP_ATTR, "wlan.timestamp", cast: cast::UInt64LE());
+
+def_attr_class!(BEACON_INTERVAL_ATTR, "wlan.beaconInterval", cast: cast::UInt16LE());
+
+def_attr_class!(CAPABILITY_ATTR, "wlan.capability",
+    typ: "@flags",
+    cast: cast::UInt16LE()
+);
+
+def_attr_class!(LISTEN_INTERVAL_ATTR, "wlan.listenInterval", cast: cast::UInt16LE());
+
+def_attr_class!(CURRENT_AP_ATTR, "wlan.currentAp",
+    typ: "@eth:mac",
+    cast: cast::MacAddr()
+);
+
+def_attr_class!(STATUS_ATTR, "wlan.status",
+    typ: "@enum",
+    cast: cast::UInt16LE(),
+    labels: vec![(0, "Successful"), (1, "Unspecified failure")]
+);
+
+def_attr_class!(AID_ATTR, "wlan.aid",
+    cast: cast::UInt16LE().map(|v| v & 0x3fff)
+);
+
+def_attr_class!(AUTH_ALGORITHM_ATTR, "wlan.authAlgorithm",
+    typ: "@enum",
+    cast: cast::UInt16LE(),
+    labels: vec![
+        (0, "Open System"),
+        (1, "Shared Key"),
+        (2, "Fast BSS Transition"),
+        (3, "SAE"),
+    ]
+);
+
+def_attr_class!(AUTH_SEQUENCE_ATTR, "wlan.authSequence", cast: cast::UInt16LE());
+
+def_attr_class!(REASON_ATTR, "wlan.reason", cast: cast::UInt16LE());
+
+def_attr_class!(TAG_ATTR, "wlan.tag",
+    typ: "@nested",
+    repeated: ,
+    value: true
+);
+
+def_attr_class!(TAG_NUMBER_ATTR, "wlan.tag.number",
+    typ: "@enum",
+    cast: cast::UInt8(),
+    labels: vec![
+        (0, "SSID"),
+        (1, "Supported Rates"),
+        (3, "DS Parameter Set"),
+        (5, "TIM"),
+        (7, "Country"),
+        (42, "ERP Information"),
+        (45, "HT Capabilities"),
+        (48, "RSN"),
+        (50, "Extended Supported Rates"),
+        (61, "HT Operation"),
+        (127, "Extended Capabilities"),
+        (191, "VHT Capabilities"),
+        (192, "VHT Operation"),
+        (221, "Vendor Specific"),
+        (255, "Element ID Extension"),
+    ]
+);
+
+def_attr_class!(TAG_LENGTH_ATTR, "wlan.tag.length", cast: cast::UInt8());
+
+def_attr_class!(TAG_DATA_ATTR, "wlan.tag.data", cast: cast::ByteSlice());
+
+def_attr_class!(SSID_ATTR, "wlan.ssid", cast: cast::Utf8());
+
+def_attr_class!(CHANNEL_ATTR, "wlan.channel", cast: cast::UInt8());
+
+def_attr_class!(LLC_ATTR, "wlan.llc", cast: cast::ByteSlice());
+
+def_attr_class!(SNAP_OUI_ATTR, "wlan.snap.oui", cast: cast::ByteSlice());
+
+def_attr_class!(SNAP_TYPE_ATTR, "wlan.snap.type",
+    typ: "@enum",
+    cast: cast::UInt16BE(),
+    labels: vec![
+        (0x0800, "IPv4"),
+        (0x0806, "ARP"),
+        (0x8100, "802.1Q"),
+        (0x86DD, "IPv6"),
+        (0x888E, "EAP"),
+    ]
+);
+
+genet_decoders!(
+    WlanDecoder {},
+    radiotap::RadiotapDecoder {},
+    wpa::WpaDecoder {}
+);
@@ -0,0 +1,201 @@
+use genet_sdk::{cast, decoder::*, prelude::*};
+
+const HEADER_LEN: usize = 8;
+
+const EXT: u32 = 1 << 31;
+
+const TSFT: usize = 0;
+const FLAGS: usize = 1;
+const RATE: usize = 2;
+const CHANNEL: usize = 3;
+const ANTENNA_SIGNAL: usize = 5;
+const ANTENNA_NOISE: usize = 6;
+const ANTENNA: usize = 11;
+const MCS: usize = 19;
+
+const FLAG_FCS: u8 = 0x10;
+
+/// The size and the alignment of the fields in the order of the present bits.
+const FIELDS: [(usize, usize); 23] = [
+    (8, 8),
+    (1, 1),
+    (1, 1),
+    (4, 2),
+    (2, 1),
+    (1, 1),
+    (1, 1),
+    (2, 2),
+    (2, 2),
+    (2, 2),
+    (1, 1),
+    (1, 1),
+    (1, 1),
+    (1, 1),
+    (2, 2),
+    (2, 2),
+    (1, 1),
+    (1, 1),
+    (8, 4),
+    (3, 1),
+    (8, 4),
+    (12, 2),
+    (12, 8),
+];
+
+struct RadiotapWorker {}
+
+impl Worker for RadiotapWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        if parent.id() != token!("[link-127]") {
+            return Ok(Status::Skip);
+        }
+
+        let data = parent.data();
+        let mut layer = Layer::new(&RADIOTAP_CLASS, data);
+        if data.len() < HEADER_LEN {
+            layer.error("truncated header");
+            parent.add_child(layer);
+            return Ok(Status::Done);
+        }
+
+        let len: usize = LENGTH_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        if data.len() < len {
+            layer.error("truncated header");
+            parent.add_child(layer);
+            return Ok(Status::Done);
+        }
+
+        // Skips the extended presence bitmaps; only the fields of the
+        // first bitmap are decoded.
+        let present = read_u32(&data[4..]);
+        let mut pos = HEADER_LEN;
+        let mut word = present;
+        while word & EXT != 0 && pos + 4 <= len {
+            word = read_u32(&data[pos..]);
+            pos += 4;
+        }
+
+        let mut fcs = false;
+        for (bit, &(size, align)) in FIELDS.iter().enumerate() {
+            if present & (1 << bit) == 0 {
+                continue;
+            }
+            pos = (pos + align - 1) / align * align;
+            if pos + size > len {
+                layer.error("malformed field");
+                break;
+            }
+            match bit {
+                TSFT => layer.add_attr(attr!(&TSFT_ATTR, range: pos..pos + size)),
+                FLAGS => {
+                    fcs = data[pos] & FLAG_FCS != 0;
+                    layer.add_attr(attr!(&FLAGS_ATTR, range: pos..pos + size));
+                    layer.add_attr(attr!(&FLAGS_FCS_ATTR, bit_range: pos 3..4));
+                    layer.add_attr(attr!(&FLAGS_BAD_FCS_ATTR, bit_range: pos 1..2));
+                }
+                RATE => layer.add_attr(attr!(&RATE_ATTR, range: pos..pos + size)),
+                CHANNEL => {
+                    layer.add_attr(attr!(&CHANNEL_FREQ_ATTR, range: pos..pos + 2));
+                    layer.add_attr(attr!(&CHANNEL_FLAGS_ATTR, range: pos + 2..pos + 4));
+                }
+                ANTENNA_SIGNAL => layer.add_attr(attr!(&SIGNAL_ATTR, range: pos..pos + size)),
+                ANTENNA_NOISE => layer.add_attr(attr!(&NOISE_ATTR, range: pos..pos + size)),
+                ANTENNA => layer.add_attr(attr!(&ANTENNA_ATTR, range: pos..pos + size)),
+                MCS => layer.add_attr(attr!(&MCS_INDEX_ATTR, range: pos + 2..pos + 3)),
+                _ => {}
+            }
+            pos += size;
+        }
+
+        let end = if fcs && data.len() >= len + 4 {
+            data.len() - 4
+        } else {
+            data.len()
+        };
+        let payload = data.try_get(len..end)?;
+        layer.add_payload(Payload::new(payload, "@data:wlan"));
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+fn read_u32(data: &[u8]) -> u32 {
+    (data[3] as u32) << 24 | (data[2] as u32) << 16 | (data[1] as u32) << 8 | data[0] as u32
+}
+
+#[derive(Clone)]
+pub struct RadiotapDecoder {}
+
+impl Decoder for RadiotapDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(RadiotapWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.decoder.radiotap".into(),
+            name: "Radiotap".into(),
+            description: "Radiotap capture header".into(),
+            exec_type: ExecType::ParallelSync,
+            references: vec!["https://www.radiotap.org/".into()],
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(RADIOTAP_CLASS, "radiotap",
+    header: attr!(&VERSION_ATTR, range: 0..1),
+    header: &LENGTH_ATTR_HEADER,
+    header: attr!(&PRESENT_ATTR, range: 4..8)
+);
+
+def_attr!(LENGTH_ATTR_HEADER, &LENGTH_ATTR, range: 2..4);
+
+def_attr_class!(VERSION_ATTR, "radiotap.version", cast: cast::UInt8());
+
+def_attr_class!(LENGTH_ATTR, "radiotap.length", cast: cast::UInt16LE());
+
+def_attr_class!(PRESENT_ATTR, "radiotap.present",
+    typ: "@flags",
+    cast: cast::UInt32LE()
+);
+
+def_attr_class!(TSFT_ATTR, "radiotap.tsft", cast: cast::UInt64LE());
+
+def_attr_class!(FLAGS_ATTR, "radiotap.flags",
+    typ: "@flags",
+    cast: cast::UInt8()
+);
+
+def_attr_class!(FLAGS_FCS_ATTR, "radiotap.flags.fcs",
+    cast: cast::UInt8().map(|v| v & 0b00010000 != 0)
+);
+
+def_attr_class!(FLAGS_BAD_FCS_ATTR, "radiotap.flags.badFcs",
+    cast: cast::UInt8().map(|v| v & 0b01000000 != 0)
+);
+
+def_attr_class!(RATE_ATTR, "radiotap.rate",
+    cast: cast::UInt8().map(|v| f64::from(v) * 0.5)
+);
+
+def_attr_class!(CHANNEL_FREQ_ATTR, "radiotap.channel.frequency", cast: cast::UInt16LE());
+
+def_attr_class!(CHANNEL_FLAGS_ATTR, "radiotap.channel.flags",
+    typ: "@flags",
+    cast: cast::UInt16LE()
+);
+
+def_attr_class!(SIGNAL_ATTR, "radiotap.signal", cast: cast::Int8());
+
+def_attr_class!(NOISE_ATTR, "radiotap.noise", cast: cast::Int8());
+
+def_attr_class!(ANTENNA_ATTR, "radiotap.antenna", cast: cast::UInt8());
+
+def_attr_class!(MCS_INDEX_ATTR, "radiotap.mcs", cast: cast::UInt8());
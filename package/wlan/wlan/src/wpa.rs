@@ -0,0 +1,422 @@
+//! WPA2-PSK decryption.
+//!
+//! The pairwise transient keys are derived from the EAPOL 4-way handshakes
+//! with the passphrases in the config, and the CCMP-protected unicast data frames
+//! are decrypted. Group-addressed frames are not decrypted.
+
+use aes::{
+    cipher::{generic_array::GenericArray, BlockEncrypt, NewBlockCipher},
+    Aes128,
+};
+use genet_sdk::{cast, decoder::*, prelude::*};
+use ring::{hmac, pbkdf2};
+use std::{collections::HashMap, num::NonZeroU32};
+
+/// The passphrases in the form of `passphrase:ssid` or `passphrase`.
+///
+/// The SSID is taken from the beacons if omitted.
+const PASSWORDS_KEY: &str = "@genet/wlan.wpaPasswords";
+
+const PBKDF2_ITERATIONS: u32 = 4096;
+
+const EAPOL_KEY: u8 = 3;
+const EAPOL_KEY_MIC: usize = 81;
+const EAPOL_KEY_LEN: usize = 99;
+
+const KEY_INFO_VERSION: u16 = 0b111;
+const KEY_INFO_PAIRWISE: u16 = 0x0008;
+const KEY_INFO_INSTALL: u16 = 0x0040;
+const KEY_INFO_ACK: u16 = 0x0080;
+const KEY_INFO_MIC: u16 = 0x0100;
+
+/// HMAC-SHA1 MIC and AES key wrap.
+const KEY_DESCRIPTOR_AES: u16 = 2;
+
+const FLAG_TO_DS: u8 = 0b0000_0001;
+const FLAG_FROM_DS: u8 = 0b0000_0010;
+const FLAG_PROTECTED: u8 = 0b0100_0000;
+const FLAG_ORDER: u8 = 0b1000_0000;
+
+const QOS: u8 = 0b1000;
+
+const CCMP_HEADER_LEN: usize = 8;
+const CCMP_MIC_LEN: usize = 8;
+const EXT_IV: u8 = 0x20;
+
+const LLC_SNAP: [u8; 3] = [0xaa, 0xaa, 0x03];
+const LLC_SNAP_LEN: usize = 8;
+
+type MacAddr = [u8; 6];
+
+/// The state of the handshake between an access point and a station.
+#[derive(Default)]
+struct Handshake {
+    anonce: Option<[u8; 32]>,
+    tk: Option<[u8; 16]>,
+}
+
+struct WpaWorker {
+    passwords: Vec<(String, Option<Vec<u8>>)>,
+    ssids: HashMap<MacAddr, Vec<u8>>,
+    pmks: HashMap<(usize, Vec<u8>), [u8; 32]>,
+    handshakes: HashMap<(MacAddr, MacAddr), Handshake>,
+}
+
+impl WpaWorker {
+    /// Returns the PMKs for the access point.
+    fn pmks(&mut self, ap: &MacAddr) -> Vec<[u8; 32]> {
+        let ssid = self.ssids.get(ap).cloned();
+        let mut pmks = Vec::new();
+        for (index, (passphrase, password_ssid)) in self.passwords.iter().enumerate() {
+            let ssid = match password_ssid.as_ref().or_else(|| ssid.as_ref()) {
+                Some(ssid) => ssid.clone(),
+                None => continue,
+            };
+            let pmk = self.pmks.entry((index, ssid.clone())).or_insert_with(|| {
+                let mut pmk = [0u8; 32];
+                pbkdf2::derive(
+                    pbkdf2::PBKDF2_HMAC_SHA1,
+                    NonZeroU32::new(PBKDF2_ITERATIONS).unwrap(),
+                    &ssid,
+                    passphrase.as_bytes(),
+                    &mut pmk,
+                );
+                pmk
+            });
+            pmks.push(*pmk);
+        }
+        pmks
+    }
+
+    fn decode_eapol(&mut self, parent: &mut Parent, ap: MacAddr, sta: MacAddr, eapol: &[u8]) {
+        if eapol.len() < EAPOL_KEY_LEN || eapol[1] != EAPOL_KEY {
+            return;
+        }
+        let len = 4 + ((eapol[2] as usize) << 8 | eapol[3] as usize);
+        let info = (eapol[5] as u16) << 8 | eapol[6] as u16;
+        if len > eapol.len()
+            || info & KEY_INFO_PAIRWISE == 0
+            || info & KEY_INFO_VERSION != KEY_DESCRIPTOR_AES
+        {
+            return;
+        }
+        let mut nonce = [0u8; 32];
+        nonce.copy_from_slice(&eapol[17..49]);
+
+        if info & KEY_INFO_ACK != 0 {
+            // Message 1 or 3 from the access point.
+            let handshake = self.handshakes.entry((ap, sta)).or_default();
+            if info & KEY_INFO_MIC == 0 || handshake.anonce.is_none() {
+                handshake.anonce = Some(nonce);
+            }
+            return;
+        }
+        if info & (KEY_INFO_MIC | KEY_INFO_INSTALL) != KEY_INFO_MIC || nonce == [0u8; 32] {
+            return;
+        }
+
+        // Message 2 from the station.
+        let anonce = match self.handshakes.get(&(ap, sta)).and_then(|h| h.anonce) {
+            Some(anonce) => anonce,
+            None => return,
+        };
+        let mut frame = eapol[..len].to_vec();
+        for b in &mut frame[EAPOL_KEY_MIC..EAPOL_KEY_MIC + 16] {
+            *b = 0;
+        }
+        let mic = &eapol[EAPOL_KEY_MIC..EAPOL_KEY_MIC + 16];
+        for pmk in self.pmks(&ap) {
+            let ptk = derive_ptk(&pmk, &ap, &sta, &anonce, &nonce);
+            let kck = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, &ptk[0..16]);
+            if &hmac::sign(&kck, &frame).as_ref()[..16] == mic {
+                let mut tk = [0u8; 16];
+                tk.copy_from_slice(&ptk[32..48]);
+                self.handshakes.entry((ap, sta)).or_default().tk = Some(tk);
+                parent.annotate(Annotation::new(
+                    Severity::Note,
+                    "pairwise key derived from the passphrase",
+                ));
+                return;
+            }
+        }
+    }
+
+    fn decode_protected(
+        &mut self,
+        parent: &mut Parent,
+        ap: MacAddr,
+        sta: MacAddr,
+        header: &[u8],
+        body: &[u8],
+    ) -> Result<Status> {
+        let tk = match self.handshakes.get(&(ap, sta)).and_then(|h| h.tk) {
+            Some(tk) => tk,
+            None => return Ok(Status::Skip),
+        };
+        let data = match decrypt(&tk, header, body) {
+            Some(data) => data,
+            None => {
+                parent.annotate(Annotation::new(Severity::Warn, "CCMP decryption failed"));
+                return Ok(Status::Done);
+            }
+        };
+
+        let pn = [body[7], body[6], body[5], body[4], body[1], body[0]]
+            .iter()
+            .fold(0u64, |acc, b| acc << 8 | u64::from(*b));
+        let mut layer = Layer::new(&CCMP_CLASS, ByteSlice::from(data));
+        layer.add_attr(attr!(&PN_ATTR, value: pn));
+        layer.add_attr(attr!(&KEY_ID_ATTR, value: u64::from(body[3] >> 6)));
+        let data = layer.data();
+        if data.len() >= LLC_SNAP_LEN && data[0..3] == LLC_SNAP {
+            layer.add_attr(attr!(&TYPE_ATTR, range: 6..8));
+            let ethertype = (data[6] as u64) << 8 | data[7] as u64;
+            if let Some(typ) = super::get_type(ethertype) {
+                layer.add_payload(Payload::new(data.try_get(LLC_SNAP_LEN..)?, typ));
+            }
+        }
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+impl Worker for WpaWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        if parent.id() != token!("wlan") || self.passwords.is_empty() {
+            return Ok(Status::Skip);
+        }
+        let data = parent.data();
+
+        if let Some(ssid) = parent.attr(token!("wlan.ssid")) {
+            if data.len() >= 22 {
+                let ssid = data.try_get(ssid.range())?.to_vec();
+                self.ssids.insert(mac_addr(&data[16..22]), ssid);
+            }
+            return Ok(Status::Skip);
+        }
+
+        let (ap, sta) = match endpoints(&data) {
+            Some(endpoints) => endpoints,
+            None => return Ok(Status::Skip),
+        };
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:eap"))
+        {
+            let eapol = payload.data();
+            self.decode_eapol(parent, ap, sta, &eapol);
+            return Ok(Status::Skip);
+        }
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@protected:wlan"))
+        {
+            let body = payload.data();
+            let header = data.try_get(..data.len() - body.len())?;
+            return self.decode_protected(parent, ap, sta, &header, &body);
+        }
+        Ok(Status::Skip)
+    }
+}
+
+fn mac_addr(data: &[u8]) -> MacAddr {
+    let mut addr = [0u8; 6];
+    addr.copy_from_slice(&data[..6]);
+    addr
+}
+
+/// Returns the addresses of the access point and the station of a unicast data frame.
+fn endpoints(header: &[u8]) -> Option<(MacAddr, MacAddr)> {
+    if header.len() < 24 {
+        return None;
+    }
+    let (ap, sta) = match header[1] & (FLAG_TO_DS | FLAG_FROM_DS) {
+        FLAG_TO_DS => (&header[4..10], &header[10..16]),
+        FLAG_FROM_DS => (&header[10..16], &header[4..10]),
+        _ => return None,
+    };
+    if sta[0] & 1 != 0 {
+        return None;
+    }
+    Some((mac_addr(ap), mac_addr(sta)))
+}
+
+/// Derives the PTK with PRF-384 defined in IEEE 802.11i.
+fn derive_ptk(
+    pmk: &[u8],
+    ap: &MacAddr,
+    sta: &MacAddr,
+    anonce: &[u8; 32],
+    snonce: &[u8; 32],
+) -> Vec<u8> {
+    let mut data = Vec::with_capacity(76);
+    data.extend_from_slice(ap.min(sta));
+    data.extend_from_slice(ap.max(sta));
+    data.extend_from_slice(anonce.min(snonce));
+    data.extend_from_slice(anonce.max(snonce));
+
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, pmk);
+    let mut ptk = Vec::with_capacity(60);
+    let mut counter = 0u8;
+    while ptk.len() < 48 {
+        let mut ctx = hmac::Context::with_key(&key);
+        ctx.update(b"Pairwise key expansion\0");
+        ctx.update(&data);
+        ctx.update(&[counter]);
+        ptk.extend_from_slice(ctx.sign().as_ref());
+        counter += 1;
+    }
+    ptk.truncate(48);
+    ptk
+}
+
+fn encrypt_block(cipher: &Aes128, block: &[u8; 16]) -> [u8; 16] {
+    let mut data = GenericArray::clone_from_slice(block);
+    cipher.encrypt_block(&mut data);
+    let mut out = [0u8; 16];
+    out.copy_from_slice(&data);
+    out
+}
+
+/// Updates the CBC-MAC with the data padded to the block size.
+fn cbc_mac(cipher: &Aes128, mac: &mut [u8; 16], data: &[u8]) {
+    for chunk in data.chunks(16) {
+        for (m, b) in mac.iter_mut().zip(chunk) {
+            *m ^= b;
+        }
+        *mac = encrypt_block(cipher, mac);
+    }
+}
+
+/// Decrypts the CCMP-protected frame body and verifies the MIC.
+fn decrypt(tk: &[u8; 16], header: &[u8], body: &[u8]) -> Option<Vec<u8>> {
+    if body.len() < CCMP_HEADER_LEN + CCMP_MIC_LEN || body[3] & EXT_IV == 0 {
+        return None;
+    }
+    let a4 = header[1] & (FLAG_TO_DS | FLAG_FROM_DS) == FLAG_TO_DS | FLAG_FROM_DS;
+    let qos = (header[0] >> 4) & QOS != 0;
+    let qos_pos = if a4 { 30 } else { 24 };
+    if header.len() < qos_pos + if qos { 2 } else { 0 } {
+        return None;
+    }
+
+    let mut aad = Vec::with_capacity(32);
+    let mut flags = (header[1] & 0xc7) | FLAG_PROTECTED;
+    if qos {
+        flags &= !FLAG_ORDER;
+    }
+    aad.extend_from_slice(&[header[0] & 0x8f, flags]);
+    aad.extend_from_slice(&header[4..22]);
+    aad.extend_from_slice(&[header[22] & 0x0f, 0]);
+    if a4 {
+        aad.extend_from_slice(&header[24..30]);
+    }
+    let priority = if qos { header[qos_pos] & 0x0f } else { 0 };
+    if qos {
+        aad.extend_from_slice(&[priority, 0]);
+    }
+
+    let mut nonce = [0u8; 13];
+    nonce[0] = priority;
+    nonce[1..7].copy_from_slice(&header[10..16]);
+    nonce[7..13].copy_from_slice(&[body[7], body[6], body[5], body[4], body[1], body[0]]);
+
+    let cipher = Aes128::new(GenericArray::from_slice(tk));
+    let ciphertext = &body[CCMP_HEADER_LEN..body.len() - CCMP_MIC_LEN];
+    let mut ctr = [0u8; 16];
+    ctr[0] = 0x01;
+    ctr[1..14].copy_from_slice(&nonce);
+
+    let mut plaintext = Vec::with_capacity(ciphertext.len());
+    for (i, chunk) in ciphertext.chunks(16).enumerate() {
+        let counter = i + 1;
+        ctr[14] = (counter >> 8) as u8;
+        ctr[15] = counter as u8;
+        let stream = encrypt_block(&cipher, &ctr);
+        plaintext.extend(chunk.iter().zip(stream.iter()).map(|(c, s)| c ^ s));
+    }
+
+    let mut b0 = [0u8; 16];
+    b0[0] = 0x59;
+    b0[1..14].copy_from_slice(&nonce);
+    b0[14] = (plaintext.len() >> 8) as u8;
+    b0[15] = plaintext.len() as u8;
+    let mut mac = encrypt_block(&cipher, &b0);
+    let mut aad_blocks = vec![(aad.len() >> 8) as u8, aad.len() as u8];
+    aad_blocks.extend_from_slice(&aad);
+    cbc_mac(&cipher, &mut mac, &aad_blocks);
+    cbc_mac(&cipher, &mut mac, &plaintext);
+
+    ctr[14] = 0;
+    ctr[15] = 0;
+    let stream = encrypt_block(&cipher, &ctr);
+    let mic = &body[body.len() - CCMP_MIC_LEN..];
+    if mac
+        .iter()
+        .zip(stream.iter())
+        .map(|(m, s)| m ^ s)
+        .eq(mic.iter().cloned())
+    {
+        Some(plaintext)
+    } else {
+        None
+    }
+}
+
+#[derive(Clone)]
+pub struct WpaDecoder {}
+
+impl Decoder for WpaDecoder {
+    fn new_worker(&self, ctx: &Context) -> Box<Worker> {
+        let passwords = ctx
+            .config::<Vec<String>>(PASSWORDS_KEY)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|password| {
+                let mut fields = password.rsplitn(2, ':');
+                match (fields.next(), fields.next()) {
+                    (Some(ssid), Some(passphrase)) => {
+                        (passphrase.to_string(), Some(ssid.as_bytes().to_vec()))
+                    }
+                    _ => (password, None),
+                }
+            })
+            .collect();
+        Box::new(WpaWorker {
+            passwords,
+            ssids: HashMap::new(),
+            pmks: HashMap::new(),
+            handshakes: HashMap::new(),
+        })
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.decoder.wlan-wpa".into(),
+            name: "WPA2".into(),
+            description: "WPA2-PSK decryption of IEEE 802.11 data frames".into(),
+            exec_type: ExecType::SerialSync,
+            references: vec!["https://standards.ieee.org/standard/802_11-2016.html".into()],
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(CCMP_CLASS, "ccmp");
+
+def_attr_class!(PN_ATTR, "ccmp.pn");
+
+def_attr_class!(KEY_ID_ATTR, "ccmp.keyId");
+
+def_attr_class!(TYPE_ATTR, "ccmp.type",
+    typ: "@enum",
+    cast: cast::UInt16BE(),
+    labels: vec![(0x0800, "IPv4"), (0x0806, "ARP"), (0x86DD, "IPv6"), (0x888E, "EAPOL")]
+);
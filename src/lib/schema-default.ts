@@ -35,6 +35,64 @@ export default {
       maximum: 8,
       default: 0,
     },
+    '_.decoder.timeout': {
+      description: 'Maximum time in milliseconds a decoder may spend on a frame (0 for no limit). Native decoders are checked after they return, so this does not stop a hung native decoder.',
+      type: 'integer',
+      minimum: 0,
+      default: 1000,
+    },
+    '_.decoder.wasmInstructions': {
+      description: 'Maximum number of instructions a WebAssembly decoder may execute on a frame (0 for no limit)',
+      type: 'integer',
+      minimum: 0,
+      default: 0,
+    },
+    '_.decoder.memoryLimit': {
+      description: 'Megabytes of frame data kept in memory before spilling to temporary files (0 for no limit)',
+      type: 'integer',
+      minimum: 0,
+      default: 0,
+    },
+    '_.decoder.trace': {
+      description: 'Record the decoders invoked for each frame (slows down decoding)',
+      type: 'boolean',
+      default: false,
+    },
+    '_.decoder.validateChecksums': {
+      description: 'Disable this when checksum offloading makes all checksums appear wrong',
+      type: 'boolean',
+      default: true,
+    },
+    '_.decoder.resolveNames': {
+      description: 'Show host names, MAC vendors and service names',
+      type: 'boolean',
+      default: false,
+    },
+    '_.decoder.resolver.hosts': {
+      description: 'Path to the hosts file',
+      type: 'string',
+      default: '',
+    },
+    '_.decoder.resolver.oui': {
+      description: 'Path to the OUI list',
+      type: 'string',
+      default: '',
+    },
+    '_.decoder.resolver.services': {
+      description: 'Path to the services file',
+      type: 'string',
+      default: '',
+    },
+    '_.decoder.geoip.country': {
+      description: 'Path to the GeoIP2 Country or City database',
+      type: 'string',
+      default: '',
+    },
+    '_.decoder.geoip.asn': {
+      description: 'Path to the GeoIP2 ASN database',
+      type: 'string',
+      default: '',
+    },
     '_.dev.tabReloading': {
       description: 'Touch $HOME/.genet/.reload to reload all tabs',
       type: 'boolean',
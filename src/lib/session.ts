@@ -96,8 +96,23 @@ export default class Session extends EventEmitter {
   async create() {
     const profile = new native.Session.Profile()
     profile.concurrency = genet.config.get('_.decoder.concurrency')
+    profile.decodeTimeout = genet.config.get('_.decoder.timeout')
+    profile.decodeInstructions = genet.config.get('_.decoder.wasmInstructions')
+    profile.memoryLimit = genet.config.get('_.decoder.memoryLimit') * 1024 * 1024
+    profile.trace = genet.config.get('_.decoder.trace')
+    for (const [key, schema] of Object.entries(this._config.schema)) {
+      try {
+        profile.setConfigSchema(key, JSON.stringify(schema))
+      } catch (err) {
+        this.emit('error', err)
+      }
+    }
     for (const [key, value] of Object.entries(this._config.toJSON())) {
-      profile.setConfig(key, JSON.stringify(value))
+      try {
+        profile.setConfig(key, JSON.stringify(value))
+      } catch (err) {
+        this.emit('error', err)
+      }
     }
     for (const file of this._libs) {
       try {
@@ -12,7 +12,11 @@ class AttrRenderer {
 
 export default class Summary {
   view(vnode) {
-    const { frame } = vnode.attrs
+    const { frame, sess } = vnode.attrs
+    const [summary] = sess.summaries(frame.index, frame.index + 1)
+    if (summary) {
+      return m('span', [' ', summary])
+    }
     const src = frame.query(`_.src`)
     const dst = frame.query(`_.dst`)
     if (!src || !dst) {
@@ -18,7 +18,7 @@ class FrameView {
     }
     const columns = vnode.attrs.columns.map((column, index) => {
       const content = [
-        column.func(this.frame)
+        column.func(this.frame, sess)
       ]
       if (index === 0) {
         content.unshift(m('span', [
@@ -234,8 +234,11 @@ export default class FrameListView {
       genet.config.get('_.framelist.columns', [])
     this.columns.push(...columns
       .map((col) => ({
-        func: (frame) => {
-          const result = frame.query(col.value)
+        func: (frame, sess) => {
+          // Package columns are computed and cached by the kernel.
+          const result = col.column
+            ? sess.columnValues(col.column, frame.index, frame.index + 1)[0]
+            : frame.query(col.value)
           let renderer = AttributeValueItem
           if (result !== null &&
             typeof result === 'object' &&
@@ -248,10 +251,10 @@ export default class FrameListView {
       })))
 
     this.columns.push({
-      func: (frame) => {
+      func: (frame, sess) => {
         const { id } = frame.primary
         const renderer = genet.session.layerRenderer(id) || DefaultSummary
-        return m(renderer, { layer: frame.primary, frame })
+        return m(renderer, { layer: frame.primary, frame, sess })
       },
     })
   }